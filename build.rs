@@ -0,0 +1,10 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/market_data.proto");
+        if std::env::var_os("PROTOC").is_none() {
+            std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("vendored protoc"));
+        }
+        tonic_build::compile_protos("proto/market_data.proto").expect("compile market_data.proto");
+    }
+}
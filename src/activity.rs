@@ -0,0 +1,182 @@
+//! Message activity over time: counts and volume per fixed interval, for
+//! every message and per symbol, so intraday patterns show in a capture.
+
+use crate::{DataFormat, Error, Result, StreamingData};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// CSV header of the rows `ActivityRow::format` writes
+pub const ACTIVITY_CSV_HEADER: &str = "bucket,symbol,messages,messages_per_sec,trades,trades_per_minute,trade_volume,volume_per_minute,quotes,bars,bar_volume";
+
+/// What one interval held
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BucketCounts {
+    pub messages: u64,
+    pub trades: u64,
+    pub quotes: u64,
+    pub bars: u64,
+    /// Shares traded, from the size of each trade
+    pub trade_volume: u64,
+    /// Volume of the bars
+    pub bar_volume: u64,
+}
+
+/// One interval of one symbol, or of every message when `symbol` is `None`,
+/// with its rates
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ActivityRow {
+    /// Start of the interval
+    pub bucket: DateTime<Utc>,
+    pub symbol: Option<String>,
+    pub messages: u64,
+    pub messages_per_sec: f64,
+    pub trades: u64,
+    pub trades_per_minute: f64,
+    pub trade_volume: u64,
+    pub volume_per_minute: f64,
+    pub quotes: u64,
+    pub bars: u64,
+    pub bar_volume: u64,
+}
+
+impl ActivityRow {
+    /// The row as a line in `format`, matching `ACTIVITY_CSV_HEADER` for CSV
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let bucket = self
+            .bucket
+            .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+        Ok(match format {
+            DataFormat::Plain => format!(
+                "{} {}: {} messages ({:.2}/s), {} trades ({:.1}/min), volume {} ({:.1}/min), {} quotes, {} bars",
+                bucket,
+                self.symbol.as_deref().unwrap_or("all"),
+                self.messages,
+                self.messages_per_sec,
+                self.trades,
+                self.trades_per_minute,
+                self.trade_volume,
+                self.volume_per_minute,
+                self.quotes,
+                self.bars
+            ),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{:.4},{},{:.4},{},{:.4},{},{},{}",
+                bucket,
+                self.symbol.as_deref().unwrap_or_default(),
+                self.messages,
+                self.messages_per_sec,
+                self.trades,
+                self.trades_per_minute,
+                self.trade_volume,
+                self.volume_per_minute,
+                self.quotes,
+                self.bars,
+                self.bar_volume
+            ),
+        })
+    }
+}
+
+/// Counts of messages per interval of a fixed length, aligned to the Unix
+/// epoch, by event time when the message has one
+#[derive(Debug)]
+pub struct ActivitySeries {
+    bucket: Duration,
+    /// Totals have no symbol, so they sort before the symbols of their bucket
+    buckets: BTreeMap<(DateTime<Utc>, Option<String>), BucketCounts>,
+}
+
+impl ActivitySeries {
+    pub fn new(bucket: Duration) -> Result<Self> {
+        if bucket.as_millis() == 0 {
+            return Err(Error::Invalid(
+                "The bucket must be at least 1ms".to_string(),
+            ));
+        }
+        Ok(Self {
+            bucket,
+            buckets: BTreeMap::new(),
+        })
+    }
+
+    /// Start of the interval that holds `time`
+    pub fn bucket_start(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let length = self.bucket.as_millis() as i64;
+        let start = time.timestamp_millis().div_euclid(length) * length;
+        DateTime::from_timestamp_millis(start).unwrap_or(time)
+    }
+
+    /// Count `data` in its interval
+    pub fn add(&mut self, data: &StreamingData) {
+        let start = self.bucket_start(data.event_time.unwrap_or(data.received_time));
+        let count = |counts: &mut BucketCounts| {
+            counts.messages += 1;
+            match data.message_type.as_str() {
+                "t" => {
+                    counts.trades += 1;
+                    counts.trade_volume += data.data["s"].as_u64().unwrap_or(0);
+                }
+                "q" => counts.quotes += 1,
+                "b" => {
+                    counts.bars += 1;
+                    counts.bar_volume += data.data["v"].as_u64().unwrap_or(0);
+                }
+                _ => {}
+            }
+        };
+        count(self.buckets.entry((start, None)).or_default());
+        if let Some(symbol) = &data.symbol {
+            count(
+                self.buckets
+                    .entry((start, Some(symbol.clone())))
+                    .or_default(),
+            );
+        }
+    }
+
+    /// Every interval with messages, in time order, the total first and then
+    /// each symbol
+    pub fn rows(&self) -> Vec<ActivityRow> {
+        let seconds = self.bucket.as_secs_f64();
+        let minutes = seconds / 60.0;
+        self.buckets
+            .iter()
+            .map(|((bucket, symbol), counts)| ActivityRow {
+                bucket: *bucket,
+                symbol: symbol.clone(),
+                messages: counts.messages,
+                messages_per_sec: counts.messages as f64 / seconds,
+                trades: counts.trades,
+                trades_per_minute: counts.trades as f64 / minutes,
+                trade_volume: counts.trade_volume,
+                volume_per_minute: counts.trade_volume as f64 / minutes,
+                quotes: counts.quotes,
+                bars: counts.bars,
+                bar_volume: counts.bar_volume,
+            })
+            .collect()
+    }
+
+    /// Number of intervals with messages
+    pub fn intervals(&self) -> usize {
+        self.buckets
+            .keys()
+            .filter(|(_, symbol)| symbol.is_none())
+            .count()
+    }
+
+    /// The busiest interval and its messages per second; the earliest of
+    /// equally busy ones
+    pub fn peak(&self) -> Option<(DateTime<Utc>, f64)> {
+        self.buckets
+            .iter()
+            .filter(|((_, symbol), _)| symbol.is_none())
+            .rev()
+            .max_by_key(|(_, counts)| counts.messages)
+            .map(|((bucket, _), counts)| {
+                (*bucket, counts.messages as f64 / self.bucket.as_secs_f64())
+            })
+    }
+}
@@ -0,0 +1,374 @@
+use crate::conflation::parse_duration;
+use crate::{Error, OutputSink, Result, StreamingData, TradingStatus};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// Trade price (or bar close) rises above `threshold`
+    PriceAbove,
+    /// Trade price (or bar close) falls below `threshold`
+    PriceBelow,
+    /// Trade price (or bar close) crosses `threshold` in either direction
+    PriceCross,
+    /// Quote ask minus bid exceeds `threshold`
+    SpreadAbove,
+    /// Bar volume exceeds `threshold` times the average of the previous
+    /// `window` bars
+    VolumeSpike,
+    /// Trading in the symbol is halted or paused; `threshold` is unused
+    Halted,
+}
+
+impl std::fmt::Display for AlertCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AlertCondition::PriceAbove => "price_above",
+            AlertCondition::PriceBelow => "price_below",
+            AlertCondition::PriceCross => "price_cross",
+            AlertCondition::SpreadAbove => "spread_above",
+            AlertCondition::VolumeSpike => "volume_spike",
+            AlertCondition::Halted => "halted",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One `[[rule]]` entry of an alert rules file
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    /// Defaults to `<symbol> <condition>`
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Symbol to watch, or `*` for every symbol
+    pub symbol: String,
+    pub condition: AlertCondition,
+    #[serde(default)]
+    pub threshold: f64,
+    /// Bars averaged by `volume_spike`
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// Minimum time between two alerts of this rule for the same symbol
+    #[serde(default, deserialize_with = "deserialize_cooldown")]
+    pub cooldown: Option<Duration>,
+}
+
+fn default_window() -> usize {
+    20
+}
+
+fn deserialize_cooldown<'de, D>(deserializer: D) -> std::result::Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: String = serde::Deserialize::deserialize(deserializer)?;
+    parse_duration(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+impl AlertRule {
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{} {}", self.symbol, self.condition))
+    }
+
+    fn applies_to(&self, symbol: &str) -> bool {
+        self.symbol == "*" || self.symbol.eq_ignore_ascii_case(symbol)
+    }
+}
+
+/// Contents of an alert rules file:
+///
+/// ```toml
+/// webhooks = ["https://example.com/hooks/alerts"]
+///
+/// [[rule]]
+/// symbol = "AAPL"
+/// condition = "price_cross"
+/// threshold = 200.0
+/// cooldown = "5m"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRules {
+    /// URLs every alert is POSTed to as JSON
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<AlertRule>,
+}
+
+impl std::str::FromStr for AlertRules {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let rules: AlertRules =
+            toml::from_str(source).map_err(|e| Error::Invalid(format!("Invalid alert rules: {}", e)))?;
+        for rule in &rules.rules {
+            if rule.condition == AlertCondition::VolumeSpike && rule.window == 0 {
+                return Err(Error::Invalid(format!(
+                    "Invalid alert rule {}: window must be at least 1",
+                    rule.name()
+                )));
+            }
+        }
+        Ok(rules)
+    }
+}
+
+impl AlertRules {
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        source
+            .parse()
+            .map_err(|e| Error::Invalid(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// A triggered rule; the payload of `alert` messages
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Alert {
+    pub rule: String,
+    pub symbol: String,
+    pub condition: AlertCondition,
+    /// The price, spread or volume that triggered the rule
+    pub value: f64,
+    pub threshold: f64,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+struct RuleState {
+    /// Whether the condition held on the previous message
+    active: bool,
+    last_price: Option<f64>,
+    volumes: VecDeque<f64>,
+    last_fired: Option<DateTime<Utc>>,
+}
+
+type AlertHook = Arc<dyn Fn(&Alert) + Send + Sync>;
+
+/// Evaluates alert rules against the records passing through to the wrapped
+/// sink and writes an `alert` record after each one that triggers a rule.
+/// Clones share the same state.
+///
+/// Level conditions fire when they become true, not on every message while
+/// they stay true; `volume_spike` fires on every spiking bar. A rule's
+/// cooldown applies per symbol.
+#[derive(Clone)]
+pub struct AlertEngine {
+    inner: Arc<dyn OutputSink>,
+    rules: Arc<Vec<AlertRule>>,
+    state: Arc<Mutex<HashMap<(usize, String), RuleState>>>,
+    hooks: Vec<AlertHook>,
+}
+
+impl std::fmt::Debug for AlertEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AlertEngine")
+            .field("inner", &self.inner)
+            .field("rules", &self.rules)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>, inner: Box<dyn OutputSink>) -> Self {
+        Self {
+            inner: Arc::from(inner),
+            rules: Arc::new(rules),
+            state: Arc::new(Mutex::new(HashMap::new())),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Call `hook` for every alert, e.g. to forward it to a webhook
+    pub fn on_alert<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Alert) + Send + Sync + 'static,
+    {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Alerts triggered by `data`, updating each rule's state
+    pub fn evaluate(&self, data: &StreamingData) -> Vec<Alert> {
+        let Some(symbol) = &data.symbol else {
+            return Vec::new();
+        };
+        let price = match data.message_type.as_str() {
+            "t" => data.data["p"].as_f64(),
+            "b" => data.data["c"].as_f64(),
+            _ => None,
+        };
+        let spread = match (
+            data.message_type.as_str(),
+            data.data["ap"].as_f64(),
+            data.data["bp"].as_f64(),
+        ) {
+            ("q", Some(ask), Some(bid)) => Some(ask - bid),
+            _ => None,
+        };
+        let volume = match data.message_type.as_str() {
+            "b" => data.data["v"].as_f64(),
+            _ => None,
+        };
+        let halt = match data.message_type.as_str() {
+            "s" => serde_json::from_value::<TradingStatus>(data.data.clone())
+                .ok()
+                .filter(TradingStatus::is_halt),
+            _ => None,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let mut alerts = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !rule.applies_to(symbol) {
+                continue;
+            }
+            let value = match rule.condition {
+                AlertCondition::PriceAbove
+                | AlertCondition::PriceBelow
+                | AlertCondition::PriceCross => price,
+                AlertCondition::SpreadAbove => spread,
+                AlertCondition::VolumeSpike => volume,
+                AlertCondition::Halted => halt.as_ref().map(|_| 0.0),
+            };
+            let Some(value) = value else {
+                continue;
+            };
+            let rule_state = state.entry((index, symbol.clone())).or_default();
+            let message = match (&halt, rule.condition) {
+                (Some(status), AlertCondition::Halted) => {
+                    Some(format!("{} halted: {}", symbol, status.description()))
+                }
+                _ => check(rule, rule_state, symbol, value),
+            };
+            let Some(message) = message else {
+                continue;
+            };
+            let cooling_down = match (rule.cooldown, rule_state.last_fired) {
+                (Some(cooldown), Some(last_fired)) => {
+                    (data.received_time - last_fired)
+                        .to_std()
+                        .unwrap_or_default()
+                        < cooldown
+                }
+                _ => false,
+            };
+            if cooling_down {
+                continue;
+            }
+            rule_state.last_fired = Some(data.received_time);
+            alerts.push(Alert {
+                rule: rule.name(),
+                symbol: symbol.clone(),
+                condition: rule.condition,
+                value,
+                threshold: rule.threshold,
+                message,
+            });
+        }
+        alerts
+    }
+}
+
+/// Update `state` with `value` and describe the alert if the rule fires
+fn check(rule: &AlertRule, state: &mut RuleState, symbol: &str, value: f64) -> Option<String> {
+    let threshold = rule.threshold;
+    match rule.condition {
+        AlertCondition::PriceAbove | AlertCondition::PriceBelow | AlertCondition::SpreadAbove => {
+            let (holds, what, side) = match rule.condition {
+                AlertCondition::PriceAbove => (value > threshold, "price", "above"),
+                AlertCondition::PriceBelow => (value < threshold, "price", "below"),
+                _ => (value > threshold, "spread", "above"),
+            };
+            let fires = holds && !state.active;
+            state.active = holds;
+            fires.then(|| format!("{} {} {:.2} {} {:.2}", symbol, what, value, side, threshold))
+        }
+        AlertCondition::PriceCross => {
+            let previous = state.last_price.replace(value)?;
+            if previous < threshold && value >= threshold {
+                Some(format!(
+                    "{} price crossed above {:.2} ({:.2})",
+                    symbol, threshold, value
+                ))
+            } else if previous > threshold && value <= threshold {
+                Some(format!(
+                    "{} price crossed below {:.2} ({:.2})",
+                    symbol, threshold, value
+                ))
+            } else {
+                None
+            }
+        }
+        AlertCondition::VolumeSpike => {
+            // Wait for a full window so the first bars of a session don't fire
+            let average = (state.volumes.len() == rule.window)
+                .then(|| state.volumes.iter().sum::<f64>() / rule.window as f64);
+            if state.volumes.len() == rule.window {
+                state.volumes.pop_front();
+            }
+            state.volumes.push_back(value);
+            let average = average.filter(|average| *average > 0.0)?;
+            (value > threshold * average).then(|| {
+                format!(
+                    "{} volume {} is {:.1}x the {}-bar average",
+                    symbol,
+                    value,
+                    value / average,
+                    rule.window
+                )
+            })
+        }
+        // Described in `evaluate`, which has the status message
+        AlertCondition::Halted => None,
+    }
+}
+
+impl OutputSink for AlertEngine {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        let alerts = self.evaluate(data);
+        self.inner.write_streaming_data(data)?;
+        for alert in alerts {
+            tracing::info!(rule = %alert.rule, symbol = %alert.symbol, "{}", alert.message);
+            for hook in &self.hooks {
+                hook(&alert);
+            }
+            let mut payload = serde_json::to_value(&alert)?;
+            payload["source"] = serde_json::json!("rule");
+            self.inner.write_streaming_data(&StreamingData {
+                received_time: Utc::now(),
+                event_time: data.event_time,
+                message_type: "alert".to_string(),
+                symbol: Some(alert.symbol),
+                data: payload,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> crate::Result<()> {
+        self.inner.close()
+    }
+
+    fn rotate(&self) -> crate::Result<()> {
+        self.inner.rotate()
+    }
+
+    fn writeln(&self, message: &str) -> crate::Result<()> {
+        self.inner.writeln(message)
+    }
+}
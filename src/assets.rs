@@ -0,0 +1,291 @@
+use crate::trade_updates::DEFAULT_TRADING_BASE_URL;
+use crate::{DataFormat, Error, Result, StreamingConfig};
+use clap::ValueEnum;
+use futures_util::{StreamExt, TryStreamExt};
+use std::time::Duration;
+
+/// Asset lookups run at most this many at a time
+const CONCURRENT_LOOKUPS: usize = 8;
+
+/// Header of `assets` CSV output
+pub const ASSETS_CSV_HEADER: &str =
+    "symbol,name,exchange,class,status,tradable,shortable,easy_to_borrow,fractionable,marginable";
+
+/// Asset classes of `GET /v2/assets`
+pub const ASSET_CLASSES: [&str; 3] = ["us_equity", "us_option", "crypto"];
+
+/// An Alpaca asset: whether it can stream, and what it can be traded as
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Asset {
+    pub symbol: String,
+    /// `active` or `inactive`
+    pub status: String,
+    pub tradable: bool,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub exchange: String,
+    /// `us_equity`, `us_option` or `crypto`
+    #[serde(default)]
+    pub class: String,
+    #[serde(default)]
+    pub shortable: bool,
+    #[serde(default)]
+    pub easy_to_borrow: bool,
+    #[serde(default)]
+    pub fractionable: bool,
+    #[serde(default)]
+    pub marginable: bool,
+}
+
+impl Asset {
+    /// The line written for this asset; CSV lines match `ASSETS_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        Ok(match format {
+            DataFormat::Plain => {
+                let flags: Vec<&str> = [
+                    (self.tradable, "tradable"),
+                    (self.shortable, "shortable"),
+                    (self.easy_to_borrow, "easy to borrow"),
+                    (self.fractionable, "fractionable"),
+                    (self.marginable, "marginable"),
+                ]
+                .iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, name)| *name)
+                .collect();
+                format!(
+                    "🏷️ {}: {} | {} {} | {}{}",
+                    self.symbol,
+                    self.name,
+                    self.exchange,
+                    self.class,
+                    self.status,
+                    if flags.is_empty() {
+                        String::new()
+                    } else {
+                        format!(", {}", flags.join(", "))
+                    }
+                )
+            }
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{},{},{}",
+                self.symbol,
+                csv_text(&self.name),
+                self.exchange,
+                self.class,
+                self.status,
+                self.tradable,
+                self.shortable,
+                self.easy_to_borrow,
+                self.fractionable,
+                self.marginable
+            ),
+        })
+    }
+}
+
+/// `text` as a CSV field, quoted if it holds a comma or a quote
+fn csv_text(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Filters of an asset listing; `None` and `false` leave a filter off
+#[derive(Debug, Clone, Default)]
+pub struct AssetQuery {
+    /// `active` or `inactive`; `None` lists both
+    pub status: Option<String>,
+    /// One of `ASSET_CLASSES`
+    pub class: Option<String>,
+    /// Exchanges, e.g. `NASDAQ` or `NYSE`; empty lists every exchange
+    pub exchanges: Vec<String>,
+    pub tradable: bool,
+    pub shortable: bool,
+    pub fractionable: bool,
+}
+
+impl AssetQuery {
+    /// Whether `asset` passes every filter
+    pub fn matches(&self, asset: &Asset) -> bool {
+        self.status
+            .as_ref()
+            .is_none_or(|status| &asset.status == status)
+            && self
+                .class
+                .as_ref()
+                .is_none_or(|class| &asset.class == class)
+            && (self.exchanges.is_empty()
+                || self
+                    .exchanges
+                    .iter()
+                    .any(|exchange| exchange.eq_ignore_ascii_case(&asset.exchange)))
+            && (!self.tradable || asset.tradable)
+            && (!self.shortable || asset.shortable)
+            && (!self.fractionable || asset.fractionable)
+    }
+}
+
+/// Why a configured symbol will not stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolProblem {
+    Unknown,
+    Inactive,
+    Untradable,
+}
+
+impl std::fmt::Display for SymbolProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SymbolProblem::Unknown => "unknown",
+            SymbolProblem::Inactive => "inactive",
+            SymbolProblem::Untradable => "not tradable",
+        })
+    }
+}
+
+/// The problem with a symbol given its asset, or `None` if it is fine
+pub fn check_asset(asset: Option<&Asset>) -> Option<SymbolProblem> {
+    match asset {
+        None => Some(SymbolProblem::Unknown),
+        Some(asset) if asset.status != "active" => Some(SymbolProblem::Inactive),
+        Some(asset) if !asset.tradable => Some(SymbolProblem::Untradable),
+        Some(_) => None,
+    }
+}
+
+/// What to do with symbols that fail validation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SymbolValidation {
+    /// Log a warning and subscribe anyway (default)
+    #[default]
+    Warn,
+    /// Log a warning and leave the symbol out of every subscription
+    Drop,
+    /// Exit before connecting
+    Fail,
+}
+
+/// Looks symbols up on the Alpaca assets endpoint (`GET /v2/assets/{symbol}`)
+#[derive(Debug, Clone)]
+pub struct AssetsClient {
+    client: reqwest::Client,
+    base_url: String,
+    key: String,
+    secret: String,
+}
+
+impl AssetsClient {
+    /// Credentials from `APCA_API_KEY_ID` and `APCA_API_SECRET_KEY`, endpoint
+    /// from `APCA_API_BASE_URL` (paper trading by default)
+    pub fn from_env() -> Result<Self> {
+        let base_url = std::env::var("APCA_API_BASE_URL")
+            .unwrap_or_else(|_| DEFAULT_TRADING_BASE_URL.to_string());
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v2").unwrap_or(base_url).to_string();
+        let var =
+            |name: &str| std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)));
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            base_url,
+            key: var("APCA_API_KEY_ID")?,
+            secret: var("APCA_API_SECRET_KEY")?,
+        })
+    }
+
+    /// The asset for `symbol`, or `None` if Alpaca does not know it
+    pub async fn asset(&self, symbol: &str) -> Result<Option<Asset>> {
+        let response = self
+            .client
+            .get(format!("{}/v2/assets/{}", self.base_url, symbol))
+            .header("APCA-API-KEY-ID", &self.key)
+            .header("APCA-API-SECRET-KEY", &self.secret)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+
+    /// Every asset matching `query`, sorted by symbol. The API filters by
+    /// status, class and a single exchange; the rest is filtered here.
+    #[tracing::instrument(name = "list_assets", skip(self))]
+    pub async fn list(&self, query: &AssetQuery) -> Result<Vec<Asset>> {
+        let mut params = Vec::new();
+        if let Some(status) = &query.status {
+            params.push(("status", status.clone()));
+        }
+        if let Some(class) = &query.class {
+            params.push(("asset_class", class.clone()));
+        }
+        if let [exchange] = query.exchanges.as_slice() {
+            params.push(("exchange", exchange.to_uppercase()));
+        }
+        let mut assets: Vec<Asset> = self
+            .client
+            .get(format!("{}/v2/assets", self.base_url))
+            .query(&params)
+            // Every US equity is tens of megabytes
+            .timeout(Duration::from_secs(120))
+            .header("APCA-API-KEY-ID", &self.key)
+            .header("APCA-API-SECRET-KEY", &self.secret)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        assets.retain(|asset| query.matches(asset));
+        assets.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        Ok(assets)
+    }
+
+    /// Every symbol with a problem, in the order given
+    pub async fn check_symbols(&self, symbols: &[String]) -> Result<Vec<(String, SymbolProblem)>> {
+        let checked: Vec<(String, Option<SymbolProblem>)> = futures_util::stream::iter(symbols)
+            .map(|symbol| async move {
+                let asset = self.asset(symbol).await?;
+                Ok::<_, Error>((symbol.clone(), check_asset(asset.as_ref())))
+            })
+            .buffered(CONCURRENT_LOOKUPS)
+            .try_collect()
+            .await?;
+        Ok(checked
+            .into_iter()
+            .filter_map(|(symbol, problem)| Some((symbol, problem?)))
+            .collect())
+    }
+}
+
+/// Warn about each problem symbol, then drop them from `config` or fail as
+/// `policy` says
+pub fn apply_symbol_validation(
+    config: &mut StreamingConfig,
+    problems: &[(String, SymbolProblem)],
+    policy: SymbolValidation,
+) -> Result<()> {
+    for (symbol, problem) in problems {
+        tracing::warn!(%symbol, %problem, "Symbol will not stream");
+    }
+    match policy {
+        SymbolValidation::Warn => Ok(()),
+        SymbolValidation::Drop => {
+            config.retain_symbols(|symbol| problems.iter().all(|(bad, _)| bad != symbol));
+            Ok(())
+        }
+        SymbolValidation::Fail if problems.is_empty() => Ok(()),
+        SymbolValidation::Fail => {
+            let list: Vec<String> = problems
+                .iter()
+                .map(|(symbol, problem)| format!("{} ({})", symbol, problem))
+                .collect();
+            Err(Error::Invalid(format!("Invalid symbols: {}", list.join(", "))))
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Publishing of finished exports. A file is written as `<path>.partial` and
+//! only renamed to `path` once it is complete, with a `<path>.sha256`
+//! checksum beside it, so a file under its final name is never truncated.
+
+use crate::Result;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// `bars.csv` is written as `bars.csv.partial`
+pub fn partial_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".partial")
+}
+
+/// `bars.csv` is checksummed in `bars.csv.sha256`
+pub fn checksum_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".sha256")
+}
+
+/// The file to write `path` to until it is published. A resumed run carries
+/// on with the partial file an interrupted one left; appending otherwise
+/// starts from a copy of `path`.
+pub fn begin(path: &Path, append: bool, resume: bool) -> Result<PathBuf> {
+    let partial = partial_path(path);
+    if append && !(resume && partial.exists()) && path.exists() {
+        std::fs::copy(path, &partial)?;
+    }
+    Ok(partial)
+}
+
+/// Move the finished partial file of `path` into place and write its
+/// checksum in the `sha256sum` format; returns the checksum. The checksum of
+/// an earlier version is removed first and the new one written last, so a
+/// checksum always matches the file beside it.
+pub fn publish(path: &Path) -> Result<String> {
+    let partial = partial_path(path);
+    File::open(&partial)?.sync_all()?;
+    let checksum = sha256_file(&partial)?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let checksum_file = checksum_path(path);
+    let checksum_partial = partial_path(&checksum_file);
+    let mut sidecar = File::create(&checksum_partial)?;
+    writeln!(sidecar, "{}  {}", checksum, name)?;
+    sidecar.sync_all()?;
+
+    match std::fs::remove_file(&checksum_file) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    std::fs::rename(&partial, path)?;
+    std::fs::rename(&checksum_partial, &checksum_file)?;
+    Ok(checksum)
+}
+
+/// Write `contents` to `path` through a partial file and publish it
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<String> {
+    std::fs::write(partial_path(path), contents)?;
+    publish(path)
+}
+
+/// SHA-256 of the file at `path`, as lowercase hex
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
@@ -0,0 +1,23 @@
+use algorithms_trading::cli::auth_check::{self, AuthCheckArgs};
+use algorithms_trading::cli::GlobalArgs;
+use anyhow::Result;
+use clap::Parser;
+
+/// Same as `trade auth-check`
+#[derive(Parser, Debug)]
+#[command(name = "auth-check")]
+#[command(about = "Verify Alpaca credentials, account status and market data entitlements")]
+#[command(version)]
+struct Args {
+    #[command(flatten)]
+    auth_check: AuthCheckArgs,
+    
+    #[command(flatten)]
+    global: GlobalArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    auth_check::run(args.auth_check, &args.global).await
+}
@@ -0,0 +1,373 @@
+//! Backtests: a strategy runs over historical bars, trades or quotes in time
+//! order while a simulated broker fills its orders, with latency, slippage
+//! and commissions, and keeps its cash, positions and equity curve.
+//!
+//! Orders fill at the first event of their symbol at least the latency after
+//! they were placed, and never on the event they were placed on: market
+//! orders at the open of a bar, the price of a trade or the far side of a
+//! quote, less slippage; limit orders when that price, or the bar's range,
+//! reaches the limit. Orders a strategy places when it stops fill at the
+//! last price of their symbol. Slippage on market orders and commissions
+//! come from the models in `costs`.
+
+use crate::costs::{CommissionModel, Execution, NoCommission, NoSlippage, SlippageModel};
+use crate::strategy::{
+    Engine, EngineStats, Fill, MarketEvent, OrderRequest, OrderType, Side, Strategy,
+};
+use crate::{DataFormat, Error, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Header of equity curves written as CSV
+pub const EQUITY_CSV_HEADER: &str = "timestamp,cash,equity";
+
+/// How a backtest's broker fills orders
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    /// Cash at the start
+    pub initial_cash: f64,
+    /// Time between placing an order and its earliest fill
+    pub latency: Duration,
+    /// Price market orders fill at
+    pub slippage: Arc<dyn SlippageModel>,
+    /// Charged on each fill
+    pub commission: Arc<dyn CommissionModel>,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            initial_cash: 100_000.0,
+            latency: Duration::ZERO,
+            slippage: Arc::new(NoSlippage),
+            commission: Arc::new(NoCommission),
+        }
+    }
+}
+
+impl BacktestConfig {
+    fn validate(&self) -> Result<()> {
+        if !(self.initial_cash >= 0.0 && self.initial_cash.is_finite()) {
+            return Err(Error::Invalid(format!(
+                "Invalid initial cash: {}. Use 0 or more",
+                self.initial_cash
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Cash and equity after the events at one time
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EquityPoint {
+    pub timestamp: DateTime<Utc>,
+    pub cash: f64,
+    /// Cash plus the positions at their last prices
+    pub equity: f64,
+}
+
+impl EquityPoint {
+    /// The point as a line in `format`, matching `EQUITY_CSV_HEADER` for CSV
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let timestamp = self
+            .timestamp
+            .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+        Ok(match format {
+            DataFormat::Plain => format!(
+                "{} | Cash: ${:.2} | Equity: ${:.2}",
+                timestamp, self.cash, self.equity
+            ),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!("{},{:.2},{:.2}", timestamp, self.cash, self.equity),
+        })
+    }
+}
+
+/// What a backtest did
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestResult {
+    pub strategy: String,
+    pub initial_cash: f64,
+    pub final_cash: f64,
+    pub final_equity: f64,
+    /// Shares held of each symbol at the end
+    pub positions: HashMap<String, f64>,
+    /// Last price of each symbol, which positions are valued at
+    pub prices: HashMap<String, f64>,
+    pub fills: Vec<Fill>,
+    pub equity_curve: Vec<EquityPoint>,
+    /// Orders still waiting for a price at the end
+    pub unfilled: usize,
+    pub stats: EngineStats,
+}
+
+impl BacktestResult {
+    /// Change of equity over the backtest, as a fraction of the initial cash
+    pub fn total_return(&self) -> f64 {
+        if self.initial_cash > 0.0 {
+            self.final_equity / self.initial_cash - 1.0
+        } else {
+            0.0
+        }
+    }
+
+    pub fn commission(&self) -> f64 {
+        self.fills.iter().map(|fill| fill.commission).sum()
+    }
+}
+
+impl std::fmt::Display for BacktestResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: equity ${:.2} from ${:.2} ({:+.2}%), {} fills, ${:.2} commission, {} unfilled orders, over {} bars, {} trades and {} quotes",
+            self.strategy,
+            self.final_equity,
+            self.initial_cash,
+            self.total_return() * 100.0,
+            self.fills.len(),
+            self.commission(),
+            self.unfilled,
+            self.stats.bars,
+            self.stats.trades,
+            self.stats.quotes
+        )
+    }
+}
+
+/// The simulated broker of a backtest
+struct Broker<'a> {
+    config: &'a BacktestConfig,
+    cash: f64,
+    positions: HashMap<String, f64>,
+    prices: HashMap<String, f64>,
+    /// Ask less bid of each symbol's last quote
+    spreads: HashMap<String, f64>,
+    /// Shares filled so far
+    traded: f64,
+    pending: Vec<OrderRequest>,
+}
+
+impl<'a> Broker<'a> {
+    fn new(config: &'a BacktestConfig) -> Self {
+        Self {
+            config,
+            cash: config.initial_cash,
+            positions: HashMap::new(),
+            prices: HashMap::new(),
+            spreads: HashMap::new(),
+            traded: 0.0,
+            pending: Vec::new(),
+        }
+    }
+
+    fn equity(&self) -> f64 {
+        let holdings: f64 = self
+            .positions
+            .iter()
+            .map(|(symbol, quantity)| quantity * self.prices.get(symbol).copied().unwrap_or(0.0))
+            .sum();
+        self.cash + holdings
+    }
+
+    /// Remember the price positions in the event's symbol are worth
+    fn mark(&mut self, event: &MarketEvent) {
+        let price = match event {
+            MarketEvent::Bar(bar) => bar.close,
+            MarketEvent::Trade(trade) => trade.price,
+            MarketEvent::Quote(quote) => {
+                self.spreads
+                    .insert(quote.symbol.clone(), quote.ask_price - quote.bid_price);
+                (quote.bid_price + quote.ask_price) / 2.0
+            }
+            MarketEvent::Fill(_) => return,
+        };
+        self.prices.insert(event.symbol().to_string(), price);
+    }
+
+    /// Fill the pending orders of the event's symbol that are due and whose
+    /// price it reaches
+    fn match_orders(&mut self, event: &MarketEvent) -> Vec<Fill> {
+        // What a market buy and sell would pay, the lowest and highest prices
+        // a limit order could have met, and the shares a buy and a sell met
+        let (buy, sell, low, high, buy_volume, sell_volume) = match event {
+            MarketEvent::Bar(bar) => {
+                let volume = bar.volume as f64;
+                (bar.open, bar.open, bar.low, bar.high, volume, volume)
+            }
+            MarketEvent::Trade(trade) => {
+                let size = trade.size as f64;
+                (
+                    trade.price,
+                    trade.price,
+                    trade.price,
+                    trade.price,
+                    size,
+                    size,
+                )
+            }
+            MarketEvent::Quote(quote) => (
+                quote.ask_price,
+                quote.bid_price,
+                quote.ask_price,
+                quote.bid_price,
+                quote.ask_size as f64,
+                quote.bid_size as f64,
+            ),
+            MarketEvent::Fill(_) => return Vec::new(),
+        };
+        let now = event.timestamp();
+        let latency = chrono::Duration::from_std(self.config.latency).unwrap_or_default();
+        let mut fills = Vec::new();
+        let mut index = 0;
+        while index < self.pending.len() {
+            let order = &self.pending[index];
+            if order.symbol != event.symbol() || order.created_at + latency > now {
+                index += 1;
+                continue;
+            }
+            let price = match (order.order_type, order.side) {
+                (OrderType::Market, Side::Buy) => Some(self.slipped(order, buy, Some(buy_volume))),
+                (OrderType::Market, Side::Sell) => {
+                    Some(self.slipped(order, sell, Some(sell_volume)))
+                }
+                (OrderType::Limit { limit_price }, Side::Buy) => {
+                    (low <= limit_price).then_some(buy.min(limit_price))
+                }
+                (OrderType::Limit { limit_price }, Side::Sell) => {
+                    (high >= limit_price).then_some(sell.max(limit_price))
+                }
+            };
+            match price {
+                Some(price) => {
+                    let order = self.pending.remove(index);
+                    fills.push(self.execute(&order, price, now));
+                }
+                None => index += 1,
+            }
+        }
+        fills
+    }
+
+    fn slipped(&self, order: &OrderRequest, price: f64, volume: Option<f64>) -> f64 {
+        self.config.slippage.price(&Execution {
+            side: order.side,
+            quantity: order.quantity,
+            price,
+            spread: self.spreads.get(&order.symbol).copied(),
+            volume,
+        })
+    }
+
+    fn execute(&mut self, order: &OrderRequest, price: f64, timestamp: DateTime<Utc>) -> Fill {
+        let commission = self
+            .config
+            .commission
+            .commission(order.quantity, price, self.traded);
+        self.traded += order.quantity;
+        self.cash -= order.side.sign() * order.quantity * price + commission;
+        *self.positions.entry(order.symbol.clone()).or_default() +=
+            order.side.sign() * order.quantity;
+        Fill {
+            symbol: order.symbol.clone(),
+            side: order.side,
+            quantity: order.quantity,
+            price,
+            timestamp,
+            commission,
+            order_id: None,
+        }
+    }
+}
+
+/// Add the point at `timestamp`, replacing one already there
+fn record(curve: &mut Vec<EquityPoint>, point: EquityPoint) {
+    match curve.last_mut() {
+        Some(last) if last.timestamp == point.timestamp => *last = point,
+        _ => curve.push(point),
+    }
+}
+
+/// Run `strategy` over `events`, which must be in time order, and fill its
+/// orders as `config` says. Fill events in the input are ignored; the
+/// strategy gets the broker's fills instead.
+pub fn run_backtest<S: Strategy>(
+    strategy: S,
+    events: impl IntoIterator<Item = MarketEvent>,
+    config: &BacktestConfig,
+) -> Result<BacktestResult> {
+    config.validate()?;
+    let mut engine = Engine::new(strategy);
+    let mut broker = Broker::new(config);
+    let mut fills = Vec::new();
+    let mut curve = Vec::new();
+    for event in events {
+        if matches!(event, MarketEvent::Fill(_)) {
+            continue;
+        }
+        for fill in broker.match_orders(&event) {
+            let orders = engine.handle(&MarketEvent::Fill(fill.clone()));
+            broker.pending.extend(orders);
+            fills.push(fill);
+        }
+        broker.mark(&event);
+        let orders = engine.handle(&event);
+        broker.pending.extend(orders);
+        record(
+            &mut curve,
+            EquityPoint {
+                timestamp: event.timestamp(),
+                cash: broker.cash,
+                equity: broker.equity(),
+            },
+        );
+    }
+
+    let orders = engine.stop();
+    if let Some(last) = curve.last().map(|point| point.timestamp) {
+        for order in orders {
+            let Some(&price) = broker.prices.get(&order.symbol) else {
+                broker.pending.push(order);
+                continue;
+            };
+            let price = match order.order_type {
+                OrderType::Market => broker.slipped(&order, price, None),
+                OrderType::Limit { limit_price } => {
+                    let reached = match order.side {
+                        Side::Buy => price <= limit_price,
+                        Side::Sell => price >= limit_price,
+                    };
+                    if !reached {
+                        broker.pending.push(order);
+                        continue;
+                    }
+                    price
+                }
+            };
+            fills.push(broker.execute(&order, price, last));
+        }
+        record(
+            &mut curve,
+            EquityPoint {
+                timestamp: last,
+                cash: broker.cash,
+                equity: broker.equity(),
+            },
+        );
+    }
+
+    Ok(BacktestResult {
+        strategy: engine.strategy().name().to_string(),
+        initial_cash: config.initial_cash,
+        final_cash: broker.cash,
+        final_equity: broker.equity(),
+        positions: broker.positions,
+        prices: broker.prices,
+        fills,
+        equity_curve: curve,
+        unfilled: broker.pending.len(),
+        stats: engine.stats(),
+    })
+}
@@ -0,0 +1,926 @@
+//! Performance report of a backtest: returns, risk-adjusted ratios,
+//! drawdowns, trade statistics and the list of round trips, written as JSON,
+//! CSV or a self-contained HTML page.
+//!
+//! Sharpe and Sortino ratios are computed from daily returns, the equity at
+//! the last event of each New York trading day against the day before, and
+//! annualized over 252 trading days with a risk-free rate of 0. Measured
+//! against a benchmark's daily bars, the report also has alpha, beta,
+//! correlation and the drawdown of the equity relative to the benchmark, over
+//! the days both have. A Monte Carlo simulation of the round trips can be
+//! added too, see `monte_carlo`.
+
+use crate::backtest::{BacktestResult, EquityPoint};
+use crate::market_time::new_york_date;
+use crate::monte_carlo::{simulate, Distribution, MonteCarloConfig, MonteCarloResult, Resampling};
+use crate::resample::OhlcvBar;
+use crate::strategy::Fill;
+use crate::{Error, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Header of round trips written as CSV
+pub const ROUND_TRIPS_CSV_HEADER: &str =
+    "symbol,direction,entry_time,exit_time,quantity,entry_price,exit_price,pnl,return,commission,open";
+
+/// Trading days in a year, to annualize daily figures
+const TRADING_DAYS: f64 = 252.0;
+
+/// Quantities closer to 0 than this are flat, against float rounding
+const EPSILON: f64 = 1e-9;
+
+/// Whether a round trip bought first or sold first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Long,
+    Short,
+}
+
+impl Direction {
+    fn sign(&self) -> f64 {
+        match self {
+            Direction::Long => 1.0,
+            Direction::Short => -1.0,
+        }
+    }
+}
+
+/// A position in one symbol from when it was opened until it was flat again
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RoundTrip {
+    pub symbol: String,
+    pub direction: Direction,
+    pub entry_time: DateTime<Utc>,
+    pub exit_time: DateTime<Utc>,
+    /// Largest number of shares held
+    pub quantity: f64,
+    /// Average price of the shares bought (long) or sold (short) to open
+    pub entry_price: f64,
+    /// Average price of the shares that closed it
+    pub exit_price: f64,
+    /// Profit after commission
+    pub pnl: f64,
+    /// `pnl` as a fraction of the cost of the entries
+    #[serde(rename = "return")]
+    pub return_pct: f64,
+    pub commission: f64,
+    /// Still held at the end, and valued at the last price
+    pub open: bool,
+}
+
+impl RoundTrip {
+    /// The round trip as a CSV line matching `ROUND_TRIPS_CSV_HEADER`
+    pub fn to_csv(&self) -> String {
+        let time = |time: DateTime<Utc>| time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+        format!(
+            "{},{},{},{},{},{:.4},{:.4},{:.2},{:.6},{:.2},{}",
+            self.symbol,
+            match self.direction {
+                Direction::Long => "long",
+                Direction::Short => "short",
+            },
+            time(self.entry_time),
+            time(self.exit_time),
+            self.quantity,
+            self.entry_price,
+            self.exit_price,
+            self.pnl,
+            self.return_pct,
+            self.commission,
+            self.open
+        )
+    }
+}
+
+/// A round trip being built from fills
+struct OpenTrip {
+    direction: Direction,
+    entry_time: DateTime<Utc>,
+    position: f64,
+    quantity: f64,
+    entry_shares: f64,
+    entry_value: f64,
+    exit_shares: f64,
+    exit_value: f64,
+    commission: f64,
+}
+
+impl OpenTrip {
+    fn new(direction: Direction, time: DateTime<Utc>) -> Self {
+        Self {
+            direction,
+            entry_time: time,
+            position: 0.0,
+            quantity: 0.0,
+            entry_shares: 0.0,
+            entry_value: 0.0,
+            exit_shares: 0.0,
+            exit_value: 0.0,
+            commission: 0.0,
+        }
+    }
+
+    fn add(&mut self, shares: f64, price: f64, commission: f64) {
+        self.position += shares;
+        self.quantity = self.quantity.max(self.position);
+        self.entry_shares += shares;
+        self.entry_value += shares * price;
+        self.commission += commission;
+    }
+
+    fn reduce(&mut self, shares: f64, price: f64, commission: f64) {
+        self.position -= shares;
+        self.exit_shares += shares;
+        self.exit_value += shares * price;
+        self.commission += commission;
+    }
+
+    fn finish(self, symbol: &str, exit_time: DateTime<Utc>, open: bool) -> RoundTrip {
+        let entry_price = self.entry_value / self.entry_shares;
+        let exit_price = if self.exit_shares > 0.0 {
+            self.exit_value / self.exit_shares
+        } else {
+            entry_price
+        };
+        let pnl =
+            self.direction.sign() * (exit_price - entry_price) * self.exit_shares - self.commission;
+        RoundTrip {
+            symbol: symbol.to_string(),
+            direction: self.direction,
+            entry_time: self.entry_time,
+            exit_time,
+            quantity: self.quantity,
+            entry_price,
+            exit_price,
+            pnl,
+            return_pct: pnl / self.entry_value,
+            commission: self.commission,
+            open,
+        }
+    }
+}
+
+/// Round trips of `fills`, in the order they closed. Positions still held
+/// are closed at `prices` at `end`, and marked open.
+pub fn round_trips(
+    fills: &[Fill],
+    prices: &HashMap<String, f64>,
+    end: DateTime<Utc>,
+) -> Vec<RoundTrip> {
+    let mut open: HashMap<&str, OpenTrip> = HashMap::new();
+    let mut trips = Vec::new();
+    for fill in fills {
+        let mut shares = fill.quantity;
+        let direction = match fill.side.sign() > 0.0 {
+            true => Direction::Long,
+            false => Direction::Short,
+        };
+        if let Some(trip) = open.get_mut(fill.symbol.as_str()) {
+            if trip.direction == direction {
+                trip.add(shares, fill.price, fill.commission);
+                continue;
+            }
+            let closing = shares.min(trip.position);
+            trip.reduce(closing, fill.price, fill.commission * closing / shares);
+            if trip.position > EPSILON {
+                continue;
+            }
+            let trip = open.remove(fill.symbol.as_str()).unwrap();
+            trips.push(trip.finish(&fill.symbol, fill.timestamp, false));
+            shares -= closing;
+            if shares <= EPSILON {
+                continue;
+            }
+        }
+        // What is left of the fill opens a position the other way
+        let mut trip = OpenTrip::new(direction, fill.timestamp);
+        trip.add(shares, fill.price, fill.commission * shares / fill.quantity);
+        open.insert(&fill.symbol, trip);
+    }
+    let mut held: Vec<_> = open.into_iter().collect();
+    held.sort_by(|a, b| a.0.cmp(b.0));
+    for (symbol, mut trip) in held {
+        let price = prices
+            .get(symbol)
+            .copied()
+            .unwrap_or(trip.entry_value / trip.entry_shares);
+        let position = trip.position;
+        trip.reduce(position, price, 0.0);
+        trips.push(trip.finish(symbol, end, true));
+    }
+    trips
+}
+
+/// Largest fall of equity from a peak, as a fraction of the peak, and the
+/// longest time equity spent below a previous peak
+fn drawdowns(curve: &[EquityPoint], initial: f64) -> (f64, chrono::Duration) {
+    let mut peak = initial;
+    let mut peak_time = curve.first().map(|point| point.timestamp);
+    let mut max_drawdown: f64 = 0.0;
+    let mut longest = chrono::Duration::zero();
+    for point in curve {
+        if point.equity >= peak {
+            peak = point.equity;
+            peak_time = Some(point.timestamp);
+            continue;
+        }
+        if peak > 0.0 {
+            max_drawdown = max_drawdown.max((peak - point.equity) / peak);
+        }
+        if let Some(peak_time) = peak_time {
+            longest = longest.max(point.timestamp - peak_time);
+        }
+    }
+    (max_drawdown, longest)
+}
+
+/// The last value of each New York trading day, in time order
+fn daily_closes(values: impl IntoIterator<Item = (DateTime<Utc>, f64)>) -> Vec<(NaiveDate, f64)> {
+    let mut closes: Vec<(NaiveDate, f64)> = Vec::new();
+    for (time, value) in values {
+        let date = new_york_date(time);
+        match closes.last_mut() {
+            Some(last) if last.0 == date => last.1 = value,
+            _ => closes.push((date, value)),
+        }
+    }
+    closes
+}
+
+/// Changes from each value to the next, as fractions, starting from `first`
+fn returns(first: f64, values: impl IntoIterator<Item = f64>) -> Vec<f64> {
+    let mut previous = first;
+    values
+        .into_iter()
+        .map(|value| {
+            let change = if previous != 0.0 {
+                value / previous - 1.0
+            } else {
+                0.0
+            };
+            previous = value;
+            change
+        })
+        .collect()
+}
+
+/// Returns from one New York trading day's last equity to the next; the
+/// first day is measured from `initial`
+pub fn daily_returns(curve: &[EquityPoint], initial: f64) -> Vec<f64> {
+    let closes = daily_closes(curve.iter().map(|point| (point.timestamp, point.equity)));
+    returns(initial, closes.into_iter().map(|(_, equity)| equity))
+}
+
+/// Largest fall of `values` from a previous peak, as a fraction of the peak
+fn max_drawdown(values: impl IntoIterator<Item = f64>) -> f64 {
+    let mut peak = f64::NEG_INFINITY;
+    let mut max_drawdown: f64 = 0.0;
+    for value in values {
+        peak = peak.max(value);
+        if peak > 0.0 {
+            max_drawdown = max_drawdown.max((peak - value) / peak);
+        }
+    }
+    max_drawdown
+}
+
+/// Fraction of the time from `start` to `end` with a position in any symbol
+fn exposure(fills: &[Fill], start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+    let total = (end - start).num_milliseconds() as f64;
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let mut positions: HashMap<&str, f64> = HashMap::new();
+    let mut since = None;
+    let mut invested = chrono::Duration::zero();
+    for fill in fills {
+        *positions.entry(&fill.symbol).or_default() += fill.side.sign() * fill.quantity;
+        let holding = positions.values().any(|position| position.abs() > EPSILON);
+        match (holding, since) {
+            (true, None) => since = Some(fill.timestamp),
+            (false, Some(from)) => {
+                invested += fill.timestamp - from;
+                since = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = since {
+        invested += end - from;
+    }
+    invested.num_milliseconds() as f64 / total
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Sample covariance of two series of the same length
+fn covariance(a: &[f64], b: &[f64]) -> f64 {
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - mean_a) * (b - mean_b))
+        .sum::<f64>()
+        / (a.len() - 1) as f64
+}
+
+/// How a backtest did against a benchmark, such as SPY, over the New York
+/// trading days both have
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BenchmarkMetrics {
+    pub symbol: String,
+    /// Days with both an equity point and a benchmark bar
+    pub days: usize,
+    /// Return of holding the benchmark from the first of those days to the last
+    pub total_return: f64,
+    /// Return of the strategy over the same days
+    pub strategy_return: f64,
+    /// Annualized daily return the strategy made beyond what its beta to the
+    /// benchmark explains; `None` without a beta
+    pub alpha: Option<f64>,
+    /// How much the strategy's daily returns move with the benchmark's;
+    /// `None` when the benchmark never moves
+    pub beta: Option<f64>,
+    /// Correlation of the daily returns; `None` when either never moves
+    pub correlation: Option<f64>,
+    /// Largest fall of the benchmark from a peak
+    pub max_drawdown: f64,
+    /// Largest fall of the equity over the benchmark from a peak: how far
+    /// the strategy fell behind it
+    pub relative_max_drawdown: f64,
+}
+
+impl BenchmarkMetrics {
+    /// Compare `curve` with the closes of the benchmark `bars`, the last one
+    /// of each day. `None` with fewer than two days in common.
+    pub fn new(symbol: &str, curve: &[EquityPoint], bars: &[OhlcvBar]) -> Option<Self> {
+        let benchmark: HashMap<NaiveDate, f64> =
+            daily_closes(bars.iter().map(|bar| (bar.timestamp, bar.close)))
+                .into_iter()
+                .collect();
+        let days: Vec<(f64, f64)> =
+            daily_closes(curve.iter().map(|point| (point.timestamp, point.equity)))
+                .into_iter()
+                .filter_map(|(date, equity)| Some((equity, *benchmark.get(&date)?)))
+                .collect();
+        if days.len() < 2 {
+            return None;
+        }
+        let (first, last) = (days[0], days[days.len() - 1]);
+        let strategy = returns(first.0, days[1..].iter().map(|day| day.0));
+        let market = returns(first.1, days[1..].iter().map(|day| day.1));
+
+        let market_variance = covariance(&market, &market);
+        let strategy_variance = covariance(&strategy, &strategy);
+        let beta = (strategy.len() >= 2 && market_variance > 0.0)
+            .then(|| covariance(&strategy, &market) / market_variance);
+        let correlation = (strategy.len() >= 2 && market_variance > 0.0 && strategy_variance > 0.0)
+            .then(|| covariance(&strategy, &market) / (strategy_variance * market_variance).sqrt());
+        let alpha = beta.map(|beta| (mean(&strategy) - beta * mean(&market)) * TRADING_DAYS);
+
+        let ratio = |(equity, close): &(f64, f64)| if *close > 0.0 { equity / close } else { 0.0 };
+        Some(Self {
+            symbol: symbol.to_string(),
+            days: days.len(),
+            total_return: if first.1 > 0.0 {
+                last.1 / first.1 - 1.0
+            } else {
+                0.0
+            },
+            strategy_return: if first.0 > 0.0 {
+                last.0 / first.0 - 1.0
+            } else {
+                0.0
+            },
+            alpha,
+            beta,
+            correlation,
+            max_drawdown: max_drawdown(days.iter().map(|day| day.1)),
+            relative_max_drawdown: max_drawdown(days.iter().map(ratio)),
+        })
+    }
+}
+
+/// How a backtest performed
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BacktestReport {
+    pub strategy: String,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub initial_cash: f64,
+    pub final_equity: f64,
+    pub total_return: f64,
+    /// Total return compounded to a year; `None` for backtests shorter than a day
+    pub annualized_return: Option<f64>,
+    /// `None` with fewer than two daily returns, or returns that never change
+    pub sharpe_ratio: Option<f64>,
+    /// `None` with fewer than two daily returns, or no down days
+    pub sortino_ratio: Option<f64>,
+    /// Largest fall from a peak, as a fraction of the peak
+    pub max_drawdown: f64,
+    /// Longest time spent below a previous peak, in days
+    pub max_drawdown_days: f64,
+    /// Fraction of the time with a position
+    pub exposure: f64,
+    pub fills: usize,
+    pub commission: f64,
+    /// Round trips closed during the backtest; open ones are listed but not counted
+    pub closed_trades: usize,
+    /// Fraction of closed round trips with a profit
+    pub win_rate: Option<f64>,
+    /// Profits of winning round trips over losses of losing ones; `None` without losses
+    pub profit_factor: Option<f64>,
+    pub average_trade: Option<f64>,
+    pub best_trade: Option<f64>,
+    pub worst_trade: Option<f64>,
+    /// Set with `with_benchmark`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub benchmark: Option<BenchmarkMetrics>,
+    /// Set with `with_monte_carlo`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monte_carlo: Option<MonteCarloResult>,
+    pub round_trips: Vec<RoundTrip>,
+}
+
+impl BacktestReport {
+    pub fn new(result: &BacktestResult) -> Self {
+        let curve = &result.equity_curve;
+        let start = curve.first().map(|point| point.timestamp);
+        let end = curve.last().map(|point| point.timestamp);
+        let round_trips = match end {
+            Some(end) => round_trips(&result.fills, &result.prices, end),
+            None => Vec::new(),
+        };
+
+        let total_return = result.total_return();
+        let days = match (start, end) {
+            (Some(start), Some(end)) => (end - start).num_seconds() as f64 / 86_400.0,
+            _ => 0.0,
+        };
+        let annualized_return = (days >= 1.0 && total_return > -1.0)
+            .then(|| (1.0 + total_return).powf(365.25 / days) - 1.0);
+
+        let returns = daily_returns(curve, result.initial_cash);
+        let (sharpe_ratio, sortino_ratio) = if returns.len() >= 2 {
+            let average = mean(&returns);
+            let variance = returns.iter().map(|r| (r - average).powi(2)).sum::<f64>()
+                / (returns.len() - 1) as f64;
+            let downside = (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>()
+                / returns.len() as f64)
+                .sqrt();
+            let annualize = TRADING_DAYS.sqrt();
+            (
+                (variance > 0.0).then(|| average / variance.sqrt() * annualize),
+                (downside > 0.0).then(|| average / downside * annualize),
+            )
+        } else {
+            (None, None)
+        };
+
+        let (max_drawdown, longest) = drawdowns(curve, result.initial_cash);
+        let exposure = match (start, end) {
+            (Some(start), Some(end)) => exposure(&result.fills, start, end),
+            _ => 0.0,
+        };
+
+        let closed: Vec<f64> = round_trips
+            .iter()
+            .filter(|trip| !trip.open)
+            .map(|trip| trip.pnl)
+            .collect();
+        let profits: f64 = closed.iter().filter(|pnl| **pnl > 0.0).sum();
+        let losses: f64 = -closed.iter().filter(|pnl| **pnl < 0.0).sum::<f64>();
+        let has_trades = !closed.is_empty();
+        Self {
+            strategy: result.strategy.clone(),
+            start,
+            end,
+            initial_cash: result.initial_cash,
+            final_equity: result.final_equity,
+            total_return,
+            annualized_return,
+            sharpe_ratio,
+            sortino_ratio,
+            max_drawdown,
+            max_drawdown_days: longest.num_seconds() as f64 / 86_400.0,
+            exposure,
+            fills: result.fills.len(),
+            commission: result.commission(),
+            closed_trades: closed.len(),
+            win_rate: has_trades.then(|| {
+                closed.iter().filter(|pnl| **pnl > 0.0).count() as f64 / closed.len() as f64
+            }),
+            profit_factor: (losses > 0.0).then(|| profits / losses),
+            average_trade: has_trades.then(|| mean(&closed)),
+            best_trade: closed.iter().copied().reduce(f64::max),
+            worst_trade: closed.iter().copied().reduce(f64::min),
+            benchmark: None,
+            monte_carlo: None,
+            round_trips,
+        }
+    }
+
+    /// Add how the backtest of `curve` did against the benchmark `symbol`,
+    /// from its bars
+    pub fn with_benchmark(
+        mut self,
+        symbol: &str,
+        curve: &[EquityPoint],
+        bars: &[OhlcvBar],
+    ) -> Result<Self> {
+        self.benchmark = Some(BenchmarkMetrics::new(symbol, curve, bars).ok_or_else(|| {
+            Error::Invalid(format!(
+                "Benchmark {} has fewer than two days in common with the backtest",
+                symbol
+            ))
+        })?);
+        Ok(self)
+    }
+
+    /// Add a Monte Carlo simulation of equity curves from the profits of the
+    /// round trips, open ones included, so the trades add up to the final equity
+    pub fn with_monte_carlo(mut self, config: &MonteCarloConfig) -> Result<Self> {
+        let pnls: Vec<f64> = self.round_trips.iter().map(|trip| trip.pnl).collect();
+        self.monte_carlo = Some(simulate(&pnls, self.initial_cash, config)?);
+        Ok(self)
+    }
+
+    /// The figures of the report as `metric,value` lines, without the round trips
+    pub fn metrics_csv(&self) -> String {
+        let optional =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        let time = |time: Option<DateTime<Utc>>| {
+            time.map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true))
+                .unwrap_or_default()
+        };
+        let mut rows = vec![
+            ("strategy", self.strategy.clone()),
+            ("start", time(self.start)),
+            ("end", time(self.end)),
+            ("initial_cash", self.initial_cash.to_string()),
+            ("final_equity", self.final_equity.to_string()),
+            ("total_return", self.total_return.to_string()),
+            ("annualized_return", optional(self.annualized_return)),
+            ("sharpe_ratio", optional(self.sharpe_ratio)),
+            ("sortino_ratio", optional(self.sortino_ratio)),
+            ("max_drawdown", self.max_drawdown.to_string()),
+            ("max_drawdown_days", self.max_drawdown_days.to_string()),
+            ("exposure", self.exposure.to_string()),
+            ("fills", self.fills.to_string()),
+            ("commission", self.commission.to_string()),
+            ("closed_trades", self.closed_trades.to_string()),
+            ("win_rate", optional(self.win_rate)),
+            ("profit_factor", optional(self.profit_factor)),
+            ("average_trade", optional(self.average_trade)),
+            ("best_trade", optional(self.best_trade)),
+            ("worst_trade", optional(self.worst_trade)),
+        ];
+        if let Some(benchmark) = &self.benchmark {
+            rows.extend([
+                ("benchmark", benchmark.symbol.clone()),
+                ("benchmark_days", benchmark.days.to_string()),
+                ("benchmark_return", benchmark.total_return.to_string()),
+                (
+                    "benchmark_strategy_return",
+                    benchmark.strategy_return.to_string(),
+                ),
+                ("alpha", optional(benchmark.alpha)),
+                ("beta", optional(benchmark.beta)),
+                ("correlation", optional(benchmark.correlation)),
+                ("benchmark_max_drawdown", benchmark.max_drawdown.to_string()),
+                (
+                    "relative_max_drawdown",
+                    benchmark.relative_max_drawdown.to_string(),
+                ),
+            ]);
+        }
+        let mut csv = String::from("metric,value\n");
+        for (metric, value) in rows {
+            let _ = writeln!(csv, "{},{}", metric, value);
+        }
+        if let Some(simulation) = &self.monte_carlo {
+            let _ = writeln!(csv, "monte_carlo_method,{}", method_name(simulation.method));
+            let _ = writeln!(csv, "monte_carlo_runs,{}", simulation.runs);
+            for (name, value) in simulation.max_drawdown.values() {
+                let _ = writeln!(csv, "monte_carlo_max_drawdown_{},{}", name, value);
+            }
+            for (name, value) in simulation.final_equity.values() {
+                let _ = writeln!(csv, "monte_carlo_final_equity_{},{}", name, value);
+            }
+            let _ = writeln!(
+                csv,
+                "monte_carlo_probability_of_loss,{}",
+                simulation.probability_of_loss
+            );
+        }
+        csv
+    }
+
+    /// The round trips as CSV, with `ROUND_TRIPS_CSV_HEADER`
+    pub fn round_trips_csv(&self) -> String {
+        let mut csv = format!("{}\n", ROUND_TRIPS_CSV_HEADER);
+        for trip in &self.round_trips {
+            let _ = writeln!(csv, "{}", trip.to_csv());
+        }
+        csv
+    }
+
+    /// A self-contained HTML page with the figures, a chart of `curve` and
+    /// the round trips
+    pub fn to_html(&self, curve: &[EquityPoint]) -> String {
+        let percent = |value: Option<f64>| match value {
+            Some(value) => format!("{:.2}%", value * 100.0),
+            None => "-".to_string(),
+        };
+        let number = |value: Option<f64>| match value {
+            Some(value) => format!("{:.2}", value),
+            None => "-".to_string(),
+        };
+        let mut rows = vec![
+            ("Initial cash", format!("${:.2}", self.initial_cash)),
+            ("Final equity", format!("${:.2}", self.final_equity)),
+            ("Total return", percent(Some(self.total_return))),
+            ("Annualized return", percent(self.annualized_return)),
+            ("Sharpe ratio", number(self.sharpe_ratio)),
+            ("Sortino ratio", number(self.sortino_ratio)),
+            ("Max drawdown", percent(Some(self.max_drawdown))),
+            (
+                "Max drawdown duration",
+                format!("{:.1} days", self.max_drawdown_days),
+            ),
+            ("Exposure", percent(Some(self.exposure))),
+            ("Fills", self.fills.to_string()),
+            ("Commission", format!("${:.2}", self.commission)),
+            ("Closed trades", self.closed_trades.to_string()),
+            ("Win rate", percent(self.win_rate)),
+            ("Profit factor", number(self.profit_factor)),
+            ("Average trade", number(self.average_trade)),
+            ("Best trade", number(self.best_trade)),
+            ("Worst trade", number(self.worst_trade)),
+        ];
+        if let Some(benchmark) = &self.benchmark {
+            rows.extend([
+                ("Benchmark", escape(&benchmark.symbol)),
+                ("Benchmark return", percent(Some(benchmark.total_return))),
+                (
+                    "Strategy return over the same days",
+                    percent(Some(benchmark.strategy_return)),
+                ),
+                ("Alpha", percent(benchmark.alpha)),
+                ("Beta", number(benchmark.beta)),
+                ("Correlation", number(benchmark.correlation)),
+                (
+                    "Benchmark max drawdown",
+                    percent(Some(benchmark.max_drawdown)),
+                ),
+                (
+                    "Relative max drawdown",
+                    percent(Some(benchmark.relative_max_drawdown)),
+                ),
+            ]);
+        }
+        if let Some(simulation) = &self.monte_carlo {
+            let spread = |distribution: &Distribution, format: &dyn Fn(f64) -> String| {
+                format!(
+                    "{} / {} / {}",
+                    format(distribution.p5),
+                    format(distribution.median),
+                    format(distribution.p95)
+                )
+            };
+            rows.extend([
+                (
+                    "Monte Carlo",
+                    format!(
+                        "{} runs of {} trades, {}",
+                        simulation.runs,
+                        simulation.trades,
+                        method_name(simulation.method)
+                    ),
+                ),
+                (
+                    "Max drawdown (5th / 50th / 95th percentile)",
+                    spread(&simulation.max_drawdown, &|value| {
+                        format!("{:.2}%", value * 100.0)
+                    }),
+                ),
+                (
+                    "Final equity (5th / 50th / 95th percentile)",
+                    spread(&simulation.final_equity, &|value| format!("${:.2}", value)),
+                ),
+                (
+                    "Probability of loss",
+                    percent(Some(simulation.probability_of_loss)),
+                ),
+            ]);
+        }
+
+        let mut html = String::new();
+        let title = format!("Backtest of {}", escape(&self.strategy));
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+             body {{ font-family: sans-serif; margin: 2em; color: #222; }}\n\
+             table {{ border-collapse: collapse; margin-bottom: 2em; }}\n\
+             td, th {{ padding: 4px 12px; border-bottom: 1px solid #ddd; text-align: right; }}\n\
+             td:first-child, th:first-child {{ text-align: left; }}\n\
+             .loss {{ color: #c0392b; }}\n\
+             </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            title, title
+        );
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            let _ = writeln!(
+                html,
+                "<p>{} to {}</p>",
+                start.to_rfc3339(),
+                end.to_rfc3339()
+            );
+        }
+        html.push_str("<table>\n");
+        for (name, value) in rows {
+            let _ = writeln!(html, "<tr><td>{}</td><td>{}</td></tr>", name, value);
+        }
+        html.push_str("</table>\n<h2>Equity</h2>\n");
+        html.push_str(&equity_svg(curve, 900.0, 300.0));
+        let _ = writeln!(html, "\n<h2>Round trips</h2>\n<table>");
+        html.push_str(
+            "<tr><th>Symbol</th><th>Direction</th><th>Entry</th><th>Exit</th><th>Quantity</th>\
+             <th>Entry price</th><th>Exit price</th><th>P&amp;L</th><th>Return</th></tr>\n",
+        );
+        for trip in &self.round_trips {
+            let _ = writeln!(
+                html,
+                "<tr{}><td>{}</td><td>{:?}{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}%</td></tr>",
+                if trip.pnl < 0.0 { " class=\"loss\"" } else { "" },
+                escape(&trip.symbol),
+                trip.direction,
+                if trip.open { " (open)" } else { "" },
+                trip.entry_time.to_rfc3339(),
+                trip.exit_time.to_rfc3339(),
+                trip.quantity,
+                trip.entry_price,
+                trip.exit_price,
+                trip.pnl,
+                trip.return_pct * 100.0
+            );
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+
+    /// Write the report to `path`, as HTML for `.html` and `.htm` files, CSV
+    /// for `.csv` files, with the round trips in `<name>.trades.csv` next to
+    /// it, and JSON otherwise. Returns the files written.
+    pub fn write(&self, path: &Path, curve: &[EquityPoint]) -> Result<Vec<PathBuf>> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("html" | "htm") => std::fs::write(path, self.to_html(curve))?,
+            Some("csv") => {
+                std::fs::write(path, self.metrics_csv())?;
+                let trades = path.with_extension("trades.csv");
+                std::fs::write(&trades, self.round_trips_csv())?;
+                return Ok(vec![path.to_path_buf(), trades]);
+            }
+            _ => std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?,
+        }
+        Ok(vec![path.to_path_buf()])
+    }
+}
+
+impl std::fmt::Display for BacktestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |value: Option<f64>| match value {
+            Some(value) => format!("{:+.2}%", value * 100.0),
+            None => "-".to_string(),
+        };
+        let number = |value: Option<f64>| match value {
+            Some(value) => format!("{:.2}", value),
+            None => "-".to_string(),
+        };
+        write!(
+            f,
+            "📈 {}: ${:.2} → ${:.2} ({}",
+            self.strategy,
+            self.initial_cash,
+            self.final_equity,
+            percent(Some(self.total_return))
+        )?;
+        match self.annualized_return {
+            Some(annualized) => writeln!(f, ", {} a year)", percent(Some(annualized)))?,
+            None => writeln!(f, ")")?,
+        }
+        writeln!(
+            f,
+            "   Sharpe {} | Sortino {} | Max drawdown {:.2}% over {:.1} days | Exposure {:.1}%",
+            number(self.sharpe_ratio),
+            number(self.sortino_ratio),
+            self.max_drawdown * 100.0,
+            self.max_drawdown_days,
+            self.exposure * 100.0
+        )?;
+        write!(
+            f,
+            "   {} closed trades | Win rate {} | Profit factor {} | Average {} | {} fills, ${:.2} commission",
+            self.closed_trades,
+            self.win_rate
+                .map(|rate| format!("{:.1}%", rate * 100.0))
+                .unwrap_or_else(|| "-".to_string()),
+            number(self.profit_factor),
+            number(self.average_trade),
+            self.fills,
+            self.commission
+        )?;
+        if let Some(benchmark) = &self.benchmark {
+            write!(
+                f,
+                "\n   vs {}: {} over {} days | Alpha {} | Beta {} | Correlation {} | Relative max drawdown {:.2}%",
+                benchmark.symbol,
+                percent(Some(benchmark.total_return)),
+                benchmark.days,
+                percent(benchmark.alpha),
+                number(benchmark.beta),
+                number(benchmark.correlation),
+                benchmark.relative_max_drawdown * 100.0
+            )?;
+        }
+        if let Some(simulation) = &self.monte_carlo {
+            write!(
+                f,
+                "\n   Monte Carlo ({} runs, {}): max drawdown {:.2}% median, {:.2}% at the 95th percentile | final equity ${:.2} to ${:.2} (5th to 95th) | {:.1}% end in a loss",
+                simulation.runs,
+                method_name(simulation.method),
+                simulation.max_drawdown.median * 100.0,
+                simulation.max_drawdown.p95 * 100.0,
+                simulation.final_equity.p5,
+                simulation.final_equity.p95,
+                simulation.probability_of_loss * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The equity curve as an inline SVG line chart
+fn equity_svg(curve: &[EquityPoint], width: f64, height: f64) -> String {
+    if curve.len() < 2 {
+        return "<p>Not enough points to chart.</p>".to_string();
+    }
+    let (low, high) = curve
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), point| {
+            (low.min(point.equity), high.max(point.equity))
+        });
+    let range = if high > low { high - low } else { 1.0 };
+    let step = width / (curve.len() - 1) as f64;
+    let mut points = String::new();
+    for (index, point) in curve.iter().enumerate() {
+        let y = height - (point.equity - low) / range * height;
+        let _ = write!(points, "{:.1},{:.1} ", index as f64 * step, y);
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\
+         <polyline fill=\"none\" stroke=\"#2c7be5\" stroke-width=\"1.5\" points=\"{}\"/>\
+         <text x=\"4\" y=\"14\" font-size=\"12\">${:.2}</text>\
+         <text x=\"4\" y=\"{}\" font-size=\"12\">${:.2}</text></svg>",
+        width,
+        height,
+        width,
+        height,
+        points.trim_end(),
+        high,
+        height - 4.0,
+        low
+    )
+}
+
+fn method_name(method: Resampling) -> &'static str {
+    match method {
+        Resampling::Bootstrap => "bootstrap",
+        Resampling::Shuffle => "shuffle",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Fail early on report paths whose directory does not exist
+pub fn check_report_path(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => Err(Error::Invalid(format!(
+            "Report directory {} does not exist",
+            dir.display()
+        ))),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,177 @@
+//! On-disk cache of historical bars, so repeated downloads of overlapping
+//! ranges are served locally instead of from the API.
+//!
+//! Bars are stored in calendar chunks, a month for intraday timeframes and a
+//! year for daily and longer ones:
+//! `<root>/bars/<feed>/<adjustment>/<timeframe>/<SYMBOL>/<chunk start>.json`.
+//! A chunk is only stored once it has ended, so the cache never serves a
+//! range that could still change.
+
+use crate::Result;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// A chunk of the bars of one symbol, timeframe, feed and adjustment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    pub symbol: String,
+    pub timeframe: String,
+    pub feed: String,
+    pub adjustment: String,
+    /// First day of the chunk
+    pub start: NaiveDate,
+    /// First day after the chunk
+    pub end: NaiveDate,
+}
+
+impl CacheKey {
+    /// The chunk that holds `date`
+    pub fn containing(
+        symbol: &str,
+        timeframe: &str,
+        feed: &str,
+        adjustment: &str,
+        date: NaiveDate,
+    ) -> Self {
+        let intraday = timeframe.ends_with("Min") || timeframe.ends_with("Hour");
+        let (start, end) = if intraday {
+            let start = date.with_day(1).expect("every month has a first day");
+            let end = if start.month() == 12 {
+                NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+            } else {
+                NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+            };
+            (start, end.expect("valid month"))
+        } else {
+            (
+                NaiveDate::from_ymd_opt(date.year(), 1, 1).expect("valid year"),
+                NaiveDate::from_ymd_opt(date.year() + 1, 1, 1).expect("valid year"),
+            )
+        };
+        Self {
+            symbol: symbol.to_string(),
+            timeframe: timeframe.to_string(),
+            feed: feed.to_string(),
+            adjustment: adjustment.to_string(),
+            start,
+            end,
+        }
+    }
+
+    /// The chunk after this one
+    pub fn next(&self) -> Self {
+        Self::containing(
+            &self.symbol,
+            &self.timeframe,
+            &self.feed,
+            &self.adjustment,
+            self.end,
+        )
+    }
+
+    /// Whether the chunk ended before `now`'s UTC date, so its bars are final
+    pub fn is_complete(&self, now: DateTime<Utc>) -> bool {
+        self.end <= now.date_naive()
+    }
+
+    fn relative_path(&self) -> PathBuf {
+        Path::new("bars")
+            .join(&self.feed)
+            .join(&self.adjustment)
+            .join(&self.timeframe)
+            .join(&self.symbol)
+            .join(format!("{}.json", self.start.format("%Y-%m-%d")))
+    }
+}
+
+impl std::fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}..{}",
+            self.symbol, self.timeframe, self.feed, self.adjustment, self.start, self.end
+        )
+    }
+}
+
+/// The cache directory and its chunks
+#[derive(Debug, Clone)]
+pub struct BarCache {
+    root: PathBuf,
+}
+
+impl BarCache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// `ALPACA_CACHE_DIR`, or `algorithms-trading` in `XDG_CACHE_HOME` or
+    /// `~/.cache`; `None` if none of them is set
+    pub fn default_dir() -> Option<PathBuf> {
+        if let Some(dir) = std::env::var_os("ALPACA_CACHE_DIR") {
+            return Some(PathBuf::from(dir));
+        }
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(base.join("algorithms-trading"))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The stored bars of `key`, or `None` if the chunk is not cached
+    pub fn get<T: DeserializeOwned>(&self, key: &CacheKey) -> Result<Option<Vec<T>>> {
+        match std::fs::read_to_string(self.root.join(key.relative_path())) {
+            Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether `key` is cached, without reading it
+    pub fn contains(&self, key: &CacheKey) -> bool {
+        self.root.join(key.relative_path()).is_file()
+    }
+
+    /// Store the bars of `key`, replacing what was there
+    pub fn put<T: Serialize>(&self, key: &CacheKey, bars: &[T]) -> Result<()> {
+        let path = self.root.join(key.relative_path());
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write to a temporary file first so readers never see half a chunk
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string(bars)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Delete every cached chunk; returns the number of files and bytes removed
+    pub fn clear(&self) -> Result<(u64, u64)> {
+        let dir = self.root.join("bars");
+        let mut removed = (0, 0);
+        if dir.exists() {
+            count_files(&dir, &mut removed)?;
+            std::fs::remove_dir_all(&dir)?;
+        }
+        Ok(removed)
+    }
+}
+
+fn count_files(dir: &Path, count: &mut (u64, u64)) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            count_files(&entry.path(), count)?;
+        } else {
+            count.0 += 1;
+            count.1 += metadata.len();
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,153 @@
+//! Reading capture files back in any of the formats the tools write them in,
+//! for the commands that work on captures after the fact.
+
+use crate::compression::{open_reader, Compression};
+use crate::raw_frames::raw_frame;
+use crate::{Result, StreamingData};
+use std::io::BufRead;
+use std::path::Path;
+use tracing::warn;
+
+/// A format captures are stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CaptureFormat {
+    /// JSON lines, as written with --format json
+    Json,
+    /// CSV, as written with --format csv
+    Csv,
+    /// Parquet dataset directory, as written with --dataset; trades, quotes and bars only
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// SQLite database with a `messages` table and a typed view per message type
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl CaptureFormat {
+    /// The format a path suggests: a directory, or a path without an
+    /// extension that is not a file yet, is a Parquet dataset; `.db`,
+    /// `.sqlite` and `.sqlite3` files are SQLite, `.csv` files CSV, and any
+    /// other file JSON lines. Compression extensions are ignored.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let path = Compression::from_extension(path).strip_extension(path);
+        let extension = path.extension().and_then(|e| e.to_str());
+        match extension.unwrap_or_default() {
+            _ if path.is_dir() || (extension.is_none() && !path.exists()) => parquet_format(&path),
+            "db" | "sqlite" | "sqlite3" => sqlite_format(&path),
+            "csv" => Ok(CaptureFormat::Csv),
+            _ => Ok(CaptureFormat::Json),
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_format(_path: &Path) -> Result<CaptureFormat> {
+    Ok(CaptureFormat::Parquet)
+}
+
+#[cfg(not(feature = "parquet"))]
+fn parquet_format(path: &Path) -> Result<CaptureFormat> {
+    Err(crate::Error::Invalid(format!(
+        "{} is a dataset directory; Parquet datasets need the parquet feature",
+        path.display()
+    )))
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_format(_path: &Path) -> Result<CaptureFormat> {
+    Ok(CaptureFormat::Sqlite)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sqlite_format(path: &Path) -> Result<CaptureFormat> {
+    Err(crate::Error::Invalid(format!(
+        "{} is a SQLite database, which needs the sqlite feature",
+        path.display()
+    )))
+}
+
+/// Call `on_message` with each message of the capture at `input`. JSON and
+/// CSV files are read in file order, skipping lines that are not messages;
+/// gzip and zstd files are decompressed. The frames of a JSON recording
+/// written with `--record-raw` are decoded as the session decodes them.
+/// Datasets and databases are read table by table.
+pub fn read_capture(
+    input: &Path,
+    format: CaptureFormat,
+    mut on_message: impl FnMut(StreamingData) -> Result<()>,
+) -> Result<()> {
+    match format {
+        CaptureFormat::Json => {
+            for line in open_reader(input)?.lines() {
+                let line = line?;
+                if let Some(data) = json_message(&line) {
+                    on_message(data)?;
+                } else if let Some(frame) = raw_frame(&line) {
+                    // Recordings keep the frames the session failed to parse as well
+                    match frame.decode() {
+                        Ok(records) => records.into_iter().try_for_each(&mut on_message)?,
+                        Err(e) => {
+                            warn!(error = %e, frame = %frame.frame, "Skipping undecodable frame")
+                        }
+                    }
+                }
+            }
+        }
+        CaptureFormat::Csv => {
+            // Console CSV has no header, and older files have four columns
+            let mut csv_reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(open_reader(input)?);
+            for result in csv_reader.records() {
+                if let Some(data) = csv_message(&result?) {
+                    on_message(data)?;
+                }
+            }
+        }
+        #[cfg(feature = "parquet")]
+        CaptureFormat::Parquet => crate::dataset::read_dataset(input, on_message)?,
+        #[cfg(feature = "sqlite")]
+        CaptureFormat::Sqlite => crate::sqlite::read_database(input, on_message)?,
+    }
+    Ok(())
+}
+
+/// The message on a line of a JSON capture; `None` for status lines and
+/// malformed JSON
+pub fn json_message(line: &str) -> Option<StreamingData> {
+    if line.trim().is_empty() || !line.starts_with('{') {
+        return None; // Skip non-JSON lines (like status messages)
+    }
+    serde_json::from_str(line).ok()
+}
+
+/// The message in a record of a CSV capture; `None` for the header and
+/// short records
+pub fn csv_message(record: &csv::StringRecord) -> Option<StreamingData> {
+    if record.len() < 4 {
+        return None;
+    }
+    let received_time = record[0].parse::<chrono::DateTime<chrono::Utc>>().ok()?;
+    Some(StreamingData {
+        received_time,
+        event_time: record.get(4).and_then(|time| time.parse().ok()),
+        message_type: record[1].to_string(),
+        symbol: if record[2].is_empty() {
+            None
+        } else {
+            Some(record[2].to_string())
+        },
+        data: decode_payload(&record[3]),
+    })
+}
+
+/// The payload column of a CSV capture. Console CSV replaces the commas of
+/// the JSON with semicolons to keep it in one column; those are restored
+/// when the column is not JSON as written. A payload that is not JSON
+/// either is kept as a string.
+pub fn decode_payload(text: &str) -> serde_json::Value {
+    serde_json::from_str(text)
+        .or_else(|_| serde_json::from_str(&text.replace(';', ",")))
+        .unwrap_or_else(|_| serde_json::Value::String(text.to_string()))
+}
@@ -0,0 +1,323 @@
+//! Comparison of two captures of the same session, such as a primary and a
+//! redundant client or two feeds, for `trade diff`: the messages only one of
+//! them has, how much later one received them than the other, and prices
+//! and sizes that differ.
+
+use crate::{payload_fields, DataFormat, Result, StreamingData};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+/// CSV header of the rows `Difference::format` writes
+pub const DIFFERENCES_CSV_HEADER: &str =
+    "symbol,event_time,message_type,id,issue,field,first,second";
+
+/// How the captures differ on a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Issue {
+    /// Only the first capture has the message
+    OnlyFirst,
+    /// Only the second capture has the message
+    OnlySecond,
+    /// A price or size of the message differs by more than the tolerance
+    Mismatch,
+}
+
+impl Issue {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Issue::OnlyFirst => "only_first",
+            Issue::OnlySecond => "only_second",
+            Issue::Mismatch => "mismatch",
+        }
+    }
+}
+
+/// One difference between the captures. Mismatches carry the field, named
+/// as for templates, and its value in each capture.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Difference {
+    pub symbol: String,
+    pub event_time: DateTime<Utc>,
+    pub message_type: String,
+    /// Trade id, for messages that have one
+    pub id: Option<u64>,
+    pub issue: Issue,
+    pub field: Option<String>,
+    pub first: Option<f64>,
+    pub second: Option<f64>,
+}
+
+impl Difference {
+    /// The difference as a line in `format`, matching
+    /// `DIFFERENCES_CSV_HEADER` for CSV
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let time = self
+            .event_time
+            .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+        let value = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+        let id = self.id.map(|id| format!(" #{}", id)).unwrap_or_default();
+        Ok(match format {
+            DataFormat::Plain => match self.issue {
+                Issue::OnlyFirst => format!(
+                    "{} {} {}{}: only in the first capture",
+                    self.symbol, time, self.message_type, id
+                ),
+                Issue::OnlySecond => format!(
+                    "{} {} {}{}: only in the second capture",
+                    self.symbol, time, self.message_type, id
+                ),
+                Issue::Mismatch => format!(
+                    "{} {} {}{}: {} {} vs {}",
+                    self.symbol,
+                    time,
+                    self.message_type,
+                    id,
+                    self.field.as_deref().unwrap_or_default(),
+                    value(self.first),
+                    value(self.second)
+                ),
+            },
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{}",
+                self.symbol,
+                time,
+                self.message_type,
+                self.id.map(|id| id.to_string()).unwrap_or_default(),
+                self.issue.as_str(),
+                self.field.as_deref().unwrap_or_default(),
+                value(self.first),
+                value(self.second)
+            ),
+        })
+    }
+}
+
+/// How a symbol's messages compare between the captures. Skew is when the
+/// second capture received a message minus when the first did, so it is
+/// positive when the second was behind.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SymbolDiff {
+    pub symbol: String,
+    pub first: u64,
+    pub second: u64,
+    /// Messages in both captures
+    pub matched: u64,
+    pub only_first: u64,
+    pub only_second: u64,
+    /// Matched messages with a price or size beyond the tolerance
+    pub mismatched: u64,
+    pub median_skew_ms: Option<f64>,
+    pub mean_skew_ms: Option<f64>,
+    pub min_skew_ms: Option<f64>,
+    pub max_skew_ms: Option<f64>,
+}
+
+impl std::fmt::Display for SymbolDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let skew = |skew: Option<f64>| match skew {
+            Some(ms) => format!("{:+.1}ms", ms),
+            None => "-".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} matched, {} only in first, {} only in second, {} mismatched, skew median {} (mean {}, {} to {})",
+            self.symbol,
+            self.matched,
+            self.only_first,
+            self.only_second,
+            self.mismatched,
+            skew(self.median_skew_ms),
+            skew(self.mean_skew_ms),
+            skew(self.min_skew_ms),
+            skew(self.max_skew_ms)
+        )
+    }
+}
+
+/// Symbol, event time, type and trade id of a message
+type MessageId = (String, DateTime<Utc>, String, Option<u64>);
+
+/// What a message is matched on: its id, and which of the messages with that
+/// id it is, in capture order
+type MatchKey = (MessageId, usize);
+
+/// What is compared of a message
+#[derive(Debug)]
+struct Seen {
+    received: DateTime<Utc>,
+    fields: Vec<(&'static str, f64)>,
+}
+
+impl Seen {
+    fn of(data: &StreamingData) -> Self {
+        Self {
+            received: data.received_time,
+            fields: payload_fields(&data.message_type)
+                .iter()
+                .filter(|(name, _)| *name != "id")
+                .filter_map(|(name, key)| Some((*name, data.data.get(*key)?.as_f64()?)))
+                .collect(),
+        }
+    }
+}
+
+/// Matches the market data messages of two captures. Messages without a
+/// symbol or an event time, such as status messages, are not compared.
+#[derive(Debug, Default)]
+pub struct CaptureDiff {
+    tolerance: f64,
+    messages: BTreeMap<MatchKey, (Option<Seen>, Option<Seen>)>,
+    /// Messages seen per match key without the index, per capture
+    occurrences: [HashMap<MessageId, usize>; 2],
+}
+
+impl CaptureDiff {
+    /// Prices and sizes more than `tolerance` apart, as a fraction of the
+    /// first capture's value, are mismatches; 0 compares them exactly
+    pub fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            ..Self::default()
+        }
+    }
+
+    /// Add a message of the first capture
+    pub fn add_first(&mut self, data: &StreamingData) {
+        self.add(0, data);
+    }
+
+    /// Add a message of the second capture
+    pub fn add_second(&mut self, data: &StreamingData) {
+        self.add(1, data);
+    }
+
+    fn add(&mut self, capture: usize, data: &StreamingData) {
+        let (Some(symbol), Some(event_time)) = (&data.symbol, data.event_time) else {
+            return;
+        };
+        let id = data.data.get("i").and_then(|id| id.as_u64());
+        let message = (symbol.clone(), event_time, data.message_type.clone(), id);
+        let occurrence = self.occurrences[capture]
+            .entry(message.clone())
+            .or_default();
+        let key = (message, *occurrence);
+        *occurrence += 1;
+        let entry = self.messages.entry(key).or_default();
+        let seen = Some(Seen::of(data));
+        if capture == 0 {
+            entry.0 = seen;
+        } else {
+            entry.1 = seen;
+        }
+    }
+
+    /// The comparison of every symbol, sorted by symbol, and every
+    /// difference, by symbol and then event time
+    pub fn finish(self) -> (Vec<SymbolDiff>, Vec<Difference>) {
+        let mut symbols: BTreeMap<String, (SymbolDiff, Vec<f64>)> = BTreeMap::new();
+        let mut differences = Vec::new();
+        for (((symbol, event_time, message_type, id), _), seen) in self.messages {
+            let (diff, skews) = symbols.entry(symbol.clone()).or_insert_with(|| {
+                (
+                    SymbolDiff {
+                        symbol: symbol.clone(),
+                        first: 0,
+                        second: 0,
+                        matched: 0,
+                        only_first: 0,
+                        only_second: 0,
+                        mismatched: 0,
+                        median_skew_ms: None,
+                        mean_skew_ms: None,
+                        min_skew_ms: None,
+                        max_skew_ms: None,
+                    },
+                    Vec::new(),
+                )
+            });
+            let difference = |issue, field: Option<&str>, first, second| Difference {
+                symbol: symbol.clone(),
+                event_time,
+                message_type: message_type.clone(),
+                id,
+                issue,
+                field: field.map(str::to_string),
+                first,
+                second,
+            };
+            match seen {
+                (Some(_), None) => {
+                    diff.first += 1;
+                    diff.only_first += 1;
+                    differences.push(difference(Issue::OnlyFirst, None, None, None));
+                }
+                (None, Some(_)) => {
+                    diff.second += 1;
+                    diff.only_second += 1;
+                    differences.push(difference(Issue::OnlySecond, None, None, None));
+                }
+                (Some(first), Some(second)) => {
+                    diff.first += 1;
+                    diff.second += 1;
+                    diff.matched += 1;
+                    skews.push(
+                        (second.received - first.received)
+                            .num_microseconds()
+                            .unwrap_or(0) as f64
+                            / 1000.0,
+                    );
+                    let mut mismatched = false;
+                    for (field, value) in &first.fields {
+                        let other = second
+                            .fields
+                            .iter()
+                            .find(|(name, _)| name == field)
+                            .map(|(_, value)| *value);
+                        if other.is_none_or(|other| !within(*value, other, self.tolerance)) {
+                            mismatched = true;
+                            differences.push(difference(
+                                Issue::Mismatch,
+                                Some(field),
+                                Some(*value),
+                                other,
+                            ));
+                        }
+                    }
+                    diff.mismatched += mismatched as u64;
+                }
+                (None, None) => unreachable!("every key comes from a message"),
+            }
+        }
+
+        let diffs = symbols
+            .into_values()
+            .map(|(mut diff, mut skews)| {
+                if !skews.is_empty() {
+                    skews.sort_by(f64::total_cmp);
+                    let middle = skews.len() / 2;
+                    diff.median_skew_ms = Some(if skews.len().is_multiple_of(2) {
+                        (skews[middle - 1] + skews[middle]) / 2.0
+                    } else {
+                        skews[middle]
+                    });
+                    diff.mean_skew_ms = Some(skews.iter().sum::<f64>() / skews.len() as f64);
+                    diff.min_skew_ms = skews.first().copied();
+                    diff.max_skew_ms = skews.last().copied();
+                }
+                diff
+            })
+            .collect();
+        (diffs, differences)
+    }
+}
+
+/// Whether `other` is within `tolerance` of `value`, as a fraction of
+/// `value`
+fn within(value: f64, other: f64, tolerance: f64) -> bool {
+    if value == other {
+        return true;
+    }
+    value != 0.0 && (other - value).abs() / value.abs() <= tolerance
+}
@@ -0,0 +1,568 @@
+//! Candlestick and line charts of bars for `trade chart`, drawn with text for
+//! the terminal or, with the `charts` feature, as PNG images, with volume and
+//! moving averages overlaid.
+
+use crate::console::{GREEN, RED, RESET, YELLOW};
+use crate::indicators::{self, Indicator as _};
+use crate::resample::OhlcvBar;
+use crate::{Error, Result};
+use std::fmt;
+
+/// Width of the price labels left of the chart, axis excluded
+const LABEL_WIDTH: usize = 10;
+
+/// Characters of the indicators, in the order they are given
+const MARKS: [char; 4] = ['+', 'x', 'o', '~'];
+
+/// How each bar is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChartKind {
+    /// A candle of each bar's open, high, low and close
+    Candle,
+    /// A line through the closes
+    Line,
+}
+
+/// A line drawn over the prices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indicator {
+    /// Simple moving average of the last N closes
+    Sma(usize),
+    /// Exponential moving average of the closes over N bars, starting from
+    /// the average of the first N
+    Ema(usize),
+    /// Volume-weighted average price of each bar
+    Vwap,
+}
+
+impl Indicator {
+    /// Parse `sma:N`, `ema:N` or `vwap`
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Invalid(format!(
+                "Invalid indicator {:?}: expected sma:N, ema:N or vwap",
+                value
+            ))
+        };
+        let value = value.trim().to_ascii_lowercase();
+        if value == "vwap" {
+            return Ok(Indicator::Vwap);
+        }
+        let (name, period) = value.split_once(':').ok_or_else(invalid)?;
+        let period = period
+            .parse()
+            .ok()
+            .filter(|period| *period > 0)
+            .ok_or_else(invalid)?;
+        match name {
+            "sma" => Ok(Indicator::Sma(period)),
+            "ema" => Ok(Indicator::Ema(period)),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// The indicator at each of `bars`; `None` while an average has fewer
+    /// than N bars
+    pub fn values(&self, bars: &[OhlcvBar]) -> Vec<Option<f64>> {
+        match *self {
+            Indicator::Sma(period) => indicators::Sma::new(period)
+                .map(|mut sma| sma.compute(bars))
+                .unwrap_or_else(|_| vec![None; bars.len()]),
+            Indicator::Ema(period) => indicators::Ema::new(period)
+                .map(|mut ema| ema.compute(bars))
+                .unwrap_or_else(|_| vec![None; bars.len()]),
+            Indicator::Vwap => bars.iter().map(|bar| Some(bar.vwap)).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Indicator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indicator::Sma(period) => write!(f, "SMA({})", period),
+            Indicator::Ema(period) => write!(f, "EMA({})", period),
+            Indicator::Vwap => write!(f, "VWAP"),
+        }
+    }
+}
+
+/// What is drawn, and how large
+#[derive(Debug, Clone)]
+pub struct ChartOptions {
+    pub kind: ChartKind,
+    pub indicators: Vec<Indicator>,
+    /// Draw a volume panel under the prices
+    pub volume: bool,
+    /// Characters across, price labels included, or pixels of an image
+    pub width: usize,
+    /// Lines of the price panel, or pixels of an image
+    pub height: usize,
+    /// Draw text charts with ASCII characters only
+    pub ascii: bool,
+    /// Color text charts: up bars green, down bars red, indicators yellow
+    pub color: bool,
+}
+
+/// The characters of a text chart
+struct Glyphs {
+    wick: char,
+    up: char,
+    down: char,
+    point: char,
+    axis: char,
+    label: char,
+    corner: char,
+    rule: char,
+}
+
+const UNICODE: Glyphs = Glyphs {
+    wick: '│',
+    up: '█',
+    down: '░',
+    point: '•',
+    axis: '│',
+    label: '┤',
+    corner: '└',
+    rule: '─',
+};
+
+const ASCII: Glyphs = Glyphs {
+    wick: '|',
+    up: '#',
+    down: '=',
+    point: '*',
+    axis: '|',
+    label: '+',
+    corner: '+',
+    rule: '-',
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tone {
+    Plain,
+    Up,
+    Down,
+    Indicator,
+}
+
+/// The lowest and highest price drawn: lows and highs of candles, closes of
+/// lines, and the indicators. A flat range is widened so it has a height.
+fn price_range(bars: &[OhlcvBar], kind: ChartKind, indicators: &[Vec<Option<f64>>]) -> (f64, f64) {
+    let prices = bars.iter().flat_map(|bar| match kind {
+        ChartKind::Candle => [bar.low, bar.high],
+        ChartKind::Line => [bar.close, bar.close],
+    });
+    let (low, high) = prices
+        .chain(indicators.iter().flatten().flatten().copied())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), price| {
+            (low.min(price), high.max(price))
+        });
+    if high <= low {
+        let pad = (low.abs() * 0.001).max(0.01);
+        (low - pad, high + pad)
+    } else {
+        (low, high)
+    }
+}
+
+/// The bars and indicator values that fit in `columns`: the latest ones.
+/// Indicators are computed over every bar, so averages start filled.
+fn visible<'a>(
+    bars: &'a [OhlcvBar],
+    indicators: &[Indicator],
+    columns: usize,
+) -> (&'a [OhlcvBar], Vec<Vec<Option<f64>>>) {
+    let start = bars.len().saturating_sub(columns);
+    let values = indicators
+        .iter()
+        .map(|indicator| indicator.values(bars).split_off(start))
+        .collect();
+    (&bars[start..], values)
+}
+
+/// Volume with a K, M or B suffix
+fn compact(volume: u64) -> String {
+    match volume {
+        v if v >= 1_000_000_000 => format!("{:.1}B", v as f64 / 1e9),
+        v if v >= 1_000_000 => format!("{:.1}M", v as f64 / 1e6),
+        v if v >= 1_000 => format!("{:.1}K", v as f64 / 1e3),
+        v => v.to_string(),
+    }
+}
+
+/// The bars drawn as lines of text, one column per bar, with the latest bars
+/// that fit in the width: a title, a legend of the indicators, the prices
+/// with labels on the left, the volume and the time axis
+pub fn render_text(bars: &[OhlcvBar], options: &ChartOptions) -> Vec<String> {
+    let glyphs = if options.ascii { &ASCII } else { &UNICODE };
+    let columns = options.width.saturating_sub(LABEL_WIDTH + 1).max(1);
+    let (bars, indicators) = visible(bars, &options.indicators, columns);
+    let (Some(first), Some(last)) = (bars.first(), bars.last()) else {
+        return Vec::new();
+    };
+    let rows = options.height.max(2);
+    let (low, high) = price_range(bars, options.kind, &indicators);
+    let row_of = |price: f64| {
+        (((high - price) / (high - low) * (rows - 1) as f64).round() as usize).min(rows - 1)
+    };
+
+    let mut grid = vec![vec![(' ', Tone::Plain); bars.len()]; rows];
+    let mut previous = None;
+    for (column, bar) in bars.iter().enumerate() {
+        match options.kind {
+            ChartKind::Candle => {
+                let (tone, body) = if bar.close >= bar.open {
+                    (Tone::Up, glyphs.up)
+                } else {
+                    (Tone::Down, glyphs.down)
+                };
+                let (top, bottom) = (row_of(bar.high), row_of(bar.low));
+                for row in &mut grid[top.min(bottom)..=top.max(bottom)] {
+                    row[column] = (glyphs.wick, tone);
+                }
+                for row in
+                    &mut grid[row_of(bar.open.max(bar.close))..=row_of(bar.open.min(bar.close))]
+                {
+                    row[column] = (body, tone);
+                }
+            }
+            ChartKind::Line => {
+                let row = row_of(bar.close);
+                // Join the points with the rows between them
+                if let Some(previous) = previous {
+                    for between in grid[row.min(previous)..row.max(previous)]
+                        .iter_mut()
+                        .skip(1)
+                    {
+                        between[column] = (glyphs.wick, Tone::Plain);
+                    }
+                }
+                grid[row][column] = (glyphs.point, Tone::Plain);
+                previous = Some(row);
+            }
+        }
+    }
+    for (values, mark) in indicators.iter().zip(MARKS.iter().cycle()) {
+        for (column, value) in values.iter().enumerate() {
+            if let Some(value) = value {
+                let cell = &mut grid[row_of(*value)][column];
+                if cell.0 == ' ' || cell.0 == glyphs.wick {
+                    *cell = (*mark, Tone::Indicator);
+                }
+            }
+        }
+    }
+
+    let paint = |cells: &[(char, Tone)]| -> String {
+        let mut line = String::new();
+        for (glyph, tone) in cells {
+            let color = match tone {
+                Tone::Up => GREEN,
+                Tone::Down => RED,
+                Tone::Indicator => YELLOW,
+                Tone::Plain => "",
+            };
+            if options.color && !color.is_empty() {
+                line.push_str(color);
+                line.push(*glyph);
+                line.push_str(RESET);
+            } else {
+                line.push(*glyph);
+            }
+        }
+        line.trim_end().to_string()
+    };
+
+    let decimals = if high - low < 1.0 { 4 } else { 2 };
+    let change = (last.close - first.open) / first.open * 100.0;
+    let mut lines = vec![format!(
+        "{} {} to {}: {} bars, last {:.*} ({:+.2}%)",
+        first.symbol,
+        first.timestamp.format("%Y-%m-%d %H:%M"),
+        last.timestamp.format("%Y-%m-%d %H:%M"),
+        bars.len(),
+        decimals,
+        last.close,
+        change
+    )];
+    if !options.indicators.is_empty() {
+        let legend: Vec<String> = options
+            .indicators
+            .iter()
+            .zip(MARKS.iter().cycle())
+            .map(|(indicator, mark)| format!("{} {}", mark, indicator))
+            .collect();
+        lines.push(legend.join("  "));
+    }
+
+    for (index, cells) in grid.iter().enumerate() {
+        let label = if index % 4 == 0 || index == rows - 1 {
+            let price = high - index as f64 * (high - low) / (rows - 1) as f64;
+            format!(
+                "{:>width$.decimals$} {}",
+                price,
+                glyphs.label,
+                width = LABEL_WIDTH - 1
+            )
+        } else {
+            format!("{:>width$}{}", "", glyphs.axis, width = LABEL_WIDTH)
+        };
+        lines.push(format!("{}{}", label, paint(cells)).trim_end().to_string());
+    }
+
+    if options.volume {
+        let volume_rows = (rows / 4).max(2);
+        let most = bars.iter().map(|bar| bar.volume).max().unwrap_or(0).max(1);
+        for index in 0..volume_rows {
+            let cells: Vec<(char, Tone)> = bars
+                .iter()
+                .map(|bar| {
+                    let height =
+                        (bar.volume as f64 / most as f64 * volume_rows as f64).ceil() as usize;
+                    if height >= volume_rows - index {
+                        let tone = if bar.close >= bar.open {
+                            Tone::Up
+                        } else {
+                            Tone::Down
+                        };
+                        (glyphs.up, tone)
+                    } else {
+                        (' ', Tone::Plain)
+                    }
+                })
+                .collect();
+            let label = if index == 0 {
+                format!(
+                    "{:>width$} {}",
+                    compact(most),
+                    glyphs.label,
+                    width = LABEL_WIDTH - 1
+                )
+            } else {
+                format!("{:>width$}{}", "", glyphs.axis, width = LABEL_WIDTH)
+            };
+            lines.push(format!("{}{}", label, paint(&cells)).trim_end().to_string());
+        }
+    }
+
+    lines.push(format!(
+        "{:>width$}{}{}",
+        "",
+        glyphs.corner,
+        glyphs.rule.to_string().repeat(bars.len()),
+        width = LABEL_WIDTH
+    ));
+    let start = first.timestamp.format("%Y-%m-%d %H:%M").to_string();
+    let end = last.timestamp.format("%Y-%m-%d %H:%M").to_string();
+    let mut times = format!("{:>width$}{}", "", start, width = LABEL_WIDTH + 1);
+    if bars.len() >= start.len() + end.len() + 2 {
+        let gap = bars.len() - start.len() - end.len();
+        times.push_str(&format!("{:>width$}{}", "", end, width = gap));
+    }
+    lines.push(times);
+    lines
+}
+
+#[cfg(feature = "charts")]
+pub use image::write_png;
+
+#[cfg(feature = "charts")]
+mod image {
+    use super::{compact, price_range, visible, ChartKind, ChartOptions};
+    use crate::resample::OhlcvBar;
+    use crate::Result;
+    use chrono::NaiveTime;
+    use plotters::prelude::*;
+    use std::path::Path;
+
+    const GRID: RGBColor = RGBColor(225, 225, 225);
+    const UP: RGBColor = RGBColor(38, 166, 91);
+    const DOWN: RGBColor = RGBColor(214, 48, 49);
+    const UP_VOLUME: RGBColor = RGBColor(160, 215, 180);
+    const DOWN_VOLUME: RGBColor = RGBColor(240, 165, 165);
+    const LINE: RGBColor = RGBColor(33, 102, 172);
+    /// Colors of the indicators, in the order they are given
+    const PALETTE: [RGBColor; 4] = [
+        RGBColor(255, 127, 14),
+        RGBColor(148, 103, 189),
+        RGBColor(140, 86, 75),
+        RGBColor(227, 119, 194),
+    ];
+
+    const FONT: &str = "sans-serif";
+    /// Space around the panels, in pixels
+    const MARGIN: u32 = 10;
+    /// Width of the price and volume labels left of the panels
+    const LABEL_AREA: u32 = 70;
+    /// Height of the time labels under the panels
+    const TIME_AREA: u32 = 30;
+
+    /// The runs of values without a gap, as points
+    fn runs(values: &[Option<f64>]) -> Vec<Vec<(f64, f64)>> {
+        let mut runs = Vec::new();
+        let mut run = Vec::new();
+        for (index, value) in values.iter().enumerate() {
+            match value {
+                Some(value) => run.push((index as f64, *value)),
+                None if !run.is_empty() => runs.push(std::mem::take(&mut run)),
+                None => {}
+            }
+        }
+        if !run.is_empty() {
+            runs.push(run);
+        }
+        runs
+    }
+
+    /// Draw the bars as a PNG image at `path`, with as many of the latest
+    /// bars as there are pixels across: a title with the symbol and the
+    /// time range, price labels on the left, the volume panel with its own
+    /// labels, the times along the bottom and a legend of the lines
+    pub fn write_png(bars: &[OhlcvBar], options: &ChartOptions, path: &Path) -> Result<()> {
+        let width = options.width.max(4 * LABEL_AREA as usize) as u32;
+        let height = options.height.max(4 * TIME_AREA as usize) as u32;
+        let image = BitMapBackend::new(path, (width, height)).into_drawing_area();
+        image.fill(&WHITE)?;
+
+        let columns = (width - LABEL_AREA - 2 * MARGIN) as usize;
+        let (bars, indicators) = visible(bars, &options.indicators, columns);
+        let (Some(first), Some(last)) = (bars.first(), bars.last()) else {
+            image.present()?;
+            return Ok(());
+        };
+        let title = format!(
+            "{}  {} to {}",
+            first.symbol,
+            first.timestamp.format("%Y-%m-%d %H:%M"),
+            last.timestamp.format("%Y-%m-%d %H:%M")
+        );
+        let area = image.titled(&title, (FONT, 18))?;
+        let (price_area, volume_area) = if options.volume {
+            let (prices, volumes) = area.split_vertically(area.dim_in_pixel().1 * 3 / 4);
+            (prices, Some(volumes))
+        } else {
+            (area, None)
+        };
+
+        // Daily bars are labelled by date, a single day's by time
+        let time_format = if bars.iter().all(|bar| bar.timestamp.time() == NaiveTime::MIN) {
+            "%Y-%m-%d"
+        } else if first.timestamp.date_naive() == last.timestamp.date_naive() {
+            "%H:%M"
+        } else {
+            "%m-%d %H:%M"
+        };
+        let time_label = |x: &f64| {
+            let index = x.round();
+            if index >= 0.0 && (index as usize) < bars.len() {
+                bars[index as usize].timestamp.format(time_format).to_string()
+            } else {
+                String::new()
+            }
+        };
+        let x_range = -0.5..bars.len() as f64 - 0.5;
+
+        let (low, high) = price_range(bars, options.kind, &indicators);
+        let pad = (high - low) * 0.05;
+        let mut prices = ChartBuilder::on(&price_area)
+            .margin(MARGIN)
+            .y_label_area_size(LABEL_AREA)
+            .x_label_area_size(if volume_area.is_some() { 0 } else { TIME_AREA })
+            .build_cartesian_2d(x_range.clone(), low - pad..high + pad)?;
+        prices
+            .configure_mesh()
+            .light_line_style(WHITE)
+            .bold_line_style(GRID)
+            .x_labels(6)
+            .y_labels(8)
+            .x_label_formatter(&time_label)
+            .y_label_formatter(&|price| format!("{:.2}", price))
+            .label_style((FONT, 12))
+            .draw()?;
+
+        match options.kind {
+            ChartKind::Candle => {
+                let across = prices.plotting_area().dim_in_pixel().0 as f64;
+                let body = (across / bars.len() as f64 * 0.7).max(1.0) as u32;
+                prices.draw_series(bars.iter().enumerate().map(|(index, bar)| {
+                    CandleStick::new(
+                        index as f64,
+                        bar.open,
+                        bar.high,
+                        bar.low,
+                        bar.close,
+                        UP.filled(),
+                        DOWN.filled(),
+                        body,
+                    )
+                }))?;
+            }
+            ChartKind::Line => {
+                let closes = bars
+                    .iter()
+                    .enumerate()
+                    .map(|(index, bar)| (index as f64, bar.close));
+                prices
+                    .draw_series(LineSeries::new(closes, LINE.stroke_width(2)))?
+                    .label("Close")
+                    .legend(|(x, y)| {
+                        PathElement::new(vec![(x, y), (x + 20, y)], LINE.stroke_width(2))
+                    });
+            }
+        }
+        let mut labeled = options.kind == ChartKind::Line;
+        for ((indicator, values), color) in options
+            .indicators
+            .iter()
+            .zip(&indicators)
+            .zip(PALETTE.into_iter().cycle())
+        {
+            for (run, points) in runs(values).into_iter().enumerate() {
+                let series = prices.draw_series(LineSeries::new(points, color.stroke_width(2)))?;
+                if run == 0 {
+                    series
+                        .label(indicator.to_string())
+                        .legend(move |(x, y)| {
+                            PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+                        });
+                    labeled = true;
+                }
+            }
+        }
+        if labeled {
+            prices
+                .configure_series_labels()
+                .position(SeriesLabelPosition::UpperLeft)
+                .background_style(WHITE.mix(0.8))
+                .border_style(GRID)
+                .label_font((FONT, 12))
+                .draw()?;
+        }
+
+        if let Some(volume_area) = volume_area {
+            let most = bars.iter().map(|bar| bar.volume).max().unwrap_or(0).max(1);
+            let mut volumes = ChartBuilder::on(&volume_area)
+                .margin(MARGIN)
+                .y_label_area_size(LABEL_AREA)
+                .x_label_area_size(TIME_AREA)
+                .build_cartesian_2d(x_range, 0.0..most as f64 * 1.1)?;
+            volumes
+                .configure_mesh()
+                .light_line_style(WHITE)
+                .bold_line_style(GRID)
+                .x_labels(6)
+                .y_labels(3)
+                .x_label_formatter(&time_label)
+                .y_label_formatter(&|volume| compact(*volume as u64))
+                .label_style((FONT, 12))
+                .draw()?;
+            volumes.draw_series(bars.iter().enumerate().map(|(index, bar)| {
+                let color = if bar.close >= bar.open { UP_VOLUME } else { DOWN_VOLUME };
+                let x = index as f64;
+                Rectangle::new([(x - 0.35, 0.0), (x + 0.35, bar.volume as f64)], color.filled())
+            }))?;
+        }
+        image.present()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Progress of a historical download, saved next to its output so an
+//! interrupted run can continue where it stopped.
+
+use crate::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// How far a download has got with each symbol
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Checkpoint {
+    /// What was downloaded (data kind, symbols, range, timeframe and feed), so
+    /// a checkpoint is not applied to a different download
+    pub download: String,
+    pub symbols: BTreeMap<String, SymbolProgress>,
+}
+
+/// The pages of one symbol written so far
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SymbolProgress {
+    /// Token of the first page not written yet
+    pub page_token: Option<String>,
+    /// Timestamp of the last record written
+    pub last_timestamp: Option<String>,
+    pub records: u64,
+    /// Every page is written
+    pub done: bool,
+}
+
+impl Checkpoint {
+    pub fn new(download: impl Into<String>) -> Self {
+        Self {
+            download: download.into(),
+            symbols: BTreeMap::new(),
+        }
+    }
+
+    /// `data.csv.gz` is checkpointed in `data.csv.gz.checkpoint.json`
+    pub fn path_for(output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".checkpoint.json");
+        PathBuf::from(path)
+    }
+
+    /// The checkpoint at `path`, or `None` if there is none
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write to a temporary file and rename it over `path`, so a crash
+    /// leaves either the old or the new checkpoint
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn is_done(&self, symbol: &str) -> bool {
+        self.symbols.get(symbol).is_some_and(|progress| progress.done)
+    }
+
+    /// The page `symbol` continues from; `None` starts it from the first page
+    pub fn resume_token(&self, symbol: &str) -> Option<String> {
+        self.symbols
+            .get(symbol)
+            .filter(|progress| !progress.done)
+            .and_then(|progress| progress.page_token.clone())
+    }
+
+    /// Record a written page of `records` records; `next_page_token` is `None`
+    /// after the last page
+    pub fn record_page(
+        &mut self,
+        symbol: &str,
+        next_page_token: Option<&str>,
+        last_timestamp: Option<&str>,
+        records: u64,
+    ) {
+        let progress = self.symbols.entry(symbol.to_string()).or_default();
+        progress.page_token = next_page_token.map(str::to_string);
+        if let Some(timestamp) = last_timestamp {
+            progress.last_timestamp = Some(timestamp.to_string());
+        }
+        progress.records += records;
+        progress.done = next_page_token.is_none();
+    }
+}
@@ -0,0 +1,78 @@
+use super::analyze::read_capture;
+use super::{GlobalArgs, OutputArgs};
+use crate::resample::{OhlcvBar, Resampler, Session, Timeframe, BARS_CSV_HEADER};
+use crate::{Compression, DataFormat, OutputMode};
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Flags of `trade aggregate`
+#[derive(clap::Args, Debug)]
+pub struct AggregateArgs {
+    /// Capture file with trades, as written by `stream`; gzip and zstd files are decompressed automatically
+    #[arg(short, long)]
+    input: PathBuf,
+    
+    /// Input format (json, csv)
+    #[arg(long, value_enum, default_value_t = DataFormat::Json)]
+    input_format: DataFormat,
+    
+    /// Timeframe of the bars: <N>Sec, <N>Min, <N>Hour, <N>Day (trading days), <N>Week (from Monday) or <N>Month
+    #[arg(short, long)]
+    timeframe: String,
+    
+    /// Trades to keep, and where intraday bars are counted from; all keeps every trade in bars aligned to the clock, like downloaded bars
+    #[arg(long, value_enum, default_value_t = Session::All)]
+    session: Session,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Build --timeframe bars from the trades of --input and write them out
+pub fn run(args: AggregateArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let timeframe = Timeframe::parse(&args.timeframe)?;
+    info!(input = %args.input.display(), timeframe = %timeframe, session = ?args.session, format = ?args.out.format, output = ?args.out.output, "Aggregating trades");
+    
+    let output_mode = match &args.out.output {
+        Some(path) => OutputMode::create_compressed_file_mode(path, args.out.format.clone(), args.out.append, Compression::from_extension(path))?,
+        None => OutputMode::create_console_mode(args.out.format.clone()),
+    };
+    if matches!(args.out.format, DataFormat::Csv) && !args.out.append {
+        output_mode.writeln(BARS_CSV_HEADER)?;
+    }
+    
+    let mut resampler = Resampler::new(timeframe, args.session);
+    let mut trades = 0;
+    let mut late = 0;
+    let mut written = 0;
+    read_capture(&args.input, &args.input_format, |data| {
+        let Some(trade) = OhlcvBar::from_trade(&data) else {
+            return Ok(());
+        };
+        // A trade that arrives after its bar was written cannot be added
+        if resampler.current_start(&trade.symbol).is_some_and(|start| trade.timestamp < start) {
+            late += 1;
+            return Ok(());
+        }
+        trades += 1;
+        if let Some(bar) = resampler.push(trade)? {
+            output_mode.writeln(&bar.format(&args.out.format)?)?;
+            written += 1;
+        }
+        Ok(())
+    })?;
+    for bar in resampler.finish() {
+        output_mode.writeln(&bar.format(&args.out.format)?)?;
+        written += 1;
+    }
+    output_mode.close()?;
+    if late > 0 {
+        warn!(late, "Skipped trades that arrived after their bar was complete");
+    }
+    info!(trades, written, "Aggregation complete");
+    
+    Ok(())
+}
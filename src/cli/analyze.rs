@@ -0,0 +1,564 @@
+use super::GlobalArgs;
+use crate::activity::{ActivitySeries, ACTIVITY_CSV_HEADER};
+use crate::capture::{self, csv_message, json_message, CaptureFormat};
+use crate::conflation::parse_duration;
+use crate::dates::{DateResolver, RangeEnd};
+use crate::filter::{MessageSelection, TimeBound};
+use crate::follow::FollowReader;
+use crate::spreads::{SpreadStats, SpreadTracker};
+use crate::trade_analytics::{TradeAnalytics, TradeReport};
+use crate::xlsx::{Cell, XlsxWriter};
+use crate::{DataFormat, OutputMode, StreamingData};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Flags of `trade analyze` and the data-analyzer binary
+#[derive(clap::Args, Debug)]
+pub struct AnalyzeArgs {
+    /// Input file to analyze; gzip and zstd files are decompressed automatically
+    #[arg(short, long)]
+    input: PathBuf,
+    
+    /// Input format
+    #[arg(short, long, value_enum, default_value_t = DataFormat::Json)]
+    format: DataFormat,
+    
+    /// Only analyze messages of these symbols; repeat or separate with commas
+    #[arg(long = "symbol", value_name = "SYMBOL", value_delimiter = ',')]
+    symbols: Vec<String>,
+    
+    /// Only analyze messages of these types, e.g. quotes or q; repeat or separate with commas
+    #[arg(long = "type", value_name = "TYPE", value_delimiter = ',')]
+    types: Vec<String>,
+    
+    /// Only analyze messages from this time on: HH:MM[:SS] in New York on every day, or a date or time as for the historical --start, e.g. 2024-01-15T09:30
+    #[arg(long, value_name = "TIME")]
+    from: Option<String>,
+    
+    /// Only analyze messages before this time: HH:MM[:SS] in New York on every day, or a date or time as for the historical --end
+    #[arg(long, value_name = "TIME")]
+    to: Option<String>,
+    
+    /// Also write the summary to an Excel workbook: a Summary sheet and a Symbols sheet with each symbol's counts and first and last message times (New York)
+    #[arg(long, value_name = "PATH")]
+    xlsx: Option<PathBuf>,
+    
+    /// Also count messages, trades and volume per interval of this length, e.g. 1m, 5m or 1h, by event time; the series is printed after the summary
+    #[arg(long, value_name = "DURATION")]
+    bucket: Option<String>,
+    
+    /// Write the --bucket series to this file instead
+    #[arg(long, value_name = "PATH", requires = "bucket")]
+    series: Option<PathBuf>,
+    
+    /// Format of the --bucket series
+    #[arg(long, value_enum, default_value_t = DataFormat::Csv, requires = "bucket")]
+    series_format: DataFormat,
+    
+    /// Number of largest prints to list per symbol
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    top: usize,
+    
+    /// Also write the trade analytics of every symbol to this JSON file: VWAP, volume, trade sizes, largest prints and buy/sell volume
+    #[arg(long, value_name = "PATH")]
+    trade_report: Option<PathBuf>,
+    
+    /// Keep reading the input as the streaming client appends to it, like tail -f, and print the summary every --interval until interrupted
+    #[arg(long, conflicts_with_all = ["xlsx", "series", "trade_report"])]
+    follow: bool,
+    
+    /// Seconds between summaries with --follow
+    #[arg(long, value_name = "SECONDS", default_value_t = 10, requires = "follow", value_parser = clap::value_parser!(u64).range(1..))]
+    interval: u64,
+    
+    /// Format of the summary: plain text, one JSON object with every metric, or CSV with a row per symbol [default: plain; with --report, csv for .csv files and json otherwise]
+    #[arg(long, value_enum)]
+    report_format: Option<DataFormat>,
+    
+    /// Write the --report-format summary to this file; the text summary is still printed
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+}
+
+/// CSV header of the summary with --report-format csv; the first row, without a symbol, counts every message
+const REPORT_CSV_HEADER: &str = "symbol,messages,trades,quotes,bars,first,last,trade_volume,vwap,buy_percent,sell_percent,average_spread,median_spread,time_weighted_spread,locked_percent,crossed_percent";
+
+/// The summary as --report-format json writes it
+#[derive(serde::Serialize)]
+struct Report<'a> {
+    input: &'a std::path::Path,
+    messages: u64,
+    trades: u64,
+    quotes: u64,
+    bars: u64,
+    order_updates: u64,
+    success: u64,
+    subscription: u64,
+    errors: u64,
+    symbols: Vec<SymbolReport<'a>>,
+    trade_analytics: Vec<TradeReport>,
+    spreads: Vec<SpreadStats>,
+    /// With --bucket
+    intervals: Option<usize>,
+    busiest: Option<DateTime<Utc>>,
+    busiest_messages_per_sec: Option<f64>,
+}
+
+#[derive(serde::Serialize)]
+struct SymbolReport<'a> {
+    symbol: &'a str,
+    #[serde(flatten)]
+    stats: &'a SymbolStats,
+}
+
+#[derive(Debug, Default)]
+struct DataStats {
+    total_messages: u64,
+    trade_count: u64,
+    quote_count: u64,
+    bar_count: u64,
+    trade_update_count: u64,
+    success_count: u64,
+    subscription_count: u64,
+    error_count: u64,
+    symbol_counts: HashMap<String, SymbolStats>,
+    /// Activity per interval, with --bucket
+    series: Option<ActivitySeries>,
+    /// Bid/ask spreads of the quotes
+    spreads: SpreadTracker,
+    /// VWAP, sizes and sides of the trades
+    trades: TradeAnalytics,
+}
+
+/// Messages of one symbol
+#[derive(Debug, Default, serde::Serialize)]
+struct SymbolStats {
+    messages: u64,
+    trades: u64,
+    quotes: u64,
+    bars: u64,
+    first: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+}
+
+impl DataStats {
+    fn add_message(&mut self, data: &StreamingData) {
+        self.total_messages += 1;
+        if let Some(series) = &mut self.series {
+            series.add(data);
+        }
+        self.spreads.add(data);
+        self.trades.add(data);
+        
+        match data.message_type.as_str() {
+            "t" => self.trade_count += 1,
+            "q" => self.quote_count += 1,
+            "b" => self.bar_count += 1,
+            "trade_update" => self.trade_update_count += 1,
+            "success" => self.success_count += 1,
+            "subscription" => self.subscription_count += 1,
+            "error" => self.error_count += 1,
+            _ => {}
+        }
+        
+        if let Some(symbol) = &data.symbol {
+            let stats = self.symbol_counts.entry(symbol.clone()).or_default();
+            stats.messages += 1;
+            match data.message_type.as_str() {
+                "t" => stats.trades += 1,
+                "q" => stats.quotes += 1,
+                "b" => stats.bars += 1,
+                _ => {}
+            }
+            let time = data.event_time.unwrap_or(data.received_time);
+            stats.first = Some(stats.first.map_or(time, |first| first.min(time)));
+            stats.last = Some(stats.last.map_or(time, |last| last.max(time)));
+        }
+    }
+    
+    fn print_summary(&self) {
+        println!("📊 Data Analysis Summary");
+        println!("========================");
+        println!("Total messages: {}", self.total_messages);
+        println!("  Trades: {}", self.trade_count);
+        println!("  Quotes: {}", self.quote_count);
+        println!("  Bars: {}", self.bar_count);
+        println!("  Order updates: {}", self.trade_update_count);
+        println!("  Success: {}", self.success_count);
+        println!("  Subscription: {}", self.subscription_count);
+        println!("  Errors: {}", self.error_count);
+        
+        if !self.symbol_counts.is_empty() {
+            println!("\nSymbol breakdown:");
+            let symbols = self.symbols_by_count();
+            
+            for (symbol, stats) in symbols.iter().take(10) {
+                println!("  {}: {}", symbol, stats.messages);
+            }
+            
+            if symbols.len() > 10 {
+                println!("  ... and {} more", symbols.len() - 10);
+            }
+        }
+        
+        if !self.trades.is_empty() {
+            println!("\nTrade analytics:");
+            for report in self.trades.reports() {
+                println!("  {}", report);
+                for print in &report.largest {
+                    println!("    {} @ {:.4} at {}", print.size, print.price, print.timestamp.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true));
+                }
+            }
+        }
+        
+        if !self.spreads.is_empty() {
+            println!("\nQuote spreads:");
+            for stats in self.spreads.stats() {
+                println!("  {}", stats);
+                if stats.one_sided > 0 {
+                    println!("    {} one-sided quotes without a spread", stats.one_sided);
+                }
+            }
+        }
+        
+        if let Some(series) = &self.series {
+            println!("\nIntervals: {}", series.intervals());
+            if let Some((bucket, rate)) = series.peak() {
+                println!("  Busiest: {} at {:.2} messages/sec", bucket.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true), rate);
+            }
+        }
+    }
+    
+    /// Write the --bucket series to `output`
+    fn write_series(&self, output: &OutputMode, format: &DataFormat) -> Result<()> {
+        let Some(series) = &self.series else {
+            return Ok(());
+        };
+        if matches!(format, DataFormat::Csv) {
+            output.writeln(ACTIVITY_CSV_HEADER)?;
+        }
+        for row in series.rows() {
+            output.writeln(&row.format(format)?)?;
+        }
+        output.close()?;
+        Ok(())
+    }
+    
+    /// Write the summary to `output` as one JSON object, or as CSV with a
+    /// row per symbol
+    fn write_report(&self, output: &OutputMode, format: &DataFormat, input: &std::path::Path) -> Result<()> {
+        let symbols = self.symbols_by_count();
+        let trades = self.trades.reports();
+        let spreads = self.spreads.stats();
+        let peak = self.series.as_ref().and_then(ActivitySeries::peak);
+        match format {
+            DataFormat::Json => {
+                let report = Report {
+                    input,
+                    messages: self.total_messages,
+                    trades: self.trade_count,
+                    quotes: self.quote_count,
+                    bars: self.bar_count,
+                    order_updates: self.trade_update_count,
+                    success: self.success_count,
+                    subscription: self.subscription_count,
+                    errors: self.error_count,
+                    symbols: symbols.iter().map(|(symbol, stats)| SymbolReport { symbol, stats }).collect(),
+                    trade_analytics: trades,
+                    spreads,
+                    intervals: self.series.as_ref().map(ActivitySeries::intervals),
+                    busiest: peak.map(|(bucket, _)| bucket),
+                    busiest_messages_per_sec: peak.map(|(_, rate)| rate),
+                };
+                output.writeln(&serde_json::to_string(&report)?)?;
+            }
+            DataFormat::Csv => {
+                output.writeln(REPORT_CSV_HEADER)?;
+                output.writeln(&format!(",{},{},{},{},,,,,,,,,,,", self.total_messages, self.trade_count, self.quote_count, self.bar_count))?;
+                let time = |time: Option<DateTime<Utc>>| time.map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)).unwrap_or_default();
+                for (symbol, stats) in symbols {
+                    let trade = trades.iter().find(|report| &report.symbol == symbol);
+                    let spread = spreads.iter().find(|stats| &stats.symbol == symbol);
+                    output.writeln(&format!(
+                        "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                        symbol,
+                        stats.messages,
+                        stats.trades,
+                        stats.quotes,
+                        stats.bars,
+                        time(stats.first),
+                        time(stats.last),
+                        trade.map(|trade| trade.volume.to_string()).unwrap_or_default(),
+                        trade.map(|trade| format!("{:.4}", trade.vwap)).unwrap_or_default(),
+                        trade.map(|trade| format!("{:.2}", trade.buy_percent())).unwrap_or_default(),
+                        trade.map(|trade| format!("{:.2}", trade.sell_percent())).unwrap_or_default(),
+                        spread.map(|spread| format!("{:.4}", spread.average)).unwrap_or_default(),
+                        spread.map(|spread| format!("{:.4}", spread.median)).unwrap_or_default(),
+                        spread.and_then(|spread| spread.time_weighted).map(|spread| format!("{:.4}", spread)).unwrap_or_default(),
+                        spread.map(|spread| format!("{:.2}", spread.locked_percent())).unwrap_or_default(),
+                        spread.map(|spread| format!("{:.2}", spread.crossed_percent())).unwrap_or_default(),
+                    ))?;
+                }
+            }
+            DataFormat::Plain => unreachable!("the text summary is printed"),
+        }
+        output.close()?;
+        Ok(())
+    }
+    
+    /// Symbols with the most messages first
+    fn symbols_by_count(&self) -> Vec<(&String, &SymbolStats)> {
+        let mut symbols: Vec<_> = self.symbol_counts.iter().collect();
+        symbols.sort_by(|a, b| b.1.messages.cmp(&a.1.messages).then(a.0.cmp(b.0)));
+        symbols
+    }
+    
+    /// Write the summary and the counts of every symbol to a workbook
+    fn write_xlsx(&self, path: &std::path::Path) -> Result<()> {
+        let mut workbook = XlsxWriter::create(path)?;
+        workbook.start_sheet("Summary", &["messages", "count"])?;
+        for (name, count) in [
+            ("Total", self.total_messages),
+            ("Trades", self.trade_count),
+            ("Quotes", self.quote_count),
+            ("Bars", self.bar_count),
+            ("Order updates", self.trade_update_count),
+            ("Success", self.success_count),
+            ("Subscription", self.subscription_count),
+            ("Errors", self.error_count),
+        ] {
+            workbook.write_row(&[name.into(), count.into()])?;
+        }
+        
+        workbook.start_sheet("Symbols", &["symbol", "messages", "trades", "quotes", "bars", "first", "last"])?;
+        for (symbol, stats) in self.symbols_by_count() {
+            let time = |time: Option<DateTime<Utc>>| time.map_or_else(|| Cell::from(""), Cell::Time);
+            workbook.write_row(&[
+                symbol.as_str().into(),
+                stats.messages.into(),
+                stats.trades.into(),
+                stats.quotes.into(),
+                stats.bars.into(),
+                time(stats.first),
+                time(stats.last),
+            ])?;
+        }
+        
+        if !self.trades.is_empty() {
+            workbook.start_sheet("Trades", &["symbol", "trades", "volume", "vwap", "buy_percent", "sell_percent"])?;
+            for report in self.trades.reports() {
+                workbook.write_row(&[
+                    report.symbol.as_str().into(),
+                    report.trades.into(),
+                    report.volume.into(),
+                    report.vwap.into(),
+                    report.buy_percent().into(),
+                    report.sell_percent().into(),
+                ])?;
+            }
+        }
+        
+        if !self.spreads.is_empty() {
+            workbook.start_sheet("Spreads", &["symbol", "quotes", "average", "median", "max", "average_bps", "time_weighted", "locked_percent", "crossed_percent", "one_sided"])?;
+            for stats in self.spreads.stats() {
+                workbook.write_row(&[
+                    stats.symbol.as_str().into(),
+                    stats.quotes.into(),
+                    stats.average.into(),
+                    stats.median.into(),
+                    stats.max.into(),
+                    stats.average_bps.into(),
+                    stats.time_weighted.map_or_else(|| Cell::from(""), Cell::from),
+                    stats.locked_percent().into(),
+                    stats.crossed_percent().into(),
+                    stats.one_sided.into(),
+                ])?;
+            }
+        }
+        workbook.finish()?;
+        Ok(())
+    }
+}
+
+/// Count the messages of a captured file and print a summary
+pub fn run(args: AnalyzeArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    let report_format = args.report_format.clone().unwrap_or_else(|| match &args.report {
+        Some(path) if path.to_string_lossy().contains(".csv") => DataFormat::Csv,
+        Some(_) => DataFormat::Json,
+        None => DataFormat::Plain,
+    });
+    if args.report.is_some() && matches!(report_format, DataFormat::Plain) {
+        anyhow::bail!("--report needs --report-format json or csv");
+    }
+    
+    // A structured summary on the console is the only output there
+    let console_report = args.report.is_none() && !matches!(report_format, DataFormat::Plain);
+    let note = |text: String| if console_report { eprintln!("{}", text) } else { println!("{}", text) };
+    
+    note(format!("🔍 Analyzing data from: {}", args.input.display()));
+    note(format!("Format: {:?}\n", args.format));
+    
+    let resolver = DateResolver::new(Utc::now());
+    let mut selection = MessageSelection::new(&args.symbols, &args.types);
+    selection.from = args.from.as_deref().map(|from| TimeBound::parse(from, RangeEnd::Start, &resolver)).transpose()?;
+    selection.to = args.to.as_deref().map(|to| TimeBound::parse(to, RangeEnd::End, &resolver)).transpose()?;
+    if !selection.is_empty() {
+        note(format!("Selecting: {}\n", describe_selection(&args)));
+    }
+    
+    let mut stats = DataStats {
+        trades: TradeAnalytics::new(args.top),
+        ..Default::default()
+    };
+    if let Some(bucket) = &args.bucket {
+        stats.series = Some(ActivitySeries::new(parse_duration(bucket)?)?);
+    }
+    
+    if matches!(args.format, DataFormat::Plain) {
+        println!("⚠️  Plain text format analysis is not supported yet.");
+        println!("Please convert to JSON or CSV format first.");
+        return Ok(());
+    }
+    if args.follow {
+        let interval = Duration::from_secs(args.interval);
+        let mut recent = 0;
+        return follow_capture(&args.input, &args.format, interval, |event| {
+            match event {
+                FollowEvent::Message(data) if selection.matches(&data) => {
+                    stats.add_message(&data);
+                    recent += 1;
+                }
+                FollowEvent::Message(_) => {}
+                FollowEvent::Tick => {
+                    if console_report {
+                        stats.write_report(&OutputMode::create_console_mode(report_format.clone()), &report_format, &args.input)?;
+                    } else {
+                        println!("\n── {} ── {} new messages in the last {}s ({:.1}/s)\n", Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true), recent, args.interval, recent as f64 / interval.as_secs_f64());
+                        stats.print_summary();
+                    }
+                    if let Some(path) = &args.report {
+                        stats.write_report(&OutputMode::create_file_mode(path, report_format.clone(), false)?, &report_format, &args.input)?;
+                    }
+                    recent = 0;
+                }
+            }
+            Ok(())
+        });
+    }
+    
+    let mut skipped = 0;
+    read_capture(&args.input, &args.format, |data| {
+        if selection.matches(&data) {
+            stats.add_message(&data);
+        } else {
+            skipped += 1;
+        }
+        Ok(())
+    })?;
+    if skipped > 0 {
+        note(format!("Skipped {} messages outside the selection\n", skipped));
+    }
+    
+    if console_report {
+        stats.write_report(&OutputMode::create_console_mode(report_format.clone()), &report_format, &args.input)?;
+    } else {
+        stats.print_summary();
+    }
+    if let Some(path) = &args.report {
+        stats.write_report(&OutputMode::create_file_mode(path, report_format.clone(), false)?, &report_format, &args.input)?;
+        note(format!("\nWrote report: {}", path.display()));
+    }
+    match &args.series {
+        Some(path) => {
+            stats.write_series(&OutputMode::create_file_mode(path, args.series_format.clone(), false)?, &args.series_format)?;
+            note(format!("\nWrote series: {}", path.display()));
+        }
+        None if stats.series.is_some() => {
+            println!();
+            stats.write_series(&OutputMode::create_console_mode(args.series_format.clone()), &args.series_format)?;
+        }
+        None => {}
+    }
+    if let Some(path) = &args.trade_report {
+        std::fs::write(path, serde_json::to_string_pretty(&stats.trades.reports())?)?;
+        note(format!("\nWrote trade report: {}", path.display()));
+    }
+    if let Some(path) = &args.xlsx {
+        stats.write_xlsx(path)?;
+        note(format!("\nWrote workbook: {}", path.display()));
+    }
+    Ok(())
+}
+
+/// The --symbol, --type, --from and --to flags as given
+fn describe_selection(args: &AnalyzeArgs) -> String {
+    let mut parts = Vec::new();
+    if !args.symbols.is_empty() {
+        parts.push(format!("symbols {}", args.symbols.join(",")));
+    }
+    if !args.types.is_empty() {
+        parts.push(format!("types {}", args.types.join(",")));
+    }
+    if let Some(from) = &args.from {
+        parts.push(format!("from {}", from));
+    }
+    if let Some(to) = &args.to {
+        parts.push(format!("to {}", to));
+    }
+    parts.join(", ")
+}
+
+/// Call `on_message` with each message of a JSON or CSV capture file, in
+/// file order; lines that are not messages are skipped
+pub(crate) fn read_capture(input: &std::path::Path, format: &DataFormat, on_message: impl FnMut(StreamingData) -> crate::Result<()>) -> Result<()> {
+    let format = match format {
+        DataFormat::Json => CaptureFormat::Json,
+        DataFormat::Csv => CaptureFormat::Csv,
+        DataFormat::Plain => anyhow::bail!("Plain text captures cannot be read; capture in JSON or CSV format"),
+    };
+    Ok(capture::read_capture(input, format, on_message)?)
+}
+
+/// What `follow_capture` reports
+enum FollowEvent {
+    Message(StreamingData),
+    /// Another interval has passed
+    Tick,
+}
+
+/// Follow a capture file that is still being written, passing each message
+/// on as its line is completed and a tick every `interval`, until
+/// interrupted
+fn follow_capture(input: &std::path::Path, format: &DataFormat, interval: Duration, mut on_event: impl FnMut(FollowEvent) -> Result<()>) -> Result<()> {
+    let mut reader = FollowReader::open(input)?;
+    let mut last_tick = Instant::now();
+    loop {
+        match reader.next_line()? {
+            Some(line) => {
+                let data = match format {
+                    DataFormat::Json => json_message(&line),
+                    _ => {
+                        let mut record = csv::StringRecord::new();
+                        let mut csv_reader = csv::ReaderBuilder::new().has_headers(false).from_reader(line.as_bytes());
+                        if csv_reader.read_record(&mut record)? { csv_message(&record) } else { None }
+                    }
+                };
+                if let Some(data) = data {
+                    on_event(FollowEvent::Message(data))?;
+                }
+            }
+            None => std::thread::sleep(FOLLOW_POLL),
+        }
+        if last_tick.elapsed() >= interval {
+            on_event(FollowEvent::Tick)?;
+            last_tick = Instant::now();
+        }
+    }
+}
+
+/// How often --follow checks the file for new lines
+const FOLLOW_POLL: Duration = Duration::from_millis(200);
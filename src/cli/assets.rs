@@ -0,0 +1,76 @@
+use super::{GlobalArgs, OutputArgs};
+use crate::assets::{AssetQuery, AssetsClient, ASSETS_CSV_HEADER, ASSET_CLASSES};
+use crate::{DataFormat, OutputMode};
+use anyhow::Result;
+use tracing::{info, warn};
+
+/// Flags of `trade assets`
+#[derive(clap::Args, Debug)]
+pub struct AssetsArgs {
+    /// Only assets with this status
+    #[arg(long, value_parser = ["active", "inactive", "all"], default_value = "active")]
+    status: String,
+    
+    /// Only assets of this class
+    #[arg(long, value_parser = ASSET_CLASSES)]
+    class: Option<String>,
+    
+    /// Only assets listed on these exchanges, e.g. NASDAQ,NYSE (comma-separated)
+    #[arg(long)]
+    exchange: Option<String>,
+    
+    /// Only assets that can be traded
+    #[arg(long)]
+    tradable: bool,
+    
+    /// Only assets that can be sold short
+    #[arg(long)]
+    shortable: bool,
+    
+    /// Only assets that can be bought in fractional shares
+    #[arg(long)]
+    fractionable: bool,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// List the assets matching the filters, e.g. to build a `--symbols-file`
+pub async fn run(args: AssetsArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let query = AssetQuery {
+        status: (args.status != "all").then_some(args.status),
+        class: args.class,
+        exchanges: args.exchange
+            .iter()
+            .flat_map(|exchanges| exchanges.split(','))
+            .map(|exchange| exchange.trim().to_uppercase())
+            .filter(|exchange| !exchange.is_empty())
+            .collect(),
+        tradable: args.tradable,
+        shortable: args.shortable,
+        fractionable: args.fractionable,
+    };
+    info!(query = ?query, format = ?args.out.format, output = ?args.out.output, "Asset listing");
+    
+    let assets = AssetsClient::from_env()?.list(&query).await?;
+    if assets.is_empty() {
+        warn!("No assets match");
+    }
+    
+    let output_mode = match &args.out.output {
+        Some(path) => OutputMode::create_file_mode(path, args.out.format.clone(), args.out.append)?,
+        None => OutputMode::create_console_mode(args.out.format.clone()),
+    };
+    if matches!(args.out.format, DataFormat::Csv) {
+        output_mode.writeln(ASSETS_CSV_HEADER)?;
+    }
+    for asset in &assets {
+        output_mode.writeln(&asset.format(&args.out.format)?)?;
+    }
+    output_mode.close()?;
+    info!(assets = assets.len(), output = ?args.out.output, "Listing complete");
+    
+    Ok(())
+}
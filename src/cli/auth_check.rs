@@ -0,0 +1,84 @@
+use super::GlobalArgs;
+use crate::credentials::{AuthReport, CredentialsChecker};
+use crate::{feed_name, parse_feed};
+use alpaca_trading_api_rust::StreamingFeed;
+use anyhow::Result;
+
+/// Flags of `trade auth-check` and the auth-check binary
+#[derive(clap::Args, Debug)]
+pub struct AuthCheckArgs {
+    /// Feed the streaming client will use (iex, sip, delayed_sip) [default: ALPACA_FEED or iex]
+    #[arg(long, value_parser = parse_feed)]
+    feed: Option<StreamingFeed>,
+    
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+fn print_report(report: &AuthReport) {
+    println!("🔑 Key ID: {} ({})", report.key_id, if report.paper { "paper" } else { "live" });
+    println!("Trading API: {}", report.base_url);
+    println!("Data API: {}\n", report.data_url);
+    
+    match &report.account {
+        Some(account) => {
+            println!("✅ Account {}: {}", account.account_number, account.status);
+            if account.trading_blocked || account.account_blocked {
+                println!("   Blocked: account {}, trading {}", account.account_blocked, account.trading_blocked);
+            }
+        }
+        None => println!("❌ Account: HTTP {}", report.account_status),
+    }
+    
+    for access in &report.feeds {
+        match &access.message {
+            None => println!("✅ {} data: allowed", access.feed.to_uppercase()),
+            Some(message) => println!("❌ {} data: HTTP {} - {}", access.feed.to_uppercase(), access.status, message),
+        }
+    }
+    println!("Data subscription: {}", report.entitlement().map(str::to_uppercase).unwrap_or_else(|| "none".to_string()));
+    println!("Configured feed: {}", report.configured_feed);
+    
+    match &report.rate_limit {
+        Some(limit) => {
+            let show = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_else(|| "?".to_string());
+            println!("Rate limit: {} remaining of {} per minute, resets at {}", show(limit.remaining), show(limit.limit), show(limit.reset));
+        }
+        None => println!("Rate limit: no X-RateLimit headers"),
+    }
+    
+    println!();
+    if report.problems.is_empty() {
+        println!("✅ Ready to stream");
+    } else {
+        for problem in &report.problems {
+            println!("⚠️  {}", problem);
+        }
+    }
+}
+
+/// Check the credentials and print the report; fails if it lists any problem
+pub async fn run(args: AuthCheckArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let feed = match args.feed {
+        Some(feed) => feed,
+        None => match std::env::var("ALPACA_FEED") {
+            Ok(feed) => parse_feed(&feed)?,
+            Err(_) => StreamingFeed::Iex,
+        },
+    };
+    
+    let report = CredentialsChecker::from_env()?.check(feed_name(&feed)).await?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    
+    if !report.problems.is_empty() {
+        return Err(anyhow::anyhow!("Credentials check found {} problem(s)", report.problems.len()));
+    }
+    Ok(())
+}
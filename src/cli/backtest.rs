@@ -0,0 +1,246 @@
+use super::cache::CacheArgs;
+use super::historical::{date_resolver, fetch_bars, given_symbols, parse_bound, BarRequests};
+use super::{GlobalArgs, OutputArgs};
+use crate::backtest::{run_backtest, BacktestConfig, EQUITY_CSV_HEADER};
+use crate::backtest_report::{check_report_path, BacktestReport};
+use crate::capture::{read_capture, CaptureFormat};
+use crate::conflation::parse_duration;
+use crate::costs::{CommissionModel, FixedBps, PerOrder, PerShare, SlippageModel};
+use crate::dates::RangeEnd;
+use crate::market_time::new_york_date;
+use crate::monte_carlo::{MonteCarloConfig, Resampling};
+use crate::resample::read_bars;
+use crate::retry::DEFAULT_REQUEST_RETRIES;
+use crate::strategies::{self, StrategyParams};
+use crate::strategy::MarketEvent;
+use crate::{Compression, DataFormat, OutputMode};
+use anyhow::{bail, Result};
+use chrono::SecondsFormat;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+/// Flags of `trade backtest`
+#[derive(clap::Args, Debug)]
+pub struct BacktestArgs {
+    /// Strategy to run: buy-and-hold, sma-crossover, rsi-reversion or opening-range-breakout
+    #[arg(long)]
+    strategy: String,
+    
+    /// Parameter of the strategy as NAME=VALUE, e.g. quantity=10; repeat for several. Overrides [strategies.<name>] in --config
+    #[arg(long = "param", value_name = "NAME=VALUE")]
+    params: Vec<String>,
+    
+    /// Bars written by `historical` or `resample`, or with --capture a capture written by `stream`; gzip and zstd files are decompressed automatically
+    #[arg(short, long, required_unless_present_any = ["symbols", "symbols_file"], conflicts_with_all = ["symbols", "symbols_file", "start", "end"])]
+    input: Option<PathBuf>,
+    
+    /// Format of a bars input (json, csv) [default: csv for .csv files, json otherwise]
+    #[arg(long, value_enum, requires = "input")]
+    input_format: Option<DataFormat>,
+    
+    /// The input is a capture: run on its bars, trades and quotes. Its format comes from its name, as for `convert`
+    #[arg(long, requires = "input")]
+    capture: bool,
+    
+    /// Download the bars of these symbols instead (comma-separated)
+    #[arg(short, long)]
+    symbols: Option<String>,
+    
+    /// Read the symbols to download from this file instead, as for `historical`
+    #[arg(long, value_name = "PATH", conflicts_with = "symbols")]
+    symbols_file: Option<PathBuf>,
+    
+    /// Start of the download, in the forms `historical --start` takes
+    #[arg(long)]
+    start: Option<String>,
+    
+    /// End of the download, in the same forms or now [default: now]
+    #[arg(long)]
+    end: Option<String>,
+    
+    /// Timeframe of downloaded bars (1Min, 5Min, 15Min, 1Hour, 1Day)
+    #[arg(short, long, default_value = "1Day")]
+    timeframe: String,
+    
+    /// Data feed of downloaded bars (sip, iex, boats, otc)
+    #[arg(long, default_value = "sip")]
+    feed: String,
+    
+    /// Page size for requests (max 10000)
+    #[arg(long, default_value = "1000")]
+    page_size: u32,
+    
+    #[command(flatten)]
+    cache: CacheArgs,
+    
+    /// Retries of each page request after a 429, a 5xx or a network error
+    #[arg(long, default_value_t = DEFAULT_REQUEST_RETRIES)]
+    max_request_retries: u32,
+    
+    /// Compare the backtest with this symbol, e.g. SPY: its daily bars over the backtest's days are downloaded from --feed
+    #[arg(long, value_name = "SYMBOL")]
+    benchmark: Option<String>,
+    
+    /// Simulate this many alternative equity curves from the round trips, e.g. 10000, and report the spread of max drawdown and final equity
+    #[arg(long, value_name = "RUNS")]
+    monte_carlo: Option<usize>,
+    
+    /// How each simulated curve draws its trades
+    #[arg(long, value_enum, default_value = "bootstrap", requires = "monte_carlo")]
+    monte_carlo_method: Resampling,
+    
+    /// Seed of the simulation, so it repeats
+    #[arg(long, value_name = "N", requires = "monte_carlo")]
+    monte_carlo_seed: Option<u64>,
+    
+    /// Cash at the start
+    #[arg(long, default_value_t = 100_000.0)]
+    cash: f64,
+    
+    /// Time between placing an order and its earliest fill, e.g. 250ms or 1s
+    #[arg(long, default_value = "0s")]
+    latency: String,
+    
+    /// Price lost on each market order, in basis points. Overrides [costs.slippage] in --config [default: none]
+    #[arg(long, value_name = "BPS")]
+    slippage_bps: Option<f64>,
+    
+    /// Commission per share filled. With --commission-per-order, overrides [costs.commission] in --config [default: none]
+    #[arg(long, value_name = "AMOUNT")]
+    commission_per_share: Option<f64>,
+    
+    /// Commission per fill
+    #[arg(long, value_name = "AMOUNT")]
+    commission_per_order: Option<f64>,
+    
+    /// Write a performance report here: HTML for .html, metrics for .csv with the trades in <name>.trades.csv next to it, JSON otherwise
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Run a strategy over historical data and report how it did
+pub async fn run(args: BacktestArgs, global: &GlobalArgs) -> Result<()> {
+    let config_file = global.init(false)?;
+    
+    // --param overrides [strategies.<name>] in --config
+    let mut params = config_file.strategy_params(&args.strategy);
+    params.extend(StrategyParams::parse(&args.params)?);
+    let strategy = strategies::create(&args.strategy, &params)?;
+    // The cost flags override the models of [costs] in --config
+    let slippage: Arc<dyn SlippageModel> = match args.slippage_bps {
+        Some(bps) => Arc::new(FixedBps::new(bps)?),
+        None => config_file.costs.slippage.build()?,
+    };
+    let commission: Arc<dyn CommissionModel> = match (args.commission_per_share, args.commission_per_order) {
+        (None, None) => config_file.costs.commission.build()?,
+        (per_share, per_order) => Arc::new(vec![
+            Arc::new(PerShare::new(per_share.unwrap_or(0.0), 0.0)?) as Arc<dyn CommissionModel>,
+            Arc::new(PerOrder::new(per_order.unwrap_or(0.0))?),
+        ]),
+    };
+    let config = BacktestConfig {
+        initial_cash: args.cash,
+        latency: parse_duration(&args.latency)?,
+        slippage,
+        commission,
+    };
+    if let Some(report) = &args.report {
+        check_report_path(report)?;
+    }
+    info!(strategy = %args.strategy, params = ?args.params, input = ?args.input, symbols = ?args.symbols, ?config, "Backtesting");
+    
+    let mut events = Vec::new();
+    match &args.input {
+        Some(input) if args.capture => {
+            read_capture(input, CaptureFormat::from_path(input)?, |data| {
+                events.extend(MarketEvent::from_streaming_data(&data));
+                Ok(())
+            })?;
+        }
+        Some(input) => {
+            let input_format = args.input_format.clone().unwrap_or_else(|| {
+                if input.to_string_lossy().contains(".csv") { DataFormat::Csv } else { DataFormat::Json }
+            });
+            read_bars(input, &input_format, |bar| {
+                events.push(MarketEvent::Bar(bar));
+                Ok(())
+            })?;
+        }
+        None => {
+            let Some(symbols) = given_symbols(args.symbols.as_deref(), args.symbols_file.as_deref())? else {
+                bail!("--input, --symbols or --symbols-file is required");
+            };
+            let Some(start) = args.start.as_deref() else {
+                bail!("--start is required to download bars");
+            };
+            let dates = date_resolver(&[Some(start), args.end.as_deref()]).await;
+            let start = dates.resolve(start, RangeEnd::Start)?;
+            let end = dates.resolve(args.end.as_deref().unwrap_or("now"), RangeEnd::End)?;
+            if parse_bound(&start)? >= parse_bound(&end)? {
+                bail!("Start date must be before end date");
+            }
+            let requests = BarRequests::new(&args.timeframe, &args.feed, &end, args.page_size, args.cache.open(), args.max_request_retries)?;
+            for symbol in &symbols {
+                events.extend(fetch_bars(&requests, symbol, &start).await?.into_iter().map(MarketEvent::Bar));
+            }
+        }
+    }
+    if events.is_empty() {
+        bail!("No bars, trades or quotes to backtest on");
+    }
+    // Files and downloads hold one symbol after another; the stable sort keeps
+    // events at the same time in the order they were read
+    events.sort_by_key(MarketEvent::timestamp);
+    
+    let benchmark = match &args.benchmark {
+        Some(symbol) => {
+            let symbol = symbol.trim().to_uppercase();
+            let start = new_york_date(events[0].timestamp()).to_string();
+            let end = events[events.len() - 1].timestamp().to_rfc3339_opts(SecondsFormat::Secs, true);
+            let requests = BarRequests::new("1Day", &args.feed, &end, args.page_size, args.cache.open(), args.max_request_retries)?;
+            let bars = fetch_bars(&requests, &symbol, &start).await?;
+            info!(%symbol, bars = bars.len(), "Downloaded benchmark");
+            Some((symbol, bars))
+        }
+        None => None,
+    };
+    
+    let result = run_backtest(strategy, events, &config)?;
+    
+    // The equity curve
+    let output_mode = match &args.out.output {
+        Some(path) => OutputMode::create_compressed_file_mode(path, args.out.format.clone(), args.out.append, Compression::from_extension(path))?,
+        None => OutputMode::create_console_mode(args.out.format.clone()),
+    };
+    if matches!(args.out.format, DataFormat::Csv) && !args.out.append {
+        output_mode.writeln(EQUITY_CSV_HEADER)?;
+    }
+    for point in &result.equity_curve {
+        output_mode.writeln(&point.format(&args.out.format)?)?;
+    }
+    output_mode.close()?;
+    let mut report = BacktestReport::new(&result);
+    if let Some((symbol, bars)) = &benchmark {
+        report = report.with_benchmark(symbol, &result.equity_curve, bars)?;
+    }
+    if let Some(runs) = args.monte_carlo {
+        let config = MonteCarloConfig { runs, method: args.monte_carlo_method, seed: args.monte_carlo_seed };
+        report = report.with_monte_carlo(&config)?;
+    }
+    println!("{}", report);
+    if result.unfilled > 0 {
+        println!("   {} orders were never filled", result.unfilled);
+    }
+    if let Some(path) = &args.report {
+        for file in report.write(path, &result.equity_curve)? {
+            println!("📄 Report written to {}", file.display());
+        }
+    }
+    info!(fills = result.fills.len(), points = result.equity_curve.len(), output = ?args.out.output, "Backtest complete");
+    
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use super::GlobalArgs;
+use crate::cache::BarCache;
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Flags of commands that read and fill the historical bar cache
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct CacheArgs {
+    /// Fetch every bar from the API, neither reading nor filling the cache
+    #[arg(long)]
+    pub no_cache: bool,
+    
+    /// Cache directory [default: ALPACA_CACHE_DIR, or algorithms-trading in XDG_CACHE_HOME or ~/.cache]
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl CacheArgs {
+    /// The cache to use, or `None` with --no-cache or when there is nowhere to keep it
+    pub fn open(&self) -> Option<BarCache> {
+        if self.no_cache {
+            return None;
+        }
+        let dir = self.cache_dir.clone().or_else(BarCache::default_dir);
+        if dir.is_none() {
+            warn!("No cache directory: set ALPACA_CACHE_DIR or pass --cache-dir; bars will not be cached");
+        }
+        dir.map(BarCache::new)
+    }
+}
+
+/// `trade cache <command>`
+#[derive(clap::Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Delete every cached bar
+    Clear {
+        /// Cache directory [default: ALPACA_CACHE_DIR, or algorithms-trading in XDG_CACHE_HOME or ~/.cache]
+        #[arg(long, value_name = "DIR")]
+        cache_dir: Option<PathBuf>,
+    },
+}
+
+pub fn run(command: CacheCommand, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    match command {
+        CacheCommand::Clear { cache_dir } => {
+            let dir = cache_dir.or_else(BarCache::default_dir)
+                .ok_or_else(|| anyhow::anyhow!("No cache directory: set ALPACA_CACHE_DIR or pass --cache-dir"))?;
+            let (files, bytes) = BarCache::new(&dir).clear()?;
+            println!("🗑️  Removed {} cached chunks ({} bytes) from {}", files, bytes, dir.display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use super::GlobalArgs;
+use crate::capture::{read_capture, CaptureFormat};
+use crate::chart::{render_text, ChartKind, ChartOptions, Indicator};
+use crate::console::ColorChoice;
+use crate::resample::{read_bars, OhlcvBar, Resampler, Session, Timeframe};
+use crate::DataFormat;
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Flags of `trade chart`
+#[derive(clap::Args, Debug)]
+pub struct ChartArgs {
+    /// Bars written by `historical` or `resample`, or with --capture a capture written by `stream`; gzip and zstd files are decompressed automatically
+    #[arg(short, long)]
+    input: PathBuf,
+    
+    /// Format of a bars input (json, csv) [default: csv for .csv files, json otherwise]
+    #[arg(long, value_enum)]
+    input_format: Option<DataFormat>,
+    
+    /// The input is a capture: chart its bar messages, or with --timeframe bars built from its trades. Its format comes from its name, as for `convert`
+    #[arg(long)]
+    capture: bool,
+    
+    /// Timeframe to chart: <N>Sec, <N>Min, <N>Hour, <N>Day (trading days), <N>Week (from Monday) or <N>Month; bars inputs are resampled to it, and must be a fraction of it
+    #[arg(short, long)]
+    timeframe: Option<String>,
+    
+    /// Symbol to chart [default: the first in the input]
+    #[arg(short, long)]
+    symbol: Option<String>,
+    
+    /// Draw candles, or a line through the closes
+    #[arg(long, value_enum, default_value_t = ChartKind::Candle)]
+    kind: ChartKind,
+    
+    /// Overlay an indicator: sma:N, ema:N or vwap; repeat or separate with commas for several
+    #[arg(long = "indicator", value_name = "INDICATOR", value_delimiter = ',')]
+    indicators: Vec<String>,
+    
+    /// Leave out the volume panel
+    #[arg(long)]
+    no_volume: bool,
+    
+    /// Width in characters, price labels included, or in pixels with --png; the latest bars that fit are drawn [default: $COLUMNS or 100, 1200 with --png]
+    #[arg(long)]
+    width: Option<usize>,
+    
+    /// Height of the price panel in lines, or of the image in pixels with --png [default: 20, 700 with --png]
+    #[arg(long)]
+    height: Option<usize>,
+    
+    /// Draw with ASCII characters only, for terminals and files without Unicode
+    #[arg(long)]
+    ascii: bool,
+    
+    /// Color up bars green, down bars red and indicators yellow
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    
+    /// Write the chart to this PNG image instead of the terminal
+    #[cfg(feature = "charts")]
+    #[arg(long, value_name = "PATH")]
+    png: Option<PathBuf>,
+}
+
+/// Read the bars of one symbol from --input and draw them
+pub fn run(args: ChartArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let timeframe = args.timeframe.as_deref().map(Timeframe::parse).transpose()?;
+    let indicators = args.indicators.iter().map(|indicator| Indicator::parse(indicator)).collect::<crate::Result<Vec<_>>>()?;
+    info!(input = %args.input.display(), capture = args.capture, timeframe = ?args.timeframe, symbol = ?args.symbol, "Charting bars");
+    
+    let mut symbol = args.symbol.clone();
+    let mut resampler = timeframe.map(|timeframe| Resampler::new(timeframe, Session::All));
+    let mut bars = Vec::new();
+    let mut late = 0;
+    let mut add = |bar: OhlcvBar| -> crate::Result<()> {
+        if *symbol.get_or_insert_with(|| bar.symbol.clone()) != bar.symbol {
+            return Ok(());
+        }
+        match &mut resampler {
+            Some(resampler) => {
+                // A trade that arrives after its bar was complete cannot be added
+                if resampler.current_start(&bar.symbol).is_some_and(|start| bar.timestamp < start) {
+                    late += 1;
+                    return Ok(());
+                }
+                bars.extend(resampler.push(bar)?);
+            }
+            None => bars.push(bar),
+        }
+        Ok(())
+    };
+    if args.capture {
+        let format = CaptureFormat::from_path(&args.input)?;
+        read_capture(&args.input, format, |data| {
+            let bar = if timeframe.is_some() { OhlcvBar::from_trade(&data) } else { OhlcvBar::from_bar_message(&data) };
+            match bar {
+                Some(bar) => add(bar),
+                None => Ok(()),
+            }
+        })?;
+    } else {
+        let input_format = args.input_format.unwrap_or_else(|| {
+            if args.input.to_string_lossy().contains(".csv") { DataFormat::Csv } else { DataFormat::Json }
+        });
+        read_bars(&args.input, &input_format, &mut add)?;
+    }
+    if let Some(resampler) = resampler {
+        bars.extend(resampler.finish());
+    }
+    if late > 0 {
+        warn!(late, "Skipped trades that arrived after their bar was complete");
+    }
+    let Some(symbol) = symbol else {
+        bail!("{} has no bars to chart", args.input.display());
+    };
+    if bars.is_empty() {
+        bail!("{} has no bars of {}", args.input.display(), symbol);
+    }
+    
+    let options = ChartOptions {
+        kind: args.kind,
+        indicators,
+        volume: !args.no_volume,
+        width: args.width.unwrap_or_else(|| {
+            std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).unwrap_or(100)
+        }),
+        height: args.height.unwrap_or(20),
+        ascii: args.ascii,
+        color: args.color.enabled(),
+    };
+    
+    #[cfg(feature = "charts")]
+    if let Some(path) = &args.png {
+        let options = ChartOptions {
+            width: args.width.unwrap_or(1200),
+            height: args.height.unwrap_or(700),
+            ..options
+        };
+        crate::chart::write_png(&bars, &options, path)?;
+        let (low, high) = bars.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), bar| (low.min(bar.low), high.max(bar.high)));
+        info!(png = %path.display(), symbol = %symbol, bars = bars.len(), from = %bars[0].timestamp, to = %bars[bars.len() - 1].timestamp, low, high, "Chart written");
+        return Ok(());
+    }
+    
+    for line in render_text(&bars, &options) {
+        println!("{}", line);
+    }
+    info!(symbol = %symbol, bars = bars.len(), "Chart drawn");
+    
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use super::GlobalArgs;
+use crate::capture::{read_capture, CaptureFormat};
+use crate::{Compression, DataFormat, OutputMode, OutputSink};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Flags of `trade convert`
+#[derive(clap::Args, Debug)]
+pub struct ConvertArgs {
+    /// Capture files to convert, in order; repeat for several. gzip and zstd files are decompressed automatically
+    #[arg(short, long = "input", value_name = "INPUT", required = true)]
+    inputs: Vec<PathBuf>,
+    
+    /// Format of the inputs [default: from each input's name: a directory or a new path without an extension is a Parquet dataset, .db, .sqlite and .sqlite3 are SQLite, .csv is CSV, anything else JSON lines]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    from: Option<CaptureFormat>,
+    
+    /// File, database or dataset directory to write
+    #[arg(short, long)]
+    output: PathBuf,
+    
+    /// Format of the output [default: from the output's name, as for --from]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    to: Option<CaptureFormat>,
+    
+    /// Add to an existing output instead of replacing it; Parquet datasets are always added to
+    #[arg(short, long)]
+    append: bool,
+}
+
+/// Convert every --input to the --to format, in one --output
+pub fn run(args: ConvertArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let to = match args.to {
+        Some(format) => format,
+        None => CaptureFormat::from_path(&args.output)?,
+    };
+    info!(inputs = args.inputs.len(), output = %args.output.display(), format = ?to, "Converting captures");
+    
+    let sink = open_sink(&args.output, to, args.append)?;
+    
+    // A dataset only has tables for trades, quotes and bars
+    #[cfg(feature = "parquet")]
+    let market_data_only = to == CaptureFormat::Parquet;
+    #[cfg(not(feature = "parquet"))]
+    let market_data_only = false;
+    
+    let mut total = 0;
+    let mut dropped = 0;
+    for input in &args.inputs {
+        let from = match args.from {
+            Some(format) => format,
+            None => CaptureFormat::from_path(input)?,
+        };
+        let mut messages = 0;
+        read_capture(input, from, |data| {
+            messages += 1;
+            if market_data_only && !matches!(data.message_type.as_str(), "t" | "q" | "b") {
+                dropped += 1;
+                return Ok(());
+            }
+            sink.write_streaming_data(&data)
+        })?;
+        info!(input = %input.display(), format = ?from, messages, "Converted capture");
+        total += messages;
+    }
+    sink.close()?;
+    if dropped > 0 {
+        warn!(dropped, "Left out messages other than trades, quotes and bars, which Parquet datasets do not hold");
+    }
+    info!(messages = total - dropped, output = %args.output.display(), "Conversion complete");
+    
+    Ok(())
+}
+
+/// A sink that writes captures in `format` to `output`, which is replaced
+/// unless `append`
+pub(crate) fn open_sink(output: &Path, format: CaptureFormat, append: bool) -> Result<Box<dyn OutputSink>> {
+    let path = output.to_path_buf();
+    Ok(match format {
+        CaptureFormat::Json => Box::new(OutputMode::create_compressed_file_mode(&path, DataFormat::Json, append, Compression::from_extension(output))?),
+        CaptureFormat::Csv => Box::new(OutputMode::create_compressed_file_mode(&path, DataFormat::Csv, append, Compression::from_extension(output))?),
+        #[cfg(feature = "parquet")]
+        CaptureFormat::Parquet => Box::new(crate::dataset::DatasetWriter::new(output)?),
+        #[cfg(feature = "sqlite")]
+        CaptureFormat::Sqlite => {
+            if !append && output.exists() {
+                std::fs::remove_file(output)?;
+            }
+            Box::new(crate::sqlite::SqliteSink::open(output)?)
+        }
+    })
+}
@@ -0,0 +1,194 @@
+use super::historical::parse_date;
+use super::{GlobalArgs, OutputArgs};
+use crate::corporate_actions::{
+    adjusted_bars_csv_header, read_corporate_actions, ActionGroup, CorporateActionKind, CorporateActionsClient, PriceAdjuster,
+    CORPORATE_ACTIONS_CSV_HEADER,
+};
+use crate::market_time::new_york_date;
+use crate::resample::read_bars;
+use crate::{Compression, DataFormat, OutputMode};
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Flags of `trade corporate-actions`
+#[derive(clap::Args, Debug)]
+pub struct CorporateActionsArgs {
+    /// Symbols to retrieve actions for (comma-separated)
+    #[arg(short, long)]
+    symbols: String,
+    
+    /// Start date (YYYY-MM-DD)
+    #[arg(long)]
+    start: String,
+    
+    /// End date (YYYY-MM-DD)
+    #[arg(long)]
+    end: String,
+    
+    /// Kinds of action (comma-separated) [default: all]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    types: Vec<ActionGroup>,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// What `trade adjust` adjusts for
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjustFor {
+    /// Splits and stock dividends
+    Splits,
+    /// Splits, stock dividends and cash dividends
+    All,
+}
+
+/// Flags of `trade adjust`
+#[derive(clap::Args, Debug)]
+pub struct AdjustArgs {
+    /// Raw bars written by `historical`; gzip and zstd files are decompressed automatically
+    #[arg(short, long)]
+    input: PathBuf,
+    
+    /// Input format (json, csv) [default: csv for .csv files, json otherwise]
+    #[arg(long, value_enum)]
+    input_format: Option<DataFormat>,
+    
+    /// Corporate actions written by `trade corporate-actions` (csv or json) instead of fetching them for the bars' symbols and dates
+    #[arg(long, value_name = "PATH")]
+    actions: Option<PathBuf>,
+    
+    /// Adjust for splits only, or for dividends as well
+    #[arg(long, value_enum, default_value_t = AdjustFor::All)]
+    adjust: AdjustFor,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Write the bars of --input adjusted for the splits and dividends after
+/// them, with the factors used
+pub async fn run_adjust(args: AdjustArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let input_format = args.input_format.unwrap_or_else(|| {
+        if args.input.to_string_lossy().contains(".csv") { DataFormat::Csv } else { DataFormat::Json }
+    });
+    info!(input = %args.input.display(), adjust = ?args.adjust, format = ?args.out.format, output = ?args.out.output, "Adjusting bars");
+    
+    // First pass: the close of each day, to price the dividends, and the
+    // symbols and dates to fetch actions for
+    let mut closes: HashMap<String, BTreeMap<NaiveDate, f64>> = HashMap::new();
+    read_bars(&args.input, &input_format, |bar| {
+        closes.entry(bar.symbol).or_default().insert(new_york_date(bar.timestamp), bar.close);
+        Ok(())
+    })?;
+    let mut symbols: Vec<String> = closes.keys().cloned().collect();
+    symbols.sort();
+    let dates = closes.values().flat_map(|closes| closes.keys().copied());
+    let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+        return Err(anyhow::anyhow!("No bars in {}", args.input.display()));
+    };
+    
+    let actions = match &args.actions {
+        Some(path) => read_corporate_actions(path)?,
+        None => {
+            let kinds = [
+                CorporateActionKind::ForwardSplit,
+                CorporateActionKind::ReverseSplit,
+                CorporateActionKind::StockDividend,
+                CorporateActionKind::CashDividend,
+            ];
+            let format = |date: NaiveDate| date.format("%Y-%m-%d").to_string();
+            CorporateActionsClient::from_env()?
+                .fetch_actions(&symbols, &kinds, &format(first), &format(last))
+                .await?
+        }
+    };
+    let adjuster = PriceAdjuster::new(&actions, &closes, args.adjust == AdjustFor::All);
+    drop(closes);
+    info!(symbols = symbols.len(), first = %first, last = %last, actions = actions.len(), "Loaded corporate actions");
+    
+    let output_mode = match &args.out.output {
+        Some(path) => OutputMode::create_compressed_file_mode(path, args.out.format.clone(), args.out.append, Compression::from_extension(path))?,
+        None => OutputMode::create_console_mode(args.out.format.clone()),
+    };
+    if matches!(args.out.format, DataFormat::Csv) && !args.out.append {
+        output_mode.writeln(&adjusted_bars_csv_header())?;
+    }
+    let mut records = 0;
+    let mut adjusted = 0;
+    read_bars(&args.input, &input_format, |bar| {
+        let date = new_york_date(bar.timestamp);
+        let bar = adjuster.adjust(bar, date);
+        records += 1;
+        adjusted += (bar.split_factor != 1.0 || bar.dividend_factor != 1.0) as usize;
+        output_mode.writeln(&bar.format(&args.out.format)?)?;
+        Ok(())
+    })?;
+    output_mode.close()?;
+    info!(records, adjusted, output = ?args.out.output, "Adjustment complete");
+    
+    Ok(())
+}
+
+/// Fetch the splits, dividends and mergers of every symbol and write them out
+pub async fn run(args: CorporateActionsArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let symbols: Vec<String> = args.symbols
+        .split(',')
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if symbols.is_empty() {
+        return Err(anyhow::anyhow!("No symbols given"));
+    }
+    
+    let start_date = parse_date(&args.start)?;
+    let end_date = parse_date(&args.end)?;
+    if start_date > end_date {
+        return Err(anyhow::anyhow!("Start date must not be after end date"));
+    }
+    
+    let groups = if args.types.is_empty() {
+        vec![ActionGroup::Splits, ActionGroup::Dividends, ActionGroup::Mergers]
+    } else {
+        args.types.clone()
+    };
+    let kinds: Vec<_> = groups.iter().flat_map(|group| group.kinds().iter().copied()).collect();
+    
+    info!(
+        symbols = ?symbols,
+        start = %start_date,
+        end = %end_date,
+        types = ?groups,
+        format = ?args.out.format,
+        output = ?args.out.output,
+        "Corporate actions retrieval"
+    );
+    
+    let actions = CorporateActionsClient::from_env()?
+        .fetch_actions(&symbols, &kinds, &start_date, &end_date)
+        .await?;
+    if actions.is_empty() {
+        warn!("No corporate actions found");
+    }
+    
+    let output_mode = match &args.out.output {
+        Some(path) => OutputMode::create_file_mode(path, args.out.format.clone(), args.out.append)?,
+        None => OutputMode::create_console_mode(args.out.format.clone()),
+    };
+    if matches!(args.out.format, DataFormat::Csv) {
+        output_mode.writeln(CORPORATE_ACTIONS_CSV_HEADER)?;
+    }
+    for action in &actions {
+        output_mode.writeln(&action.format(&args.out.format)?)?;
+    }
+    output_mode.close()?;
+    info!(symbols = symbols.len(), records = actions.len(), output = ?args.out.output, "Retrieval complete");
+    
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use super::GlobalArgs;
+use crate::keychain::{parse_credential, Keychain, CREDENTIAL_VARS};
+use anyhow::Result;
+use std::io::BufRead;
+
+/// `trade credentials <command>` and the credentials binary
+#[derive(clap::Subcommand, Debug)]
+pub enum CredentialsCommand {
+    /// Store a credential, read as one line from stdin
+    Set {
+        /// key-id or secret-key
+        #[arg(value_parser = parse_credential)]
+        name: &'static str,
+    },
+    /// Print a stored credential; the secret key is masked unless --show is given
+    Get {
+        /// key-id or secret-key
+        #[arg(value_parser = parse_credential)]
+        name: &'static str,
+        
+        /// Print the secret key in full
+        #[arg(long)]
+        show: bool,
+    },
+    /// Remove a credential, or both with `all`
+    Delete {
+        /// key-id, secret-key or all
+        name: String,
+    },
+}
+
+/// Run one keychain command for `--profile`. Profiles only name the entries
+/// here, so they need not exist in `--config` or as `.env` files.
+pub fn run(command: CredentialsCommand, global: &GlobalArgs) -> Result<()> {
+    let keychain = Keychain::new(global.profile.as_deref());
+    
+    match command {
+        CredentialsCommand::Set { name } => {
+            eprintln!("Enter {} for profile {}:", name, keychain.profile());
+            let mut value = String::new();
+            std::io::stdin().lock().read_line(&mut value)?;
+            let value = value.trim();
+            if value.is_empty() {
+                return Err(anyhow::anyhow!("No value given for {}", name));
+            }
+            keychain.set(name, value)?;
+            println!("✅ Stored {}", keychain.account(name));
+        }
+        CredentialsCommand::Get { name, show } => match keychain.get(name)? {
+            Some(value) if name == "APCA_API_SECRET_KEY" && !show => println!("{}", "*".repeat(value.len())),
+            Some(value) => println!("{}", value),
+            None => return Err(anyhow::anyhow!("No {} in the keychain", keychain.account(name))),
+        },
+        CredentialsCommand::Delete { name } => {
+            let names = if name == "all" { CREDENTIAL_VARS.to_vec() } else { vec![parse_credential(&name)?] };
+            for name in names {
+                if keychain.delete(name)? {
+                    println!("🗑️  Deleted {}", keychain.account(name));
+                } else {
+                    println!("No {} in the keychain", keychain.account(name));
+                }
+            }
+        }
+    }
+    Ok(())
+}
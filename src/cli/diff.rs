@@ -0,0 +1,83 @@
+use super::GlobalArgs;
+use crate::capture::{read_capture, CaptureFormat};
+use crate::capture_diff::{CaptureDiff, DIFFERENCES_CSV_HEADER};
+use crate::{Compression, DataFormat, OutputMode};
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Flags of `trade diff`
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// Capture to compare to; gzip and zstd files are decompressed automatically
+    first: PathBuf,
+    
+    /// Capture to compare
+    second: PathBuf,
+    
+    /// Format of the captures (json, csv, parquet, sqlite) [default: from each capture's name, as for `convert`]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    from: Option<CaptureFormat>,
+    
+    /// Largest difference of a price or size that is not reported, as a fraction of the first capture's value
+    #[arg(long, default_value_t = 0.0)]
+    tolerance: f64,
+    
+    /// Write every difference to this file (messages only one capture has, and prices and sizes beyond --tolerance)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    
+    /// Format of --output
+    #[arg(short, long, value_enum, default_value_t = DataFormat::Csv)]
+    format: DataFormat,
+    
+    /// Fail when the captures differ, for scripts
+    #[arg(long)]
+    check: bool,
+}
+
+/// Compare the market data of two captures and report how they differ
+pub fn run(args: DiffArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    if args.tolerance.is_nan() || args.tolerance < 0.0 {
+        return Err(anyhow::anyhow!("--tolerance must be 0 or more"));
+    }
+    info!(first = %args.first.display(), second = %args.second.display(), tolerance = args.tolerance, "Comparing captures");
+    
+    let mut diff = CaptureDiff::new(args.tolerance);
+    for (index, input) in [&args.first, &args.second].into_iter().enumerate() {
+        let format = match args.from {
+            Some(format) => format,
+            None => CaptureFormat::from_path(input)?,
+        };
+        read_capture(input, format, |data| {
+            if index == 0 {
+                diff.add_first(&data);
+            } else {
+                diff.add_second(&data);
+            }
+            Ok(())
+        })?;
+    }
+    let (symbols, differences) = diff.finish();
+    for symbol in &symbols {
+        println!("{}", symbol);
+    }
+    
+    if let Some(path) = &args.output {
+        let output = OutputMode::create_compressed_file_mode(path, args.format.clone(), false, Compression::from_extension(path))?;
+        if matches!(args.format, DataFormat::Csv) {
+            output.writeln(DIFFERENCES_CSV_HEADER)?;
+        }
+        for difference in &differences {
+            output.writeln(&difference.format(&args.format)?)?;
+        }
+        output.close()?;
+    }
+    info!(symbols = symbols.len(), differences = differences.len(), output = ?args.output, "Capture comparison complete");
+    if args.check && !differences.is_empty() {
+        return Err(anyhow::anyhow!("The captures have {} differences", differences.len()));
+    }
+    Ok(())
+}
@@ -0,0 +1,2099 @@
+use super::cache::CacheArgs;
+use super::{GlobalArgs, OutputArgs};
+use crate::cache::{BarCache, CacheKey};
+use crate::checkpoint::Checkpoint;
+use crate::dates::{DateResolver, RangeEnd};
+use crate::export::ExportProfile;
+use crate::merge::merge_sorted;
+use crate::metadata::DownloadMetadata;
+use crate::resample::{OhlcvBar, BARS_CSV_HEADER};
+use crate::rate_limit::RateLimiter;
+use crate::retry::{retry_request, RetryPolicy, DEFAULT_REQUEST_RETRIES};
+use crate::symbols_file::read_symbols_file;
+use crate::validation::{BarValidator, BarValues, VALIDATION_CSV_HEADER};
+use crate::xlsx::{bar_cells, XlsxWriter, BAR_COLUMNS};
+use crate::{Compression, DataFormat, OutputMode};
+use alpaca_trading_api_rust::*;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, SecondsFormat, Utc};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+
+
+/// Flags of `trade historical` and the historical-data binary
+#[derive(clap::Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct HistoricalArgs {
+    #[command(subcommand)]
+    command: Option<HistoricalCommand>,
+    
+    /// Symbols to retrieve data for (comma-separated)
+    #[arg(short, long, required_unless_present = "symbols_file")]
+    symbols: Option<String>,
+    
+    /// Read the symbols from this file instead: one per line, or the csv or json output of `trade assets`
+    #[arg(long, value_name = "PATH", conflicts_with = "symbols")]
+    symbols_file: Option<PathBuf>,
+    
+    /// Start: YYYY-MM-DD, YYYY-MM, YYYY, YYYY-MM-DDTHH:MM (New York time), today, yesterday, last-week or last-month
+    #[arg(long, required_unless_present = "days_back")]
+    start: Option<String>,
+    
+    /// End, in the same forms as --start or now [default: now]
+    #[arg(long)]
+    end: Option<String>,
+    
+    /// Start this many days before today, instead of --start
+    #[arg(long, value_name = "DAYS", conflicts_with = "start")]
+    days_back: Option<u32>,
+    
+    /// Timeframe for bars (1Min, 5Min, 15Min, 1Hour, 1Day)
+    #[arg(short, long, default_value = "1Day")]
+    timeframe: String,
+    
+    /// Download bars, every individual trade, or the opening and closing auction prints; trades and auctions ignore --timeframe
+    #[cfg(feature = "historical-trades")]
+    #[arg(long, value_enum, default_value_t = HistoricalData::Bars)]
+    data: HistoricalData,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+    
+    /// Write each symbol to its own file in this directory, e.g. AAPL_1Day_2024.csv, listed in manifest.json
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+    
+    /// How --output-dir splits the download into files [default: symbol]
+    #[arg(long, value_enum, requires = "output_dir")]
+    split_by: Option<SplitBy>,
+    
+    /// Write the bars of every symbol as one stream sorted by timestamp, then symbol, for event-driven backtests; symbols are downloaded to <OUTPUT>.parts/ first
+    #[arg(long, conflicts_with = "output_dir")]
+    merge: bool,
+    
+    /// Write bars in the CSV layout of a backtesting tool, one file per symbol (--output-dir, or --output with one symbol); implies --format csv
+    #[arg(long, value_enum, value_name = "PROFILE", conflicts_with = "merge")]
+    export_profile: Option<ExportProfile>,
+    
+    /// Also write the bars to an Excel workbook, one sheet per symbol with times in New York
+    #[arg(long, value_name = "PATH", conflicts_with = "resume")]
+    xlsx: Option<PathBuf>,
+    
+    /// Also write the bars or trades as a hive-partitioned Parquet dataset in this directory
+    #[cfg(feature = "parquet")]
+    #[arg(long, value_name = "DIR")]
+    dataset: Option<PathBuf>,
+    
+    /// Compress the output file; the extension (.gz or .zst) is added to the path if missing
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    compress: Compression,
+    
+    /// Page size for requests (max 10000)
+    #[arg(long, default_value = "1000")]
+    page_size: u32,
+    
+    /// Data feed source (sip, iex, boats, otc)
+    #[arg(long, default_value = "sip")]
+    feed: String,
+    
+    #[command(flatten)]
+    cache: CacheArgs,
+    
+    /// Check every bar for impossible prices, gaps of more than 50% from the previous close and volume over 20x the recent average; issues go to <OUTPUT>.validation.csv, or the log without --output
+    #[arg(long)]
+    validate: bool,
+    
+    /// Continue an interrupted download to --output from its checkpoint (<OUTPUT>.checkpoint.json), skipping finished symbols and pages
+    #[arg(long)]
+    resume: bool,
+    
+    /// Retries of each page request after a 429, a 5xx or a network error; other errors fail the symbol at once
+    #[arg(long, default_value_t = DEFAULT_REQUEST_RETRIES)]
+    max_request_retries: u32,
+    
+    /// Print how many requests and bars the download needs, and how long it takes at the rate limit (ALPACA_RATE_LIMIT), without requesting any data
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// `historical <command>`
+#[derive(clap::Subcommand, Debug)]
+pub enum HistoricalCommand {
+    /// Append the bars after the last stored one of each symbol to a file and/or dataset
+    Sync(Box<SyncArgs>),
+    /// Report trading days missing from a file and/or dataset, and fetch them with --fix
+    #[cfg(feature = "market-hours")]
+    Backfill(Box<BackfillArgs>),
+    /// Download the same bars from two feeds, e.g. IEX and SIP, and report missing bars, price differences and volume ratios
+    CompareFeeds(Box<CompareFeedsArgs>),
+}
+
+/// Flags of `historical sync`
+#[derive(clap::Args, Debug)]
+pub struct SyncArgs {
+    /// Symbols to bring up to date (comma-separated)
+    #[arg(short, long, required_unless_present = "symbols_file")]
+    symbols: Option<String>,
+    
+    /// Read the symbols from this file instead, as for a download
+    #[arg(long, value_name = "PATH", conflicts_with = "symbols")]
+    symbols_file: Option<PathBuf>,
+    
+    /// File written by `historical` to append new bars to (csv or json, optionally .gz or .zst); created if missing
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    
+    /// Format of --output
+    #[arg(short, long, value_enum, default_value_t = DataFormat::Csv)]
+    format: DataFormat,
+    
+    /// Hive-partitioned Parquet dataset to add new bars to
+    #[cfg(feature = "parquet")]
+    #[arg(long, value_name = "DIR")]
+    dataset: Option<PathBuf>,
+    
+    /// Start of symbols with no stored bars yet, in the forms `historical --start` takes
+    #[arg(long)]
+    start: Option<String>,
+    
+    /// End, in the same forms as --start or now [default: now]
+    #[arg(long)]
+    end: Option<String>,
+    
+    /// Timeframe of the stored bars (1Min, 5Min, 15Min, 1Hour, 1Day)
+    #[arg(short, long, default_value = "1Day")]
+    timeframe: String,
+    
+    #[command(flatten)]
+    cache: CacheArgs,
+    
+    /// Page size for requests (max 10000)
+    #[arg(long, default_value = "1000")]
+    page_size: u32,
+    
+    /// Data feed source (sip, iex, boats, otc)
+    #[arg(long, default_value = "sip")]
+    feed: String,
+    
+    /// Retries of each page request after a 429, a 5xx or a network error; other errors fail the symbol at once
+    #[arg(long, default_value_t = DEFAULT_REQUEST_RETRIES)]
+    max_request_retries: u32,
+}
+
+/// Flags of `historical backfill`
+#[cfg(feature = "market-hours")]
+#[derive(clap::Args, Debug)]
+pub struct BackfillArgs {
+    /// Symbols to check (comma-separated) [default: every stored symbol]
+    #[arg(short, long)]
+    symbols: Option<String>,
+    
+    /// Read the symbols to check from this file instead, as for a download
+    #[arg(long, value_name = "PATH", conflicts_with = "symbols")]
+    symbols_file: Option<PathBuf>,
+    
+    /// File written by `historical` to check (csv or json, optionally .gz or .zst)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    
+    /// Format of --output
+    #[arg(short, long, value_enum, default_value_t = DataFormat::Csv)]
+    format: DataFormat,
+    
+    /// Hive-partitioned Parquet dataset to check
+    #[cfg(feature = "parquet")]
+    #[arg(long, value_name = "DIR")]
+    dataset: Option<PathBuf>,
+    
+    /// First day to check, in the forms `historical --start` takes [default: each symbol's first stored bar]
+    #[arg(long)]
+    start: Option<String>,
+    
+    /// Last day to check, in the same forms [default: each symbol's last stored bar]
+    #[arg(long)]
+    end: Option<String>,
+    
+    /// Timeframe of the stored bars (1Min, 5Min, 15Min, 30Min, 1Hour, 1Day)
+    #[arg(short, long, default_value = "1Day")]
+    timeframe: String,
+    
+    /// Also flag days with fewer than this fraction of a full regular session's bars; 0 only flags days without any
+    #[arg(long, default_value_t = 0.0)]
+    min_coverage: f64,
+    
+    /// Fetch the missing ranges and add the bars each store lacks
+    #[arg(long)]
+    fix: bool,
+    
+    /// Page size for requests (max 10000)
+    #[arg(long, default_value = "1000")]
+    page_size: u32,
+    
+    /// Data feed source (sip, iex, boats, otc)
+    #[arg(long, default_value = "sip")]
+    feed: String,
+    
+    #[command(flatten)]
+    cache: CacheArgs,
+    
+    /// Retries of each page request after a 429, a 5xx or a network error; other errors fail the symbol at once
+    #[arg(long, default_value_t = DEFAULT_REQUEST_RETRIES)]
+    max_request_retries: u32,
+}
+
+/// Flags of `historical compare-feeds`
+#[derive(clap::Args, Debug)]
+pub struct CompareFeedsArgs {
+    /// Symbols to compare (comma-separated)
+    #[arg(short, long, required_unless_present = "symbols_file")]
+    symbols: Option<String>,
+    
+    /// Read the symbols from this file instead, as for a download
+    #[arg(long, value_name = "PATH", conflicts_with = "symbols")]
+    symbols_file: Option<PathBuf>,
+    
+    /// Start, in the forms `historical --start` takes
+    #[arg(long)]
+    start: String,
+    
+    /// End, in the same forms or now [default: now]
+    #[arg(long)]
+    end: Option<String>,
+    
+    /// Timeframe to compare (1Min, 5Min, 15Min, 1Hour, 1Day)
+    #[arg(short, long, default_value = "1Min")]
+    timeframe: String,
+    
+    /// Feed to evaluate (sip, iex, boats, otc)
+    #[arg(long, default_value = "iex")]
+    feed: String,
+    
+    /// Feed to compare it to
+    #[arg(long, default_value = "sip")]
+    reference: String,
+    
+    /// Largest price difference that is not reported, as a fraction of the reference price
+    #[arg(long, default_value_t = 0.001)]
+    tolerance: f64,
+    
+    /// Write every discrepancy to this file (missing and extra bars, and prices beyond --tolerance)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    
+    /// Format of --output
+    #[arg(short, long, value_enum, default_value_t = DataFormat::Csv)]
+    format: DataFormat,
+    
+    #[command(flatten)]
+    cache: CacheArgs,
+    
+    /// Page size for requests (max 10000)
+    #[arg(long, default_value = "1000")]
+    page_size: u32,
+    
+    /// Retries of each page request after a 429, a 5xx or a network error; other errors fail the symbol at once
+    #[arg(long, default_value_t = DEFAULT_REQUEST_RETRIES)]
+    max_request_retries: u32,
+}
+
+/// How `--output-dir` splits a download into files
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    /// One file per symbol
+    Symbol,
+}
+
+/// What `--data` downloads
+#[cfg(feature = "historical-trades")]
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoricalData {
+    /// Aggregated bars at --timeframe
+    Bars,
+    /// Tick-level trades from /v2/stocks/{symbol}/trades
+    Trades,
+    /// Opening and closing auction prints from /v2/stocks/auctions
+    Auctions,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct HistoricalBarData {
+    symbol: String,
+    timestamp: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: u64,
+    trade_count: u64,
+    vwap: f64,
+}
+
+#[cfg(feature = "parquet")]
+impl HistoricalBarData {
+    fn to_dataset_row(&self) -> Result<crate::dataset::BarRow> {
+        let timestamp = self.timestamp.parse::<chrono::DateTime<chrono::Utc>>()
+            .map_err(|_| anyhow::anyhow!("Invalid bar timestamp: {}", self.timestamp))?;
+        Ok(crate::dataset::BarRow {
+            timestamp,
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+            volume: self.volume,
+            trade_count: Some(self.trade_count),
+            vwap: Some(self.vwap),
+            received_at: None,
+        })
+    }
+}
+
+impl HistoricalBarData {
+    fn to_ohlcv(&self) -> Result<OhlcvBar> {
+        Ok(OhlcvBar {
+            symbol: self.symbol.clone(),
+            timestamp: bar_time(self)?,
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+            volume: self.volume,
+            trade_count: self.trade_count,
+            vwap: self.vwap,
+        })
+    }
+}
+
+impl From<&Bar> for HistoricalBarData {
+    fn from(bar: &Bar) -> Self {
+        Self {
+            symbol: "".to_string(), // Will be set by caller
+            timestamp: bar.t.clone(),
+            open: bar.o,
+            high: bar.h,
+            low: bar.l,
+            close: bar.c,
+            volume: bar.v,
+            trade_count: bar.n,
+            vwap: bar.vw,
+        }
+    }
+}
+
+pub(crate) fn validate_timeframe(timeframe: &str) -> Result<String> {
+    match timeframe.to_lowercase().as_str() {
+        "1min" => Ok("1Min".to_string()),
+        "5min" => Ok("5Min".to_string()),
+        "15min" => Ok("15Min".to_string()),
+        "30min" => Ok("30Min".to_string()),
+        "1hour" | "1h" => Ok("1Hour".to_string()),
+        "1day" | "1d" => Ok("1Day".to_string()),
+        "1week" | "1w" => Ok("1Week".to_string()),
+        "1month" | "1m" => Ok("1Month".to_string()),
+        _ => Err(anyhow::anyhow!("Invalid timeframe: {}. Supported: 1Min, 5Min, 15Min, 30Min, 1Hour, 1Day, 1Week, 1Month", timeframe)),
+    }
+}
+
+fn validate_feed(feed: &str) -> Result<StockDataFeed> {
+    match feed.to_lowercase().as_str() {
+        "sip" => Ok(StockDataFeed::Sip),
+        "iex" => Ok(StockDataFeed::Iex),
+        "boats" => Ok(StockDataFeed::Boats),
+        "otc" => Ok(StockDataFeed::Otc),
+        _ => Err(anyhow::anyhow!("Invalid feed: {}. Supported: sip, iex, boats, otc", feed)),
+    }
+}
+
+fn parse_symbols(symbols: &str) -> Vec<String> {
+    symbols
+        .split(',')
+        .map(|s| s.trim().to_uppercase())
+        .collect()
+}
+
+/// The symbols of --symbols or --symbols-file, or `None` without either
+pub(crate) fn given_symbols(symbols: Option<&str>, symbols_file: Option<&Path>) -> Result<Option<Vec<String>>> {
+    Ok(match (symbols, symbols_file) {
+        (Some(symbols), _) => Some(parse_symbols(symbols)),
+        (None, Some(path)) => {
+            let symbols = read_symbols_file(path)?;
+            info!(path = %path.display(), symbols = symbols.len(), "Read symbols file");
+            Some(symbols)
+        }
+        (None, None) => None,
+    })
+}
+
+#[cfg(any(test, feature = "options", feature = "corporate-actions"))]
+pub(crate) fn parse_date(date_str: &str) -> Result<String> {
+    let naive_date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")?;
+    Ok(naive_date.format("%Y-%m-%d").to_string())
+}
+
+/// Resolves date arguments now; when one is `yesterday`, the market calendar
+/// is asked for the last trading days so holidays are skipped too
+pub(crate) async fn date_resolver(values: &[Option<&str>]) -> DateResolver {
+    let resolver = DateResolver::new(Utc::now());
+    #[cfg(feature = "market-hours")]
+    if values.iter().flatten().any(|value| value.trim().eq_ignore_ascii_case("yesterday")) {
+        let today = resolver.today();
+        let calendar = async {
+            crate::market_hours::MarketCalendar::from_env()?
+                .calendar(today - chrono::Duration::days(14), today).await
+        };
+        match calendar.await {
+            Ok(days) => {
+                let days = days.iter().filter_map(|day| day.date.parse::<NaiveDate>().ok()).collect();
+                return resolver.with_trading_days(days);
+            }
+            Err(e) => warn!(error = %e, "Could not read the market calendar; yesterday skips weekends only"),
+        }
+    }
+    #[cfg(not(feature = "market-hours"))]
+    let _ = values;
+    resolver
+}
+
+fn format_bar_data(bar: &HistoricalBarData, format: &DataFormat) -> Result<String> {
+    match format {
+        DataFormat::Plain => {
+            let change = bar.close - bar.open;
+            let change_pct = (change / bar.open) * 100.0;
+            Ok(format!(
+                "📊 {}: {} | O: ${:.2} H: ${:.2} L: ${:.2} C: ${:.2} | Vol: {} | Change: ${:.2} ({:.2}%)",
+                bar.symbol,
+                bar.timestamp,
+                bar.open,
+                bar.high,
+                bar.low,
+                bar.close,
+                bar.volume,
+                change,
+                change_pct
+            ))
+        }
+        DataFormat::Json => {
+            Ok(serde_json::to_string(bar)?)
+        }
+        DataFormat::Csv => {
+            Ok(format!(
+                "{},{},{:.2},{:.2},{:.2},{:.2},{},{},{}",
+                bar.symbol,
+                bar.timestamp,
+                bar.open,
+                bar.high,
+                bar.low,
+                bar.close,
+                bar.volume,
+                bar.trade_count,
+                bar.vwap
+            ))
+        }
+    }
+}
+
+/// One page of a symbol, as written out
+struct Page {
+    records: usize,
+    last_timestamp: Option<String>,
+    next_page_token: Option<String>,
+}
+
+/// `--output-dir`, or the parts of `--merge`: the file each symbol is
+/// written to
+struct SplitFiles {
+    dir: PathBuf,
+    /// What follows the symbol in a file name, e.g. `_1Day_2024.csv.gz`
+    suffix: String,
+    format: DataFormat,
+    compression: Compression,
+    /// Files are written as `.partial` and published once their symbol is
+    /// complete; merge parts are written in place
+    publish: bool,
+}
+
+impl SplitFiles {
+    /// Files named `<SYMBOL>_<timeframe or data>_<years>.<format>`, where the
+    /// years are those the range from `start` to `end` covers
+    fn new(dir: &Path, kind: &str, start: DateTime<Utc>, end: DateTime<Utc>, format: &DataFormat, compression: Compression) -> Self {
+        // The end is exclusive, so a range ending on January 1st stays in the year before
+        let (first, last) = (start.year(), (end - chrono::Duration::seconds(1)).year().max(start.year()));
+        let years = if first == last { first.to_string() } else { format!("{}-{}", first, last) };
+        let extension = match format {
+            DataFormat::Csv => "csv",
+            DataFormat::Json => "jsonl",
+            DataFormat::Plain => "txt",
+        };
+        let name = compression.with_extension(Path::new(&format!("_{}_{}.{}", kind, years, extension)));
+        Self { dir: dir.to_path_buf(), suffix: name.to_string_lossy().into_owned(), format: format.clone(), compression, publish: false }
+    }
+    
+    /// Open the file of `symbol`, with `first_line` (the CSV header or JSON
+    /// metadata) first unless it is appended to; `resume` continues its
+    /// partial file
+    fn open(&self, symbol: &str, append: bool, resume: bool, first_line: Option<&str>) -> Result<OutputMode> {
+        let path = match self.publish {
+            true => crate::atomic::begin(&self.path(symbol), append, resume)?,
+            false => self.path(symbol),
+        };
+        let has_header = append && path.exists();
+        let output_mode = OutputMode::create_compressed_file_mode(&path, self.format.clone(), append, self.compression)?;
+        if let (Some(line), false) = (first_line, has_header) {
+            output_mode.writeln(line)?;
+        }
+        Ok(output_mode)
+    }
+    
+    fn file_name(&self, symbol: &str) -> String {
+        format!("{}{}", symbol.replace('/', "-"), self.suffix)
+    }
+    
+    fn path(&self, symbol: &str) -> PathBuf {
+        self.dir.join(self.file_name(symbol))
+    }
+}
+
+/// `manifest.json` of a download split by `--output-dir`
+#[derive(serde::Serialize)]
+struct Manifest<'a> {
+    data: &'a str,
+    timeframe: &'a str,
+    feed: &'a str,
+    start: &'a str,
+    end: &'a str,
+    format: &'a str,
+    files: Vec<ManifestFile>,
+    /// Symbols that failed; run again with --resume to finish them
+    failed: &'a [String],
+}
+
+/// One file of a split download
+#[derive(serde::Serialize)]
+struct ManifestFile {
+    symbol: String,
+    /// File name within the directory
+    file: String,
+    records: u64,
+    last_timestamp: Option<String>,
+    /// Every page was written
+    complete: bool,
+}
+
+/// The checkpoint of a download to a file, and where it is saved
+struct Progress {
+    checkpoint: Checkpoint,
+    path: PathBuf,
+}
+
+/// Page tokens of cached downloads name the chunk to fetch next
+const CHUNK_TOKEN_PREFIX: &str = "chunk:";
+
+/// Bars are requested unadjusted
+const ADJUSTMENT: &str = "raw";
+
+/// Requests for the bars of one timeframe and feed up to `end`
+pub(crate) struct BarRequests {
+    client: AlpacaClient,
+    retry: RetryPolicy,
+    timeframe: String,
+    feed: StockDataFeed,
+    end: String,
+    page_size: u32,
+    cache: Option<BarCache>,
+}
+
+impl BarRequests {
+    /// Requests for other commands that work on downloaded bars
+    pub(crate) fn new(timeframe: &str, feed: &str, end: &str, page_size: u32, cache: Option<BarCache>, max_request_retries: u32) -> Result<Self> {
+        if page_size > 10000 {
+            return Err(anyhow::anyhow!("Page size cannot exceed 10000"));
+        }
+        Ok(Self {
+            client: AlpacaClient::new()?,
+            retry: RetryPolicy::for_requests(max_request_retries),
+            timeframe: validate_timeframe(timeframe)?,
+            feed: validate_feed(feed)?,
+            end: end.to_string(),
+            page_size,
+            cache,
+        })
+    }
+    
+    /// The bars from `start` at `page_token`. With a cache, each page is a
+    /// whole cache chunk and its token names the next chunk; API tokens, e.g.
+    /// from the checkpoint of an uncached download, keep paging the API.
+    async fn fetch_page(&self, symbol: &str, start: &str, page_token: Option<&str>) -> Result<(Vec<HistoricalBarData>, Option<String>)> {
+        let chunk = match page_token {
+            Some(token) => match token.strip_prefix(CHUNK_TOKEN_PREFIX) {
+                Some(date) => date.parse::<NaiveDate>()?,
+                None => return self.fetch_api_page(symbol, start, &self.end, page_token).await,
+            },
+            None if self.cache.is_some() => parse_bound(start)?.date_naive(),
+            None => return self.fetch_api_page(symbol, start, &self.end, page_token).await,
+        };
+        self.fetch_chunk(symbol, start, chunk).await
+    }
+    
+    /// The bars of the cache chunk holding `date` that fall within `start`
+    /// and `self.end`, from the cache if it has them
+    async fn fetch_chunk(&self, symbol: &str, start: &str, date: NaiveDate) -> Result<(Vec<HistoricalBarData>, Option<String>)> {
+        let key = CacheKey::containing(symbol, &self.timeframe, self.feed.as_str(), ADJUSTMENT, date);
+        let chunk_start = key.start.and_time(NaiveTime::MIN).and_utc();
+        let chunk_end = key.end.and_time(NaiveTime::MIN).and_utc();
+        
+        let from = parse_bound(start)?;
+        let to = parse_bound(&self.end)?;
+        
+        // Only chunks that have ended are stored, and they are stored whole
+        let cache = self.cache.as_ref().filter(|_| key.is_complete(Utc::now()));
+        let cached = match cache {
+            Some(cache) => cache.get::<HistoricalBarData>(&key)?,
+            None => None,
+        };
+        let bars = match cached {
+            Some(bars) => {
+                tracing::debug!(chunk = %key, bars = bars.len(), "Served from cache");
+                bars
+            }
+            None => {
+                let (fetch_from, fetch_to) = match cache {
+                    Some(_) => (chunk_start, chunk_end),
+                    None => (from.max(chunk_start), to.min(chunk_end)),
+                };
+                let fetch_from = fetch_from.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+                let fetch_to = fetch_to.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+                let mut bars = Vec::new();
+                let mut page_token = None;
+                loop {
+                    let (page, next_page_token) = self.fetch_api_page(symbol, &fetch_from, &fetch_to, page_token.as_deref()).await?;
+                    for bar in page {
+                        if bar_time(&bar)? < chunk_end {
+                            bars.push(bar);
+                        }
+                    }
+                    match next_page_token {
+                        Some(token) => page_token = Some(token),
+                        None => break,
+                    }
+                }
+                if let Some(cache) = cache {
+                    cache.put(&key, &bars)?;
+                }
+                bars
+            }
+        };
+        
+        let mut in_range = Vec::with_capacity(bars.len());
+        for bar in bars {
+            let time = bar_time(&bar)?;
+            if time >= from && time <= to {
+                in_range.push(bar);
+            }
+        }
+        let next_page_token = (chunk_end <= to).then(|| format!("{}{}", CHUNK_TOKEN_PREFIX, key.end));
+        Ok((in_range, next_page_token))
+    }
+    
+    /// The bars from `start` to `end` at the API's `page_token`; an empty page
+    /// ends the range even if a token comes with it
+    async fn fetch_api_page(&self, symbol: &str, start: &str, end: &str, page_token: Option<&str>) -> Result<(Vec<HistoricalBarData>, Option<String>)> {
+        tracing::debug!(page_token = ?page_token, "Requesting page");
+        let bars_response = retry_request(&self.retry, || async {
+            RateLimiter::global().acquire().await;
+            self.client
+                .get_stock_bars(
+                    &[symbol],
+                    &self.timeframe,
+                    Some(start),
+                    Some(end),
+                    None, // adjustment
+                    page_token,
+                    Some(self.page_size),
+                    Some(&self.feed), // feed
+                )
+                .await
+                .map_err(crate::Error::alpaca)
+        })
+        .await?;
+        
+        let bars: Vec<HistoricalBarData> = bars_response.bars
+            .get(symbol)
+            .map(|bars| {
+                bars.iter()
+                    .map(|bar| {
+                        let mut hist_bar = HistoricalBarData::from(bar);
+                        hist_bar.symbol = symbol.to_string();
+                        hist_bar
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let next_page_token = if bars.is_empty() { None } else { bars_response.next_page_token };
+        Ok((bars, next_page_token))
+    }
+}
+
+/// A request bound, either RFC 3339 or a date, which means its midnight UTC
+pub(crate) fn parse_bound(bound: &str) -> Result<DateTime<Utc>> {
+    match bound.parse::<DateTime<Utc>>() {
+        Ok(time) => Ok(time),
+        Err(_) => Ok(bound.parse::<NaiveDate>()?.and_time(NaiveTime::MIN).and_utc()),
+    }
+}
+
+fn bar_time(bar: &HistoricalBarData) -> Result<DateTime<Utc>> {
+    bar.timestamp.parse()
+        .map_err(|_| anyhow::anyhow!("Invalid bar timestamp: {}", bar.timestamp))
+}
+
+/// `--validate`: checks the bars of a download and reports what it finds
+struct Validation {
+    validator: Mutex<BarValidator>,
+    report: Option<(OutputMode, PathBuf)>,
+}
+
+impl Validation {
+    fn check(&self, bar: &HistoricalBarData) -> Result<()> {
+        let values = BarValues {
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume,
+        };
+        for issue in self.validator.lock().unwrap().check(&bar.symbol, &bar.timestamp, &values) {
+            match &self.report {
+                Some((report, _)) => report.writeln(&issue.to_csv())?,
+                None => warn!(symbol = %issue.symbol, timestamp = %issue.timestamp, issue = issue.issue.as_str(), detail = %issue.detail, "Invalid bar"),
+            }
+        }
+        Ok(())
+    }
+    
+    /// Log how many issues of each kind were found
+    fn finish(&self) -> Result<()> {
+        let counts = self.validator.lock().unwrap().counts().clone();
+        let report = self.report.as_ref().map(|(report, path)| {
+            report.close()?;
+            Ok::<_, crate::Error>(path.display().to_string())
+        }).transpose()?;
+        if counts.is_empty() {
+            info!("Validation found no issues");
+        } else {
+            let issues = counts.iter().map(|(kind, count)| format!("{}={}", kind.as_str(), count)).collect::<Vec<_>>().join(",");
+            warn!(issues = %issues, report = ?report, "Validation found issues");
+        }
+        Ok(())
+    }
+}
+
+/// What a download fetches and where it writes it
+struct Download {
+    bars: BarRequests,
+    start: String,
+    format: DataFormat,
+    #[cfg(feature = "historical-trades")]
+    data: HistoricalData,
+    #[cfg(feature = "historical-trades")]
+    tick_client: Option<crate::historical_trades::HistoricalTradesClient>,
+    output_mode: OutputMode,
+    #[cfg(feature = "parquet")]
+    dataset: Option<crate::dataset::DatasetWriter>,
+    validation: Option<Validation>,
+    /// Bars are written in this layout instead of `format`
+    export: Option<ExportProfile>,
+    /// Workbook of `--xlsx`, on the sheet of the symbol being downloaded
+    xlsx: Option<Mutex<XlsxWriter>>,
+}
+
+impl Download {
+    /// Fetch the page of `symbol` at `page_token` and write it out
+    async fn write_page(&self, symbol: &str, page_token: Option<&str>) -> Result<Page> {
+        let format = &self.format;
+        let bars = &self.bars;
+        
+        #[cfg(feature = "historical-trades")]
+        if let Some(client) = &self.tick_client {
+            if self.data == HistoricalData::Auctions {
+                let (prints, next_page_token) = client
+                    .fetch_auctions_page(symbol, &self.start, &bars.end, &bars.feed, bars.page_size, page_token)
+                    .await?;
+                for print in &prints {
+                    self.output_mode.writeln(&print.format(format)?)?;
+                }
+                return Ok(Page {
+                    records: prints.len(),
+                    last_timestamp: prints.last().map(|print| print.timestamp.clone()),
+                    next_page_token,
+                });
+            }
+            
+            let (trades, next_page_token) = client
+                .fetch_trades_page(symbol, &self.start, &bars.end, &bars.feed, bars.page_size, page_token)
+                .await?;
+            for trade in &trades {
+                self.output_mode.writeln(&trade.format(format)?)?;
+                #[cfg(feature = "parquet")]
+                if let Some(dataset) = &self.dataset {
+                    dataset.write_trade(&trade.symbol, trade.to_dataset_row()?)?;
+                }
+            }
+            return Ok(Page {
+                records: trades.len(),
+                last_timestamp: trades.last().map(|trade| trade.timestamp.clone()),
+                next_page_token,
+            });
+        }
+        
+        let (bars, next_page_token) = bars.fetch_page(symbol, &self.start, page_token).await?;
+        for bar in &bars {
+            if let Some(validation) = &self.validation {
+                validation.check(bar)?;
+            }
+            let formatted = match self.export {
+                Some(profile) => profile.format(&bar.to_ohlcv()?),
+                None => format_bar_data(bar, format)?,
+            };
+            self.output_mode.writeln(&formatted)?;
+            if let Some(xlsx) = &self.xlsx {
+                xlsx.lock().unwrap().write_row(&bar_cells(&bar.to_ohlcv()?))?;
+            }
+            #[cfg(feature = "parquet")]
+            if let Some(dataset) = &self.dataset {
+                dataset.write_bar(&bar.symbol, bar.to_dataset_row()?)?;
+            }
+        }
+        Ok(Page {
+            records: bars.len(),
+            last_timestamp: bars.last().map(|bar| bar.timestamp.clone()),
+            next_page_token,
+        })
+    }
+    
+    /// Write every page of `symbol` from `page_token` on, checkpointing
+    /// after each one
+    #[tracing::instrument(name = "fetch", skip(self, page_token, progress), fields(feed = self.bars.feed.as_str()))]
+    async fn download_symbol(&self, symbol: &str, mut page_token: Option<String>, progress: &mut Option<Progress>) -> Result<usize> {
+        info!("Fetching historical data");
+        let mut records = 0;
+        loop {
+            let page = self.write_page(symbol, page_token.as_deref()).await?;
+            records += page.records;
+            if let Some(progress) = progress {
+                progress.checkpoint.record_page(
+                    symbol,
+                    page.next_page_token.as_deref(),
+                    page.last_timestamp.as_deref(),
+                    page.records as u64,
+                );
+                self.flush()?;
+                progress.checkpoint.save(&progress.path)?;
+            }
+            match page.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+        info!(records, "Retrieved records");
+        Ok(records)
+    }
+    
+    /// Push everything written so far to disk, so a checkpoint never runs
+    /// ahead of the data
+    fn flush(&self) -> Result<()> {
+        self.output_mode.flush()?;
+        #[cfg(feature = "parquet")]
+        if let Some(dataset) = &self.dataset {
+            dataset.flush_all()?;
+        }
+        if let Some((report, _)) = self.validation.as_ref().and_then(|validation| validation.report.as_ref()) {
+            report.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Fetch the bars or trades of every symbol and write them out
+pub async fn run(mut args: HistoricalArgs, global: &GlobalArgs) -> Result<()> {
+    match args.command {
+        Some(HistoricalCommand::Sync(sync)) => return run_sync(*sync, global).await,
+        #[cfg(feature = "market-hours")]
+        Some(HistoricalCommand::Backfill(backfill)) => return run_backfill(*backfill, global).await,
+        Some(HistoricalCommand::CompareFeeds(compare)) => return run_compare_feeds(*compare, global).await,
+        None => {}
+    }
+    global.init(false)?;
+    
+    // Required without a subcommand
+    let Some(symbols) = given_symbols(args.symbols.as_deref(), args.symbols_file.as_deref())? else {
+        return Err(anyhow::anyhow!("--symbols or --symbols-file is required"));
+    };
+    
+    // Parse dates
+    let dates = date_resolver(&[args.start.as_deref(), args.end.as_deref()]).await;
+    let start_date = match (&args.start, args.days_back) {
+        (Some(start), _) => dates.resolve(start, RangeEnd::Start)?,
+        (None, Some(days)) => dates.days_back(days),
+        (None, None) => return Err(anyhow::anyhow!("--start or --days-back is required")),
+    };
+    let end_date = dates.resolve(args.end.as_deref().unwrap_or("now"), RangeEnd::End)?;
+    
+    if parse_bound(&start_date)? >= parse_bound(&end_date)? {
+        return Err(anyhow::anyhow!("Start date must be before end date"));
+    }
+    
+    // Validate timeframe
+    let timeframe = validate_timeframe(&args.timeframe)?;
+    
+    // Validate data feed
+    let feed = validate_feed(&args.feed)?;
+    
+    // Validate page size
+    if args.page_size > 10000 {
+        return Err(anyhow::anyhow!("Page size cannot exceed 10000"));
+    }
+    
+    info!(
+        symbols = ?symbols,
+        start = %start_date,
+        end = %end_date,
+        timeframe = %timeframe,
+        feed = feed.as_str(),
+        format = ?args.out.format,
+        output = ?args.out.output,
+        "Historical data retrieval"
+    );
+    
+    #[cfg(feature = "historical-trades")]
+    let data_name = format!("{:?}", args.data).to_lowercase();
+    #[cfg(not(feature = "historical-trades"))]
+    let data_name = "bars".to_string();
+    // The range as given, so a download ending `now` can still be resumed
+    // later; the page tokens saved carry on from where it stopped
+    let range = match (&args.start, args.days_back) {
+        (Some(start), _) => start.clone(),
+        (None, days) => format!("{}d", days.unwrap_or_default()),
+    };
+    let download_id = format!("{} {} {}..{} {} {}", data_name, symbols.join(","), range, args.end.as_deref().unwrap_or("now"), timeframe, feed.as_str());
+    
+    if args.merge && data_name != "bars" {
+        return Err(anyhow::anyhow!("--merge interleaves bars, not trades or auctions"));
+    }
+    if args.export_profile.is_some() {
+        if data_name != "bars" {
+            return Err(anyhow::anyhow!("--export-profile writes bars, not trades or auctions"));
+        }
+        if args.output_dir.is_none() && symbols.len() > 1 {
+            return Err(anyhow::anyhow!("--export-profile layouts have no symbol column; use --output-dir for several symbols"));
+        }
+        args.out.format = DataFormat::Csv;
+    }
+    if args.xlsx.is_some() && data_name != "bars" {
+        return Err(anyhow::anyhow!("--xlsx writes bars, not trades or auctions"));
+    }
+    if args.dry_run {
+        if data_name != "bars" {
+            return Err(anyhow::anyhow!("--dry-run estimates bar downloads, not trades or auctions"));
+        }
+        return dry_run(&symbols, &start_date, &end_date, &timeframe, &feed, args.page_size, args.cache.open().as_ref());
+    }
+    
+    // Downloads to a file keep a checkpoint next to it, split downloads one
+    // in their directory. Merged downloads are split into JSON parts first,
+    // next to the output so they can be resumed.
+    let output_path = args.out.output.as_ref().map(|path| args.compress.with_extension(path));
+    let merge_parts_dir = args.merge.then(|| match &output_path {
+        Some(path) => {
+            let mut parts = path.as_os_str().to_owned();
+            parts.push(".parts");
+            PathBuf::from(parts)
+        }
+        None => std::env::temp_dir().join(format!("historical-merge-{}", std::process::id())),
+    });
+    let split_files = |dir: &Path, format: &DataFormat, compression: Compression| -> Result<SplitFiles> {
+        std::fs::create_dir_all(dir)?;
+        let kind = if data_name == "bars" { timeframe.clone() } else { data_name.clone() };
+        Ok(SplitFiles::new(dir, &kind, parse_bound(&start_date)?, parse_bound(&end_date)?, format, compression))
+    };
+    let mut split = match (&args.output_dir, &merge_parts_dir) {
+        (Some(dir), _) => Some(SplitFiles { publish: true, ..split_files(dir, &args.out.format, args.compress)? }),
+        (None, Some(parts)) => Some(split_files(parts, &DataFormat::Json, Compression::None)?),
+        (None, None) => None,
+    };
+    // zipline's csvdir bundle finds each symbol by its file name
+    if let (Some(split), Some(ExportProfile::Zipline)) = (&mut split, args.export_profile) {
+        split.suffix = args.compress.with_extension(Path::new(".csv")).to_string_lossy().into_owned();
+    }
+    let checkpoint_path = match &split {
+        Some(split) => Some(split.dir.join("checkpoint.json")),
+        None => output_path.as_deref().map(Checkpoint::path_for),
+    };
+    let resumed = match (&checkpoint_path, args.resume) {
+        (None, true) => return Err(anyhow::anyhow!("--resume needs --output or --output-dir")),
+        (Some(path), true) => match Checkpoint::load(path)? {
+            Some(checkpoint) if checkpoint.download != download_id => {
+                return Err(anyhow::anyhow!(
+                    "Checkpoint {} is for a different download ({}); repeat its options or delete it",
+                    path.display(),
+                    checkpoint.download
+                ));
+            }
+            Some(checkpoint) => {
+                let done = checkpoint.symbols.values().filter(|progress| progress.done).count();
+                info!(checkpoint = %path.display(), done, "Resuming download");
+                Some(checkpoint)
+            }
+            None => {
+                warn!(checkpoint = %path.display(), "No checkpoint to resume from, starting from the beginning");
+                None
+            }
+        },
+        _ => None,
+    };
+    
+    // Create output mode; a resumed download appends to what it wrote before,
+    // except a merged one, which writes every part out again at the end.
+    // Split downloads open each symbol's file in turn instead.
+    let output_mode = if args.output_dir.is_some() {
+        OutputMode::create_console_mode(args.out.format.clone())
+    } else if let Some(output_path) = &output_path {
+        let resume = resumed.is_some() && !args.merge;
+        let append = args.out.append || resume;
+        let partial = crate::atomic::begin(output_path, append, resume)?;
+        OutputMode::create_compressed_file_mode(&partial, args.out.format.clone(), append, args.compress)?
+    } else {
+        OutputMode::create_console_mode(args.out.format.clone())
+    };
+    
+    #[cfg(all(feature = "historical-trades", feature = "parquet"))]
+    if args.data == HistoricalData::Auctions && args.dataset.is_some() {
+        return Err(anyhow::anyhow!("--dataset supports bars and trades, not auctions"));
+    }
+    
+    #[cfg(feature = "historical-trades")]
+    let tick_client = match args.data {
+        HistoricalData::Bars => None,
+        HistoricalData::Trades | HistoricalData::Auctions => Some(crate::historical_trades::HistoricalTradesClient::from_env()?.with_max_retries(args.max_request_retries)),
+    };
+    
+    #[cfg(feature = "historical-trades")]
+    let csv_header = match args.data {
+        HistoricalData::Bars => BARS_CSV_HEADER,
+        HistoricalData::Trades => crate::historical_trades::TRADES_CSV_HEADER,
+        HistoricalData::Auctions => crate::historical_trades::AUCTIONS_CSV_HEADER,
+    };
+    #[cfg(not(feature = "historical-trades"))]
+    let csv_header = BARS_CSV_HEADER;
+    let csv_header = match args.export_profile {
+        Some(profile) => profile.header(),
+        None => Some(csv_header),
+    };
+    
+    let metadata = DownloadMetadata {
+        timeframe: (data_name == "bars").then(|| timeframe.clone()),
+        adjustment: (data_name == "bars").then(|| ADJUSTMENT.to_string()),
+        ..DownloadMetadata::new(&data_name, feed.as_str(), symbols.clone(), &start_date, &end_date)
+    };
+    
+    // Write CSV header if needed, and the metadata of a new JSON file
+    if let (DataFormat::Csv, Some(header)) = (&args.out.format, csv_header) {
+        if (resumed.is_none() || args.merge) && args.output_dir.is_none() {
+            output_mode.writeln(header)?;
+        }
+    }
+    if let (DataFormat::Json, Some(_)) = (&args.out.format, &output_path) {
+        if (resumed.is_none() || args.merge) && !args.out.append && args.output_dir.is_none() {
+            output_mode.writeln(&metadata.json_line()?)?;
+        }
+    }
+    let (output_mode, merged_output) = if args.merge {
+        (OutputMode::create_console_mode(DataFormat::Json), Some(output_mode))
+    } else {
+        (output_mode, None)
+    };
+    
+    #[cfg(feature = "parquet")]
+    let dataset = match &args.dataset {
+        Some(dir) => Some(crate::dataset::DatasetWriter::new(dir)?),
+        None => None,
+    };
+    
+    #[cfg(feature = "historical-trades")]
+    if args.validate && args.data != HistoricalData::Bars {
+        return Err(anyhow::anyhow!("--validate checks bars, not trades or auctions"));
+    }
+    let validation = if args.validate {
+        let report_path = match (&args.output_dir, &output_path) {
+            (Some(dir), _) => Some(dir.join("validation.csv")),
+            (None, Some(output_path)) => {
+                let mut path = output_path.as_os_str().to_owned();
+                path.push(".validation.csv");
+                Some(PathBuf::from(path))
+            }
+            (None, None) => None,
+        };
+        let report = match report_path {
+            Some(path) => {
+                let append = resumed.is_some() && path.exists();
+                let report = OutputMode::create_file_mode(&path, DataFormat::Csv, append)?;
+                if !append {
+                    report.writeln(VALIDATION_CSV_HEADER)?;
+                }
+                Some((report, path))
+            }
+            None => None,
+        };
+        Some(Validation { validator: Mutex::new(BarValidator::new()), report })
+    } else {
+        None
+    };
+    
+    // Initialize Alpaca API client
+    let (range_start, range_end) = (start_date.clone(), end_date.clone());
+    let mut download = Download {
+        bars: BarRequests {
+            client: AlpacaClient::new()?,
+            retry: RetryPolicy::for_requests(args.max_request_retries),
+            timeframe,
+            feed,
+            end: end_date,
+            page_size: args.page_size,
+            cache: args.cache.open(),
+        },
+        start: start_date,
+        format: if args.merge { DataFormat::Json } else { args.out.format.clone() },
+        #[cfg(feature = "historical-trades")]
+        data: args.data,
+        #[cfg(feature = "historical-trades")]
+        tick_client,
+        output_mode,
+        #[cfg(feature = "parquet")]
+        dataset,
+        validation,
+        export: args.export_profile,
+        xlsx: match &args.xlsx {
+            Some(path) => Some(Mutex::new(XlsxWriter::create(&crate::atomic::partial_path(path))?)),
+            None => None,
+        },
+    };
+    let mut progress = checkpoint_path.map(|path| Progress {
+        checkpoint: resumed.unwrap_or_else(|| Checkpoint::new(download_id)),
+        path,
+    });
+    
+    // Fetch data for each symbol
+    let mut total_records = 0;
+    let mut failed = Vec::new();
+    for symbol in &symbols {
+        let mut page_token = None;
+        if let Some(progress) = &progress {
+            if progress.checkpoint.is_done(symbol) {
+                info!(symbol = %symbol, "Already downloaded, skipping");
+                continue;
+            }
+            page_token = progress.checkpoint.resume_token(symbol);
+            if let Some(token) = &page_token {
+                info!(symbol = %symbol, page_token = %token, "Resuming symbol");
+            }
+        }
+        
+        if let Some(split) = &split {
+            let resume = page_token.is_some();
+            let first_line = match (&split.format, split.publish) {
+                (DataFormat::Csv, _) => csv_header.map(str::to_string),
+                (DataFormat::Json, true) => Some(metadata.for_symbols(vec![symbol.clone()]).json_line()?),
+                _ => None,
+            };
+            download.output_mode = split.open(symbol, args.out.append || resume, resume, first_line.as_deref())?;
+        }
+        if let Some(xlsx) = &download.xlsx {
+            xlsx.lock().unwrap().start_sheet(symbol, &BAR_COLUMNS)?;
+        }
+        
+        let result = download.download_symbol(symbol, page_token, &mut progress).await;
+        if split.is_some() {
+            download.output_mode.close()?;
+        }
+        match result {
+            Ok(records) => {
+                total_records += records;
+                if records == 0 {
+                    warn!(symbol = %symbol, "No data found for symbol");
+                }
+                if let Some(split) = split.as_ref().filter(|split| split.publish) {
+                    if !matches!(split.format, DataFormat::Json) {
+                        metadata.for_symbols(vec![symbol.clone()]).write_sidecar(&split.path(symbol))?;
+                    }
+                    crate::atomic::publish(&split.path(symbol))?;
+                }
+            }
+            Err(e) => {
+                error!(symbol = %symbol, error = %e, "Error fetching data");
+                failed.push(symbol.clone());
+            }
+        }
+    }
+    
+    download.output_mode.close()?;
+    #[cfg(feature = "parquet")]
+    if let Some(dataset) = &download.dataset {
+        dataset.flush_all()?;
+        metadata.write_sidecar(args.dataset.as_deref().expect("dataset given"))?;
+    }
+    if let Some(validation) = &download.validation {
+        validation.finish()?;
+    }
+    if let Some(xlsx) = download.xlsx.take() {
+        xlsx.into_inner().unwrap().finish()?;
+    }
+    if let (Some(output), Some(parts)) = (&merged_output, &split) {
+        let records = merge_parts(parts, &symbols, output, &args.out.format)?;
+        output.close()?;
+        info!(records, "Merged symbols by timestamp");
+    }
+    // Files only get their final name once complete; a failed symbol keeps
+    // them partial for --resume
+    let finished: Vec<&PathBuf> = output_path.iter().filter(|_| args.output_dir.is_none()).chain(&args.xlsx).collect();
+    for path in finished {
+        if failed.is_empty() {
+            if !matches!(args.out.format, DataFormat::Json) || Some(path) == args.xlsx.as_ref() {
+                metadata.write_sidecar(path)?;
+            }
+            let checksum = crate::atomic::publish(path)?;
+            info!(file = %path.display(), sha256 = %checksum, "Published");
+        } else {
+            warn!(file = %crate::atomic::partial_path(path).display(), "Left incomplete");
+        }
+    }
+    if let (Some(split), Some(progress), true) = (&split, &progress, args.output_dir.is_some()) {
+        let files = symbols.iter().filter_map(|symbol| {
+            let symbol_progress = progress.checkpoint.symbols.get(symbol)?;
+            Some(ManifestFile {
+                symbol: symbol.clone(),
+                file: split.file_name(symbol),
+                records: symbol_progress.records,
+                last_timestamp: symbol_progress.last_timestamp.clone(),
+                complete: symbol_progress.done,
+            })
+        }).collect();
+        let manifest = Manifest {
+            data: &data_name,
+            timeframe: &download.bars.timeframe,
+            feed: download.bars.feed.as_str(),
+            start: &range_start,
+            end: &range_end,
+            format: match args.out.format {
+                DataFormat::Csv => "csv",
+                DataFormat::Json => "json",
+                DataFormat::Plain => "plain",
+            },
+            files,
+            failed: &failed,
+        };
+        let path = split.dir.join("manifest.json");
+        crate::atomic::write(&path, serde_json::to_string_pretty(&manifest)?)?;
+        info!(manifest = %path.display(), "Wrote manifest");
+    }
+    if let Some(progress) = &progress {
+        if failed.is_empty() {
+            match std::fs::remove_file(&progress.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        } else {
+            progress.checkpoint.save(&progress.path)?;
+            warn!(symbols = %failed.join(","), checkpoint = %progress.path.display(), "Some symbols failed; run again with --resume to retry them");
+        }
+    }
+    // The parts are only kept to resume from
+    if let Some(parts) = merge_parts_dir.filter(|_| failed.is_empty()) {
+        std::fs::remove_dir_all(parts)?;
+    }
+    info!(symbols = symbols.len(), records = total_records, output = ?output_path.as_ref().or(args.output_dir.as_ref()), "Retrieval complete");
+    
+    Ok(())
+}
+
+/// Print the requests and bars `symbols` would take and how long the rate
+/// limit makes them; chunks already in `cache` need no requests
+fn dry_run(symbols: &[String], start: &str, end: &str, timeframe: &str, feed: &StockDataFeed, page_size: u32, cache: Option<&BarCache>) -> Result<()> {
+    use crate::estimate::{estimate_range, rate_limited_duration, Estimate};
+    
+    let from = parse_bound(start)?;
+    let to = parse_bound(end)?;
+    let now = Utc::now();
+    let mut total = Estimate::default();
+    let mut cached_chunks = 0;
+    for symbol in symbols {
+        let mut estimate = Estimate::default();
+        match cache {
+            // Mirrors `BarRequests::fetch_chunk`: finished chunks are fetched
+            // whole unless cached, the current one only up to `end`
+            Some(cache) => {
+                let mut key = CacheKey::containing(symbol, timeframe, feed.as_str(), ADJUSTMENT, from.date_naive());
+                while key.start.and_time(NaiveTime::MIN).and_utc() < to {
+                    let chunk_start = key.start.and_time(NaiveTime::MIN).and_utc();
+                    let chunk_end = key.end.and_time(NaiveTime::MIN).and_utc();
+                    let range = estimate_range(from.max(chunk_start), to.min(chunk_end), timeframe, page_size)?;
+                    estimate.bars += range.bars;
+                    if !key.is_complete(now) {
+                        estimate.requests += range.requests;
+                    } else if cache.contains(&key) {
+                        cached_chunks += 1;
+                    } else {
+                        estimate.requests += estimate_range(chunk_start, chunk_end, timeframe, page_size)?.requests;
+                    }
+                    key = key.next();
+                }
+            }
+            None => estimate = estimate_range(from, to, timeframe, page_size)?,
+        }
+        println!("{}: {} requests, up to {} bars", symbol, estimate.requests, estimate.bars);
+        total += estimate;
+    }
+    
+    let per_minute = RateLimiter::global().requests_per_minute();
+    let duration = rate_limited_duration(total.requests, per_minute).as_secs();
+    println!(
+        "Total: {} symbol{}, {} requests, up to {} bars, {} cached chunks; at least {}h {:02}m {:02}s at {} requests/minute",
+        symbols.len(),
+        if symbols.len() == 1 { "" } else { "s" },
+        total.requests,
+        total.bars,
+        cached_chunks,
+        duration / 3600,
+        duration / 60 % 60,
+        duration % 60,
+        per_minute
+    );
+    Ok(())
+}
+
+/// Interleave the JSON part files of `symbols` by timestamp, then symbol, and
+/// write the bars to `output`; returns the number written
+fn merge_parts(parts: &SplitFiles, symbols: &[String], output: &OutputMode, format: &DataFormat) -> Result<usize> {
+    let mut symbols = symbols.to_vec();
+    symbols.sort();
+    let mut streams = Vec::new();
+    for symbol in &symbols {
+        let path = parts.path(symbol);
+        if !path.exists() {
+            continue;
+        }
+        let lines = std::io::BufReader::new(std::fs::File::open(&path)?).lines();
+        streams.push(lines.map(|line| -> Result<(DateTime<Utc>, HistoricalBarData)> {
+            let bar: HistoricalBarData = serde_json::from_str(&line?)?;
+            Ok((bar_time(&bar)?, bar))
+        }));
+    }
+    let mut written = 0;
+    // Unreadable lines sort first, so they stop the merge right away
+    for item in merge_sorted(streams, |item| item.as_ref().ok().map(|(time, _)| *time)) {
+        let (_, bar) = item?;
+        output.writeln(&format_bar_data(&bar, format)?)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Where `historical sync` adds bars and the last bar each store already has
+struct SyncTargets {
+    format: DataFormat,
+    output: Option<OutputMode>,
+    output_last: HashMap<String, DateTime<Utc>>,
+    #[cfg(feature = "parquet")]
+    dataset: Option<crate::dataset::DatasetWriter>,
+    #[cfg(feature = "parquet")]
+    dataset_last: HashMap<String, DateTime<Utc>>,
+}
+
+impl SyncTargets {
+    /// The stores that are synced, for `sync::resume_from`
+    fn stores(&self) -> Vec<&HashMap<String, DateTime<Utc>>> {
+        let mut stores = Vec::new();
+        if self.output.is_some() {
+            stores.push(&self.output_last);
+        }
+        #[cfg(feature = "parquet")]
+        if self.dataset.is_some() {
+            stores.push(&self.dataset_last);
+        }
+        stores
+    }
+    
+    /// Write each bar to the stores that do not have it yet; returns the
+    /// number of bars that were new to any of them
+    fn write(&self, bars: &[HistoricalBarData]) -> Result<usize> {
+        let mut written = 0;
+        for bar in bars {
+            let timestamp = bar_time(bar)?;
+            let is_new = |last: &HashMap<String, DateTime<Utc>>| last.get(&bar.symbol).is_none_or(|last| timestamp > *last);
+            let mut new = false;
+            if let Some(output) = &self.output {
+                if is_new(&self.output_last) {
+                    output.writeln(&format_bar_data(bar, &self.format)?)?;
+                    new = true;
+                }
+            }
+            #[cfg(feature = "parquet")]
+            if let Some(dataset) = &self.dataset {
+                if is_new(&self.dataset_last) {
+                    dataset.write_bar(&bar.symbol, bar.to_dataset_row()?)?;
+                    new = true;
+                }
+            }
+            written += new as usize;
+        }
+        Ok(written)
+    }
+}
+
+#[tracing::instrument(name = "sync", skip(requests, targets), fields(feed = requests.feed.as_str()))]
+async fn sync_symbol(requests: &BarRequests, targets: &SyncTargets, symbol: &str, start: &str) -> Result<usize> {
+    info!("Syncing bars");
+    let mut records = 0;
+    let mut page_token = None;
+    loop {
+        let (bars, next_page_token) = requests.fetch_page(symbol, start, page_token.as_deref()).await?;
+        records += targets.write(&bars)?;
+        match next_page_token {
+            Some(token) => page_token = Some(token),
+            None => break,
+        }
+    }
+    info!(records, "Added bars");
+    Ok(records)
+}
+
+/// Fetch the bars after the last stored one of each symbol and append them
+async fn run_sync(args: SyncArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let Some(symbols) = given_symbols(args.symbols.as_deref(), args.symbols_file.as_deref())? else {
+        return Err(anyhow::anyhow!("--symbols or --symbols-file is required"));
+    };
+    let dates = date_resolver(&[args.start.as_deref(), args.end.as_deref()]).await;
+    let start = args.start.as_deref().map(|start| dates.resolve(start, RangeEnd::Start)).transpose()?;
+    let end = dates.resolve(args.end.as_deref().unwrap_or("now"), RangeEnd::End)?;
+    let timeframe = validate_timeframe(&args.timeframe)?;
+    let feed = validate_feed(&args.feed)?;
+    if args.page_size > 10000 {
+        return Err(anyhow::anyhow!("Page size cannot exceed 10000"));
+    }
+    
+    #[cfg(feature = "parquet")]
+    let has_dataset = args.dataset.is_some();
+    #[cfg(not(feature = "parquet"))]
+    let has_dataset = false;
+    if args.output.is_none() && !has_dataset {
+        return Err(anyhow::anyhow!("Nothing to sync: pass --output and/or --dataset"));
+    }
+    
+    // Read where each store ends before appending to it
+    let (output, output_last) = match &args.output {
+        Some(path) => {
+            let output_last = crate::sync::last_timestamps(path, &args.format)?;
+            let existed = path.exists();
+            let output = OutputMode::create_compressed_file_mode(path, args.format.clone(), true, Compression::from_extension(path))?;
+            if !existed && matches!(args.format, DataFormat::Csv) {
+                output.writeln(BARS_CSV_HEADER)?;
+            }
+            (Some(output), output_last)
+        }
+        None => (None, HashMap::new()),
+    };
+    let targets = SyncTargets {
+        format: args.format.clone(),
+        output,
+        output_last,
+        #[cfg(feature = "parquet")]
+        dataset: args.dataset.as_deref().map(crate::dataset::DatasetWriter::new).transpose()?,
+        #[cfg(feature = "parquet")]
+        dataset_last: match &args.dataset {
+            Some(dir) => crate::dataset::last_timestamps(dir, "bars")?,
+            None => HashMap::new(),
+        },
+    };
+    
+    info!(
+        symbols = ?symbols,
+        end = %end,
+        timeframe = %timeframe,
+        feed = feed.as_str(),
+        output = ?args.output,
+        "Historical sync"
+    );
+    
+    let requests = BarRequests {
+        client: AlpacaClient::new()?,
+        retry: RetryPolicy::for_requests(args.max_request_retries),
+        timeframe,
+        feed,
+        end,
+        page_size: args.page_size,
+        cache: args.cache.open(),
+    };
+    let stores = targets.stores();
+    let mut total_records = 0;
+    let mut failed = Vec::new();
+    for symbol in &symbols {
+        // The last stored bar is fetched again and skipped by `write`
+        let symbol_start = match (crate::sync::resume_from(&stores, symbol), &start) {
+            (Some(last), _) => last.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            (None, Some(start)) => start.clone(),
+            (None, None) => {
+                error!(symbol = %symbol, "No stored bars to continue from; pass --start for new symbols");
+                failed.push(symbol.clone());
+                continue;
+            }
+        };
+        match sync_symbol(&requests, &targets, symbol, &symbol_start).await {
+            Ok(records) => total_records += records,
+            Err(e) => {
+                error!(symbol = %symbol, error = %e, "Error syncing bars");
+                failed.push(symbol.clone());
+            }
+        }
+    }
+    
+    if let Some(output) = &targets.output {
+        output.close()?;
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(dataset) = &targets.dataset {
+        dataset.flush_all()?;
+    }
+    info!(symbols = symbols.len(), records = total_records, "Sync complete");
+    if !failed.is_empty() {
+        return Err(anyhow::anyhow!("Could not sync {}", failed.join(",")));
+    }
+    Ok(())
+}
+
+/// Find the trading days each symbol lacks in the stores and, with --fix,
+/// fetch them and add the bars each store does not have
+#[cfg(feature = "market-hours")]
+async fn run_backfill(args: BackfillArgs, global: &GlobalArgs) -> Result<()> {
+    use crate::gaps::{bar_duration, find_gaps, TradingDay};
+    use crate::market_hours::{MarketCalendar, MarketSession};
+    use std::collections::HashSet;
+    
+    global.init(false)?;
+    
+    let timeframe = validate_timeframe(&args.timeframe)?;
+    let bar = bar_duration(&timeframe)?;
+    let feed = validate_feed(&args.feed)?;
+    if !(0.0..=1.0).contains(&args.min_coverage) {
+        return Err(anyhow::anyhow!("--min-coverage must be between 0 and 1"));
+    }
+    if args.page_size > 10000 {
+        return Err(anyhow::anyhow!("Page size cannot exceed 10000"));
+    }
+    let dates = date_resolver(&[args.start.as_deref(), args.end.as_deref()]).await;
+    let start = args.start.as_deref().map(|start| dates.resolve_day(start, RangeEnd::Start)).transpose()?;
+    let end = args.end.as_deref().map(|end| dates.resolve_day(end, RangeEnd::End)).transpose()?;
+    
+    // What each store holds
+    let mut stores: Vec<HashMap<String, Vec<DateTime<Utc>>>> = Vec::new();
+    if let Some(path) = &args.output {
+        stores.push(crate::sync::stored_timestamps(path, &args.format)?);
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(dir) = &args.dataset {
+        stores.push(crate::dataset::stored_timestamps(dir, "bars")?);
+    }
+    if stores.is_empty() {
+        return Err(anyhow::anyhow!("Nothing to check: pass --output and/or --dataset"));
+    }
+    
+    let symbols = match given_symbols(args.symbols.as_deref(), args.symbols_file.as_deref())? {
+        Some(symbols) => symbols,
+        None => {
+            let mut symbols: Vec<String> = stores.iter().flat_map(|store| store.keys().cloned()).collect();
+            symbols.sort();
+            symbols.dedup();
+            symbols
+        }
+    };
+    
+    // The days to check for each symbol, from its stored bars unless given
+    let mut ranges = Vec::new();
+    for symbol in &symbols {
+        let stored = stores.iter().filter_map(|store| store.get(symbol)).flatten();
+        let first = stored.clone().min().map(|time| time.date_naive());
+        let last = stored.max().map(|time| time.date_naive());
+        match (start.or(first), end.or(last)) {
+            (Some(first), Some(last)) if first <= last => ranges.push((symbol, first, last)),
+            (Some(_), Some(_)) => warn!(symbol = %symbol, "Nothing to check: --start is after --end"),
+            _ => warn!(symbol = %symbol, "No stored bars; pass --start and --end to check it"),
+        }
+    }
+    let (Some(calendar_start), Some(calendar_end)) = (
+        ranges.iter().map(|(_, first, _)| *first).min(),
+        ranges.iter().map(|(_, _, last)| *last).max(),
+    ) else {
+        return Ok(());
+    };
+    
+    let mut days = Vec::new();
+    for day in MarketCalendar::from_env()?.calendar(calendar_start, calendar_end).await? {
+        let date = day.date.parse::<NaiveDate>()
+            .map_err(|_| anyhow::anyhow!("Invalid calendar date: {}", day.date))?;
+        if let Some((open, close)) = day.windows(&[MarketSession::Regular])?.first() {
+            days.push(TradingDay { date, open: *open, close: *close });
+        }
+    }
+    info!(symbols = ranges.len(), trading_days = days.len(), timeframe = %timeframe, "Checking for gaps");
+    
+    let mut gaps = Vec::new();
+    for (symbol, first, last) in &ranges {
+        let symbol_days: Vec<TradingDay> = days.iter().filter(|day| day.date >= *first && day.date <= *last).copied().collect();
+        let timestamps: Vec<&[DateTime<Utc>]> = stores.iter()
+            .map(|store| store.get(*symbol).map(Vec::as_slice).unwrap_or_default())
+            .collect();
+        gaps.extend(find_gaps(symbol, &timestamps, &symbol_days, bar, args.min_coverage));
+    }
+    for gap in &gaps {
+        println!("{}", gap);
+    }
+    info!(gaps = gaps.len(), trading_days = gaps.iter().map(|gap| gap.days).sum::<usize>(), "Gap check complete");
+    if !args.fix || gaps.is_empty() {
+        return Ok(());
+    }
+    
+    // Repair: fetch each gap and add the bars a store does not have yet
+    let output = match &args.output {
+        Some(path) => Some(OutputMode::create_compressed_file_mode(path, args.format.clone(), true, Compression::from_extension(path))?),
+        None => None,
+    };
+    #[cfg(feature = "parquet")]
+    let dataset = args.dataset.as_deref().map(crate::dataset::DatasetWriter::new).transpose()?;
+    let mut have: Vec<HashMap<String, HashSet<DateTime<Utc>>>> = stores.into_iter()
+        .map(|store| store.into_iter().map(|(symbol, timestamps)| (symbol, timestamps.into_iter().collect())).collect())
+        .collect();
+    
+    let mut requests = BarRequests {
+        client: AlpacaClient::new()?,
+        retry: RetryPolicy::for_requests(args.max_request_retries),
+        timeframe,
+        feed,
+        end: String::new(),
+        page_size: args.page_size,
+        cache: args.cache.open(),
+    };
+    let mut total_records = 0;
+    let mut failed = 0;
+    for gap in &gaps {
+        let (from, to) = gap.range();
+        let from = from.to_rfc3339_opts(SecondsFormat::Secs, true);
+        requests.end = to.to_rfc3339_opts(SecondsFormat::Secs, true);
+        
+        let mut bars = Vec::new();
+        let mut page_token = None;
+        let fetched: Result<()> = async {
+            loop {
+                let (page, next_page_token) = requests.fetch_page(&gap.symbol, &from, page_token.as_deref()).await?;
+                bars.extend(page);
+                match next_page_token {
+                    Some(token) => page_token = Some(token),
+                    None => return Ok(()),
+                }
+            }
+        }
+        .await;
+        if let Err(e) = fetched {
+            error!(symbol = %gap.symbol, error = %e, "Error fetching gap");
+            failed += 1;
+            continue;
+        }
+        
+        let mut added = 0;
+        for bar in &bars {
+            // The API includes a bar at the end of the range, which is the next day's
+            let timestamp = bar_time(bar)?;
+            if timestamp >= to {
+                continue;
+            }
+            let mut stores = have.iter_mut();
+            let mut new = false;
+            if let Some(output) = &output {
+                let stored = stores.next().expect("one store per target").entry(bar.symbol.clone()).or_default();
+                if stored.insert(timestamp) {
+                    output.writeln(&format_bar_data(bar, &args.format)?)?;
+                    new = true;
+                }
+            }
+            #[cfg(feature = "parquet")]
+            if let Some(dataset) = &dataset {
+                let stored = stores.next().expect("one store per target").entry(bar.symbol.clone()).or_default();
+                if stored.insert(timestamp) {
+                    dataset.write_bar(&bar.symbol, bar.to_dataset_row()?)?;
+                    new = true;
+                }
+            }
+            added += new as usize;
+        }
+        if added == 0 {
+            warn!(symbol = %gap.symbol, first_day = %gap.first_day, last_day = %gap.last_day, "No bars available for gap");
+        } else {
+            info!(symbol = %gap.symbol, first_day = %gap.first_day, last_day = %gap.last_day, records = added, "Filled gap");
+        }
+        total_records += added;
+    }
+    
+    if let Some(output) = &output {
+        output.close()?;
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(dataset) = &dataset {
+        dataset.flush_all()?;
+    }
+    info!(gaps = gaps.len(), records = total_records, "Backfill complete");
+    if failed > 0 {
+        return Err(anyhow::anyhow!("Could not fetch {} of {} gaps", failed, gaps.len()));
+    }
+    Ok(())
+}
+
+/// Every bar of `symbol` from `start` to the end of `requests`
+pub(crate) async fn fetch_bars(requests: &BarRequests, symbol: &str, start: &str) -> Result<Vec<OhlcvBar>> {
+    let mut bars = Vec::new();
+    let mut page_token = None;
+    loop {
+        let (page, next_page_token) = requests.fetch_page(symbol, start, page_token.as_deref()).await?;
+        for bar in &page {
+            bars.push(bar.to_ohlcv()?);
+        }
+        match next_page_token {
+            Some(token) => page_token = Some(token),
+            None => return Ok(bars),
+        }
+    }
+}
+
+/// Download each symbol from --feed and --reference and report how they differ
+async fn run_compare_feeds(args: CompareFeedsArgs, global: &GlobalArgs) -> Result<()> {
+    use crate::feed_compare::{compare_feeds, DISCREPANCIES_CSV_HEADER};
+    
+    global.init(false)?;
+    
+    let Some(symbols) = given_symbols(args.symbols.as_deref(), args.symbols_file.as_deref())? else {
+        return Err(anyhow::anyhow!("--symbols or --symbols-file is required"));
+    };
+    let dates = date_resolver(&[Some(args.start.as_str()), args.end.as_deref()]).await;
+    let start = dates.resolve(&args.start, RangeEnd::Start)?;
+    let end = dates.resolve(args.end.as_deref().unwrap_or("now"), RangeEnd::End)?;
+    let timeframe = validate_timeframe(&args.timeframe)?;
+    let feed = validate_feed(&args.feed)?;
+    let reference = validate_feed(&args.reference)?;
+    if parse_bound(&start)? >= parse_bound(&end)? {
+        return Err(anyhow::anyhow!("Start date must be before end date"));
+    }
+    if feed.as_str() == reference.as_str() {
+        return Err(anyhow::anyhow!("--feed and --reference are both {}", feed.as_str()));
+    }
+    if args.tolerance.is_nan() || args.tolerance < 0.0 {
+        return Err(anyhow::anyhow!("--tolerance must be 0 or more"));
+    }
+    if args.page_size > 10000 {
+        return Err(anyhow::anyhow!("Page size cannot exceed 10000"));
+    }
+    
+    let output = match &args.output {
+        Some(path) => {
+            let output = OutputMode::create_compressed_file_mode(path, args.format.clone(), false, Compression::from_extension(path))?;
+            if matches!(args.format, DataFormat::Csv) {
+                output.writeln(DISCREPANCIES_CSV_HEADER)?;
+            }
+            Some(output)
+        }
+        None => None,
+    };
+    info!(symbols = ?symbols, start = %start, end = %end, timeframe = %timeframe, feed = feed.as_str(), reference = reference.as_str(), "Comparing feeds");
+    
+    let requests = |feed| -> Result<BarRequests> {
+        Ok(BarRequests {
+            client: AlpacaClient::new()?,
+            retry: RetryPolicy::for_requests(args.max_request_retries),
+            timeframe: timeframe.clone(),
+            feed,
+            end: end.clone(),
+            page_size: args.page_size,
+            cache: args.cache.open(),
+        })
+    };
+    let (feed_requests, reference_requests) = (requests(feed)?, requests(reference)?);
+    let mut discrepancy_count = 0;
+    let mut failed = Vec::new();
+    for symbol in &symbols {
+        let fetched = async {
+            let feed_bars = fetch_bars(&feed_requests, symbol, &start).await?;
+            let reference_bars = fetch_bars(&reference_requests, symbol, &start).await?;
+            Ok::<_, anyhow::Error>((feed_bars, reference_bars))
+        }
+        .await;
+        let (feed_bars, reference_bars) = match fetched {
+            Ok(bars) => bars,
+            Err(e) => {
+                error!(symbol = %symbol, error = %e, "Error fetching bars");
+                failed.push(symbol.clone());
+                continue;
+            }
+        };
+        let (comparison, discrepancies) = compare_feeds(symbol, &feed_bars, &reference_bars, args.tolerance);
+        println!("{}", comparison);
+        if let Some(output) = &output {
+            for discrepancy in &discrepancies {
+                output.writeln(&discrepancy.format(&args.format)?)?;
+            }
+        }
+        discrepancy_count += discrepancies.len();
+    }
+    
+    if let Some(output) = &output {
+        output.close()?;
+    }
+    info!(symbols = symbols.len(), discrepancies = discrepancy_count, output = ?args.output, "Feed comparison complete");
+    if !failed.is_empty() {
+        return Err(anyhow::anyhow!("Could not compare {}", failed.join(",")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use std::fs;
+
+    #[test]
+    fn test_parse_date_valid() {
+        let result = parse_date("2024-01-15");
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "2024-01-15");
+    }
+
+    #[test]
+    fn test_parse_date_invalid_format() {
+        let result = parse_date("01-15-2024");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_date_invalid_date() {
+        let result = parse_date("2024-13-32");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validate_timeframe_valid() {
+        assert_eq!(validate_timeframe("1min").unwrap(), "1Min");
+        assert_eq!(validate_timeframe("5Min").unwrap(), "5Min");
+        assert_eq!(validate_timeframe("1Day").unwrap(), "1Day");
+        assert_eq!(validate_timeframe("1h").unwrap(), "1Hour");
+        assert_eq!(validate_timeframe("1w").unwrap(), "1Week");
+        assert_eq!(validate_timeframe("1m").unwrap(), "1Month");
+    }
+
+    #[test]
+    fn test_validate_timeframe_invalid() {
+        let result = validate_timeframe("invalid");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("Invalid timeframe"));
+    }
+
+    #[test]
+    fn test_historical_bar_data_from_bar() {
+        let bar = Bar {
+            t: "2024-01-15T10:00:00Z".to_string(),
+            o: 150.0,
+            h: 155.0,
+            l: 149.0,
+            c: 153.0,
+            v: 10000,
+            n: 500,
+            vw: 152.5,
+        };
+
+        let mut hist_bar = HistoricalBarData::from(&bar);
+        hist_bar.symbol = "AAPL".to_string();
+
+        assert_eq!(hist_bar.symbol, "AAPL");
+        assert_eq!(hist_bar.timestamp, "2024-01-15T10:00:00Z");
+        assert_eq!(hist_bar.open, 150.0);
+        assert_eq!(hist_bar.high, 155.0);
+        assert_eq!(hist_bar.low, 149.0);
+        assert_eq!(hist_bar.close, 153.0);
+        assert_eq!(hist_bar.volume, 10000);
+        assert_eq!(hist_bar.trade_count, 500);
+        assert_eq!(hist_bar.vwap, 152.5);
+    }
+
+    #[test]
+    fn test_format_bar_data_plain() {
+        let bar = HistoricalBarData {
+            symbol: "AAPL".to_string(),
+            timestamp: "2024-01-15T10:00:00Z".to_string(),
+            open: 150.0,
+            high: 155.0,
+            low: 149.0,
+            close: 153.0,
+            volume: 10000,
+            trade_count: 500,
+            vwap: 152.5,
+        };
+
+        let result = format_bar_data(&bar, &DataFormat::Plain).unwrap();
+        assert!(result.contains("📊 AAPL"));
+        assert!(result.contains("O: $150.00"));
+        assert!(result.contains("H: $155.00"));
+        assert!(result.contains("L: $149.00"));
+        assert!(result.contains("C: $153.00"));
+        assert!(result.contains("Vol: 10000"));
+        assert!(result.contains("Change: $3.00 (2.00%)"));
+    }
+
+    #[test]
+    fn test_format_bar_data_json() {
+        let bar = HistoricalBarData {
+            symbol: "AAPL".to_string(),
+            timestamp: "2024-01-15T10:00:00Z".to_string(),
+            open: 150.0,
+            high: 155.0,
+            low: 149.0,
+            close: 153.0,
+            volume: 10000,
+            trade_count: 500,
+            vwap: 152.5,
+        };
+
+        let result = format_bar_data(&bar, &DataFormat::Json).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(parsed["symbol"], "AAPL");
+        assert_eq!(parsed["open"], 150.0);
+        assert_eq!(parsed["close"], 153.0);
+    }
+
+    #[test]
+    fn test_format_bar_data_csv() {
+        let bar = HistoricalBarData {
+            symbol: "AAPL".to_string(),
+            timestamp: "2024-01-15T10:00:00Z".to_string(),
+            open: 150.0,
+            high: 155.0,
+            low: 149.0,
+            close: 153.0,
+            volume: 10000,
+            trade_count: 500,
+            vwap: 152.5,
+        };
+
+        let result = format_bar_data(&bar, &DataFormat::Csv).unwrap();
+        assert_eq!(result, "AAPL,2024-01-15T10:00:00Z,150.00,155.00,149.00,153.00,10000,500,152.5");
+    }
+
+    #[test]
+    fn test_output_mode_console() {
+        let output_mode = OutputMode::create_console_mode(DataFormat::Plain);
+        // Just test that it doesn't panic - we can't easily capture stdout in tests
+        assert!(output_mode.writeln("test message").is_ok());
+    }
+
+    #[test]
+    fn test_output_mode_file() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_output.txt");
+        
+        let output_mode = OutputMode::create_file_mode(&file_path, DataFormat::Plain, false).unwrap();
+        assert!(output_mode.writeln("test message").is_ok());
+        
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "test message\n");
+    }
+
+    #[test]
+    fn test_output_mode_file_append() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_append.txt");
+        
+        // Write initial content
+        fs::write(&file_path, "initial\n").unwrap();
+        
+        let output_mode = OutputMode::create_file_mode(&file_path, DataFormat::Plain, true).unwrap();
+        assert!(output_mode.writeln("appended").is_ok());
+        
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "initial\nappended\n");
+    }
+
+    #[test]
+    fn test_page_size_validation() {
+        // This would be tested in main function, but we can test the logic
+        let page_size = 15000_u32;
+        assert!(page_size > 10000);
+    }
+
+    #[test]
+    fn test_date_range_validation() {
+        let start = "2024-01-15";
+        let end = "2024-01-10";
+        
+        let start_parsed = parse_date(start).unwrap();
+        let end_parsed = parse_date(end).unwrap();
+        
+        assert!(start_parsed >= end_parsed);
+    }
+
+    #[test]
+    fn test_symbols_parsing() {
+        let symbols_str = "AAPL,MSFT, GOOGL , tsla";
+        let symbols: Vec<String> = symbols_str
+            .split(',')
+            .map(|s| s.trim().to_uppercase())
+            .collect();
+        
+        assert_eq!(symbols, vec!["AAPL", "MSFT", "GOOGL", "TSLA"]);
+    }
+
+    #[test]
+    fn test_validate_feed_valid() {
+        assert!(matches!(validate_feed("sip").unwrap(), StockDataFeed::Sip));
+        assert!(matches!(validate_feed("SIP").unwrap(), StockDataFeed::Sip));
+        assert!(matches!(validate_feed("iex").unwrap(), StockDataFeed::Iex));
+        assert!(matches!(validate_feed("IEX").unwrap(), StockDataFeed::Iex));
+        assert!(matches!(validate_feed("boats").unwrap(), StockDataFeed::Boats));
+        assert!(matches!(validate_feed("BOATS").unwrap(), StockDataFeed::Boats));
+        assert!(matches!(validate_feed("otc").unwrap(), StockDataFeed::Otc));
+        assert!(matches!(validate_feed("OTC").unwrap(), StockDataFeed::Otc));
+    }
+
+    #[test]
+    fn test_validate_feed_invalid() {
+        let result = validate_feed("invalid");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("Invalid feed"));
+    }
+
+    #[test]
+    fn test_feed_as_str() {
+        assert_eq!(StockDataFeed::Sip.as_str(), "sip");
+        assert_eq!(StockDataFeed::Iex.as_str(), "iex");
+        assert_eq!(StockDataFeed::Boats.as_str(), "boats");
+        assert_eq!(StockDataFeed::Otc.as_str(), "otc");
+    }
+
+    #[test]
+    fn test_split_file_names() {
+        let time = |value: &str| parse_bound(value).unwrap();
+        let dir = Path::new("data");
+        
+        let split = SplitFiles::new(dir, "1Day", time("2024-01-01"), time("2025-01-01"), &DataFormat::Csv, Compression::None);
+        assert_eq!(split.path("AAPL"), dir.join("AAPL_1Day_2024.csv"));
+        assert_eq!(split.file_name("BTC/USD"), "BTC-USD_1Day_2024.csv");
+        
+        let split = SplitFiles::new(dir, "trades", time("2023-12-28"), time("2024-01-03T16:00:00Z"), &DataFormat::Json, Compression::Gzip);
+        assert_eq!(split.file_name("MSFT"), "MSFT_trades_2023-2024.jsonl.gz");
+    }
+}
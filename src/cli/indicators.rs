@@ -0,0 +1,188 @@
+use super::cache::CacheArgs;
+use super::historical::{date_resolver, fetch_bars, given_symbols, parse_bound, BarRequests};
+use super::{GlobalArgs, OutputArgs};
+use crate::dates::RangeEnd;
+use crate::indicators::{column_names, IndicatorColumns, IndicatorSpec};
+use crate::resample::{read_bars, OhlcvBar, BARS_CSV_HEADER};
+use crate::retry::DEFAULT_REQUEST_RETRIES;
+use crate::{Compression, DataFormat, OutputMode};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Flags of `trade indicators`
+#[derive(clap::Args, Debug)]
+pub struct IndicatorsArgs {
+    /// Indicators to add as columns, separated by commas: sma(N), ema(N), wma(N), rsi(N), macd(FAST,SLOW,SIGNAL), bbands(N,K), atr(N), stoch(K,D), obv or vwap (since the start of each day)
+    #[arg(long, value_name = "LIST")]
+    add: String,
+    
+    /// Bars written by `historical` or `resample`; gzip and zstd files are decompressed automatically
+    #[arg(short, long, required_unless_present_any = ["symbols", "symbols_file"], conflicts_with_all = ["symbols", "symbols_file", "start", "end"])]
+    input: Option<PathBuf>,
+    
+    /// Format of --input (json, csv) [default: csv for .csv files, json otherwise]
+    #[arg(long, value_enum, requires = "input")]
+    input_format: Option<DataFormat>,
+    
+    /// Download the bars of these symbols instead (comma-separated)
+    #[arg(short, long)]
+    symbols: Option<String>,
+    
+    /// Read the symbols to download from this file instead, as for `historical`
+    #[arg(long, value_name = "PATH", conflicts_with = "symbols")]
+    symbols_file: Option<PathBuf>,
+    
+    /// Start of the download, in the forms `historical --start` takes
+    #[arg(long)]
+    start: Option<String>,
+    
+    /// End of the download, in the same forms or now [default: now]
+    #[arg(long)]
+    end: Option<String>,
+    
+    /// Timeframe of downloaded bars (1Min, 5Min, 15Min, 1Hour, 1Day)
+    #[arg(short, long, default_value = "1Day")]
+    timeframe: String,
+    
+    /// Data feed of downloaded bars (sip, iex, boats, otc)
+    #[arg(long, default_value = "sip")]
+    feed: String,
+    
+    /// Page size for requests (max 10000)
+    #[arg(long, default_value = "1000")]
+    page_size: u32,
+    
+    #[command(flatten)]
+    cache: CacheArgs,
+    
+    /// Retries of each page request after a 429, a 5xx or a network error
+    #[arg(long, default_value_t = DEFAULT_REQUEST_RETRIES)]
+    max_request_retries: u32,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+    
+    /// Also write the bars with their indicators to this Parquet file
+    #[cfg(feature = "parquet")]
+    #[arg(long, value_name = "PATH")]
+    parquet: Option<PathBuf>,
+}
+
+/// Indicator columns in JSON, in order after the fields of the bar
+struct Columns<'a>(&'a [String], &'a [Option<f64>]);
+
+impl serde::Serialize for Columns<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().zip(self.1))
+    }
+}
+
+/// The bar in `format`, followed by its indicator columns
+fn format_row(bar: &OhlcvBar, names: &[String], values: &[Option<f64>], format: &DataFormat) -> crate::Result<String> {
+    Ok(match format {
+        DataFormat::Csv => {
+            let mut line = bar.format(format)?;
+            for value in values {
+                line.push(',');
+                if let Some(value) = value {
+                    line.push_str(&value.to_string());
+                }
+            }
+            line
+        }
+        DataFormat::Json => {
+            #[derive(serde::Serialize)]
+            struct Row<'a> {
+                #[serde(flatten)]
+                bar: &'a OhlcvBar,
+                #[serde(flatten)]
+                columns: Columns<'a>,
+            }
+            serde_json::to_string(&Row { bar, columns: Columns(names, values) })?
+        }
+        DataFormat::Plain => {
+            let mut line = bar.format(format)?;
+            for (name, value) in names.iter().zip(values) {
+                if let Some(value) = value {
+                    line.push_str(&format!(" | {}: {:.2}", name, value));
+                }
+            }
+            line
+        }
+    })
+}
+
+/// Compute the indicators of --add over the bars of --input, or of a
+/// download, and write the bars out with a column for each
+pub async fn run(args: IndicatorsArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let specs = IndicatorSpec::parse_list(&args.add)?;
+    let names = column_names(&specs);
+    info!(input = ?args.input, symbols = ?args.symbols, columns = %names.join(","), format = ?args.out.format, output = ?args.out.output, "Computing indicators");
+    
+    let output_mode = match &args.out.output {
+        Some(path) => OutputMode::create_compressed_file_mode(path, args.out.format.clone(), args.out.append, Compression::from_extension(path))?,
+        None => OutputMode::create_console_mode(args.out.format.clone()),
+    };
+    if matches!(args.out.format, DataFormat::Csv) && !args.out.append {
+        output_mode.writeln(&format!("{},{}", BARS_CSV_HEADER, names.join(",")))?;
+    }
+    
+    // Each symbol's indicators only see its own bars, whatever order they come in
+    let fresh = IndicatorColumns::new(&specs)?;
+    let mut indicators: HashMap<String, IndicatorColumns> = HashMap::new();
+    #[cfg(feature = "parquet")]
+    let mut rows = Vec::new();
+    let mut records = 0;
+    let mut add = |bar: OhlcvBar| -> crate::Result<()> {
+        let values = indicators.entry(bar.symbol.clone()).or_insert_with(|| fresh.clone()).update(&bar);
+        output_mode.writeln(&format_row(&bar, &names, &values, &args.out.format)?)?;
+        #[cfg(feature = "parquet")]
+        if args.parquet.is_some() {
+            rows.push((bar, values));
+        }
+        records += 1;
+        Ok(())
+    };
+    match &args.input {
+        Some(input) => {
+            let input_format = args.input_format.clone().unwrap_or_else(|| {
+                if input.to_string_lossy().contains(".csv") { DataFormat::Csv } else { DataFormat::Json }
+            });
+            read_bars(input, &input_format, &mut add)?;
+        }
+        None => {
+            let Some(symbols) = given_symbols(args.symbols.as_deref(), args.symbols_file.as_deref())? else {
+                bail!("--input, --symbols or --symbols-file is required");
+            };
+            let Some(start) = args.start.as_deref() else {
+                bail!("--start is required to download bars");
+            };
+            let dates = date_resolver(&[Some(start), args.end.as_deref()]).await;
+            let start = dates.resolve(start, RangeEnd::Start)?;
+            let end = dates.resolve(args.end.as_deref().unwrap_or("now"), RangeEnd::End)?;
+            if parse_bound(&start)? >= parse_bound(&end)? {
+                bail!("Start date must be before end date");
+            }
+            let requests = BarRequests::new(&args.timeframe, &args.feed, &end, args.page_size, args.cache.open(), args.max_request_retries)?;
+            for symbol in &symbols {
+                for bar in fetch_bars(&requests, symbol, &start).await? {
+                    add(bar)?;
+                }
+            }
+        }
+    }
+    output_mode.close()?;
+    
+    #[cfg(feature = "parquet")]
+    if let Some(path) = &args.parquet {
+        crate::dataset::write_bars_file(path, &names, &rows)?;
+        info!(parquet = %path.display(), rows = rows.len(), "Parquet file written");
+    }
+    info!(records, symbols = indicators.len(), output = ?args.out.output, "Indicators complete");
+    
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use super::{GlobalArgs, OutputArgs};
+use crate::dates::{DateResolver, RangeEnd};
+use crate::market_hours::{MarketCalendar, CALENDAR_CSV_HEADER, MARKET_CLOCK_CSV_HEADER};
+use crate::{DataFormat, OutputMode};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use tracing::{info, warn};
+
+/// Days `trade calendar` lists without --end
+const DEFAULT_CALENDAR_DAYS: i64 = 30;
+
+/// Flags of `trade calendar`
+#[derive(clap::Args, Debug)]
+pub struct CalendarArgs {
+    /// First day, in the forms `historical --start` takes [default: today]
+    #[arg(long)]
+    start: Option<String>,
+    
+    /// Last day, included [default: 30 days after --start]
+    #[arg(long)]
+    end: Option<String>,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Flags of `trade clock`
+#[derive(clap::Args, Debug)]
+pub struct ClockArgs {
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+fn output_mode(out: &OutputArgs) -> Result<OutputMode> {
+    Ok(match &out.output {
+        Some(path) => OutputMode::create_file_mode(path, out.format.clone(), out.append)?,
+        None => OutputMode::create_console_mode(out.format.clone()),
+    })
+}
+
+/// Print the trading days between --start and --end with their session times
+pub async fn run_calendar(args: CalendarArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let dates = DateResolver::new(Utc::now());
+    let start = match &args.start {
+        Some(start) => dates.resolve_day(start, RangeEnd::Start)?,
+        None => dates.today(),
+    };
+    let end = match &args.end {
+        Some(end) => dates.resolve_day(end, RangeEnd::End)?,
+        None => start + Duration::days(DEFAULT_CALENDAR_DAYS),
+    };
+    if start > end {
+        return Err(anyhow::anyhow!("--start must not be after --end"));
+    }
+    info!(start = %start, end = %end, format = ?args.out.format, output = ?args.out.output, "Market calendar retrieval");
+    
+    let days = MarketCalendar::from_env()?.calendar(start, end).await?;
+    if days.is_empty() {
+        warn!("No trading days in the range");
+    }
+    
+    let output_mode = output_mode(&args.out)?;
+    if matches!(args.out.format, DataFormat::Csv) {
+        output_mode.writeln(CALENDAR_CSV_HEADER)?;
+    }
+    for day in &days {
+        output_mode.writeln(&day.format(&args.out.format)?)?;
+    }
+    output_mode.close()?;
+    info!(trading_days = days.len(), early_closes = days.iter().filter(|day| day.is_early_close()).count(), "Retrieval complete");
+    
+    Ok(())
+}
+
+/// Print whether the market is open and when it next opens and closes
+pub async fn run_clock(args: ClockArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let clock = MarketCalendar::from_env()?.clock().await?;
+    let output_mode = output_mode(&args.out)?;
+    if matches!(args.out.format, DataFormat::Csv) {
+        output_mode.writeln(MARKET_CLOCK_CSV_HEADER)?;
+    }
+    output_mode.writeln(&clock.format(&args.out.format)?)?;
+    output_mode.close()?;
+    
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use super::convert::open_sink;
+use super::GlobalArgs;
+use crate::capture::{read_capture, CaptureFormat};
+use crate::dedup::{message_time, Deduplicator};
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Flags of `trade merge`
+#[derive(clap::Args, Debug)]
+pub struct MergeArgs {
+    /// Capture files to merge; repeat for each. gzip and zstd files are decompressed automatically
+    #[arg(short, long = "input", value_name = "INPUT", required = true)]
+    inputs: Vec<PathBuf>,
+    
+    /// Format of the inputs (json, csv, parquet, sqlite) [default: from each input's name, as for `convert`]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    from: Option<CaptureFormat>,
+    
+    /// File, database or dataset directory to write
+    #[arg(short, long)]
+    output: PathBuf,
+    
+    /// Format of the output [default: from the output's name]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    to: Option<CaptureFormat>,
+    
+    /// Keep every message, even ones that are in several inputs
+    #[arg(long)]
+    keep_duplicates: bool,
+}
+
+/// Merge every --input into one --output in time order, without the
+/// messages that are in more than one
+pub fn run(args: MergeArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let to = match args.to {
+        Some(format) => format,
+        None => CaptureFormat::from_path(&args.output)?,
+    };
+    info!(inputs = args.inputs.len(), output = %args.output.display(), format = ?to, "Merging captures");
+    
+    let mut messages = Vec::new();
+    for input in &args.inputs {
+        let from = match args.from {
+            Some(format) => format,
+            None => CaptureFormat::from_path(input)?,
+        };
+        let before = messages.len();
+        read_capture(input, from, |data| {
+            messages.push(data);
+            Ok(())
+        })?;
+        info!(input = %input.display(), format = ?from, messages = messages.len() - before, "Read capture");
+    }
+    // Stable, so messages of the same time stay in input order
+    messages.sort_by_key(message_time);
+    
+    let sink = open_sink(&args.output, to, false)?;
+    let mut deduplicator = Deduplicator::new();
+    let mut written = 0;
+    for data in &messages {
+        if args.keep_duplicates || deduplicator.is_new(data) {
+            sink.write_streaming_data(data)?;
+            written += 1;
+        }
+    }
+    sink.close()?;
+    info!(read = messages.len(), written, duplicates = deduplicator.duplicates(), output = %args.output.display(), "Merge complete");
+    
+    Ok(())
+}
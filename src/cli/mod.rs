@@ -0,0 +1,255 @@
+//! The commands behind the `trade` binary. The single-purpose binaries
+//! (`streaming-client`, `historical-data`, ...) wrap the same commands.
+
+use crate::keychain::Keychain;
+use crate::logging::{init_logging_at, LogFormat};
+use crate::rate_limit::RateLimiter;
+use crate::{ConfigFile, DataFormat};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+pub mod aggregate;
+pub mod analyze;
+#[cfg(feature = "assets")]
+pub mod assets;
+#[cfg(feature = "auth-check")]
+pub mod auth_check;
+pub mod backtest;
+pub mod cache;
+pub mod chart;
+pub mod convert;
+#[cfg(feature = "corporate-actions")]
+pub mod corporate_actions;
+pub mod credentials;
+pub mod diff;
+pub mod historical;
+pub mod indicators;
+#[cfg(feature = "market-hours")]
+pub mod market;
+pub mod merge;
+#[cfg(feature = "options")]
+pub mod options;
+#[cfg(feature = "orders")]
+pub mod orders;
+#[cfg(feature = "duckdb")]
+pub mod query;
+pub mod resample;
+#[cfg(feature = "orders")]
+pub mod run;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod stream;
+
+/// Flags every command takes
+#[derive(clap::Args, Debug, Clone, Default)]
+#[command(next_help_heading = "Global options")]
+pub struct GlobalArgs {
+    /// Read settings such as the retry policy, symbol lists and account profiles from this TOML file; environment variables and flags override it
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Account profile: `[profiles.NAME]` in --config and/or a `.env.NAME` file
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Read the API key ID and secret from the OS keychain (see `credentials`), falling back to the environment
+    #[arg(long, global = true)]
+    pub keychain: bool,
+
+    /// Operational log level or filter, e.g. debug or algorithms_trading=trace [default: RUST_LOG or info]
+    #[arg(long, global = true, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Append operational logs to this file instead of stderr
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Format for operational logs
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// REST requests per minute shared by every download; lowered further by the X-RateLimit headers [default: ALPACA_RATE_LIMIT or 200]
+    #[arg(long, global = true, value_name = "PER_MINUTE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub rate_limit: Option<u32>,
+}
+
+impl GlobalArgs {
+    /// Load `.env`, install logging (discarded without `--log-file` when
+    /// `quiet`), then apply the profile and the keychain. Returns the config
+    /// file, or defaults without `--config`.
+    pub fn init(&self, quiet: bool) -> Result<ConfigFile> {
+        dotenv::dotenv().ok();
+        init_logging_at(
+            self.log_format,
+            self.log_file.as_deref(),
+            self.log_level.as_deref(),
+            quiet,
+        )?;
+
+        let config_file = match &self.config {
+            Some(path) => ConfigFile::load(path)?,
+            None => ConfigFile::default(),
+        };
+        if let Some(profile) = &self.profile {
+            let vars = config_file.activate_profile(profile, Path::new("."))?;
+            let base_url = std::env::var("APCA_API_BASE_URL").unwrap_or_default();
+            info!(profile = %profile, vars = %vars.join(","), base_url = %base_url, "Using profile");
+        }
+        if self.keychain {
+            let loaded = Keychain::new(self.profile.as_deref()).load_into_env()?;
+            info!(vars = %loaded.join(","), "Loaded credentials from the keychain");
+        }
+        if let Some(per_minute) = self.rate_limit {
+            RateLimiter::global().set_requests_per_minute(per_minute);
+        }
+        Ok(config_file)
+    }
+}
+
+/// Where and how commands that produce data write it
+#[derive(clap::Args, Debug, Clone)]
+pub struct OutputArgs {
+    /// Output to file instead of console
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Append to existing file instead of overwriting
+    #[arg(short, long)]
+    pub append: bool,
+
+    /// Data format for output
+    #[arg(short, long, value_enum, default_value_t = DataFormat::Plain)]
+    pub format: DataFormat,
+}
+
+/// `trade <command>`
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Stream real-time market data (same as the streaming-client binary)
+    Stream(Box<stream::StreamArgs>),
+    /// Retrieve historical bars (same as the historical-data binary)
+    Historical(Box<historical::HistoricalArgs>),
+    /// Analyze captured data files (same as the data-analyzer binary)
+    Analyze(analyze::AnalyzeArgs),
+    /// Rebuild bars of any timeframe from the trades of a capture file
+    Aggregate(aggregate::AggregateArgs),
+    /// Convert capture files between JSON lines, CSV, Parquet datasets and SQLite
+    Convert(convert::ConvertArgs),
+    /// Merge capture files into one in time order, without the messages they have in common
+    Merge(merge::MergeArgs),
+    /// Compare two captures of the same session: messages only one has, timing skew and price mismatches
+    Diff(diff::DiffArgs),
+    /// Run SQL on captures loaded into an in-memory DuckDB database
+    #[cfg(feature = "duckdb")]
+    Query(query::QueryArgs),
+    /// Draw candlestick or line charts of bars in the terminal, or as PNG images
+    Chart(chart::ChartArgs),
+    /// List tradable assets by exchange, class and flags, e.g. to build a --symbols-file
+    #[cfg(feature = "assets")]
+    Assets(assets::AssetsArgs),
+    /// Verify credentials, account status and data entitlements (same as the auth-check binary)
+    #[cfg(feature = "auth-check")]
+    AuthCheck(auth_check::AuthCheckArgs),
+    /// List trading days with their open and close times
+    #[cfg(feature = "market-hours")]
+    Calendar(market::CalendarArgs),
+    /// Show whether the market is open and when it next opens and closes
+    #[cfg(feature = "market-hours")]
+    Clock(market::ClockArgs),
+    /// Manage the on-disk cache of historical bars
+    Cache {
+        #[command(subcommand)]
+        command: cache::CacheCommand,
+    },
+    /// Store API keys in the OS keychain (same as the credentials binary)
+    Credentials {
+        #[command(subcommand)]
+        command: credentials::CredentialsCommand,
+    },
+    /// Download splits, dividends and mergers
+    #[cfg(feature = "corporate-actions")]
+    CorporateActions(corporate_actions::CorporateActionsArgs),
+    /// Adjust downloaded bars for splits and dividends, with the factors in extra columns
+    #[cfg(feature = "corporate-actions")]
+    Adjust(corporate_actions::AdjustArgs),
+    /// List option contracts and download option bars and trades
+    #[cfg(feature = "options")]
+    Options {
+        #[command(subcommand)]
+        command: options::OptionsCommand,
+    },
+    /// Aggregate downloaded bars, e.g. 1Min bars, into longer timeframes locally
+    Resample(resample::ResampleArgs),
+    /// Add technical indicator columns such as sma(20) or rsi(14) to bars from a file or a download
+    Indicators(Box<indicators::IndicatorsArgs>),
+    /// Run a strategy over historical bars or a capture with simulated fills, and report its equity
+    Backtest(Box<backtest::BacktestArgs>),
+    /// Print the latest trade, quote and bars of some symbols
+    #[cfg(feature = "snapshot")]
+    Snapshot(snapshot::SnapshotArgs),
+    /// Place, list, replace and cancel orders on the trading API (paper trading unless APCA_API_BASE_URL says otherwise)
+    #[cfg(feature = "orders")]
+    Orders {
+        #[command(subcommand)]
+        command: orders::OrdersCommand,
+    },
+    /// Trade a strategy live: stream market data to it and place its orders on the paper trading account
+    #[cfg(feature = "orders")]
+    Run(Box<run::RunArgs>),
+}
+
+/// The `trade` command line
+#[derive(clap::Parser, Debug)]
+#[command(name = "trade")]
+#[command(about = "Alpaca market data toolkit: streaming, historical bars, analysis and credentials")]
+#[command(version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<()> {
+        match self.command {
+            Command::Stream(args) => stream::run(*args, &self.global).await,
+            Command::Historical(args) => historical::run(*args, &self.global).await,
+            Command::Analyze(args) => analyze::run(args, &self.global),
+            Command::Aggregate(args) => aggregate::run(args, &self.global),
+            Command::Convert(args) => convert::run(args, &self.global),
+            Command::Merge(args) => merge::run(args, &self.global),
+            Command::Diff(args) => diff::run(args, &self.global),
+            #[cfg(feature = "duckdb")]
+            Command::Query(args) => query::run(args, &self.global),
+            Command::Chart(args) => chart::run(args, &self.global),
+            #[cfg(feature = "assets")]
+            Command::Assets(args) => assets::run(args, &self.global).await,
+            #[cfg(feature = "auth-check")]
+            Command::AuthCheck(args) => auth_check::run(args, &self.global).await,
+            #[cfg(feature = "market-hours")]
+            Command::Calendar(args) => market::run_calendar(args, &self.global).await,
+            #[cfg(feature = "market-hours")]
+            Command::Clock(args) => market::run_clock(args, &self.global).await,
+            Command::Cache { command } => cache::run(command, &self.global),
+            Command::Credentials { command } => credentials::run(command, &self.global),
+            #[cfg(feature = "corporate-actions")]
+            Command::CorporateActions(args) => corporate_actions::run(args, &self.global).await,
+            #[cfg(feature = "corporate-actions")]
+            Command::Adjust(args) => corporate_actions::run_adjust(args, &self.global).await,
+            #[cfg(feature = "options")]
+            Command::Options { command } => options::run(command, &self.global).await,
+            Command::Resample(args) => resample::run(args, &self.global),
+            Command::Indicators(args) => indicators::run(*args, &self.global).await,
+            Command::Backtest(args) => backtest::run(*args, &self.global).await,
+            #[cfg(feature = "snapshot")]
+            Command::Snapshot(args) => snapshot::run(args, &self.global).await,
+            #[cfg(feature = "orders")]
+            Command::Orders { command } => orders::run(command, &self.global).await,
+            #[cfg(feature = "orders")]
+            Command::Run(args) => run::run(*args, &self.global).await,
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use super::historical::{parse_date, validate_timeframe};
+use super::{GlobalArgs, OutputArgs};
+use crate::options::{
+    ContractQuery, OptionRight, OptionsClient, OccSymbol, OPTION_BARS_CSV_HEADER,
+    OPTION_CONTRACTS_CSV_HEADER, OPTION_TRADES_CSV_HEADER,
+};
+use crate::{DataFormat, OutputMode};
+use anyhow::Result;
+use chrono::NaiveDate;
+use tracing::{error, info, warn};
+
+/// `trade options <command>`
+#[derive(clap::Subcommand, Debug)]
+pub enum OptionsCommand {
+    /// List the active option contracts of one or more underlyings
+    Chain(ChainArgs),
+    /// Download bars or trades of option contracts
+    History(OptionHistoryArgs),
+}
+
+/// Flags of `trade options chain`
+#[derive(clap::Args, Debug)]
+pub struct ChainArgs {
+    /// Underlying symbols (comma-separated)
+    #[arg(short, long)]
+    underlying: String,
+    
+    /// Only contracts expiring on this date (YYYY-MM-DD)
+    #[arg(long, conflicts_with_all = ["expiration_from", "expiration_to"])]
+    expiration: Option<String>,
+    
+    /// Only contracts expiring on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    expiration_from: Option<String>,
+    
+    /// Only contracts expiring on or before this date (YYYY-MM-DD)
+    #[arg(long)]
+    expiration_to: Option<String>,
+    
+    /// Lowest strike price
+    #[arg(long)]
+    strike_min: Option<f64>,
+    
+    /// Highest strike price
+    #[arg(long)]
+    strike_max: Option<f64>,
+    
+    /// Only calls or only puts
+    #[arg(long = "type", value_enum)]
+    right: Option<OptionRight>,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// What `trade options history --data` downloads
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionData {
+    Bars,
+    Trades,
+}
+
+/// Flags of `trade options history`
+#[derive(clap::Args, Debug)]
+pub struct OptionHistoryArgs {
+    /// OCC contract symbols, e.g. AAPL240119C00190000 (comma-separated)
+    #[arg(short, long)]
+    symbols: String,
+    
+    /// Start date (YYYY-MM-DD)
+    #[arg(long)]
+    start: String,
+    
+    /// End date (YYYY-MM-DD)
+    #[arg(long)]
+    end: String,
+    
+    /// Timeframe for bars (1Min, 5Min, 15Min, 1Hour, 1Day)
+    #[arg(short, long, default_value = "1Day")]
+    timeframe: String,
+    
+    /// Download bars or every trade; trades ignore --timeframe
+    #[arg(long, value_enum, default_value_t = OptionData::Bars)]
+    data: OptionData,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+    
+    /// Page size for requests (max 10000)
+    #[arg(long, default_value = "1000")]
+    page_size: u32,
+}
+
+pub async fn run(command: OptionsCommand, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    match command {
+        OptionsCommand::Chain(args) => chain(args).await,
+        OptionsCommand::History(args) => history(args).await,
+    }
+}
+
+fn output_mode(out: &OutputArgs) -> Result<OutputMode> {
+    Ok(match &out.output {
+        Some(path) => OutputMode::create_file_mode(path, out.format.clone(), out.append)?,
+        None => OutputMode::create_console_mode(out.format.clone()),
+    })
+}
+
+fn date(value: Option<&String>) -> Result<Option<NaiveDate>> {
+    value
+        .map(|value| Ok(NaiveDate::parse_from_str(&parse_date(value)?, "%Y-%m-%d")?))
+        .transpose()
+}
+
+fn symbol_list(symbols: &str) -> Vec<String> {
+    symbols
+        .split(',')
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+async fn chain(args: ChainArgs) -> Result<()> {
+    let underlyings = symbol_list(&args.underlying);
+    if underlyings.is_empty() {
+        return Err(anyhow::anyhow!("No underlying symbols given"));
+    }
+    
+    let (expiration_from, expiration_to) = match &args.expiration {
+        Some(expiration) => (date(Some(expiration))?, date(Some(expiration))?),
+        None => (date(args.expiration_from.as_ref())?, date(args.expiration_to.as_ref())?),
+    };
+    if let (Some(from), Some(to)) = (expiration_from, expiration_to) {
+        if from > to {
+            return Err(anyhow::anyhow!("--expiration-from must not be after --expiration-to"));
+        }
+    }
+    if let (Some(min), Some(max)) = (args.strike_min, args.strike_max) {
+        if min > max {
+            return Err(anyhow::anyhow!("--strike-min must not be above --strike-max"));
+        }
+    }
+    
+    let query = ContractQuery {
+        underlyings,
+        expiration_from,
+        expiration_to,
+        strike_min: args.strike_min,
+        strike_max: args.strike_max,
+        right: args.right,
+    };
+    info!(query = ?query, format = ?args.out.format, output = ?args.out.output, "Option chain retrieval");
+    
+    let contracts = OptionsClient::from_env()?.fetch_contracts(&query).await?;
+    if contracts.is_empty() {
+        warn!("No option contracts match");
+    }
+    
+    let output_mode = output_mode(&args.out)?;
+    if matches!(args.out.format, DataFormat::Csv) {
+        output_mode.writeln(OPTION_CONTRACTS_CSV_HEADER)?;
+    }
+    for contract in &contracts {
+        output_mode.writeln(&contract.format(&args.out.format)?)?;
+    }
+    output_mode.close()?;
+    info!(contracts = contracts.len(), output = ?args.out.output, "Retrieval complete");
+    
+    Ok(())
+}
+
+async fn history(args: OptionHistoryArgs) -> Result<()> {
+    let symbols = symbol_list(&args.symbols);
+    for symbol in &symbols {
+        OccSymbol::parse(symbol)?;
+    }
+    
+    let start_date = parse_date(&args.start)?;
+    let end_date = parse_date(&args.end)?;
+    if start_date >= end_date {
+        return Err(anyhow::anyhow!("Start date must be before end date"));
+    }
+    let timeframe = validate_timeframe(&args.timeframe)?;
+    if args.page_size > 10000 {
+        return Err(anyhow::anyhow!("Page size cannot exceed 10000"));
+    }
+    
+    info!(
+        symbols = ?symbols,
+        start = %start_date,
+        end = %end_date,
+        timeframe = %timeframe,
+        data = ?args.data,
+        format = ?args.out.format,
+        output = ?args.out.output,
+        "Option history retrieval"
+    );
+    
+    let client = OptionsClient::from_env()?;
+    let output_mode = output_mode(&args.out)?;
+    if matches!(args.out.format, DataFormat::Csv) {
+        output_mode.writeln(match args.data {
+            OptionData::Bars => OPTION_BARS_CSV_HEADER,
+            OptionData::Trades => OPTION_TRADES_CSV_HEADER,
+        })?;
+    }
+    
+    let mut total_records = 0;
+    for symbol in &symbols {
+        // Each page is written as it arrives, so a long range is never held
+        // in memory
+        let records = match args.data {
+            OptionData::Bars => client
+                .fetch_bars(symbol, &start_date, &end_date, &timeframe, args.page_size, |bars| {
+                    for bar in &bars {
+                        output_mode.writeln(&bar.format(&args.out.format)?)?;
+                    }
+                    Ok(())
+                })
+                .await,
+            OptionData::Trades => client
+                .fetch_trades(symbol, &start_date, &end_date, args.page_size, |trades| {
+                    for trade in &trades {
+                        output_mode.writeln(&trade.format(&args.out.format)?)?;
+                    }
+                    Ok(())
+                })
+                .await,
+        };
+        match records {
+            Ok(records) => {
+                info!(symbol = %symbol, records, "Retrieved records");
+                if records == 0 {
+                    warn!(symbol = %symbol, "No data found for contract");
+                }
+                total_records += records;
+            }
+            Err(e) => {
+                error!(symbol = %symbol, error = %e, "Error fetching data");
+            }
+        }
+    }
+    
+    output_mode.close()?;
+    info!(symbols = symbols.len(), records = total_records, output = ?args.out.output, "Retrieval complete");
+    
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use super::{GlobalArgs, OutputArgs};
+use crate::orders::{
+    NewOrder, Order, OrderKind, OrderQuery, OrdersClient, ReplaceOrder, TimeInForce,
+    ORDERS_CSV_HEADER, ORDER_STATUSES, ORDER_TYPES,
+};
+use crate::strategy::Side;
+use crate::{DataFormat, OutputMode};
+use anyhow::{bail, Result};
+use tracing::{info, warn};
+
+/// `trade orders <command>`
+#[derive(clap::Subcommand, Debug)]
+pub enum OrdersCommand {
+    /// Place an order
+    Submit(SubmitArgs),
+    /// List orders, open ones by default
+    List(ListArgs),
+    /// Show one order
+    Get(GetArgs),
+    /// Change the quantity, prices or time in force of an open order
+    Replace(ReplaceArgs),
+    /// Cancel open orders
+    Cancel(CancelArgs),
+}
+
+/// Flags of `trade orders submit`
+#[derive(clap::Args, Debug)]
+pub struct SubmitArgs {
+    /// Symbol to trade
+    #[arg(short, long)]
+    symbol: String,
+    
+    /// Buy or sell
+    #[arg(long, value_enum)]
+    side: Side,
+    
+    /// Shares; fractional for fractionable assets
+    #[arg(long)]
+    qty: f64,
+    
+    /// Order type
+    #[arg(long = "type", value_parser = ORDER_TYPES, default_value = "market")]
+    order_type: String,
+    
+    /// Limit price of limit and stop_limit orders
+    #[arg(long)]
+    limit_price: Option<f64>,
+    
+    /// Stop price of stop and stop_limit orders
+    #[arg(long)]
+    stop_price: Option<f64>,
+    
+    /// Trail of a trailing_stop order in dollars
+    #[arg(long, conflicts_with = "trail_percent")]
+    trail_price: Option<f64>,
+    
+    /// Trail of a trailing_stop order in percent of the price
+    #[arg(long)]
+    trail_percent: Option<f64>,
+    
+    /// How long the order stays working
+    #[arg(long, value_enum, default_value = "day")]
+    time_in_force: TimeInForce,
+    
+    /// Also fill before the open and after the close; limit orders with --time-in-force day only
+    #[arg(long)]
+    extended_hours: bool,
+    
+    /// Our own ID of the order, unique per account
+    #[arg(long)]
+    client_order_id: Option<String>,
+    
+    /// Print the order that would be sent and exit
+    #[arg(long)]
+    dry_run: bool,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Flags of `trade orders list`
+#[derive(clap::Args, Debug)]
+pub struct ListArgs {
+    /// Only orders with this status
+    #[arg(long, value_parser = ORDER_STATUSES, default_value = "open")]
+    status: String,
+    
+    /// Most orders to list, newest first (max 500) [default: 50]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=500))]
+    limit: Option<u32>,
+    
+    /// Only orders of these symbols (comma-separated)
+    #[arg(short, long)]
+    symbols: Option<String>,
+    
+    /// Only buys or only sells
+    #[arg(long, value_enum)]
+    side: Option<Side>,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Flags of `trade orders get`
+#[derive(clap::Args, Debug)]
+pub struct GetArgs {
+    /// Order ID
+    id: String,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Flags of `trade orders replace`
+#[derive(clap::Args, Debug)]
+pub struct ReplaceArgs {
+    /// ID of the open order
+    id: String,
+    
+    /// New quantity
+    #[arg(long)]
+    qty: Option<f64>,
+    
+    /// New limit price
+    #[arg(long)]
+    limit_price: Option<f64>,
+    
+    /// New stop price
+    #[arg(long)]
+    stop_price: Option<f64>,
+    
+    /// New trail of a trailing_stop order, in dollars or percent as it was placed
+    #[arg(long)]
+    trail: Option<f64>,
+    
+    /// New time in force
+    #[arg(long, value_enum)]
+    time_in_force: Option<TimeInForce>,
+    
+    /// New client order ID
+    #[arg(long)]
+    client_order_id: Option<String>,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Flags of `trade orders cancel`
+#[derive(clap::Args, Debug)]
+pub struct CancelArgs {
+    /// IDs of the orders to cancel
+    #[arg(required_unless_present = "all", conflicts_with = "all")]
+    ids: Vec<String>,
+    
+    /// Cancel every open order
+    #[arg(long)]
+    all: bool,
+}
+
+pub async fn run(command: OrdersCommand, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    match command {
+        OrdersCommand::Submit(args) => submit(args).await,
+        OrdersCommand::List(args) => list(args).await,
+        OrdersCommand::Get(args) => {
+            let order = OrdersClient::from_env()?.get(&args.id).await?;
+            write_orders(&args.out, &[order])
+        }
+        OrdersCommand::Replace(args) => replace(args).await,
+        OrdersCommand::Cancel(args) => cancel(args).await,
+    }
+}
+
+fn write_orders(out: &OutputArgs, orders: &[Order]) -> Result<()> {
+    let output_mode = match &out.output {
+        Some(path) => OutputMode::create_file_mode(path, out.format.clone(), out.append)?,
+        None => OutputMode::create_console_mode(out.format.clone()),
+    };
+    if matches!(out.format, DataFormat::Csv) && !out.append {
+        output_mode.writeln(ORDERS_CSV_HEADER)?;
+    }
+    for order in orders {
+        output_mode.writeln(&order.format(&out.format)?)?;
+    }
+    output_mode.close()?;
+    Ok(())
+}
+
+async fn submit(args: SubmitArgs) -> Result<()> {
+    let kind = OrderKind::new(&args.order_type, args.limit_price, args.stop_price, args.trail_price, args.trail_percent)?;
+    let order = NewOrder::new(&args.symbol, args.side, args.qty, kind)
+        .time_in_force(args.time_in_force)
+        .extended_hours(args.extended_hours)
+        .client_order_id(args.client_order_id);
+    order.validate()?;
+    if args.dry_run {
+        println!("{}", serde_json::to_string_pretty(&order)?);
+        return Ok(());
+    }
+    
+    let client = OrdersClient::from_env()?;
+    info!(order = ?order, base_url = %client.base_url(), "Submitting order");
+    let placed = client.submit(&order).await?;
+    info!(id = %placed.id, status = %placed.status, "Order submitted");
+    write_orders(&args.out, &[placed])
+}
+
+async fn list(args: ListArgs) -> Result<()> {
+    let query = OrderQuery {
+        status: Some(args.status),
+        limit: args.limit,
+        symbols: args.symbols
+            .iter()
+            .flat_map(|symbols| symbols.split(','))
+            .map(|symbol| symbol.trim().to_uppercase())
+            .filter(|symbol| !symbol.is_empty())
+            .collect(),
+        side: args.side,
+    };
+    let orders = OrdersClient::from_env()?.list(&query).await?;
+    if orders.is_empty() {
+        warn!(query = ?query, "No orders match");
+    }
+    write_orders(&args.out, &orders)?;
+    info!(orders = orders.len(), output = ?args.out.output, "Listing complete");
+    Ok(())
+}
+
+async fn replace(args: ReplaceArgs) -> Result<()> {
+    let changes = ReplaceOrder {
+        qty: args.qty,
+        time_in_force: args.time_in_force,
+        limit_price: args.limit_price,
+        stop_price: args.stop_price,
+        trail: args.trail,
+        client_order_id: args.client_order_id,
+    };
+    let order = OrdersClient::from_env()?.replace(&args.id, &changes).await?;
+    info!(replaced = %args.id, id = %order.id, status = %order.status, "Order replaced");
+    write_orders(&args.out, &[order])
+}
+
+async fn cancel(args: CancelArgs) -> Result<()> {
+    let client = OrdersClient::from_env()?;
+    if args.all {
+        let canceled = client.cancel_all().await?;
+        let failed: Vec<&str> = canceled.iter().filter(|order| order.status != 200).map(|order| order.id.as_str()).collect();
+        println!("🚫 Asked to cancel {} open orders", canceled.len());
+        if !failed.is_empty() {
+            bail!("Failed to cancel {} orders: {}", failed.len(), failed.join(", "));
+        }
+        return Ok(());
+    }
+    let mut failed = 0;
+    for id in &args.ids {
+        match client.cancel(id).await {
+            Ok(()) => println!("🚫 Asked to cancel {}", id),
+            Err(e) => {
+                warn!(id = %id, error = %e, "Failed to cancel order");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("Failed to cancel {} of {} orders", failed, args.ids.len());
+    }
+    Ok(())
+}
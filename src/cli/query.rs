@@ -0,0 +1,64 @@
+use super::GlobalArgs;
+use crate::capture::{read_capture, CaptureFormat};
+use crate::duckdb_query::QueryDatabase;
+use crate::{Compression, DataFormat, OutputMode};
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Flags of `trade query`
+#[derive(clap::Args, Debug)]
+pub struct QueryArgs {
+    /// SQL to run on the `messages` table and the views of each message type, e.g. "SELECT symbol, avg(price) FROM trades GROUP BY symbol"
+    sql: String,
+    
+    /// Capture to load; repeat for several. gzip and zstd files are decompressed automatically
+    #[arg(short, long = "input", value_name = "INPUT", required = true)]
+    inputs: Vec<PathBuf>,
+    
+    /// Format of the inputs (json, csv, parquet, sqlite) [default: from each input's name, as for `convert`]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    from: Option<CaptureFormat>,
+    
+    /// Write the result to this file instead of the console
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    
+    /// Format of the result: plain is an aligned table
+    #[arg(short, long, value_enum, default_value_t = DataFormat::Plain)]
+    format: DataFormat,
+}
+
+/// Load every --input into a DuckDB database in memory and run the query on it
+pub fn run(args: QueryArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let database = QueryDatabase::open_in_memory()?;
+    let mut loader = database.loader()?;
+    for input in &args.inputs {
+        let format = match args.from {
+            Some(format) => format,
+            None => CaptureFormat::from_path(input)?,
+        };
+        let mut messages = 0;
+        read_capture(input, format, |data| {
+            messages += 1;
+            loader.append(&data)
+        })?;
+        info!(input = %input.display(), format = ?format, messages, "Loaded capture");
+    }
+    loader.finish()?;
+    
+    let result = database.query(&args.sql)?;
+    let output = match &args.output {
+        Some(path) => OutputMode::create_compressed_file_mode(path, args.format.clone(), false, Compression::from_extension(path))?,
+        None => OutputMode::create_console_mode(args.format.clone()),
+    };
+    for line in result.lines(&args.format)? {
+        output.writeln(&line)?;
+    }
+    output.close()?;
+    info!(rows = result.rows.len(), output = ?args.output, "Query complete");
+    
+    Ok(())
+}
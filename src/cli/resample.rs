@@ -0,0 +1,92 @@
+use super::{GlobalArgs, OutputArgs};
+use crate::resample::{read_bars, Fill, Filler, OhlcvBar, Resampler, Session, Timeframe, BARS_CSV_HEADER};
+use crate::{Compression, DataFormat, OutputMode};
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Flags of `trade resample`
+#[derive(clap::Args, Debug)]
+pub struct ResampleArgs {
+    /// Bars written by `historical`, e.g. 1Min bars; gzip and zstd files are decompressed automatically
+    #[arg(short, long)]
+    input: PathBuf,
+    
+    /// Input format (json, csv) [default: csv for .csv files, json otherwise]
+    #[arg(long, value_enum)]
+    input_format: Option<DataFormat>,
+    
+    /// Timeframe to resample to: <N>Sec, <N>Min, <N>Hour, <N>Day (trading days), <N>Week (from Monday) or <N>Month; a multiple of the input's
+    #[arg(short, long)]
+    timeframe: String,
+    
+    /// Bars to keep, and where intraday bars are counted from
+    #[arg(long, value_enum, default_value_t = Session::Regular)]
+    session: Session,
+    
+    /// Add the bars missing from each session day, for tools that need one row per interval: forward repeats the previous bar, zero-volume writes a flat bar at the previous close (Min and Hour timeframes only)
+    #[arg(long, value_enum, default_value_t = Fill::None)]
+    fill: Fill,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Resample the bars of --input to --timeframe and write them out
+pub fn run(args: ResampleArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let timeframe = Timeframe::parse(&args.timeframe)?;
+    let mut filler = match args.fill {
+        Fill::None => None,
+        fill => Some(Filler::new(timeframe, args.session, fill)?),
+    };
+    let input_format = args.input_format.unwrap_or_else(|| {
+        if args.input.to_string_lossy().contains(".csv") { DataFormat::Csv } else { DataFormat::Json }
+    });
+    info!(input = %args.input.display(), timeframe = %timeframe, session = ?args.session, format = ?args.out.format, output = ?args.out.output, "Resampling bars");
+    
+    let output_mode = match &args.out.output {
+        Some(path) => OutputMode::create_compressed_file_mode(path, args.out.format.clone(), args.out.append, Compression::from_extension(path))?,
+        None => OutputMode::create_console_mode(args.out.format.clone()),
+    };
+    if matches!(args.out.format, DataFormat::Csv) && !args.out.append {
+        output_mode.writeln(BARS_CSV_HEADER)?;
+    }
+    
+    let mut resampler = Resampler::new(timeframe, args.session);
+    let mut read = 0;
+    let mut written = 0;
+    let mut filled = 0;
+    let mut write = |bar: OhlcvBar, filler: &mut Option<Filler>| -> crate::Result<()> {
+        let bars = match filler {
+            Some(filler) => filler.push(bar),
+            None => vec![bar],
+        };
+        filled += bars.len() - 1;
+        for bar in &bars {
+            output_mode.writeln(&bar.format(&args.out.format)?)?;
+        }
+        written += bars.len();
+        Ok(())
+    };
+    read_bars(&args.input, &input_format, |bar| {
+        read += 1;
+        if let Some(done) = resampler.push(bar)? {
+            write(done, &mut filler)?;
+        }
+        Ok(())
+    })?;
+    for bar in resampler.finish() {
+        write(bar, &mut filler)?;
+    }
+    for bar in filler.map(Filler::finish).unwrap_or_default() {
+        output_mode.writeln(&bar.format(&args.out.format)?)?;
+        written += 1;
+        filled += 1;
+    }
+    output_mode.close()?;
+    info!(read, written, filled, "Resampling complete");
+    
+    Ok(())
+}
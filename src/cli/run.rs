@@ -0,0 +1,164 @@
+use super::historical::given_symbols;
+use super::{GlobalArgs, OutputArgs};
+use crate::orders::{OrderQueue, OrdersClient, OwnOrders, TimeInForce};
+use crate::portfolio::Portfolio;
+use crate::strategies::{self, StrategyParams};
+use crate::strategy::{DryRun, OrderRouter, StrategySink};
+use crate::{parse_feed, OutputMode, OutputSink, StreamingConfig, run_streaming_client, subscription_channel};
+use alpaca_trading_api_rust::StreamingFeed;
+use anyhow::{bail, Result};
+use chrono::Utc;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::info;
+
+/// Market data a live strategy receives
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveData {
+    Bars,
+    Trades,
+    Quotes,
+}
+
+/// Flags of `trade run`
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    /// Strategy to run: buy-and-hold, sma-crossover, rsi-reversion or opening-range-breakout
+    #[arg(long)]
+    strategy: String,
+    
+    /// Parameter of the strategy as NAME=VALUE, e.g. quantity=10; repeat for several. Overrides [strategies.<name>] in --config
+    #[arg(long = "param", value_name = "NAME=VALUE")]
+    params: Vec<String>,
+    
+    /// Symbols to trade (comma-separated)
+    #[arg(short, long, required_unless_present = "symbols_file")]
+    symbols: Option<String>,
+    
+    /// Read the symbols to trade from this file instead, as for `historical`
+    #[arg(long, value_name = "PATH", conflicts_with = "symbols")]
+    symbols_file: Option<PathBuf>,
+    
+    /// Market data the strategy receives (comma-separated: bars, trades, quotes)
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "bars")]
+    data: Vec<LiveData>,
+    
+    /// Data feed (iex, sip, delayed_sip) [default: ALPACA_FEED or iex]
+    #[arg(long, value_parser = parse_feed)]
+    feed: Option<StreamingFeed>,
+    
+    /// Time in force of the strategy's orders
+    #[arg(long, value_enum, default_value = "day")]
+    time_in_force: TimeInForce,
+    
+    /// Log the strategy's orders instead of placing them
+    #[arg(long)]
+    dry_run: bool,
+    
+    /// Allow an APCA_API_BASE_URL that is not the paper trading API, placing real orders
+    #[arg(long)]
+    live: bool,
+    
+    /// Write the account's positions and P&L every SECS seconds, plus a final snapshot at exit
+    #[arg(long, value_name = "SECS")]
+    portfolio_interval: Option<u64>,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Run a strategy on live market data, placing its orders on the paper
+/// trading account until Ctrl+C or SIGTERM
+pub async fn run(args: RunArgs, global: &GlobalArgs) -> Result<()> {
+    let config_file = global.init(false)?;
+    
+    // --param overrides [strategies.<name>] in --config
+    let mut params = config_file.strategy_params(&args.strategy);
+    params.extend(StrategyParams::parse(&args.params)?);
+    let strategy = strategies::create(&args.strategy, &params)?;
+    let Some(symbols) = given_symbols(args.symbols.as_deref(), args.symbols_file.as_deref())? else {
+        bail!("--symbols or --symbols-file is required");
+    };
+    let feed = match args.feed {
+        Some(feed) => feed,
+        None => match std::env::var("ALPACA_FEED") {
+            Ok(feed) => parse_feed(&feed)?,
+            Err(_) => StreamingFeed::Iex,
+        },
+    };
+    
+    // Client order IDs of this run start with the prefix, so the strategy
+    // only sees fills of its own orders
+    let prefix = format!("{}-{}", args.strategy, Utc::now().format("%Y%m%d%H%M%S"));
+    // A dry run never talks to the trading API, so its portfolio starts empty
+    let portfolio = Portfolio::new();
+    let (router, placer): (Box<dyn OrderRouter>, _) = if args.dry_run {
+        (Box::new(DryRun), None)
+    } else {
+        let client = OrdersClient::from_env()?;
+        if !args.live && !client.base_url().contains("paper-api.") {
+            bail!("APCA_API_BASE_URL is {}, not the paper trading API. Pass --live to place real orders", client.base_url());
+        }
+        // The portfolio starts from what the account holds, so earlier runs'
+        // positions count towards its P&L
+        let held = client.positions().await?;
+        portfolio.reconcile(&held);
+        info!(positions = held.len(), "Portfolio reconciled with the account");
+        info!(base_url = %client.base_url(), prefix = %prefix, "Placing orders");
+        let (queue, orders) = OrderQueue::new(&prefix, args.time_in_force);
+        (Box::new(queue), Some(tokio::spawn(async move { client.place_queued(orders).await })))
+    };
+    let sink = StrategySink::new(strategy, router);
+    
+    let output_mode: Box<dyn OutputSink> = match &args.out.output {
+        Some(path) => Box::new(OutputMode::create_file_mode(path, args.out.format.clone(), args.out.append)?),
+        None => Box::new(OutputMode::create_console_mode(args.out.format.clone())),
+    };
+    let mut builder = StreamingConfig::builder()
+        .feed(feed)
+        .trade_updates(!args.dry_run)
+        .sink(output_mode)
+        .sink(Box::new(portfolio.clone()))
+        .sink(Box::new(OwnOrders::new(sink.clone(), &prefix)));
+    for data in &args.data {
+        builder = match data {
+            LiveData::Bars => builder.bars(&symbols),
+            LiveData::Trades => builder.trades(&symbols),
+            LiveData::Quotes => builder.quotes(&symbols),
+        };
+    }
+    let config = builder.build()?;
+    info!(strategy = %args.strategy, params = ?args.params, symbols = ?symbols, data = ?args.data, dry_run = args.dry_run, "Starting live strategy");
+    
+    let snapshots = args.portfolio_interval.map(|secs| {
+        tokio::spawn(portfolio.clone().emit_every(config.output_mode.clone(), Duration::from_secs(secs.max(1))))
+    });
+    
+    let (_subscriptions, mut commands) = subscription_channel();
+    let result = run_streaming_client(&config, &mut commands).await;
+    if let Some(snapshots) = snapshots {
+        snapshots.abort();
+    }
+    portfolio.emit(&config.output_mode, true)?;
+    // Closing the outputs stops the strategy; the orders it places on the way
+    // out are sent before the queue closes
+    config.output_mode.close()?;
+    let stats = sink.stats();
+    drop(sink);
+    drop(config);
+    println!(
+        "🤖 {}: {} bars, {} trades, {} quotes, {} fills, {} orders",
+        args.strategy, stats.bars, stats.trades, stats.quotes, stats.fills, stats.orders
+    );
+    if let Some(placer) = placer {
+        let placed = placer.await?;
+        println!("   {} orders placed, {} rejected", placed.submitted, placed.rejected);
+    }
+    let totals = portfolio.totals();
+    println!(
+        "   {} positions worth ${:.2}, P&L ${:.2} ({:.2} unrealized, {:.2} realized)",
+        totals.positions, totals.market_value, totals.total_pnl, totals.unrealized_pnl, totals.realized_pnl
+    );
+    
+    Ok(result?)
+}
@@ -0,0 +1,66 @@
+use super::{GlobalArgs, OutputArgs};
+use crate::snapshot::{parse_snapshot_feed, SnapshotClient, SNAPSHOT_CSV_HEADER};
+use crate::{DataFormat, OutputMode};
+use anyhow::Result;
+use tracing::{info, warn};
+
+/// Flags of `trade snapshot`
+#[derive(clap::Args, Debug)]
+pub struct SnapshotArgs {
+    /// Symbols to look up (comma-separated)
+    #[arg(short, long)]
+    symbols: String,
+    
+    /// Data feed source (iex, sip, delayed_sip, boats, otc) [default: ALPACA_FEED or iex]
+    #[arg(long, value_parser = parse_snapshot_feed)]
+    feed: Option<String>,
+    
+    #[command(flatten)]
+    out: OutputArgs,
+}
+
+/// Print the latest trade, quote, minute bar, daily bar and previous daily
+/// bar of every symbol
+pub async fn run(args: SnapshotArgs, global: &GlobalArgs) -> Result<()> {
+    global.init(false)?;
+    
+    let symbols: Vec<String> = args.symbols
+        .split(',')
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if symbols.is_empty() {
+        return Err(anyhow::anyhow!("No symbols given"));
+    }
+    
+    let feed = match args.feed {
+        Some(feed) => feed,
+        None => match std::env::var("ALPACA_FEED") {
+            Ok(feed) => parse_snapshot_feed(&feed)?,
+            Err(_) => "iex".to_string(),
+        },
+    };
+    
+    let output_mode = match &args.out.output {
+        Some(path) => OutputMode::create_file_mode(path, args.out.format.clone(), args.out.append)?,
+        None => OutputMode::create_console_mode(args.out.format.clone()),
+    };
+    
+    let snapshots = SnapshotClient::from_env()?.fetch_snapshots(&symbols, &feed).await?;
+    for symbol in &symbols {
+        if !snapshots.iter().any(|snapshot| &snapshot.symbol == symbol) {
+            warn!(symbol = %symbol, "No snapshot for symbol");
+        }
+    }
+    
+    if matches!(args.out.format, DataFormat::Csv) {
+        output_mode.writeln(SNAPSHOT_CSV_HEADER)?;
+    }
+    for snapshot in &snapshots {
+        output_mode.writeln(&snapshot.format(&args.out.format)?)?;
+    }
+    output_mode.close()?;
+    info!(symbols = symbols.len(), snapshots = snapshots.len(), feed = %feed, "Snapshot complete");
+    
+    Ok(())
+}
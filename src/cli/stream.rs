@@ -0,0 +1,692 @@
+use super::{GlobalArgs, OutputArgs};
+use crate::capture::CaptureFormat;
+use crate::conflation::{parse_duration, parse_interval, QuoteConflation, QuoteConflator, QuoteFilter};
+use crate::raw_frames::RawRecorder;
+use crate::replay::{parse_replay_speed, run_replay, ReplaySpeed};
+use crate::retry::{parse_jitter, parse_max_retries};
+use crate::rotation::parse_size;
+use crate::simulator::{run_simulator, DataSource, SimulatorConfig};
+use crate::{parse_feed, AlertEngine, ConfigFile, ConfigWatcher, RetryPolicy, AlertRules, BackpressurePolicy, ColorChoice, ConsoleOptions, PlainTemplates, Compression, LatencyMonitor, MessageFilter, OutputMode, OutputSink, PipelinedSink, RotateInterval, RotatingFileSink, RotationPolicy, SessionStats, SessionSummary, StreamingConfig, StructuredCsvSink, SubscriptionCommand, SubscriptionHandle, run_streaming_client, subscription_channel};
+use anyhow::Result;
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{info, warn};
+
+/// Flags of `trade stream` and the streaming-client binary
+#[derive(clap::Args, Debug)]
+pub struct StreamArgs {
+    #[command(flatten)]
+    out: OutputArgs,
+    
+    /// Start a new output file every hour or day, e.g. capture-2024-05-03.jsonl
+    #[arg(long, value_enum, requires = "output")]
+    rotate: Option<RotateInterval>,
+    
+    /// Start a new output file once the current one reaches this size (e.g. 500MB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "output")]
+    rotate_size: Option<u64>,
+    
+    /// Gzip output files once they have been rotated
+    #[arg(long)]
+    compress_rotated: bool,
+    
+    /// Compress file output; the extension (.gz or .zst) is added to the path if missing
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    compress: Compression,
+    
+    /// Messages buffered per output before backpressure applies
+    #[arg(long, default_value = "10000")]
+    queue_capacity: usize,
+    
+    /// What to do when an output falls behind: wait (block) or discard the oldest queued message
+    #[arg(long, value_enum, default_value_t = BackpressurePolicy::Block)]
+    backpressure: BackpressurePolicy,
+    
+    /// Additional output as FORMAT[:PATH], repeatable (e.g. --tee json:raw.jsonl --tee csv:data.csv)
+    #[arg(long, value_name = "FORMAT[:PATH]")]
+    tee: Vec<String>,
+    
+    /// Also write trades, quotes, bars and other typed messages as CSV files (trades.csv, quotes.csv, bars.csv, daily_bars.csv, ...) in this directory
+    #[arg(long, value_name = "DIR")]
+    csv_dir: Option<PathBuf>,
+    
+    /// Also write trades, quotes and bars as a hive-partitioned Parquet dataset in this directory
+    #[cfg(feature = "parquet")]
+    #[arg(long, value_name = "DIR")]
+    dataset: Option<PathBuf>,
+    
+    /// Only write messages matching this expression, e.g. "type==t && price>100 && size>=500"
+    #[arg(long, value_name = "EXPR")]
+    filter: Option<MessageFilter>,
+    
+    /// Write only the latest quote per symbol once per interval, e.g. 250ms or 1s
+    #[arg(long, value_name = "DURATION", value_parser = parse_interval, conflicts_with = "quotes")]
+    quote_sample: Option<std::time::Duration>,
+    
+    /// Which quotes to write
+    #[arg(long, value_enum, default_value_t = QuoteFilter::All)]
+    quotes: QuoteFilter,
+    
+    /// Evaluate the alert rules in this TOML file and write an alert message when one triggers
+    #[arg(long, value_name = "PATH")]
+    alerts: Option<PathBuf>,
+    
+    /// Send disconnects, Alpaca errors and alerts to the [[notifier]] entries in this TOML file (plus any NOTIFY_* env vars)
+    #[cfg(feature = "webhooks")]
+    #[arg(long, value_name = "PATH")]
+    notify: Option<PathBuf>,
+    
+    /// Run each message through this Rhai script's on_message(msg) before output
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "PATH")]
+    script: Option<PathBuf>,
+    
+    /// Log receive latency percentiles (p50/p95/p99) per symbol every SECS seconds
+    #[arg(long, value_name = "SECS")]
+    latency_report: Option<u64>,
+    
+    /// Write per-symbol session stats (VWAP, volume, high/low, trades) every SECS seconds, plus a summary at exit
+    #[arg(long, value_name = "SECS")]
+    stats_interval: Option<u64>,
+    
+    /// Spread the symbols over several websocket connections of at most N symbols each
+    #[arg(long, value_name = "N")]
+    max_symbols_per_connection: Option<usize>,
+    
+    /// Split the symbols over N parallel websocket connections, each with its own retries
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    shards: usize,
+    
+    /// Log per-connection message, disconnect and reconnect counts every SECS seconds
+    #[arg(long, value_name = "SECS")]
+    shard_report: Option<u64>,
+    
+    /// Feed to switch to when the primary feed keeps failing (iex, sip, delayed_sip)
+    #[arg(long, value_name = "FEED", value_parser = parse_feed)]
+    backup_feed: Option<alpaca_trading_api_rust::StreamingFeed>,
+    
+    /// Consecutive failed connections on the primary feed before switching to the backup
+    #[arg(long, value_name = "N", default_value_t = 2, requires = "backup_feed")]
+    failover_after: u32,
+    
+    /// Seconds to stay on the backup feed before trying the primary again
+    #[arg(long, value_name = "SECS", default_value_t = 900, requires = "backup_feed")]
+    failback_after: u64,
+    
+    /// Reconnect when no message arrives for SECS seconds during market hours
+    #[arg(long, value_name = "SECS")]
+    silence_timeout: Option<u64>,
+    
+    /// Apply --silence-timeout around the clock, not just from 04:00 to 20:00 New York time on weekdays
+    #[arg(long, requires = "silence_timeout")]
+    silence_always: bool,
+    
+    /// Most symbols sent in one subscribe message; longer lists are split
+    #[arg(long, value_name = "N", default_value_t = crate::DEFAULT_SUBSCRIBE_CHUNK_SIZE)]
+    subscribe_chunk_size: usize,
+    
+    /// Apply edits of the --config symbol lists to the running session
+    #[arg(long)]
+    watch_config: bool,
+    
+    /// Consecutive failed connection attempts before giving up, or "infinite" (default 5)
+    #[arg(long, value_name = "N")]
+    max_retries: Option<String>,
+    
+    /// Wait before the first reconnect attempt, doubled for each further one (default 2s)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    retry_backoff_base: Option<std::time::Duration>,
+    
+    /// Longest wait between reconnect attempts (default 64s)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    retry_backoff_max: Option<std::time::Duration>,
+    
+    /// Randomize each reconnect wait by up to this fraction either way (0.0 to 1.0)
+    #[arg(long, value_name = "FRACTION", value_parser = parse_jitter)]
+    retry_jitter: Option<f64>,
+    
+    /// Only forget failed attempts once a connection has stayed up this long (default: as soon as it authenticates)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    retry_reset_after: Option<std::time::Duration>,
+    
+    /// Also write the end-of-session summary (messages per type and symbol, duration, reconnects, drops) to PATH as JSON
+    #[arg(long, value_name = "PATH")]
+    summary_file: Option<PathBuf>,
+    
+    /// Revise session stats when trade corrections and cancels arrive
+    #[arg(long, requires = "stats_interval")]
+    apply_corrections: bool,
+    
+    /// Check the configured symbols against the Alpaca assets API before subscribing and warn about, drop or fail on unknown, inactive or untradable ones
+    #[cfg(feature = "assets")]
+    #[arg(long, value_enum, value_name = "POLICY", num_args = 0..=1, default_missing_value = "warn")]
+    validate_symbols: Option<crate::assets::SymbolValidation>,
+    
+    /// Only connect during these market sessions (pre, regular, after; comma-separated), sleeping in between
+    #[cfg(feature = "market-hours")]
+    #[arg(long, value_enum, value_name = "SESSIONS", value_delimiter = ',')]
+    sessions: Option<Vec<crate::market_hours::MarketSession>>,
+    
+    /// Run across trading days: connect before each session (regular hours unless --sessions says otherwise), rotate output at the close, and wait for the next trading day
+    #[cfg(feature = "market-hours")]
+    #[arg(long)]
+    daemon: bool,
+    
+    /// Connect this many seconds before each session opens (default 300 with --daemon, else 0)
+    #[cfg(feature = "market-hours")]
+    #[arg(long, value_name = "SECS")]
+    connect_early: Option<u64>,
+    
+    /// Write the process ID to PATH while the daemon runs
+    #[cfg(feature = "market-hours")]
+    #[arg(long, value_name = "PATH", requires = "daemon")]
+    pid_file: Option<PathBuf>,
+    
+    /// Upload finished output files to this S3 bucket (credentials from AWS_* env vars)
+    #[cfg(feature = "s3")]
+    #[arg(long)]
+    s3_bucket: Option<String>,
+    
+    /// Key prefix for uploaded objects
+    #[cfg(feature = "s3")]
+    #[arg(long, default_value = "captures")]
+    s3_prefix: String,
+    
+    /// Stream JSON lines straight into one S3 object per session instead of uploading files
+    #[cfg(feature = "s3")]
+    #[arg(long, requires = "s3_bucket")]
+    s3_stream: bool,
+    
+    /// Publish to this NATS server (subjects md.<symbol>.<type>); defaults to $NATS_URL
+    #[cfg(feature = "nats")]
+    #[arg(long)]
+    nats_url: Option<String>,
+    
+    /// Persist published messages in this JetStream stream; defaults to $NATS_STREAM
+    #[cfg(feature = "nats")]
+    #[arg(long)]
+    nats_stream: Option<String>,
+    
+    /// Serve captured data over HTTP on this address (e.g. 127.0.0.1:8080)
+    #[cfg(feature = "http")]
+    #[arg(long)]
+    http_addr: Option<std::net::SocketAddr>,
+    
+    /// Serve the live stream over gRPC on this address (e.g. 127.0.0.1:50051)
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    grpc_addr: Option<std::net::SocketAddr>,
+    
+    /// Also capture order events from the account's trade_updates stream
+    #[arg(long)]
+    trade_updates: bool,
+    
+    /// Also record every websocket message as received, before it is decoded or filtered, to this NDJSON file (compressed if it ends in .gz or .zst); --replay and the capture commands read it back
+    #[arg(long, value_name = "PATH")]
+    record_raw: Option<PathBuf>,
+    
+    /// Where market data comes from: the Alpaca websocket, or sim for random-walk trades, quotes and minute bars of the configured symbols, generated locally without credentials
+    #[arg(long, value_enum, default_value_t = DataSource::Alpaca)]
+    source: DataSource,
+    
+    /// Mean trades and quotes per second over all symbols with --source sim (default 10)
+    #[arg(long, value_name = "N")]
+    sim_rate: Option<f64>,
+    
+    /// Annualized volatility of the simulated prices (default 0.3 for 30%)
+    #[arg(long, value_name = "FRACTION")]
+    sim_volatility: Option<f64>,
+    
+    /// Simulated bid-ask spread in basis points of the price, at least one cent (default 5)
+    #[arg(long, value_name = "BPS")]
+    sim_spread: Option<f64>,
+    
+    /// Price every simulated symbol starts at [default: between $20 and $500 per symbol]
+    #[arg(long, value_name = "PRICE")]
+    sim_price: Option<f64>,
+    
+    /// Seed of the simulator, so runs repeat the same prices and sizes
+    #[arg(long, value_name = "N")]
+    sim_seed: Option<u64>,
+    
+    /// Stop the simulated session after this many trades and quotes
+    #[arg(long, value_name = "N")]
+    sim_messages: Option<u64>,
+    
+    /// Replay this capture through the outputs, filter, alerts, script and stats instead of connecting, e.g. to test them on yesterday's data; its format comes from its name, as for `convert`
+    #[arg(long, value_name = "CAPTURE", conflicts_with = "source")]
+    replay: Option<PathBuf>,
+    
+    /// Pace of --replay: max writes messages as fast as the outputs take them, a factor such as 1, 10x or 0.5x keeps the gaps between them as received, divided by the factor
+    #[arg(long, value_name = "SPEED", value_parser = parse_replay_speed, default_value = "1", requires = "replay")]
+    replay_speed: ReplaySpeed,
+    
+    /// Read subscription commands from stdin (e.g. "subscribe trades AAPL,MSFT")
+    #[arg(long)]
+    stdin_control: bool,
+    
+    /// Show a live dashboard instead of printing lines (logs are dropped unless --log-file is set)
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with = "stdin_control")]
+    tui: bool,
+    
+    /// Plain-format template for one message type as TYPE=TEMPLATE, repeatable (e.g. --template trades="{{symbol}} {{price}} {{size}}")
+    #[arg(long, value_name = "TYPE=TEMPLATE")]
+    template: Vec<String>,
+    
+    /// Color plain console output: green/red for up/down ticks and bars, red for errors
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    
+    /// Strip emoji from plain console output
+    #[arg(long)]
+    no_emoji: bool,
+}
+
+/// Retry policy from the config file, then environment variables, then flags
+fn retry_policy(args: &StreamArgs, file: &ConfigFile) -> Result<RetryPolicy> {
+    let mut retry = file.retry.clone().from_env()?;
+    if let Some(max_retries) = &args.max_retries {
+        retry.max_retries = parse_max_retries(max_retries)?;
+    }
+    if let Some(base) = args.retry_backoff_base {
+        retry.backoff_base = base;
+    }
+    if let Some(max) = args.retry_backoff_max {
+        retry.backoff_max = max;
+    }
+    if let Some(jitter) = args.retry_jitter {
+        retry.jitter = jitter;
+    }
+    if let Some(reset_after) = args.retry_reset_after {
+        retry.reset_after = reset_after;
+    }
+    Ok(retry)
+}
+
+/// Stream right away, or only during market sessions with `--sessions` or `--daemon`, or replay a capture with `--replay`, or simulate data with `--source sim`
+async fn stream(config: &StreamingConfig, commands: &mut tokio::sync::mpsc::UnboundedReceiver<SubscriptionCommand>, args: &StreamArgs) -> Result<()> {
+    if let Some(path) = &args.replay {
+        let stats = run_replay(path, CaptureFormat::from_path(path)?, args.replay_speed, config).await?;
+        info!(read = stats.read, written = stats.written, filtered = stats.filtered, "Replay complete");
+        return Ok(());
+    }
+    if args.source == DataSource::Sim {
+        let defaults = SimulatorConfig::default();
+        let settings = SimulatorConfig {
+            rate: args.sim_rate.unwrap_or(defaults.rate),
+            volatility: args.sim_volatility.unwrap_or(defaults.volatility),
+            spread_bps: args.sim_spread.unwrap_or(defaults.spread_bps),
+            start_price: args.sim_price,
+            seed: args.sim_seed,
+            max_messages: args.sim_messages,
+        };
+        let stats = run_simulator(&settings, config, commands).await?;
+        info!(trades = stats.trades, quotes = stats.quotes, bars = stats.bars, filtered = stats.filtered, "Simulation complete");
+        return Ok(());
+    }
+    #[cfg(feature = "market-hours")]
+    if args.daemon || args.sessions.is_some() {
+        use crate::market_hours::{run_during_sessions, MarketCalendar, MarketSession, SessionSchedule};
+        let mut schedule = SessionSchedule::new(args.sessions.clone().unwrap_or_else(|| vec![MarketSession::Regular]));
+        schedule.daemon = args.daemon;
+        let connect_early = args.connect_early.unwrap_or(if args.daemon { 300 } else { 0 });
+        schedule.connect_early = std::time::Duration::from_secs(connect_early);
+        return Ok(run_during_sessions(config, commands, &MarketCalendar::from_env()?, &schedule).await?);
+    }
+    Ok(run_streaming_client(config, commands).await?)
+}
+
+async fn read_stdin_commands(handle: SubscriptionHandle) -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.parse::<SubscriptionCommand>() {
+            Ok(command) => handle.send(command)?,
+            Err(e) => warn!(error = %e, "Ignoring stdin command"),
+        }
+    }
+    Ok(())
+}
+
+/// Stream until the session ends, Ctrl+C or SIGTERM
+pub async fn run(args: StreamArgs, global: &GlobalArgs) -> Result<()> {
+    // The dashboard draws on the terminal, so logs only go to --log-file
+    #[cfg(feature = "tui")]
+    let config_file = global.init(args.tui)?;
+    #[cfg(not(feature = "tui"))]
+    let config_file = global.init(false)?;
+    // Checked here rather than by clap, which cannot see a --config given before `trade stream`
+    if args.watch_config && global.config.is_none() {
+        return Err(anyhow::anyhow!("--watch-config needs --config"));
+    }
+    let sim_flags = [args.sim_rate.is_some(), args.sim_volatility.is_some(), args.sim_spread.is_some(), args.sim_price.is_some(), args.sim_seed.is_some(), args.sim_messages.is_some()];
+    if args.source != DataSource::Sim && sim_flags.contains(&true) {
+        return Err(anyhow::anyhow!("The --sim-* flags need --source sim"));
+    }
+    
+    #[cfg(feature = "tui")]
+    let dashboard = args.tui.then(crate::dashboard::DashboardState::new);
+    
+    #[cfg(feature = "s3")]
+    let s3_config = args.s3_bucket.as_ref().map(|bucket| crate::s3_sink::S3Config {
+        bucket: bucket.clone(),
+        prefix: args.s3_prefix.clone(),
+    });
+    #[cfg(feature = "s3")]
+    let s3_uploader = match &s3_config {
+        Some(_) if args.out.output.is_none() && !args.s3_stream => {
+            return Err(anyhow::anyhow!("--s3-bucket uploads files written with --output. Use --s3-stream to stream without a local file"));
+        }
+        Some(s3_config) if !args.s3_stream => {
+            info!(bucket = %s3_config.bucket, prefix = %s3_config.prefix, "Uploading finished files to S3");
+            Some(crate::s3_sink::S3Uploader::new(s3_config.clone(), s3_config.build_store()?))
+        }
+        _ => None,
+    };
+    #[cfg(feature = "s3")]
+    let upload_files = s3_uploader.is_some();
+    #[cfg(not(feature = "s3"))]
+    let upload_files = false;
+    #[cfg(feature = "market-hours")]
+    let daemon = args.daemon;
+    #[cfg(not(feature = "market-hours"))]
+    let daemon = false;
+    #[cfg(feature = "market-hours")]
+    let _pid_file = match &args.pid_file {
+        Some(path) => Some(crate::daemon::PidFile::create(path)?),
+        None => None,
+    };
+    
+    let mut templates = PlainTemplates::new();
+    for spec in &args.template {
+        templates.add(spec)?;
+    }
+    let templates = std::sync::Arc::new(templates);
+    
+    // Create output mode based on arguments
+    let output_mode: Box<dyn OutputSink> = match &args.out.output {
+        // The daemon starts a new file at every close
+        Some(output_path) if args.rotate.is_some() || args.rotate_size.is_some() || upload_files || daemon => {
+            let policy = RotationPolicy {
+                interval: args.rotate,
+                max_bytes: args.rotate_size,
+                compress: args.compress_rotated,
+            };
+            info!(path = %output_path.display(), format = ?args.out.format, ?policy, "Writing output to rotating files");
+            let sink = RotatingFileSink::new(output_path, args.out.format.clone(), args.out.append, policy)
+                .with_compression(args.compress)
+                .with_templates(templates.clone());
+            #[cfg(feature = "s3")]
+            let sink = match s3_uploader.clone() {
+                Some(uploader) => sink.on_finished(move |path| uploader.upload(path)),
+                None => sink,
+            };
+            Box::new(sink)
+        }
+        Some(output_path) => {
+            let output_path = args.compress.with_extension(output_path);
+            info!(path = %output_path.display(), format = ?args.out.format, compression = ?args.compress, "Writing output to file");
+            Box::new(OutputMode::create_compressed_file_mode(&output_path, args.out.format.clone(), args.out.append, args.compress)?.with_templates(templates.clone()))
+        }
+        None => {
+            let options = ConsoleOptions::new(args.color.enabled(), !args.no_emoji);
+            Box::new(OutputMode::create_styled_console_mode(args.out.format.clone(), options).with_templates(templates.clone()))
+        }
+    };
+    // The dashboard takes the console's place
+    #[cfg(feature = "tui")]
+    let output_mode: Box<dyn OutputSink> = match &dashboard {
+        Some(dashboard) if args.out.output.is_none() => Box::new(dashboard.clone()),
+        _ => output_mode,
+    };
+    
+    let session_summary = SessionSummary::new();
+    // Console and file writes run on their own writer threads so slow I/O never blocks the stream
+    let pipelined = |sink: Box<dyn OutputSink>| -> Result<Box<dyn OutputSink>> {
+        let sink = PipelinedSink::new(sink, args.queue_capacity, args.backpressure)?;
+        session_summary.add_drop_counter(sink.dropped_counter());
+        Ok(Box::new(sink))
+    };
+    
+    let mut config = StreamingConfig::new(pipelined(output_mode)?);
+    config_file.symbols.apply(&mut config);
+    #[cfg(feature = "assets")]
+    if let Some(policy) = args.validate_symbols {
+        use crate::assets::{apply_symbol_validation, AssetsClient};
+        let problems = AssetsClient::from_env()?.check_symbols(&config.all_symbols()).await?;
+        info!(checked = config.all_symbols().len(), problems = problems.len(), "Validated symbols");
+        apply_symbol_validation(&mut config, &problems, policy)?;
+    }
+    #[cfg(feature = "tui")]
+    if let Some(dashboard) = &dashboard {
+        dashboard.add_symbols(config.trade_symbols.iter().chain(&config.quote_symbols).chain(&config.bar_symbols).cloned());
+        if args.out.output.is_some() {
+            config.add_sink(Box::new(dashboard.clone()));
+        }
+    }
+    for spec in &args.tee {
+        info!(spec = %spec, "Adding tee output");
+        config.add_sink(pipelined(Box::new(OutputMode::from_spec(spec, args.out.append)?.with_templates(templates.clone())))?);
+    }
+    if let Some(csv_dir) = &args.csv_dir {
+        info!(dir = %csv_dir.display(), "Writing typed CSV files");
+        config.add_sink(pipelined(Box::new(StructuredCsvSink::create(csv_dir, args.out.append)?))?);
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(dataset_dir) = &args.dataset {
+        info!(dir = %dataset_dir.display(), "Writing Parquet dataset");
+        config.add_sink(pipelined(Box::new(crate::dataset::DatasetWriter::new(dataset_dir)?))?);
+    }
+    #[cfg(feature = "s3")]
+    if let Some(s3_config) = s3_config.as_ref().filter(|_| args.s3_stream) {
+        let name = format!("capture-{}.jsonl", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"));
+        let key = s3_config.object_key(&name);
+        info!(bucket = %s3_config.bucket, %key, "Streaming to S3");
+        config.add_sink(Box::new(crate::s3_sink::S3StreamSink::create(s3_config.build_store()?, key).await?));
+    }
+    #[cfg(feature = "nats")]
+    if args.nats_url.is_some() || std::env::var("NATS_URL").is_ok() {
+        let mut nats_config = crate::nats_sink::NatsConfig::from_env();
+        if let Some(url) = &args.nats_url {
+            nats_config.url = url.clone();
+        }
+        if let Some(stream) = &args.nats_stream {
+            nats_config.stream = Some(stream.clone());
+        }
+        info!(url = %nats_config.url, stream = ?nats_config.stream, "Publishing to NATS");
+        config.add_sink(Box::new(crate::nats_sink::NatsSink::connect(nats_config).await?));
+    }
+    
+    #[cfg(feature = "http")]
+    if let Some(addr) = args.http_addr {
+        let buffer = crate::CaptureBuffer::new(10_000);
+        config.add_sink(Box::new(buffer.clone()));
+        info!(%addr, "Serving captured data over HTTP");
+        tokio::spawn(async move {
+            if let Err(e) = crate::http_server::serve(addr, buffer).await {
+                tracing::error!(error = %e, "HTTP server error");
+            }
+        });
+    }
+    
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = args.grpc_addr {
+        let sink = crate::grpc_server::GrpcBroadcastSink::new(4096);
+        config.add_sink(Box::new(sink.clone()));
+        info!(%addr, "Serving live stream over gRPC");
+        tokio::spawn(async move {
+            if let Err(e) = crate::grpc_server::serve(addr, sink).await {
+                tracing::error!(error = %e, "gRPC server error");
+            }
+        });
+    }
+    
+    // Added ahead of the wrappers below so it also sees the alerts they raise
+    #[cfg(feature = "webhooks")]
+    let notifier = {
+        let mut notifiers = crate::notifier::NotifierConfig::from_env()?;
+        if let Some(path) = &args.notify {
+            notifiers.extend(crate::notifier::NotifierConfig::load(path)?);
+        }
+        if notifiers.is_empty() {
+            None
+        } else {
+            info!(notifiers = notifiers.len(), "Sending notifications");
+            let notifier = crate::notifier::Notifier::spawn(notifiers)?;
+            config.add_sink(Box::new(notifier.clone()));
+            Some(notifier)
+        }
+    };
+    
+    // Conflate quotes once, ahead of every output; the monitors below still see every quote
+    let conflation = match (args.quote_sample, args.quotes) {
+        (Some(interval), _) => Some(QuoteConflation::Sample(interval)),
+        (None, QuoteFilter::OnChangeOnly) => Some(QuoteConflation::OnChange),
+        (None, QuoteFilter::All) => None,
+    };
+    if let Some(mode) = conflation {
+        info!(?mode, "Conflating quotes");
+        let conflator = QuoteConflator::new(Box::new(std::mem::take(&mut config.output_mode)), mode);
+        tokio::spawn(conflator.clone().sample_every());
+        session_summary.add_drop_counter({ let conflator = conflator.clone(); move || conflator.dropped() });
+        config.add_sink(Box::new(conflator));
+    }
+    // Rules see every quote, after the script has run
+    #[cfg(feature = "webhooks")]
+    let mut alert_webhooks = Vec::new();
+    if let Some(path) = &args.alerts {
+        let rules = AlertRules::load(path)?;
+        info!(path = %path.display(), rules = rules.rules.len(), "Evaluating alert rules");
+        #[allow(unused_mut)]
+        let mut engine = AlertEngine::new(rules.rules, Box::new(std::mem::take(&mut config.output_mode)));
+        #[cfg(feature = "webhooks")]
+        for url in &rules.webhooks {
+            let webhook = crate::webhook::Webhook::spawn(url)?;
+            alert_webhooks.push(webhook.clone());
+            engine = engine.on_alert(move |alert| webhook.post(serde_json::json!(alert)));
+        }
+        #[cfg(not(feature = "webhooks"))]
+        if !rules.webhooks.is_empty() {
+            warn!("Alert webhooks are ignored; rebuild with --features webhooks");
+        }
+        config.add_sink(Box::new(engine));
+    }
+    // The script sees messages before conflation
+    #[cfg(feature = "scripting")]
+    if let Some(script) = &args.script {
+        info!(path = %script.display(), "Running message script");
+        let hook = crate::scripting::ScriptHook::load(script, Box::new(std::mem::take(&mut config.output_mode)))?;
+        config.add_sink(Box::new(hook));
+    }
+    
+    let latency_monitor = args.latency_report.map(|secs| {
+        let monitor = LatencyMonitor::new(1000);
+        config.add_sink(Box::new(monitor.clone()));
+        tokio::spawn(monitor.clone().report_every(std::time::Duration::from_secs(secs.max(1))));
+        monitor
+    });
+    let session_stats = args.stats_interval.map(|secs| {
+        let stats = SessionStats::new().with_corrections(args.apply_corrections);
+        config.add_sink(Box::new(stats.clone()));
+        tokio::spawn(stats.clone().emit_every(config.output_mode.clone(), std::time::Duration::from_secs(secs.max(1))));
+        stats
+    });
+    config.add_sink(Box::new(session_summary.clone()));
+    
+    if args.replay.is_none() && args.source == DataSource::Alpaca {
+        info!("Starting Alpaca streaming client");
+    }
+    config.trade_updates |= args.trade_updates;
+    config.subscribe_chunk_size = args.subscribe_chunk_size;
+    config.max_symbols_per_connection = args.max_symbols_per_connection;
+    config.shards = args.shards;
+    if let Some(feed) = args.backup_feed.clone() {
+        config.backup_feed = Some(feed);
+    }
+    config.retry = retry_policy(&args, &config_file)?;
+    info!(retry = ?config.retry, "Retry policy");
+    config.failover_after = args.failover_after.max(1);
+    config.failback_after = std::time::Duration::from_secs(args.failback_after);
+    config.silence_timeout = args.silence_timeout.map(|secs| std::time::Duration::from_secs(secs.max(1)));
+    config.silence_market_hours_only = !args.silence_always;
+    if let Some(secs) = args.shard_report {
+        tokio::spawn(config.shard_metrics.clone().report_every(std::time::Duration::from_secs(secs.max(1))));
+    }
+    if let Some(filter) = &args.filter {
+        info!(%filter, "Filtering messages");
+    }
+    config.filter = args.filter.clone();
+    if let Some(path) = &args.record_raw {
+        info!(path = %path.display(), "Recording raw websocket messages");
+        config.raw_recorder = Some(RawRecorder::create(path, args.out.append)?);
+    }
+    
+    let (subscription_handle, mut commands) = subscription_channel();
+    if let (Some(path), true) = (&global.config, args.watch_config) {
+        info!(path = %path.display(), "Watching config for symbol list changes");
+        tokio::spawn(ConfigWatcher::new(path, &config).run(subscription_handle.clone(), std::time::Duration::from_millis(500)));
+    }
+    if args.stdin_control {
+        tokio::spawn(read_stdin_commands(subscription_handle.clone()));
+    }
+    
+    #[cfg(feature = "tui")]
+    let result = match &dashboard {
+        Some(dashboard) => {
+            let ui = tokio::task::spawn_blocking({
+                let dashboard = dashboard.clone();
+                move || crate::dashboard::run(dashboard)
+            });
+            tokio::pin!(ui);
+            // Quitting the dashboard ends the session; a session that ends on its own closes the dashboard
+            tokio::select! {
+                result = stream(&config, &mut commands, &args) => {
+                    dashboard.stop();
+                    ui.await??;
+                    result
+                }
+                ui_result = &mut ui => Ok(ui_result??),
+            }
+        }
+        None => stream(&config, &mut commands, &args).await,
+    };
+    #[cfg(not(feature = "tui"))]
+    let result = stream(&config, &mut commands, &args).await;
+    if result.is_ok() {
+        info!("Streaming session completed successfully");
+    }
+    if let Some(stats) = &session_stats {
+        stats.emit(&config.output_mode, true)?;
+    }
+    session_summary.finish(&config.output_mode, args.summary_file.as_deref())?;
+    // Drains every writer queue before the files are closed
+    config.output_mode.close()?;
+    if let Some(recorder) = &config.raw_recorder {
+        recorder.close()?;
+    }
+    if let Some(monitor) = &latency_monitor {
+        monitor.report();
+    }
+    if args.shard_report.is_some() {
+        config.shard_metrics.report();
+    }
+    
+    #[cfg(feature = "s3")]
+    if let Some(uploader) = &s3_uploader {
+        uploader.shutdown().await?;
+    }
+    #[cfg(feature = "webhooks")]
+    {
+        if let Some(notifier) = &notifier {
+            notifier.shutdown().await;
+        }
+        for webhook in &alert_webhooks {
+            webhook.shutdown().await;
+        }
+    }
+    
+    result
+}
\ No newline at end of file
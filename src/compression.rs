@@ -0,0 +1,194 @@
+use crate::Result;
+use clap::ValueEnum;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    /// Uncompressed output (default)
+    #[default]
+    None,
+    /// gzip (.gz), readable everywhere
+    Gzip,
+    /// Zstandard (.zst), faster and smaller than gzip
+    Zstd,
+}
+
+impl Compression {
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+
+    /// `path` with this compression's extension appended, unless it already has it
+    pub fn with_extension(&self, path: &Path) -> PathBuf {
+        match self.extension() {
+            Some(extension) if path.extension().and_then(|e| e.to_str()) != Some(extension) => {
+                let mut name = path.as_os_str().to_os_string();
+                name.push(".");
+                name.push(extension);
+                PathBuf::from(name)
+            }
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// The compression a path's extension names, e.g. `Gzip` for `bars.csv.gz`
+    pub fn from_extension(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// `path` without this compression's extension
+    pub fn strip_extension(&self, path: &Path) -> PathBuf {
+        match self.extension() {
+            Some(extension) if path.extension().and_then(|e| e.to_str()) == Some(extension) => path.with_extension(""),
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+enum Encoder {
+    Plain(File),
+    Gzip(flate2::write::GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+/// An output file, optionally written through a gzip or zstd encoder.
+///
+/// `flush` only reaches the disk for uncompressed files: flushing an encoder
+/// after every record would ruin the compression ratio. Compressed data is
+/// written as encoder blocks fill up and completed by `finish`.
+pub struct OutputFile {
+    encoder: Encoder,
+    /// The compressed stream was completed; neither encoder takes further
+    /// writes, so the next write replaces it
+    finished: bool,
+}
+
+impl std::fmt::Debug for OutputFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.encoder {
+            Encoder::Plain(_) => "plain",
+            Encoder::Gzip(_) => "gzip",
+            Encoder::Zstd(_) => "zstd",
+        };
+        f.debug_struct("OutputFile").field("compression", &kind).finish()
+    }
+}
+
+impl OutputFile {
+    pub fn new(file: File, compression: Compression) -> Result<Self> {
+        let encoder = match compression {
+            Compression::None => Encoder::Plain(file),
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(file, flate2::Compression::default())),
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(file, 0)?),
+        };
+        Ok(Self { encoder, finished: false })
+    }
+
+    /// Write out everything buffered so far, including for compressed files
+    pub fn sync(&mut self) -> Result<()> {
+        match &mut self.encoder {
+            Encoder::Plain(file) => file.flush()?,
+            Encoder::Gzip(_) | Encoder::Zstd(_) if self.finished => {}
+            Encoder::Gzip(encoder) => encoder.flush()?,
+            Encoder::Zstd(encoder) => encoder.flush()?,
+        }
+        Ok(())
+    }
+
+    /// Complete the compressed stream. Later writes go through a new encoder
+    /// on the same file, starting a new gzip member or zstd frame, which
+    /// readers handle transparently.
+    pub fn finish(&mut self) -> Result<()> {
+        match &mut self.encoder {
+            Encoder::Plain(file) => file.flush()?,
+            Encoder::Gzip(encoder) => encoder.try_finish()?,
+            Encoder::Zstd(encoder) => encoder.do_finish()?,
+        }
+        self.finished = !matches!(self.encoder, Encoder::Plain(_));
+        Ok(())
+    }
+
+    /// Start a new stream on the file of a finished encoder
+    fn restart(&mut self) -> std::io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Plain(_) => {}
+            Encoder::Gzip(encoder) => {
+                *encoder = flate2::write::GzEncoder::new(encoder.get_ref().try_clone()?, flate2::Compression::default());
+            }
+            Encoder::Zstd(encoder) => *encoder = zstd::Encoder::new(encoder.get_ref().try_clone()?, 0)?,
+        }
+        self.finished = false;
+        Ok(())
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.finished {
+            self.restart()?;
+        }
+        match &mut self.encoder {
+            Encoder::Plain(file) => file.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Plain(file) => file.flush(),
+            Encoder::Gzip(_) | Encoder::Zstd(_) => Ok(()),
+        }
+    }
+}
+
+/// Handle onto an `OutputFile` that is also written directly, so the CSV
+/// writer and plain lines share one encoder
+#[derive(Debug, Clone)]
+pub struct SharedOutputFile(pub Arc<Mutex<OutputFile>>);
+
+impl Write for SharedOutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+/// Open a capture for reading, decompressing gzip or zstd input based on its
+/// leading magic bytes
+pub fn open_reader(path: &Path) -> Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let header = reader.fill_buf()?;
+    let reader: Box<dyn BufRead> = if header.starts_with(&GZIP_MAGIC) {
+        Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader)))
+    } else if header.starts_with(&ZSTD_MAGIC) {
+        Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Box::new(reader)
+    };
+    Ok(reader)
+}
+
+/// Read a whole capture into memory, decompressing it if needed
+pub fn read_to_string(path: &Path) -> Result<String> {
+    let mut content = String::new();
+    open_reader(path)?.read_to_string(&mut content)?;
+    Ok(content)
+}
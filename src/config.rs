@@ -0,0 +1,502 @@
+//! What a streaming session subscribes to and how it connects.
+
+use crate::raw_frames::RawRecorder;
+use crate::{
+    DataFormat, Error, MessageFilter, OutputMode, OutputSink, Result, RetryPolicy, ShardMetrics,
+};
+use alpaca_trading_api_rust::{StreamingDataType, StreamingFeed};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Alpaca market data REST endpoint unless `APCA_API_DATA_URL` says otherwise
+pub const DEFAULT_DATA_URL: &str = "https://data.alpaca.markets";
+
+/// Symbols per subscribe message unless `subscribe_chunk_size` says otherwise
+pub const DEFAULT_SUBSCRIBE_CHUNK_SIZE: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct StreamingConfig {
+    pub feed: StreamingFeed,
+    pub trade_symbols: Vec<String>,
+    pub quote_symbols: Vec<String>,
+    pub bar_symbols: Vec<String>,
+    /// Daily bars (`d`), sent through the day as the daily bar changes
+    pub daily_bar_symbols: Vec<String>,
+    /// Minute bars re-sent after late trades (`u`)
+    pub updated_bar_symbols: Vec<String>,
+    /// Trading halts, resumptions and other status changes (`s`)
+    pub status_symbols: Vec<String>,
+    /// Limit-up/limit-down price bands (`l`)
+    pub luld_symbols: Vec<String>,
+    /// Auction order imbalances (`i`)
+    pub imbalance_symbols: Vec<String>,
+    /// Feed to fail over to when `feed` keeps failing
+    pub backup_feed: Option<StreamingFeed>,
+    /// Consecutive failed sessions on the primary feed before failing over
+    pub failover_after: u32,
+    /// How long to stay on the backup feed before trying the primary again
+    pub failback_after: Duration,
+    /// Treat a connection that delivers no messages for this long as stale:
+    /// write a `stale_connection` event and reconnect
+    pub silence_timeout: Option<Duration>,
+    /// Only watch for silence on weekdays from 04:00 to 20:00 New York time,
+    /// since quiet nights and weekends are expected
+    pub silence_market_hours_only: bool,
+    pub retry: RetryPolicy,
+    pub auth_timeout: Duration,
+    pub subscribe_timeout: Duration,
+    /// Most symbols sent in one subscribe or unsubscribe message; longer lists
+    /// are split over several messages
+    pub subscribe_chunk_size: usize,
+    /// Most symbols one websocket carries; a larger universe is spread over
+    /// several connections. `None` keeps everything on one connection.
+    pub max_symbols_per_connection: Option<usize>,
+    /// Connections to split the symbols over, each with its own retries;
+    /// more are opened if `max_symbols_per_connection` requires it
+    pub shards: usize,
+    /// Index of this connection, set on the configs from `connection_configs`
+    /// when there is more than one
+    pub shard: Option<usize>,
+    /// Counters per connection, updated while the session runs
+    pub shard_metrics: ShardMetrics,
+    /// Every record and status line is written to each of these sinks
+    pub output_mode: Vec<Arc<dyn OutputSink>>,
+    /// Also listen to the account's `trade_updates` stream for order events
+    pub trade_updates: bool,
+    /// Only stream messages matching this expression are written; events the
+    /// client writes itself (reconnects, subscription changes) are not filtered
+    pub filter: Option<MessageFilter>,
+    /// Record every websocket message before it is decoded or filtered
+    pub raw_recorder: Option<RawRecorder>,
+}
+
+impl StreamingConfig {
+    /// Settings from the environment: `ALPACA_FEED`, `ALPACA_BACKUP_FEED`,
+    /// `TRADE_UPDATES` and the `*_SYMBOLS` lists, with default symbols for
+    /// trades, quotes and bars. Use `builder()` to start from nothing instead.
+    pub fn new(output_mode: Box<dyn OutputSink>) -> Self {
+        let mut config = Self::base(vec![Arc::from(output_mode)]);
+        config.feed = std::env::var("ALPACA_FEED")
+            .ok()
+            .and_then(|f| parse_feed(&f).ok())
+            .unwrap_or(StreamingFeed::Iex);
+        config.trade_symbols = get_symbols_from_env("TRADE_SYMBOLS", vec!["AAPL", "GOOGL", "TSLA", "MSFT"]);
+        config.quote_symbols = get_symbols_from_env("QUOTE_SYMBOLS", vec!["AAPL", "MSFT", "NVDA"]);
+        config.bar_symbols = get_symbols_from_env("BAR_SYMBOLS", vec!["AAPL", "SPY"]);
+        config.daily_bar_symbols = get_symbols_from_env("DAILY_BAR_SYMBOLS", vec![]);
+        config.updated_bar_symbols = get_symbols_from_env("UPDATED_BAR_SYMBOLS", vec![]);
+        config.status_symbols = get_symbols_from_env("STATUS_SYMBOLS", vec![]);
+        config.luld_symbols = get_symbols_from_env("LULD_SYMBOLS", vec![]);
+        config.imbalance_symbols = get_symbols_from_env("IMBALANCE_SYMBOLS", vec![]);
+        config.backup_feed = std::env::var("ALPACA_BACKUP_FEED").ok().and_then(|f| parse_feed(&f).ok());
+        config.trade_updates = matches!(std::env::var("TRADE_UPDATES").as_deref(), Ok("1") | Ok("true"));
+        config
+    }
+    
+    /// Start a config without reading the environment; see `StreamingConfigBuilder`
+    pub fn builder() -> StreamingConfigBuilder {
+        StreamingConfigBuilder::default()
+    }
+    
+    /// IEX, no symbols and the default timeouts and retries
+    fn base(output_mode: Vec<Arc<dyn OutputSink>>) -> Self {
+        Self {
+            feed: StreamingFeed::Iex,
+            trade_symbols: Vec::new(),
+            quote_symbols: Vec::new(),
+            bar_symbols: Vec::new(),
+            daily_bar_symbols: Vec::new(),
+            updated_bar_symbols: Vec::new(),
+            status_symbols: Vec::new(),
+            luld_symbols: Vec::new(),
+            imbalance_symbols: Vec::new(),
+            backup_feed: None,
+            failover_after: 2,
+            failback_after: Duration::from_secs(15 * 60),
+            silence_timeout: None,
+            silence_market_hours_only: true,
+            retry: RetryPolicy::default(),
+            auth_timeout: Duration::from_secs(10),
+            subscribe_timeout: Duration::from_secs(10),
+            subscribe_chunk_size: DEFAULT_SUBSCRIBE_CHUNK_SIZE,
+            max_symbols_per_connection: None,
+            shards: 1,
+            shard: None,
+            shard_metrics: ShardMetrics::new(),
+            output_mode,
+            trade_updates: false,
+            filter: None,
+            raw_recorder: None,
+        }
+    }
+    
+    pub fn with_sinks(sinks: Vec<Box<dyn OutputSink>>) -> Self {
+        let mut config = Self::new(Box::new(OutputMode::create_console_mode(DataFormat::Plain)));
+        config.output_mode = sinks.into_iter().map(Arc::from).collect();
+        config
+    }
+    
+    pub fn add_sink(&mut self, sink: Box<dyn OutputSink>) {
+        self.output_mode.push(Arc::from(sink));
+    }
+    
+    /// Every configured symbol across all channels, sorted and deduplicated
+    pub fn all_symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = [
+            &self.trade_symbols, &self.quote_symbols, &self.bar_symbols,
+            &self.daily_bar_symbols, &self.updated_bar_symbols, &self.status_symbols,
+            &self.luld_symbols, &self.imbalance_symbols,
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+    
+    /// One config per websocket: the symbols split evenly over `shards`
+    /// connections, or more if needed to stay within
+    /// `max_symbols_per_connection`, each symbol keeping all of its channels on
+    /// one connection. Only the first listens to `trade_updates`.
+    pub fn connection_configs(&self) -> Vec<StreamingConfig> {
+        let symbols = self.all_symbols();
+        let needed = match self.max_symbols_per_connection {
+            Some(max) if max > 0 => symbols.len().div_ceil(max),
+            _ => 1,
+        };
+        let connections = self.shards.max(needed).min(symbols.len());
+        if connections <= 1 {
+            return vec![self.clone()];
+        }
+        // The first `symbols % connections` groups take one extra symbol
+        let (base, extra) = (symbols.len() / connections, symbols.len() % connections);
+        let mut rest = symbols.as_slice();
+        (0..connections)
+            .map(|index| {
+                let (group, remaining) = rest.split_at(base + usize::from(index < extra));
+                rest = remaining;
+                let mut config = self.clone();
+                config.retain_symbols(|symbol| group.iter().any(|s| s == symbol));
+                config.trade_updates &= index == 0;
+                config.shard = Some(index);
+                config
+            })
+            .collect()
+    }
+    
+    /// Symbols subscribed on the channel of `data_type`
+    pub fn symbols(&self, data_type: &StreamingDataType) -> &[String] {
+        match data_type {
+            StreamingDataType::Trades => &self.trade_symbols,
+            StreamingDataType::Quotes => &self.quote_symbols,
+            StreamingDataType::Bars => &self.bar_symbols,
+            StreamingDataType::DailyBars => &self.daily_bar_symbols,
+            StreamingDataType::UpdatedBars => &self.updated_bar_symbols,
+            StreamingDataType::Statuses => &self.status_symbols,
+            StreamingDataType::Lulds => &self.luld_symbols,
+            StreamingDataType::Imbalances => &self.imbalance_symbols,
+        }
+    }
+    
+    pub fn symbols_mut(&mut self, data_type: &StreamingDataType) -> &mut Vec<String> {
+        match data_type {
+            StreamingDataType::Trades => &mut self.trade_symbols,
+            StreamingDataType::Quotes => &mut self.quote_symbols,
+            StreamingDataType::Bars => &mut self.bar_symbols,
+            StreamingDataType::DailyBars => &mut self.daily_bar_symbols,
+            StreamingDataType::UpdatedBars => &mut self.updated_bar_symbols,
+            StreamingDataType::Statuses => &mut self.status_symbols,
+            StreamingDataType::Lulds => &mut self.luld_symbols,
+            StreamingDataType::Imbalances => &mut self.imbalance_symbols,
+        }
+    }
+    
+    /// Keep only the symbols for which `keep` returns true, on every channel
+    pub fn retain_symbols<F: Fn(&str) -> bool>(&mut self, keep: F) {
+        for symbols in [
+            &mut self.trade_symbols, &mut self.quote_symbols, &mut self.bar_symbols,
+            &mut self.daily_bar_symbols, &mut self.updated_bar_symbols, &mut self.status_symbols,
+            &mut self.luld_symbols, &mut self.imbalance_symbols,
+        ] {
+            symbols.retain(|symbol| keep(symbol));
+        }
+    }
+}
+
+/// Builds a `StreamingConfig` step by step, without environment lookups:
+///
+/// ```no_run
+/// # use algorithms_trading::{DataFormat, OutputMode, StreamingConfig};
+/// # use alpaca_trading_api_rust::StreamingFeed;
+/// # fn main() -> algorithms_trading::Result<()> {
+/// let config = StreamingConfig::builder()
+///     .feed(StreamingFeed::Sip)
+///     .trades(["AAPL", "MSFT"])
+///     .bars(["SPY"])
+///     .sink(OutputMode::builder().format(DataFormat::Json).build()?)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Symbols are trimmed and upper-cased. Without a sink, records go to the
+/// console in plain format. `build` checks the settings fit together.
+#[derive(Debug)]
+pub struct StreamingConfigBuilder {
+    config: StreamingConfig,
+    sinks: Vec<Arc<dyn OutputSink>>,
+}
+
+impl Default for StreamingConfigBuilder {
+    fn default() -> Self {
+        Self {
+            config: StreamingConfig::base(Vec::new()),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl StreamingConfigBuilder {
+    pub fn feed(mut self, feed: StreamingFeed) -> Self {
+        self.config.feed = feed;
+        self
+    }
+    
+    /// Feed to fail over to after `failover_after` failed sessions
+    pub fn backup_feed(mut self, feed: StreamingFeed) -> Self {
+        self.config.backup_feed = Some(feed);
+        self
+    }
+    
+    pub fn failover_after(mut self, sessions: u32) -> Self {
+        self.config.failover_after = sessions;
+        self
+    }
+    
+    pub fn failback_after(mut self, duration: Duration) -> Self {
+        self.config.failback_after = duration;
+        self
+    }
+    
+    pub fn trades<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.channel(StreamingDataType::Trades, symbols)
+    }
+    
+    pub fn quotes<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.channel(StreamingDataType::Quotes, symbols)
+    }
+    
+    pub fn bars<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.channel(StreamingDataType::Bars, symbols)
+    }
+    
+    pub fn daily_bars<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.channel(StreamingDataType::DailyBars, symbols)
+    }
+    
+    pub fn updated_bars<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.channel(StreamingDataType::UpdatedBars, symbols)
+    }
+    
+    pub fn statuses<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.channel(StreamingDataType::Statuses, symbols)
+    }
+    
+    pub fn lulds<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.channel(StreamingDataType::Lulds, symbols)
+    }
+    
+    pub fn imbalances<I, S>(self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.channel(StreamingDataType::Imbalances, symbols)
+    }
+    
+    /// Add `symbols` to one channel; repeated calls add up
+    pub fn channel<I, S>(mut self, data_type: StreamingDataType, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let current = self.config.symbols_mut(&data_type);
+        for symbol in symbols {
+            let symbol = symbol.into().trim().to_uppercase();
+            if !current.contains(&symbol) {
+                current.push(symbol);
+            }
+        }
+        self
+    }
+    
+    /// Also listen to the account's order updates
+    pub fn trade_updates(mut self, enabled: bool) -> Self {
+        self.config.trade_updates = enabled;
+        self
+    }
+    
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = policy;
+        self
+    }
+    
+    pub fn auth_timeout(mut self, timeout: Duration) -> Self {
+        self.config.auth_timeout = timeout;
+        self
+    }
+    
+    pub fn subscribe_timeout(mut self, timeout: Duration) -> Self {
+        self.config.subscribe_timeout = timeout;
+        self
+    }
+    
+    pub fn subscribe_chunk_size(mut self, symbols: usize) -> Self {
+        self.config.subscribe_chunk_size = symbols;
+        self
+    }
+    
+    /// Reconnect when no message arrives for `timeout`
+    pub fn silence_timeout(mut self, timeout: Duration) -> Self {
+        self.config.silence_timeout = Some(timeout);
+        self
+    }
+    
+    /// Also watch for silence outside market hours
+    pub fn silence_around_the_clock(mut self) -> Self {
+        self.config.silence_market_hours_only = false;
+        self
+    }
+    
+    pub fn max_symbols_per_connection(mut self, symbols: usize) -> Self {
+        self.config.max_symbols_per_connection = Some(symbols);
+        self
+    }
+    
+    pub fn shards(mut self, connections: usize) -> Self {
+        self.config.shards = connections;
+        self
+    }
+    
+    pub fn filter(mut self, filter: MessageFilter) -> Self {
+        self.config.filter = Some(filter);
+        self
+    }
+    
+    /// Record the websocket messages to `recorder` as they arrive
+    pub fn record_raw(mut self, recorder: RawRecorder) -> Self {
+        self.config.raw_recorder = Some(recorder);
+        self
+    }
+    
+    /// Write every record to `sink` as well; see `OutputMode::builder`
+    pub fn sink(mut self, sink: Box<dyn OutputSink>) -> Self {
+        self.sinks.push(Arc::from(sink));
+        self
+    }
+    
+    /// Check the settings and build the config. Fails without any symbols
+    /// (unless trade updates are on), on empty or malformed symbols, a zero
+    /// chunk size, shard count or connection limit, a backup feed equal to
+    /// the primary, or an invalid retry policy.
+    pub fn build(self) -> Result<StreamingConfig> {
+        let mut config = self.config;
+        if config.all_symbols().is_empty() && !config.trade_updates {
+            return Err(Error::Invalid("No symbols to stream. Add some with trades(), quotes(), bars(), ... or enable trade_updates()".to_string()));
+        }
+        if let Some(symbol) = config.all_symbols().into_iter()
+            .find(|symbol| symbol.is_empty() || symbol.contains(|c: char| c == ',' || c.is_whitespace()))
+        {
+            return Err(Error::Invalid(format!("Invalid symbol: {:?}", symbol)));
+        }
+        if config.subscribe_chunk_size == 0 {
+            return Err(Error::Invalid("Invalid subscribe chunk size: 0".to_string()));
+        }
+        if config.shards == 0 {
+            return Err(Error::Invalid("Invalid shards: 0. Use at least one connection".to_string()));
+        }
+        if config.max_symbols_per_connection == Some(0) {
+            return Err(Error::Invalid("Invalid max symbols per connection: 0".to_string()));
+        }
+        if config.backup_feed.as_ref() == Some(&config.feed) {
+            return Err(Error::Invalid(format!("Invalid backup feed: {}. It must differ from the primary feed", feed_name(&config.feed))));
+        }
+        if !(0.0..=1.0).contains(&config.retry.jitter) {
+            return Err(Error::Invalid(format!("Invalid jitter: {}. Use a fraction from 0.0 to 1.0", config.retry.jitter)));
+        }
+        config.output_mode = if self.sinks.is_empty() {
+            vec![Arc::new(OutputMode::create_console_mode(DataFormat::Plain))]
+        } else {
+            self.sinks
+        };
+        Ok(config)
+    }
+}
+
+pub fn data_type_name(data_type: &StreamingDataType) -> &'static str {
+    match data_type {
+        StreamingDataType::Trades => "trades",
+        StreamingDataType::Quotes => "quotes",
+        StreamingDataType::Bars => "bars",
+        StreamingDataType::DailyBars => "daily_bars",
+        StreamingDataType::UpdatedBars => "updated_bars",
+        StreamingDataType::Statuses => "statuses",
+        StreamingDataType::Lulds => "lulds",
+        StreamingDataType::Imbalances => "imbalances",
+    }
+}
+
+/// Parse a feed name: `iex`, `sip` or `delayed_sip`
+pub fn parse_feed(name: &str) -> Result<StreamingFeed> {
+    match name.trim().to_lowercase().as_str() {
+        "iex" => Ok(StreamingFeed::Iex),
+        "sip" => Ok(StreamingFeed::Sip),
+        "delayed_sip" => Ok(StreamingFeed::DelayedSip),
+        other => Err(Error::Invalid(format!("Invalid feed: {}. Supported: iex, sip, delayed_sip", other))),
+    }
+}
+
+pub fn feed_name(feed: &StreamingFeed) -> &'static str {
+    match feed {
+        StreamingFeed::Iex => "iex",
+        StreamingFeed::Sip => "sip",
+        StreamingFeed::DelayedSip => "delayed_sip",
+    }
+}
+
+pub fn get_symbols_from_env(env_var: &str, default: Vec<&str>) -> Vec<String> {
+    match std::env::var(env_var) {
+        Ok(symbols) => symbols.split(',').map(|s| s.trim().to_uppercase()).collect(),
+        Err(_) => default.iter().map(|s| s.to_string()).collect(),
+    }
+}
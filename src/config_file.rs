@@ -0,0 +1,338 @@
+use crate::costs::CostsConfig;
+use crate::strategies::StrategyParams;
+use crate::{
+    data_type_name, RetryPolicy, StreamingConfig, SubscriptionCommand, SubscriptionHandle,
+};
+use alpaca_trading_api_rust::StreamingDataType;
+use crate::{Error, Result};
+use notify_debouncer_mini::notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Settings read from the TOML file given with `--config`. Environment
+/// variables and command-line flags override the retry policy; symbol lists
+/// in the file replace the `*_SYMBOLS` environment variables.
+///
+/// ```toml
+/// [retry]
+/// max_retries = "infinite"
+/// backoff_base = "1s"
+/// backoff_max = "2m"
+/// jitter = 0.2
+/// reset_after = "5m"
+///
+/// [symbols]
+/// trades = ["AAPL", "MSFT"]
+/// quotes = ["AAPL"]
+///
+/// [costs.slippage]
+/// model = "fixed-bps"
+/// bps = 2
+///
+/// [costs.commission]
+/// model = "per-share"
+/// rate = 0.005
+/// minimum = 1
+///
+/// [strategies.sma-crossover]
+/// fast = 20
+/// slow = 50
+///
+/// [profiles.live]
+/// key_id = "..."
+/// secret_key = "..."
+/// base_url = "https://api.alpaca.markets"
+/// feed = "sip"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub retry: RetryPolicy,
+    pub symbols: SymbolLists,
+    /// Slippage and commission models of simulated fills
+    pub costs: CostsConfig,
+    /// Parameters of built-in strategies, by strategy name
+    pub strategies: BTreeMap<String, toml::Table>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Credentials and endpoints of one account, selected with `--profile`
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// `APCA_API_KEY_ID`
+    pub key_id: Option<String>,
+    /// `APCA_API_SECRET_KEY`
+    pub secret_key: Option<String>,
+    /// `APCA_API_BASE_URL`
+    pub base_url: Option<String>,
+    /// `ALPACA_FEED`
+    pub feed: Option<String>,
+}
+
+impl Profile {
+    /// The environment variables this profile sets
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        [
+            ("APCA_API_KEY_ID", &self.key_id),
+            ("APCA_API_SECRET_KEY", &self.secret_key),
+            ("APCA_API_BASE_URL", &self.base_url),
+            ("ALPACA_FEED", &self.feed),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.clone()?)))
+        .collect()
+    }
+}
+
+/// Symbols per channel; channels left out keep their configured symbols
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SymbolLists {
+    pub trades: Option<Vec<String>>,
+    pub quotes: Option<Vec<String>>,
+    pub bars: Option<Vec<String>>,
+    pub daily_bars: Option<Vec<String>>,
+    pub updated_bars: Option<Vec<String>>,
+    pub statuses: Option<Vec<String>>,
+    pub lulds: Option<Vec<String>>,
+    pub imbalances: Option<Vec<String>>,
+}
+
+impl SymbolLists {
+    /// The channels the file sets, with upper-cased symbols
+    pub fn channels(&self) -> Vec<(StreamingDataType, Vec<String>)> {
+        [
+            (StreamingDataType::Trades, &self.trades),
+            (StreamingDataType::Quotes, &self.quotes),
+            (StreamingDataType::Bars, &self.bars),
+            (StreamingDataType::DailyBars, &self.daily_bars),
+            (StreamingDataType::UpdatedBars, &self.updated_bars),
+            (StreamingDataType::Statuses, &self.statuses),
+            (StreamingDataType::Lulds, &self.lulds),
+            (StreamingDataType::Imbalances, &self.imbalances),
+        ]
+        .into_iter()
+        .filter_map(|(data_type, symbols)| {
+            let symbols = symbols.as_ref()?;
+            Some((
+                data_type,
+                symbols.iter().map(|s| s.trim().to_uppercase()).collect(),
+            ))
+        })
+        .collect()
+    }
+
+    /// Replace the symbols of every channel the file sets
+    pub fn apply(&self, config: &mut StreamingConfig) {
+        for (data_type, symbols) in self.channels() {
+            *config.symbols_mut(&data_type) = symbols;
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigFile {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        toml::from_str(source).map_err(|e| Error::Invalid(format!("Invalid config: {}", e)))
+    }
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        source
+            .parse()
+            .map_err(|e| Error::Invalid(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The parameters of the built-in strategy `name` from
+    /// `[strategies.<name>]`, none when the file has no such table
+    pub fn strategy_params(&self, name: &str) -> StrategyParams {
+        let mut params = StrategyParams::default();
+        for (key, value) in self.strategies.get(name).into_iter().flatten() {
+            match value {
+                toml::Value::String(text) => params.insert(key, text),
+                value => params.insert(key, &value.to_string()),
+            }
+        }
+        params
+    }
+
+    /// Switch to profile `name`: set the variables of `.env.<name>` in `dir`,
+    /// then those of `[profiles.<name>]`. Both override the environment, so a
+    /// selected profile always wins over `.env` and exported variables.
+    /// Returns the names of the variables set.
+    pub fn activate_profile(&self, name: &str, dir: &Path) -> Result<Vec<String>> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(Error::Invalid(format!(
+                "Invalid profile: {}. Use letters, digits, - and _",
+                name
+            )));
+        }
+        let mut vars = Vec::new();
+        let env_file = dir.join(format!(".env.{}", name));
+        if env_file.exists() {
+            // `from_path` would keep variables that are already set
+            #[allow(deprecated)]
+            let iter = dotenv::from_path_iter(&env_file)
+                .map_err(|e| Error::Invalid(format!("{}: {}", env_file.display(), e)))?;
+            for var in iter {
+                vars.push(var.map_err(|e| Error::Invalid(format!("{}: {}", env_file.display(), e)))?);
+            }
+        }
+        if let Some(profile) = self.profiles.get(name) {
+            vars.extend(
+                profile
+                    .vars()
+                    .into_iter()
+                    .map(|(var, value)| (var.to_string(), value)),
+            );
+        } else if !env_file.exists() {
+            return Err(Error::Invalid(format!(
+                "Unknown profile: {}. Define [profiles.{}] in the config file or create {}",
+                name,
+                name,
+                env_file.display()
+            )));
+        }
+        let mut names = Vec::new();
+        for (var, value) in vars {
+            std::env::set_var(&var, value);
+            if !names.contains(&var) {
+                names.push(var);
+            }
+        }
+        Ok(names)
+    }
+}
+
+/// Turns edits of the config file's symbol lists into subscription commands
+/// for the running session
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    /// Symbols per channel as last applied
+    current: HashMap<StreamingDataType, Vec<String>>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`, starting from the symbols `config` subscribes to
+    pub fn new(path: &Path, config: &StreamingConfig) -> Self {
+        let current = [
+            StreamingDataType::Trades,
+            StreamingDataType::Quotes,
+            StreamingDataType::Bars,
+            StreamingDataType::DailyBars,
+            StreamingDataType::UpdatedBars,
+            StreamingDataType::Statuses,
+            StreamingDataType::Lulds,
+            StreamingDataType::Imbalances,
+        ]
+        .into_iter()
+        .map(|data_type| {
+            let symbols = config.symbols(&data_type).to_vec();
+            (data_type, symbols)
+        })
+        .collect();
+        Self {
+            path: path.to_path_buf(),
+            current,
+        }
+    }
+
+    /// Commands that take the live subscriptions to the symbol lists of
+    /// `file`: unsubscribes first, then subscribes, in channel order
+    pub fn changes(&mut self, file: &ConfigFile) -> Vec<SubscriptionCommand> {
+        let mut removals = Vec::new();
+        let mut additions = Vec::new();
+        for (data_type, symbols) in file.symbols.channels() {
+            let current = self.current.entry(data_type.clone()).or_default();
+            let removed: Vec<String> = current
+                .iter()
+                .filter(|symbol| !symbols.contains(symbol))
+                .cloned()
+                .collect();
+            let added: Vec<String> = symbols
+                .iter()
+                .filter(|symbol| !current.contains(symbol))
+                .cloned()
+                .collect();
+            if !removed.is_empty() {
+                removals.push(SubscriptionCommand::Unsubscribe(data_type.clone(), removed));
+            }
+            if !added.is_empty() {
+                additions.push(SubscriptionCommand::Subscribe(data_type, added));
+            }
+            *current = symbols;
+        }
+        removals.extend(additions);
+        removals
+    }
+
+    /// Watch the file for edits and send the changes of each to `handle`.
+    /// Events within `debounce` of each other are taken as one edit. An edit
+    /// that does not parse is logged and skipped. Runs until the session
+    /// stops taking commands, or returns at once if the file cannot be
+    /// watched.
+    pub async fn run(mut self, handle: SubscriptionHandle, debounce: Duration) {
+        let (edits, mut edited) = tokio::sync::mpsc::unbounded_channel();
+        let name = self.path.file_name().map(|name| name.to_os_string());
+        let debouncer = new_debouncer(debounce, move |result: DebounceEventResult| match result {
+            Ok(events) => {
+                if events.iter().any(|event| event.path.file_name() == name.as_deref()) {
+                    let _ = edits.send(());
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "Config watch failed"),
+        });
+        // Editors often save by writing a new file over the old one, which a
+        // watch on the file itself would lose, so its directory is watched
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let _debouncer = match debouncer.and_then(|mut debouncer| {
+            debouncer.watcher().watch(dir, RecursiveMode::NonRecursive)?;
+            Ok(debouncer)
+        }) {
+            Ok(debouncer) => debouncer,
+            Err(e) => {
+                tracing::warn!(path = %self.path.display(), error = %e, "Cannot watch config");
+                return;
+            }
+        };
+        while edited.recv().await.is_some() {
+            let file = match ConfigFile::load(&self.path) {
+                Ok(file) => file,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Ignoring config change");
+                    continue;
+                }
+            };
+            let changes = self.changes(&file);
+            tracing::info!(path = %self.path.display(), changes = changes.len(), "Config reloaded");
+            for command in changes {
+                let (action, data_type, symbols) = match &command {
+                    SubscriptionCommand::Subscribe(data_type, symbols) => {
+                        ("subscribe", data_type, symbols)
+                    }
+                    SubscriptionCommand::Unsubscribe(data_type, symbols) => {
+                        ("unsubscribe", data_type, symbols)
+                    }
+                };
+                tracing::info!(action, channel = data_type_name(data_type), symbols = %symbols.join(","), "Applying config change");
+                if handle.send(command).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
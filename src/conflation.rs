@@ -0,0 +1,167 @@
+use crate::{OutputSink, StreamingData};
+use alpaca_trading_api_rust::StreamingQuote;
+use crate::{Error, Result};
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum QuoteFilter {
+    /// Write every quote (default)
+    #[default]
+    All,
+    /// Drop quotes whose bid and ask prices match the symbol's previous quote
+    OnChangeOnly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteConflation {
+    /// Write only the latest quote per symbol once per interval
+    Sample(Duration),
+    /// Write a quote only when the bid or ask price changed
+    OnChange,
+}
+
+/// Parse a duration such as `250ms`, `1s`, `1.5s` or `5m`
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let invalid = || Error::Invalid(format!("Invalid duration: {}. Use e.g. 250ms, 1s or 5m", value));
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// Parse a duration like [`parse_duration`] for something run periodically,
+/// which needs a period longer than zero
+pub fn parse_interval(value: &str) -> Result<Duration> {
+    let interval = parse_duration(value)?;
+    if interval.is_zero() {
+        return Err(Error::Invalid(format!("Interval must be longer than zero: {}", value.trim())));
+    }
+    Ok(interval)
+}
+
+#[derive(Debug, Default)]
+struct ConflationState {
+    /// Latest unsent quote per symbol (sampling)
+    pending: HashMap<String, StreamingData>,
+    /// Bid and ask of the last quote written per symbol (on-change)
+    last: HashMap<String, (f64, f64)>,
+}
+
+/// Thins out quotes before they reach the wrapped sink; everything else is
+/// passed through unchanged. Clones share the same state.
+///
+/// In sampling mode quotes are held back until `sample_every` (or a flush)
+/// writes them, so they can reach the output up to one interval after
+/// trades that arrived later.
+#[derive(Debug, Clone)]
+pub struct QuoteConflator {
+    inner: Arc<dyn OutputSink>,
+    mode: QuoteConflation,
+    state: Arc<Mutex<ConflationState>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl QuoteConflator {
+    pub fn new(inner: Box<dyn OutputSink>, mode: QuoteConflation) -> Self {
+        Self {
+            inner: Arc::from(inner),
+            mode,
+            state: Arc::new(Mutex::new(ConflationState::default())),
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Quotes dropped or replaced by a newer one so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Write the held-back quotes, in symbol order
+    pub fn flush_pending(&self) -> Result<()> {
+        let mut pending: Vec<StreamingData> = {
+            let mut state = self.state.lock().unwrap();
+            state.pending.drain().map(|(_, data)| data).collect()
+        };
+        pending.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        for data in &pending {
+            self.inner.write_streaming_data(data)?;
+        }
+        Ok(())
+    }
+
+    /// Call `flush_pending` every sampling interval; runs until the task is
+    /// dropped. Returns immediately in on-change mode.
+    pub async fn sample_every(self) {
+        let QuoteConflation::Sample(interval) = self.mode else {
+            return;
+        };
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(1)));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.flush_pending() {
+                tracing::warn!(error = %e, "Failed to write sampled quotes");
+            }
+        }
+    }
+}
+
+impl OutputSink for QuoteConflator {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        if data.message_type != "q" {
+            return self.inner.write_streaming_data(data);
+        }
+        let quote: StreamingQuote = serde_json::from_value(data.data.clone())?;
+        let mut state = self.state.lock().unwrap();
+        match self.mode {
+            QuoteConflation::Sample(_) => {
+                if state.pending.insert(quote.symbol, data.clone()).is_some() {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(())
+            }
+            QuoteConflation::OnChange => {
+                let prices = (quote.bid_price, quote.ask_price);
+                if state.last.insert(quote.symbol, prices) == Some(prices) {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                drop(state);
+                self.inner.write_streaming_data(data)
+            }
+        }
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        self.flush_pending()?;
+        self.inner.flush()
+    }
+
+    fn close(&self) -> crate::Result<()> {
+        self.flush_pending()?;
+        tracing::info!(dropped = self.dropped(), "Quote conflation finished");
+        self.inner.close()
+    }
+
+    fn rotate(&self) -> crate::Result<()> {
+        self.flush_pending()?;
+        self.inner.rotate()
+    }
+
+    fn writeln(&self, message: &str) -> crate::Result<()> {
+        self.inner.writeln(message)
+    }
+}
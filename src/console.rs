@@ -0,0 +1,123 @@
+use crate::trading_status::{HALT_CODES, RESUME_CODES};
+use crate::StreamingData;
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::sync::{Arc, Mutex};
+
+pub(crate) const GREEN: &str = "\x1b[32m";
+pub(crate) const RED: &str = "\x1b[31m";
+pub(crate) const YELLOW: &str = "\x1b[33m";
+pub(crate) const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is not set (default)
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// How `OutputMode::Console` renders plain-format lines. The default matches
+/// file output: emoji, no color.
+#[derive(Debug, Clone)]
+pub struct ConsoleOptions {
+    /// Green for up ticks, up bars and resumptions, red for down ticks, down
+    /// bars, halts and errors
+    pub color: bool,
+    pub emoji: bool,
+    /// Last trade price per symbol, to tell up ticks from down ticks
+    last_prices: Arc<Mutex<HashMap<String, f64>>>,
+}
+
+impl Default for ConsoleOptions {
+    fn default() -> Self {
+        Self::new(false, true)
+    }
+}
+
+impl ConsoleOptions {
+    pub fn new(color: bool, emoji: bool) -> Self {
+        Self {
+            color,
+            emoji,
+            last_prices: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Apply the options to `line`, the plain-format rendering of `data`
+    pub fn render(&self, line: String, data: &StreamingData) -> String {
+        let line = if self.emoji { line } else { strip_emoji(&line) };
+        let color = match data.message_type.as_str() {
+            "t" => self.tick_color(data),
+            "b" | "d" | "u" => match (data.data["o"].as_f64(), data.data["c"].as_f64()) {
+                (Some(open), Some(close)) if close > open => Some(GREEN),
+                (Some(open), Some(close)) if close < open => Some(RED),
+                _ => None,
+            },
+            "s" => match data.data["sc"].as_str() {
+                Some(code) if HALT_CODES.contains(&code) => Some(RED),
+                Some(code) if RESUME_CODES.contains(&code) => Some(GREEN),
+                _ => None,
+            },
+            "error" | "disconnect" => Some(RED),
+            "alert" | "failover" | "stale_connection" => Some(YELLOW),
+            _ => None,
+        };
+        match color {
+            Some(color) if self.color => format!("{}{}{}", color, line, RESET),
+            _ => line,
+        }
+    }
+
+    fn tick_color(&self, data: &StreamingData) -> Option<&'static str> {
+        let (Some(symbol), Some(price)) = (&data.symbol, data.data["p"].as_f64()) else {
+            return None;
+        };
+        let previous = self
+            .last_prices
+            .lock()
+            .unwrap()
+            .insert(symbol.clone(), price)?;
+        if price > previous {
+            Some(GREEN)
+        } else if price < previous {
+            Some(RED)
+        } else {
+            None
+        }
+    }
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF // pictographs, emoticons, transport, ...
+        | 0x2300..=0x23FF // technical symbols such as ⏱
+        | 0x2600..=0x27BF // miscellaneous symbols and dingbats
+        | 0x2B00..=0x2BFF
+        | 0xFE0F // emoji presentation selector
+        | 0x200D // zero-width joiner
+    )
+}
+
+/// Drop emoji and the spacing that followed a leading one
+pub fn strip_emoji(line: &str) -> String {
+    let stripped: String = line.chars().filter(|c| !is_emoji(*c)).collect();
+    if stripped.len() == line.len() {
+        return stripped;
+    }
+    stripped.trim_start().to_string()
+}
@@ -0,0 +1,615 @@
+//! Splits, dividends and mergers from the corporate actions API, and the
+//! split and dividend factors used to adjust prices across them.
+
+use crate::compression::open_reader;
+use crate::config::DEFAULT_DATA_URL;
+use crate::rate_limit::RateLimiter;
+use crate::resample::{OhlcvBar, BARS_CSV_HEADER};
+use crate::retry::{retry_request, HttpError, RetryPolicy, DEFAULT_REQUEST_RETRIES};
+use crate::{DataFormat, Error, Result};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::path::Path;
+use std::time::Duration;
+
+/// Header of the CSV written for corporate actions
+pub const CORPORATE_ACTIONS_CSV_HEADER: &str =
+    "symbol,type,date,record_date,payable_date,old_rate,new_rate,rate,acquirer";
+
+/// The kinds of corporate action this crate reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorporateActionKind {
+    ForwardSplit,
+    ReverseSplit,
+    CashDividend,
+    StockDividend,
+    CashMerger,
+    StockMerger,
+    StockAndCashMerger,
+}
+
+impl CorporateActionKind {
+    /// The name used by the API and in the output, e.g. `forward_split`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CorporateActionKind::ForwardSplit => "forward_split",
+            CorporateActionKind::ReverseSplit => "reverse_split",
+            CorporateActionKind::CashDividend => "cash_dividend",
+            CorporateActionKind::StockDividend => "stock_dividend",
+            CorporateActionKind::CashMerger => "cash_merger",
+            CorporateActionKind::StockMerger => "stock_merger",
+            CorporateActionKind::StockAndCashMerger => "stock_and_cash_merger",
+        }
+    }
+}
+
+/// Groups of kinds selectable on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ActionGroup {
+    Splits,
+    Dividends,
+    Mergers,
+}
+
+impl ActionGroup {
+    pub fn kinds(&self) -> &'static [CorporateActionKind] {
+        match self {
+            ActionGroup::Splits => &[
+                CorporateActionKind::ForwardSplit,
+                CorporateActionKind::ReverseSplit,
+            ],
+            ActionGroup::Dividends => &[
+                CorporateActionKind::CashDividend,
+                CorporateActionKind::StockDividend,
+            ],
+            ActionGroup::Mergers => &[
+                CorporateActionKind::CashMerger,
+                CorporateActionKind::StockMerger,
+                CorporateActionKind::StockAndCashMerger,
+            ],
+        }
+    }
+}
+
+/// One split, dividend or merger. `date` is the ex date of splits and
+/// dividends and the effective date of mergers. Splits and stock mergers turn
+/// `old_rate` shares of `symbol` into `new_rate` shares (of `acquirer` for a
+/// merger); `rate` is the cash or stock paid per share.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CorporateAction {
+    pub symbol: String,
+    #[serde(rename = "type")]
+    pub kind: CorporateActionKind,
+    pub date: String,
+    pub record_date: Option<String>,
+    pub payable_date: Option<String>,
+    pub old_rate: Option<f64>,
+    pub new_rate: Option<f64>,
+    pub rate: Option<f64>,
+    pub acquirer: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ApiSplit {
+    symbol: String,
+    new_rate: f64,
+    old_rate: f64,
+    #[serde(default)]
+    ex_date: Option<String>,
+    #[serde(default)]
+    process_date: Option<String>,
+    #[serde(default)]
+    record_date: Option<String>,
+    #[serde(default)]
+    payable_date: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ApiDividend {
+    symbol: String,
+    rate: f64,
+    #[serde(default)]
+    ex_date: Option<String>,
+    #[serde(default)]
+    process_date: Option<String>,
+    #[serde(default)]
+    record_date: Option<String>,
+    #[serde(default)]
+    payable_date: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ApiMerger {
+    acquirer_symbol: Option<String>,
+    acquiree_symbol: String,
+    /// Cash per acquiree share
+    #[serde(default, alias = "cash_rate")]
+    rate: Option<f64>,
+    #[serde(default)]
+    acquirer_rate: Option<f64>,
+    #[serde(default)]
+    acquiree_rate: Option<f64>,
+    #[serde(default)]
+    effective_date: Option<String>,
+    #[serde(default)]
+    process_date: Option<String>,
+    #[serde(default)]
+    payable_date: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct ApiActions {
+    #[serde(default)]
+    forward_splits: Vec<ApiSplit>,
+    #[serde(default)]
+    reverse_splits: Vec<ApiSplit>,
+    #[serde(default)]
+    cash_dividends: Vec<ApiDividend>,
+    #[serde(default)]
+    stock_dividends: Vec<ApiDividend>,
+    #[serde(default)]
+    cash_mergers: Vec<ApiMerger>,
+    #[serde(default)]
+    stock_mergers: Vec<ApiMerger>,
+    #[serde(default)]
+    stock_and_cash_mergers: Vec<ApiMerger>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ActionsPage {
+    #[serde(default)]
+    corporate_actions: ApiActions,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+/// The first date that is set; the process date is always there
+fn first_date(dates: [Option<String>; 2]) -> String {
+    dates.into_iter().flatten().next().unwrap_or_default()
+}
+
+/// The actions of one response page, and the token of the next page if
+/// there is one
+pub fn parse_corporate_actions_page(body: &str) -> Result<(Vec<CorporateAction>, Option<String>)> {
+    let page: ActionsPage = serde_json::from_str(body)?;
+    let actions = page.corporate_actions;
+    let mut all = Vec::new();
+
+    let splits = [
+        (CorporateActionKind::ForwardSplit, actions.forward_splits),
+        (CorporateActionKind::ReverseSplit, actions.reverse_splits),
+    ];
+    for (kind, splits) in splits {
+        all.extend(splits.into_iter().map(|split| CorporateAction {
+            symbol: split.symbol,
+            kind,
+            date: first_date([split.ex_date, split.process_date]),
+            record_date: split.record_date,
+            payable_date: split.payable_date,
+            old_rate: Some(split.old_rate),
+            new_rate: Some(split.new_rate),
+            rate: None,
+            acquirer: None,
+        }));
+    }
+
+    let dividends = [
+        (CorporateActionKind::CashDividend, actions.cash_dividends),
+        (CorporateActionKind::StockDividend, actions.stock_dividends),
+    ];
+    for (kind, dividends) in dividends {
+        all.extend(dividends.into_iter().map(|dividend| CorporateAction {
+            symbol: dividend.symbol,
+            kind,
+            date: first_date([dividend.ex_date, dividend.process_date]),
+            record_date: dividend.record_date,
+            payable_date: dividend.payable_date,
+            old_rate: None,
+            new_rate: None,
+            rate: Some(dividend.rate),
+            acquirer: None,
+        }));
+    }
+
+    let mergers = [
+        (CorporateActionKind::CashMerger, actions.cash_mergers),
+        (CorporateActionKind::StockMerger, actions.stock_mergers),
+        (
+            CorporateActionKind::StockAndCashMerger,
+            actions.stock_and_cash_mergers,
+        ),
+    ];
+    for (kind, mergers) in mergers {
+        all.extend(mergers.into_iter().map(|merger| CorporateAction {
+            symbol: merger.acquiree_symbol,
+            kind,
+            date: first_date([merger.effective_date, merger.process_date]),
+            record_date: None,
+            payable_date: merger.payable_date,
+            old_rate: merger.acquiree_rate,
+            new_rate: merger.acquirer_rate,
+            rate: merger.rate,
+            acquirer: merger.acquirer_symbol,
+        }));
+    }
+
+    Ok((all, page.next_page_token))
+}
+
+impl CorporateAction {
+    /// New shares per old share of a forward or reverse split
+    pub fn split_ratio(&self) -> Option<f64> {
+        match self.kind {
+            CorporateActionKind::ForwardSplit | CorporateActionKind::ReverseSplit => {
+                match (self.old_rate, self.new_rate) {
+                    (Some(old), Some(new)) if old > 0.0 => Some(new / old),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The line written for this action; CSV lines match
+    /// `CORPORATE_ACTIONS_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+        Ok(match format {
+            DataFormat::Plain => {
+                let detail = match self.kind {
+                    CorporateActionKind::ForwardSplit | CorporateActionKind::ReverseSplit => {
+                        format!(
+                            "{}-for-{}",
+                            optional(self.new_rate),
+                            optional(self.old_rate)
+                        )
+                    }
+                    CorporateActionKind::CashDividend => {
+                        format!("${:.4} per share", self.rate.unwrap_or_default())
+                    }
+                    CorporateActionKind::StockDividend => {
+                        format!("{} shares per share", optional(self.rate))
+                    }
+                    _ => {
+                        let mut parts = Vec::new();
+                        if let Some(new_rate) = self.new_rate {
+                            parts.push(format!(
+                                "{} {} for {}",
+                                new_rate,
+                                self.acquirer.as_deref().unwrap_or("?"),
+                                optional(self.old_rate)
+                            ));
+                        }
+                        if let Some(rate) = self.rate {
+                            parts.push(format!("${:.4} cash per share", rate));
+                        }
+                        format!(
+                            "acquired by {}: {}",
+                            self.acquirer.as_deref().unwrap_or("?"),
+                            parts.join(" + ")
+                        )
+                    }
+                };
+                let payable = match &self.payable_date {
+                    Some(date) => format!(" | Payable: {}", date),
+                    None => String::new(),
+                };
+                format!(
+                    "🏛️  {}: {} {} | {}{}",
+                    self.symbol,
+                    self.date,
+                    self.kind.as_str(),
+                    detail,
+                    payable
+                )
+            }
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{},{}",
+                self.symbol,
+                self.kind.as_str(),
+                self.date,
+                self.record_date.as_deref().unwrap_or_default(),
+                self.payable_date.as_deref().unwrap_or_default(),
+                optional(self.old_rate),
+                optional(self.new_rate),
+                optional(self.rate),
+                self.acquirer.as_deref().unwrap_or_default()
+            ),
+        })
+    }
+}
+
+/// The factor that puts a price of `symbol` traded on `date` on the share
+/// basis after every split in `actions` with a later ex date: 0.25 before a
+/// 4-for-1 split, 1.0 if there is none. Divide volumes by it.
+pub fn split_adjustment_factor(actions: &[CorporateAction], symbol: &str, date: NaiveDate) -> f64 {
+    actions
+        .iter()
+        .filter(|action| action.symbol == symbol)
+        .filter(|action| {
+            NaiveDate::parse_from_str(&action.date, "%Y-%m-%d").is_ok_and(|ex_date| ex_date > date)
+        })
+        .filter_map(CorporateAction::split_ratio)
+        .fold(1.0, |factor, ratio| factor / ratio)
+}
+
+/// Actions written by `trade corporate-actions` as CSV or JSON lines
+pub fn read_corporate_actions(path: &Path) -> Result<Vec<CorporateAction>> {
+    let columns: Vec<&str> = CORPORATE_ACTIONS_CSV_HEADER.split(',').collect();
+    let mut actions = Vec::new();
+    for line in open_reader(path)?.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line == CORPORATE_ACTIONS_CSV_HEADER {
+            continue;
+        }
+        if line.starts_with('{') {
+            actions.push(serde_json::from_str(line)?);
+            continue;
+        }
+        // CSV values are plain, so they read back as JSON strings and numbers
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != columns.len() {
+            return Err(Error::Invalid(format!(
+                "Expected {} columns in {}: {}",
+                columns.len(),
+                path.display(),
+                line
+            )));
+        }
+        let mut value = serde_json::Map::new();
+        for (column, field) in columns.iter().zip(fields) {
+            let field = match *column {
+                _ if field.is_empty() => serde_json::Value::Null,
+                "old_rate" | "new_rate" | "rate" => serde_json::Value::from(field.parse::<f64>().map_err(|_| {
+                    Error::Invalid(format!("Invalid {}: {}", column, field))
+                })?),
+                _ => serde_json::Value::from(field),
+            };
+            value.insert(column.to_string(), field);
+        }
+        actions.push(serde_json::from_value(serde_json::Value::Object(value))?);
+    }
+    Ok(actions)
+}
+
+/// Header of adjusted bars written as CSV: the bar columns, then the
+/// factors they were adjusted by
+pub fn adjusted_bars_csv_header() -> String {
+    format!("{},split_factor,dividend_factor", BARS_CSV_HEADER)
+}
+
+/// A bar with its prices and volume adjusted. The raw prices are the
+/// adjusted ones divided by both factors, and the raw volume the adjusted
+/// one times `split_factor`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AdjustedBar {
+    #[serde(flatten)]
+    pub bar: OhlcvBar,
+    pub split_factor: f64,
+    pub dividend_factor: f64,
+}
+
+impl AdjustedBar {
+    /// The line written for this bar; CSV lines match
+    /// `adjusted_bars_csv_header`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        Ok(match format {
+            DataFormat::Plain => format!(
+                "{} | Split: {} Dividend: {:.6}",
+                self.bar.format(format)?,
+                self.split_factor,
+                self.dividend_factor
+            ),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{}",
+                self.bar.format(format)?,
+                self.split_factor,
+                self.dividend_factor
+            ),
+        })
+    }
+}
+
+/// Adjusts bars for the splits and, optionally, cash dividends after them.
+/// Stock dividends count as splits of `1 + rate` new shares per share. A
+/// cash dividend scales earlier prices by `1 - dividend / close`, with the
+/// close of the last day before its ex date, so returns across the ex date
+/// include the dividend.
+#[derive(Debug, Default)]
+pub struct PriceAdjuster {
+    /// Ex date and new shares per old share of each split, by symbol
+    splits: HashMap<String, Vec<(NaiveDate, f64)>>,
+    /// Ex date and price factor of each cash dividend, by symbol
+    dividends: HashMap<String, Vec<(NaiveDate, f64)>>,
+}
+
+impl PriceAdjuster {
+    /// `closes` holds each symbol's raw close of each day, to price the
+    /// dividends; dividends without a close before their ex date are skipped
+    pub fn new(
+        actions: &[CorporateAction],
+        closes: &HashMap<String, BTreeMap<NaiveDate, f64>>,
+        dividends: bool,
+    ) -> Self {
+        let mut adjuster = Self::default();
+        for action in actions {
+            let Ok(ex_date) = NaiveDate::parse_from_str(&action.date, "%Y-%m-%d") else {
+                continue;
+            };
+            let ratio = match action.kind {
+                CorporateActionKind::StockDividend => action.rate.map(|rate| 1.0 + rate),
+                _ => action.split_ratio(),
+            };
+            if let Some(ratio) = ratio {
+                adjuster
+                    .splits
+                    .entry(action.symbol.clone())
+                    .or_default()
+                    .push((ex_date, ratio));
+                continue;
+            }
+            if !dividends || action.kind != CorporateActionKind::CashDividend {
+                continue;
+            }
+            let previous_close = closes
+                .get(&action.symbol)
+                .and_then(|closes| closes.range(..ex_date).next_back());
+            if let (Some(rate), Some((_, close))) = (action.rate, previous_close) {
+                if *close > rate {
+                    adjuster
+                        .dividends
+                        .entry(action.symbol.clone())
+                        .or_default()
+                        .push((ex_date, 1.0 - rate / close));
+                }
+            }
+        }
+        adjuster
+    }
+
+    /// The split and dividend factors of `symbol` on `date`: the products
+    /// over the actions with a later ex date
+    pub fn factors(&self, symbol: &str, date: NaiveDate) -> (f64, f64) {
+        let product = |actions: &HashMap<String, Vec<(NaiveDate, f64)>>, split: bool| {
+            actions
+                .get(symbol)
+                .into_iter()
+                .flatten()
+                .filter(|(ex_date, _)| *ex_date > date)
+                .fold(1.0, |factor, (_, value)| {
+                    if split {
+                        factor / value
+                    } else {
+                        factor * value
+                    }
+                })
+        };
+        (product(&self.splits, true), product(&self.dividends, false))
+    }
+
+    /// `bar`, traded on `date`, with its prices multiplied by both factors
+    /// and its volume divided by the split factor
+    pub fn adjust(&self, bar: OhlcvBar, date: NaiveDate) -> AdjustedBar {
+        let (split_factor, dividend_factor) = self.factors(&bar.symbol, date);
+        let price = split_factor * dividend_factor;
+        AdjustedBar {
+            bar: OhlcvBar {
+                open: bar.open * price,
+                high: bar.high * price,
+                low: bar.low * price,
+                close: bar.close * price,
+                vwap: bar.vwap * price,
+                volume: (bar.volume as f64 / split_factor).round() as u64,
+                ..bar
+            },
+            split_factor,
+            dividend_factor,
+        }
+    }
+}
+
+/// Client for the corporate actions endpoint of the market data API
+pub struct CorporateActionsClient {
+    client: reqwest::Client,
+    data_url: String,
+    key: String,
+    secret: String,
+    retry: RetryPolicy,
+}
+
+impl CorporateActionsClient {
+    /// Credentials from `APCA_API_KEY_ID` / `APCA_API_SECRET_KEY` and the
+    /// data API from `APCA_API_DATA_URL`
+    pub fn from_env() -> Result<Self> {
+        let var =
+            |name: &str| std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)));
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            data_url: std::env::var("APCA_API_DATA_URL")
+                .unwrap_or_else(|_| DEFAULT_DATA_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            key: var("APCA_API_KEY_ID")?,
+            secret: var("APCA_API_SECRET_KEY")?,
+            retry: RetryPolicy::for_requests(DEFAULT_REQUEST_RETRIES),
+        })
+    }
+
+    /// Retry each request up to `max_retries` times after a 429, a 5xx or a
+    /// network error
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.retry = RetryPolicy::for_requests(max_retries);
+        self
+    }
+
+    /// Every action of `kinds` for `symbols` from `start` to `end`
+    /// (YYYY-MM-DD), sorted by date and symbol
+    #[tracing::instrument(name = "fetch_corporate_actions", skip(self, kinds))]
+    pub async fn fetch_actions(
+        &self,
+        symbols: &[String],
+        kinds: &[CorporateActionKind],
+        start: &str,
+        end: &str,
+    ) -> Result<Vec<CorporateAction>> {
+        let url = format!("{}/v1/corporate-actions", self.data_url);
+        let symbols = symbols.join(",");
+        let types = kinds
+            .iter()
+            .map(CorporateActionKind::as_str)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut all = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            tracing::debug!(page_token = ?page_token, "Requesting page");
+            let mut query = vec![
+                ("symbols", symbols.as_str()),
+                ("types", types.as_str()),
+                ("start", start),
+                ("end", end),
+                ("limit", "1000"),
+            ];
+            if let Some(token) = &page_token {
+                query.push(("page_token", token));
+            }
+            let body = self.get(&url, &query).await?;
+            let (actions, next_page_token) = parse_corporate_actions_page(&body)?;
+            all.extend(actions);
+            match next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+        all.sort_by(|a, b| (&a.date, &a.symbol).cmp(&(&b.date, &b.symbol)));
+        Ok(all)
+    }
+
+    /// GET `url` with `query` within the rate limit, retried per `self.retry`
+    async fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<String> {
+        retry_request(&self.retry, move || async move {
+            RateLimiter::global().acquire().await;
+            let response = self
+                .client
+                .get(url)
+                .query(query)
+                .header("APCA-API-KEY-ID", &self.key)
+                .header("APCA-API-SECRET-KEY", &self.secret)
+                .send()
+                .await?;
+            RateLimiter::global()
+                .observe(|name| response.headers().get(name)?.to_str().ok()?.parse().ok());
+            let status = response.status().as_u16();
+            HttpError::check(status, response.text().await?)
+        })
+        .await
+    }
+}
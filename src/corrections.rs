@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+/// A `c` stream message: an earlier trade's price, size or conditions were
+/// corrected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeCorrection {
+    #[serde(rename = "S")]
+    pub symbol: String,
+    #[serde(rename = "x")]
+    pub exchange: String,
+    #[serde(rename = "oi")]
+    pub original_id: u64,
+    #[serde(rename = "op")]
+    pub original_price: f64,
+    #[serde(rename = "os")]
+    pub original_size: u64,
+    #[serde(rename = "oc", default)]
+    pub original_conditions: Vec<String>,
+    #[serde(rename = "ci")]
+    pub corrected_id: u64,
+    #[serde(rename = "cp")]
+    pub corrected_price: f64,
+    #[serde(rename = "cs")]
+    pub corrected_size: u64,
+    #[serde(rename = "cc", default)]
+    pub corrected_conditions: Vec<String>,
+    #[serde(rename = "z", default)]
+    pub tape: String,
+    #[serde(rename = "t")]
+    pub timestamp: String,
+}
+
+/// An `x` stream message: an earlier trade was canceled or reported in error
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeCancel {
+    #[serde(rename = "S")]
+    pub symbol: String,
+    #[serde(rename = "i")]
+    pub id: u64,
+    #[serde(rename = "x")]
+    pub exchange: String,
+    #[serde(rename = "p")]
+    pub price: f64,
+    #[serde(rename = "s")]
+    pub size: u64,
+    /// `C` for a cancel, `E` for an error
+    #[serde(rename = "a")]
+    pub action: String,
+    #[serde(rename = "z", default)]
+    pub tape: String,
+    #[serde(rename = "t")]
+    pub timestamp: String,
+}
+
+impl TradeCancel {
+    pub fn is_error(&self) -> bool {
+        self.action == "E"
+    }
+}
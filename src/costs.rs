@@ -0,0 +1,353 @@
+//! Trading costs of simulated fills: a `SlippageModel` moves the price a
+//! market order gets, and a `CommissionModel` charges for each fill.
+//!
+//! Models are picked in code, or in the `[costs]` table of the config file:
+//!
+//! ```toml
+//! [costs.slippage]
+//! model = "volume-share"
+//! impact_bps = 10
+//!
+//! [costs.commission]
+//! model = "tiered"
+//! minimum = 0.35
+//! tiers = [{ up_to = 300000, per_share = 0.0035 }, { per_share = 0.002 }]
+//! ```
+
+use crate::strategy::Side;
+use crate::{Error, Result};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// A market order about to fill
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Execution {
+    pub side: Side,
+    pub quantity: f64,
+    /// Price before slippage: a bar's open, a trade's price or the far side
+    /// of a quote
+    pub price: f64,
+    /// Ask less bid of the symbol's last quote, when it has had one
+    pub spread: Option<f64>,
+    /// Shares the market traded at that price: a bar's volume, a trade's size
+    /// or the size at the far side of a quote; unknown for orders filled at
+    /// the last price when a strategy stops
+    pub volume: Option<f64>,
+}
+
+/// How far the price of a market order moves against it
+pub trait SlippageModel: Debug + Send + Sync {
+    /// The price `execution` fills at
+    fn price(&self, execution: &Execution) -> f64;
+}
+
+/// What a fill costs
+pub trait CommissionModel: Debug + Send + Sync {
+    /// Commission on `quantity` shares at `price`, after `traded` shares
+    /// filled before it in the same run
+    fn commission(&self, quantity: f64, price: f64, traded: f64) -> f64;
+}
+
+fn check_amount(name: &str, value: f64) -> Result<f64> {
+    if !(value >= 0.0 && value.is_finite()) {
+        return Err(Error::Invalid(format!(
+            "Invalid {}: {}. Use 0 or more",
+            name, value
+        )));
+    }
+    Ok(value)
+}
+
+/// No slippage
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NoSlippage;
+
+impl SlippageModel for NoSlippage {
+    fn price(&self, execution: &Execution) -> f64 {
+        execution.price
+    }
+}
+
+/// The same fraction of the price, in basis points, on every order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedBps {
+    bps: f64,
+}
+
+impl FixedBps {
+    pub fn new(bps: f64) -> Result<Self> {
+        Ok(Self {
+            bps: check_amount("slippage", bps)?,
+        })
+    }
+}
+
+impl SlippageModel for FixedBps {
+    fn price(&self, execution: &Execution) -> f64 {
+        execution.price * (1.0 + execution.side.sign() * self.bps / 10_000.0)
+    }
+}
+
+/// A fraction of the symbol's last quoted spread: 0.5 crosses from the
+/// middle of the quote to its far side. Fills on quotes are at the far side
+/// already, so there it is slippage beyond the quote. Without a quote, the
+/// price does not move.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpreadSlippage {
+    fraction: f64,
+}
+
+impl SpreadSlippage {
+    pub fn new(fraction: f64) -> Result<Self> {
+        Ok(Self {
+            fraction: check_amount("spread fraction", fraction)?,
+        })
+    }
+}
+
+impl SlippageModel for SpreadSlippage {
+    fn price(&self, execution: &Execution) -> f64 {
+        let spread = execution.spread.unwrap_or(0.0).max(0.0);
+        execution.price + execution.side.sign() * self.fraction * spread
+    }
+}
+
+/// Market impact growing with the square root of the order's share of the
+/// volume: `impact_bps` basis points for an order as large as the volume,
+/// a quarter of it for one sixteenth. Orders without a volume pay
+/// `impact_bps`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumeShare {
+    impact_bps: f64,
+}
+
+impl VolumeShare {
+    pub fn new(impact_bps: f64) -> Result<Self> {
+        Ok(Self {
+            impact_bps: check_amount("impact", impact_bps)?,
+        })
+    }
+}
+
+impl SlippageModel for VolumeShare {
+    fn price(&self, execution: &Execution) -> f64 {
+        let participation = match execution.volume {
+            Some(volume) if volume > 0.0 => execution.quantity / volume,
+            _ => 1.0,
+        };
+        let bps = self.impact_bps * participation.sqrt();
+        execution.price * (1.0 + execution.side.sign() * bps / 10_000.0)
+    }
+}
+
+/// No commission
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NoCommission;
+
+impl CommissionModel for NoCommission {
+    fn commission(&self, _quantity: f64, _price: f64, _traded: f64) -> f64 {
+        0.0
+    }
+}
+
+/// A rate per share, with a minimum per fill
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PerShare {
+    rate: f64,
+    minimum: f64,
+}
+
+impl PerShare {
+    pub fn new(rate: f64, minimum: f64) -> Result<Self> {
+        Ok(Self {
+            rate: check_amount("commission per share", rate)?,
+            minimum: check_amount("minimum commission", minimum)?,
+        })
+    }
+}
+
+impl CommissionModel for PerShare {
+    fn commission(&self, quantity: f64, _price: f64, _traded: f64) -> f64 {
+        (self.rate * quantity).max(self.minimum)
+    }
+}
+
+/// The same amount on every fill
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PerOrder {
+    amount: f64,
+}
+
+impl PerOrder {
+    pub fn new(amount: f64) -> Result<Self> {
+        Ok(Self {
+            amount: check_amount("commission per order", amount)?,
+        })
+    }
+}
+
+impl CommissionModel for PerOrder {
+    fn commission(&self, _quantity: f64, _price: f64, _traded: f64) -> f64 {
+        self.amount
+    }
+}
+
+/// One tier of a `Tiered` commission
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tier {
+    /// Shares traded in the run up to which the tier applies; the last tier
+    /// has none
+    pub up_to: Option<f64>,
+    pub per_share: f64,
+}
+
+/// A rate per share that falls as the run trades more shares, like a
+/// broker's monthly volume tiers, with a minimum per fill. A fill that
+/// crosses into the next tier pays each tier's rate for its shares in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tiered {
+    tiers: Vec<Tier>,
+    minimum: f64,
+}
+
+impl Tiered {
+    /// `tiers` in order of `up_to`, the last one without a limit
+    pub fn new(tiers: Vec<Tier>, minimum: f64) -> Result<Self> {
+        if tiers.is_empty() {
+            return Err(Error::Invalid("Tiered commission needs a tier".to_string()));
+        }
+        let mut previous = 0.0;
+        for (index, tier) in tiers.iter().enumerate() {
+            check_amount("commission per share", tier.per_share)?;
+            match (tier.up_to, index + 1 == tiers.len()) {
+                (None, true) => {}
+                (Some(_), true) => {
+                    return Err(Error::Invalid(
+                        "The last commission tier must not have up_to".to_string(),
+                    ))
+                }
+                (None, false) => {
+                    return Err(Error::Invalid(
+                        "Only the last commission tier can be without up_to".to_string(),
+                    ))
+                }
+                (Some(up_to), false) => {
+                    if !(up_to > previous && up_to.is_finite()) {
+                        return Err(Error::Invalid(format!(
+                            "Invalid commission tier up to {}: tiers must rise",
+                            up_to
+                        )));
+                    }
+                    previous = up_to;
+                }
+            }
+        }
+        Ok(Self {
+            tiers,
+            minimum: check_amount("minimum commission", minimum)?,
+        })
+    }
+}
+
+impl CommissionModel for Tiered {
+    fn commission(&self, quantity: f64, _price: f64, traded: f64) -> f64 {
+        let mut from = traded;
+        let end = traded + quantity;
+        let mut total = 0.0;
+        for tier in &self.tiers {
+            let limit = tier.up_to.unwrap_or(f64::INFINITY);
+            if from >= limit {
+                continue;
+            }
+            let to = end.min(limit);
+            total += (to - from) * tier.per_share;
+            from = to;
+            if from >= end {
+                break;
+            }
+        }
+        total.max(self.minimum)
+    }
+}
+
+/// Several charges on every fill, e.g. a per-share rate and a fee per order
+impl CommissionModel for Vec<Arc<dyn CommissionModel>> {
+    fn commission(&self, quantity: f64, price: f64, traded: f64) -> f64 {
+        self.iter()
+            .map(|model| model.commission(quantity, price, traded))
+            .sum()
+    }
+}
+
+/// A slippage model as written in the config file
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(tag = "model", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum SlippageConfig {
+    #[default]
+    None,
+    FixedBps {
+        bps: f64,
+    },
+    Spread {
+        fraction: f64,
+    },
+    VolumeShare {
+        impact_bps: f64,
+    },
+}
+
+impl SlippageConfig {
+    pub fn build(&self) -> Result<Arc<dyn SlippageModel>> {
+        Ok(match self {
+            SlippageConfig::None => Arc::new(NoSlippage),
+            SlippageConfig::FixedBps { bps } => Arc::new(FixedBps::new(*bps)?),
+            SlippageConfig::Spread { fraction } => Arc::new(SpreadSlippage::new(*fraction)?),
+            SlippageConfig::VolumeShare { impact_bps } => Arc::new(VolumeShare::new(*impact_bps)?),
+        })
+    }
+}
+
+/// A commission model as written in the config file
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(tag = "model", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum CommissionConfig {
+    #[default]
+    None,
+    PerShare {
+        rate: f64,
+        #[serde(default)]
+        minimum: f64,
+    },
+    PerOrder {
+        amount: f64,
+    },
+    Tiered {
+        tiers: Vec<Tier>,
+        #[serde(default)]
+        minimum: f64,
+    },
+}
+
+impl CommissionConfig {
+    pub fn build(&self) -> Result<Arc<dyn CommissionModel>> {
+        Ok(match self {
+            CommissionConfig::None => Arc::new(NoCommission),
+            CommissionConfig::PerShare { rate, minimum } => {
+                Arc::new(PerShare::new(*rate, *minimum)?)
+            }
+            CommissionConfig::PerOrder { amount } => Arc::new(PerOrder::new(*amount)?),
+            CommissionConfig::Tiered { tiers, minimum } => {
+                Arc::new(Tiered::new(tiers.clone(), *minimum)?)
+            }
+        })
+    }
+}
+
+/// The `[costs]` table of the config file
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CostsConfig {
+    pub slippage: SlippageConfig,
+    pub commission: CommissionConfig,
+}
@@ -0,0 +1,264 @@
+pub use crate::config::DEFAULT_DATA_URL;
+use crate::trade_updates::DEFAULT_TRADING_BASE_URL;
+use crate::{Error, Result};
+use std::time::Duration;
+
+/// Symbol used to probe feed entitlements
+const PROBE_SYMBOL: &str = "SPY";
+
+/// The fields of `GET /v2/account` that matter before a capture session
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Account {
+    pub account_number: String,
+    /// `ACTIVE` once the account can trade
+    pub status: String,
+    #[serde(default)]
+    pub trading_blocked: bool,
+    #[serde(default)]
+    pub account_blocked: bool,
+}
+
+/// The `X-RateLimit-*` headers of a response
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct RateLimit {
+    /// Requests allowed per minute
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    /// Unix time the window resets
+    pub reset: Option<u64>,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+        };
+        let limit = Self {
+            limit: header("x-ratelimit-limit"),
+            remaining: header("x-ratelimit-remaining"),
+            reset: header("x-ratelimit-reset"),
+        };
+        (limit != Self::default()).then_some(limit)
+    }
+}
+
+/// Whether the credentials may read recent data from one feed
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FeedAccess {
+    /// `sip` or `iex`
+    pub feed: String,
+    pub status: u16,
+    /// Error message from the response body, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl FeedAccess {
+    pub fn allowed(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Everything `auth-check` found out, with the problems it diagnosed
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AuthReport {
+    pub base_url: String,
+    pub data_url: String,
+    /// Key ID with all but its first four characters masked
+    pub key_id: String,
+    pub paper: bool,
+    /// `ALPACA_FEED`, the feed the streaming client will use
+    pub configured_feed: String,
+    pub account: Option<Account>,
+    /// HTTP status of `GET /v2/account`
+    pub account_status: u16,
+    pub feeds: Vec<FeedAccess>,
+    pub rate_limit: Option<RateLimit>,
+    pub problems: Vec<String>,
+}
+
+impl AuthReport {
+    /// The best feed the credentials may read, if any
+    pub fn entitlement(&self) -> Option<&str> {
+        ["sip", "iex"].into_iter().find(|feed| {
+            self.feeds
+                .iter()
+                .any(|access| access.feed == *feed && access.allowed())
+        })
+    }
+
+    /// Fill in `problems` from what was found
+    pub fn diagnose(&mut self) {
+        let mut problems = Vec::new();
+        match self.account_status {
+            401 | 403 => problems.push(format!(
+                "The trading API rejected the key ID and secret ({}). Check APCA_API_KEY_ID and APCA_API_SECRET_KEY",
+                self.account_status
+            )),
+            status if !(200..300).contains(&status) => problems.push(format!(
+                "GET /v2/account failed with status {}",
+                status
+            )),
+            _ => {}
+        }
+        // Paper key IDs start with PK, live ones with AK
+        if self.account_status == 401 || self.account_status == 403 {
+            if self.key_id.starts_with("PK") && !self.paper {
+                problems.push(format!("{} looks like a paper key, but {} is the live endpoint. Set APCA_API_BASE_URL to the paper endpoint", self.key_id, self.base_url));
+            } else if self.key_id.starts_with("AK") && self.paper {
+                problems.push(format!("{} looks like a live key, but {} is the paper endpoint. Set APCA_API_BASE_URL to the live endpoint", self.key_id, self.base_url));
+            }
+        }
+        if let Some(account) = &self.account {
+            if account.status != "ACTIVE" {
+                problems.push(format!("Account status is {}", account.status));
+            }
+            if account.account_blocked {
+                problems.push("Account is blocked".to_string());
+            }
+            if account.trading_blocked {
+                problems.push("Trading is blocked on the account".to_string());
+            }
+        }
+        let configured_allowed = self
+            .feeds
+            .iter()
+            .find(|access| access.feed == self.configured_feed)
+            .map(FeedAccess::allowed);
+        match (configured_allowed, self.entitlement()) {
+            (_, None) => {
+                problems.push("No market data access on either the SIP or the IEX feed".to_string())
+            }
+            (Some(false), Some(feed)) => problems.push(format!(
+                "ALPACA_FEED is {}, but the data subscription only covers {}. Use --feed {}",
+                self.configured_feed, feed, feed
+            )),
+            _ => {}
+        }
+        if self.rate_limit.as_ref().and_then(|limit| limit.remaining) == Some(0) {
+            problems.push("The API rate limit is used up".to_string());
+        }
+        self.problems = problems;
+    }
+}
+
+/// Masks all but the first four characters of a key ID
+pub fn mask_key(key: &str) -> String {
+    let visible: String = key.chars().take(4).collect();
+    format!(
+        "{}{}",
+        visible,
+        "*".repeat(key.chars().count().saturating_sub(4))
+    )
+}
+
+/// Probes the trading and market data APIs with the configured credentials
+#[derive(Debug, Clone)]
+pub struct CredentialsChecker {
+    client: reqwest::Client,
+    base_url: String,
+    data_url: String,
+    key: String,
+    secret: String,
+}
+
+impl CredentialsChecker {
+    /// Credentials from `APCA_API_KEY_ID` and `APCA_API_SECRET_KEY`, endpoints
+    /// from `APCA_API_BASE_URL` (paper trading by default) and
+    /// `APCA_API_DATA_URL`
+    pub fn from_env() -> Result<Self> {
+        let var =
+            |name: &str| std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)));
+        let base_url = std::env::var("APCA_API_BASE_URL")
+            .unwrap_or_else(|_| DEFAULT_TRADING_BASE_URL.to_string());
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v2").unwrap_or(base_url).to_string();
+        let data_url = std::env::var("APCA_API_DATA_URL")
+            .unwrap_or_else(|_| DEFAULT_DATA_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            base_url,
+            data_url,
+            key: var("APCA_API_KEY_ID")?,
+            secret: var("APCA_API_SECRET_KEY")?,
+        })
+    }
+
+    async fn get(&self, url: String, query: &[(&str, &str)]) -> Result<reqwest::Response> {
+        Ok(self
+            .client
+            .get(url)
+            .query(query)
+            .header("APCA-API-KEY-ID", &self.key)
+            .header("APCA-API-SECRET-KEY", &self.secret)
+            .send()
+            .await?)
+    }
+
+    /// Whether the latest quote can be read from `feed`
+    pub async fn feed_access(&self, feed: &str) -> Result<FeedAccess> {
+        let response = self
+            .get(
+                format!("{}/v2/stocks/quotes/latest", self.data_url),
+                &[("symbols", PROBE_SYMBOL), ("feed", feed)],
+            )
+            .await?;
+        let status = response.status();
+        let message = if status.is_success() {
+            None
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Some(
+                serde_json::from_str::<serde_json::Value>(&body)
+                    .ok()
+                    .and_then(|json| json["message"].as_str().map(str::to_string))
+                    .unwrap_or(body),
+            )
+        };
+        Ok(FeedAccess {
+            feed: feed.to_string(),
+            status: status.as_u16(),
+            message,
+        })
+    }
+
+    /// Check the account and both feeds, and diagnose the results.
+    /// `configured_feed` is the feed the streaming client would use.
+    pub async fn check(&self, configured_feed: &str) -> Result<AuthReport> {
+        let response = self
+            .get(format!("{}/v2/account", self.base_url), &[])
+            .await?;
+        let account_status = response.status().as_u16();
+        let rate_limit = RateLimit::from_headers(response.headers());
+        let account = if response.status().is_success() {
+            Some(response.json::<Account>().await?)
+        } else {
+            None
+        };
+        let feeds = vec![
+            self.feed_access("sip").await?,
+            self.feed_access("iex").await?,
+        ];
+        let mut report = AuthReport {
+            base_url: self.base_url.clone(),
+            data_url: self.data_url.clone(),
+            key_id: mask_key(&self.key),
+            paper: self.base_url.contains("paper"),
+            configured_feed: configured_feed.to_string(),
+            account,
+            account_status,
+            feeds,
+            rate_limit,
+            problems: Vec::new(),
+        };
+        report.diagnose();
+        Ok(report)
+    }
+}
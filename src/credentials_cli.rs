@@ -0,0 +1,22 @@
+use algorithms_trading::cli::credentials::{self, CredentialsCommand};
+use algorithms_trading::cli::GlobalArgs;
+use anyhow::Result;
+use clap::Parser;
+
+/// Same as `trade credentials`
+#[derive(Parser, Debug)]
+#[command(name = "credentials")]
+#[command(about = "Store Alpaca API keys in the OS keychain instead of a plaintext .env file")]
+#[command(version)]
+struct Args {
+    #[command(flatten)]
+    global: GlobalArgs,
+    
+    #[command(subcommand)]
+    command: CredentialsCommand,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    credentials::run(args.command, &args.global)
+}
@@ -0,0 +1,82 @@
+use crate::{Error, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Holds the process ID in a file for as long as it lives; the file is
+/// locked while it is held and removed on drop
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    /// Keeps the exclusive lock; closing it releases the lock
+    _file: File,
+}
+
+impl PidFile {
+    /// Lock `path` and write this process's ID to it. Fails if another
+    /// process holds the lock; a file left behind by one that is gone is
+    /// replaced.
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut contents = String::new();
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                file.read_to_string(&mut contents)?;
+                return Err(match contents.trim().parse::<u32>() {
+                    Ok(pid) => Error::AlreadyRunning(format!("{} holds process {}", path.display(), pid)),
+                    Err(_) => Error::AlreadyRunning(format!("{} is locked by another process", path.display())),
+                });
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        file.read_to_string(&mut contents)?;
+        if !contents.trim().is_empty() {
+            tracing::warn!(path = %path.display(), pid = contents.trim(), "Replacing stale PID file");
+        }
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        writeln!(file, "{}", std::process::id())?;
+        file.flush()?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            _file: file,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!(path = %self.path.display(), error = %e, "Failed to remove PID file");
+        }
+    }
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM where there is one
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => tracing::info!("Received SIGTERM"),
+                }
+                return;
+            }
+            Err(e) => tracing::warn!(error = %e, "Cannot listen for SIGTERM"),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
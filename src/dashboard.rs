@@ -0,0 +1,321 @@
+use crate::format::attempt_label;
+use crate::{DataFormat, OutputMode, OutputSink, Result, StreamingData};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, List, ListItem, Paragraph, Row, Table};
+use ratatui::Frame;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Lines kept in the scrolling message log
+const LOG_LINES: usize = 500;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolRow {
+    pub last: Option<f64>,
+    /// First price seen this session, the base for the change
+    pub first: Option<f64>,
+    pub bid: Option<f64>,
+    pub ask: Option<f64>,
+    trade_volume: u64,
+    bar_volume: u64,
+}
+
+impl SymbolRow {
+    /// Percent change of the last price since the session's first price
+    pub fn change_percent(&self) -> Option<f64> {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) if first != 0.0 => Some((last - first) / first * 100.0),
+            _ => None,
+        }
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.ask? - self.bid?)
+    }
+
+    /// Traded volume, or the summed bar volume for symbols without trades
+    pub fn volume(&self) -> u64 {
+        if self.trade_volume > 0 {
+            self.trade_volume
+        } else {
+            self.bar_volume
+        }
+    }
+
+    fn set_price(&mut self, price: f64) {
+        self.first.get_or_insert(price);
+        self.last = Some(price);
+    }
+}
+
+#[derive(Debug, Default)]
+struct DashboardInner {
+    symbols: BTreeMap<String, SymbolRow>,
+    log: VecDeque<String>,
+    status: String,
+    messages: u64,
+}
+
+/// What the `--tui` dashboard shows, filled in by writing records to it as a
+/// sink. Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct DashboardState {
+    inner: Arc<Mutex<DashboardInner>>,
+    formatter: Arc<OutputMode>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Default for DashboardState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DashboardState {
+    pub fn new() -> Self {
+        let inner = DashboardInner {
+            status: "Connecting".to_string(),
+            ..Default::default()
+        };
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+            formatter: Arc::new(OutputMode::create_console_mode(DataFormat::Plain)),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Show a row for each symbol, even before any data arrives
+    pub fn add_symbols<I, S>(&self, symbols: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        for symbol in symbols {
+            inner.symbols.entry(symbol.into()).or_default();
+        }
+    }
+
+    /// Make `run` restore the terminal and return
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn row(&self, symbol: &str) -> Option<SymbolRow> {
+        self.inner.lock().unwrap().symbols.get(symbol).cloned()
+    }
+
+    pub fn status(&self) -> String {
+        self.inner.lock().unwrap().status.clone()
+    }
+
+    /// Most recent log lines, oldest first
+    pub fn log_tail(&self, lines: usize) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let skip = inner.log.len().saturating_sub(lines);
+        inner.log.iter().skip(skip).cloned().collect()
+    }
+
+    pub fn record(&self, data: &StreamingData) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.messages += 1;
+        let payload = &data.data;
+        match data.message_type.as_str() {
+            "t" | "q" | "b" => {
+                let Some(symbol) = &data.symbol else {
+                    return;
+                };
+                let row = inner.symbols.entry(symbol.clone()).or_default();
+                match data.message_type.as_str() {
+                    "t" => {
+                        if let Some(price) = payload["p"].as_f64() {
+                            row.set_price(price);
+                        }
+                        row.trade_volume += payload["s"].as_u64().unwrap_or(0);
+                    }
+                    "q" => {
+                        row.bid = payload["bp"].as_f64().or(row.bid);
+                        row.ask = payload["ap"].as_f64().or(row.ask);
+                    }
+                    _ => {
+                        if row.trade_volume == 0 {
+                            if let Some(close) = payload["c"].as_f64() {
+                                row.set_price(close);
+                            }
+                        }
+                        row.bar_volume += payload["v"].as_u64().unwrap_or(0);
+                    }
+                }
+            }
+            "success" if payload.as_str() == Some("authenticated") => {
+                inner.status = "Connected".to_string();
+            }
+            "reconnect" => inner.status = "Connected".to_string(),
+            "disconnect" => {
+                inner.status = match payload["retry_in_secs"].as_u64() {
+                    Some(secs) => format!(
+                        "Reconnecting in {}s (attempt {})",
+                        secs,
+                        attempt_label(payload)
+                    ),
+                    None => "Disconnected".to_string(),
+                };
+            }
+            "control" if payload["action"] == "subscribe" => {
+                for symbol in payload["symbols"].as_array().into_iter().flatten() {
+                    if let Some(symbol) = symbol.as_str() {
+                        inner.symbols.entry(symbol.to_string()).or_default();
+                    }
+                }
+            }
+            _ => {}
+        }
+        if inner.log.len() == LOG_LINES {
+            inner.log.pop_front();
+        }
+        let line = self.formatter.format_plain(data);
+        inner.log.push_back(line);
+    }
+}
+
+impl OutputSink for DashboardState {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        self.record(data);
+        Ok(())
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn close(&self) -> crate::Result<()> {
+        self.stop();
+        Ok(())
+    }
+}
+
+fn price_cell(value: Option<f64>) -> Cell<'static> {
+    Cell::from(
+        value
+            .map(|v| format!("{:.2}", v))
+            .unwrap_or_else(|| "-".to_string()),
+    )
+}
+
+/// Render the status line, the symbol table and the message log
+pub fn draw(frame: &mut Frame, state: &DashboardState) {
+    let [status_area, table_area, log_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(5),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+
+    let (status, messages, rows) = {
+        let inner = state.inner.lock().unwrap();
+        let rows: Vec<(String, SymbolRow)> = inner
+            .symbols
+            .iter()
+            .map(|(symbol, row)| (symbol.clone(), row.clone()))
+            .collect();
+        (inner.status.clone(), inner.messages, rows)
+    };
+    let status_style = match status.as_str() {
+        "Connected" => Style::default().fg(Color::Green),
+        "Connecting" => Style::default().fg(Color::Yellow),
+        _ => Style::default().fg(Color::Red),
+    };
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled(status, status_style.add_modifier(Modifier::BOLD)),
+            Span::raw(format!(" | {} messages | q to quit", messages)),
+        ])),
+        status_area,
+    );
+
+    let header = Row::new(["Symbol", "Last", "Chg %", "Bid", "Ask", "Spread", "Volume"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows = rows.into_iter().map(|(symbol, row)| {
+        let change = row.change_percent();
+        let change_style = match change {
+            Some(change) if change > 0.0 => Style::default().fg(Color::Green),
+            Some(change) if change < 0.0 => Style::default().fg(Color::Red),
+            _ => Style::default(),
+        };
+        Row::new([
+            Cell::from(symbol),
+            price_cell(row.last),
+            Cell::from(
+                change
+                    .map(|c| format!("{:+.2}%", c))
+                    .unwrap_or_else(|| "-".to_string()),
+            )
+            .style(change_style),
+            price_cell(row.bid),
+            price_cell(row.ask),
+            Cell::from(
+                row.spread()
+                    .map(|s| format!("{:.4}", s))
+                    .unwrap_or_else(|| "-".to_string()),
+            ),
+            Cell::from(row.volume().to_string()),
+        ])
+    });
+    let widths = [
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Min(8),
+    ];
+    frame.render_widget(
+        Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title("Symbols")),
+        table_area,
+    );
+
+    let visible = log_area.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = state
+        .log_tail(visible)
+        .into_iter()
+        .map(ListItem::new)
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title("Messages")),
+        log_area,
+    );
+}
+
+/// Take over the terminal and redraw until `q`, `Esc` or `Ctrl+C` is pressed
+/// or `stop` is called. Blocking; run it on its own thread.
+pub fn run(state: DashboardState) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        while !state.stopped.load(Ordering::Relaxed) {
+            terminal.draw(|frame| draw(frame, &state))?;
+            if !event::poll(Duration::from_millis(250))? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press
+                    && (ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc))
+                {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+    result
+}
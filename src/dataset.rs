@@ -0,0 +1,595 @@
+use crate::resample::OhlcvBar;
+use crate::{OutputSink, StreamingData};
+use alpaca_trading_api_rust::{StreamingBar, StreamingQuote, StreamingTrade};
+use crate::{Error, Result};
+use arrow_array::{Array, ArrayRef, Float64Array, RecordBatch, StringArray, TimestampMicrosecondArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, NaiveDate, Utc};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Rows buffered per partition before a part file is written
+const DEFAULT_MAX_ROWS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeRow {
+    pub timestamp: DateTime<Utc>,
+    pub price: f64,
+    pub size: u64,
+    pub exchange: String,
+    pub id: u64,
+    /// Space-separated condition codes; only known for historical trades
+    pub conditions: Option<String>,
+    /// Only known for historical trades
+    pub tape: Option<String>,
+    pub received_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteRow {
+    pub timestamp: DateTime<Utc>,
+    pub bid_price: f64,
+    pub bid_size: u64,
+    pub ask_price: f64,
+    pub ask_size: u64,
+    pub received_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarRow {
+    pub timestamp: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    /// Only known for historical bars
+    pub trade_count: Option<u64>,
+    /// Only known for historical bars
+    pub vwap: Option<f64>,
+    /// Only set for streamed bars
+    pub received_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+enum Rows {
+    Trades(Vec<TradeRow>),
+    Quotes(Vec<QuoteRow>),
+    Bars(Vec<BarRow>),
+}
+
+impl Rows {
+    fn table(&self) -> &'static str {
+        match self {
+            Rows::Trades(_) => "trades",
+            Rows::Quotes(_) => "quotes",
+            Rows::Bars(_) => "bars",
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Rows::Trades(rows) => rows.len(),
+            Rows::Quotes(rows) => rows.len(),
+            Rows::Bars(rows) => rows.len(),
+        }
+    }
+
+    fn to_record_batch(&self) -> Result<RecordBatch> {
+        let (fields, columns): (Vec<Field>, Vec<ArrayRef>) = match self {
+            Rows::Trades(rows) => (
+                vec![
+                    timestamp_field("timestamp", false),
+                    Field::new("price", DataType::Float64, false),
+                    Field::new("size", DataType::UInt64, false),
+                    Field::new("exchange", DataType::Utf8, false),
+                    Field::new("id", DataType::UInt64, false),
+                    Field::new("conditions", DataType::Utf8, true),
+                    Field::new("tape", DataType::Utf8, true),
+                    timestamp_field("received_at", true),
+                ],
+                vec![
+                    timestamps(rows.iter().map(|r| Some(r.timestamp))),
+                    Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.price))),
+                    Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.size))),
+                    Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.exchange.as_str()))),
+                    Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.id))),
+                    Arc::new(StringArray::from_iter(rows.iter().map(|r| r.conditions.as_deref()))),
+                    Arc::new(StringArray::from_iter(rows.iter().map(|r| r.tape.as_deref()))),
+                    timestamps(rows.iter().map(|r| r.received_at)),
+                ],
+            ),
+            Rows::Quotes(rows) => (
+                vec![
+                    timestamp_field("timestamp", false),
+                    Field::new("bid_price", DataType::Float64, false),
+                    Field::new("bid_size", DataType::UInt64, false),
+                    Field::new("ask_price", DataType::Float64, false),
+                    Field::new("ask_size", DataType::UInt64, false),
+                    timestamp_field("received_at", true),
+                ],
+                vec![
+                    timestamps(rows.iter().map(|r| Some(r.timestamp))),
+                    Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.bid_price))),
+                    Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.bid_size))),
+                    Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.ask_price))),
+                    Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.ask_size))),
+                    timestamps(rows.iter().map(|r| r.received_at)),
+                ],
+            ),
+            Rows::Bars(rows) => (
+                vec![
+                    timestamp_field("timestamp", false),
+                    Field::new("open", DataType::Float64, false),
+                    Field::new("high", DataType::Float64, false),
+                    Field::new("low", DataType::Float64, false),
+                    Field::new("close", DataType::Float64, false),
+                    Field::new("volume", DataType::UInt64, false),
+                    Field::new("trade_count", DataType::UInt64, true),
+                    Field::new("vwap", DataType::Float64, true),
+                    timestamp_field("received_at", true),
+                ],
+                vec![
+                    timestamps(rows.iter().map(|r| Some(r.timestamp))),
+                    Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.open))),
+                    Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.high))),
+                    Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.low))),
+                    Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.close))),
+                    Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.volume))),
+                    Arc::new(rows.iter().map(|r| r.trade_count).collect::<UInt64Array>()),
+                    Arc::new(rows.iter().map(|r| r.vwap).collect::<Float64Array>()),
+                    timestamps(rows.iter().map(|r| r.received_at)),
+                ],
+            ),
+        };
+        Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+    }
+}
+
+fn timestamp_field(name: &str, nullable: bool) -> Field {
+    Field::new(name, DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), nullable)
+}
+
+fn timestamps(values: impl Iterator<Item = Option<DateTime<Utc>>>) -> ArrayRef {
+    let array: TimestampMicrosecondArray = values.map(|v| v.map(|t| t.timestamp_micros())).collect();
+    Arc::new(array.with_timezone("UTC"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PartitionKey {
+    table: &'static str,
+    symbol: String,
+    date: NaiveDate,
+}
+
+/// Writes trades, quotes and bars as a hive-partitioned Parquet dataset:
+/// `<root>/<table>/symbol=AAPL/date=2024-05-03/part-0.parquet`, where table is
+/// `trades`, `quotes` or `bars`.
+///
+/// Rows are buffered per partition and written as a new part file once
+/// `max_rows` is reached and when the writer is closed; flushing it as a sink
+/// writes nothing, so periodic flushes do not scatter small parts. The
+/// partition date is the UTC date of the exchange timestamp. Part files appear
+/// atomically, so the dataset can be queried while a capture is running.
+#[derive(Debug)]
+pub struct DatasetWriter {
+    root: PathBuf,
+    max_rows: usize,
+    buffers: Mutex<HashMap<PartitionKey, Rows>>,
+}
+
+impl DatasetWriter {
+    pub fn new(root: &Path) -> Result<Self> {
+        std::fs::create_dir_all(root)?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            max_rows: DEFAULT_MAX_ROWS,
+            buffers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows.max(1);
+        self
+    }
+
+    pub fn write_trade(&self, symbol: &str, row: TradeRow) -> Result<()> {
+        let date = row.timestamp.date_naive();
+        self.push(PartitionKey { table: "trades", symbol: symbol.to_string(), date }, |rows| match rows {
+            Rows::Trades(rows) => rows.push(row),
+            _ => unreachable!("trades partition holds trades"),
+        }, || Rows::Trades(Vec::new()))
+    }
+
+    pub fn write_quote(&self, symbol: &str, row: QuoteRow) -> Result<()> {
+        let date = row.timestamp.date_naive();
+        self.push(PartitionKey { table: "quotes", symbol: symbol.to_string(), date }, |rows| match rows {
+            Rows::Quotes(rows) => rows.push(row),
+            _ => unreachable!("quotes partition holds quotes"),
+        }, || Rows::Quotes(Vec::new()))
+    }
+
+    pub fn write_bar(&self, symbol: &str, row: BarRow) -> Result<()> {
+        let date = row.timestamp.date_naive();
+        self.push(PartitionKey { table: "bars", symbol: symbol.to_string(), date }, |rows| match rows {
+            Rows::Bars(rows) => rows.push(row),
+            _ => unreachable!("bars partition holds bars"),
+        }, || Rows::Bars(Vec::new()))
+    }
+
+    fn push<F, N>(&self, key: PartitionKey, push: F, empty: N) -> Result<()>
+    where
+        F: FnOnce(&mut Rows),
+        N: FnOnce() -> Rows,
+    {
+        let mut buffers = self.buffers.lock().unwrap();
+        let rows = buffers.entry(key.clone()).or_insert_with(empty);
+        push(rows);
+        if rows.len() >= self.max_rows {
+            let rows = buffers.remove(&key).expect("partition just written");
+            self.write_part(&key, &rows)?;
+        }
+        Ok(())
+    }
+
+    /// Write every buffered partition to a new part file
+    pub fn flush_all(&self) -> Result<()> {
+        let buffers: Vec<_> = self.buffers.lock().unwrap().drain().collect();
+        for (key, rows) in buffers {
+            self.write_part(&key, &rows)?;
+        }
+        Ok(())
+    }
+
+    fn partition_dir(&self, key: &PartitionKey) -> PathBuf {
+        self.root
+            .join(key.table)
+            .join(format!("symbol={}", key.symbol))
+            .join(format!("date={}", key.date.format("%Y-%m-%d")))
+    }
+
+    fn write_part(&self, key: &PartitionKey, rows: &Rows) -> Result<()> {
+        debug_assert_eq!(key.table, rows.table());
+        if rows.len() == 0 {
+            return Ok(());
+        }
+        let dir = self.partition_dir(key);
+        std::fs::create_dir_all(&dir)?;
+        // Earlier runs may have written parts already; never overwrite them
+        let mut index = 0;
+        while dir.join(format!("part-{}.parquet", index)).exists() {
+            index += 1;
+        }
+        let path = dir.join(format!("part-{}.parquet", index));
+        let temp_path = dir.join(format!(".part-{}.parquet.tmp", index));
+
+        let batch = rows.to_record_batch()?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let mut writer = ArrowWriter::try_new(std::fs::File::create(&temp_path)?, batch.schema(), Some(properties))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        std::fs::rename(&temp_path, &path)?;
+
+        tracing::debug!(path = %path.display(), rows = rows.len(), "Wrote dataset part");
+        Ok(())
+    }
+}
+
+/// Write bars to a single Parquet file at `path`, each with the values of the
+/// extra columns `names`, e.g. indicators; missing values are nulls
+pub fn write_bars_file(path: &Path, names: &[String], rows: &[(OhlcvBar, Vec<Option<f64>>)]) -> Result<()> {
+    let mut fields = vec![
+        Field::new("symbol", DataType::Utf8, false),
+        timestamp_field("timestamp", false),
+        Field::new("open", DataType::Float64, false),
+        Field::new("high", DataType::Float64, false),
+        Field::new("low", DataType::Float64, false),
+        Field::new("close", DataType::Float64, false),
+        Field::new("volume", DataType::UInt64, false),
+        Field::new("trade_count", DataType::UInt64, false),
+        Field::new("vwap", DataType::Float64, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(bar, _)| bar.symbol.as_str()))),
+        timestamps(rows.iter().map(|(bar, _)| Some(bar.timestamp))),
+        Arc::new(Float64Array::from_iter_values(rows.iter().map(|(bar, _)| bar.open))),
+        Arc::new(Float64Array::from_iter_values(rows.iter().map(|(bar, _)| bar.high))),
+        Arc::new(Float64Array::from_iter_values(rows.iter().map(|(bar, _)| bar.low))),
+        Arc::new(Float64Array::from_iter_values(rows.iter().map(|(bar, _)| bar.close))),
+        Arc::new(UInt64Array::from_iter_values(rows.iter().map(|(bar, _)| bar.volume))),
+        Arc::new(UInt64Array::from_iter_values(rows.iter().map(|(bar, _)| bar.trade_count))),
+        Arc::new(Float64Array::from_iter_values(rows.iter().map(|(bar, _)| bar.vwap))),
+    ];
+    for (index, name) in names.iter().enumerate() {
+        fields.push(Field::new(name, DataType::Float64, true));
+        columns.push(Arc::new(rows.iter().map(|(_, values)| values.get(index).copied().flatten()).collect::<Float64Array>()));
+    }
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut writer = ArrowWriter::try_new(std::fs::File::create(path)?, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// The timestamp of the last row of each symbol in `table` (`trades`, `quotes`
+/// or `bars`) of the dataset at `root`, read from the part files of the
+/// symbol's latest date partition. A missing dataset has no rows.
+pub fn last_timestamps(root: &Path, table: &str) -> Result<HashMap<String, DateTime<Utc>>> {
+    let mut last = HashMap::new();
+    for (symbol, symbol_dir) in symbol_dirs(root, table)? {
+        let latest_partition = date_dirs(&symbol_dir)?.into_iter().max_by_key(|(date, _)| *date);
+        let Some((_, dir)) = latest_partition else {
+            continue;
+        };
+        let timestamps = part_timestamps(&dir)?;
+        if let Some(timestamp) = timestamps.into_iter().max() {
+            last.insert(symbol, timestamp);
+        }
+    }
+    Ok(last)
+}
+
+/// The timestamps of every row of each symbol in `table` of the dataset at
+/// `root`, in no particular order. A missing dataset has no rows.
+pub fn stored_timestamps(root: &Path, table: &str) -> Result<HashMap<String, Vec<DateTime<Utc>>>> {
+    let mut stored = HashMap::new();
+    for (symbol, symbol_dir) in symbol_dirs(root, table)? {
+        let mut timestamps = Vec::new();
+        for (_, dir) in date_dirs(&symbol_dir)? {
+            timestamps.extend(part_timestamps(&dir)?);
+        }
+        stored.insert(symbol, timestamps);
+    }
+    Ok(stored)
+}
+
+/// The `symbol=` directories of `table`
+fn symbol_dirs(root: &Path, table: &str) -> Result<Vec<(String, PathBuf)>> {
+    let table_dir = root.join(table);
+    if !table_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(&table_dir)? {
+        let entry = entry?;
+        if let Some(symbol) = entry.file_name().to_str().and_then(|name| name.strip_prefix("symbol=")) {
+            dirs.push((symbol.to_string(), entry.path()));
+        }
+    }
+    Ok(dirs)
+}
+
+/// The `date=` partitions of a symbol
+fn date_dirs(symbol_dir: &Path) -> Result<Vec<(NaiveDate, PathBuf)>> {
+    Ok(std::fs::read_dir(symbol_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let date = entry.file_name().to_str()?.strip_prefix("date=")?.parse::<NaiveDate>().ok()?;
+            Some((date, entry.path()))
+        })
+        .collect())
+}
+
+/// The part files of a partition, in the order they were written
+fn part_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut parts: Vec<(usize, PathBuf)> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            // Parts still being written end in .tmp
+            let index = entry.file_name().to_str()?.strip_prefix("part-")?.strip_suffix(".parquet")?.parse().ok()?;
+            Some((index, entry.path()))
+        })
+        .collect();
+    parts.sort();
+    Ok(parts.into_iter().map(|(_, path)| path).collect())
+}
+
+/// The record batches of a part file
+fn read_part(path: &Path) -> Result<impl Iterator<Item = std::result::Result<RecordBatch, arrow_schema::ArrowError>>> {
+    Ok(ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path)?)?.build()?)
+}
+
+/// The `timestamp` column of every part file of a partition
+fn part_timestamps(dir: &Path) -> Result<Vec<DateTime<Utc>>> {
+    let mut timestamps = Vec::new();
+    for path in part_files(dir)? {
+        for batch in read_part(&path)? {
+            let batch = batch?;
+            let column: &TimestampMicrosecondArray = column(&batch, "timestamp", &path)?;
+            timestamps.extend(column.iter().flatten().filter_map(DateTime::from_timestamp_micros));
+        }
+    }
+    Ok(timestamps)
+}
+
+/// A column that every part file of its table has
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str, path: &Path) -> Result<&'a T> {
+    optional_column(batch, name).ok_or_else(|| Error::Invalid(format!("{} has no {} column", path.display(), name)))
+}
+
+/// A column that only some part files have, such as `vwap` of streamed bars
+fn optional_column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Option<&'a T> {
+    batch.column_by_name(name).and_then(|column| column.as_any().downcast_ref::<T>())
+}
+
+/// Call `on_message` with a message rebuilt from each row of the dataset at
+/// `root`: every trade, then every quote, then every bar, each by symbol and
+/// date. The payloads have the keys of streamed messages; rows without
+/// `received_at` were received at their timestamp.
+pub fn read_dataset(root: &Path, mut on_message: impl FnMut(StreamingData) -> crate::Result<()>) -> Result<()> {
+    for table in ["trades", "quotes", "bars"] {
+        let mut symbols = symbol_dirs(root, table)?;
+        symbols.sort();
+        for (symbol, symbol_dir) in symbols {
+            let mut dates = date_dirs(&symbol_dir)?;
+            dates.sort();
+            for (_, dir) in dates {
+                for path in part_files(&dir)? {
+                    for batch in read_part(&path)? {
+                        for data in batch_messages(table, &symbol, &batch?, &path)? {
+                            on_message(data)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The rows of a batch of `table` as messages of `symbol`
+fn batch_messages(table: &str, symbol: &str, batch: &RecordBatch, path: &Path) -> Result<Vec<StreamingData>> {
+    let timestamps: &TimestampMicrosecondArray = column(batch, "timestamp", path)?;
+    let received: Option<&TimestampMicrosecondArray> = optional_column(batch, "received_at");
+    let time = |array: &TimestampMicrosecondArray, row: usize| {
+        array.is_valid(row).then(|| DateTime::from_timestamp_micros(array.value(row))).flatten()
+    };
+    let mut payloads = Vec::with_capacity(batch.num_rows());
+    match table {
+        "trades" => {
+            let price: &Float64Array = column(batch, "price", path)?;
+            let size: &UInt64Array = column(batch, "size", path)?;
+            let exchange: &StringArray = column(batch, "exchange", path)?;
+            let id: &UInt64Array = column(batch, "id", path)?;
+            let conditions: Option<&StringArray> = optional_column(batch, "conditions");
+            let tape: Option<&StringArray> = optional_column(batch, "tape");
+            for row in 0..batch.num_rows() {
+                let mut payload = serde_json::json!({
+                    "T": "t", "S": symbol, "i": id.value(row), "x": exchange.value(row),
+                    "p": price.value(row), "s": size.value(row),
+                });
+                if let Some(conditions) = conditions.filter(|array| array.is_valid(row)) {
+                    payload["c"] = conditions.value(row).split_whitespace().collect::<Vec<_>>().into();
+                }
+                if let Some(tape) = tape.filter(|array| array.is_valid(row)) {
+                    payload["z"] = tape.value(row).into();
+                }
+                payloads.push(("t", payload));
+            }
+        }
+        "quotes" => {
+            let bid_price: &Float64Array = column(batch, "bid_price", path)?;
+            let bid_size: &UInt64Array = column(batch, "bid_size", path)?;
+            let ask_price: &Float64Array = column(batch, "ask_price", path)?;
+            let ask_size: &UInt64Array = column(batch, "ask_size", path)?;
+            for row in 0..batch.num_rows() {
+                payloads.push(("q", serde_json::json!({
+                    "T": "q", "S": symbol, "bp": bid_price.value(row), "bs": bid_size.value(row),
+                    "ap": ask_price.value(row), "as": ask_size.value(row),
+                })));
+            }
+        }
+        _ => {
+            let open: &Float64Array = column(batch, "open", path)?;
+            let high: &Float64Array = column(batch, "high", path)?;
+            let low: &Float64Array = column(batch, "low", path)?;
+            let close: &Float64Array = column(batch, "close", path)?;
+            let volume: &UInt64Array = column(batch, "volume", path)?;
+            let trade_count: Option<&UInt64Array> = optional_column(batch, "trade_count");
+            let vwap: Option<&Float64Array> = optional_column(batch, "vwap");
+            for row in 0..batch.num_rows() {
+                let mut payload = serde_json::json!({
+                    "T": "b", "S": symbol, "o": open.value(row), "h": high.value(row),
+                    "l": low.value(row), "c": close.value(row), "v": volume.value(row),
+                });
+                if let Some(trade_count) = trade_count.filter(|array| array.is_valid(row)) {
+                    payload["n"] = trade_count.value(row).into();
+                }
+                if let Some(vwap) = vwap.filter(|array| array.is_valid(row)) {
+                    payload["vw"] = vwap.value(row).into();
+                }
+                payloads.push(("b", payload));
+            }
+        }
+    }
+
+    payloads
+        .into_iter()
+        .enumerate()
+        .map(|(row, (message_type, mut payload))| {
+            let timestamp = time(timestamps, row).ok_or_else(|| Error::Invalid(format!("{} has a row without a timestamp", path.display())))?;
+            payload["t"] = timestamp.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true).into();
+            Ok(StreamingData {
+                received_time: received.and_then(|received| time(received, row)).unwrap_or(timestamp),
+                event_time: Some(timestamp),
+                message_type: message_type.to_string(),
+                symbol: Some(symbol.to_string()),
+                data: payload,
+            })
+        })
+        .collect()
+}
+
+/// Captures written before `event_time` existed only carry it in the payload
+fn event_time(data: &StreamingData, payload_time: &str) -> DateTime<Utc> {
+    data.event_time.or_else(|| payload_time.parse().ok()).unwrap_or(data.received_time)
+}
+
+impl OutputSink for DatasetWriter {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        let received_at = Some(data.received_time);
+        match data.message_type.as_str() {
+            "t" => {
+                let trade: StreamingTrade = serde_json::from_value(data.data.clone())?;
+                let row = TradeRow {
+                    timestamp: event_time(data, &trade.timestamp.to_string()),
+                    price: trade.price,
+                    size: trade.size,
+                    exchange: trade.exchange.clone(),
+                    id: trade.id,
+                    conditions: None,
+                    tape: None,
+                    received_at,
+                };
+                Ok(self.write_trade(&trade.symbol, row)?)
+            }
+            "q" => {
+                let quote: StreamingQuote = serde_json::from_value(data.data.clone())?;
+                let row = QuoteRow {
+                    timestamp: event_time(data, &quote.timestamp.to_string()),
+                    bid_price: quote.bid_price,
+                    bid_size: quote.bid_size,
+                    ask_price: quote.ask_price,
+                    ask_size: quote.ask_size,
+                    received_at,
+                };
+                Ok(self.write_quote(&quote.symbol, row)?)
+            }
+            "b" => {
+                let bar: StreamingBar = serde_json::from_value(data.data.clone())?;
+                let row = BarRow {
+                    timestamp: event_time(data, &bar.timestamp.to_string()),
+                    open: bar.open,
+                    high: bar.high,
+                    low: bar.low,
+                    close: bar.close,
+                    volume: bar.volume,
+                    trade_count: None,
+                    vwap: None,
+                    received_at,
+                };
+                Ok(self.write_bar(&bar.symbol, row)?)
+            }
+            // Only market data belongs in the dataset
+            _ => Ok(()),
+        }
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn close(&self) -> crate::Result<()> {
+        self.flush_all()
+    }
+}
@@ -0,0 +1,174 @@
+//! Date arguments such as `yesterday`, `2024-01` or `2024-01-15T09:30`,
+//! resolved in New York time into bounds the market data API accepts.
+
+use crate::market_time::{eastern_offset, new_york_date};
+use crate::{Error, Result};
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc, Weekday,
+};
+
+/// The forms a date argument can take, for help and error messages
+pub const DATE_ARG_FORMS: &str = "YYYY-MM-DD, YYYY-MM, YYYY, YYYY-MM-DDTHH:MM[:SS] in New York time, RFC 3339, now, today, yesterday, last-week or last-month";
+
+/// Which end of a range an argument gives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeEnd {
+    Start,
+    End,
+}
+
+/// What an argument names
+enum DateArg {
+    Day(NaiveDate),
+    /// First and last day of a week, month or year
+    Period(NaiveDate, NaiveDate),
+    Time(DateTime<Utc>),
+}
+
+/// Resolves date arguments at a fixed time, optionally against the trading
+/// days of the market calendar
+#[derive(Debug, Clone)]
+pub struct DateResolver {
+    now: DateTime<Utc>,
+    trading_days: Vec<NaiveDate>,
+}
+
+impl DateResolver {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now,
+            trading_days: Vec::new(),
+        }
+    }
+
+    /// Trading days up to today, so `yesterday` skips holidays as well as
+    /// weekends
+    pub fn with_trading_days(mut self, mut days: Vec<NaiveDate>) -> Self {
+        days.sort();
+        self.trading_days = days;
+        self
+    }
+
+    /// Today in New York
+    pub fn today(&self) -> NaiveDate {
+        new_york_date(self.now)
+    }
+
+    /// Resolve `value` into a request bound, a day as `YYYY-MM-DD` or a time
+    /// as RFC 3339 in UTC:
+    ///
+    /// - `YYYY-MM-DD` as given
+    /// - `YYYY-MM` and `YYYY`: the month or year
+    /// - `YYYY-MM-DDTHH:MM[:SS]`: a time in New York; RFC 3339 with an
+    ///   offset is taken as is
+    /// - `now`, and `today` in New York
+    /// - `yesterday`: the last trading day before today
+    /// - `last-week` (Monday to Sunday) and `last-month`
+    ///
+    /// The API takes a day as its midnight, so a period starts on its first
+    /// day and, as the end, ends on the day after its last.
+    pub fn resolve(&self, value: &str, end: RangeEnd) -> Result<String> {
+        let day = match (self.parse(value)?, end) {
+            (DateArg::Time(time), _) => {
+                return Ok(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            (DateArg::Day(day), _) | (DateArg::Period(day, _), RangeEnd::Start) => day,
+            (DateArg::Period(_, last), RangeEnd::End) => last + Duration::days(1),
+        };
+        Ok(day.format("%Y-%m-%d").to_string())
+    }
+
+    /// Resolve `value` into a day of an inclusive range of days: a period
+    /// gives its first or last day, a time its New York date
+    pub fn resolve_day(&self, value: &str, end: RangeEnd) -> Result<NaiveDate> {
+        Ok(match (self.parse(value)?, end) {
+            (DateArg::Time(time), _) => new_york_date(time),
+            (DateArg::Day(day), _) | (DateArg::Period(day, _), RangeEnd::Start) => day,
+            (DateArg::Period(_, last), RangeEnd::End) => last,
+        })
+    }
+
+    /// The start of a range `days` before today in New York
+    pub fn days_back(&self, days: u32) -> String {
+        (self.today() - Duration::days(days as i64))
+            .format("%Y-%m-%d")
+            .to_string()
+    }
+
+    fn parse(&self, value: &str) -> Result<DateArg> {
+        let value = value.trim();
+        let today = self.today();
+        Ok(match value.to_lowercase().as_str() {
+            "now" => DateArg::Time(self.now),
+            "today" => DateArg::Day(today),
+            "yesterday" => DateArg::Day(self.previous_trading_day(today)),
+            "last-week" => {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+                DateArg::Period(monday - Duration::days(7), monday - Duration::days(1))
+            }
+            "last-month" => {
+                let (first, _) = month_bounds(today);
+                let (first, last) = month_bounds(first - Duration::days(1));
+                DateArg::Period(first, last)
+            }
+            _ => parse_explicit(value)?,
+        })
+    }
+
+    fn previous_trading_day(&self, today: NaiveDate) -> NaiveDate {
+        if let Some(day) = self.trading_days.iter().rev().find(|day| **day < today) {
+            return *day;
+        }
+        let mut day = today - Duration::days(1);
+        while matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+            day -= Duration::days(1);
+        }
+        day
+    }
+}
+
+/// The first and last day of the month of `date`
+fn month_bounds(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let first = date.with_day(1).expect("every month has a first day");
+    let next = if first.month() == 12 {
+        NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+    };
+    (first, next.expect("valid month") - Duration::days(1))
+}
+
+/// `value` as a date, time, month or year
+fn parse_explicit(value: &str) -> Result<DateArg> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(DateArg::Day(date));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(DateArg::Time(time.with_timezone(&Utc)));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(local) = NaiveDateTime::parse_from_str(value, format) {
+            let time = eastern_offset(local.date())
+                .from_local_datetime(&local)
+                .single()
+                .ok_or_else(|| Error::Invalid(format!("Invalid time: {}", value)))?;
+            return Ok(DateArg::Time(time.with_timezone(&Utc)));
+        }
+    }
+    if let Ok(month) = NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d") {
+        let (first, last) = month_bounds(month);
+        return Ok(DateArg::Period(first, last));
+    }
+    let year = (value.len() == 4)
+        .then(|| value.parse::<i32>().ok())
+        .flatten();
+    if let Some((first, last)) = year.and_then(|year| {
+        NaiveDate::from_ymd_opt(year, 1, 1).zip(NaiveDate::from_ymd_opt(year, 12, 31))
+    }) {
+        return Ok(DateArg::Period(first, last));
+    }
+    Err(Error::Invalid(format!(
+        "Invalid date: {}; expected {}",
+        value, DATE_ARG_FORMS
+    )))
+}
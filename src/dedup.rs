@@ -0,0 +1,74 @@
+//! Removing the messages that several captures of the same stream have in
+//! common, such as overlapping sessions or a primary and a failover client.
+
+use crate::StreamingData;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+
+/// What makes two messages the same: symbol, event time, type and trade id.
+/// Messages without a trade id, such as quotes, are also told apart by their
+/// payload, so distinct quotes with the same timestamp are all kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageKey {
+    pub symbol: Option<String>,
+    /// Event time, or the received time of messages without one
+    pub time: DateTime<Utc>,
+    pub message_type: String,
+    pub id: Option<u64>,
+    /// The payload as JSON, only for messages without an id
+    pub payload: Option<String>,
+}
+
+impl MessageKey {
+    pub fn of(data: &StreamingData) -> Self {
+        let id = data.data.get("i").and_then(|id| id.as_u64());
+        Self {
+            symbol: data.symbol.clone(),
+            time: message_time(data),
+            message_type: data.message_type.clone(),
+            id,
+            payload: id.is_none().then(|| data.data.to_string()),
+        }
+    }
+}
+
+/// The time captures are ordered by: the event time, or the received time of
+/// messages without one
+pub fn message_time(data: &StreamingData) -> DateTime<Utc> {
+    data.event_time.unwrap_or(data.received_time)
+}
+
+/// Drops repeated messages from a stream in time order. Duplicates share a
+/// time, so only the keys of the latest time are remembered.
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    time: Option<DateTime<Utc>>,
+    seen: HashSet<MessageKey>,
+    duplicates: u64,
+}
+
+impl Deduplicator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `data` is the first message with its key; duplicates are
+    /// counted
+    pub fn is_new(&mut self, data: &StreamingData) -> bool {
+        let key = MessageKey::of(data);
+        if self.time != Some(key.time) {
+            self.time = Some(key.time);
+            self.seen.clear();
+        }
+        let new = self.seen.insert(key);
+        if !new {
+            self.duplicates += 1;
+        }
+        new
+    }
+
+    /// Number of duplicates dropped so far
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
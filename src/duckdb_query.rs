@@ -0,0 +1,273 @@
+//! SQL on captures with DuckDB, in process. Messages are loaded into a
+//! `messages` table in memory, with the payload as JSON, and a view per
+//! message type decodes the payload into typed columns, so `SELECT symbol,
+//! avg(price) FROM trades GROUP BY symbol` works without knowing the payload
+//! keys.
+
+use crate::{payload_fields, DataFormat, Error, Result, StreamingData};
+use chrono::{DateTime, NaiveTime, SecondsFormat, Utc};
+use duckdb::types::ValueRef;
+use duckdb::{Appender, Connection};
+
+const SCHEMA: &str = "
+CREATE TABLE messages (
+    received_time TIMESTAMP NOT NULL,
+    event_time TIMESTAMP,
+    message_type VARCHAR NOT NULL,
+    symbol VARCHAR,
+    data JSON NOT NULL
+);
+";
+
+/// The view of each message type with payload fields
+const VIEWS: [(&str, &str); 10] = [
+    ("t", "trades"),
+    ("q", "quotes"),
+    ("b", "bars"),
+    ("d", "daily_bars"),
+    ("u", "updated_bars"),
+    ("c", "corrections"),
+    ("x", "cancels"),
+    ("s", "statuses"),
+    ("l", "luld"),
+    ("i", "imbalances"),
+];
+
+/// SQL type of a payload field in the views; fields not listed stay text
+fn column_type(name: &str) -> Option<&'static str> {
+    match name {
+        "price" | "bid" | "ask" | "open" | "high" | "low" | "close" | "original_price"
+        | "corrected_price" | "limit_up" | "limit_down" => Some("DOUBLE"),
+        "size" | "bid_size" | "ask_size" | "volume" | "original_size" | "corrected_size" => {
+            Some("BIGINT")
+        }
+        "id" => Some("UBIGINT"),
+        _ => None,
+    }
+}
+
+/// `CREATE VIEW` of the messages of `message_type`, with a column per
+/// payload field
+fn view_sql(message_type: &str, view: &str) -> String {
+    let columns: String = payload_fields(message_type)
+        .iter()
+        .map(|(name, key)| {
+            let text = format!("json_extract_string(data, '$.\"{}\"')", key);
+            match column_type(name) {
+                Some(sql_type) => format!(", TRY_CAST({} AS {}) AS \"{}\"", text, sql_type, name),
+                None => format!(", {} AS \"{}\"", text, name),
+            }
+        })
+        .collect();
+    format!(
+        "CREATE VIEW \"{}\" AS SELECT received_time, event_time, symbol{} FROM messages WHERE message_type = '{}';",
+        view, columns, message_type
+    )
+}
+
+/// Times are loaded as UTC timestamps with microseconds
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S%.6f").to_string()
+}
+
+/// An in-memory DuckDB database to load captures into and query
+pub struct QueryDatabase {
+    connection: Connection,
+}
+
+impl QueryDatabase {
+    /// An empty database with the `messages` table and the views
+    pub fn open_in_memory() -> Result<Self> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute_batch(SCHEMA)?;
+        for (message_type, view) in VIEWS {
+            connection.execute_batch(&view_sql(message_type, view))?;
+        }
+        Ok(Self { connection })
+    }
+
+    /// A loader that appends messages in bulk until it is finished
+    pub fn loader(&self) -> Result<Loader<'_>> {
+        Ok(Loader {
+            appender: self.connection.appender("messages")?,
+        })
+    }
+
+    /// Run `sql` and collect its result
+    pub fn query(&self, sql: &str) -> Result<QueryResult> {
+        let mut statement = self.connection.prepare(sql)?;
+        let mut results = statement.query([])?;
+        let columns = results
+            .as_ref()
+            .map(|statement| statement.column_names())
+            .unwrap_or_default();
+        let mut rows = Vec::new();
+        while let Some(row) = results.next()? {
+            let mut values = Vec::with_capacity(columns.len());
+            for (index, column) in columns.iter().enumerate() {
+                values.push(json_value(row.get_ref(index)?, column)?);
+            }
+            rows.push(values);
+        }
+        Ok(QueryResult { columns, rows })
+    }
+}
+
+/// Appends messages to the `messages` table of a [`QueryDatabase`]
+pub struct Loader<'a> {
+    appender: Appender<'a>,
+}
+
+impl Loader<'_> {
+    pub fn append(&mut self, data: &StreamingData) -> Result<()> {
+        self.appender.append_row([
+            Some(format_time(data.received_time)),
+            data.event_time.map(format_time),
+            Some(data.message_type.clone()),
+            data.symbol.clone(),
+            Some(data.data.to_string()),
+        ])?;
+        Ok(())
+    }
+
+    /// Write the messages still buffered, so queries see them
+    pub fn finish(mut self) -> Result<()> {
+        self.appender.flush()?;
+        Ok(())
+    }
+}
+
+/// A value of a result as JSON. Lists, structs and other nested values have
+/// no JSON form here and are an error; casting them to VARCHAR prints them.
+fn json_value(value: ValueRef<'_>, column: &str) -> Result<serde_json::Value> {
+    Ok(match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Boolean(value) => value.into(),
+        ValueRef::TinyInt(value) => value.into(),
+        ValueRef::SmallInt(value) => value.into(),
+        ValueRef::Int(value) => value.into(),
+        ValueRef::BigInt(value) => value.into(),
+        ValueRef::UTinyInt(value) => value.into(),
+        ValueRef::USmallInt(value) => value.into(),
+        ValueRef::UInt(value) => value.into(),
+        ValueRef::UBigInt(value) => value.into(),
+        ValueRef::HugeInt(value) => match i64::try_from(value) {
+            Ok(value) => value.into(),
+            Err(_) => value.to_string().into(),
+        },
+        ValueRef::Float(value) => f64::from(value).into(),
+        ValueRef::Double(value) => value.into(),
+        ValueRef::Decimal(value) => serde_json::from_str(&value.to_string())?,
+        ValueRef::Text(text) | ValueRef::Blob(text) => String::from_utf8_lossy(text).into(),
+        ValueRef::Timestamp(unit, value) => DateTime::from_timestamp_micros(unit.to_micros(value))
+            .map(|time| time.to_rfc3339_opts(SecondsFormat::Micros, true))
+            .into(),
+        ValueRef::Date32(days) => DateTime::from_timestamp(i64::from(days) * 86_400, 0)
+            .map(|time| time.date_naive().to_string())
+            .into(),
+        ValueRef::Time64(unit, value) => {
+            let micros = unit.to_micros(value);
+            NaiveTime::from_num_seconds_from_midnight_opt(
+                (micros / 1_000_000) as u32,
+                (micros % 1_000_000) as u32 * 1000,
+            )
+            .map(|time| time.to_string())
+            .into()
+        }
+        _ => {
+            return Err(Error::Invalid(format!(
+                "Column {} has a type that cannot be printed; cast it to VARCHAR",
+                column
+            )))
+        }
+    })
+}
+
+/// The columns and rows a query returned
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+impl QueryResult {
+    /// The result as lines in `format`: an aligned table for plain text, a
+    /// header and rows for CSV, and an object per row for JSON
+    pub fn lines(&self, format: &DataFormat) -> Result<Vec<String>> {
+        let text = |value: &serde_json::Value| match value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(text) => text.clone(),
+            value => value.to_string(),
+        };
+        Ok(match format {
+            DataFormat::Plain => {
+                let cells: Vec<Vec<String>> = self
+                    .rows
+                    .iter()
+                    .map(|row| row.iter().map(text).collect())
+                    .collect();
+                let widths: Vec<usize> = self
+                    .columns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, column)| {
+                        cells
+                            .iter()
+                            .map(|row| row[index].chars().count())
+                            .chain([column.chars().count()])
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect();
+                let line = |row: &[String]| {
+                    row.iter()
+                        .zip(&widths)
+                        .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                        .collect::<Vec<_>>()
+                        .join("  ")
+                        .trim_end()
+                        .to_string()
+                };
+                let mut lines = vec![
+                    line(&self.columns),
+                    widths
+                        .iter()
+                        .map(|width| "-".repeat(*width))
+                        .collect::<Vec<_>>()
+                        .join("  "),
+                ];
+                lines.extend(cells.iter().map(|row| line(row)));
+                lines
+            }
+            DataFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                writer.write_record(&self.columns)?;
+                for row in &self.rows {
+                    writer.write_record(row.iter().map(text))?;
+                }
+                let bytes = writer.into_inner().map_err(|e| Error::Io(e.into_error()))?;
+                String::from_utf8(bytes)
+                    .map_err(|e| Error::Invalid(e.to_string()))?
+                    .lines()
+                    .map(str::to_string)
+                    .collect()
+            }
+            DataFormat::Json => self
+                .rows
+                .iter()
+                .map(|row| {
+                    // Built by hand to keep the columns in query order
+                    let fields = self
+                        .columns
+                        .iter()
+                        .zip(row)
+                        .map(|(column, value)| {
+                            Ok(format!("{}:{}", serde_json::to_string(column)?, value))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok(format!("{{{}}}", fields.join(",")))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+}
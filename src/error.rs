@@ -0,0 +1,123 @@
+/// Errors returned by the library. Binaries and the `cli` module wrap them in
+/// `anyhow` for context.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Reading or writing a file, the console or a socket failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A record could not be encoded or decoded as JSON
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// A CSV row could not be written
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    /// Authentication or a subscription took longer than its timeout
+    #[error(transparent)]
+    Timeout(#[from] tokio::time::error::Elapsed),
+    /// An argument, file or command that is not valid or not supported
+    #[error("{0}")]
+    Invalid(String),
+    /// The streaming session or an output writer is no longer running
+    #[error("{0}")]
+    Stopped(String),
+    /// A server turned down authentication or a subscription
+    #[error("{0}")]
+    Rejected(String),
+    /// Another process holds the PID file
+    #[error("Already running: {0}")]
+    AlreadyRunning(String),
+    /// The trade updates WebSocket could not connect, send or receive
+    #[error(transparent)]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    /// A REST request returned a status other than 2xx
+    #[error(transparent)]
+    Http(#[from] crate::retry::HttpError),
+    /// A REST request could not be sent or its response read
+    #[cfg(any(
+        feature = "webhooks",
+        feature = "assets",
+        feature = "market-hours",
+        feature = "auth-check",
+        feature = "historical-trades",
+        feature = "snapshot",
+        feature = "options",
+        feature = "corporate-actions",
+        feature = "orders"
+    ))]
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    /// The Alpaca client failed to connect, authenticate or fetch data
+    #[error(transparent)]
+    Alpaca(Box<dyn std::error::Error + Send + Sync>),
+    /// The config file could not be watched for changes
+    #[error(transparent)]
+    Watch(#[from] notify_debouncer_mini::notify::Error),
+    /// The keychain could not read, store or remove a credential
+    #[error("Failed to {action} {account} in the keychain: {source}")]
+    Keychain {
+        action: &'static str,
+        account: String,
+        source: keyring::Error,
+    },
+    /// The SQLite database could not be opened, written or queried
+    #[cfg(feature = "sqlite")]
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    /// The DuckDB database could not be loaded or queried
+    #[cfg(feature = "duckdb")]
+    #[error(transparent)]
+    DuckDb(#[from] duckdb::Error),
+    /// A Parquet file could not be written or read
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    Parquet(#[from] parquet::errors::ParquetError),
+    /// A record batch does not match its schema
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    Arrow(#[from] arrow_schema::ArrowError),
+    /// Connecting to NATS or creating its JetStream stream failed
+    #[cfg(feature = "nats")]
+    #[error(transparent)]
+    Nats(async_nats::Error),
+    /// The gRPC server could not start or stopped with an error
+    #[cfg(feature = "grpc")]
+    #[error(transparent)]
+    Grpc(#[from] tonic::transport::Error),
+    /// The chart could not be drawn or written as PNG
+    #[cfg(feature = "charts")]
+    #[error(transparent)]
+    Chart(Box<dyn std::error::Error + Send + Sync>),
+    /// An upload to S3 failed
+    #[cfg(feature = "s3")]
+    #[error(transparent)]
+    S3(#[from] object_store::Error),
+}
+
+impl Error {
+    /// Wrap an error of the Alpaca client, which reports failures as
+    /// `anyhow::Error`. Errors of this crate returned through the client's
+    /// callbacks come back unwrapped.
+    pub fn alpaca(error: anyhow::Error) -> Self {
+        error
+            .downcast::<Error>()
+            .unwrap_or_else(|error| Error::Alpaca(error.into()))
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(error))
+    }
+}
+
+#[cfg(feature = "charts")]
+impl<E> From<plotters::drawing::DrawingAreaErrorKind<E>> for Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    fn from(error: plotters::drawing::DrawingAreaErrorKind<E>) -> Self {
+        Error::Chart(Box::new(error))
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,84 @@
+//! Estimates of how large a bar download is and how long the rate limit
+//! makes it take, for `historical --dry-run`. Nothing is requested: bars are
+//! counted from the timeframe and the weekdays in the range.
+
+use crate::{Error, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+
+/// Minutes from the start of pre-market to the end of after-hours trading,
+/// the most a symbol trades in a day
+const EXTENDED_SESSION_MINUTES: f64 = 16.0 * 60.0;
+
+/// Requests and bars of part of a download
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Estimate {
+    pub requests: u64,
+    pub bars: u64,
+}
+
+impl std::ops::AddAssign for Estimate {
+    fn add_assign(&mut self, other: Self) {
+        self.requests += other.requests;
+        self.bars += other.bars;
+    }
+}
+
+/// The most bars of `timeframe` (as `validate_timeframe` spells it) a symbol
+/// has in a trading day: one per interval of the extended session, which
+/// only the most liquid symbols fill
+pub fn bars_per_day(timeframe: &str) -> Result<f64> {
+    let invalid = || Error::Invalid(format!("Invalid timeframe: {}", timeframe));
+    let split = timeframe
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = timeframe.split_at(split);
+    let amount: f64 = amount.parse().map_err(|_| invalid())?;
+    Ok(match unit {
+        "Min" => EXTENDED_SESSION_MINUTES / amount,
+        "Hour" => EXTENDED_SESSION_MINUTES / 60.0 / amount,
+        "Day" => 1.0 / amount,
+        "Week" => 1.0 / 5.0 / amount,
+        "Month" => 1.0 / 21.0 / amount,
+        _ => return Err(invalid()),
+    })
+}
+
+/// Weekdays from `from` to `to`, counting part days in proportion; market
+/// holidays are not known without the calendar, so they count too
+pub fn weekdays(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    let mut days = 0.0;
+    let mut date = from.date_naive();
+    while date.and_time(NaiveTime::MIN).and_utc() < to {
+        let day_start = date.and_time(NaiveTime::MIN).and_utc();
+        let day_end = day_start + Duration::days(1);
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            let covered = to.min(day_end) - from.max(day_start);
+            days += covered.num_seconds() as f64 / 86_400.0;
+        }
+        date = date.succ_opt().expect("date in range");
+    }
+    days
+}
+
+/// The bars of `timeframe` from `from` to `to`, at most, and the requests
+/// that fetch them `page_size` at a time; an empty range still takes one
+pub fn estimate_range(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    timeframe: &str,
+    page_size: u32,
+) -> Result<Estimate> {
+    let bars = (weekdays(from, to) * bars_per_day(timeframe)?).ceil() as u64;
+    Ok(Estimate {
+        requests: bars.div_ceil(page_size.max(1) as u64).max(1),
+        bars,
+    })
+}
+
+/// How long `requests` take at `per_minute`: the first minute's worth are
+/// sent at once, the rest as the budget refills
+pub fn rate_limited_duration(requests: u64, per_minute: u32) -> std::time::Duration {
+    let per_minute = per_minute.max(1) as u64;
+    let waiting = requests.saturating_sub(per_minute);
+    std::time::Duration::from_secs_f64(waiting as f64 * 60.0 / per_minute as f64)
+}
@@ -0,0 +1,64 @@
+//! The CSV layouts backtesting tools read bars in, for `--export-profile`.
+//! None has a symbol column, so each symbol goes in its own file.
+
+use crate::market_time::eastern_offset;
+use crate::resample::OhlcvBar;
+use chrono::NaiveTime;
+
+/// A backtesting tool's CSV layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportProfile {
+    /// backtrader's `GenericCSVData` defaults: `datetime` as
+    /// `%Y-%m-%d %H:%M:%S` in New York time, then OHLCV and open interest
+    Backtrader,
+    /// zipline's `csvdir` bundle: `date`, OHLCV, `dividend` and `split`, in
+    /// files named `<SYMBOL>.csv`. Daily bars are dated in New York,
+    /// intraday bars timestamped in UTC.
+    Zipline,
+    /// MetaTrader's history import: no header, `YYYY.MM.DD,HH:MM` in New
+    /// York time, then OHLC and volume
+    Metatrader,
+}
+
+impl ExportProfile {
+    /// The header line, if the layout has one
+    pub fn header(&self) -> Option<&'static str> {
+        match self {
+            ExportProfile::Backtrader => Some("datetime,open,high,low,close,volume,openinterest"),
+            ExportProfile::Zipline => Some("date,open,high,low,close,volume,dividend,split"),
+            ExportProfile::Metatrader => None,
+        }
+    }
+
+    /// `bar` as a line of the layout
+    pub fn format(&self, bar: &OhlcvBar) -> String {
+        let local = bar
+            .timestamp
+            .with_timezone(&eastern_offset(bar.timestamp.date_naive()));
+        let prices = format!("{},{},{},{}", bar.open, bar.high, bar.low, bar.close);
+        match self {
+            ExportProfile::Backtrader => format!(
+                "{},{},{},0",
+                local.format("%Y-%m-%d %H:%M:%S"),
+                prices,
+                bar.volume
+            ),
+            ExportProfile::Zipline => {
+                let date = if local.time() == NaiveTime::MIN {
+                    local.format("%Y-%m-%d").to_string()
+                } else {
+                    bar.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+                };
+                format!("{},{},{},0.0,1.0", date, prices, bar.volume)
+            }
+            ExportProfile::Metatrader => {
+                format!(
+                    "{},{},{}",
+                    local.format("%Y.%m.%d,%H:%M"),
+                    prices,
+                    bar.volume
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Comparison of the bars two data feeds report for the same symbol and
+//! range, such as IEX against SIP, for `historical compare-feeds`.
+
+use crate::resample::OhlcvBar;
+use crate::{DataFormat, Result};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+/// CSV header of the rows `Discrepancy::format` writes
+pub const DISCREPANCIES_CSV_HEADER: &str = "symbol,timestamp,issue,field,feed,reference,difference";
+
+/// How a bar of the feed differs from the reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Issue {
+    /// The reference has a bar the feed lacks
+    Missing,
+    /// The feed has a bar the reference lacks
+    Extra,
+    /// A price differs by more than the tolerance
+    Price,
+}
+
+impl Issue {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Issue::Missing => "missing",
+            Issue::Extra => "extra",
+            Issue::Price => "price",
+        }
+    }
+}
+
+/// One difference between the feeds. Missing and extra bars carry the
+/// volume of the bar that exists, price differences the prices and how far
+/// apart they are as a fraction of the reference price.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Discrepancy {
+    pub symbol: String,
+    pub timestamp: DateTime<Utc>,
+    pub issue: Issue,
+    pub field: String,
+    pub feed: Option<f64>,
+    pub reference: Option<f64>,
+    pub difference: Option<f64>,
+}
+
+impl Discrepancy {
+    /// The discrepancy as a line in `format`, matching
+    /// `DISCREPANCIES_CSV_HEADER` for CSV
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let timestamp = self
+            .timestamp
+            .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+        let value = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+        Ok(match format {
+            DataFormat::Plain => match self.issue {
+                Issue::Missing => format!(
+                    "{} {}: missing from the feed (reference volume {})",
+                    self.symbol,
+                    timestamp,
+                    value(self.reference)
+                ),
+                Issue::Extra => format!(
+                    "{} {}: not in the reference (feed volume {})",
+                    self.symbol,
+                    timestamp,
+                    value(self.feed)
+                ),
+                Issue::Price => format!(
+                    "{} {}: {} {} vs {} ({:.3}%)",
+                    self.symbol,
+                    timestamp,
+                    self.field,
+                    value(self.feed),
+                    value(self.reference),
+                    self.difference.unwrap_or_default() * 100.0
+                ),
+            },
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{}",
+                self.symbol,
+                timestamp,
+                self.issue.as_str(),
+                self.field,
+                value(self.feed),
+                value(self.reference),
+                value(self.difference)
+            ),
+        })
+    }
+}
+
+/// How a symbol's bars on the feed compare to the reference
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FeedComparison {
+    pub symbol: String,
+    pub feed_bars: usize,
+    pub reference_bars: usize,
+    /// Reference bars the feed lacks
+    pub missing: usize,
+    /// Feed bars the reference lacks
+    pub extra: usize,
+    /// Bars in both with a price beyond the tolerance
+    pub price_mismatches: usize,
+    /// Largest price difference of the bars in both, as a fraction of the
+    /// reference price
+    pub max_difference: f64,
+    pub feed_volume: u64,
+    pub reference_volume: u64,
+    /// Median of feed volume over reference volume, of the bars in both with
+    /// reference volume
+    pub median_volume_ratio: Option<f64>,
+}
+
+impl FeedComparison {
+    /// Total feed volume over total reference volume
+    pub fn volume_ratio(&self) -> Option<f64> {
+        (self.reference_volume > 0).then(|| self.feed_volume as f64 / self.reference_volume as f64)
+    }
+}
+
+impl std::fmt::Display for FeedComparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |ratio: Option<f64>| match ratio {
+            Some(ratio) => format!("{:.1}%", ratio * 100.0),
+            None => "-".to_string(),
+        };
+        let coverage = (self.reference_bars > 0)
+            .then(|| (self.reference_bars - self.missing) as f64 / self.reference_bars as f64);
+        write!(
+            f,
+            "{}: {}/{} reference bars ({}), {} extra, {} with prices off (max {:.3}%), volume {} of reference (median bar {})",
+            self.symbol,
+            self.reference_bars - self.missing,
+            self.reference_bars,
+            percent(coverage),
+            self.extra,
+            self.price_mismatches,
+            self.max_difference * 100.0,
+            percent(self.volume_ratio()),
+            percent(self.median_volume_ratio)
+        )
+    }
+}
+
+/// Compare the bars of `feed` to those of `reference`, matching them by
+/// timestamp. Prices are compared field by field; any of open, high, low or
+/// close more than `tolerance` (a fraction of the reference price) away is a
+/// discrepancy. Discrepancies come in timestamp order.
+pub fn compare_feeds(
+    symbol: &str,
+    feed: &[OhlcvBar],
+    reference: &[OhlcvBar],
+    tolerance: f64,
+) -> (FeedComparison, Vec<Discrepancy>) {
+    let reference_bars: HashMap<DateTime<Utc>, &OhlcvBar> =
+        reference.iter().map(|bar| (bar.timestamp, bar)).collect();
+    let mut timestamps: BTreeMap<DateTime<Utc>, (Option<&OhlcvBar>, Option<&OhlcvBar>)> =
+        BTreeMap::new();
+    for bar in feed {
+        timestamps.entry(bar.timestamp).or_default().0 = Some(bar);
+    }
+    for (timestamp, bar) in &reference_bars {
+        timestamps.entry(*timestamp).or_default().1 = Some(bar);
+    }
+
+    let mut comparison = FeedComparison {
+        symbol: symbol.to_string(),
+        feed_bars: feed.len(),
+        reference_bars: reference_bars.len(),
+        missing: 0,
+        extra: 0,
+        price_mismatches: 0,
+        max_difference: 0.0,
+        feed_volume: feed.iter().map(|bar| bar.volume).sum(),
+        reference_volume: reference.iter().map(|bar| bar.volume).sum(),
+        median_volume_ratio: None,
+    };
+    let discrepancy =
+        |timestamp: DateTime<Utc>, issue, field: &str, feed, reference, difference| Discrepancy {
+            symbol: symbol.to_string(),
+            timestamp,
+            issue,
+            field: field.to_string(),
+            feed,
+            reference,
+            difference,
+        };
+    let mut discrepancies = Vec::new();
+    let mut volume_ratios = Vec::new();
+    for (timestamp, bars) in timestamps {
+        match bars {
+            (None, Some(reference)) => {
+                comparison.missing += 1;
+                discrepancies.push(discrepancy(
+                    timestamp,
+                    Issue::Missing,
+                    "volume",
+                    None,
+                    Some(reference.volume as f64),
+                    None,
+                ));
+            }
+            (Some(feed), None) => {
+                comparison.extra += 1;
+                discrepancies.push(discrepancy(
+                    timestamp,
+                    Issue::Extra,
+                    "volume",
+                    Some(feed.volume as f64),
+                    None,
+                    None,
+                ));
+            }
+            (Some(feed), Some(reference)) => {
+                let mut mismatched = false;
+                for (field, feed_price, reference_price) in [
+                    ("open", feed.open, reference.open),
+                    ("high", feed.high, reference.high),
+                    ("low", feed.low, reference.low),
+                    ("close", feed.close, reference.close),
+                ] {
+                    if reference_price == 0.0 {
+                        continue;
+                    }
+                    let difference = (feed_price - reference_price).abs() / reference_price.abs();
+                    comparison.max_difference = comparison.max_difference.max(difference);
+                    if difference > tolerance {
+                        mismatched = true;
+                        discrepancies.push(discrepancy(
+                            timestamp,
+                            Issue::Price,
+                            field,
+                            Some(feed_price),
+                            Some(reference_price),
+                            Some(difference),
+                        ));
+                    }
+                }
+                comparison.price_mismatches += mismatched as usize;
+                if reference.volume > 0 {
+                    volume_ratios.push(feed.volume as f64 / reference.volume as f64);
+                }
+            }
+            (None, None) => unreachable!("every timestamp comes from a bar"),
+        }
+    }
+    comparison.median_volume_ratio = median(&mut volume_ratios);
+    (comparison, discrepancies)
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    })
+}
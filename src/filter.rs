@@ -0,0 +1,414 @@
+use crate::dates::{DateResolver, RangeEnd};
+use crate::market_time::{eastern_offset, new_york_date};
+use crate::{Error, Result, StreamingData};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use std::cmp::Ordering;
+
+/// Friendly names for payload fields; any other name is looked up in the
+/// payload as-is (e.g. `bp`, `c`)
+const FIELD_ALIASES: &[(&str, &str)] = &[
+    ("price", "p"),
+    ("size", "s"),
+    ("exchange", "x"),
+    ("id", "i"),
+    ("conditions", "c"),
+    ("bid", "bp"),
+    ("bid_size", "bs"),
+    ("ask", "ap"),
+    ("ask_size", "as"),
+    ("open", "o"),
+    ("high", "h"),
+    ("low", "l"),
+    ("close", "c"),
+    ("volume", "v"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        field: String,
+        op: Op,
+        value: Literal,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/// Boolean expression over decoded messages, such as
+/// `type==t && price>100 && size>=500`.
+///
+/// Comparisons take a field on the left and a number or text on the right;
+/// combine them with `&&`, `||`, `!` and parentheses. Fields are `type`
+/// (`t`/`trade`, `q`/`quote`, `b`/`bar`, ...), `symbol`, `spread` (ask minus
+/// bid), the names in `FIELD_ALIASES`, or any raw payload key. A comparison
+/// against a field the message does not have is false.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageFilter {
+    source: String,
+    expr: Expr,
+}
+
+impl std::fmt::Display for MessageFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl std::str::FromStr for MessageFilter {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expr = parser.or().map_err(|e| invalid(source, &e.to_string()))?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(invalid(source, &format!("unexpected {:?}", token)));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+}
+
+impl MessageFilter {
+    pub fn matches(&self, data: &StreamingData) -> bool {
+        evaluate(&self.expr, data)
+    }
+}
+
+/// One end of a time window: a time of day in New York, which applies to
+/// every day, or an instant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBound {
+    TimeOfDay(NaiveTime),
+    At(DateTime<Utc>),
+}
+
+impl TimeBound {
+    /// Parse `HH:MM[:SS]` as a time of day, or anything `DateResolver`
+    /// takes as the `end` of a range
+    pub fn parse(value: &str, end: RangeEnd, resolver: &DateResolver) -> Result<Self> {
+        for format in ["%H:%M", "%H:%M:%S"] {
+            if let Ok(time) = NaiveTime::parse_from_str(value.trim(), format) {
+                return Ok(TimeBound::TimeOfDay(time));
+            }
+        }
+        let resolved = resolver.resolve(value, end)?;
+        let at = match NaiveDate::parse_from_str(&resolved, "%Y-%m-%d") {
+            Ok(day) => eastern_offset(day)
+                .from_local_datetime(&day.and_time(NaiveTime::MIN))
+                .single()
+                .expect("a fixed offset has one local time")
+                .with_timezone(&Utc),
+            Err(_) => resolved
+                .parse()
+                .map_err(|_| Error::Invalid(format!("Invalid time: {}", value)))?,
+        };
+        Ok(TimeBound::At(at))
+    }
+
+    /// Whether `time` is at or after the bound
+    fn reached(&self, time: DateTime<Utc>) -> bool {
+        match self {
+            TimeBound::TimeOfDay(bound) => {
+                time.with_timezone(&eastern_offset(new_york_date(time)))
+                    .time()
+                    >= *bound
+            }
+            TimeBound::At(bound) => time >= *bound,
+        }
+    }
+}
+
+/// Messages of some symbols and types in a time window, timed by event time
+/// when they have one. `from` is inclusive and `to` exclusive; empty lists
+/// and missing bounds select everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageSelection {
+    /// Upper case symbols
+    pub symbols: Vec<String>,
+    /// Message type codes such as `t` or `q`
+    pub types: Vec<String>,
+    pub from: Option<TimeBound>,
+    pub to: Option<TimeBound>,
+}
+
+impl MessageSelection {
+    /// Select `symbols` and `types`, the latter as codes or names such as
+    /// `quotes`
+    pub fn new(symbols: &[String], types: &[String]) -> Self {
+        Self {
+            symbols: symbols
+                .iter()
+                .map(|symbol| symbol.trim().to_uppercase())
+                .collect(),
+            types: types
+                .iter()
+                .map(|name| message_type_code(&name.trim().to_lowercase()).to_string())
+                .collect(),
+            from: None,
+            to: None,
+        }
+    }
+
+    /// Whether every message is selected
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn matches(&self, data: &StreamingData) -> bool {
+        if !self.types.is_empty() && !self.types.contains(&data.message_type) {
+            return false;
+        }
+        if !self.symbols.is_empty()
+            && !data
+                .symbol
+                .as_ref()
+                .is_some_and(|symbol| self.symbols.contains(symbol))
+        {
+            return false;
+        }
+        let time = data.event_time.unwrap_or(data.received_time);
+        self.from.is_none_or(|from| from.reached(time))
+            && self.to.is_none_or(|to| !to.reached(time))
+    }
+}
+
+fn invalid(source: &str, reason: &str) -> Error {
+    Error::Invalid(format!("Invalid filter: {} ({})", source, reason))
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, width) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('"', _) | ('\'', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&ch| ch == c)
+                    .ok_or_else(|| invalid(source, "unterminated string"))?;
+                let text: String = chars[i + 1..i + 1 + end].iter().collect();
+                (Token::Text(text), end + 2)
+            }
+            _ => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|&ch| ch.is_whitespace() || "&|=!<>()\"'".contains(ch))
+                    .unwrap_or(chars.len() - i);
+                if end == 0 {
+                    return Err(invalid(source, &format!("unexpected '{}'", c)));
+                }
+                (Token::Word(chars[i..i + end].iter().collect()), end)
+            }
+        };
+        tokens.push(token);
+        i += width;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(Error::Invalid("missing ')'".to_string())),
+                }
+            }
+            Some(Token::Word(field)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(Error::Invalid(format!("expected a comparison after '{}'", field))),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(word)) => match word.parse::<f64>() {
+                        Ok(number) => Literal::Number(number),
+                        Err(_) => Literal::Text(word),
+                    },
+                    Some(Token::Text(text)) => Literal::Text(text),
+                    _ => return Err(Error::Invalid(format!("expected a value after '{}'", field))),
+                };
+                Ok(Expr::Compare { field, op, value })
+            }
+            token => Err(Error::Invalid(format!("expected a comparison, found {:?}", token))),
+        }
+    }
+}
+
+fn evaluate(expr: &Expr, data: &StreamingData) -> bool {
+    match expr {
+        Expr::Or(left, right) => evaluate(left, data) || evaluate(right, data),
+        Expr::And(left, right) => evaluate(left, data) && evaluate(right, data),
+        Expr::Not(inner) => !evaluate(inner, data),
+        Expr::Compare { field, op, value } => compare(data, field, *op, value),
+    }
+}
+
+fn compare(data: &StreamingData, field: &str, op: Op, value: &Literal) -> bool {
+    let actual = match field {
+        "type" => serde_json::Value::String(data.message_type.clone()),
+        "symbol" => match &data.symbol {
+            Some(symbol) => serde_json::Value::String(symbol.clone()),
+            None => return false,
+        },
+        "spread" => match (data.data["ap"].as_f64(), data.data["bp"].as_f64()) {
+            (Some(ask), Some(bid)) => serde_json::json!(ask - bid),
+            _ => return false,
+        },
+        _ => {
+            let key = FIELD_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == field)
+                .map_or(field, |(_, key)| *key);
+            match data.data.get(key).or_else(|| data.data.get(field)) {
+                Some(actual) => actual.clone(),
+                None => return false,
+            }
+        }
+    };
+    let value = match (field, value) {
+        ("type", Literal::Text(text)) => Literal::Text(message_type_code(text).to_string()),
+        ("symbol", Literal::Text(text)) => Literal::Text(text.to_uppercase()),
+        _ => value.clone(),
+    };
+    // Lists such as trade conditions match when any element does
+    if let serde_json::Value::Array(items) = &actual {
+        let any = items.iter().any(|item| compare_value(item, Op::Eq, &value));
+        return match op {
+            Op::Ne => !any,
+            _ => any,
+        };
+    }
+    compare_value(&actual, op, &value)
+}
+
+fn compare_value(actual: &serde_json::Value, op: Op, value: &Literal) -> bool {
+    let ordering = match value {
+        Literal::Number(number) => {
+            // Trade update prices arrive as strings
+            let actual = actual
+                .as_f64()
+                .or_else(|| actual.as_str().and_then(|s| s.parse().ok()));
+            match actual.and_then(|actual| actual.partial_cmp(number)) {
+                Some(ordering) => ordering,
+                None => return false,
+            }
+        }
+        Literal::Text(text) => match actual {
+            serde_json::Value::String(actual) => actual.as_str().cmp(text.as_str()),
+            other => other.to_string().cmp(text),
+        },
+    };
+    match op {
+        Op::Eq => ordering == Ordering::Equal,
+        Op::Ne => ordering != Ordering::Equal,
+        Op::Gt => ordering == Ordering::Greater,
+        Op::Ge => ordering != Ordering::Less,
+        Op::Lt => ordering == Ordering::Less,
+        Op::Le => ordering != Ordering::Greater,
+    }
+}
+
+pub(crate) fn message_type_code(name: &str) -> &str {
+    match name {
+        "trade" | "trades" => "t",
+        "quote" | "quotes" => "q",
+        "bar" | "bars" => "b",
+        "daily_bar" | "daily_bars" => "d",
+        "updated_bar" | "updated_bars" => "u",
+        "status" | "statuses" => "s",
+        "luld" | "lulds" => "l",
+        "imbalance" | "imbalances" => "i",
+        "correction" | "corrections" => "c",
+        "cancel" | "cancels" => "x",
+        other => other,
+    }
+}
@@ -0,0 +1,61 @@
+//! Reading a file that is still being written, like `tail -f`, for
+//! `analyze --follow` on a live capture.
+
+use crate::compression::Compression;
+use crate::{Error, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Reads the complete lines of a growing file from its start. A line is
+/// only returned once its newline is written, so a writer caught mid-line
+/// is never seen. When the file shrinks, e.g. because it was truncated or
+/// replaced by a new one, it is read again from the start.
+#[derive(Debug)]
+pub struct FollowReader {
+    path: PathBuf,
+    reader: BufReader<File>,
+    /// Bytes read so far
+    position: u64,
+    /// The start of a line whose newline is not written yet
+    pending: String,
+}
+
+impl FollowReader {
+    /// Fails for compressed files, which cannot be read before they are
+    /// complete
+    pub fn open(path: &Path) -> Result<Self> {
+        if Compression::from_extension(path) != Compression::None {
+            return Err(Error::Invalid(format!(
+                "Cannot follow compressed file {}",
+                path.display()
+            )));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader: BufReader::new(File::open(path)?),
+            position: 0,
+            pending: String::new(),
+        })
+    }
+
+    /// The next complete line, without its line ending, or `None` if no
+    /// complete line has been written yet
+    pub fn next_line(&mut self) -> Result<Option<String>> {
+        let read = self.reader.read_line(&mut self.pending)?;
+        self.position += read as u64;
+        if read == 0 {
+            if std::fs::metadata(&self.path)?.len() < self.position {
+                self.reader = BufReader::new(File::open(&self.path)?);
+                self.position = 0;
+                self.pending.clear();
+            }
+            return Ok(None);
+        }
+        if !self.pending.ends_with('\n') {
+            return Ok(None);
+        }
+        let line = std::mem::take(&mut self.pending);
+        Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+}
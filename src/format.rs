@@ -0,0 +1,499 @@
+//! Records written to the outputs: decoding raw stream messages into
+//! `StreamingData` and rendering them as plain text or CSV.
+
+use crate::portfolio::{PortfolioTotals, PositionSnapshot};
+use crate::{
+    LuldBand, OrderImbalance, OutputMode, Result, SymbolSessionStats, TradeCancel, TradeCorrection,
+    TradeUpdate, TradingStatus,
+};
+use alpaca_trading_api_rust::{StreamingBar, StreamingMessage, StreamingQuote, StreamingTrade};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use csv::Writer;
+use std::io::Write;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum DataFormat {
+    /// Plain text format (default)
+    Plain,
+    /// JSON format for structured data
+    Json,
+    /// CSV format for spreadsheet compatibility
+    Csv,
+}
+
+impl Default for DataFormat {
+    fn default() -> Self {
+        DataFormat::Plain
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StreamingData {
+    /// When the client received the message
+    #[serde(alias = "timestamp")]
+    pub received_time: DateTime<Utc>,
+    /// Exchange timestamp of a trade, quote or bar, or the event time of an
+    /// order update; `None` for status messages
+    #[serde(default)]
+    pub event_time: Option<DateTime<Utc>>,
+    pub message_type: String,
+    pub symbol: Option<String>,
+    pub data: serde_json::Value,
+}
+
+impl OutputMode {
+    /// The line `DataFormat::Plain` writes for `data`
+    pub fn format_plain(&self, data: &StreamingData) -> String {
+        let (OutputMode::Console { templates, .. } | OutputMode::File { templates, .. }) = self;
+        if let Some(line) = templates.as_ref().and_then(|templates| templates.render(data)) {
+            return line;
+        }
+        match data.message_type.as_str() {
+            "t" => {
+                if let Ok(trade) = serde_json::from_value::<StreamingTrade>(data.data.clone()) {
+                    format!("🔄 Trade: {} - ${:.2} x {} @ {} (Exchange: {}, ID: {}){}", 
+                        trade.symbol, trade.price, trade.size, 
+                        trade.timestamp, trade.exchange, trade.id, format_received(data))
+                } else {
+                    format!("🔄 Trade: {}", data.data)
+                }
+            }
+            "q" => {
+                if let Ok(quote) = serde_json::from_value::<StreamingQuote>(data.data.clone()) {
+                    let spread = quote.ask_price - quote.bid_price;
+                    format!("💰 Quote: {} - Bid: ${:.2} x {} | Ask: ${:.2} x {} | Spread: ${:.2} @ {}{}", 
+                        quote.symbol, quote.bid_price, quote.bid_size, 
+                        quote.ask_price, quote.ask_size, spread, quote.timestamp, format_received(data))
+                } else {
+                    format!("💰 Quote: {}", data.data)
+                }
+            }
+            "b" | "d" | "u" => {
+                let label = match data.message_type.as_str() {
+                    "d" => "📅 Daily bar",
+                    "u" => "🔄 Updated bar",
+                    _ => "📈 Bar",
+                };
+                if let Ok(bar) = serde_json::from_value::<StreamingBar>(data.data.clone()) {
+                    let change = bar.close - bar.open;
+                    let change_pct = (change / bar.open) * 100.0;
+                    format!("{}: {} - O: ${:.2} H: ${:.2} L: ${:.2} C: ${:.2} V: {} | Change: ${:.2} ({:.2}%) @ {}{}", 
+                        label, bar.symbol, bar.open, bar.high, bar.low, 
+                        bar.close, bar.volume, change, change_pct, bar.timestamp, format_received(data))
+                } else {
+                    format!("{}: {}", label, data.data)
+                }
+            }
+            "c" => {
+                if let Ok(correction) = serde_json::from_value::<TradeCorrection>(data.data.clone()) {
+                    format!("✏️  Correction: {} - ${:.2} x {} -> ${:.2} x {} @ {} (Exchange: {}, ID: {}){}",
+                        correction.symbol, correction.original_price, correction.original_size,
+                        correction.corrected_price, correction.corrected_size,
+                        correction.timestamp, correction.exchange, correction.original_id, format_received(data))
+                } else {
+                    format!("✏️  Correction: {}", data.data)
+                }
+            }
+            "x" => {
+                if let Ok(cancel) = serde_json::from_value::<TradeCancel>(data.data.clone()) {
+                    format!("🚫 Trade {}: {} - ${:.2} x {} @ {} (Exchange: {}, ID: {}){}",
+                        if cancel.is_error() { "error" } else { "cancel" },
+                        cancel.symbol, cancel.price, cancel.size,
+                        cancel.timestamp, cancel.exchange, cancel.id, format_received(data))
+                } else {
+                    format!("🚫 Trade cancel: {}", data.data)
+                }
+            }
+            "l" => {
+                if let Ok(band) = serde_json::from_value::<LuldBand>(data.data.clone()) {
+                    format!("🚦 LULD: {} - Up: ${:.2} | Down: ${:.2}{} @ {}{}",
+                        band.symbol, band.limit_up, band.limit_down,
+                        if band.indicator.trim().is_empty() { String::new() } else { format!(" (Indicator: {})", band.indicator) },
+                        band.timestamp, format_received(data))
+                } else {
+                    format!("🚦 LULD: {}", data.data)
+                }
+            }
+            "i" => {
+                if let Ok(imbalance) = serde_json::from_value::<OrderImbalance>(data.data.clone()) {
+                    format!("⚖️  Imbalance: {} - ${:.2} @ {}{}",
+                        imbalance.symbol, imbalance.price, imbalance.timestamp, format_received(data))
+                } else {
+                    format!("⚖️  Imbalance: {}", data.data)
+                }
+            }
+            "s" => {
+                if let Ok(status) = serde_json::from_value::<TradingStatus>(data.data.clone()) {
+                    let label = if status.is_halt() {
+                        "🛑 HALTED"
+                    } else if status.is_resume() {
+                        "✅ RESUMED"
+                    } else {
+                        "ℹ️  Status"
+                    };
+                    format!("{}: {} - {} @ {}{}", label, status.symbol, status.description(),
+                        status.timestamp, format_received(data))
+                } else {
+                    format!("ℹ️  Status: {}", data.data)
+                }
+            }
+            "trade_update" => {
+                if let Ok(update) = serde_json::from_value::<TradeUpdate>(data.data.clone()) {
+                    self.format_trade_update(&update)
+                } else {
+                    format!("📝 Order update: {}", data.data)
+                }
+            }
+            "stats" => {
+                if let Ok(stats) = serde_json::from_value::<SymbolSessionStats>(data.data.clone()) {
+                    format!("📊 {}: {} - VWAP: ${:.2} | Vol: {} | Trades: {} | H: ${:.2} L: ${:.2} | Last: ${:.2}",
+                        if stats.is_final { "Session summary" } else { "Stats" },
+                        stats.symbol, stats.vwap, stats.volume, stats.trades,
+                        stats.high, stats.low, stats.last)
+                } else {
+                    format!("📊 Stats: {}", data.data)
+                }
+            }
+            "position" => {
+                if let Ok(position) = serde_json::from_value::<PositionSnapshot>(data.data.clone()) {
+                    format!("💼 {}: {} {} @ ${:.2} | Last: {} | Value: ${:.2} | Unrealized: ${:.2} | Realized: ${:.2}",
+                        if position.is_final { "Final position" } else { "Position" },
+                        position.symbol, position.quantity, position.average_cost,
+                        position.last_price.map_or("-".to_string(), |price| format!("${:.2}", price)),
+                        position.market_value, position.unrealized_pnl, position.realized_pnl)
+                } else {
+                    format!("💼 Position: {}", data.data)
+                }
+            }
+            "portfolio" => {
+                if let Ok(totals) = serde_json::from_value::<PortfolioTotals>(data.data.clone()) {
+                    format!("💰 {}: {} positions | Value: ${:.2} | Unrealized: ${:.2} | Realized: ${:.2} | P&L: ${:.2}",
+                        if totals.is_final { "Final portfolio" } else { "Portfolio" },
+                        totals.positions, totals.market_value, totals.unrealized_pnl,
+                        totals.realized_pnl, totals.total_pnl)
+                } else {
+                    format!("💰 Portfolio: {}", data.data)
+                }
+            }
+            "alert" => format!("🚨 Alert: {} - {}",
+                data.symbol.as_deref().unwrap_or("-"),
+                data.data["message"].as_str().unwrap_or("?")),
+            "control" => format!("🎛️  Control: {} {} {} (active: {})",
+                data.data["action"].as_str().unwrap_or("?"),
+                data.data["channel"].as_str().unwrap_or("?"),
+                json_symbol_list(&data.data["symbols"]),
+                json_symbol_list(&data.data["active"])),
+            "disconnect" => match data.data["retry_in_secs"].as_u64() {
+                Some(secs) => format!("⚠️  Disconnected (attempt {}): {} - retrying in {}s",
+                    attempt_label(&data.data),
+                    data.data["error"].as_str().unwrap_or("?"), secs),
+                None => format!("⛔ Disconnected: {} - giving up after {} attempts",
+                    data.data["error"].as_str().unwrap_or("?"), data.data["attempt"]),
+            },
+            "reconnect" => format!("🔄 Reconnected (attempt {}) after: {}",
+                data.data["attempt"], data.data["reason"].as_str().unwrap_or("?")),
+            "failover" => format!("🔀 Feed failover: {} -> {} ({})",
+                data.data["from"].as_str().unwrap_or("?"),
+                data.data["to"].as_str().unwrap_or("?"),
+                data.data["reason"].as_str().unwrap_or("?")),
+            "market" => format!("{} until {}",
+                if data.data["open"].as_bool() == Some(true) { "🔔 Market session open" } else { "💤 Market closed" },
+                data.data["until"].as_str().unwrap_or("?")),
+            "stale_connection" => format!("🥶 Stale connection: no messages for {}s - reconnecting",
+                data.data["silent_secs"]),
+            "summary" => format!("📋 Session summary: {} messages over {} symbols in {:.0}s ({} disconnects, {} reconnects, {} dropped)",
+                data.data["messages"], data.data["by_symbol"].as_object().map_or(0, |symbols| symbols.len()),
+                data.data["duration_secs"].as_f64().unwrap_or(0.0),
+                data.data["disconnects"], data.data["reconnects"], data.data["dropped"]),
+            "success" => format!("✅ Success: {}", data.data),
+            "subscription" => format!("📡 Subscription: {}", data.data),
+            "error" => format!("❌ Error: {}", data.data),
+            _ => format!("❓ Unknown: {} - {}", data.message_type, data.data),
+        }
+    }
+    
+    fn format_trade_update(&self, update: &TradeUpdate) -> String {
+        let order = &update.order;
+        let price = update.price.as_deref().unwrap_or("-");
+        let qty = update.qty.as_deref().unwrap_or("-");
+        let filled = order.filled_qty.as_deref().unwrap_or("0");
+        let ordered = order.qty.as_deref().unwrap_or("-");
+        match update.event.as_str() {
+            "fill" => format!("✅ Fill: {} {} {} @ ${} (filled {}/{}, order {})",
+                order.symbol, order.side, qty, price, filled, ordered, order.id),
+            "partial_fill" => format!("🧩 Partial fill: {} {} {} @ ${} (filled {}/{}, order {})",
+                order.symbol, order.side, qty, price, filled, ordered, order.id),
+            "canceled" => format!("🚫 Canceled: {} {} {} (filled {}, order {})",
+                order.symbol, order.side, ordered, filled, order.id),
+            event => format!("📝 Order {}: {} {} {} (order {})",
+                event, order.symbol, order.side, ordered, order.id),
+        }
+    }
+    
+    pub(crate) fn format_csv_line(&self, data: &StreamingData) -> String {
+        format!("{},{},{},{},{}", 
+            data.received_time.format("%Y-%m-%d %H:%M:%S%.3f UTC"),
+            data.message_type,
+            data.symbol.as_deref().unwrap_or(""),
+            data.data.to_string().replace(",", ";"),
+            format_csv_time(data.event_time))
+    }
+    
+    // event_time goes last so readers of the older four-column layout keep working
+    pub(crate) fn write_csv_record<W: Write>(&self, writer: &mut Writer<W>, data: &StreamingData) -> Result<()> {
+        writer.write_record(&[
+            data.received_time.format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string(),
+            data.message_type.clone(),
+            data.symbol.as_deref().unwrap_or("").to_string(),
+            data.data.to_string(),
+            format_csv_time(data.event_time),
+        ])?;
+        Ok(())
+    }
+}
+
+/// Turn a raw stream message into the record written to the outputs. Returns
+/// `None` for messages that fail to parse (logged) or carry no text.
+pub fn decode_streaming_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    match message.message_type.as_str() {
+        "t" => decode_trade_message(message),
+        "q" => decode_quote_message(message),
+        "b" | "d" | "u" => decode_bar_message(message),
+        "c" => decode_correction_message(message),
+        "x" => decode_cancel_message(message),
+        "s" => decode_trading_status_message(message),
+        "l" => decode_luld_message(message),
+        "i" => decode_imbalance_message(message),
+        "trade_update" => Ok(decode_trade_update_message(message)),
+        "success" | "subscription" | "error" => Ok(decode_status_message(message)),
+        _ => Ok(Some(decode_unknown_message(message))),
+    }
+}
+
+fn decode_trade_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    let message_json = serde_json::to_value(message)?;
+    match serde_json::from_value::<StreamingTrade>(message_json.clone()) {
+        Ok(trade) => Ok(Some(StreamingData {
+            received_time: Utc::now(),
+            event_time: parse_event_time(&trade.timestamp.to_string()),
+            message_type: "t".to_string(),
+            symbol: Some(trade.symbol.clone()),
+            data: message_json,
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse trade");
+            Ok(None)
+        }
+    }
+}
+
+fn decode_correction_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    let message_json = serde_json::to_value(message)?;
+    match serde_json::from_value::<TradeCorrection>(message_json.clone()) {
+        Ok(correction) => Ok(Some(StreamingData {
+            received_time: Utc::now(),
+            event_time: parse_event_time(&correction.timestamp),
+            message_type: "c".to_string(),
+            symbol: Some(correction.symbol),
+            data: message_json,
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse trade correction");
+            Ok(None)
+        }
+    }
+}
+
+fn decode_cancel_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    let message_json = serde_json::to_value(message)?;
+    match serde_json::from_value::<TradeCancel>(message_json.clone()) {
+        Ok(cancel) => Ok(Some(StreamingData {
+            received_time: Utc::now(),
+            event_time: parse_event_time(&cancel.timestamp),
+            message_type: "x".to_string(),
+            symbol: Some(cancel.symbol),
+            data: message_json,
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse trade cancel");
+            Ok(None)
+        }
+    }
+}
+
+fn decode_trading_status_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    let message_json = serde_json::to_value(message)?;
+    match serde_json::from_value::<TradingStatus>(message_json.clone()) {
+        Ok(status) => {
+            if status.is_halt() {
+                warn!(symbol = %status.symbol, status = %status.description(), "Trading halted");
+            } else if status.is_resume() {
+                info!(symbol = %status.symbol, status = %status.description(), "Trading resumed");
+            }
+            Ok(Some(StreamingData {
+                received_time: Utc::now(),
+                event_time: parse_event_time(&status.timestamp),
+                message_type: "s".to_string(),
+                symbol: Some(status.symbol),
+                data: message_json,
+            }))
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to parse trading status");
+            Ok(None)
+        }
+    }
+}
+
+fn decode_luld_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    let message_json = serde_json::to_value(message)?;
+    match serde_json::from_value::<LuldBand>(message_json.clone()) {
+        Ok(band) => Ok(Some(StreamingData {
+            received_time: Utc::now(),
+            event_time: parse_event_time(&band.timestamp),
+            message_type: "l".to_string(),
+            symbol: Some(band.symbol),
+            data: message_json,
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse LULD band");
+            Ok(None)
+        }
+    }
+}
+
+fn decode_imbalance_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    let message_json = serde_json::to_value(message)?;
+    match serde_json::from_value::<OrderImbalance>(message_json.clone()) {
+        Ok(imbalance) => Ok(Some(StreamingData {
+            received_time: Utc::now(),
+            event_time: parse_event_time(&imbalance.timestamp),
+            message_type: "i".to_string(),
+            symbol: Some(imbalance.symbol),
+            data: message_json,
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse order imbalance");
+            Ok(None)
+        }
+    }
+}
+
+fn decode_quote_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    let message_json = serde_json::to_value(message)?;
+    match serde_json::from_value::<StreamingQuote>(message_json.clone()) {
+        Ok(quote) => Ok(Some(StreamingData {
+            received_time: Utc::now(),
+            event_time: parse_event_time(&quote.timestamp.to_string()),
+            message_type: "q".to_string(),
+            symbol: Some(quote.symbol.clone()),
+            data: message_json,
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse quote");
+            Ok(None)
+        }
+    }
+}
+
+fn decode_bar_message(message: &StreamingMessage) -> Result<Option<StreamingData>> {
+    let message_json = serde_json::to_value(message)?;
+    match serde_json::from_value::<StreamingBar>(message_json.clone()) {
+        Ok(bar) => Ok(Some(StreamingData {
+            received_time: Utc::now(),
+            event_time: parse_event_time(&bar.timestamp.to_string()),
+            message_type: message.message_type.clone(),
+            symbol: Some(bar.symbol.clone()),
+            data: message_json,
+        })),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse bar");
+            Ok(None)
+        }
+    }
+}
+
+fn decode_trade_update_message(message: &StreamingMessage) -> Option<StreamingData> {
+    match serde_json::from_value::<TradeUpdate>(message.data.clone()) {
+        Ok(update) => Some(StreamingData {
+            received_time: Utc::now(),
+            event_time: update.timestamp.as_deref().and_then(parse_event_time),
+            message_type: "trade_update".to_string(),
+            symbol: Some(update.order.symbol.clone()),
+            data: message.data.clone(),
+        }),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse trade update");
+            None
+        }
+    }
+}
+
+/// `success`, `subscription` and `error` messages: only the text is kept
+fn decode_status_message(message: &StreamingMessage) -> Option<StreamingData> {
+    message.message.as_ref().map(|msg| StreamingData {
+        received_time: Utc::now(),
+        event_time: None,
+        message_type: message.message_type.clone(),
+        symbol: None,
+        data: serde_json::Value::String(msg.clone()),
+    })
+}
+
+fn decode_unknown_message(message: &StreamingMessage) -> StreamingData {
+    StreamingData {
+        received_time: Utc::now(),
+        event_time: None,
+        message_type: message.message_type.clone(),
+        symbol: None,
+        data: message.data.clone(),
+    }
+}
+
+/// Friendly names for the payload fields of market data messages, as exposed
+/// to scripts and templates
+pub(crate) fn payload_fields(message_type: &str) -> &'static [(&'static str, &'static str)] {
+    match message_type {
+        "t" => &[("price", "p"), ("size", "s"), ("exchange", "x"), ("id", "i"), ("conditions", "c")],
+        "q" => &[("bid", "bp"), ("bid_size", "bs"), ("ask", "ap"), ("ask_size", "as")],
+        "b" | "d" | "u" => &[("open", "o"), ("high", "h"), ("low", "l"), ("close", "c"), ("volume", "v")],
+        "c" => &[
+            ("original_price", "op"), ("original_size", "os"), ("corrected_price", "cp"),
+            ("corrected_size", "cs"), ("exchange", "x"), ("id", "oi"),
+        ],
+        "x" => &[("price", "p"), ("size", "s"), ("exchange", "x"), ("id", "i"), ("action", "a")],
+        "s" => &[("status_code", "sc"), ("status", "sm"), ("reason_code", "rc"), ("reason", "rm")],
+        "l" => &[("limit_up", "u"), ("limit_down", "d"), ("indicator", "i")],
+        "i" => &[("price", "p")],
+        _ => &[],
+    }
+}
+
+fn parse_event_time(timestamp: &str) -> Option<DateTime<Utc>> {
+    timestamp.parse().ok()
+}
+
+fn format_received(data: &StreamingData) -> String {
+    format!(" (received {})", data.received_time.format("%H:%M:%S%.3f"))
+}
+
+fn format_csv_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|time| time.format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string()).unwrap_or_default()
+}
+
+/// `attempt/max_retries` of a `disconnect` payload, or just the attempt when
+/// retries are unlimited
+pub(crate) fn attempt_label(payload: &serde_json::Value) -> String {
+    match payload["max_retries"].as_u64() {
+        Some(max_retries) => format!("{}/{}", payload["attempt"], max_retries),
+        None => payload["attempt"].to_string(),
+    }
+}
+
+fn json_symbol_list(value: &serde_json::Value) -> String {
+    value.as_array()
+        .map(|symbols| symbols.iter().filter_map(|s| s.as_str()).collect::<Vec<_>>().join(","))
+        .unwrap_or_default()
+}
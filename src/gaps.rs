@@ -0,0 +1,166 @@
+//! Missing bars in stored data: trading days of the market calendar with no
+//! bars, or with fewer than expected, grouped into gaps to report or backfill.
+
+use crate::market_time::eastern_offset;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::HashMap;
+
+/// The regular session of one trading day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TradingDay {
+    pub date: NaiveDate,
+    pub open: DateTime<Utc>,
+    pub close: DateTime<Utc>,
+}
+
+/// Consecutive trading days of a symbol with missing or too few bars
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Gap {
+    pub symbol: String,
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    /// Trading days in the gap
+    pub days: usize,
+    /// Bars stored on those days, the fewest of any store
+    pub bars_found: usize,
+    /// Bars a full regular session has on those days
+    pub bars_expected: usize,
+}
+
+impl Gap {
+    /// Midnight in New York before the first day and after the last one, the
+    /// range to fetch again
+    pub fn range(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        (
+            new_york_midnight(self.first_day),
+            new_york_midnight(self.last_day + Duration::days(1)),
+        )
+    }
+}
+
+impl std::fmt::Display for Gap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}..{}: {} trading day{}, {}/{} bars",
+            self.symbol,
+            self.first_day,
+            self.last_day,
+            self.days,
+            if self.days == 1 { "" } else { "s" },
+            self.bars_found,
+            self.bars_expected
+        )
+    }
+}
+
+fn new_york_midnight(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("valid time");
+    (midnight - Duration::seconds(eastern_offset(date).local_minus_utc() as i64)).and_utc()
+}
+
+/// The New York date of a bar
+fn trading_date(timestamp: DateTime<Utc>) -> NaiveDate {
+    timestamp
+        .with_timezone(&eastern_offset(timestamp.date_naive()))
+        .date_naive()
+}
+
+/// Bars each trading day holds: every bar dated that day for daily bars
+/// (`bar` is `None`), the bars overlapping the regular session for shorter
+/// ones
+fn day_counts(
+    timestamps: &[DateTime<Utc>],
+    days: &[TradingDay],
+    bar: Option<Duration>,
+) -> Vec<usize> {
+    let index: HashMap<NaiveDate, usize> = days
+        .iter()
+        .enumerate()
+        .map(|(i, day)| (day.date, i))
+        .collect();
+    let mut counts = vec![0; days.len()];
+    for timestamp in timestamps {
+        let Some(&i) = index.get(&trading_date(*timestamp)) else {
+            continue;
+        };
+        let day = &days[i];
+        let in_session = match bar {
+            Some(bar) => *timestamp < day.close && *timestamp + bar > day.open,
+            None => true,
+        };
+        if in_session {
+            counts[i] += 1;
+        }
+    }
+    counts
+}
+
+/// The gaps of `symbol` over trading `days` in the stores holding the
+/// timestamps of its bars. A day lacks bars if a store has none, or fewer
+/// than `min_coverage` of a full session of `bar`-long bars; daily bars (`bar`
+/// is `None`) expect one bar a day. Illiquid symbols have minutes without
+/// trades, so a `min_coverage` of 0 only reports days without any bar.
+pub fn find_gaps(
+    symbol: &str,
+    stores: &[&[DateTime<Utc>]],
+    days: &[TradingDay],
+    bar: Option<Duration>,
+    min_coverage: f64,
+) -> Vec<Gap> {
+    let counts: Vec<Vec<usize>> = stores
+        .iter()
+        .map(|timestamps| day_counts(timestamps, days, bar))
+        .collect();
+    let mut gaps: Vec<Gap> = Vec::new();
+    let mut previous_lacking = false;
+    for (i, day) in days.iter().enumerate() {
+        let found = counts.iter().map(|counts| counts[i]).min().unwrap_or(0);
+        let expected = match bar {
+            // Hourly bars start on the hour, so 09:30-16:00 has 7 of them
+            Some(bar) if bar > Duration::zero() => {
+                let (session, bar) = ((day.close - day.open).num_seconds(), bar.num_seconds());
+                ((session + bar - 1) / bar).max(1) as usize
+            }
+            _ => 1,
+        };
+        let lacking = found == 0 || (found as f64) < expected as f64 * min_coverage;
+        if lacking {
+            match gaps.last_mut() {
+                Some(gap) if previous_lacking => {
+                    gap.last_day = day.date;
+                    gap.days += 1;
+                    gap.bars_found += found;
+                    gap.bars_expected += expected;
+                }
+                _ => gaps.push(Gap {
+                    symbol: symbol.to_string(),
+                    first_day: day.date,
+                    last_day: day.date,
+                    days: 1,
+                    bars_found: found,
+                    bars_expected: expected,
+                }),
+            }
+        }
+        previous_lacking = lacking;
+    }
+    gaps
+}
+
+/// The length of a bar of `timeframe` (`1Min` ... `1Hour`), or `None` for
+/// daily bars; weekly and monthly bars cannot be checked against the calendar
+pub fn bar_duration(timeframe: &str) -> crate::Result<Option<Duration>> {
+    match timeframe {
+        "1Min" => Ok(Some(Duration::minutes(1))),
+        "5Min" => Ok(Some(Duration::minutes(5))),
+        "15Min" => Ok(Some(Duration::minutes(15))),
+        "30Min" => Ok(Some(Duration::minutes(30))),
+        "1Hour" => Ok(Some(Duration::hours(1))),
+        "1Day" => Ok(None),
+        _ => Err(crate::Error::Invalid(format!(
+            "Gaps can only be found in bars of 1Day or shorter, not {}",
+            timeframe
+        ))),
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{OutputSink, StreamingData};
+use alpaca_trading_api_rust::{StreamingBar, StreamingQuote, StreamingTrade};
+use crate::{Error, Result};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("market_data");
+}
+
+use proto::market_data_event::Event;
+use proto::market_data_server::{MarketData, MarketDataServer};
+use proto::{MarketDataEvent, SubscribeRequest};
+
+/// Convert a trade, quote or bar record into its protobuf event; other
+/// message types are not part of the gRPC stream
+pub fn to_market_data_event(data: &StreamingData) -> Option<MarketDataEvent> {
+    let event = match data.message_type.as_str() {
+        "t" => {
+            let trade: StreamingTrade = serde_json::from_value(data.data.clone()).ok()?;
+            Event::Trade(proto::Trade {
+                symbol: trade.symbol,
+                timestamp: trade.timestamp.to_string(),
+                price: trade.price,
+                size: trade.size,
+                exchange: trade.exchange,
+                id: trade.id,
+            })
+        }
+        "q" => {
+            let quote: StreamingQuote = serde_json::from_value(data.data.clone()).ok()?;
+            Event::Quote(proto::Quote {
+                symbol: quote.symbol,
+                timestamp: quote.timestamp.to_string(),
+                bid_price: quote.bid_price,
+                bid_size: quote.bid_size,
+                ask_price: quote.ask_price,
+                ask_size: quote.ask_size,
+            })
+        }
+        "b" => {
+            let bar: StreamingBar = serde_json::from_value(data.data.clone()).ok()?;
+            Event::Bar(proto::Bar {
+                symbol: bar.symbol,
+                timestamp: bar.timestamp.to_string(),
+                open: bar.open,
+                high: bar.high,
+                low: bar.low,
+                close: bar.close,
+                volume: bar.volume,
+            })
+        }
+        _ => return None,
+    };
+    Some(MarketDataEvent {
+        received_at: data.received_time.to_rfc3339(),
+        event: Some(event),
+    })
+}
+
+/// Broadcasts every record to connected gRPC subscribers.
+///
+/// Subscribers that fall more than `capacity` events behind skip the missed
+/// events instead of slowing down the stream.
+#[derive(Debug, Clone)]
+pub struct GrpcBroadcastSink {
+    sender: broadcast::Sender<MarketDataEvent>,
+}
+
+impl GrpcBroadcastSink {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn service(&self) -> MarketDataServer<MarketDataService> {
+        MarketDataServer::new(MarketDataService {
+            sender: self.sender.clone(),
+        })
+    }
+}
+
+impl OutputSink for GrpcBroadcastSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        if let Some(event) = to_market_data_event(data) {
+            // No receivers just means nobody is subscribed right now
+            let _ = self.sender.send(event);
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct MarketDataService {
+    sender: broadcast::Sender<MarketDataEvent>,
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<MarketDataEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl MarketData for MarketDataService {
+    type SubscribeStream = EventStream;
+
+    async fn subscribe(&self, request: Request<SubscribeRequest>) -> Result<Response<Self::SubscribeStream>, Status> {
+        let filter = SubscriptionFilter::new(request.into_inner()).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let stream = BroadcastStream::new(self.sender.subscribe()).filter_map(move |item| match item {
+            Ok(event) if filter.matches(&event) => Some(Ok(event)),
+            Ok(_) => None,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "gRPC subscriber lagged");
+                None
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+struct SubscriptionFilter {
+    symbols: Vec<String>,
+    trades: bool,
+    quotes: bool,
+    bars: bool,
+}
+
+impl SubscriptionFilter {
+    fn new(request: SubscribeRequest) -> Result<Self> {
+        let all_types = request.types.is_empty();
+        let mut filter = Self {
+            symbols: request.symbols.iter().map(|s| s.trim().to_uppercase()).collect(),
+            trades: all_types,
+            quotes: all_types,
+            bars: all_types,
+        };
+        for data_type in &request.types {
+            match data_type.trim().to_lowercase().as_str() {
+                "trades" => filter.trades = true,
+                "quotes" => filter.quotes = true,
+                "bars" => filter.bars = true,
+                other => {
+                    return Err(Error::Invalid(format!("Invalid type: {}. Supported: trades, quotes, bars", other)));
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    fn matches(&self, event: &MarketDataEvent) -> bool {
+        let (symbol, wanted) = match &event.event {
+            Some(Event::Trade(trade)) => (&trade.symbol, self.trades),
+            Some(Event::Quote(quote)) => (&quote.symbol, self.quotes),
+            Some(Event::Bar(bar)) => (&bar.symbol, self.bars),
+            None => return false,
+        };
+        wanted && (self.symbols.is_empty() || self.symbols.contains(symbol))
+    }
+}
+
+pub async fn serve(addr: SocketAddr, sink: GrpcBroadcastSink) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(sink.service())
+        .serve(addr)
+        .await
+        .map_err(Error::from)?;
+    Ok(())
+}
@@ -0,0 +1,418 @@
+//! Tick-level history from the market data API: individual trades and the
+//! prints of the opening and closing auctions.
+
+use crate::config::DEFAULT_DATA_URL;
+use crate::rate_limit::RateLimiter;
+use crate::retry::{retry_request, HttpError, RetryPolicy, DEFAULT_REQUEST_RETRIES};
+use crate::DataFormat;
+use alpaca_trading_api_rust::StockDataFeed;
+use crate::{Error, Result};
+use std::time::Duration;
+use tracing::info;
+
+/// Header of the CSV written for historical trades
+pub const TRADES_CSV_HEADER: &str = "symbol,timestamp,price,size,exchange,id,conditions,tape";
+/// Header of the CSV written for auction prints
+pub const AUCTIONS_CSV_HEADER: &str =
+    "symbol,date,auction,timestamp,price,size,exchange,condition,official";
+
+/// One trade from `GET /v2/stocks/{symbol}/trades`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct HistoricalTrade {
+    pub symbol: String,
+    pub timestamp: String,
+    pub price: f64,
+    pub size: u64,
+    pub exchange: String,
+    pub id: u64,
+    /// Sale condition codes
+    pub conditions: Vec<String>,
+    /// `A`, `B` or `C`
+    pub tape: String,
+}
+
+/// A trade as the API sends it
+#[derive(Debug, serde::Deserialize)]
+struct ApiTrade {
+    t: String,
+    p: f64,
+    s: u64,
+    x: String,
+    i: u64,
+    #[serde(default)]
+    c: Vec<String>,
+    #[serde(default)]
+    z: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TradesPage {
+    /// `null` when the range has no trades
+    #[serde(default)]
+    trades: Option<Vec<ApiTrade>>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+/// The trades of one response page for `symbol`, and the token of the next
+/// page if there is one
+pub fn parse_trades_page(
+    symbol: &str,
+    body: &str,
+) -> Result<(Vec<HistoricalTrade>, Option<String>)> {
+    let page: TradesPage = serde_json::from_str(body)?;
+    let trades = page
+        .trades
+        .unwrap_or_default()
+        .into_iter()
+        .map(|trade| HistoricalTrade {
+            symbol: symbol.to_string(),
+            timestamp: trade.t,
+            price: trade.p,
+            size: trade.s,
+            exchange: trade.x,
+            id: trade.i,
+            conditions: trade.c,
+            tape: trade.z,
+        })
+        .collect();
+    Ok((trades, page.next_page_token))
+}
+
+impl HistoricalTrade {
+    /// The line written for this trade; CSV lines match `TRADES_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        Ok(match format {
+            DataFormat::Plain => format!(
+                "🔄 {}: {} | ${:.2} x {} | Exchange: {} | ID: {} | Conditions: {}",
+                self.symbol,
+                self.timestamp,
+                self.price,
+                self.size,
+                self.exchange,
+                self.id,
+                self.conditions.join(" ")
+            ),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{}",
+                self.symbol,
+                self.timestamp,
+                self.price,
+                self.size,
+                self.exchange,
+                self.id,
+                self.conditions.join(" "),
+                self.tape
+            ),
+        })
+    }
+
+    #[cfg(feature = "parquet")]
+    pub fn to_dataset_row(&self) -> Result<crate::dataset::TradeRow> {
+        let timestamp = self
+            .timestamp
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .map_err(|_| Error::Invalid(format!("Invalid trade timestamp: {}", self.timestamp)))?;
+        Ok(crate::dataset::TradeRow {
+            timestamp,
+            price: self.price,
+            size: self.size,
+            exchange: self.exchange.clone(),
+            id: self.id,
+            conditions: Some(self.conditions.join(" ")),
+            tape: Some(self.tape.clone()),
+            received_at: None,
+        })
+    }
+}
+
+/// One print of an opening or closing auction, from `GET /v2/stocks/auctions`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuctionPrint {
+    pub symbol: String,
+    /// Trading day, `YYYY-MM-DD`
+    pub date: String,
+    /// `open` or `close`
+    pub auction: String,
+    pub timestamp: String,
+    pub price: f64,
+    pub size: u64,
+    pub exchange: String,
+    pub condition: String,
+    /// The listing exchange's official opening (`Q`) or closing (`M`) price
+    pub official: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ApiAuctionPrint {
+    t: String,
+    p: f64,
+    #[serde(default)]
+    s: u64,
+    x: String,
+    #[serde(default)]
+    c: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ApiAuctionDay {
+    d: String,
+    #[serde(default)]
+    o: Vec<ApiAuctionPrint>,
+    #[serde(default)]
+    c: Vec<ApiAuctionPrint>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct AuctionsPage {
+    #[serde(default)]
+    auctions: Option<std::collections::HashMap<String, Vec<ApiAuctionDay>>>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+/// The auction prints of one response page for `symbol`, opening prints
+/// before closing prints on each day, and the token of the next page
+pub fn parse_auctions_page(
+    symbol: &str,
+    body: &str,
+) -> Result<(Vec<AuctionPrint>, Option<String>)> {
+    let mut page: AuctionsPage = serde_json::from_str(body)?;
+    let days = page
+        .auctions
+        .as_mut()
+        .and_then(|auctions| auctions.remove(symbol))
+        .unwrap_or_default();
+    let mut prints = Vec::new();
+    for day in days {
+        for (auction, official, day_prints) in [("open", "Q", day.o), ("close", "M", day.c)] {
+            prints.extend(day_prints.into_iter().map(|print| AuctionPrint {
+                symbol: symbol.to_string(),
+                date: day.d.clone(),
+                auction: auction.to_string(),
+                timestamp: print.t,
+                price: print.p,
+                size: print.s,
+                exchange: print.x,
+                official: print.c == official,
+                condition: print.c,
+            }));
+        }
+    }
+    Ok((prints, page.next_page_token))
+}
+
+impl AuctionPrint {
+    /// The line written for this print; CSV lines match `AUCTIONS_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        Ok(match format {
+            DataFormat::Plain => format!(
+                "{} {} auction: {} {} | ${:.2} x {} @ {} | Exchange: {} | Condition: {}{}",
+                if self.auction == "open" {
+                    "🔔"
+                } else {
+                    "🏁"
+                },
+                if self.auction == "open" {
+                    "Opening"
+                } else {
+                    "Closing"
+                },
+                self.symbol,
+                self.date,
+                self.price,
+                self.size,
+                self.timestamp,
+                self.exchange,
+                self.condition,
+                if self.official { " (official)" } else { "" }
+            ),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{},{}",
+                self.symbol,
+                self.date,
+                self.auction,
+                self.timestamp,
+                self.price,
+                self.size,
+                self.exchange,
+                self.condition,
+                self.official
+            ),
+        })
+    }
+}
+
+/// Downloads tick-level trades and auction prints from the market data API
+#[derive(Debug, Clone)]
+pub struct HistoricalTradesClient {
+    client: reqwest::Client,
+    data_url: String,
+    key: String,
+    secret: String,
+    retry: RetryPolicy,
+}
+
+impl HistoricalTradesClient {
+    /// Credentials from `APCA_API_KEY_ID` and `APCA_API_SECRET_KEY`, endpoint
+    /// from `APCA_API_DATA_URL`
+    pub fn from_env() -> Result<Self> {
+        let var =
+            |name: &str| std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)));
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            data_url: std::env::var("APCA_API_DATA_URL")
+                .unwrap_or_else(|_| DEFAULT_DATA_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            key: var("APCA_API_KEY_ID")?,
+            secret: var("APCA_API_SECRET_KEY")?,
+            retry: RetryPolicy::for_requests(DEFAULT_REQUEST_RETRIES),
+        })
+    }
+
+    /// Retry each request up to `max_retries` times after a 429, a 5xx or a
+    /// network error
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.retry = RetryPolicy::for_requests(max_retries);
+        self
+    }
+
+    /// Every trade of `symbol` from `start` to `end` (dates or RFC 3339
+    /// times), following `next_page_token` with `page_size` trades per
+    /// request. Each page goes to `on_page` before the next is requested, so
+    /// months of ticks are never held at once; returns the number of trades.
+    #[tracing::instrument(name = "fetch_trades", skip(self, page_size, feed, on_page), fields(feed = feed.as_str()))]
+    pub async fn fetch_trades(
+        &self,
+        symbol: &str,
+        start: &str,
+        end: &str,
+        feed: &StockDataFeed,
+        page_size: u32,
+        mut on_page: impl FnMut(Vec<HistoricalTrade>) -> Result<()>,
+    ) -> Result<usize> {
+        info!("Fetching historical trades");
+        let mut trades = 0;
+        let mut page_token: Option<String> = None;
+        loop {
+            let (page, next_page_token) = self
+                .fetch_trades_page(symbol, start, end, feed, page_size, page_token.as_deref())
+                .await?;
+            trades += page.len();
+            on_page(page)?;
+            match next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+        info!(trades, "Retrieved trades");
+        Ok(trades)
+    }
+
+    /// The page of trades at `page_token` (the first page for `None`), and
+    /// the token of the next page if there is one
+    pub async fn fetch_trades_page(
+        &self,
+        symbol: &str,
+        start: &str,
+        end: &str,
+        feed: &StockDataFeed,
+        page_size: u32,
+        page_token: Option<&str>,
+    ) -> Result<(Vec<HistoricalTrade>, Option<String>)> {
+        let url = format!("{}/v2/stocks/{}/trades", self.data_url, symbol);
+        let limit = page_size.to_string();
+        let mut query = vec![
+            ("start", start),
+            ("end", end),
+            ("limit", limit.as_str()),
+            ("feed", feed.as_str()),
+        ];
+        query.extend(page_token.map(|token| ("page_token", token)));
+        tracing::debug!(page_token = ?page_token, "Requesting page");
+        let body = self.get(&url, &query).await?;
+        parse_trades_page(symbol, &body)
+    }
+
+    /// Every opening and closing auction print of `symbol` from `start` to
+    /// `end`, with `page_size` days per request, passed to `on_page` a page
+    /// at a time; returns the number of prints
+    #[tracing::instrument(name = "fetch_auctions", skip(self, page_size, feed, on_page), fields(feed = feed.as_str()))]
+    pub async fn fetch_auctions(
+        &self,
+        symbol: &str,
+        start: &str,
+        end: &str,
+        feed: &StockDataFeed,
+        page_size: u32,
+        mut on_page: impl FnMut(Vec<AuctionPrint>) -> Result<()>,
+    ) -> Result<usize> {
+        info!("Fetching auctions");
+        let mut prints = 0;
+        let mut page_token: Option<String> = None;
+        loop {
+            let (page, next_page_token) = self
+                .fetch_auctions_page(symbol, start, end, feed, page_size, page_token.as_deref())
+                .await?;
+            prints += page.len();
+            on_page(page)?;
+            match next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+        info!(prints, "Retrieved auction prints");
+        Ok(prints)
+    }
+
+    /// The page of auction prints at `page_token` (the first page for
+    /// `None`), and the token of the next page if there is one
+    pub async fn fetch_auctions_page(
+        &self,
+        symbol: &str,
+        start: &str,
+        end: &str,
+        feed: &StockDataFeed,
+        page_size: u32,
+        page_token: Option<&str>,
+    ) -> Result<(Vec<AuctionPrint>, Option<String>)> {
+        let url = format!("{}/v2/stocks/auctions", self.data_url);
+        let limit = page_size.to_string();
+        let mut query = vec![
+            ("symbols", symbol),
+            ("start", start),
+            ("end", end),
+            ("limit", limit.as_str()),
+            ("feed", feed.as_str()),
+        ];
+        query.extend(page_token.map(|token| ("page_token", token)));
+        tracing::debug!(page_token = ?page_token, "Requesting page");
+        let body = self.get(&url, &query).await?;
+        parse_auctions_page(symbol, &body)
+    }
+
+    /// GET `url` with `query` within the rate limit, retried per `self.retry`
+    async fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<String> {
+        retry_request(&self.retry, move || async move {
+            RateLimiter::global().acquire().await;
+            let response = self
+                .client
+                .get(url)
+                .query(query)
+                .header("APCA-API-KEY-ID", &self.key)
+                .header("APCA-API-SECRET-KEY", &self.secret)
+                .send()
+                .await?;
+            RateLimiter::global()
+                .observe(|name| response.headers().get(name)?.to_str().ok()?.parse().ok());
+            let status = response.status().as_u16();
+            HttpError::check(status, response.text().await?)
+        })
+        .await
+    }
+}
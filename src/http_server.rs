@@ -0,0 +1,104 @@
+use crate::{CaptureBuffer, Error, Result, SymbolCapture};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::net::SocketAddr;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct BarsQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct BarResponse {
+    symbol: String,
+    timestamp: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: u64,
+}
+
+/// Routes:
+/// - `GET /symbols` — symbols seen so far
+/// - `GET /latest/{symbol}` — latest trade, quote and bar
+/// - `GET /bars/{symbol}?from=&to=` — buffered bars, bounds as RFC 3339 or `YYYY-MM-DD`
+pub fn router(buffer: CaptureBuffer) -> Router {
+    Router::new()
+        .route("/symbols", get(symbols))
+        .route("/latest/:symbol", get(latest))
+        .route("/bars/:symbol", get(bars))
+        .with_state(buffer)
+}
+
+pub async fn serve(addr: SocketAddr, buffer: CaptureBuffer) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(buffer)).await?;
+    Ok(())
+}
+
+async fn symbols(State(buffer): State<CaptureBuffer>) -> Json<Vec<String>> {
+    Json(buffer.symbols())
+}
+
+async fn latest(State(buffer): State<CaptureBuffer>, Path(symbol): Path<String>) -> Response {
+    match buffer.latest(&symbol) {
+        Some(capture) => Json::<SymbolCapture>(capture).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, format!("No data for symbol: {}", symbol)),
+    }
+}
+
+async fn bars(
+    State(buffer): State<CaptureBuffer>,
+    Path(symbol): Path<String>,
+    Query(query): Query<BarsQuery>,
+) -> Response {
+    let from = match query.from.as_deref().map(|value| parse_query_time(value, false)).transpose() {
+        Ok(from) => from,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let to = match query.to.as_deref().map(|value| parse_query_time(value, true)).transpose() {
+        Ok(to) => to,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    let bars: Vec<BarResponse> = buffer
+        .bars(&symbol, from, to)
+        .into_iter()
+        .map(|bar| BarResponse {
+            symbol: bar.symbol,
+            timestamp: bar.timestamp.to_string(),
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume,
+        })
+        .collect();
+    Json(bars).into_response()
+}
+
+/// Accepts RFC 3339 timestamps or plain dates; a date used as an upper bound
+/// covers the whole day
+fn parse_query_time(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    if let Ok(time) = value.parse::<DateTime<Utc>>() {
+        return Ok(time);
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| Error::Invalid(format!("Invalid time: {}. Use RFC 3339 or YYYY-MM-DD", value)))?;
+    let time = if end_of_day {
+        date.and_hms_milli_opt(23, 59, 59, 999)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.expect("valid time of day").and_utc())
+}
+
+fn error_response(status: StatusCode, message: String) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,873 @@
+//! Technical indicators over bars, updated one bar at a time so the same
+//! code serves a live session and a backtest over downloaded bars.
+//!
+//! Every indicator implements [`Indicator`]: `update` adds the next bar and
+//! returns the value at it, and `compute` runs over a slice of bars. The
+//! indicators of a single price (SMA, EMA, WMA, RSI, MACD and Bollinger
+//! Bands) use the close, and also take any other series through `next`.
+//! Values are `None` until an indicator has seen enough bars.
+//!
+//! [`IndicatorSpec`] names an indicator the way the command line does, e.g.
+//! `macd(12,26,9)`, and [`IndicatorColumns`] runs a list of them as columns
+//! to add to bars.
+
+use crate::market_time::new_york_date;
+use crate::resample::OhlcvBar;
+use crate::{Error, Result};
+use chrono::NaiveDate;
+use std::collections::VecDeque;
+
+/// A technical indicator fed one bar at a time
+pub trait Indicator {
+    /// What the indicator gives at each bar
+    type Output;
+
+    /// Add the next bar and return the indicator at it; `None` until enough
+    /// bars have been added
+    fn update(&mut self, bar: &OhlcvBar) -> Option<Self::Output>;
+
+    /// Forget every bar added, as if just created
+    fn reset(&mut self);
+
+    /// The indicator at each of `bars`, carrying on from the bars added so far
+    fn compute(&mut self, bars: &[OhlcvBar]) -> Vec<Option<Self::Output>> {
+        bars.iter().map(|bar| self.update(bar)).collect()
+    }
+}
+
+fn check_period(name: &str, period: usize) -> Result<usize> {
+    if period == 0 {
+        return Err(Error::Invalid(format!(
+            "Invalid {} period: 0. Use at least 1 bar",
+            name
+        )));
+    }
+    Ok(period)
+}
+
+/// Values kept for the last `period` bars
+#[derive(Debug, Clone)]
+struct Window {
+    period: usize,
+    values: VecDeque<f64>,
+}
+
+impl Window {
+    fn new(period: usize) -> Self {
+        Self {
+            period,
+            values: VecDeque::with_capacity(period + 1),
+        }
+    }
+
+    /// Add a value and return the one that fell out of the window
+    fn push(&mut self, value: f64) -> Option<f64> {
+        self.values.push_back(value);
+        if self.values.len() > self.period {
+            self.values.pop_front()
+        } else {
+            None
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.values.len() == self.period
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// Simple moving average of the last N values
+#[derive(Debug, Clone)]
+pub struct Sma {
+    window: Window,
+    sum: f64,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Result<Self> {
+        Ok(Self {
+            window: Window::new(check_period("SMA", period)?),
+            sum: 0.0,
+        })
+    }
+
+    /// Add the next value and return the average
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        self.sum += value;
+        if let Some(dropped) = self.window.push(value) {
+            self.sum -= dropped;
+        }
+        self.window
+            .is_full()
+            .then(|| self.sum / self.window.period as f64)
+    }
+}
+
+impl Indicator for Sma {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<f64> {
+        self.next(bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.sum = 0.0;
+    }
+}
+
+/// Exponential moving average over N values, weighing each by 2 / (N + 1)
+/// and starting from the simple average of the first N
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    alpha: f64,
+    seen: usize,
+    sum: f64,
+    ema: Option<f64>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Result<Self> {
+        let period = check_period("EMA", period)?;
+        Ok(Self {
+            period,
+            alpha: 2.0 / (period as f64 + 1.0),
+            seen: 0,
+            sum: 0.0,
+            ema: None,
+        })
+    }
+
+    /// Add the next value and return the average
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        match self.ema {
+            Some(ema) => self.ema = Some(ema + self.alpha * (value - ema)),
+            None => {
+                self.seen += 1;
+                self.sum += value;
+                if self.seen == self.period {
+                    self.ema = Some(self.sum / self.period as f64);
+                }
+            }
+        }
+        self.ema
+    }
+}
+
+impl Indicator for Ema {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<f64> {
+        self.next(bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.seen = 0;
+        self.sum = 0.0;
+        self.ema = None;
+    }
+}
+
+/// Weighted moving average of the last N values, weighing the newest by N
+/// and the oldest by 1
+#[derive(Debug, Clone)]
+pub struct Wma {
+    window: Window,
+    sum: f64,
+    weighted_sum: f64,
+}
+
+impl Wma {
+    pub fn new(period: usize) -> Result<Self> {
+        Ok(Self {
+            window: Window::new(check_period("WMA", period)?),
+            sum: 0.0,
+            weighted_sum: 0.0,
+        })
+    }
+
+    /// Add the next value and return the average
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        let period = self.window.period as f64;
+        if self.window.is_full() {
+            // Every value already in the window loses one weight
+            self.weighted_sum += period * value - self.sum;
+        } else {
+            self.weighted_sum += (self.window.values.len() + 1) as f64 * value;
+        }
+        self.sum += value;
+        if let Some(dropped) = self.window.push(value) {
+            self.sum -= dropped;
+        }
+        self.window
+            .is_full()
+            .then(|| self.weighted_sum / (period * (period + 1.0) / 2.0))
+    }
+}
+
+impl Indicator for Wma {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<f64> {
+        self.next(bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.sum = 0.0;
+        self.weighted_sum = 0.0;
+    }
+}
+
+/// Relative strength index over N changes with Wilder's smoothing, from 0 to
+/// 100; the first value comes with the N + 1st value
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    period: usize,
+    previous: Option<f64>,
+    changes: usize,
+    gain: f64,
+    loss: f64,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Result<Self> {
+        Ok(Self {
+            period: check_period("RSI", period)?,
+            previous: None,
+            changes: 0,
+            gain: 0.0,
+            loss: 0.0,
+        })
+    }
+
+    /// Add the next value and return the index
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        let previous = self.previous.replace(value)?;
+        let change = value - previous;
+        let (gain, loss) = (change.max(0.0), (-change).max(0.0));
+        let period = self.period as f64;
+        self.changes += 1;
+        if self.changes <= self.period {
+            // The first averages are simple ones
+            self.gain += gain / period;
+            self.loss += loss / period;
+            if self.changes < self.period {
+                return None;
+            }
+        } else {
+            self.gain = (self.gain * (period - 1.0) + gain) / period;
+            self.loss = (self.loss * (period - 1.0) + loss) / period;
+        }
+        Some(if self.loss > 0.0 {
+            100.0 - 100.0 / (1.0 + self.gain / self.loss)
+        } else if self.gain > 0.0 {
+            100.0
+        } else {
+            // Flat prices
+            50.0
+        })
+    }
+}
+
+impl Indicator for Rsi {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<f64> {
+        self.next(bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.previous = None;
+        self.changes = 0;
+        self.gain = 0.0;
+        self.loss = 0.0;
+    }
+}
+
+/// The three lines of a MACD
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MacdValue {
+    /// Fast EMA less slow EMA
+    pub macd: f64,
+    /// EMA of the MACD line
+    pub signal: f64,
+    /// MACD line less signal line
+    pub histogram: f64,
+}
+
+/// Moving average convergence/divergence, e.g. 12, 26 and 9 bars; values
+/// start once the signal line has its first value
+#[derive(Debug, Clone)]
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+}
+
+impl Macd {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Result<Self> {
+        if fast >= slow {
+            return Err(Error::Invalid(format!(
+                "Invalid MACD periods: the fast period ({}) must be shorter than the slow one ({})",
+                fast, slow
+            )));
+        }
+        Ok(Self {
+            fast: Ema::new(fast)?,
+            slow: Ema::new(slow)?,
+            signal: Ema::new(signal)?,
+        })
+    }
+
+    /// Add the next value and return the lines
+    pub fn next(&mut self, value: f64) -> Option<MacdValue> {
+        let (fast, slow) = (self.fast.next(value), self.slow.next(value));
+        let macd = fast? - slow?;
+        let signal = self.signal.next(macd)?;
+        Some(MacdValue {
+            macd,
+            signal,
+            histogram: macd - signal,
+        })
+    }
+}
+
+impl Indicator for Macd {
+    type Output = MacdValue;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<MacdValue> {
+        self.next(bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.fast.reset();
+        self.slow.reset();
+        self.signal.reset();
+    }
+}
+
+/// The three bands of Bollinger Bands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BollingerValue {
+    pub upper: f64,
+    /// Simple moving average
+    pub middle: f64,
+    pub lower: f64,
+}
+
+/// Simple moving average of the last N values with bands K population
+/// standard deviations above and below, e.g. 20 bars and 2.0
+#[derive(Debug, Clone)]
+pub struct BollingerBands {
+    window: Window,
+    width: f64,
+}
+
+impl BollingerBands {
+    pub fn new(period: usize, width: f64) -> Result<Self> {
+        if !(width > 0.0 && width.is_finite()) {
+            return Err(Error::Invalid(format!(
+                "Invalid Bollinger Bands width: {}. Use a number of standard deviations such as 2",
+                width
+            )));
+        }
+        Ok(Self {
+            window: Window::new(check_period("Bollinger Bands", period)?),
+            width,
+        })
+    }
+
+    /// Add the next value and return the bands
+    pub fn next(&mut self, value: f64) -> Option<BollingerValue> {
+        self.window.push(value);
+        if !self.window.is_full() {
+            return None;
+        }
+        let count = self.window.period as f64;
+        let mean = self.window.values.iter().sum::<f64>() / count;
+        let variance = self
+            .window
+            .values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let band = self.width * variance.sqrt();
+        Some(BollingerValue {
+            upper: mean + band,
+            middle: mean,
+            lower: mean - band,
+        })
+    }
+}
+
+impl Indicator for BollingerBands {
+    type Output = BollingerValue;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<BollingerValue> {
+        self.next(bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// Average true range over N bars with Wilder's smoothing; the true range of
+/// the first bar is its high less its low
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    previous_close: Option<f64>,
+    seen: usize,
+    atr: f64,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Result<Self> {
+        Ok(Self {
+            period: check_period("ATR", period)?,
+            previous_close: None,
+            seen: 0,
+            atr: 0.0,
+        })
+    }
+}
+
+impl Indicator for Atr {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<f64> {
+        let range = match self.previous_close.replace(bar.close) {
+            Some(close) => (bar.high - bar.low)
+                .max((bar.high - close).abs())
+                .max((bar.low - close).abs()),
+            None => bar.high - bar.low,
+        };
+        let period = self.period as f64;
+        self.seen += 1;
+        if self.seen <= self.period {
+            self.atr += range / period;
+            (self.seen == self.period).then_some(self.atr)
+        } else {
+            self.atr = (self.atr * (period - 1.0) + range) / period;
+            Some(self.atr)
+        }
+    }
+
+    fn reset(&mut self) {
+        self.previous_close = None;
+        self.seen = 0;
+        self.atr = 0.0;
+    }
+}
+
+/// %K and %D of a stochastic oscillator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StochasticValue {
+    /// Where the close is in the range of the last N bars, from 0 to 100
+    pub k: f64,
+    /// Simple moving average of %K
+    pub d: f64,
+}
+
+/// Stochastic oscillator over the highs and lows of the last N bars, with %D
+/// the average of the last M %K values, e.g. 14 and 3. A bar range without
+/// width puts %K at 50.
+#[derive(Debug, Clone)]
+pub struct Stochastic {
+    highs: Window,
+    lows: Window,
+    d: Sma,
+}
+
+impl Stochastic {
+    pub fn new(k_period: usize, d_period: usize) -> Result<Self> {
+        let k_period = check_period("stochastic %K", k_period)?;
+        Ok(Self {
+            highs: Window::new(k_period),
+            lows: Window::new(k_period),
+            d: Sma::new(d_period)?,
+        })
+    }
+}
+
+impl Indicator for Stochastic {
+    type Output = StochasticValue;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<StochasticValue> {
+        self.highs.push(bar.high);
+        self.lows.push(bar.low);
+        if !self.highs.is_full() {
+            return None;
+        }
+        let high = self.highs.values.iter().copied().fold(f64::MIN, f64::max);
+        let low = self.lows.values.iter().copied().fold(f64::MAX, f64::min);
+        let k = if high > low {
+            100.0 * (bar.close - low) / (high - low)
+        } else {
+            50.0
+        };
+        let d = self.d.next(k)?;
+        Some(StochasticValue { k, d })
+    }
+
+    fn reset(&mut self) {
+        self.highs.clear();
+        self.lows.clear();
+        self.d.reset();
+    }
+}
+
+/// On-balance volume: the running total of the volume of bars that closed
+/// up, less that of bars that closed down, from 0 at the first bar
+#[derive(Debug, Clone, Default)]
+pub struct Obv {
+    previous_close: Option<f64>,
+    obv: f64,
+}
+
+impl Obv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Indicator for Obv {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<f64> {
+        if let Some(close) = self.previous_close.replace(bar.close) {
+            let volume = bar.volume as f64;
+            if bar.close > close {
+                self.obv += volume;
+            } else if bar.close < close {
+                self.obv -= volume;
+            }
+        }
+        Some(self.obv)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Volume-weighted average price since the first bar of the New York
+/// trading day, from each bar's VWAP, or its typical price (high + low +
+/// close) / 3 for bars without one. Starts over on each new day; `None`
+/// until the day has volume.
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    day: Option<NaiveDate>,
+    notional: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Indicator for Vwap {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OhlcvBar) -> Option<f64> {
+        let day = new_york_date(bar.timestamp);
+        if self.day.replace(day) != Some(day) {
+            self.notional = 0.0;
+            self.volume = 0.0;
+        }
+        let price = if bar.vwap > 0.0 {
+            bar.vwap
+        } else {
+            (bar.high + bar.low + bar.close) / 3.0
+        };
+        self.notional += price * bar.volume as f64;
+        self.volume += bar.volume as f64;
+        (self.volume > 0.0).then(|| self.notional / self.volume)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// An indicator as named on the command line, e.g. `sma(20)` or
+/// `macd(12,26,9)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndicatorSpec {
+    Sma(usize),
+    Ema(usize),
+    Wma(usize),
+    Rsi(usize),
+    Macd {
+        fast: usize,
+        slow: usize,
+        signal: usize,
+    },
+    Bollinger {
+        period: usize,
+        width: f64,
+    },
+    Atr(usize),
+    Stochastic {
+        k: usize,
+        d: usize,
+    },
+    Obv,
+    Vwap,
+}
+
+impl IndicatorSpec {
+    /// Parse `sma(N)`, `ema(N)`, `wma(N)`, `rsi(N)`, `macd(FAST,SLOW,SIGNAL)`,
+    /// `bbands(N,K)`, `atr(N)`, `stoch(K,D)`, `obv` or `vwap`. RSI, MACD,
+    /// Bollinger Bands, ATR and the stochastic can leave out their parameters
+    /// for the usual 14, (12,26,9), (20,2), 14 and (14,3).
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Invalid(format!(
+                "Invalid indicator {:?}: expected sma(N), ema(N), wma(N), rsi(N), \
+                 macd(FAST,SLOW,SIGNAL), bbands(N,K), atr(N), stoch(K,D), obv or vwap",
+                value
+            ))
+        };
+        let lowercase = value.trim().to_ascii_lowercase();
+        let (name, args) = match lowercase.split_once('(') {
+            Some((name, args)) => {
+                let args = args.strip_suffix(')').ok_or_else(invalid)?.trim();
+                let args: Vec<&str> = if args.is_empty() {
+                    Vec::new()
+                } else {
+                    args.split(',').map(str::trim).collect()
+                };
+                (name.trim(), args)
+            }
+            None => (lowercase.as_str(), Vec::new()),
+        };
+        let period = |arg: &str| arg.parse::<usize>().map_err(|_| invalid());
+        let spec = match (name, args.as_slice()) {
+            ("sma", [n]) => IndicatorSpec::Sma(period(n)?),
+            ("ema", [n]) => IndicatorSpec::Ema(period(n)?),
+            ("wma", [n]) => IndicatorSpec::Wma(period(n)?),
+            ("rsi", []) => IndicatorSpec::Rsi(14),
+            ("rsi", [n]) => IndicatorSpec::Rsi(period(n)?),
+            ("macd", []) => IndicatorSpec::Macd {
+                fast: 12,
+                slow: 26,
+                signal: 9,
+            },
+            ("macd", [fast, slow, signal]) => IndicatorSpec::Macd {
+                fast: period(fast)?,
+                slow: period(slow)?,
+                signal: period(signal)?,
+            },
+            ("bbands", []) => IndicatorSpec::Bollinger {
+                period: 20,
+                width: 2.0,
+            },
+            ("bbands", [n]) => IndicatorSpec::Bollinger {
+                period: period(n)?,
+                width: 2.0,
+            },
+            ("bbands", [n, k]) => IndicatorSpec::Bollinger {
+                period: period(n)?,
+                width: k.parse().map_err(|_| invalid())?,
+            },
+            ("atr", []) => IndicatorSpec::Atr(14),
+            ("atr", [n]) => IndicatorSpec::Atr(period(n)?),
+            ("stoch", []) => IndicatorSpec::Stochastic { k: 14, d: 3 },
+            ("stoch", [k, d]) => IndicatorSpec::Stochastic {
+                k: period(k)?,
+                d: period(d)?,
+            },
+            ("obv", []) => IndicatorSpec::Obv,
+            ("vwap", []) => IndicatorSpec::Vwap,
+            _ => return Err(invalid()),
+        };
+        // Catch zero periods and the like now rather than at the first bar
+        spec.start()?;
+        Ok(spec)
+    }
+
+    /// Parse a list such as `sma(20),rsi(14),macd(12,26,9)`; commas within
+    /// parentheses separate parameters
+    pub fn parse_list(value: &str) -> Result<Vec<Self>> {
+        let mut specs = Vec::new();
+        let mut depth = 0usize;
+        let mut start = 0;
+        for (index, c) in value.char_indices().chain([(value.len(), ',')]) {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    let item = value[start..index].trim();
+                    if !item.is_empty() {
+                        specs.push(Self::parse(item)?);
+                    }
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        if depth > 0 {
+            return Err(Error::Invalid(format!(
+                "Unclosed parenthesis in indicators {:?}",
+                value
+            )));
+        }
+        if specs.is_empty() {
+            return Err(Error::Invalid("No indicators given".to_string()));
+        }
+        let columns = column_names(&specs);
+        for (index, column) in columns.iter().enumerate() {
+            if columns[..index].contains(column) {
+                return Err(Error::Invalid(format!("Indicator {} given twice", column)));
+            }
+        }
+        Ok(specs)
+    }
+
+    /// Names of the columns the indicator adds, e.g. `sma_20`, or `macd_12_26_9`,
+    /// `macd_signal_12_26_9` and `macd_hist_12_26_9`
+    pub fn columns(&self) -> Vec<String> {
+        match self {
+            IndicatorSpec::Sma(n) => vec![format!("sma_{}", n)],
+            IndicatorSpec::Ema(n) => vec![format!("ema_{}", n)],
+            IndicatorSpec::Wma(n) => vec![format!("wma_{}", n)],
+            IndicatorSpec::Rsi(n) => vec![format!("rsi_{}", n)],
+            IndicatorSpec::Macd { fast, slow, signal } => {
+                let suffix = format!("{}_{}_{}", fast, slow, signal);
+                vec![
+                    format!("macd_{}", suffix),
+                    format!("macd_signal_{}", suffix),
+                    format!("macd_hist_{}", suffix),
+                ]
+            }
+            IndicatorSpec::Bollinger { period, width } => {
+                let suffix = format!("{}_{}", period, width);
+                vec![
+                    format!("bb_upper_{}", suffix),
+                    format!("bb_middle_{}", suffix),
+                    format!("bb_lower_{}", suffix),
+                ]
+            }
+            IndicatorSpec::Atr(n) => vec![format!("atr_{}", n)],
+            IndicatorSpec::Stochastic { k, d } => {
+                vec![
+                    format!("stoch_k_{}_{}", k, d),
+                    format!("stoch_d_{}_{}", k, d),
+                ]
+            }
+            IndicatorSpec::Obv => vec!["obv".to_string()],
+            // Bars already have a vwap column, the VWAP of the bar itself
+            IndicatorSpec::Vwap => vec!["day_vwap".to_string()],
+        }
+    }
+
+    fn start(&self) -> Result<Running> {
+        Ok(match *self {
+            IndicatorSpec::Sma(n) => Running::Sma(Sma::new(n)?),
+            IndicatorSpec::Ema(n) => Running::Ema(Ema::new(n)?),
+            IndicatorSpec::Wma(n) => Running::Wma(Wma::new(n)?),
+            IndicatorSpec::Rsi(n) => Running::Rsi(Rsi::new(n)?),
+            IndicatorSpec::Macd { fast, slow, signal } => {
+                Running::Macd(Macd::new(fast, slow, signal)?)
+            }
+            IndicatorSpec::Bollinger { period, width } => {
+                Running::Bollinger(BollingerBands::new(period, width)?)
+            }
+            IndicatorSpec::Atr(n) => Running::Atr(Atr::new(n)?),
+            IndicatorSpec::Stochastic { k, d } => Running::Stochastic(Stochastic::new(k, d)?),
+            IndicatorSpec::Obv => Running::Obv(Obv::new()),
+            IndicatorSpec::Vwap => Running::Vwap(Vwap::new()),
+        })
+    }
+}
+
+/// The columns of every indicator of `specs`, in order
+pub fn column_names(specs: &[IndicatorSpec]) -> Vec<String> {
+    specs.iter().flat_map(IndicatorSpec::columns).collect()
+}
+
+/// An indicator of an `IndicatorSpec` with its state
+#[derive(Debug, Clone)]
+enum Running {
+    Sma(Sma),
+    Ema(Ema),
+    Wma(Wma),
+    Rsi(Rsi),
+    Macd(Macd),
+    Bollinger(BollingerBands),
+    Atr(Atr),
+    Stochastic(Stochastic),
+    Obv(Obv),
+    Vwap(Vwap),
+}
+
+impl Running {
+    fn update(&mut self, bar: &OhlcvBar, values: &mut Vec<Option<f64>>) {
+        match self {
+            Running::Sma(sma) => values.push(sma.update(bar)),
+            Running::Ema(ema) => values.push(ema.update(bar)),
+            Running::Wma(wma) => values.push(wma.update(bar)),
+            Running::Rsi(rsi) => values.push(rsi.update(bar)),
+            Running::Macd(macd) => {
+                let value = macd.update(bar);
+                values.extend([
+                    value.map(|value| value.macd),
+                    value.map(|value| value.signal),
+                    value.map(|value| value.histogram),
+                ]);
+            }
+            Running::Bollinger(bands) => {
+                let value = bands.update(bar);
+                values.extend([
+                    value.map(|value| value.upper),
+                    value.map(|value| value.middle),
+                    value.map(|value| value.lower),
+                ]);
+            }
+            Running::Atr(atr) => values.push(atr.update(bar)),
+            Running::Stochastic(stochastic) => {
+                let value = stochastic.update(bar);
+                values.extend([value.map(|value| value.k), value.map(|value| value.d)]);
+            }
+            Running::Obv(obv) => values.push(obv.update(bar)),
+            Running::Vwap(vwap) => values.push(vwap.update(bar)),
+        }
+    }
+}
+
+/// Indicators over the bars of one symbol, giving a row of numbers per bar
+/// for the columns of `column_names`
+#[derive(Debug, Clone)]
+pub struct IndicatorColumns {
+    indicators: Vec<Running>,
+}
+
+impl IndicatorColumns {
+    pub fn new(specs: &[IndicatorSpec]) -> Result<Self> {
+        Ok(Self {
+            indicators: specs
+                .iter()
+                .map(IndicatorSpec::start)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Add the next bar and return every column at it
+    pub fn update(&mut self, bar: &OhlcvBar) -> Vec<Option<f64>> {
+        let mut values = Vec::new();
+        for indicator in &mut self.indicators {
+            indicator.update(bar, &mut values);
+        }
+        values
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{Error, Result};
+use keyring::Entry;
+
+/// Service name the credentials are stored under
+pub const SERVICE: &str = "algorithms-trading";
+/// Profile used when none is selected
+pub const DEFAULT_PROFILE: &str = "default";
+/// Variables the keychain can hold
+pub const CREDENTIAL_VARS: [&str; 2] = ["APCA_API_KEY_ID", "APCA_API_SECRET_KEY"];
+
+/// A credential name as typed on the command line: `key-id`, `secret-key`, or
+/// the variable name itself
+pub fn parse_credential(name: &str) -> Result<&'static str> {
+    match name.trim().to_lowercase().replace('_', "-").as_str() {
+        "key-id" | "apca-api-key-id" => Ok("APCA_API_KEY_ID"),
+        "secret-key" | "apca-api-secret-key" => Ok("APCA_API_SECRET_KEY"),
+        _ => Err(Error::Invalid(format!(
+            "Invalid credential: {}. Supported: key-id, secret-key",
+            name
+        ))),
+    }
+}
+
+/// API credentials of one profile in the OS keychain, through the `keyring`
+/// crate: the macOS login keychain, the Windows Credential Manager, or the
+/// Secret Service (GNOME Keyring, KWallet) on Linux and the BSDs. Values go
+/// to the keychain in-process and never appear on a command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keychain {
+    profile: String,
+}
+
+impl Keychain {
+    pub fn new(profile: Option<&str>) -> Self {
+        Self {
+            profile: profile.unwrap_or(DEFAULT_PROFILE).to_string(),
+        }
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Account name of `var` within the service, e.g. `live:APCA_API_KEY_ID`
+    pub fn account(&self, var: &str) -> String {
+        format!("{}:{}", self.profile, var)
+    }
+
+    fn entry(&self, var: &str) -> Result<Entry> {
+        Entry::new(SERVICE, &self.account(var)).map_err(|e| failed("open", &self.account(var), e))
+    }
+
+    pub fn get(&self, var: &str) -> Result<Option<String>> {
+        match self.entry(var)?.get_password() {
+            Ok(value) => Ok((!value.is_empty()).then_some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(failed("read", &self.account(var), e)),
+        }
+    }
+
+    /// Store `value`, replacing any earlier one
+    pub fn set(&self, var: &str, value: &str) -> Result<()> {
+        self.entry(var)?
+            .set_password(value)
+            .map_err(|e| failed("store", &self.account(var), e))
+    }
+
+    /// Remove the entry; `false` if there was none
+    pub fn delete(&self, var: &str) -> Result<bool> {
+        match self.entry(var)?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(e) => Err(failed("delete", &self.account(var), e)),
+        }
+    }
+
+    /// Set every credential the keychain holds for this profile as an
+    /// environment variable; the others keep their environment values.
+    /// Returns the variables set.
+    pub fn load_into_env(&self) -> Result<Vec<&'static str>> {
+        let mut loaded = Vec::new();
+        for var in CREDENTIAL_VARS {
+            if let Some(value) = self.get(var)? {
+                std::env::set_var(var, value);
+                loaded.push(var);
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+fn failed(action: &'static str, account: &str, source: keyring::Error) -> Error {
+    Error::Keychain {
+        action,
+        account: account.to_string(),
+        source,
+    }
+}
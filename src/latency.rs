@@ -0,0 +1,122 @@
+use crate::{OutputSink, Result, StreamingData};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Receive latency percentiles for one symbol, in milliseconds
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct LatencyStats {
+    pub symbol: String,
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Tracks `received_time - event_time` per symbol over the most recent
+/// `window` messages. Clones share the same samples, so one clone can be
+/// added as a sink while another reports.
+///
+/// Latencies can be negative when the local clock is behind the exchange's.
+#[derive(Debug, Clone)]
+pub struct LatencyMonitor {
+    samples: Arc<Mutex<HashMap<String, VecDeque<i64>>>>,
+    window: usize,
+}
+
+impl LatencyMonitor {
+    /// Keep the last `window` latencies per symbol
+    pub fn new(window: usize) -> Self {
+        Self {
+            samples: Arc::new(Mutex::new(HashMap::new())),
+            window: window.max(1),
+        }
+    }
+
+    pub fn record(&self, symbol: &str, latency: chrono::Duration) {
+        let micros = latency.num_microseconds().unwrap_or(i64::MAX);
+        let mut samples = self.samples.lock().unwrap();
+        let symbol_samples = samples.entry(symbol.to_string()).or_default();
+        if symbol_samples.len() == self.window {
+            symbol_samples.pop_front();
+        }
+        symbol_samples.push_back(micros);
+    }
+
+    /// Current percentiles for every symbol seen so far, sorted by symbol
+    pub fn snapshot(&self) -> Vec<LatencyStats> {
+        let samples = self.samples.lock().unwrap();
+        let mut stats: Vec<LatencyStats> = samples
+            .iter()
+            .filter(|(_, values)| !values.is_empty())
+            .map(|(symbol, values)| {
+                let mut sorted: Vec<i64> = values.iter().copied().collect();
+                sorted.sort_unstable();
+                LatencyStats {
+                    symbol: symbol.clone(),
+                    samples: sorted.len(),
+                    p50_ms: percentile(&sorted, 50.0),
+                    p95_ms: percentile(&sorted, 95.0),
+                    p99_ms: percentile(&sorted, 99.0),
+                    max_ms: to_ms(sorted[sorted.len() - 1]),
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        stats
+    }
+
+    /// Log the current percentiles, one event per symbol
+    pub fn report(&self) {
+        for stats in self.snapshot() {
+            tracing::info!(
+                symbol = %stats.symbol,
+                samples = stats.samples,
+                p50_ms = stats.p50_ms,
+                p95_ms = stats.p95_ms,
+                p99_ms = stats.p99_ms,
+                max_ms = stats.max_ms,
+                "Receive latency"
+            );
+        }
+    }
+
+    /// Call `report` every `interval`; runs until the task is dropped
+    pub async fn report_every(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately, before any samples exist
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            self.report();
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted microsecond samples
+fn percentile(sorted: &[i64], percent: f64) -> f64 {
+    let rank = ((percent / 100.0) * sorted.len() as f64).ceil() as usize;
+    to_ms(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+fn to_ms(micros: i64) -> f64 {
+    micros as f64 / 1000.0
+}
+
+impl OutputSink for LatencyMonitor {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        // Order updates are timed by the trading API, not the market data feed
+        if !matches!(data.message_type.as_str(), "t" | "q" | "b") {
+            return Ok(());
+        }
+        if let (Some(symbol), Some(event_time)) = (&data.symbol, data.event_time) {
+            self.record(symbol, data.received_time - event_time);
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
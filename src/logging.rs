@@ -0,0 +1,62 @@
+use crate::{Error, Result};
+use clap::ValueEnum;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines (default)
+    #[default]
+    Text,
+    /// One JSON object per event, including the active spans
+    Json,
+}
+
+/// Install the global tracing subscriber for operational logs.
+///
+/// Logs go to stderr, or are appended to `log_file`, so they never mix with
+/// data written to stdout. The level comes from `RUST_LOG` and defaults to `info`.
+pub fn init_logging(format: LogFormat, log_file: Option<&Path>) -> Result<()> {
+    install(format, log_file, None, BoxMakeWriter::new(std::io::stderr))
+}
+
+/// Like `init_logging`, but logs are discarded instead of written to stderr
+/// when there is no `log_file`, for modes that draw on the terminal
+pub fn init_quiet_logging(format: LogFormat, log_file: Option<&Path>) -> Result<()> {
+    install(format, log_file, None, BoxMakeWriter::new(std::io::sink))
+}
+
+/// `init_logging`, or `init_quiet_logging` when `quiet`, with the level taken
+/// from `level` (a filter such as `debug` or `algorithms_trading=trace`)
+/// instead of `RUST_LOG` when given
+pub fn init_logging_at(format: LogFormat, log_file: Option<&Path>, level: Option<&str>, quiet: bool) -> Result<()> {
+    let fallback = if quiet { BoxMakeWriter::new(std::io::sink) } else { BoxMakeWriter::new(std::io::stderr) };
+    install(format, log_file, level, fallback)
+}
+
+fn install(format: LogFormat, log_file: Option<&Path>, level: Option<&str>, fallback: BoxMakeWriter) -> Result<()> {
+    let filter = match level {
+        Some(level) => EnvFilter::try_new(level).map_err(|_| Error::Invalid(format!("Invalid log level: {}", level)))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let (writer, ansi) = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            (BoxMakeWriter::new(Mutex::new(file)), false)
+        }
+        None => (fallback, true),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi);
+    let result = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(true).try_init(),
+    };
+    result.map_err(|e| Error::Invalid(format!("Failed to initialize logging: {}", e)))
+}
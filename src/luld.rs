@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// An `l` stream message from the `lulds` channel: the limit-up/limit-down
+/// price band a symbol may trade within
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LuldBand {
+    #[serde(rename = "S")]
+    pub symbol: String,
+    #[serde(rename = "u")]
+    pub limit_up: f64,
+    #[serde(rename = "d")]
+    pub limit_down: f64,
+    /// `A` (limit-up state), `B` (limit-down state), `C` (both) or blank
+    #[serde(rename = "i", default)]
+    pub indicator: String,
+    #[serde(rename = "z", default)]
+    pub tape: String,
+    #[serde(rename = "t")]
+    pub timestamp: String,
+}
+
+/// An `i` stream message from the `imbalances` channel: an auction order
+/// imbalance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderImbalance {
+    #[serde(rename = "S")]
+    pub symbol: String,
+    /// Reference price of the imbalance
+    #[serde(rename = "p")]
+    pub price: f64,
+    #[serde(rename = "z", default)]
+    pub tape: String,
+    #[serde(rename = "t")]
+    pub timestamp: String,
+}
@@ -0,0 +1,422 @@
+use crate::daemon::shutdown_signal;
+pub use crate::market_time::eastern_offset;
+use crate::trade_updates::DEFAULT_TRADING_BASE_URL;
+use crate::{
+    run_streaming_client, DataFormat, OutputSink, StreamingConfig, StreamingData,
+    SubscriptionCommand,
+};
+use crate::{Error, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use clap::ValueEnum;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+/// Header of `trade clock` CSV output
+pub const MARKET_CLOCK_CSV_HEADER: &str = "timestamp,is_open,next_open,next_close";
+
+/// Header of `trade calendar` CSV output; times are New York local
+pub const CALENDAR_CSV_HEADER: &str = "date,open,close,session_open,session_close,early_close";
+
+/// Regular close of a full trading day; earlier ones are half days
+const REGULAR_CLOSE: &str = "16:00";
+
+/// Days of calendar fetched when looking for the next session; covers the
+/// longest run of weekends and holidays
+const LOOKAHEAD_DAYS: i64 = 10;
+/// Wait before asking again after a failed clock or calendar request
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// A part of the US equity trading day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum MarketSession {
+    /// Pre-market, from the extended session open to the regular open
+    Pre,
+    /// Regular trading hours
+    Regular,
+    /// After-hours, from the regular close to the extended session close
+    After,
+}
+
+/// `GET /v2/clock`: the market's current time and next open and close
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MarketClock {
+    /// Current time in New York, with its UTC offset
+    pub timestamp: String,
+    pub is_open: bool,
+    pub next_open: String,
+    pub next_close: String,
+}
+
+/// One trading day from `GET /v2/calendar`, in New York local time
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CalendarDay {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// Regular open, `HH:MM`
+    pub open: String,
+    /// Regular close, `HH:MM`; early on half days
+    pub close: String,
+    /// Extended session open, `HHMM`
+    #[serde(default)]
+    pub session_open: Option<String>,
+    /// Extended session close, `HHMM`
+    #[serde(default)]
+    pub session_close: Option<String>,
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H%M"))
+        .map_err(|_| Error::Invalid(format!("Invalid calendar time: {}", time)))
+}
+
+impl CalendarDay {
+    fn at(&self, date: NaiveDate, time: &str) -> Result<DateTime<Utc>> {
+        let local = date.and_time(parse_time(time)?);
+        eastern_offset(date)
+            .from_local_datetime(&local)
+            .single()
+            .map(|time| time.with_timezone(&Utc))
+            .ok_or_else(|| Error::Invalid(format!("Invalid calendar time: {} {}", self.date, time)))
+    }
+
+    /// Start and end of each selected session on this day, with adjacent
+    /// sessions merged into one window
+    pub fn windows(
+        &self,
+        sessions: &[MarketSession],
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>> {
+        let date = NaiveDate::parse_from_str(&self.date, "%Y-%m-%d")
+            .map_err(|_| Error::Invalid(format!("Invalid calendar date: {}", self.date)))?;
+        let open = self.at(date, &self.open)?;
+        let close = self.at(date, &self.close)?;
+        let session_open = self
+            .session_open
+            .as_deref()
+            .map(|t| self.at(date, t))
+            .transpose()?;
+        let session_close = self
+            .session_close
+            .as_deref()
+            .map(|t| self.at(date, t))
+            .transpose()?;
+
+        let mut selected = sessions.to_vec();
+        selected.sort();
+        selected.dedup();
+        let mut windows: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+        for session in selected {
+            let (start, end) = match session {
+                MarketSession::Pre => (session_open.unwrap_or(open), open),
+                MarketSession::Regular => (open, close),
+                MarketSession::After => (close, session_close.unwrap_or(close)),
+            };
+            if start >= end {
+                continue;
+            }
+            match windows.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => windows.push((start, end)),
+            }
+        }
+        Ok(windows)
+    }
+}
+
+impl MarketClock {
+    /// The line written for the clock; CSV lines match
+    /// `MARKET_CLOCK_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        Ok(match format {
+            DataFormat::Plain => {
+                let time = |value: &str| {
+                    DateTime::parse_from_rfc3339(value)
+                        .map(|time| time.format("%a %Y-%m-%d %H:%M ET").to_string())
+                        .unwrap_or_else(|_| value.to_string())
+                };
+                let (next, at) = if self.is_open {
+                    ("closes", &self.next_close)
+                } else {
+                    ("opens", &self.next_open)
+                };
+                let until = DateTime::parse_from_rfc3339(at)
+                    .ok()
+                    .zip(DateTime::parse_from_rfc3339(&self.timestamp).ok())
+                    .map(|(at, now)| {
+                        let minutes = (at - now).num_minutes().max(0);
+                        format!(" (in {}h {:02}m)", minutes / 60, minutes % 60)
+                    })
+                    .unwrap_or_default();
+                format!(
+                    "🕐 Market {} | now {} | {} {}{}",
+                    if self.is_open { "open" } else { "closed" },
+                    time(&self.timestamp),
+                    next,
+                    time(at),
+                    until
+                )
+            }
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{}",
+                self.timestamp, self.is_open, self.next_open, self.next_close
+            ),
+        })
+    }
+}
+
+/// `HHMM` or `HH:MM` as `HH:MM`
+fn clock_time(time: &str) -> String {
+    parse_time(time)
+        .map(|time| time.format("%H:%M").to_string())
+        .unwrap_or_else(|_| time.to_string())
+}
+
+impl CalendarDay {
+    /// Whether the regular session closes before 16:00, as on the day after
+    /// Thanksgiving
+    pub fn is_early_close(&self) -> bool {
+        clock_time(&self.close).as_str() < REGULAR_CLOSE
+    }
+
+    /// The line written for this day; CSV lines match `CALENDAR_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let optional = |time: &Option<String>| time.as_deref().map(clock_time).unwrap_or_default();
+        Ok(match format {
+            DataFormat::Plain => {
+                let weekday = NaiveDate::parse_from_str(&self.date, "%Y-%m-%d")
+                    .map(|date| date.format("%a ").to_string())
+                    .unwrap_or_default();
+                let extended = match (&self.session_open, &self.session_close) {
+                    (Some(open), Some(close)) => {
+                        format!(" | extended {}-{}", clock_time(open), clock_time(close))
+                    }
+                    _ => String::new(),
+                };
+                format!(
+                    "📅 {}{}: regular {}-{}{}{}",
+                    weekday,
+                    self.date,
+                    clock_time(&self.open),
+                    clock_time(&self.close),
+                    extended,
+                    if self.is_early_close() {
+                        " | early close"
+                    } else {
+                        ""
+                    }
+                )
+            }
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{}",
+                self.date,
+                clock_time(&self.open),
+                clock_time(&self.close),
+                optional(&self.session_open),
+                optional(&self.session_close),
+                self.is_early_close()
+            ),
+        })
+    }
+}
+
+/// The first window that has not ended by `now`, over calendar `days`
+pub fn next_window(
+    days: &[CalendarDay],
+    sessions: &[MarketSession],
+    now: DateTime<Utc>,
+) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+    for day in days {
+        if let Some(window) = day
+            .windows(sessions)?
+            .into_iter()
+            .find(|(_, end)| *end > now)
+        {
+            return Ok(Some(window));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads the Alpaca market clock and calendar
+#[derive(Debug, Clone)]
+pub struct MarketCalendar {
+    client: reqwest::Client,
+    base_url: String,
+    key: String,
+    secret: String,
+}
+
+impl MarketCalendar {
+    /// Credentials from `APCA_API_KEY_ID` and `APCA_API_SECRET_KEY`, endpoint
+    /// from `APCA_API_BASE_URL` (paper trading by default)
+    pub fn from_env() -> Result<Self> {
+        let base_url = std::env::var("APCA_API_BASE_URL")
+            .unwrap_or_else(|_| DEFAULT_TRADING_BASE_URL.to_string());
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v2").unwrap_or(base_url).to_string();
+        let var =
+            |name: &str| std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)));
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            base_url,
+            key: var("APCA_API_KEY_ID")?,
+            secret: var("APCA_API_SECRET_KEY")?,
+        })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T> {
+        Ok(self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .query(query)
+            .header("APCA-API-KEY-ID", &self.key)
+            .header("APCA-API-SECRET-KEY", &self.secret)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    pub async fn clock(&self) -> Result<MarketClock> {
+        self.get("/v2/clock", &[]).await
+    }
+
+    /// Trading days from `start` to `end`, inclusive
+    pub async fn calendar(&self, start: NaiveDate, end: NaiveDate) -> Result<Vec<CalendarDay>> {
+        self.get(
+            "/v2/calendar",
+            &[("start", start.to_string()), ("end", end.to_string())],
+        )
+        .await
+    }
+
+    /// The next window of `sessions` that has not ended yet, looked up from
+    /// the market's current date
+    pub async fn upcoming_window(
+        &self,
+        sessions: &[MarketSession],
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+        let clock = self.clock().await?;
+        let now = DateTime::parse_from_rfc3339(&clock.timestamp)
+            .map_err(|_| Error::Invalid(format!("Invalid clock timestamp: {}", clock.timestamp)))?;
+        let today = now.date_naive();
+        let days = self
+            .calendar(today, today + chrono::Duration::days(LOOKAHEAD_DAYS))
+            .await?;
+        next_window(&days, sessions, Utc::now())?.ok_or_else(|| {
+            Error::Invalid(format!("No trading sessions in the next {} days", LOOKAHEAD_DAYS))
+        })
+    }
+}
+
+fn write_market_event(config: &StreamingConfig, open: bool, until: DateTime<Utc>) {
+    let data = StreamingData {
+        received_time: Utc::now(),
+        event_time: None,
+        message_type: "market".to_string(),
+        symbol: None,
+        data: serde_json::json!({ "open": open, "until": until.to_rfc3339() }),
+    };
+    if let Err(e) = config.output_mode.write_streaming_data(&data) {
+        warn!(error = %e, "Failed to write market event");
+    }
+}
+
+/// When `run_during_sessions` connects and what it does between sessions
+#[derive(Debug, Clone)]
+pub struct SessionSchedule {
+    pub sessions: Vec<MarketSession>,
+    /// Connect this long before each session opens
+    pub connect_early: Duration,
+    /// Daemon mode: rotate output files at every close, and wait for the
+    /// next session instead of exiting when the client gives up
+    pub daemon: bool,
+}
+
+impl SessionSchedule {
+    pub fn new(sessions: Vec<MarketSession>) -> Self {
+        Self {
+            sessions,
+            connect_early: Duration::ZERO,
+            daemon: false,
+        }
+    }
+}
+
+/// Sleep until `time`; `false` if a shutdown signal came first
+async fn sleep_until(time: DateTime<Utc>) -> bool {
+    let duration = (time - Utc::now()).to_std().unwrap_or_default();
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => true,
+        _ = shutdown_signal() => {
+            info!("Received shutdown signal, shutting down gracefully");
+            false
+        }
+    }
+}
+
+/// Run the streaming client only during the scheduled sessions: sleep until
+/// the next one opens, stream until it closes, disconnect and repeat. Returns
+/// on Ctrl+C or SIGTERM, or when the client itself stops outside daemon mode.
+pub async fn run_during_sessions(
+    config: &StreamingConfig,
+    commands: &mut mpsc::UnboundedReceiver<SubscriptionCommand>,
+    calendar: &MarketCalendar,
+    schedule: &SessionSchedule,
+) -> Result<()> {
+    loop {
+        let (start, end) = match calendar.upcoming_window(&schedule.sessions).await {
+            Ok(window) => window,
+            Err(e) => {
+                warn!(error = %e, retry_in_secs = RETRY_DELAY.as_secs(), "Failed to read the market calendar");
+                if !sleep_until(Utc::now() + RETRY_DELAY).await {
+                    return Ok(());
+                }
+                continue;
+            }
+        };
+        let connect_at = start - schedule.connect_early;
+        if connect_at > Utc::now() {
+            info!(opens_at = %start, connect_at = %connect_at, "Market closed, waiting for the next session");
+            write_market_event(config, false, start);
+            if !sleep_until(connect_at).await {
+                return Ok(());
+            }
+        }
+
+        info!(opens_at = %start, closes_at = %end, "Connecting for the market session");
+        write_market_event(config, true, end);
+        tokio::select! {
+            result = run_streaming_client(config, commands) => match result {
+                Err(e) if schedule.daemon => {
+                    error!(error = %e, "Streaming failed, waiting for the next session");
+                    if !sleep_until(end).await {
+                        return Ok(());
+                    }
+                }
+                result => return result,
+            },
+            open = sleep_until(end) => {
+                if !open {
+                    return Ok(());
+                }
+                info!("Market session closed, disconnecting");
+            }
+        }
+        if schedule.daemon {
+            if let Err(e) = config.output_mode.rotate() {
+                warn!(error = %e, "Failed to rotate output at the close");
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, Utc, Weekday};
+
+/// New York's UTC offset on `date`: EDT from the second Sunday in March to
+/// the first Sunday in November, EST otherwise
+pub fn eastern_offset(date: NaiveDate) -> FixedOffset {
+    let year = date.year();
+    let dst_start = NaiveDate::from_weekday_of_month_opt(year, 3, Weekday::Sun, 2).unwrap();
+    let dst_end = NaiveDate::from_weekday_of_month_opt(year, 11, Weekday::Sun, 1).unwrap();
+    let hours = if date >= dst_start && date < dst_end {
+        -4
+    } else {
+        -5
+    };
+    FixedOffset::east_opt(hours * 3600).unwrap()
+}
+
+/// The date in New York at `time`
+pub fn new_york_date(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&eastern_offset(time.date_naive()))
+        .date_naive()
+}
+
+/// Whether `time` falls on a weekday between 04:00 and 20:00 New York time,
+/// when US equities trade in the extended and regular sessions. Holidays are
+/// not known here.
+pub fn in_extended_hours(time: DateTime<Utc>) -> bool {
+    let offset = eastern_offset(time.date_naive());
+    let local = time.with_timezone(&offset);
+    // The offset of the UTC date can differ from New York's on the night DST
+    // changes, which is a Sunday and outside trading hours either way
+    let open = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+    let close = NaiveTime::from_hms_opt(20, 0, 0).unwrap();
+    !matches!(local.weekday(), Weekday::Sat | Weekday::Sun)
+        && local.time() >= open
+        && local.time() < close
+}
@@ -0,0 +1,62 @@
+//! Interleaving of record streams that are each in time order, such as the
+//! bars of several symbols, into one stream in time order for replay.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Yields the items of several streams by ascending key, the way `merge_sorted`
+/// builds it
+pub struct MergeSorted<I: Iterator, K, F> {
+    streams: Vec<I>,
+    heads: Vec<Option<I::Item>>,
+    /// Key of each stream's next item and the stream's index
+    heap: BinaryHeap<Reverse<(K, usize)>>,
+    key: F,
+}
+
+/// Merge `streams`, each sorted by `key`, into one stream sorted by `key`.
+/// Items with equal keys come in stream order, and in their order within a
+/// stream, so the merge is stable. Only one item per stream is held at a
+/// time.
+pub fn merge_sorted<I, K, F>(streams: Vec<I>, mut key: F) -> MergeSorted<I, K, F>
+where
+    I: Iterator,
+    K: Ord,
+    F: FnMut(&I::Item) -> K,
+{
+    let mut streams = streams;
+    let mut heads = Vec::with_capacity(streams.len());
+    let mut heap = BinaryHeap::with_capacity(streams.len());
+    for (index, stream) in streams.iter_mut().enumerate() {
+        let head = stream.next();
+        if let Some(item) = &head {
+            heap.push(Reverse((key(item), index)));
+        }
+        heads.push(head);
+    }
+    MergeSorted {
+        streams,
+        heads,
+        heap,
+        key,
+    }
+}
+
+impl<I, K, F> Iterator for MergeSorted<I, K, F>
+where
+    I: Iterator,
+    K: Ord,
+    F: FnMut(&I::Item) -> K,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, index)) = self.heap.pop()?;
+        let item = self.heads[index].take();
+        self.heads[index] = self.streams[index].next();
+        if let Some(next) = &self.heads[index] {
+            self.heap.push(Reverse(((self.key)(next), index)));
+        }
+        item
+    }
+}
@@ -0,0 +1,93 @@
+//! What a downloaded file holds and how it was made, so it is self-describing
+//! long after the command that wrote it is forgotten. JSON lines files start
+//! with it as a `{"_meta": ...}` record; other files get a `.meta.json`
+//! sidecar.
+
+use crate::Result;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+
+/// Key of the record that holds the metadata in a JSON lines file
+pub const METADATA_KEY: &str = "_meta";
+
+/// The request behind a download
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DownloadMetadata {
+    /// `algorithms-trading <version>`
+    pub generator: String,
+    /// bars, trades or auctions
+    pub data: String,
+    pub feed: String,
+    pub symbols: Vec<String>,
+    /// Bar timeframe; `None` for trades and auctions
+    pub timeframe: Option<String>,
+    /// Price adjustment of bars; `None` for trades and auctions
+    pub adjustment: Option<String>,
+    pub start: String,
+    pub end: String,
+    pub generated_at: DateTime<Utc>,
+}
+
+impl DownloadMetadata {
+    /// Metadata generated now by this build
+    pub fn new(data: &str, feed: &str, symbols: Vec<String>, start: &str, end: &str) -> Self {
+        Self {
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            data: data.to_string(),
+            feed: feed.to_string(),
+            symbols,
+            timeframe: None,
+            adjustment: None,
+            start: start.to_string(),
+            end: end.to_string(),
+            generated_at: Utc::now(),
+        }
+    }
+
+    /// The same download, of `symbols` only, for a file that holds fewer
+    pub fn for_symbols(&self, symbols: Vec<String>) -> Self {
+        Self {
+            symbols,
+            ..self.clone()
+        }
+    }
+
+    /// The first line of a JSON lines file
+    pub fn json_line(&self) -> Result<String> {
+        Ok(format!(
+            "{{\"{}\":{}}}",
+            METADATA_KEY,
+            serde_json::to_string(self)?
+        ))
+    }
+
+    /// The metadata in a line `json_line` wrote, or `None` for any other line
+    pub fn from_json_line(line: &str) -> Option<Self> {
+        if !is_metadata_line(line) {
+            return None;
+        }
+        let mut value: serde_json::Value = serde_json::from_str(line).ok()?;
+        serde_json::from_value(value.get_mut(METADATA_KEY)?.take()).ok()
+    }
+
+    /// Write the sidecar of the file at `path`
+    pub fn write_sidecar(&self, path: &Path) -> Result<()> {
+        std::fs::write(sidecar_path(path), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// `bars.csv` is described by `bars.csv.meta.json`
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".meta.json");
+    PathBuf::from(path)
+}
+
+/// Whether a line of a JSON lines file is the metadata record, which readers
+/// of the records skip
+pub fn is_metadata_line(line: &str) -> bool {
+    line.trim_start()
+        .strip_prefix('{')
+        .is_some_and(|rest| rest.trim_start().starts_with("\"_meta\""))
+}
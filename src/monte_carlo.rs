@@ -0,0 +1,178 @@
+//! Monte Carlo robustness check of a backtest: the profits of its round
+//! trips are drawn in other orders to build thousands of alternative equity
+//! curves, showing how much of the result was down to the order the trades
+//! happened in.
+//!
+//! Shuffling keeps every trade once, so each curve ends at the same equity
+//! and only the drawdowns vary. Bootstrapping draws the same number of trades
+//! with replacement, so the final equity varies too. Curves move a trade at
+//! a time, so their drawdowns leave out moves within a trade and are compared
+//! best with each other rather than with the backtest's equity curve.
+
+use crate::{Error, Result};
+
+/// How the trades of each simulated curve are drawn
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Resampling {
+    /// As many trades as the backtest had, drawn with replacement
+    Bootstrap,
+    /// The backtest's trades in a random order
+    Shuffle,
+}
+
+/// Settings of a simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonteCarloConfig {
+    /// Number of equity curves
+    pub runs: usize,
+    pub method: Resampling,
+    /// Seed of the random generator, so a simulation can be repeated
+    pub seed: Option<u64>,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        Self {
+            runs: 1000,
+            method: Resampling::Bootstrap,
+            seed: None,
+        }
+    }
+}
+
+/// Percentiles of a simulated figure
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Distribution {
+    pub mean: f64,
+    pub min: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    /// The distribution of `values`, which must not be empty
+    fn new(mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            min: values[0],
+            p5: percentile(&values, 5.0),
+            p25: percentile(&values, 25.0),
+            median: percentile(&values, 50.0),
+            p75: percentile(&values, 75.0),
+            p95: percentile(&values, 95.0),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+impl Distribution {
+    /// The figures with their names, from the mean to the maximum
+    pub fn values(&self) -> [(&'static str, f64); 8] {
+        [
+            ("mean", self.mean),
+            ("min", self.min),
+            ("p5", self.p5),
+            ("p25", self.p25),
+            ("median", self.median),
+            ("p75", self.p75),
+            ("p95", self.p95),
+            ("max", self.max),
+        ]
+    }
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = ((percent / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// What the simulated equity curves did
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MonteCarloResult {
+    pub method: Resampling,
+    pub runs: usize,
+    /// Trades in each curve
+    pub trades: usize,
+    /// Largest fall of each curve from a peak, as a fraction of the peak
+    pub max_drawdown: Distribution,
+    pub final_equity: Distribution,
+    /// Fraction of the curves that end below the initial cash
+    pub probability_of_loss: f64,
+}
+
+/// Largest fall of the equity from `initial` after each profit in `pnls`,
+/// as a fraction of the peak, and the equity at the end
+fn max_drawdown(initial: f64, pnls: impl IntoIterator<Item = f64>) -> (f64, f64) {
+    let mut equity = initial;
+    let mut peak = initial;
+    let mut drawdown: f64 = 0.0;
+    for pnl in pnls {
+        equity += pnl;
+        peak = peak.max(equity);
+        if peak > 0.0 {
+            drawdown = drawdown.max((peak - equity) / peak);
+        }
+    }
+    (drawdown, equity)
+}
+
+/// Simulate `config.runs` equity curves starting at `initial_cash` from the
+/// profits of the trades in `pnls`
+pub fn simulate(
+    pnls: &[f64],
+    initial_cash: f64,
+    config: &MonteCarloConfig,
+) -> Result<MonteCarloResult> {
+    if config.runs == 0 {
+        return Err(Error::Invalid(
+            "Monte Carlo runs must be over 0".to_string(),
+        ));
+    }
+    if pnls.is_empty() {
+        return Err(Error::Invalid(
+            "No trades to simulate: the backtest made no round trips".to_string(),
+        ));
+    }
+    let mut rng = config
+        .seed
+        .map(fastrand::Rng::with_seed)
+        .unwrap_or_default();
+    let mut order = pnls.to_vec();
+    let mut drawdowns = Vec::with_capacity(config.runs);
+    let mut finals = Vec::with_capacity(config.runs);
+    for _ in 0..config.runs {
+        let (drawdown, equity) = match config.method {
+            Resampling::Bootstrap => max_drawdown(
+                initial_cash,
+                (0..pnls.len()).map(|_| pnls[rng.usize(..pnls.len())]),
+            ),
+            Resampling::Shuffle => {
+                rng.shuffle(&mut order);
+                max_drawdown(initial_cash, order.iter().copied())
+            }
+        };
+        drawdowns.push(drawdown);
+        finals.push(equity);
+    }
+    let losses = finals
+        .iter()
+        .filter(|equity| **equity < initial_cash)
+        .count();
+    Ok(MonteCarloResult {
+        method: config.method,
+        runs: config.runs,
+        trades: pnls.len(),
+        max_drawdown: Distribution::new(drawdowns),
+        final_equity: Distribution::new(finals),
+        probability_of_loss: losses as f64 / config.runs as f64,
+    })
+}
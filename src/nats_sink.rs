@@ -0,0 +1,167 @@
+use crate::{Error, OutputSink, Result, StreamingData};
+use std::sync::{mpsc as std_mpsc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone)]
+pub struct NatsConfig {
+    pub url: String,
+    /// JetStream stream to persist into; plain core NATS publishing when `None`
+    pub stream: Option<String>,
+    pub subject_prefix: String,
+    pub credentials_file: Option<String>,
+    /// Maximum age of persisted messages (JetStream only, 0 keeps them forever)
+    pub max_age: Duration,
+}
+
+impl NatsConfig {
+    pub fn from_env() -> Self {
+        Self {
+            url: std::env::var("NATS_URL").unwrap_or_else(|_| "nats://127.0.0.1:4222".to_string()),
+            stream: std::env::var("NATS_STREAM").ok(),
+            subject_prefix: std::env::var("NATS_SUBJECT_PREFIX").unwrap_or_else(|_| "md".to_string()),
+            credentials_file: std::env::var("NATS_CREDS").ok(),
+            max_age: std::env::var("NATS_MAX_AGE_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Subject for a record: `<prefix>.<symbol>.<type>`, or `<prefix>.<type>` for
+/// messages that are not tied to a symbol
+pub fn nats_subject(prefix: &str, data: &StreamingData) -> String {
+    let message_type = match data.message_type.as_str() {
+        "t" => "trades",
+        "q" => "quotes",
+        "b" => "bars",
+        "d" => "daily_bars",
+        "u" => "updated_bars",
+        "s" => "statuses",
+        "l" => "lulds",
+        "i" => "imbalances",
+        "trade_update" => "trade_updates",
+        other => other,
+    };
+    match &data.symbol {
+        // '.' separates subject tokens, so class shares such as BRK.B need escaping
+        Some(symbol) => format!("{}.{}.{}", prefix, symbol.replace('.', "_"), message_type),
+        None => format!("{}.{}", prefix, message_type),
+    }
+}
+
+enum NatsCommand {
+    Publish(String, Vec<u8>),
+    Flush,
+    Close(std_mpsc::Sender<()>),
+}
+
+/// Publishes each record as JSON to NATS, optionally persisting through JetStream.
+///
+/// Publishing happens on a background task so the streaming callback never
+/// waits on the network.
+#[derive(Debug)]
+pub struct NatsSink {
+    prefix: String,
+    sender: Mutex<Option<mpsc::UnboundedSender<NatsCommand>>>,
+}
+
+impl NatsSink {
+    pub async fn connect(config: NatsConfig) -> Result<Self> {
+        let mut options = async_nats::ConnectOptions::new();
+        if let Some(path) = &config.credentials_file {
+            options = options.credentials_file(path).await?;
+        }
+        let client = options
+            .connect(config.url.as_str())
+            .await
+            .map_err(|e| Error::Nats(e.into()))?;
+
+        let jetstream = match &config.stream {
+            Some(stream) => {
+                let context = async_nats::jetstream::new(client.clone());
+                context
+                    .get_or_create_stream(async_nats::jetstream::stream::Config {
+                        name: stream.clone(),
+                        subjects: vec![format!("{}.>", config.subject_prefix)],
+                        max_age: config.max_age,
+                        ..Default::default()
+                    })
+                    .await
+                    .map_err(|e| Error::Nats(e.into()))?;
+                Some(context)
+            }
+            None => None,
+        };
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(publish_loop(client, jetstream, receiver));
+
+        Ok(Self {
+            prefix: config.subject_prefix,
+            sender: Mutex::new(Some(sender)),
+        })
+    }
+
+    fn send(&self, command: NatsCommand) -> Result<()> {
+        match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => sender
+                .send(command)
+                .map_err(|_| Error::Stopped("NATS publisher task has stopped".to_string())),
+            None => Err(Error::Stopped("NATS sink is closed".to_string())),
+        }
+    }
+}
+
+async fn publish_loop(
+    client: async_nats::Client,
+    jetstream: Option<async_nats::jetstream::Context>,
+    mut receiver: mpsc::UnboundedReceiver<NatsCommand>,
+) {
+    while let Some(command) = receiver.recv().await {
+        let result = match command {
+            NatsCommand::Publish(subject, payload) => match &jetstream {
+                Some(context) => match context.publish(subject, payload.into()).await {
+                    Ok(ack) => ack.await.map(|_| ()).map_err(async_nats::Error::from),
+                    Err(e) => Err(e.into()),
+                },
+                None => client.publish(subject, payload.into()).await.map_err(async_nats::Error::from),
+            },
+            NatsCommand::Flush => client.flush().await.map_err(async_nats::Error::from),
+            NatsCommand::Close(done) => {
+                let _ = client.flush().await;
+                let _ = done.send(());
+                break;
+            }
+        };
+        if let Err(e) = result {
+            tracing::error!(error = %e, "NATS publish failed");
+        }
+    }
+}
+
+impl OutputSink for NatsSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        self.send(NatsCommand::Publish(nats_subject(&self.prefix, data), serde_json::to_vec(data)?))
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        self.send(NatsCommand::Flush)
+    }
+
+    fn close(&self) -> crate::Result<()> {
+        let (done_tx, done_rx) = std_mpsc::channel();
+        self.send(NatsCommand::Close(done_tx))?;
+        self.sender.lock().unwrap().take();
+
+        // Wait for queued messages to drain when the runtime allows blocking
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread {
+                tokio::task::block_in_place(|| done_rx.recv_timeout(Duration::from_secs(10)).ok());
+            }
+        }
+        Ok(())
+    }
+}
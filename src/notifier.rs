@@ -0,0 +1,265 @@
+use crate::webhook::Webhook;
+use crate::{Error, OutputSink, Result, StreamingData, TradingStatus};
+use std::path::Path;
+
+/// Events that can be sent to a notifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEvent {
+    /// The stream dropped and the client is about to reconnect
+    Disconnect,
+    /// The client gave up after `max_retries` failed attempts
+    RetriesExhausted,
+    /// Alpaca sent an `error` message
+    Error,
+    /// An alert rule or script raised an alert
+    Alert,
+    /// Trading in a symbol on the `statuses` channel was halted or paused
+    Halt,
+}
+
+impl NotifyEvent {
+    pub const ALL: [NotifyEvent; 5] = [
+        NotifyEvent::Disconnect,
+        NotifyEvent::RetriesExhausted,
+        NotifyEvent::Error,
+        NotifyEvent::Alert,
+        NotifyEvent::Halt,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotifyEvent::Disconnect => "disconnect",
+            NotifyEvent::RetriesExhausted => "retries_exhausted",
+            NotifyEvent::Error => "error",
+            NotifyEvent::Alert => "alert",
+            NotifyEvent::Halt => "halt",
+        }
+    }
+}
+
+impl std::str::FromStr for NotifyEvent {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        NotifyEvent::ALL
+            .into_iter()
+            .find(|event| event.name() == s.trim().to_lowercase())
+            .ok_or_else(|| {
+                Error::Invalid(format!(
+                    "Invalid notify event: {}. Supported: disconnect, retries_exhausted, error, alert, halt",
+                    s
+                ))
+            })
+    }
+}
+
+/// Where notifications go and how the payload is shaped
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotifierTarget {
+    /// POST `{"event", "text", "time", "symbol", "data"}`
+    Webhook { url: String },
+    /// POST `{"text"}` to a Slack incoming webhook
+    Slack { url: String },
+    /// Send a message through the Telegram Bot API
+    Telegram { bot_token: String, chat_id: String },
+}
+
+impl NotifierTarget {
+    fn url(&self) -> String {
+        match self {
+            NotifierTarget::Webhook { url } | NotifierTarget::Slack { url } => url.clone(),
+            NotifierTarget::Telegram { bot_token, .. } => {
+                format!("https://api.telegram.org/bot{}/sendMessage", bot_token)
+            }
+        }
+    }
+
+    /// JSON body announcing `event`
+    pub fn payload(
+        &self,
+        event: NotifyEvent,
+        text: &str,
+        data: &StreamingData,
+    ) -> serde_json::Value {
+        match self {
+            NotifierTarget::Webhook { .. } => serde_json::json!({
+                "event": event.name(),
+                "text": text,
+                "time": data.received_time,
+                "symbol": data.symbol,
+                "data": data.data,
+            }),
+            NotifierTarget::Slack { .. } => serde_json::json!({ "text": text }),
+            NotifierTarget::Telegram { chat_id, .. } => {
+                serde_json::json!({ "chat_id": chat_id, "text": text })
+            }
+        }
+    }
+}
+
+/// One `[[notifier]]` entry of a notifier config file
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct NotifierConfig {
+    #[serde(flatten)]
+    pub target: NotifierTarget,
+    /// Defaults to every event
+    #[serde(default = "all_events")]
+    pub events: Vec<NotifyEvent>,
+}
+
+fn all_events() -> Vec<NotifyEvent> {
+    NotifyEvent::ALL.to_vec()
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct NotifierFile {
+    #[serde(default, rename = "notifier")]
+    notifiers: Vec<NotifierConfig>,
+}
+
+impl NotifierConfig {
+    /// Read the `[[notifier]]` entries of a TOML file
+    pub fn load(path: &Path) -> Result<Vec<Self>> {
+        let source = std::fs::read_to_string(path)?;
+        Self::parse(&source).map_err(|e| Error::Invalid(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn parse(source: &str) -> Result<Vec<Self>> {
+        let file: NotifierFile = toml::from_str(source)
+            .map_err(|e| Error::Invalid(format!("Invalid notifier config: {}", e)))?;
+        Ok(file.notifiers)
+    }
+
+    /// Notifiers configured through `NOTIFY_WEBHOOK_URL`, `NOTIFY_SLACK_URL`
+    /// and `NOTIFY_TELEGRAM_TOKEN` with `NOTIFY_TELEGRAM_CHAT_ID`, each sent
+    /// the events listed in `NOTIFY_EVENTS` (default all)
+    pub fn from_env() -> Result<Vec<Self>> {
+        let events = match std::env::var("NOTIFY_EVENTS") {
+            Ok(list) => list
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<_>>>()?,
+            Err(_) => all_events(),
+        };
+        let mut targets = Vec::new();
+        if let Ok(url) = std::env::var("NOTIFY_WEBHOOK_URL") {
+            targets.push(NotifierTarget::Webhook { url });
+        }
+        if let Ok(url) = std::env::var("NOTIFY_SLACK_URL") {
+            targets.push(NotifierTarget::Slack { url });
+        }
+        if let (Ok(bot_token), Ok(chat_id)) = (
+            std::env::var("NOTIFY_TELEGRAM_TOKEN"),
+            std::env::var("NOTIFY_TELEGRAM_CHAT_ID"),
+        ) {
+            targets.push(NotifierTarget::Telegram { bot_token, chat_id });
+        }
+        Ok(targets
+            .into_iter()
+            .map(|target| NotifierConfig {
+                target,
+                events: events.clone(),
+            })
+            .collect())
+    }
+}
+
+/// The event a record announces, with a one-line description, or `None` for
+/// records that are never notified
+pub fn notification(data: &StreamingData) -> Option<(NotifyEvent, String)> {
+    let text = |key: &str| data.data[key].as_str().unwrap_or("?").to_string();
+    match data.message_type.as_str() {
+        "disconnect" => match data.data["retry_in_secs"].as_u64() {
+            Some(secs) => Some((
+                NotifyEvent::Disconnect,
+                format!(
+                    "Stream disconnected (attempt {}): {}. Retrying in {}s",
+                    crate::format::attempt_label(&data.data),
+                    text("error"),
+                    secs
+                ),
+            )),
+            None => Some((
+                NotifyEvent::RetriesExhausted,
+                format!(
+                    "Streaming client gave up after {} attempts: {}",
+                    data.data["attempt"],
+                    text("error")
+                ),
+            )),
+        },
+        "error" => Some((
+            NotifyEvent::Error,
+            format!("Alpaca error: {}", data.data.as_str().unwrap_or("?")),
+        )),
+        "s" => {
+            let status: TradingStatus = serde_json::from_value(data.data.clone()).ok()?;
+            status.is_halt().then(|| {
+                (
+                    NotifyEvent::Halt,
+                    format!("{} halted: {}", status.symbol, status.description()),
+                )
+            })
+        }
+        "alert" => Some((
+            NotifyEvent::Alert,
+            format!(
+                "Alert: {} - {}",
+                data.symbol.as_deref().unwrap_or("-"),
+                text("message")
+            ),
+        )),
+        _ => None,
+    }
+}
+
+/// Sends disconnects, Alpaca errors, halts and alerts that pass through it as a sink
+/// to webhooks, Slack or Telegram. Delivery happens in the background; call
+/// `shutdown` before exiting so queued notifications are not lost.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    targets: Vec<(NotifierConfig, Webhook)>,
+}
+
+impl Notifier {
+    /// Start one delivery task per notifier; must be called inside a Tokio
+    /// runtime
+    pub fn spawn(configs: Vec<NotifierConfig>) -> Result<Self> {
+        let targets = configs
+            .into_iter()
+            .map(|config| {
+                let webhook = Webhook::spawn(&config.target.url())?;
+                Ok((config, webhook))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { targets })
+    }
+
+    /// Deliver the queued notifications and stop the delivery tasks
+    pub async fn shutdown(&self) {
+        for (_, webhook) in &self.targets {
+            webhook.shutdown().await;
+        }
+    }
+}
+
+impl OutputSink for Notifier {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        let Some((event, text)) = notification(data) else {
+            return Ok(());
+        };
+        for (config, webhook) in &self.targets {
+            if config.events.contains(&event) {
+                webhook.post(config.target.payload(event, &text, data));
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
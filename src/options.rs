@@ -0,0 +1,606 @@
+//! Option contracts and their history: the contract list of an underlying
+//! from the trading API, bars and trades from the options market data API,
+//! and parsing of OCC contract symbols such as `AAPL240119C00190000`.
+
+use crate::config::DEFAULT_DATA_URL;
+use crate::rate_limit::RateLimiter;
+use crate::retry::{retry_request, HttpError, RetryPolicy, DEFAULT_REQUEST_RETRIES};
+use crate::trade_updates::DEFAULT_TRADING_BASE_URL;
+use crate::{DataFormat, Error, Result};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Header of the CSV written for option contracts
+pub const OPTION_CONTRACTS_CSV_HEADER: &str = "symbol,underlying,expiration,right,strike,style,status,tradable,open_interest,open_interest_date,close_price,close_price_date";
+/// Header of the CSV written for option bars
+pub const OPTION_BARS_CSV_HEADER: &str =
+    "symbol,underlying,expiration,right,strike,timestamp,open,high,low,close,volume,trade_count,vwap";
+/// Header of the CSV written for option trades
+pub const OPTION_TRADES_CSV_HEADER: &str =
+    "symbol,underlying,expiration,right,strike,timestamp,price,size,exchange,condition";
+
+/// Call or put
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionRight {
+    Call,
+    Put,
+}
+
+impl OptionRight {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptionRight::Call => "call",
+            OptionRight::Put => "put",
+        }
+    }
+}
+
+/// The parts of an OCC option symbol: the root (1-6 characters), the
+/// expiration as YYMMDD, `C` or `P`, and the strike times 1000 in 8 digits
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OccSymbol {
+    pub underlying: String,
+    pub expiration: NaiveDate,
+    pub right: OptionRight,
+    pub strike: f64,
+}
+
+impl OccSymbol {
+    /// Parse `AAPL240119C00190000`; the space-padded 21-character form
+    /// `AAPL  240119C00190000` is accepted too
+    pub fn parse(symbol: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Invalid(format!("Invalid OCC option symbol: {}. Expected ROOT + YYMMDD + C/P + 8-digit strike, e.g. AAPL240119C00190000", symbol))
+        };
+        let symbol_upper = symbol.trim().to_uppercase();
+        if !symbol_upper.is_ascii() || symbol_upper.len() < 16 {
+            return Err(invalid());
+        }
+        let (root, rest) = symbol_upper.split_at(symbol_upper.len() - 15);
+        let root = root.trim_end();
+        if root.is_empty() || root.len() > 6 || !root.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid());
+        }
+        let expiration = NaiveDate::parse_from_str(&rest[..6], "%y%m%d").map_err(|_| invalid())?;
+        let right = match &rest[6..7] {
+            "C" => OptionRight::Call,
+            "P" => OptionRight::Put,
+            _ => return Err(invalid()),
+        };
+        let strike = &rest[7..];
+        if !strike.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let strike: u64 = strike.parse().map_err(|_| invalid())?;
+        Ok(Self {
+            underlying: root.to_string(),
+            expiration,
+            right,
+            strike: strike as f64 / 1000.0,
+        })
+    }
+
+    /// `AAPL 2024-01-19 call $190.00`
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {} {} ${:.2}",
+            self.underlying,
+            self.expiration.format("%Y-%m-%d"),
+            self.right.as_str(),
+            self.strike
+        )
+    }
+
+    /// The `underlying,expiration,right,strike` CSV columns
+    fn csv_fields(&self) -> String {
+        format!(
+            "{},{},{},{}",
+            self.underlying,
+            self.expiration.format("%Y-%m-%d"),
+            self.right.as_str(),
+            self.strike
+        )
+    }
+}
+
+/// The OCC symbol, e.g. `AAPL240119C00190000`
+impl fmt::Display for OccSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{:08}",
+            self.underlying,
+            self.expiration.format("%y%m%d"),
+            match self.right {
+                OptionRight::Call => 'C',
+                OptionRight::Put => 'P',
+            },
+            (self.strike * 1000.0).round() as u64
+        )
+    }
+}
+
+/// One contract from `GET /v2/options/contracts`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OptionContract {
+    pub symbol: String,
+    #[serde(flatten)]
+    pub occ: OccSymbol,
+    /// `american` or `european`
+    pub style: String,
+    /// `active` or `inactive`
+    pub status: String,
+    pub tradable: bool,
+    pub open_interest: Option<u64>,
+    pub open_interest_date: Option<String>,
+    pub close_price: Option<f64>,
+    pub close_price_date: Option<String>,
+}
+
+/// A contract as the trading API sends it; numbers may come as strings
+#[derive(Debug, serde::Deserialize)]
+struct ApiContract {
+    symbol: String,
+    #[serde(default)]
+    style: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    tradable: bool,
+    #[serde(default)]
+    open_interest: Option<Value>,
+    #[serde(default)]
+    open_interest_date: Option<String>,
+    #[serde(default)]
+    close_price: Option<Value>,
+    #[serde(default)]
+    close_price_date: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ContractsPage {
+    #[serde(default)]
+    option_contracts: Option<Vec<ApiContract>>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+/// A number sent either as a JSON number or as a string
+fn number(value: Option<Value>) -> Option<f64> {
+    match value? {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The contracts of one response page, and the token of the next page if
+/// there is one
+pub fn parse_contracts_page(body: &str) -> Result<(Vec<OptionContract>, Option<String>)> {
+    let page: ContractsPage = serde_json::from_str(body)?;
+    let contracts = page
+        .option_contracts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|contract| {
+            Ok(OptionContract {
+                occ: OccSymbol::parse(&contract.symbol)?,
+                symbol: contract.symbol,
+                style: contract.style,
+                status: contract.status,
+                tradable: contract.tradable,
+                open_interest: number(contract.open_interest).map(|n| n as u64),
+                open_interest_date: contract.open_interest_date,
+                close_price: number(contract.close_price),
+                close_price_date: contract.close_price_date,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok((contracts, page.next_page_token))
+}
+
+impl OptionContract {
+    /// The line written for this contract; CSV lines match
+    /// `OPTION_CONTRACTS_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        Ok(match format {
+            DataFormat::Plain => format!(
+                "📄 {}: {} | {} | {}{} | OI: {} | Close: {}",
+                self.symbol,
+                self.occ.describe(),
+                self.style,
+                self.status,
+                if self.tradable { "" } else { ", not tradable" },
+                self.open_interest
+                    .map(|oi| oi.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                self.close_price
+                    .map(|price| format!("${:.2}", price))
+                    .unwrap_or_else(|| "-".to_string())
+            ),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{},{}",
+                self.symbol,
+                self.occ.csv_fields(),
+                self.style,
+                self.status,
+                self.tradable,
+                optional(self.open_interest.map(|oi| oi.to_string())),
+                optional(self.open_interest_date.clone()),
+                optional(self.close_price.map(|price| price.to_string())),
+                optional(self.close_price_date.clone())
+            ),
+        })
+    }
+}
+
+/// One bar from `GET /v1beta1/options/bars`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OptionBar {
+    pub symbol: String,
+    #[serde(flatten)]
+    pub occ: OccSymbol,
+    pub timestamp: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    pub trade_count: u64,
+    pub vwap: f64,
+}
+
+/// One trade from `GET /v1beta1/options/trades`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OptionTrade {
+    pub symbol: String,
+    #[serde(flatten)]
+    pub occ: OccSymbol,
+    pub timestamp: String,
+    pub price: f64,
+    pub size: u64,
+    pub exchange: String,
+    pub condition: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ApiBar {
+    t: String,
+    o: f64,
+    h: f64,
+    l: f64,
+    c: f64,
+    v: u64,
+    #[serde(default)]
+    n: u64,
+    #[serde(default)]
+    vw: f64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ApiTrade {
+    t: String,
+    p: f64,
+    s: u64,
+    #[serde(default)]
+    x: String,
+    #[serde(default)]
+    c: String,
+}
+
+/// A page of the options data API, keyed by contract symbol
+#[derive(Debug, serde::Deserialize)]
+struct DataPage<T> {
+    #[serde(default = "BTreeMap::new", alias = "bars", alias = "trades")]
+    items: BTreeMap<String, Vec<T>>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+/// The bars of one response page, and the token of the next page if there
+/// is one
+pub fn parse_option_bars_page(body: &str) -> Result<(Vec<OptionBar>, Option<String>)> {
+    let page: DataPage<ApiBar> = serde_json::from_str(body)?;
+    let mut bars = Vec::new();
+    for (symbol, items) in page.items {
+        let occ = OccSymbol::parse(&symbol)?;
+        bars.extend(items.into_iter().map(|bar| OptionBar {
+            symbol: symbol.clone(),
+            occ: occ.clone(),
+            timestamp: bar.t,
+            open: bar.o,
+            high: bar.h,
+            low: bar.l,
+            close: bar.c,
+            volume: bar.v,
+            trade_count: bar.n,
+            vwap: bar.vw,
+        }));
+    }
+    Ok((bars, page.next_page_token))
+}
+
+/// The trades of one response page, and the token of the next page if there
+/// is one
+pub fn parse_option_trades_page(body: &str) -> Result<(Vec<OptionTrade>, Option<String>)> {
+    let page: DataPage<ApiTrade> = serde_json::from_str(body)?;
+    let mut trades = Vec::new();
+    for (symbol, items) in page.items {
+        let occ = OccSymbol::parse(&symbol)?;
+        trades.extend(items.into_iter().map(|trade| OptionTrade {
+            symbol: symbol.clone(),
+            occ: occ.clone(),
+            timestamp: trade.t,
+            price: trade.p,
+            size: trade.s,
+            exchange: trade.x,
+            condition: trade.c,
+        }));
+    }
+    Ok((trades, page.next_page_token))
+}
+
+impl OptionBar {
+    /// The line written for this bar; CSV lines match `OPTION_BARS_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        Ok(match format {
+            DataFormat::Plain => format!(
+                "📊 {} ({}): {} | O: ${:.2} H: ${:.2} L: ${:.2} C: ${:.2} | Vol: {}",
+                self.symbol,
+                self.occ.describe(),
+                self.timestamp,
+                self.open,
+                self.high,
+                self.low,
+                self.close,
+                self.volume
+            ),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{},{},{}",
+                self.symbol,
+                self.occ.csv_fields(),
+                self.timestamp,
+                self.open,
+                self.high,
+                self.low,
+                self.close,
+                self.volume,
+                self.trade_count,
+                self.vwap
+            ),
+        })
+    }
+}
+
+impl OptionTrade {
+    /// The line written for this trade; CSV lines match
+    /// `OPTION_TRADES_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        Ok(match format {
+            DataFormat::Plain => format!(
+                "🔄 {} ({}): {} | ${:.2} x {} | Exchange: {} | Condition: {}",
+                self.symbol,
+                self.occ.describe(),
+                self.timestamp,
+                self.price,
+                self.size,
+                self.exchange,
+                self.condition
+            ),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{}",
+                self.symbol,
+                self.occ.csv_fields(),
+                self.timestamp,
+                self.price,
+                self.size,
+                self.exchange,
+                self.condition
+            ),
+        })
+    }
+}
+
+/// Filters of a contract listing; `None` leaves a bound open
+#[derive(Debug, Clone, Default)]
+pub struct ContractQuery {
+    pub underlyings: Vec<String>,
+    pub expiration_from: Option<NaiveDate>,
+    pub expiration_to: Option<NaiveDate>,
+    pub strike_min: Option<f64>,
+    pub strike_max: Option<f64>,
+    pub right: Option<OptionRight>,
+}
+
+/// Client for option contracts on the trading API and option history on the
+/// market data API
+pub struct OptionsClient {
+    client: reqwest::Client,
+    base_url: String,
+    data_url: String,
+    key: String,
+    secret: String,
+    retry: RetryPolicy,
+}
+
+impl OptionsClient {
+    /// Credentials from `APCA_API_KEY_ID` / `APCA_API_SECRET_KEY`, contracts
+    /// from `APCA_API_BASE_URL` (paper trading by default) and history from
+    /// `APCA_API_DATA_URL`
+    pub fn from_env() -> Result<Self> {
+        let var =
+            |name: &str| std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)));
+        let base_url = std::env::var("APCA_API_BASE_URL")
+            .unwrap_or_else(|_| DEFAULT_TRADING_BASE_URL.to_string());
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v2").unwrap_or(base_url).to_string();
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            base_url,
+            data_url: std::env::var("APCA_API_DATA_URL")
+                .unwrap_or_else(|_| DEFAULT_DATA_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            key: var("APCA_API_KEY_ID")?,
+            secret: var("APCA_API_SECRET_KEY")?,
+            retry: RetryPolicy::for_requests(DEFAULT_REQUEST_RETRIES),
+        })
+    }
+
+    /// Retry each request up to `max_retries` times after a 429, a 5xx or a
+    /// network error
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.retry = RetryPolicy::for_requests(max_retries);
+        self
+    }
+
+    /// Every active contract matching `query`, sorted by expiration, right
+    /// and strike
+    #[tracing::instrument(name = "fetch_contracts", skip(self), fields(underlyings = %query.underlyings.join(",")))]
+    pub async fn fetch_contracts(&self, query: &ContractQuery) -> Result<Vec<OptionContract>> {
+        let underlyings = query.underlyings.join(",");
+        let date = |date: Option<NaiveDate>| date.map(|d| d.format("%Y-%m-%d").to_string());
+        let optional = [
+            ("expiration_date_gte", date(query.expiration_from)),
+            ("expiration_date_lte", date(query.expiration_to)),
+            ("strike_price_gte", query.strike_min.map(|s| s.to_string())),
+            ("strike_price_lte", query.strike_max.map(|s| s.to_string())),
+            ("type", query.right.map(|r| r.as_str().to_string())),
+        ];
+        let mut params = vec![
+            ("underlying_symbols", underlyings.as_str()),
+            ("status", "active"),
+            ("limit", "10000"),
+        ];
+        params.extend(
+            optional
+                .iter()
+                .filter_map(|(name, value)| Some((*name, value.as_deref()?))),
+        );
+        let url = format!("{}/v2/options/contracts", self.base_url);
+        let mut contracts = Vec::new();
+        self.fetch_pages(&url, &params, parse_contracts_page, |page| {
+            contracts.extend(page);
+            Ok(())
+        })
+        .await?;
+        contracts.sort_by(|a, b| {
+            (&a.occ.underlying, a.occ.expiration, a.occ.right.as_str())
+                .cmp(&(&b.occ.underlying, b.occ.expiration, b.occ.right.as_str()))
+                .then(a.occ.strike.total_cmp(&b.occ.strike))
+        });
+        Ok(contracts)
+    }
+
+    /// Every `timeframe` bar of the contract `symbol` from `start` to `end`,
+    /// passed to `on_page` a page at a time; returns the number of bars
+    #[tracing::instrument(name = "fetch_option_bars", skip(self, page_size, on_page))]
+    pub async fn fetch_bars(
+        &self,
+        symbol: &str,
+        start: &str,
+        end: &str,
+        timeframe: &str,
+        page_size: u32,
+        on_page: impl FnMut(Vec<OptionBar>) -> Result<()>,
+    ) -> Result<usize> {
+        let limit = page_size.to_string();
+        let url = format!("{}/v1beta1/options/bars", self.data_url);
+        let params = [
+            ("symbols", symbol),
+            ("start", start),
+            ("end", end),
+            ("timeframe", timeframe),
+            ("limit", limit.as_str()),
+        ];
+        self.fetch_pages(&url, &params, parse_option_bars_page, on_page)
+            .await
+    }
+
+    /// Every trade of the contract `symbol` from `start` to `end`, passed to
+    /// `on_page` a page at a time; returns the number of trades
+    #[tracing::instrument(name = "fetch_option_trades", skip(self, page_size, on_page))]
+    pub async fn fetch_trades(
+        &self,
+        symbol: &str,
+        start: &str,
+        end: &str,
+        page_size: u32,
+        on_page: impl FnMut(Vec<OptionTrade>) -> Result<()>,
+    ) -> Result<usize> {
+        let limit = page_size.to_string();
+        let url = format!("{}/v1beta1/options/trades", self.data_url);
+        let params = [
+            ("symbols", symbol),
+            ("start", start),
+            ("end", end),
+            ("limit", limit.as_str()),
+        ];
+        self.fetch_pages(&url, &params, parse_option_trades_page, on_page)
+            .await
+    }
+
+    /// GET `url` with `query`, following `next_page_token`, and pass each
+    /// page's items to `on_page` before requesting the next, so no more than
+    /// a page is held at a time; returns the number of items
+    async fn fetch_pages<T, F>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        parse: F,
+        mut on_page: impl FnMut(Vec<T>) -> Result<()>,
+    ) -> Result<usize>
+    where
+        F: Fn(&str) -> Result<(Vec<T>, Option<String>)>,
+    {
+        let mut count = 0;
+        let mut page_token: Option<String> = None;
+        loop {
+            tracing::debug!(page_token = ?page_token, "Requesting page");
+            let mut query = query.to_vec();
+            if let Some(token) = &page_token {
+                query.push(("page_token", token));
+            }
+            let body = self.get(url, &query).await?;
+            let (items, next_page_token) = parse(&body)?;
+            count += items.len();
+            on_page(items)?;
+            match next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+        Ok(count)
+    }
+
+    /// GET `url` with `query` within the rate limit, retried per `self.retry`
+    async fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<String> {
+        retry_request(&self.retry, move || async move {
+            RateLimiter::global().acquire().await;
+            let response = self
+                .client
+                .get(url)
+                .query(query)
+                .header("APCA-API-KEY-ID", &self.key)
+                .header("APCA-API-SECRET-KEY", &self.secret)
+                .send()
+                .await?;
+            RateLimiter::global()
+                .observe(|name| response.headers().get(name)?.to_str().ok()?.parse().ok());
+            let status = response.status().as_u16();
+            HttpError::check(status, response.text().await?)
+        })
+        .await
+    }
+}
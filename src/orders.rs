@@ -0,0 +1,766 @@
+//! Orders on the Alpaca trading API: typed requests to submit and replace
+//! orders, the orders it returns, and with the `orders` feature a client for
+//! `/v2/orders`.
+//!
+//! Orders go to `APCA_API_BASE_URL`, paper trading by default. Strategy
+//! orders become requests with [`NewOrder::from_request`], so a live session
+//! places what a backtest simulates: an [`OrderQueue`] routes them, and
+//! [`OwnOrders`] hands the strategy back the fills of its own orders.
+
+use crate::strategy::{OrderRequest, OrderRouter, OrderType, Side};
+use crate::{DataFormat, Error, OutputSink, Result, StreamingData};
+use chrono::{DateTime, SecondsFormat, Utc};
+use tokio::sync::mpsc;
+
+/// Header of `orders` CSV output
+pub const ORDERS_CSV_HEADER: &str = "id,client_order_id,symbol,side,type,qty,filled_qty,filled_avg_price,\
+limit_price,stop_price,trail_price,trail_percent,time_in_force,extended_hours,status,created_at,filled_at";
+
+/// Order types of `POST /v2/orders`
+pub const ORDER_TYPES: [&str; 5] = ["market", "limit", "stop", "stop_limit", "trailing_stop"];
+
+/// Statuses `GET /v2/orders` filters by
+pub const ORDER_STATUSES: [&str; 3] = ["open", "closed", "all"];
+
+/// How long an order stays working
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeInForce {
+    /// Until the end of the regular session
+    #[default]
+    Day,
+    /// Good until canceled
+    Gtc,
+    /// Only in the opening auction
+    Opg,
+    /// Only in the closing auction
+    Cls,
+    /// Immediate or cancel: what does not fill at once is canceled
+    Ioc,
+    /// Fill or kill: all at once or nothing
+    Fok,
+}
+
+impl TimeInForce {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimeInForce::Day => "day",
+            TimeInForce::Gtc => "gtc",
+            TimeInForce::Opg => "opg",
+            TimeInForce::Cls => "cls",
+            TimeInForce::Ioc => "ioc",
+            TimeInForce::Fok => "fok",
+        }
+    }
+}
+
+/// How an order is priced, with the prices its type needs
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderKind {
+    Market,
+    Limit {
+        limit_price: f64,
+    },
+    /// A market order once the price reaches `stop_price`
+    Stop {
+        stop_price: f64,
+    },
+    /// A limit order once the price reaches `stop_price`
+    StopLimit {
+        stop_price: f64,
+        limit_price: f64,
+    },
+    /// A stop that follows the best price since the order was placed, by an
+    /// amount or a percentage; exactly one of them is set
+    TrailingStop {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trail_price: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trail_percent: Option<f64>,
+    },
+}
+
+impl OrderKind {
+    /// The kind named `order_type`, one of `ORDER_TYPES`, from the prices
+    /// given; a price the type needs must be given and no other
+    pub fn new(
+        order_type: &str,
+        limit_price: Option<f64>,
+        stop_price: Option<f64>,
+        trail_price: Option<f64>,
+        trail_percent: Option<f64>,
+    ) -> Result<Self> {
+        let kind = match (order_type, limit_price, stop_price) {
+            ("market", None, None) => OrderKind::Market,
+            ("limit", Some(limit_price), None) => OrderKind::Limit { limit_price },
+            ("stop", None, Some(stop_price)) => OrderKind::Stop { stop_price },
+            ("stop_limit", Some(limit_price), Some(stop_price)) => OrderKind::StopLimit {
+                stop_price,
+                limit_price,
+            },
+            ("trailing_stop", None, None) => OrderKind::TrailingStop {
+                trail_price,
+                trail_percent,
+            },
+            ("market" | "limit" | "stop" | "stop_limit" | "trailing_stop", _, _) => {
+                return Err(Error::Invalid(format!(
+                    "A {} order takes {}",
+                    order_type,
+                    match order_type {
+                        "market" => "no limit or stop price",
+                        "limit" => "a limit price and no stop price",
+                        "stop" => "a stop price and no limit price",
+                        "stop_limit" => "a stop price and a limit price",
+                        _ => "a trail price or percent and no limit or stop price",
+                    }
+                )))
+            }
+            _ => {
+                return Err(Error::Invalid(format!(
+                    "Invalid order type: {}. Supported: {}",
+                    order_type,
+                    ORDER_TYPES.join(", ")
+                )))
+            }
+        };
+        if !matches!(kind, OrderKind::TrailingStop { .. })
+            && (trail_price.is_some() || trail_percent.is_some())
+        {
+            return Err(Error::Invalid(
+                "Only trailing_stop orders take a trail price or percent".to_string(),
+            ));
+        }
+        kind.validate()?;
+        Ok(kind)
+    }
+
+    /// The `type` Alpaca calls it, one of `ORDER_TYPES`
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrderKind::Market => "market",
+            OrderKind::Limit { .. } => "limit",
+            OrderKind::Stop { .. } => "stop",
+            OrderKind::StopLimit { .. } => "stop_limit",
+            OrderKind::TrailingStop { .. } => "trailing_stop",
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        match *self {
+            OrderKind::Market => {}
+            OrderKind::Limit { limit_price } => check_price("limit price", limit_price)?,
+            OrderKind::Stop { stop_price } => check_price("stop price", stop_price)?,
+            OrderKind::StopLimit {
+                stop_price,
+                limit_price,
+            } => {
+                check_price("stop price", stop_price)?;
+                check_price("limit price", limit_price)?;
+            }
+            OrderKind::TrailingStop {
+                trail_price,
+                trail_percent,
+            } => match (trail_price, trail_percent) {
+                (Some(price), None) => check_price("trail price", price)?,
+                (None, Some(percent)) => check_price("trail percent", percent)?,
+                _ => {
+                    return Err(Error::Invalid(
+                        "A trailing_stop order takes a trail price or a trail percent".to_string(),
+                    ))
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+fn check_price(name: &str, value: f64) -> Result<()> {
+    if !(value > 0.0 && value.is_finite()) {
+        return Err(Error::Invalid(format!(
+            "Invalid {}: {}. Use a number over 0",
+            name, value
+        )));
+    }
+    Ok(())
+}
+
+/// The body of `POST /v2/orders`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NewOrder {
+    pub symbol: String,
+    /// Shares, always positive; fractional for fractionable assets
+    pub qty: f64,
+    pub side: Side,
+    #[serde(flatten)]
+    pub kind: OrderKind,
+    pub time_in_force: TimeInForce,
+    /// Also fill before the open and after the close; limit orders good for
+    /// the day only
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub extended_hours: bool,
+    /// Our own ID of the order, unique per account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+}
+
+impl NewOrder {
+    /// A day order of `qty` shares
+    pub fn new(symbol: &str, side: Side, qty: f64, kind: OrderKind) -> Self {
+        Self {
+            symbol: symbol.trim().to_uppercase(),
+            qty,
+            side,
+            kind,
+            time_in_force: TimeInForce::Day,
+            extended_hours: false,
+            client_order_id: None,
+        }
+    }
+
+    /// The order a strategy asked for
+    pub fn from_request(request: &OrderRequest, time_in_force: TimeInForce) -> Self {
+        let kind = match request.order_type {
+            OrderType::Market => OrderKind::Market,
+            OrderType::Limit { limit_price } => OrderKind::Limit { limit_price },
+        };
+        Self {
+            time_in_force,
+            ..Self::new(&request.symbol, request.side, request.quantity, kind)
+        }
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn extended_hours(mut self, extended_hours: bool) -> Self {
+        self.extended_hours = extended_hours;
+        self
+    }
+
+    pub fn client_order_id(mut self, client_order_id: Option<String>) -> Self {
+        self.client_order_id = client_order_id;
+        self
+    }
+
+    /// Check what Alpaca would reject before sending it
+    pub fn validate(&self) -> Result<()> {
+        if self.symbol.is_empty() {
+            return Err(Error::Invalid("An order needs a symbol".to_string()));
+        }
+        check_price("quantity", self.qty)?;
+        self.kind.validate()?;
+        if self.extended_hours
+            && !(matches!(self.kind, OrderKind::Limit { .. })
+                && self.time_in_force == TimeInForce::Day)
+        {
+            return Err(Error::Invalid(
+                "Extended hours orders must be limit orders good for the day".to_string(),
+            ));
+        }
+        if matches!(self.kind, OrderKind::TrailingStop { .. })
+            && !matches!(self.time_in_force, TimeInForce::Day | TimeInForce::Gtc)
+        {
+            return Err(Error::Invalid(
+                "Trailing stop orders must be good for the day or until canceled".to_string(),
+            ));
+        }
+        check_client_order_id(self.client_order_id.as_deref())
+    }
+}
+
+fn check_client_order_id(id: Option<&str>) -> Result<()> {
+    match id {
+        Some(id) if id.is_empty() || id.len() > 128 => Err(Error::Invalid(format!(
+            "Invalid client order ID: {:?}. Use 1 to 128 characters",
+            id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The body of `PATCH /v2/orders/{id}`: the fields to change of an open
+/// order, which Alpaca replaces with a new order
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReplaceOrder {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qty: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_in_force: Option<TimeInForce>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_price: Option<f64>,
+    /// New trail of a trailing stop, in its own unit: price or percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trail: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+}
+
+impl ReplaceOrder {
+    pub fn validate(&self) -> Result<()> {
+        if *self == Self::default() {
+            return Err(Error::Invalid("Nothing to replace: give a quantity, price, trail, time in force or client order ID".to_string()));
+        }
+        for (name, value) in [
+            ("quantity", self.qty),
+            ("limit price", self.limit_price),
+            ("stop price", self.stop_price),
+            ("trail", self.trail),
+        ] {
+            if let Some(value) = value {
+                check_price(name, value)?;
+            }
+        }
+        check_client_order_id(self.client_order_id.as_deref())
+    }
+}
+
+/// An order as Alpaca returns it. Its decimals come as strings and are read
+/// as numbers.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Order {
+    pub id: String,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    pub symbol: String,
+    pub side: Side,
+    /// One of `ORDER_TYPES`
+    #[serde(rename = "type")]
+    pub order_type: String,
+    /// Shares; none for orders of a dollar amount
+    #[serde(default, deserialize_with = "decimal")]
+    pub qty: Option<f64>,
+    #[serde(default, deserialize_with = "decimal")]
+    pub filled_qty: Option<f64>,
+    #[serde(default, deserialize_with = "decimal")]
+    pub filled_avg_price: Option<f64>,
+    #[serde(default, deserialize_with = "decimal")]
+    pub limit_price: Option<f64>,
+    #[serde(default, deserialize_with = "decimal")]
+    pub stop_price: Option<f64>,
+    #[serde(default, deserialize_with = "decimal")]
+    pub trail_price: Option<f64>,
+    #[serde(default, deserialize_with = "decimal")]
+    pub trail_percent: Option<f64>,
+    pub time_in_force: TimeInForce,
+    #[serde(default)]
+    pub extended_hours: bool,
+    /// `new`, `partially_filled`, `filled`, `canceled`, `replaced`, ...
+    pub status: String,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub filled_at: Option<DateTime<Utc>>,
+    /// The order that replaced this one
+    #[serde(default)]
+    pub replaced_by: Option<String>,
+}
+
+/// A decimal that Alpaca sends as a string, or a number as written back
+pub(crate) fn decimal<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<f64>, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Decimal {
+        Number(f64),
+        Text(String),
+    }
+    match serde::Deserialize::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Decimal::Number(number)) => Ok(Some(number)),
+        Some(Decimal::Text(text)) => text
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("invalid decimal: {}", text))),
+    }
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+}
+
+fn number(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn time(value: Option<DateTime<Utc>>) -> String {
+    value
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+impl Order {
+    /// Whether the order can still fill
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.status.as_str(),
+            "new"
+                | "partially_filled"
+                | "accepted"
+                | "pending_new"
+                | "accepted_for_bidding"
+                | "pending_cancel"
+                | "pending_replace"
+                | "held"
+        )
+    }
+
+    /// The line written for this order; CSV lines match `ORDERS_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let side = side_name(self.side);
+        Ok(match format {
+            DataFormat::Plain => {
+                let mut prices = Vec::new();
+                if let Some(price) = self.limit_price {
+                    prices.push(format!("limit ${:.2}", price));
+                }
+                if let Some(price) = self.stop_price {
+                    prices.push(format!("stop ${:.2}", price));
+                }
+                if let Some(price) = self.trail_price {
+                    prices.push(format!("trail ${:.2}", price));
+                }
+                if let Some(percent) = self.trail_percent {
+                    prices.push(format!("trail {}%", percent));
+                }
+                let filled = match self.filled_avg_price {
+                    Some(price) => format!(" @ ${:.2}", price),
+                    None => String::new(),
+                };
+                format!(
+                    "🧾 {} {} {} {}{} {}{} | Filled {}/{}{} | {} | {}",
+                    self.symbol,
+                    side,
+                    number(self.qty),
+                    self.order_type,
+                    if prices.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" ({})", prices.join(", "))
+                    },
+                    self.time_in_force.name(),
+                    if self.extended_hours { " +ext" } else { "" },
+                    self.filled_qty.unwrap_or(0.0),
+                    number(self.qty),
+                    filled,
+                    self.status,
+                    self.id
+                )
+            }
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                self.id,
+                self.client_order_id.as_deref().unwrap_or_default(),
+                self.symbol,
+                side,
+                self.order_type,
+                number(self.qty),
+                number(self.filled_qty),
+                number(self.filled_avg_price),
+                number(self.limit_price),
+                number(self.stop_price),
+                number(self.trail_price),
+                number(self.trail_percent),
+                self.time_in_force.name(),
+                self.extended_hours,
+                self.status,
+                time(self.created_at),
+                time(self.filled_at)
+            ),
+        })
+    }
+}
+
+/// Filters of an order listing
+#[derive(Debug, Clone, Default)]
+pub struct OrderQuery {
+    /// One of `ORDER_STATUSES`; `None` lists open orders
+    pub status: Option<String>,
+    /// Most orders to return, newest first (Alpaca's default is 50, its
+    /// maximum 500)
+    pub limit: Option<u32>,
+    /// Only orders of these symbols; empty lists every symbol
+    pub symbols: Vec<String>,
+    pub side: Option<Side>,
+}
+
+impl OrderQuery {
+    /// Query parameters of `GET /v2/orders`
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(status) = &self.status {
+            params.push(("status", status.clone()));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if !self.symbols.is_empty() {
+            params.push(("symbols", self.symbols.join(",")));
+        }
+        if let Some(side) = self.side {
+            params.push(("side", side_name(side).to_string()));
+        }
+        params
+    }
+}
+
+/// Takes the orders of a strategy for a task that places them, since a
+/// sink cannot wait for requests. Each order gets the client order ID
+/// `<prefix>-<n>`, so `OwnOrders` can tell its fills from other orders of
+/// the account.
+#[derive(Debug)]
+pub struct OrderQueue {
+    sender: mpsc::UnboundedSender<NewOrder>,
+    prefix: String,
+    time_in_force: TimeInForce,
+    queued: u64,
+}
+
+impl OrderQueue {
+    /// The queue and the receiving end its orders come out of
+    pub fn new(
+        prefix: &str,
+        time_in_force: TimeInForce,
+    ) -> (Self, mpsc::UnboundedReceiver<NewOrder>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let queue = Self {
+            sender,
+            prefix: prefix.to_string(),
+            time_in_force,
+            queued: 0,
+        };
+        (queue, receiver)
+    }
+}
+
+impl OrderRouter for OrderQueue {
+    fn route(&mut self, order: OrderRequest) -> crate::Result<()> {
+        self.queued += 1;
+        let order = NewOrder::from_request(&order, self.time_in_force)
+            .client_order_id(Some(format!("{}-{}", self.prefix, self.queued)));
+        self.sender
+            .send(order)
+            .map_err(|_| Error::Stopped("Orders are no longer being placed".to_string()))
+    }
+}
+
+/// Passes every message to a sink except `trade_update`s of orders whose
+/// client order ID is not `<prefix>-...`, so a strategy only sees the fills
+/// of the orders it placed through an `OrderQueue` with the same prefix
+#[derive(Debug)]
+pub struct OwnOrders<S> {
+    inner: S,
+    prefix: String,
+}
+
+impl<S: OutputSink> OwnOrders<S> {
+    pub fn new(inner: S, prefix: &str) -> Self {
+        Self {
+            inner,
+            prefix: format!("{}-", prefix),
+        }
+    }
+}
+
+impl<S: OutputSink> OutputSink for OwnOrders<S> {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        if data.message_type == "trade_update"
+            && !data.data["order"]["client_order_id"]
+                .as_str()
+                .is_some_and(|id| id.starts_with(&self.prefix))
+        {
+            return Ok(());
+        }
+        self.inner.write_streaming_data(data)
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> crate::Result<()> {
+        self.inner.close()
+    }
+
+    fn rotate(&self) -> crate::Result<()> {
+        self.inner.rotate()
+    }
+
+    fn writeln(&self, message: &str) -> crate::Result<()> {
+        self.inner.writeln(message)
+    }
+}
+
+/// Orders taken from an `OrderQueue`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    pub submitted: u64,
+    /// Orders the API refused or that failed to send
+    pub rejected: u64,
+}
+
+/// What happened to one order of a cancel-all
+#[cfg(feature = "orders")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct CanceledOrder {
+    pub id: String,
+    /// HTTP status of the cancel; 200 when it went through
+    pub status: u16,
+}
+
+/// Places, lists, replaces and cancels orders on the Alpaca trading API
+/// (`/v2/orders`), and reads the positions they leave (`/v2/positions`).
+/// Requests that change orders are never retried: one that timed out may
+/// still have reached the broker.
+#[cfg(feature = "orders")]
+#[derive(Debug, Clone)]
+pub struct OrdersClient {
+    client: reqwest::Client,
+    base_url: String,
+    key: String,
+    secret: String,
+}
+
+#[cfg(feature = "orders")]
+impl OrdersClient {
+    /// Credentials from `APCA_API_KEY_ID` and `APCA_API_SECRET_KEY`, endpoint
+    /// from `APCA_API_BASE_URL` (paper trading by default)
+    pub fn from_env() -> Result<Self> {
+        let base_url = std::env::var("APCA_API_BASE_URL")
+            .unwrap_or_else(|_| crate::trade_updates::DEFAULT_TRADING_BASE_URL.to_string());
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v2").unwrap_or(base_url).to_string();
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)))
+        };
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()?,
+            base_url,
+            key: var("APCA_API_KEY_ID")?,
+            secret: var("APCA_API_SECRET_KEY")?,
+        })
+    }
+
+    /// The trading API this client sends orders to
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Send `request` with the credentials; the body of a 2xx response, the
+    /// status and Alpaca's message otherwise
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<String> {
+        let response = request
+            .header("APCA-API-KEY-ID", &self.key)
+            .header("APCA-API-SECRET-KEY", &self.secret)
+            .send()
+            .await?;
+        let status = response.status().as_u16();
+        crate::retry::HttpError::check(status, response.text().await?)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v2/orders{}", self.base_url, path)
+    }
+
+    /// Place `order` once it passes `NewOrder::validate`
+    #[tracing::instrument(name = "submit_order", skip(self), fields(symbol = %order.symbol))]
+    pub async fn submit(&self, order: &NewOrder) -> Result<Order> {
+        order.validate()?;
+        let body = self
+            .send(self.client.post(self.url("")).json(order))
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Orders matching `query`, newest first
+    pub async fn list(&self, query: &OrderQuery) -> Result<Vec<Order>> {
+        let body = self
+            .send(self.client.get(self.url("")).query(&query.params()))
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Order> {
+        let body = self
+            .send(self.client.get(self.url(&format!("/{}", id))))
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Replace an open order; returns the new order, which has a new ID
+    pub async fn replace(&self, id: &str, changes: &ReplaceOrder) -> Result<Order> {
+        changes.validate()?;
+        let body = self
+            .send(
+                self.client
+                    .patch(self.url(&format!("/{}", id)))
+                    .json(changes),
+            )
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Ask to cancel an open order; Alpaca confirms it on `trade_updates`
+    pub async fn cancel(&self, id: &str) -> Result<()> {
+        self.send(self.client.delete(self.url(&format!("/{}", id))))
+            .await?;
+        Ok(())
+    }
+
+    /// Place the orders of `orders` one after another until every sender
+    /// is gone. A rejected order is logged and the rest still go out.
+    pub async fn place_queued(&self, mut orders: mpsc::UnboundedReceiver<NewOrder>) -> QueueStats {
+        let mut stats = QueueStats::default();
+        while let Some(order) = orders.recv().await {
+            match self.submit(&order).await {
+                Ok(placed) => {
+                    stats.submitted += 1;
+                    tracing::info!(id = %placed.id, client_order_id = ?placed.client_order_id, symbol = %placed.symbol, side = ?placed.side, qty = ?placed.qty, order_type = %placed.order_type, status = %placed.status, "Order placed");
+                }
+                Err(e) => {
+                    stats.rejected += 1;
+                    tracing::warn!(symbol = %order.symbol, side = ?order.side, qty = order.qty, client_order_id = ?order.client_order_id, error = %e, "Order rejected");
+                }
+            }
+        }
+        stats
+    }
+
+    /// Positions the account holds
+    pub async fn positions(&self) -> Result<Vec<crate::portfolio::AccountPosition>> {
+        let body = self
+            .send(self.client.get(format!("{}/v2/positions", self.base_url)))
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Ask to cancel every open order
+    pub async fn cancel_all(&self) -> Result<Vec<CanceledOrder>> {
+        let body = self.send(self.client.delete(self.url(""))).await?;
+        if body.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&body)?)
+    }
+}
@@ -0,0 +1,750 @@
+//! Where records go: the `OutputSink` trait, the console and file outputs,
+//! per-type CSV files and the in-memory capture buffer.
+
+use crate::compression::{OutputFile, SharedOutputFile};
+use crate::{
+    Compression, ConsoleOptions, DataFormat, Error, LuldBand, OrderImbalance, PlainTemplates,
+    Result, RotateInterval, RotatingFileSink, RotationPolicy, StreamingData, TradeCancel,
+    TradeCorrection,
+};
+use alpaca_trading_api_rust::{StreamingBar, StreamingQuote, StreamingTrade};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use csv::Writer;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Destination for streaming data.
+///
+/// `OutputMode` provides the built-in console and file sinks; implement this
+/// trait to send data elsewhere (a database, a network service, ...).
+pub trait OutputSink: Send + Sync + std::fmt::Debug {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()>;
+    
+    fn flush(&self) -> Result<()>;
+    
+    /// Flush and release the sink at the end of a session
+    fn close(&self) -> Result<()> {
+        self.flush()
+    }
+    
+    /// Start new output files at a session boundary. Sinks without files
+    /// only flush.
+    fn rotate(&self) -> Result<()> {
+        self.flush()
+    }
+    
+    /// Human-readable status line (connection progress, retries, ...).
+    /// Sinks that only store data can ignore these.
+    fn writeln(&self, _message: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Fan-out over several sinks. Every sink receives every record; the first
+/// error is returned after all sinks have been tried, so one failing sink does
+/// not starve the others.
+impl OutputSink for Vec<Arc<dyn OutputSink>> {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        fan_out(self, |sink| sink.write_streaming_data(data))
+    }
+    
+    fn flush(&self) -> Result<()> {
+        fan_out(self, |sink| sink.flush())
+    }
+    
+    fn close(&self) -> Result<()> {
+        fan_out(self, |sink| sink.close())
+    }
+    
+    fn rotate(&self) -> Result<()> {
+        fan_out(self, |sink| sink.rotate())
+    }
+    
+    fn writeln(&self, message: &str) -> Result<()> {
+        fan_out(self, |sink| sink.writeln(message))
+    }
+}
+
+fn fan_out<F>(sinks: &[Arc<dyn OutputSink>], mut f: F) -> Result<()>
+where
+    F: FnMut(&dyn OutputSink) -> Result<()>,
+{
+    let mut first_error = None;
+    for sink in sinks {
+        if let Err(e) = f(sink.as_ref()) {
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+#[derive(Debug, Clone)]
+pub enum OutputMode {
+    Console {
+        format: DataFormat,
+        options: ConsoleOptions,
+        /// Overrides the built-in plain-format lines
+        templates: Option<Arc<PlainTemplates>>,
+    },
+    File { 
+        file: Arc<Mutex<OutputFile>>, 
+        format: DataFormat,
+        csv_writer: Option<Arc<Mutex<CsvOutput>>>,
+        templates: Option<Arc<PlainTemplates>>,
+    },
+}
+
+/// CSV writer for `OutputMode::File`. The header is written just before the
+/// first record, so files that only receive `writeln` lines (such as the
+/// historical downloader's own CSV) get no generic header.
+#[derive(Debug)]
+pub struct CsvOutput {
+    writer: Writer<SharedOutputFile>,
+    header_pending: bool,
+}
+
+impl OutputMode {
+    pub fn write(&self, message: &str) -> Result<()> {
+        match self {
+            OutputMode::Console { .. } => {
+                print!("{}", message);
+                std::io::stdout().flush()?;
+            }
+            OutputMode::File { file, .. } => {
+                let mut file = file.lock().unwrap();
+                write!(file, "{}", message)?;
+                file.flush()?;
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn writeln(&self, message: &str) -> Result<()> {
+        self.write(&format!("{}\n", message))
+    }
+    
+    #[tracing::instrument(level = "trace", name = "write", skip_all, fields(message_type = %data.message_type))]
+    pub fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        match self {
+            OutputMode::Console { format, options, .. } => {
+                match format {
+                    DataFormat::Plain => {
+                        self.writeln(&options.render(self.format_plain(data), data))?;
+                    }
+                    DataFormat::Json => {
+                        self.writeln(&serde_json::to_string(data)?)?;
+                    }
+                    DataFormat::Csv => {
+                        self.writeln(&self.format_csv_line(data))?;
+                    }
+                }
+            }
+            OutputMode::File { file, format, csv_writer, .. } => {
+                match format {
+                    DataFormat::Plain => {
+                        let mut file = file.lock().unwrap();
+                        writeln!(file, "{}", self.format_plain(data))?;
+                        file.flush()?;
+                    }
+                    DataFormat::Json => {
+                        let mut file = file.lock().unwrap();
+                        writeln!(file, "{}", serde_json::to_string(data)?)?;
+                        file.flush()?;
+                    }
+                    DataFormat::Csv => {
+                        if let Some(csv_writer) = csv_writer {
+                            let mut csv = csv_writer.lock().unwrap();
+                            if csv.header_pending {
+                                csv.writer.write_record(["received_time", "message_type", "symbol", "data", "event_time"])?;
+                                csv.header_pending = false;
+                            }
+                            self.write_csv_record(&mut csv.writer, data)?;
+                            csv.writer.flush()?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn create_file_mode(output_path: &PathBuf, format: DataFormat, append: bool) -> Result<Self> {
+        Self::create_compressed_file_mode(output_path, format, append, Compression::None)
+    }
+    
+    /// File output written through a gzip or zstd encoder. Appending adds a new
+    /// gzip member or zstd frame, which readers decode as one stream.
+    pub fn create_compressed_file_mode(output_path: &PathBuf, format: DataFormat, append: bool, compression: Compression) -> Result<Self> {
+        let file = if append {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(output_path)?
+        } else {
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(output_path)?
+        };
+        let file = Arc::new(Mutex::new(OutputFile::new(file, compression)?));
+        
+        // Create CSV writer if format is CSV
+        let csv_writer = if matches!(format, DataFormat::Csv) {
+            Some(Arc::new(Mutex::new(CsvOutput {
+                writer: Writer::from_writer(SharedOutputFile(file.clone())),
+                // Write CSV header if not appending
+                header_pending: !append,
+            })))
+        } else {
+            None
+        };
+        
+        Ok(OutputMode::File { 
+            file,
+            format,
+            csv_writer,
+            templates: None,
+        })
+    }
+    
+    /// Start building a console or file output; see `OutputModeBuilder`
+    pub fn builder() -> OutputModeBuilder {
+        OutputModeBuilder::default()
+    }
+    
+    pub fn create_console_mode(format: DataFormat) -> Self {
+        Self::create_styled_console_mode(format, ConsoleOptions::default())
+    }
+    
+    /// Console output whose plain-format lines are colored and/or stripped of emoji
+    pub fn create_styled_console_mode(format: DataFormat, options: ConsoleOptions) -> Self {
+        OutputMode::Console { format, options, templates: None }
+    }
+    
+    /// Render plain-format lines with `templates` where they define one
+    pub fn with_templates(mut self, plain_templates: Arc<PlainTemplates>) -> Self {
+        match &mut self {
+            OutputMode::Console { templates, .. } | OutputMode::File { templates, .. } => {
+                *templates = Some(plain_templates);
+            }
+        }
+        self
+    }
+    
+    /// Build an output from a `FORMAT[:PATH]` spec, e.g. `json:capture.jsonl` or `plain` for the console
+    pub fn from_spec(spec: &str, append: bool) -> Result<Self> {
+        let (format, path) = match spec.split_once(':') {
+            Some((format, path)) => (format, Some(path)),
+            None => (spec, None),
+        };
+        let format = DataFormat::from_str(format, true)
+            .map_err(|_| Error::Invalid(format!("Invalid output format: {}. Supported: plain, json, csv", format)))?;
+        
+        match path {
+            Some(path) if !path.is_empty() && path != "-" => {
+                Self::create_file_mode(&PathBuf::from(path), format, append)
+            }
+            _ => Ok(Self::create_console_mode(format)),
+        }
+    }
+    
+    pub fn flush(&self) -> Result<()> {
+        match self {
+            OutputMode::Console { .. } => std::io::stdout().flush()?,
+            OutputMode::File { file, csv_writer, .. } => {
+                if let Some(csv_writer) = csv_writer {
+                    csv_writer.lock().unwrap().writer.flush()?;
+                }
+                file.lock().unwrap().sync()?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Flush and, for compressed files, complete the compressed stream
+    pub fn close(&self) -> Result<()> {
+        match self {
+            OutputMode::Console { .. } => self.flush(),
+            OutputMode::File { file, csv_writer, .. } => {
+                if let Some(csv_writer) = csv_writer {
+                    csv_writer.lock().unwrap().writer.flush()?;
+                }
+                file.lock().unwrap().finish()
+            }
+        }
+    }
+}
+
+/// Builds an output step by step:
+///
+/// ```no_run
+/// # use algorithms_trading::{Compression, DataFormat, OutputMode};
+/// # fn main() -> algorithms_trading::Result<()> {
+/// let sink = OutputMode::builder()
+///     .format(DataFormat::Json)
+///     .file("capture.jsonl")
+///     .compression(Compression::Zstd)
+///     .rotate_daily()
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Without `file` the output goes to the console. With rotation the result
+/// is a `RotatingFileSink`, otherwise an `OutputMode`.
+#[derive(Debug, Default)]
+pub struct OutputModeBuilder {
+    format: DataFormat,
+    path: Option<PathBuf>,
+    append: bool,
+    compression: Compression,
+    console: Option<ConsoleOptions>,
+    templates: Option<Arc<PlainTemplates>>,
+    rotation: RotationPolicy,
+}
+
+impl OutputModeBuilder {
+    pub fn format(mut self, format: DataFormat) -> Self {
+        self.format = format;
+        self
+    }
+    
+    /// Write to `path` instead of the console
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+    
+    /// Add to an existing file instead of replacing it
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+    
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+    
+    /// Color and emoji settings of console output
+    pub fn console_options(mut self, options: ConsoleOptions) -> Self {
+        self.console = Some(options);
+        self
+    }
+    
+    pub fn templates(mut self, templates: Arc<PlainTemplates>) -> Self {
+        self.templates = Some(templates);
+        self
+    }
+    
+    /// New file every day (UTC)
+    pub fn rotate_daily(mut self) -> Self {
+        self.rotation.interval = Some(RotateInterval::Daily);
+        self
+    }
+    
+    /// New file every hour (UTC)
+    pub fn rotate_hourly(mut self) -> Self {
+        self.rotation.interval = Some(RotateInterval::Hourly);
+        self
+    }
+    
+    /// New file once the current one reaches `bytes`
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.rotation.max_bytes = Some(bytes);
+        self
+    }
+    
+    /// Gzip each file once it has been rotated out
+    pub fn compress_rotated(mut self) -> Self {
+        self.rotation.compress = true;
+        self
+    }
+    
+    /// Check the settings and open the output. File settings (append,
+    /// compression, rotation) need a file, console options need the console,
+    /// and rotated files cannot be gzipped when they are already compressed.
+    pub fn build(self) -> Result<Box<dyn OutputSink>> {
+        let rotates = self.rotation.interval.is_some() || self.rotation.max_bytes.is_some();
+        let Some(path) = self.path else {
+            if self.append || self.compression != Compression::None || rotates || self.rotation.compress {
+                return Err(Error::Invalid("Append, compression and rotation need an output file".to_string()));
+            }
+            let mut output = OutputMode::create_styled_console_mode(self.format, self.console.unwrap_or_default());
+            if let Some(templates) = self.templates {
+                output = output.with_templates(templates);
+            }
+            return Ok(Box::new(output));
+        };
+        if self.console.is_some() {
+            return Err(Error::Invalid("Console options only apply to console output".to_string()));
+        }
+        if self.rotation.max_bytes == Some(0) {
+            return Err(Error::Invalid("Invalid max file size: 0".to_string()));
+        }
+        if self.rotation.compress && self.compression != Compression::None {
+            return Err(Error::Invalid("Rotated files are already compressed; drop compress_rotated()".to_string()));
+        }
+        if self.rotation.compress && !rotates {
+            return Err(Error::Invalid("compress_rotated() needs rotate_daily(), rotate_hourly() or max_file_size()".to_string()));
+        }
+        if rotates {
+            let mut sink = RotatingFileSink::new(&path, self.format, self.append, self.rotation)
+                .with_compression(self.compression);
+            if let Some(templates) = self.templates {
+                sink = sink.with_templates(templates);
+            }
+            return Ok(Box::new(sink));
+        }
+        let mut output = OutputMode::create_compressed_file_mode(&path, self.format, self.append, self.compression)?;
+        if let Some(templates) = self.templates {
+            output = output.with_templates(templates);
+        }
+        Ok(Box::new(output))
+    }
+}
+
+impl OutputSink for OutputMode {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        OutputMode::write_streaming_data(self, data)
+    }
+    
+    fn flush(&self) -> Result<()> {
+        OutputMode::flush(self)
+    }
+    
+    fn close(&self) -> Result<()> {
+        OutputMode::close(self)
+    }
+    
+    fn writeln(&self, message: &str) -> Result<()> {
+        OutputMode::writeln(self, message)
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct TradeCsvRow {
+    received_at: String,
+    symbol: String,
+    timestamp: String,
+    price: f64,
+    size: u64,
+    exchange: String,
+    id: u64,
+}
+
+/// Row of `corrections.csv`, for both corrections and cancels
+#[derive(Debug, serde::Serialize)]
+struct CorrectionCsvRow {
+    received_at: String,
+    symbol: String,
+    timestamp: String,
+    /// `correction`, `cancel` or `error`
+    kind: &'static str,
+    id: u64,
+    exchange: String,
+    original_price: f64,
+    original_size: u64,
+    corrected_price: Option<f64>,
+    corrected_size: Option<u64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct LuldCsvRow {
+    received_at: String,
+    symbol: String,
+    timestamp: String,
+    limit_up: f64,
+    limit_down: f64,
+    indicator: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ImbalanceCsvRow {
+    received_at: String,
+    symbol: String,
+    timestamp: String,
+    price: f64,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct QuoteCsvRow {
+    received_at: String,
+    symbol: String,
+    timestamp: String,
+    bid_price: f64,
+    bid_size: u64,
+    ask_price: f64,
+    ask_size: u64,
+    spread: f64,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct BarCsvRow {
+    received_at: String,
+    symbol: String,
+    timestamp: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: u64,
+}
+
+/// Writes trades, quotes and bars to `trades.csv`, `quotes.csv` and `bars.csv`
+/// in one directory, each with its own typed columns, plus `daily_bars.csv`,
+/// `updated_bars.csv`, `corrections.csv`, `lulds.csv` and `imbalances.csv`.
+/// Other message types are not written.
+#[derive(Debug)]
+pub struct StructuredCsvSink {
+    trades: Mutex<Writer<std::fs::File>>,
+    quotes: Mutex<Writer<std::fs::File>>,
+    bars: Mutex<Writer<std::fs::File>>,
+    daily_bars: Mutex<Writer<std::fs::File>>,
+    updated_bars: Mutex<Writer<std::fs::File>>,
+    corrections: Mutex<Writer<std::fs::File>>,
+    lulds: Mutex<Writer<std::fs::File>>,
+    imbalances: Mutex<Writer<std::fs::File>>,
+}
+
+impl StructuredCsvSink {
+    pub fn create(dir: &std::path::Path, append: bool) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            trades: Mutex::new(Self::open_writer(&dir.join("trades.csv"), append)?),
+            quotes: Mutex::new(Self::open_writer(&dir.join("quotes.csv"), append)?),
+            bars: Mutex::new(Self::open_writer(&dir.join("bars.csv"), append)?),
+            daily_bars: Mutex::new(Self::open_writer(&dir.join("daily_bars.csv"), append)?),
+            updated_bars: Mutex::new(Self::open_writer(&dir.join("updated_bars.csv"), append)?),
+            corrections: Mutex::new(Self::open_writer(&dir.join("corrections.csv"), append)?),
+            lulds: Mutex::new(Self::open_writer(&dir.join("lulds.csv"), append)?),
+            imbalances: Mutex::new(Self::open_writer(&dir.join("imbalances.csv"), append)?),
+        })
+    }
+    
+    fn open_writer(path: &std::path::Path, append: bool) -> Result<Writer<std::fs::File>> {
+        // Only write the header row when the file starts out empty
+        let has_rows = append && std::fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false);
+        let file = if append {
+            OpenOptions::new().create(true).append(true).open(path)?
+        } else {
+            OpenOptions::new().create(true).write(true).truncate(true).open(path)?
+        };
+        Ok(csv::WriterBuilder::new().has_headers(!has_rows).from_writer(file))
+    }
+}
+
+impl OutputSink for StructuredCsvSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        let received_at = data.received_time.to_rfc3339();
+        match data.message_type.as_str() {
+            "t" => {
+                let trade: StreamingTrade = serde_json::from_value(data.data.clone())?;
+                let mut writer = self.trades.lock().unwrap();
+                writer.serialize(TradeCsvRow {
+                    received_at,
+                    symbol: trade.symbol,
+                    timestamp: trade.timestamp.to_string(),
+                    price: trade.price,
+                    size: trade.size,
+                    exchange: trade.exchange,
+                    id: trade.id,
+                })?;
+                writer.flush()?;
+            }
+            "q" => {
+                let quote: StreamingQuote = serde_json::from_value(data.data.clone())?;
+                let mut writer = self.quotes.lock().unwrap();
+                writer.serialize(QuoteCsvRow {
+                    received_at,
+                    symbol: quote.symbol,
+                    timestamp: quote.timestamp.to_string(),
+                    bid_price: quote.bid_price,
+                    bid_size: quote.bid_size,
+                    ask_price: quote.ask_price,
+                    ask_size: quote.ask_size,
+                    spread: quote.ask_price - quote.bid_price,
+                })?;
+                writer.flush()?;
+            }
+            "b" | "d" | "u" => {
+                let bar: StreamingBar = serde_json::from_value(data.data.clone())?;
+                let mut writer = match data.message_type.as_str() {
+                    "d" => self.daily_bars.lock().unwrap(),
+                    "u" => self.updated_bars.lock().unwrap(),
+                    _ => self.bars.lock().unwrap(),
+                };
+                writer.serialize(BarCsvRow {
+                    received_at,
+                    symbol: bar.symbol,
+                    timestamp: bar.timestamp.to_string(),
+                    open: bar.open,
+                    high: bar.high,
+                    low: bar.low,
+                    close: bar.close,
+                    volume: bar.volume,
+                })?;
+                writer.flush()?;
+            }
+            "c" => {
+                let correction: TradeCorrection = serde_json::from_value(data.data.clone())?;
+                let mut writer = self.corrections.lock().unwrap();
+                writer.serialize(CorrectionCsvRow {
+                    received_at,
+                    symbol: correction.symbol,
+                    timestamp: correction.timestamp,
+                    kind: "correction",
+                    id: correction.original_id,
+                    exchange: correction.exchange,
+                    original_price: correction.original_price,
+                    original_size: correction.original_size,
+                    corrected_price: Some(correction.corrected_price),
+                    corrected_size: Some(correction.corrected_size),
+                })?;
+                writer.flush()?;
+            }
+            "x" => {
+                let cancel: TradeCancel = serde_json::from_value(data.data.clone())?;
+                let mut writer = self.corrections.lock().unwrap();
+                writer.serialize(CorrectionCsvRow {
+                    received_at,
+                    kind: if cancel.is_error() { "error" } else { "cancel" },
+                    symbol: cancel.symbol,
+                    timestamp: cancel.timestamp,
+                    id: cancel.id,
+                    exchange: cancel.exchange,
+                    original_price: cancel.price,
+                    original_size: cancel.size,
+                    corrected_price: None,
+                    corrected_size: None,
+                })?;
+                writer.flush()?;
+            }
+            "l" => {
+                let band: LuldBand = serde_json::from_value(data.data.clone())?;
+                let mut writer = self.lulds.lock().unwrap();
+                writer.serialize(LuldCsvRow {
+                    received_at,
+                    symbol: band.symbol,
+                    timestamp: band.timestamp,
+                    limit_up: band.limit_up,
+                    limit_down: band.limit_down,
+                    indicator: band.indicator,
+                })?;
+                writer.flush()?;
+            }
+            "i" => {
+                let imbalance: OrderImbalance = serde_json::from_value(data.data.clone())?;
+                let mut writer = self.imbalances.lock().unwrap();
+                writer.serialize(ImbalanceCsvRow {
+                    received_at,
+                    symbol: imbalance.symbol,
+                    timestamp: imbalance.timestamp,
+                    price: imbalance.price,
+                })?;
+                writer.flush()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    
+    fn flush(&self) -> Result<()> {
+        self.trades.lock().unwrap().flush()?;
+        self.quotes.lock().unwrap().flush()?;
+        self.bars.lock().unwrap().flush()?;
+        self.daily_bars.lock().unwrap().flush()?;
+        self.updated_bars.lock().unwrap().flush()?;
+        self.corrections.lock().unwrap().flush()?;
+        self.lulds.lock().unwrap().flush()?;
+        self.imbalances.lock().unwrap().flush()?;
+        Ok(())
+    }
+}
+
+/// Most recent data per symbol kept in memory, so the session can be queried
+/// while it is running. Clones share the same buffer.
+#[derive(Debug, Clone)]
+pub struct CaptureBuffer {
+    inner: Arc<Mutex<HashMap<String, SymbolCapture>>>,
+    max_bars: usize,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SymbolCapture {
+    pub latest_trade: Option<StreamingData>,
+    pub latest_quote: Option<StreamingData>,
+    pub latest_bar: Option<StreamingData>,
+    #[serde(skip)]
+    pub bars: std::collections::VecDeque<StreamingData>,
+}
+
+impl CaptureBuffer {
+    /// Keep at most `max_bars` bars per symbol
+    pub fn new(max_bars: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HashMap::new())),
+            max_bars,
+        }
+    }
+    
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.inner.lock().unwrap().keys().cloned().collect();
+        symbols.sort();
+        symbols
+    }
+    
+    pub fn latest(&self, symbol: &str) -> Option<SymbolCapture> {
+        self.inner.lock().unwrap().get(&symbol.to_uppercase()).map(|capture| SymbolCapture {
+            bars: Default::default(),
+            ..capture.clone()
+        })
+    }
+    
+    /// Buffered bars whose bar timestamp falls within `[from, to]`
+    pub fn bars(&self, symbol: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Vec<StreamingBar> {
+        let inner = self.inner.lock().unwrap();
+        let Some(capture) = inner.get(&symbol.to_uppercase()) else {
+            return Vec::new();
+        };
+        capture.bars.iter()
+            .filter_map(|data| serde_json::from_value::<StreamingBar>(data.data.clone()).ok())
+            .filter(|bar| match bar.timestamp.to_string().parse::<DateTime<Utc>>() {
+                Ok(time) => from.map(|from| time >= from).unwrap_or(true) && to.map(|to| time <= to).unwrap_or(true),
+                Err(_) => from.is_none() && to.is_none(),
+            })
+            .collect()
+    }
+}
+
+impl OutputSink for CaptureBuffer {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        let Some(symbol) = &data.symbol else {
+            return Ok(());
+        };
+        let mut inner = self.inner.lock().unwrap();
+        let capture = inner.entry(symbol.clone()).or_default();
+        match data.message_type.as_str() {
+            "t" => capture.latest_trade = Some(data.clone()),
+            "q" => capture.latest_quote = Some(data.clone()),
+            "b" => {
+                capture.latest_bar = Some(data.clone());
+                capture.bars.push_back(data.clone());
+                while capture.bars.len() > self.max_bars {
+                    capture.bars.pop_front();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
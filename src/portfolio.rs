@@ -0,0 +1,313 @@
+//! Positions and P&L of a trading session, kept from fills and marked to
+//! live prices.
+//!
+//! A [`Portfolio`] takes the messages of a session like any other output:
+//! `trade_update` fills move its positions at average cost, and trades,
+//! quotes and bars mark them to market. [`Portfolio::reconcile`] starts it
+//! from the positions the account already holds, as `GET /v2/positions`
+//! returns them, and [`Portfolio::emit`] writes `position` and `portfolio`
+//! snapshots through the outputs.
+
+use crate::strategy::{Fill, MarketEvent};
+use crate::{OutputSink, Result, StreamingData};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+
+/// Quantities closer to zero than this are flat, so rounding in fractional
+/// fills does not leave dust positions
+const FLAT: f64 = 1e-9;
+
+/// One symbol's holding and the P&L it has made
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Position {
+    pub symbol: String,
+    /// Shares held; negative when short
+    pub quantity: f64,
+    /// Average price of the shares held; 0 when flat
+    pub average_cost: f64,
+    /// Profit of the shares closed so far, less commissions
+    pub realized_pnl: f64,
+    /// Latest trade, quote midpoint, bar close or fill price; `None` until
+    /// one arrives
+    pub last_price: Option<f64>,
+}
+
+impl Position {
+    pub fn is_flat(&self) -> bool {
+        self.quantity.abs() < FLAT
+    }
+
+    /// Shares held at the last price, or at cost before a price arrives
+    pub fn market_value(&self) -> f64 {
+        self.quantity * self.last_price.unwrap_or(self.average_cost)
+    }
+
+    /// Profit of the shares held at the last price; 0 before a price arrives
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.last_price
+            .map_or(0.0, |price| (price - self.average_cost) * self.quantity)
+    }
+
+    /// Move the position by a fill. Shares bought on the side already held
+    /// are averaged into the cost; shares that reduce it realize their
+    /// profit against the cost, and any excess opens the other side at the
+    /// fill price.
+    pub fn apply_fill(&mut self, fill: &Fill) {
+        let change = fill.side.sign() * fill.quantity;
+        if self.is_flat() || self.quantity.signum() == change.signum() {
+            let held = self.quantity.abs();
+            self.average_cost =
+                (held * self.average_cost + fill.quantity * fill.price) / (held + fill.quantity);
+            self.quantity += change;
+        } else {
+            let closed = self.quantity.abs().min(fill.quantity);
+            self.realized_pnl += closed * (fill.price - self.average_cost) * self.quantity.signum();
+            self.quantity += change;
+            if self.is_flat() {
+                self.quantity = 0.0;
+                self.average_cost = 0.0;
+            } else if fill.quantity > closed {
+                self.average_cost = fill.price;
+            }
+        }
+        self.realized_pnl -= fill.commission;
+        self.last_price = Some(fill.price);
+    }
+}
+
+/// A position as the trading API reports it (`GET /v2/positions`). Its
+/// decimals come as strings and are read as numbers.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AccountPosition {
+    pub symbol: String,
+    /// Shares held; Alpaca signs short positions
+    #[serde(deserialize_with = "crate::orders::decimal")]
+    pub qty: Option<f64>,
+    /// `long` or `short`
+    pub side: String,
+    #[serde(deserialize_with = "crate::orders::decimal")]
+    pub avg_entry_price: Option<f64>,
+    #[serde(default, deserialize_with = "crate::orders::decimal")]
+    pub current_price: Option<f64>,
+}
+
+impl AccountPosition {
+    /// Shares held, negative when short whether or not `qty` is signed
+    pub fn quantity(&self) -> f64 {
+        let qty = self.qty.unwrap_or(0.0).abs();
+        if self.side == "short" {
+            -qty
+        } else {
+            qty
+        }
+    }
+}
+
+/// One symbol of a snapshot; the payload of `position` messages
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PositionSnapshot {
+    pub symbol: String,
+    pub quantity: f64,
+    pub average_cost: f64,
+    pub last_price: Option<f64>,
+    pub market_value: f64,
+    pub unrealized_pnl: f64,
+    pub realized_pnl: f64,
+    /// Set on the snapshot written when the session ends
+    #[serde(rename = "final", default)]
+    pub is_final: bool,
+}
+
+/// Totals of a snapshot over every symbol; the payload of `portfolio`
+/// messages
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PortfolioTotals {
+    /// Symbols held
+    pub positions: usize,
+    pub market_value: f64,
+    pub unrealized_pnl: f64,
+    pub realized_pnl: f64,
+    /// Unrealized plus realized
+    pub total_pnl: f64,
+    #[serde(rename = "final", default)]
+    pub is_final: bool,
+}
+
+/// Positions of a session from the fills and prices it receives as a sink.
+/// Clones share the same positions, so one can be kept to emit snapshots
+/// while another is in the session's outputs.
+#[derive(Debug, Clone, Default)]
+pub struct Portfolio {
+    positions: Arc<Mutex<BTreeMap<String, Position>>>,
+}
+
+impl Portfolio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply_fill(&self, fill: &Fill) {
+        let mut positions = self.positions.lock().unwrap();
+        positions
+            .entry(fill.symbol.clone())
+            .or_insert_with(|| Position {
+                symbol: fill.symbol.clone(),
+                ..Position::default()
+            })
+            .apply_fill(fill);
+    }
+
+    /// Mark a held symbol to `price`; prices of symbols never held are
+    /// ignored
+    pub fn mark(&self, symbol: &str, price: f64) {
+        if !price.is_finite() || price <= 0.0 {
+            return;
+        }
+        if let Some(position) = self.positions.lock().unwrap().get_mut(symbol) {
+            position.last_price = Some(price);
+        }
+    }
+
+    /// Take the quantities and costs of what the account holds, keeping the
+    /// P&L realized so far; symbols the account does not hold become flat.
+    /// Returns the number of positions that changed.
+    pub fn reconcile(&self, held: &[AccountPosition]) -> usize {
+        let mut positions = self.positions.lock().unwrap();
+        let mut changed = 0;
+        for position in positions.values_mut() {
+            if !position.is_flat() && !held.iter().any(|held| held.symbol == position.symbol) {
+                info!(symbol = %position.symbol, quantity = position.quantity, "Position closed outside the session");
+                position.quantity = 0.0;
+                position.average_cost = 0.0;
+                changed += 1;
+            }
+        }
+        for account in held {
+            let position = positions
+                .entry(account.symbol.clone())
+                .or_insert_with(|| Position {
+                    symbol: account.symbol.clone(),
+                    ..Position::default()
+                });
+            let quantity = account.quantity();
+            let average_cost = account.avg_entry_price.unwrap_or(position.average_cost);
+            if (position.quantity - quantity).abs() >= FLAT
+                || (position.average_cost - average_cost).abs() >= FLAT
+            {
+                info!(symbol = %account.symbol, quantity, average_cost, was = position.quantity, "Position reconciled");
+                position.quantity = quantity;
+                position.average_cost = average_cost;
+                changed += 1;
+            }
+            if account.current_price.is_some() {
+                position.last_price = account.current_price;
+            }
+        }
+        changed
+    }
+
+    pub fn position(&self, symbol: &str) -> Option<Position> {
+        self.positions.lock().unwrap().get(symbol).cloned()
+    }
+
+    /// Every symbol held or traded, sorted by symbol
+    pub fn positions(&self) -> Vec<Position> {
+        self.positions.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Current figures of every symbol held or traded, sorted by symbol
+    pub fn snapshot(&self) -> Vec<PositionSnapshot> {
+        self.positions()
+            .into_iter()
+            .map(|position| PositionSnapshot {
+                market_value: position.market_value(),
+                unrealized_pnl: position.unrealized_pnl(),
+                symbol: position.symbol,
+                quantity: position.quantity,
+                average_cost: position.average_cost,
+                last_price: position.last_price,
+                realized_pnl: position.realized_pnl,
+                is_final: false,
+            })
+            .collect()
+    }
+
+    pub fn totals(&self) -> PortfolioTotals {
+        let mut totals = PortfolioTotals::default();
+        for position in self.positions() {
+            if !position.is_flat() {
+                totals.positions += 1;
+            }
+            totals.market_value += position.market_value();
+            totals.unrealized_pnl += position.unrealized_pnl();
+            totals.realized_pnl += position.realized_pnl;
+        }
+        totals.total_pnl = totals.unrealized_pnl + totals.realized_pnl;
+        totals
+    }
+
+    /// Write one `position` message per symbol and a `portfolio` message
+    /// with the totals to `output`
+    pub fn emit(&self, output: &dyn OutputSink, is_final: bool) -> Result<()> {
+        let received_time = Utc::now();
+        for position in self.snapshot() {
+            let data = StreamingData {
+                received_time,
+                event_time: None,
+                message_type: "position".to_string(),
+                symbol: Some(position.symbol.clone()),
+                data: serde_json::to_value(PositionSnapshot {
+                    is_final,
+                    ..position
+                })?,
+            };
+            output.write_streaming_data(&data)?;
+        }
+        let data = StreamingData {
+            received_time,
+            event_time: None,
+            message_type: "portfolio".to_string(),
+            symbol: None,
+            data: serde_json::to_value(PortfolioTotals {
+                is_final,
+                ..self.totals()
+            })?,
+        };
+        output.write_streaming_data(&data)?;
+        Ok(())
+    }
+
+    /// Call `emit` every `interval`; runs until the task is dropped
+    pub async fn emit_every(self, output: Vec<Arc<dyn OutputSink>>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.emit(&output, false) {
+                tracing::warn!(error = %e, "Failed to write portfolio snapshot");
+            }
+        }
+    }
+}
+
+impl OutputSink for Portfolio {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        match MarketEvent::from_streaming_data(data) {
+            Some(MarketEvent::Fill(fill)) => self.apply_fill(&fill),
+            Some(MarketEvent::Trade(trade)) => self.mark(&trade.symbol, trade.price),
+            Some(MarketEvent::Quote(quote)) if quote.bid_price > 0.0 && quote.ask_price > 0.0 => {
+                self.mark(&quote.symbol, (quote.bid_price + quote.ask_price) / 2.0)
+            }
+            Some(MarketEvent::Bar(bar)) => self.mark(&bar.symbol, bar.close),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Pacing of REST requests to the Alpaca APIs. One limiter is shared by every
+//! request of the process, so concurrent downloads draw from the same budget.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Requests per minute allowed by a standard Alpaca account
+pub const DEFAULT_REQUESTS_PER_MINUTE: u32 = 200;
+
+/// A token bucket holding up to one minute of requests, refilled at the
+/// configured rate. Responses narrow it further: `X-RateLimit-Remaining`
+/// caps the tokens and a remaining count of 0 parks every request until
+/// `X-RateLimit-Reset`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    per_minute: u32,
+    tokens: f64,
+    refilled_at: Instant,
+    parked_until: Option<Instant>,
+}
+
+impl State {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.per_minute as f64 / 60.0).min(self.per_minute as f64);
+        self.refilled_at = now;
+    }
+}
+
+static GLOBAL: OnceLock<RateLimiter> = OnceLock::new();
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        let per_minute = requests_per_minute.max(1);
+        Self {
+            state: Mutex::new(State {
+                per_minute,
+                tokens: per_minute as f64,
+                refilled_at: Instant::now(),
+                parked_until: None,
+            }),
+        }
+    }
+
+    /// The limiter every REST client uses, with `ALPACA_RATE_LIMIT` requests
+    /// per minute or `DEFAULT_REQUESTS_PER_MINUTE`
+    pub fn global() -> &'static RateLimiter {
+        GLOBAL.get_or_init(|| {
+            let per_minute = std::env::var("ALPACA_RATE_LIMIT")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_REQUESTS_PER_MINUTE);
+            RateLimiter::new(per_minute)
+        })
+    }
+
+    pub fn requests_per_minute(&self) -> u32 {
+        self.state.lock().unwrap().per_minute
+    }
+
+    /// Change the budget; tokens above the new budget are dropped
+    pub fn set_requests_per_minute(&self, requests_per_minute: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.refill(Instant::now());
+        state.per_minute = requests_per_minute.max(1);
+        state.tokens = state.tokens.min(state.per_minute as f64);
+    }
+
+    /// How long a request would have to wait now; takes a token if it need
+    /// not wait
+    pub fn try_acquire(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if let Some(until) = state.parked_until {
+            if until > now {
+                return Some(until - now);
+            }
+            state.parked_until = None;
+        }
+        state.refill(now);
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            None
+        } else {
+            let per_second = state.per_minute as f64 / 60.0;
+            Some(Duration::from_secs_f64((1.0 - state.tokens) / per_second))
+        }
+    }
+
+    /// Wait until a request may be sent
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire() {
+            debug!(
+                wait_ms = wait.as_millis() as u64,
+                "Waiting for the rate limit"
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Apply the `X-RateLimit-Limit`, `X-RateLimit-Remaining` and
+    /// `X-RateLimit-Reset` (Unix seconds) headers of a response; `header`
+    /// returns a header's value as a number
+    pub fn observe(&self, header: impl Fn(&str) -> Option<u64>) {
+        let limit = header("x-ratelimit-limit");
+        let remaining = header("x-ratelimit-remaining");
+        let reset = header("x-ratelimit-reset");
+        self.update(limit, remaining, reset);
+    }
+
+    /// `observe` with the values already read
+    pub fn update(&self, limit: Option<u64>, remaining: Option<u64>, reset: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.refill(now);
+        if let Some(limit) = limit {
+            if limit > 0 && limit < state.per_minute as u64 {
+                debug!(limit, "Lowering the rate limit to the account's");
+                state.per_minute = limit as u32;
+                state.tokens = state.tokens.min(limit as f64);
+            }
+        }
+        let Some(remaining) = remaining else {
+            return;
+        };
+        state.tokens = state.tokens.min(remaining as f64);
+        if remaining == 0 {
+            let epoch_now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            // Wait at least a second in case the clocks disagree
+            let wait = Duration::from_secs(
+                reset
+                    .map_or(60, |reset| reset.saturating_sub(epoch_now))
+                    .max(1),
+            );
+            warn!(
+                wait_secs = wait.as_secs(),
+                "Rate limit exhausted, parking requests until it resets"
+            );
+            state.parked_until = Some(now + wait);
+        }
+    }
+}
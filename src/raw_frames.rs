@@ -0,0 +1,133 @@
+//! Recording the messages of the websockets as they arrive, before they are
+//! decoded, so parser problems can be debugged and sessions replayed against
+//! the payloads Alpaca really sent.
+//!
+//! A recording is newline-delimited JSON with one [`RawFrame`] per line.
+//! Frames of the `trade_updates` stream are stored byte for byte. Market data
+//! frames are split into messages by the Alpaca client before this crate
+//! sees them, so each market data message is stored on its own line, with
+//! every field the client received.
+
+use crate::compression::{Compression, OutputFile};
+use crate::{decode_streaming_message, Result, StreamingData};
+use alpaca_trading_api_rust::StreamingMessage;
+use chrono::{DateTime, Utc};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Source of frames from the market data stream
+pub const MARKET_DATA: &str = "market_data";
+/// Source of frames from the account's `trade_updates` stream
+pub const TRADE_UPDATES: &str = "trade_updates";
+
+/// One line of a raw recording
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RawFrame {
+    /// When the client received the frame
+    pub received_time: DateTime<Utc>,
+    /// The websocket it came from, `market_data` or `trade_updates`
+    pub source: String,
+    /// Connection that received it, when the symbols are spread over several
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<usize>,
+    /// The payload as received
+    pub frame: String,
+}
+
+impl RawFrame {
+    /// Decode the frame the way the session does. A market data frame may
+    /// hold one message or an array of them; a `trade_updates` frame becomes
+    /// a `trade_update` message. Records keep the time the frame was
+    /// received at; messages that fail to parse are logged and left out.
+    pub fn decode(&self) -> Result<Vec<StreamingData>> {
+        let payload: serde_json::Value = serde_json::from_str(&self.frame)?;
+        let messages = if self.source == TRADE_UPDATES {
+            if payload["stream"] != TRADE_UPDATES {
+                return Ok(Vec::new());
+            }
+            vec![StreamingMessage {
+                message_type: "trade_update".to_string(),
+                message: None,
+                data: payload["data"].clone(),
+            }]
+        } else {
+            let payloads = match payload {
+                serde_json::Value::Array(payloads) => payloads,
+                payload => vec![payload],
+            };
+            payloads
+                .into_iter()
+                .map(serde_json::from_value::<StreamingMessage>)
+                .collect::<serde_json::Result<_>>()?
+        };
+        let mut records = Vec::new();
+        for message in &messages {
+            if let Some(mut data) = decode_streaming_message(message)? {
+                data.received_time = self.received_time;
+                records.push(data);
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// The raw frame on a line of a recording; `None` for other lines
+pub fn raw_frame(line: &str) -> Option<RawFrame> {
+    if !line.starts_with('{') {
+        return None;
+    }
+    serde_json::from_str(line).ok()
+}
+
+/// Appends frames to a recording; clones share the file, so every connection
+/// of a session can write to one recording. Each frame is flushed as it is
+/// written, so a recording is complete up to the frame that crashed a parser.
+/// Recordings named `.gz` or `.zst` are compressed, and completed by `close`.
+#[derive(Debug, Clone)]
+pub struct RawRecorder {
+    file: Arc<Mutex<BufWriter<OutputFile>>>,
+}
+
+impl RawRecorder {
+    pub fn create(path: &Path, append: bool) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(append)
+            .write(true)
+            .truncate(!append)
+            .open(path)?;
+        let file = OutputFile::new(file, Compression::from_extension(path))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+
+    /// Record a frame received now
+    pub fn record(&self, source: &str, shard: Option<usize>, frame: &str) -> Result<()> {
+        let frame = RawFrame {
+            received_time: Utc::now(),
+            source: source.to_string(),
+            shard,
+            frame: frame.to_string(),
+        };
+        let mut file = self.file.lock().unwrap();
+        serde_json::to_writer(&mut *file, &frame)?;
+        file.write_all(b"\n")?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Record a market data message as the Alpaca client handed it over
+    pub fn record_message(&self, shard: Option<usize>, message: &StreamingMessage) -> Result<()> {
+        self.record(MARKET_DATA, shard, &serde_json::to_string(message)?)
+    }
+
+    /// Write out everything recorded and complete a compressed recording
+    pub fn close(&self) -> Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.flush()?;
+        file.get_mut().finish()
+    }
+}
@@ -0,0 +1,137 @@
+//! Replay of a recorded capture through a session's outputs, filter and
+//! monitors in place of the websocket, so strategies and sinks can be run
+//! against a real day's data. Messages are written as fast as the outputs
+//! take them, or paced by the gaps between their received times.
+
+use crate::capture::{read_capture, CaptureFormat};
+use crate::daemon::shutdown_signal;
+use crate::{Error, OutputSink, Result, StreamingConfig, StreamingData};
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::info;
+
+/// Messages read ahead of the one being written
+const READ_AHEAD: usize = 1024;
+
+/// How fast a capture is replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// As fast as the outputs take messages
+    Max,
+    /// The gaps between messages as received, divided by this factor
+    Scaled(f64),
+}
+
+impl ReplaySpeed {
+    /// When a message received at `received` is due, counted from the start
+    /// of the replay; `None` at full speed. Messages received out of order
+    /// are due right away.
+    pub fn due(&self, first: DateTime<Utc>, received: DateTime<Utc>) -> Option<Duration> {
+        match self {
+            ReplaySpeed::Max => None,
+            ReplaySpeed::Scaled(factor) => Some(
+                (received - first)
+                    .to_std()
+                    .unwrap_or_default()
+                    .div_f64(*factor),
+            ),
+        }
+    }
+}
+
+/// Parse `max`, or a factor of the original timing such as `1`, `10x` or
+/// `0.5x`
+pub fn parse_replay_speed(value: &str) -> Result<ReplaySpeed> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("max") {
+        return Ok(ReplaySpeed::Max);
+    }
+    match value.trim_end_matches(['x', 'X']).parse::<f64>() {
+        Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(ReplaySpeed::Scaled(factor)),
+        _ => Err(Error::Invalid(format!(
+            "Invalid replay speed: {}. Use max, or a factor such as 1, 10x or 0.5x",
+            value
+        ))),
+    }
+}
+
+/// Messages of a replay
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    pub read: u64,
+    pub written: u64,
+    /// Messages the session's filter left out
+    pub filtered: u64,
+}
+
+/// Write every message of the capture at `path` to the outputs of `config`,
+/// in capture order, skipping those its filter rejects. Messages keep the
+/// times they were received at. Stops early on Ctrl+C or SIGTERM.
+pub async fn run_replay(
+    path: &Path,
+    format: CaptureFormat,
+    speed: ReplaySpeed,
+    config: &StreamingConfig,
+) -> Result<ReplayStats> {
+    info!(path = %path.display(), ?format, ?speed, "Replaying capture");
+    let (sender, mut receiver) = mpsc::channel::<StreamingData>(READ_AHEAD);
+    let input = path.to_path_buf();
+    let reader = tokio::task::spawn_blocking(move || {
+        read_capture(&input, format, |data| {
+            sender
+                .blocking_send(data)
+                .map_err(|_| Error::Stopped("Replay stopped".to_string()))
+        })
+    });
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut stats = ReplayStats::default();
+    let mut start = None;
+    let mut interrupted = false;
+    loop {
+        let data = tokio::select! {
+            data = receiver.recv() => data,
+            _ = &mut shutdown => {
+                interrupted = true;
+                None
+            }
+        };
+        let Some(data) = data else {
+            break;
+        };
+        stats.read += 1;
+        let (first, started) = *start.get_or_insert((data.received_time, Instant::now()));
+        if let Some(due) = speed.due(first, data.received_time) {
+            tokio::select! {
+                _ = tokio::time::sleep_until(started + due) => {}
+                _ = &mut shutdown => {
+                    interrupted = true;
+                    break;
+                }
+            }
+        }
+        if config.filter.as_ref().is_some_and(|filter| !filter.matches(&data)) {
+            stats.filtered += 1;
+            continue;
+        }
+        config.output_mode.write_streaming_data(&data)?;
+        stats.written += 1;
+    }
+    if interrupted {
+        info!("Received shutdown signal, stopping the replay");
+    }
+    // Closing the channel stops a reader that is still going
+    drop(receiver);
+    let result = reader
+        .await
+        .map_err(|e| Error::Stopped(format!("Capture reader failed: {}", e)))?;
+    match result {
+        Err(_) if interrupted => {}
+        result => result?,
+    }
+    Ok(stats)
+}
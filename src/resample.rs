@@ -0,0 +1,549 @@
+//! Resampling of downloaded bars, such as 1-minute bars, into longer
+//! timeframes locally: seconds, minutes and hours within each session,
+//! trading days, weeks from Monday and months. Captured trades are resampled
+//! the same way, each as a bar of its own.
+
+use crate::compression::open_reader;
+use crate::market_time::{eastern_offset, new_york_date};
+use crate::{DataFormat, Error, Result, StreamingData};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Header of bars written as CSV, by `historical` and `resample`
+pub const BARS_CSV_HEADER: &str = "symbol,timestamp,open,high,low,close,volume,trade_count,vwap";
+
+/// A bar as `historical` writes it
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OhlcvBar {
+    pub symbol: String,
+    /// Start of the bar
+    pub timestamp: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    pub trade_count: u64,
+    pub vwap: f64,
+}
+
+impl OhlcvBar {
+    /// A bar of the single trade in a captured `t` message, which
+    /// `Resampler` can aggregate like any other; `None` for other messages
+    /// and trades without a price or size
+    pub fn from_trade(data: &StreamingData) -> Option<Self> {
+        let symbol = data.symbol.as_ref().filter(|_| data.message_type == "t")?;
+        let price = data.data["p"].as_f64()?;
+        Some(Self {
+            symbol: symbol.clone(),
+            timestamp: data.event_time.unwrap_or(data.received_time),
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: data.data["s"].as_u64()?,
+            trade_count: 1,
+            vwap: price,
+        })
+    }
+
+    /// The bar of a captured `b` message; `None` for other messages and bars
+    /// missing a price
+    pub fn from_bar_message(data: &StreamingData) -> Option<Self> {
+        let symbol = data.symbol.as_ref().filter(|_| data.message_type == "b")?;
+        let close = data.data["c"].as_f64()?;
+        Some(Self {
+            symbol: symbol.clone(),
+            timestamp: data.event_time.unwrap_or(data.received_time),
+            open: data.data["o"].as_f64()?,
+            high: data.data["h"].as_f64()?,
+            low: data.data["l"].as_f64()?,
+            close,
+            volume: data.data["v"].as_u64().unwrap_or(0),
+            trade_count: data.data["n"].as_u64().unwrap_or(0),
+            vwap: data.data["vw"].as_f64().unwrap_or(close),
+        })
+    }
+
+    /// The bar as a line in `format`, matching `BARS_CSV_HEADER` for CSV
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        let timestamp = self
+            .timestamp
+            .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+        Ok(match format {
+            DataFormat::Plain => {
+                let change = self.close - self.open;
+                format!(
+                    "📊 {}: {} | O: ${:.2} H: ${:.2} L: ${:.2} C: ${:.2} | Vol: {} | Change: ${:.2} ({:.2}%)",
+                    self.symbol,
+                    timestamp,
+                    self.open,
+                    self.high,
+                    self.low,
+                    self.close,
+                    self.volume,
+                    change,
+                    change / self.open * 100.0
+                )
+            }
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => format!(
+                "{},{},{:.2},{:.2},{:.2},{:.2},{},{},{}",
+                self.symbol,
+                timestamp,
+                self.open,
+                self.high,
+                self.low,
+                self.close,
+                self.volume,
+                self.trade_count,
+                self.vwap
+            ),
+        })
+    }
+}
+
+/// Unit of a `Timeframe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeframeUnit {
+    Second,
+    Minute,
+    Hour,
+    /// Trading days, the days that have bars
+    Day,
+    /// Weeks starting on Monday
+    Week,
+    Month,
+}
+
+/// A timeframe to resample to, such as `30Sec`, `2Min`, `4Hour` or `1Week`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeframe {
+    pub amount: u32,
+    pub unit: TimeframeUnit,
+}
+
+impl Timeframe {
+    /// Parse `<N><UNIT>`, with unit `Sec` (`S`), `Min`, `Hour` (`H`), `Day`
+    /// (`D`), `Week` (`W`) or `Month` (`M`), in any case
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Invalid(format!(
+                "Invalid timeframe: {}; expected e.g. 30Sec, 2Min, 4Hour, 1Day, 1Week or 3Month",
+                value
+            ))
+        };
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let amount: u32 = value[..split].parse().map_err(|_| invalid())?;
+        let unit = match value[split..].to_lowercase().as_str() {
+            "sec" | "s" => TimeframeUnit::Second,
+            "min" => TimeframeUnit::Minute,
+            "hour" | "h" => TimeframeUnit::Hour,
+            "day" | "d" => TimeframeUnit::Day,
+            "week" | "w" => TimeframeUnit::Week,
+            "month" | "m" => TimeframeUnit::Month,
+            _ => return Err(invalid()),
+        };
+        if amount == 0 {
+            return Err(invalid());
+        }
+        Ok(Self { amount, unit })
+    }
+
+    /// Length of an intraday timeframe
+    fn intraday(&self) -> Option<Duration> {
+        match self.unit {
+            TimeframeUnit::Second => Some(Duration::seconds(self.amount as i64)),
+            TimeframeUnit::Minute => Some(Duration::minutes(self.amount as i64)),
+            TimeframeUnit::Hour => Some(Duration::hours(self.amount as i64)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Timeframe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            TimeframeUnit::Second => "Sec",
+            TimeframeUnit::Minute => "Min",
+            TimeframeUnit::Hour => "Hour",
+            TimeframeUnit::Day => "Day",
+            TimeframeUnit::Week => "Week",
+            TimeframeUnit::Month => "Month",
+        };
+        write!(f, "{}{}", self.amount, unit)
+    }
+}
+
+/// Which bars of a day are kept, and where intraday bars are counted from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Session {
+    /// 09:30 to 16:00 New York time
+    #[default]
+    Regular,
+    /// 04:00 to 20:00, the pre-market, regular and after-hours sessions
+    Extended,
+    /// Every bar, counted from midnight
+    All,
+}
+
+impl Session {
+    /// Start and end of the session in New York time
+    fn hours(&self) -> (NaiveTime, NaiveTime) {
+        let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        match self {
+            Session::Regular => (time(9, 30), time(16, 0)),
+            Session::Extended => (time(4, 0), time(20, 0)),
+            Session::All => (NaiveTime::MIN, NaiveTime::MIN),
+        }
+    }
+}
+
+/// The bar being built for a symbol
+#[derive(Debug)]
+struct Partial {
+    bar: OhlcvBar,
+    /// Sum of each bar's VWAP times its volume
+    notional: f64,
+    /// New York date of the last bar added
+    date: NaiveDate,
+    /// Trading days in the bar so far
+    days: u32,
+}
+
+/// Aggregates the bars of each symbol, in time order, into `timeframe` bars
+/// without crossing a session: intraday bars are counted from the start of
+/// each day's session, so `4Hour` regular bars are 09:30-13:30 and
+/// 13:30-16:00. Daily and longer bars start at New York midnight of their
+/// first day, the Monday of a week or the first of a month. Open is the
+/// first open, close the last close, high and low the extremes, volume and
+/// trade count the sums and VWAP the volume-weighted mean of the VWAPs.
+#[derive(Debug)]
+pub struct Resampler {
+    timeframe: Timeframe,
+    session: Session,
+    partial: HashMap<String, Partial>,
+}
+
+impl Resampler {
+    pub fn new(timeframe: Timeframe, session: Session) -> Self {
+        Self {
+            timeframe,
+            session,
+            partial: HashMap::new(),
+        }
+    }
+
+    /// Add the next bar of its symbol; returns the symbol's previous bar
+    /// when `bar` starts a new one. Bars outside the session are skipped.
+    pub fn push(&mut self, bar: OhlcvBar) -> Result<Option<OhlcvBar>> {
+        let offset = eastern_offset(bar.timestamp.date_naive());
+        let local = bar.timestamp.with_timezone(&offset);
+        let date = local.date_naive();
+        let (open, close) = self.session.hours();
+        if self.session != Session::All && (local.time() < open || local.time() >= close) {
+            return Ok(None);
+        }
+
+        let start = match self.timeframe.intraday() {
+            Some(length) => {
+                let elapsed = local.time() - open;
+                let buckets = elapsed.num_seconds() / length.num_seconds();
+                new_york(date, open) + length * buckets as i32
+            }
+            None => {
+                let first = match self.timeframe.unit {
+                    TimeframeUnit::Week => {
+                        let monday =
+                            date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                        // Weeks are grouped counting from a Monday
+                        let epoch = NaiveDate::from_ymd_opt(1970, 1, 5).unwrap();
+                        let weeks = (monday - epoch).num_weeks();
+                        monday - Duration::weeks(weeks.rem_euclid(self.timeframe.amount as i64))
+                    }
+                    TimeframeUnit::Month => {
+                        let months = date.year() * 12 + date.month0() as i32;
+                        let first = months - months.rem_euclid(self.timeframe.amount as i32);
+                        NaiveDate::from_ymd_opt(first / 12, first as u32 % 12 + 1, 1).unwrap()
+                    }
+                    _ => date,
+                };
+                new_york(first, NaiveTime::MIN)
+            }
+        };
+
+        let partial = self.partial.get_mut(&bar.symbol);
+        if let Some(partial) = partial {
+            if bar.timestamp < partial.bar.timestamp {
+                return Err(Error::Invalid(format!(
+                    "Bars of {} are out of order at {}",
+                    bar.symbol, bar.timestamp
+                )));
+            }
+            let same = match self.timeframe.unit {
+                TimeframeUnit::Day => date == partial.date || partial.days < self.timeframe.amount,
+                _ => start == partial.bar.timestamp,
+            };
+            if same {
+                if date != partial.date {
+                    partial.days += 1;
+                    partial.date = date;
+                }
+                partial.bar.high = partial.bar.high.max(bar.high);
+                partial.bar.low = partial.bar.low.min(bar.low);
+                partial.bar.close = bar.close;
+                partial.bar.volume += bar.volume;
+                partial.bar.trade_count += bar.trade_count;
+                partial.notional += bar.vwap * bar.volume as f64;
+                partial.bar.vwap = vwap(partial.notional, partial.bar.volume, bar.vwap);
+                return Ok(None);
+            }
+        }
+
+        let symbol = bar.symbol.clone();
+        let next = Partial {
+            notional: bar.vwap * bar.volume as f64,
+            bar: OhlcvBar {
+                timestamp: start,
+                ..bar
+            },
+            date,
+            days: 1,
+        };
+        Ok(self
+            .partial
+            .insert(symbol, next)
+            .map(|previous| previous.bar))
+    }
+
+    /// Start of the bar being built for `symbol`; earlier bars can no
+    /// longer be added
+    pub fn current_start(&self, symbol: &str) -> Option<DateTime<Utc>> {
+        self.partial
+            .get(symbol)
+            .map(|partial| partial.bar.timestamp)
+    }
+
+    /// The bars still being built, by symbol
+    pub fn finish(self) -> Vec<OhlcvBar> {
+        let mut bars: Vec<OhlcvBar> = self
+            .partial
+            .into_values()
+            .map(|partial| partial.bar)
+            .collect();
+        bars.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        bars
+    }
+}
+
+/// How `Filler` fills intervals without a bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Fill {
+    /// Leave them out
+    #[default]
+    None,
+    /// Repeat the previous bar
+    Forward,
+    /// A bar at the previous close without volume or trades
+    ZeroVolume,
+}
+
+/// Makes intraday bar series regular: one bar per `timeframe` interval of
+/// the session, from each symbol's first bar to the end of the session of
+/// its last, on every day that has bars. Days without bars, such as
+/// holidays, are not filled.
+#[derive(Debug)]
+pub struct Filler {
+    length: Duration,
+    session: Session,
+    fill: Fill,
+    last: HashMap<String, OhlcvBar>,
+}
+
+impl Filler {
+    /// Fails for daily and longer timeframes, whose expected bars depend on
+    /// the market calendar
+    pub fn new(timeframe: Timeframe, session: Session, fill: Fill) -> Result<Self> {
+        let length = timeframe.intraday().ok_or_else(|| {
+            Error::Invalid(format!(
+                "Only Sec, Min and Hour bars can be filled, not {}",
+                timeframe
+            ))
+        })?;
+        Ok(Self {
+            length,
+            session,
+            fill,
+            last: HashMap::new(),
+        })
+    }
+
+    /// The bars missing before `bar` in its symbol's series, then `bar`
+    pub fn push(&mut self, bar: OhlcvBar) -> Vec<OhlcvBar> {
+        let mut bars = Vec::new();
+        if let Some(last) = self.last.get(&bar.symbol) {
+            let mut time = last.timestamp + self.length;
+            let date = new_york_date(bar.timestamp);
+            let last_date = new_york_date(last.timestamp);
+            if date != last_date {
+                let (_, close) = self.bounds(last_date);
+                bars.extend(self.fill_until(last, &mut time, close));
+                time = self.bounds(date).0;
+            }
+            bars.extend(self.fill_until(last, &mut time, bar.timestamp));
+        }
+        self.last.insert(bar.symbol.clone(), bar.clone());
+        bars.push(bar);
+        bars
+    }
+
+    /// The bars missing after each symbol's last bar, to the end of its
+    /// session, by symbol
+    pub fn finish(self) -> Vec<OhlcvBar> {
+        let mut last: Vec<&OhlcvBar> = self.last.values().collect();
+        last.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        last.into_iter()
+            .flat_map(|last| {
+                let mut time = last.timestamp + self.length;
+                let (_, close) = self.bounds(new_york_date(last.timestamp));
+                self.fill_until(last, &mut time, close)
+            })
+            .collect()
+    }
+
+    /// Fill bars after `last` from `time` until `end`
+    fn fill_until(
+        &self,
+        last: &OhlcvBar,
+        time: &mut DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<OhlcvBar> {
+        let mut bars = Vec::new();
+        while *time < end && self.fill != Fill::None {
+            bars.push(match self.fill {
+                Fill::Forward => OhlcvBar {
+                    timestamp: *time,
+                    ..last.clone()
+                },
+                _ => OhlcvBar {
+                    timestamp: *time,
+                    open: last.close,
+                    high: last.close,
+                    low: last.close,
+                    volume: 0,
+                    trade_count: 0,
+                    vwap: last.close,
+                    ..last.clone()
+                },
+            });
+            *time += self.length;
+        }
+        bars
+    }
+
+    /// Start and end of the session on `date`, in UTC
+    fn bounds(&self, date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+        let (open, close) = self.session.hours();
+        let end = match self.session {
+            Session::All => new_york(date + Duration::days(1), close),
+            _ => new_york(date, close),
+        };
+        (new_york(date, open), end)
+    }
+}
+
+fn vwap(notional: f64, volume: u64, last: f64) -> f64 {
+    if volume == 0 {
+        last
+    } else {
+        notional / volume as f64
+    }
+}
+
+/// `time` on `date` in New York, in UTC
+fn new_york(date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    eastern_offset(date)
+        .from_local_datetime(&date.and_time(time))
+        .single()
+        .expect("a fixed offset has one local time")
+        .with_timezone(&Utc)
+}
+
+/// Call `on_bar` with each bar of a CSV or JSON-lines file written by
+/// `historical`, optionally compressed, in file order. Repeated CSV headers
+/// and blank lines are skipped.
+pub fn read_bars(
+    path: &Path,
+    format: &DataFormat,
+    mut on_bar: impl FnMut(OhlcvBar) -> Result<()>,
+) -> Result<()> {
+    let columns: Vec<&str> = BARS_CSV_HEADER.split(',').collect();
+    let mut positions: Vec<usize> = (0..columns.len()).collect();
+    for (number, line) in open_reader(path)?.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || crate::metadata::is_metadata_line(line) {
+            continue;
+        }
+        let invalid = |reason: String| {
+            Error::Invalid(format!(
+                "{} line {}: {}",
+                path.display(),
+                number + 1,
+                reason
+            ))
+        };
+        let bar = match format {
+            DataFormat::Json => {
+                serde_json::from_str::<OhlcvBar>(line).map_err(|e| invalid(e.to_string()))?
+            }
+            DataFormat::Csv => {
+                let fields: Vec<&str> = line.split(',').collect();
+                if fields.contains(&"symbol") {
+                    positions = columns
+                        .iter()
+                        .map(|column| fields.iter().position(|field| field == column))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| invalid(format!("expected columns {}", BARS_CSV_HEADER)))?;
+                    continue;
+                }
+                let field = |index: usize| {
+                    fields
+                        .get(positions[index])
+                        .copied()
+                        .ok_or_else(|| invalid(format!("missing {}", columns[index])))
+                };
+                let number = |index: usize| -> Result<f64> {
+                    field(index)?
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid {}", columns[index])))
+                };
+                OhlcvBar {
+                    symbol: field(0)?.to_string(),
+                    timestamp: field(1)?
+                        .parse()
+                        .map_err(|_| invalid("invalid timestamp".to_string()))?,
+                    open: number(2)?,
+                    high: number(3)?,
+                    low: number(4)?,
+                    close: number(5)?,
+                    volume: number(6)? as u64,
+                    trade_count: number(7)? as u64,
+                    vwap: number(8)?,
+                }
+            }
+            DataFormat::Plain => {
+                return Err(Error::Invalid(
+                    "Plain output has no bars to read; use csv or json".to_string(),
+                ))
+            }
+        };
+        on_bar(bar)?;
+    }
+    Ok(())
+}
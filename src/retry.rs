@@ -0,0 +1,240 @@
+use crate::conflation::parse_duration;
+use crate::{Error, Result};
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// Retries of one REST request unless `--max-request-retries` says otherwise
+pub const DEFAULT_REQUEST_RETRIES: u32 = 3;
+
+/// How a connection retries after it drops: how often, how long it waits
+/// between attempts and when the count starts over.
+///
+/// The defaults retry 5 times, waiting 2s, 4s, 8s, ... up to 64s.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Consecutive failed attempts before giving up; `None` retries forever
+    #[serde(with = "max_retries")]
+    pub max_retries: Option<u32>,
+    /// Wait before the first retry; doubled for each further attempt
+    #[serde(with = "duration")]
+    pub backoff_base: Duration,
+    /// Longest wait between attempts
+    #[serde(with = "duration")]
+    pub backoff_max: Duration,
+    /// Randomize each wait by up to this fraction either way (0.0 to 1.0),
+    /// so many clients do not reconnect in lockstep
+    pub jitter: f64,
+    /// A connection has to stay up this long before its failed attempts are
+    /// forgotten; zero forgets them as soon as it authenticates
+    #[serde(with = "duration")]
+    pub reset_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: Some(5),
+            backoff_base: Duration::from_secs(2),
+            backoff_max: Duration::from_secs(64),
+            jitter: 0.0,
+            reset_after: Duration::ZERO,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Overrides from `ALPACA_MAX_RETRIES` (a number or `infinite`),
+    /// `ALPACA_RETRY_BACKOFF_BASE`, `ALPACA_RETRY_BACKOFF_MAX`,
+    /// `ALPACA_RETRY_JITTER` and `ALPACA_RETRY_RESET_AFTER`
+    pub fn from_env(mut self) -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok();
+        if let Some(value) = var("ALPACA_MAX_RETRIES") {
+            self.max_retries = parse_max_retries(&value)?;
+        }
+        if let Some(value) = var("ALPACA_RETRY_BACKOFF_BASE") {
+            self.backoff_base = parse_duration(&value)?;
+        }
+        if let Some(value) = var("ALPACA_RETRY_BACKOFF_MAX") {
+            self.backoff_max = parse_duration(&value)?;
+        }
+        if let Some(value) = var("ALPACA_RETRY_JITTER") {
+            self.jitter = parse_jitter(&value)?;
+        }
+        if let Some(value) = var("ALPACA_RETRY_RESET_AFTER") {
+            self.reset_after = parse_duration(&value)?;
+        }
+        Ok(self)
+    }
+
+    /// Retries of a single REST request: `max_retries` of them, waiting 1s,
+    /// 2s, 4s, ... up to 30s with 20% jitter
+    pub fn for_requests(max_retries: u32) -> Self {
+        Self {
+            max_retries: Some(max_retries),
+            backoff_base: Duration::from_secs(1),
+            backoff_max: Duration::from_secs(30),
+            jitter: 0.2,
+            reset_after: Duration::ZERO,
+        }
+    }
+
+    /// Whether `attempt` (counted from 1) used up the retry budget
+    pub fn exhausted(&self, attempt: u32) -> bool {
+        self.max_retries.is_some_and(|max| attempt >= max)
+    }
+
+    /// Wait before retrying after failed `attempt` (counted from 1), without
+    /// jitter
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff_base
+            .saturating_mul(factor)
+            .min(self.backoff_max)
+    }
+
+    /// Wait before retrying after failed `attempt`, with jitter applied
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay(attempt);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        let spread = self.jitter.min(1.0) * (fastrand::f64() * 2.0 - 1.0);
+        delay.mul_f64(1.0 + spread)
+    }
+}
+
+/// A REST response with a status other than 2xx
+#[derive(Debug, thiserror::Error)]
+#[error("HTTP {status}: {body}")]
+pub struct HttpError {
+    pub status: u16,
+    pub body: String,
+}
+
+impl HttpError {
+    /// `body` if `status` is a success, the error otherwise
+    pub fn check(status: u16, body: String) -> Result<String> {
+        if (200..300).contains(&status) {
+            Ok(body)
+        } else {
+            Err(HttpError { status, body }.into())
+        }
+    }
+
+    /// 429 and 5xx may pass on a later try; other statuses fail again
+    pub fn is_retryable(&self) -> bool {
+        self.status == 429 || self.status >= 500
+    }
+}
+
+/// Whether a failed request is worth repeating. HTTP errors go by their
+/// status and malformed responses are fatal. Errors of the Alpaca client
+/// only carry a message, so a 4xx status in it other than 429 is fatal too;
+/// anything else, such as a timeout or a dropped connection, is retried.
+pub fn is_retryable(error: &Error) -> bool {
+    match error {
+        Error::Http(http) => return http.is_retryable(),
+        Error::Json(_) => return false,
+        _ => {}
+    }
+    let message = error.to_string();
+    let status = message
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|word| word.parse::<u16>().ok())
+        .find(|status| (400..600).contains(status));
+    !matches!(status, Some(status) if status < 500 && status != 429)
+}
+
+/// Run `request` until it succeeds, fails with an error that is not
+/// retryable, or `policy` runs out of retries
+pub async fn retry_request<T, F, Fut>(policy: &RetryPolicy, mut request: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match request().await {
+            Ok(value) => return Ok(value),
+            Err(e) if is_retryable(&e) && !policy.exhausted(attempt - 1) => {
+                let delay = policy.delay(attempt);
+                warn!(
+                    attempt,
+                    max_retries = ?policy.max_retries,
+                    retry_in_ms = delay.as_millis() as u64,
+                    error = %e,
+                    "Request failed, retrying"
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A retry count, or `infinite` / `unlimited` for no limit
+pub fn parse_max_retries(value: &str) -> Result<Option<u32>> {
+    match value.trim().to_lowercase().as_str() {
+        "infinite" | "unlimited" => Ok(None),
+        number => number.parse().map(Some).map_err(|_| {
+            Error::Invalid(format!("Invalid max retries: {}. Use a number or infinite", value))
+        }),
+    }
+}
+
+/// A fraction from 0.0 to 1.0
+pub fn parse_jitter(value: &str) -> Result<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(jitter) if (0.0..=1.0).contains(&jitter) => Ok(jitter),
+        _ => Err(Error::Invalid(format!(
+            "Invalid jitter: {}. Use a fraction from 0.0 to 1.0",
+            value
+        ))),
+    }
+}
+
+/// `max_retries = 10` or `max_retries = "infinite"` in config files
+mod max_retries {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Count(u32),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(count) => serializer.serialize_u32(*count),
+            None => serializer.serialize_str("infinite"),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u32>, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Count(count) => Ok(Some(count)),
+            Value::Text(text) => super::parse_max_retries(&text).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Durations such as `"500ms"` or `"2m"` in config files
+mod duration {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{}ms", value.as_millis()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        crate::conflation::parse_duration(&text).map_err(serde::de::Error::custom)
+    }
+}
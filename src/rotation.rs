@@ -0,0 +1,276 @@
+use crate::{
+    Compression, DataFormat, Error, OutputMode, OutputSink, PlainTemplates, Result, StreamingData,
+};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RotateInterval {
+    /// New file every hour (UTC), e.g. capture-2024-05-03T14.jsonl
+    Hourly,
+    /// New file every day (UTC), e.g. capture-2024-05-03.jsonl
+    Daily,
+}
+
+impl RotateInterval {
+    fn period(&self, time: DateTime<Utc>) -> String {
+        match self {
+            RotateInterval::Hourly => time.format("%Y-%m-%dT%H").to_string(),
+            RotateInterval::Daily => time.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RotationPolicy {
+    pub interval: Option<RotateInterval>,
+    /// Start a new file once the current one reaches this many bytes
+    pub max_bytes: Option<u64>,
+    /// Gzip each file once it has been rotated out; not needed when the files
+    /// are already written compressed
+    pub compress: bool,
+}
+
+/// Parse a size such as `500MB`, `1.5G` or `4096` (bytes). Units are binary,
+/// so `1KB` is 1024 bytes.
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| Error::Invalid(format!("Invalid size: {}. Use e.g. 500MB, 2GB or 1048576", value)))?;
+    let multiplier: u64 = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(Error::Invalid(format!("Invalid size unit: {}. Supported: B, KB, MB, GB, TB", unit))),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Called with the path of each file that is complete
+type FinishedHook = Box<dyn Fn(&Path) + Send + Sync>;
+
+#[derive(Debug, Default)]
+struct RotationState {
+    output: Option<OutputMode>,
+    path: PathBuf,
+    period: Option<String>,
+    sequence: u32,
+}
+
+/// File output that starts a new file per time period and/or size limit.
+///
+/// Files are named after the configured path with the period and a sequence
+/// number inserted before the extension: `capture.jsonl` becomes
+/// `capture-2024-05-03.jsonl`, `capture-2024-05-03-1.jsonl`, ... Compressed
+/// files keep the compression extension last (`capture-2024-05-03.jsonl.zst`).
+pub struct RotatingFileSink {
+    base: PathBuf,
+    format: DataFormat,
+    append: bool,
+    policy: RotationPolicy,
+    compression: Compression,
+    templates: Option<Arc<PlainTemplates>>,
+    on_finished: Option<FinishedHook>,
+    state: Mutex<RotationState>,
+}
+
+impl std::fmt::Debug for RotatingFileSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RotatingFileSink")
+            .field("base", &self.base)
+            .field("format", &self.format)
+            .field("policy", &self.policy)
+            .field("compression", &self.compression)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RotatingFileSink {
+    pub fn new(base: &Path, format: DataFormat, append: bool, policy: RotationPolicy) -> Self {
+        Self {
+            base: base.to_path_buf(),
+            format,
+            append,
+            policy,
+            compression: Compression::None,
+            templates: None,
+            on_finished: None,
+            state: Mutex::new(RotationState::default()),
+        }
+    }
+
+    /// Write every file through a gzip or zstd encoder
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.base = compression.strip_extension(&self.base);
+        self.compression = compression;
+        self
+    }
+
+    /// Render plain-format lines in every file with `templates`
+    pub fn with_templates(mut self, templates: Arc<PlainTemplates>) -> Self {
+        self.templates = Some(templates);
+        self
+    }
+
+    /// Run `hook` with the final path (after any gzip) of every rotated file,
+    /// and of the current file when the sink is closed. It runs on the writing
+    /// thread, so it should only hand the path off (e.g. to an uploader).
+    pub fn on_finished<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Path) + Send + Sync + 'static,
+    {
+        self.on_finished = Some(Box::new(hook));
+        self
+    }
+
+    /// Path of the file currently being written, if any
+    pub fn current_path(&self) -> Option<PathBuf> {
+        let state = self.state.lock().unwrap();
+        state.output.as_ref().map(|_| state.path.clone())
+    }
+
+    fn path_for(&self, period: Option<&str>, sequence: u32) -> PathBuf {
+        let stem = self.base.file_stem().and_then(|s| s.to_str()).unwrap_or("capture");
+        let mut name = stem.to_string();
+        if let Some(period) = period {
+            name.push('-');
+            name.push_str(period);
+        }
+        if sequence > 0 {
+            name.push_str(&format!("-{}", sequence));
+        }
+        if let Some(extension) = self.base.extension().and_then(|s| s.to_str()) {
+            name.push('.');
+            name.push_str(extension);
+        }
+        self.compression.with_extension(&self.base.with_file_name(name))
+    }
+
+    fn write_with<F>(&self, time: DateTime<Utc>, write: F) -> Result<()>
+    where
+        F: FnOnce(&OutputMode) -> Result<()>,
+    {
+        let mut state = self.state.lock().unwrap();
+        let period = self.policy.interval.map(|interval| interval.period(time));
+
+        let rotate = match &state.output {
+            None => true,
+            Some(_) if state.period != period => true,
+            Some(_) => match self.policy.max_bytes {
+                Some(max_bytes) => std::fs::metadata(&state.path).map(|m| m.len() >= max_bytes).unwrap_or(false),
+                None => false,
+            },
+        };
+        if rotate {
+            self.open_next(&mut state, period)?;
+        }
+
+        write(state.output.as_ref().expect("output opened by rotate"))
+    }
+
+    fn open_next(&self, state: &mut RotationState, period: Option<String>) -> Result<()> {
+        // A file was opened before, whether or not it is still open
+        let rotated = !state.path.as_os_str().is_empty();
+        self.finish_current(state)?;
+
+        let mut sequence = if rotated && state.period == period { state.sequence + 1 } else { 0 };
+        // Never write over a file that was already rotated and compressed
+        while gzip_path(&self.path_for(period.as_deref(), sequence)).exists() {
+            sequence += 1;
+        }
+        let path = self.path_for(period.as_deref(), sequence);
+        tracing::info!(path = %path.display(), "Opening output file");
+
+        // Only append to a file that exists, so new CSV files still get a header
+        let append = (self.append || rotated) && path.exists();
+        let mut output = OutputMode::create_compressed_file_mode(&path, self.format.clone(), append, self.compression)?;
+        if let Some(templates) = &self.templates {
+            output = output.with_templates(templates.clone());
+        }
+        state.output = Some(output);
+        state.path = path;
+        state.period = period;
+        state.sequence = sequence;
+        Ok(())
+    }
+
+    fn finish_current(&self, state: &mut RotationState) -> Result<()> {
+        if let Some(output) = state.output.take() {
+            output.close()?;
+            drop(output);
+            let path = if self.policy.compress && self.compression == Compression::None {
+                compress_file(&state.path)?
+            } else {
+                state.path.clone()
+            };
+            if let Some(hook) = &self.on_finished {
+                hook(&path);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn gzip_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// Replace `path` with `path.gz`; the original is only removed once the
+/// compressed copy is complete
+fn compress_file(path: &Path) -> Result<PathBuf> {
+    let target = gzip_path(path);
+    let mut input = File::open(path)?;
+    let mut encoder = flate2::write::GzEncoder::new(File::create(&target)?, flate2::Compression::default());
+    std::io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::remove_file(path)?;
+    tracing::info!(path = %target.display(), "Compressed rotated file");
+    Ok(target)
+}
+
+impl OutputSink for RotatingFileSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        self.write_with(data.received_time, |output| output.write_streaming_data(data))
+    }
+
+    fn flush(&self) -> Result<()> {
+        match &self.state.lock().unwrap().output {
+            Some(output) => output.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// The current file has not been rotated out, so it is not gzipped and a
+    /// restart with `--append` continues it
+    fn close(&self) -> Result<()> {
+        let state = self.state.lock().unwrap();
+        if let Some(output) = &state.output {
+            output.close()?;
+            if let Some(hook) = &self.on_finished {
+                hook(&state.path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finish the current file (gzip, upload hook); the next write opens the
+    /// next file of the period
+    fn rotate(&self) -> Result<()> {
+        self.finish_current(&mut self.state.lock().unwrap())
+    }
+
+    fn writeln(&self, message: &str) -> Result<()> {
+        self.write_with(Utc::now(), |output| output.writeln(message))
+    }
+}
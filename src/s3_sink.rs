@@ -0,0 +1,235 @@
+use crate::{Error, OutputSink, Result, StreamingData};
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, WriteMultipart};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+
+/// Parts uploaded concurrently per object
+const MAX_CONCURRENT_PARTS: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub bucket: String,
+    /// Key prefix, e.g. `captures/vm-1`; objects are stored as `<prefix>/<file name>`
+    pub prefix: String,
+}
+
+impl S3Config {
+    /// Credentials, region and endpoint come from the standard `AWS_*`
+    /// environment variables (or instance metadata when running on AWS)
+    pub fn build_store(&self) -> Result<Arc<dyn ObjectStore>> {
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(&self.bucket)
+            .build()?;
+        Ok(Arc::new(store))
+    }
+
+    pub fn object_key(&self, name: &str) -> ObjectPath {
+        let prefix = self.prefix.trim_matches('/');
+        if prefix.is_empty() {
+            ObjectPath::from(name)
+        } else {
+            ObjectPath::from(format!("{}/{}", prefix, name))
+        }
+    }
+}
+
+/// Uploads finished capture files, one at a time, on a background task.
+///
+/// Pair it with `RotatingFileSink::on_finished` so each rotated file is
+/// shipped off the machine as soon as it is complete.
+#[derive(Debug, Clone)]
+pub struct S3Uploader {
+    sender: mpsc::UnboundedSender<UploadCommand>,
+    done: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+}
+
+#[derive(Debug)]
+enum UploadCommand {
+    Upload(PathBuf),
+    Shutdown,
+}
+
+impl S3Uploader {
+    pub fn new(config: S3Config, store: Arc<dyn ObjectStore>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(upload_loop(config, store, receiver));
+        Self {
+            sender,
+            done: Arc::new(Mutex::new(Some(handle))),
+        }
+    }
+
+    /// Queue a file for upload; callable from any thread
+    pub fn upload(&self, path: &Path) {
+        if self
+            .sender
+            .send(UploadCommand::Upload(path.to_path_buf()))
+            .is_err()
+        {
+            tracing::error!(path = %path.display(), "S3 uploader has stopped, file not uploaded");
+        }
+    }
+
+    /// Wait for queued uploads to finish. Files queued afterwards are not uploaded.
+    pub async fn shutdown(&self) -> Result<()> {
+        let handle = self.done.lock().unwrap().take();
+        let _ = self.sender.send(UploadCommand::Shutdown);
+        if let Some(handle) = handle {
+            handle
+                .await
+                .map_err(|e| Error::Stopped(format!("S3 upload task failed: {}", e)))?;
+        }
+        Ok(())
+    }
+}
+
+async fn upload_loop(
+    config: S3Config,
+    store: Arc<dyn ObjectStore>,
+    mut receiver: mpsc::UnboundedReceiver<UploadCommand>,
+) {
+    while let Some(UploadCommand::Upload(path)) = receiver.recv().await {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let key = config.object_key(name);
+        match upload_file(store.as_ref(), &path, &key).await {
+            Ok(bytes) => {
+                tracing::info!(path = %path.display(), key = %key, bytes, "Uploaded to S3")
+            }
+            Err(e) => {
+                tracing::error!(path = %path.display(), key = %key, error = %e, "S3 upload failed")
+            }
+        }
+    }
+}
+
+/// Multipart upload of a local file, reading it in chunks
+pub async fn upload_file(store: &dyn ObjectStore, path: &Path, key: &ObjectPath) -> Result<u64> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut writer = WriteMultipart::new(store.put_multipart(key).await?);
+    let mut buffer = vec![0; 1 << 20];
+    let mut total = 0;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        writer.wait_for_capacity(MAX_CONCURRENT_PARTS).await?;
+        writer.write(&buffer[..read]);
+        total += read as u64;
+    }
+    writer.finish().await?;
+    Ok(total)
+}
+
+enum StreamCommand {
+    Write(Vec<u8>),
+    Close(std_mpsc::Sender<Result<()>>),
+}
+
+/// Streams records as JSON lines straight into one S3 object per session
+/// through a multipart upload; no local file is involved.
+///
+/// The object only becomes visible once the session is closed.
+#[derive(Debug)]
+pub struct S3StreamSink {
+    sender: Mutex<Option<mpsc::UnboundedSender<StreamCommand>>>,
+    key: ObjectPath,
+}
+
+impl S3StreamSink {
+    pub async fn create(store: Arc<dyn ObjectStore>, key: ObjectPath) -> Result<Self> {
+        let writer = WriteMultipart::new(store.put_multipart(&key).await?);
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(stream_loop(writer, receiver));
+        Ok(Self {
+            sender: Mutex::new(Some(sender)),
+            key,
+        })
+    }
+
+    pub fn key(&self) -> &ObjectPath {
+        &self.key
+    }
+
+    fn send(&self, command: StreamCommand) -> Result<()> {
+        match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => sender
+                .send(command)
+                .map_err(|_| Error::Stopped("S3 upload task has stopped".to_string())),
+            None => Err(Error::Stopped("S3 sink is closed".to_string())),
+        }
+    }
+}
+
+async fn stream_loop(
+    mut writer: WriteMultipart,
+    mut receiver: mpsc::UnboundedReceiver<StreamCommand>,
+) {
+    while let Some(command) = receiver.recv().await {
+        match command {
+            StreamCommand::Write(bytes) => {
+                if let Err(e) = writer.wait_for_capacity(MAX_CONCURRENT_PARTS).await {
+                    tracing::error!(error = %e, "S3 part upload failed");
+                    let _ = writer.abort().await;
+                    return;
+                }
+                writer.write(&bytes);
+            }
+            StreamCommand::Close(done) => {
+                let result = writer
+                    .finish()
+                    .await
+                    .map(|_| ())
+                    .map_err(Error::from);
+                let _ = done.send(result);
+                return;
+            }
+        }
+    }
+    // Every sender dropped without a close: keep what was streamed
+    if let Err(e) = writer.finish().await {
+        tracing::error!(error = %e, "S3 upload failed");
+    }
+}
+
+impl OutputSink for S3StreamSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        let mut line = serde_json::to_vec(data)?;
+        line.push(b'\n');
+        self.send(StreamCommand::Write(line))
+    }
+
+    /// Parts are uploaded as they fill; there is nothing to flush early
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn close(&self) -> crate::Result<()> {
+        let (done_tx, done_rx) = std_mpsc::channel();
+        self.send(StreamCommand::Close(done_tx))?;
+        self.sender.lock().unwrap().take();
+
+        // Completing the upload needs the runtime, so wait only where blocking is allowed
+        let wait = || done_rx.recv_timeout(Duration::from_secs(60));
+        let result = match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(wait)
+            }
+            Ok(_) => return Ok(()),
+            Err(_) => wait(),
+        };
+        result.unwrap_or_else(|_| {
+            Err(Error::Stopped(format!(
+                "Timed out completing S3 upload of {}",
+                self.key
+            )))
+        })
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{payload_fields, Error, OutputSink, Result, StreamingData};
+use chrono::Utc;
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Runs a Rhai script on every record before it reaches the wrapped sink.
+///
+/// The script defines `fn on_message(msg)`. `msg` is a map with `type`,
+/// `symbol`, `received_time`, `event_time`, the raw payload in `data`, and
+/// read-only friendly fields such as `price`, `size`, `bid`, `ask` or `close`.
+/// The return value decides what is written:
+///
+/// - `()` or `true`: the record unchanged
+/// - `false`: nothing
+/// - a map: the record with that map's `type`, `symbol` and `data`; a `tags`
+///   array is stored as `data.tags`
+///
+/// `alert(text)` writes an `alert` record for the current symbol. A script
+/// error is logged and the record is written unchanged.
+#[derive(Clone)]
+pub struct ScriptHook {
+    inner: Arc<dyn OutputSink>,
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    alerts: Arc<Mutex<Vec<String>>>,
+    /// Held for a whole call so alerts are attributed to the right record
+    calling: Arc<Mutex<()>>,
+}
+
+impl std::fmt::Debug for ScriptHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptHook")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScriptHook {
+    pub fn load(path: &Path, inner: Box<dyn OutputSink>) -> Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        Self::compile(&source, inner)
+            .map_err(|e| Error::Invalid(format!("Invalid script {}: {}", path.display(), e)))
+    }
+
+    pub fn compile(source: &str, inner: Box<dyn OutputSink>) -> Result<Self> {
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = Engine::new();
+        // A runaway script must not stall the stream
+        engine.set_max_operations(1_000_000);
+        let pending = alerts.clone();
+        engine.register_fn("alert", move |text: &str| {
+            pending.lock().unwrap().push(text.to_string())
+        });
+
+        let ast = engine
+            .compile(source)
+            .map_err(script_error)?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == "on_message" && f.params.len() == 1)
+        {
+            return Err(Error::Invalid(
+                "the script must define fn on_message(msg)".to_string(),
+            ));
+        }
+        engine.run_ast(&ast).map_err(script_error)?;
+
+        Ok(Self {
+            inner: Arc::from(inner),
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+            alerts,
+            calling: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Records to write for `data`: none, the original, or the script's
+    /// replacement, followed by any alerts it raised
+    pub fn apply(&self, data: &StreamingData) -> Result<Vec<StreamingData>> {
+        let _calling = self.calling.lock().unwrap();
+        self.alerts.lock().unwrap().clear();
+
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut Scope::new(),
+            &self.ast,
+            "on_message",
+            (to_script_map(data)?,),
+        );
+
+        let mut records = match result {
+            Ok(value) => from_script_value(data, value)?,
+            Err(e) => {
+                tracing::warn!(error = %e, message_type = %data.message_type, "Script failed, writing record unchanged");
+                vec![data.clone()]
+            }
+        };
+        for text in self.alerts.lock().unwrap().drain(..) {
+            records.push(StreamingData {
+                received_time: Utc::now(),
+                event_time: data.event_time,
+                message_type: "alert".to_string(),
+                symbol: data.symbol.clone(),
+                data: serde_json::json!({ "message": text, "source": "script" }),
+            });
+        }
+        Ok(records)
+    }
+}
+
+fn to_script_map(data: &StreamingData) -> Result<Map> {
+    let mut map = Map::new();
+    map.insert("type".into(), data.message_type.clone().into());
+    map.insert(
+        "symbol".into(),
+        data.symbol.clone().map_or(Dynamic::UNIT, Dynamic::from),
+    );
+    map.insert(
+        "received_time".into(),
+        data.received_time.to_rfc3339().into(),
+    );
+    map.insert(
+        "event_time".into(),
+        data.event_time
+            .map_or(Dynamic::UNIT, |t| t.to_rfc3339().into()),
+    );
+    for (name, key) in payload_fields(&data.message_type) {
+        if let Some(value) = data.data.get(key) {
+            map.insert((*name).into(), rhai::serde::to_dynamic(value).map_err(script_error)?);
+        }
+    }
+    map.insert("data".into(), rhai::serde::to_dynamic(&data.data).map_err(script_error)?);
+    Ok(map)
+}
+
+fn from_script_value(data: &StreamingData, value: Dynamic) -> Result<Vec<StreamingData>> {
+    if value.is_unit() {
+        return Ok(vec![data.clone()]);
+    }
+    if let Ok(keep) = value.as_bool() {
+        return Ok(if keep { vec![data.clone()] } else { Vec::new() });
+    }
+    let Some(map) = value.try_cast::<Map>() else {
+        return Err(Error::Invalid(
+            "on_message must return (), a bool or a map".to_string(),
+        ));
+    };
+
+    let mut record = data.clone();
+    if let Some(message_type) = map.get("type").and_then(|v| v.clone().into_string().ok()) {
+        record.message_type = message_type;
+    }
+    if let Some(symbol) = map.get("symbol") {
+        record.symbol = symbol.clone().into_string().ok();
+    }
+    if let Some(payload) = map.get("data") {
+        record.data = rhai::serde::from_dynamic(payload).map_err(script_error)?;
+    }
+    if let Some(tags) = map.get("tags") {
+        if let serde_json::Value::Object(payload) = &mut record.data {
+            payload.insert("tags".to_string(), rhai::serde::from_dynamic(tags).map_err(script_error)?);
+        }
+    }
+    Ok(vec![record])
+}
+
+/// Compile, evaluation and conversion errors of rhai carry a message only
+fn script_error(error: impl std::fmt::Display) -> Error {
+    Error::Invalid(error.to_string())
+}
+
+impl OutputSink for ScriptHook {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        for record in self.apply(data)? {
+            self.inner.write_streaming_data(&record)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> crate::Result<()> {
+        self.inner.close()
+    }
+
+    fn rotate(&self) -> crate::Result<()> {
+        self.inner.rotate()
+    }
+
+    fn writeln(&self, message: &str) -> crate::Result<()> {
+        self.inner.writeln(message)
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{OutputSink, StreamingData, TradeCancel, TradeCorrection};
+use alpaca_trading_api_rust::StreamingTrade;
+use crate::Result;
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Running trade statistics for one symbol since the session started; the
+/// payload of `stats` messages
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SymbolSessionStats {
+    pub symbol: String,
+    pub trades: u64,
+    pub volume: u64,
+    pub vwap: f64,
+    pub high: f64,
+    pub low: f64,
+    pub last: f64,
+    /// Set on the summary written when the session ends
+    #[serde(rename = "final", default)]
+    pub is_final: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Accumulator {
+    trades: u64,
+    volume: u64,
+    notional: f64,
+    high: f64,
+    low: f64,
+    last: f64,
+    /// Trade count per price, keyed by `f64::to_bits` (prices are positive,
+    /// so bit order is numeric order); lets high/low be recomputed when a
+    /// trade is removed
+    prices: BTreeMap<u64, u64>,
+}
+
+impl Accumulator {
+    fn add(&mut self, price: f64, size: u64) {
+        if self.prices.is_empty() {
+            self.high = price;
+            self.low = price;
+        }
+        self.trades += 1;
+        self.volume += size;
+        self.notional += price * size as f64;
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.last = price;
+        *self.prices.entry(price.to_bits()).or_default() += 1;
+    }
+
+    /// Take back a trade recorded earlier; false if no trade at `price` was seen
+    fn remove(&mut self, price: f64, size: u64) -> bool {
+        let Some(count) = self.prices.get_mut(&price.to_bits()) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.prices.remove(&price.to_bits());
+        }
+        self.trades -= 1;
+        self.volume = self.volume.saturating_sub(size);
+        self.notional -= price * size as f64;
+        if let (Some((&low, _)), Some((&high, _))) =
+            (self.prices.first_key_value(), self.prices.last_key_value())
+        {
+            self.low = f64::from_bits(low);
+            self.high = f64::from_bits(high);
+        }
+        true
+    }
+}
+
+/// Accumulates VWAP, volume, high/low and trade count per symbol from the
+/// trades it receives as a sink. Clones share the same totals.
+#[derive(Debug, Clone, Default)]
+pub struct SessionStats {
+    symbols: Arc<Mutex<HashMap<String, Accumulator>>>,
+    apply_corrections: bool,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Revise the totals when trade corrections (`c`) and cancels (`x`)
+    /// arrive for trades already counted. `last` is not revised.
+    pub fn with_corrections(mut self, apply: bool) -> Self {
+        self.apply_corrections = apply;
+        self
+    }
+
+    pub fn record_trade(&self, symbol: &str, price: f64, size: u64) {
+        let mut symbols = self.symbols.lock().unwrap();
+        symbols
+            .entry(symbol.to_string())
+            .or_insert(Accumulator {
+                trades: 0,
+                volume: 0,
+                notional: 0.0,
+                high: price,
+                low: price,
+                last: price,
+                prices: BTreeMap::new(),
+            })
+            .add(price, size);
+    }
+
+    /// Replace a counted trade with its corrected price and size
+    pub fn correct_trade(&self, correction: &TradeCorrection) {
+        let mut symbols = self.symbols.lock().unwrap();
+        let Some(stats) = symbols.get_mut(&correction.symbol) else {
+            return;
+        };
+        if stats.remove(correction.original_price, correction.original_size) {
+            let last = stats.last;
+            stats.add(correction.corrected_price, correction.corrected_size);
+            stats.last = last;
+        }
+    }
+
+    /// Drop a counted trade that was canceled or reported in error
+    pub fn cancel_trade(&self, cancel: &TradeCancel) {
+        let mut symbols = self.symbols.lock().unwrap();
+        let Some(stats) = symbols.get_mut(&cancel.symbol) else {
+            return;
+        };
+        if stats.remove(cancel.price, cancel.size) && stats.trades == 0 {
+            symbols.remove(&cancel.symbol);
+        }
+    }
+
+    /// Current totals for every traded symbol, sorted by symbol
+    pub fn snapshot(&self) -> Vec<SymbolSessionStats> {
+        let symbols = self.symbols.lock().unwrap();
+        let mut snapshot: Vec<SymbolSessionStats> = symbols
+            .iter()
+            .map(|(symbol, stats)| SymbolSessionStats {
+                symbol: symbol.clone(),
+                trades: stats.trades,
+                volume: stats.volume,
+                // Trades reported with zero size leave nothing to weight by
+                vwap: if stats.volume > 0 {
+                    stats.notional / stats.volume as f64
+                } else {
+                    stats.last
+                },
+                high: stats.high,
+                low: stats.low,
+                last: stats.last,
+                is_final: false,
+            })
+            .collect();
+        snapshot.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        snapshot
+    }
+
+    /// Write one `stats` message per symbol to `output`
+    pub fn emit(&self, output: &dyn OutputSink, is_final: bool) -> Result<()> {
+        for stats in self.snapshot() {
+            let data = StreamingData {
+                received_time: Utc::now(),
+                event_time: None,
+                message_type: "stats".to_string(),
+                symbol: Some(stats.symbol.clone()),
+                data: serde_json::to_value(SymbolSessionStats { is_final, ..stats })?,
+            };
+            output.write_streaming_data(&data)?;
+        }
+        Ok(())
+    }
+
+    /// Call `emit` every `interval`; runs until the task is dropped
+    pub async fn emit_every(self, output: Vec<Arc<dyn OutputSink>>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.emit(&output, false) {
+                tracing::warn!(error = %e, "Failed to write session stats");
+            }
+        }
+    }
+}
+
+impl OutputSink for SessionStats {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        match data.message_type.as_str() {
+            "t" => {
+                let trade: StreamingTrade = serde_json::from_value(data.data.clone())?;
+                self.record_trade(&trade.symbol, trade.price, trade.size);
+            }
+            "c" if self.apply_corrections => {
+                self.correct_trade(&serde_json::from_value(data.data.clone())?);
+            }
+            "x" if self.apply_corrections => {
+                self.cancel_trade(&serde_json::from_value(data.data.clone())?);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
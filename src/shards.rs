@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Counters for one websocket connection of a streaming session
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ShardStats {
+    pub shard: usize,
+    pub symbols: usize,
+    pub connected: bool,
+    pub messages: u64,
+    pub disconnects: u64,
+    pub reconnects: u64,
+}
+
+/// Per-connection counters of a streaming session, filled in by
+/// `run_streaming_client`. Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct ShardMetrics {
+    shards: Arc<Mutex<Vec<ShardStats>>>,
+}
+
+impl ShardMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start counting for connections carrying `symbols[i]` symbols each
+    pub(crate) fn reset(&self, symbols: &[usize]) {
+        *self.shards.lock().unwrap() = symbols
+            .iter()
+            .enumerate()
+            .map(|(shard, symbols)| ShardStats {
+                shard,
+                symbols: *symbols,
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    fn update<F: FnOnce(&mut ShardStats)>(&self, shard: usize, update: F) {
+        if let Some(stats) = self.shards.lock().unwrap().get_mut(shard) {
+            update(stats);
+        }
+    }
+
+    pub(crate) fn record_message(&self, shard: usize) {
+        self.update(shard, |stats| stats.messages += 1);
+    }
+
+    pub(crate) fn record_connected(&self, shard: usize, reconnect: bool) {
+        self.update(shard, |stats| {
+            stats.connected = true;
+            stats.reconnects += reconnect as u64;
+        });
+    }
+
+    pub(crate) fn record_disconnect(&self, shard: usize) {
+        self.update(shard, |stats| {
+            stats.connected = false;
+            stats.disconnects += 1;
+        });
+    }
+
+    pub fn snapshot(&self) -> Vec<ShardStats> {
+        self.shards.lock().unwrap().clone()
+    }
+
+    /// Log the counters, one event per connection
+    pub fn report(&self) {
+        for stats in self.snapshot() {
+            tracing::info!(
+                shard = stats.shard,
+                symbols = stats.symbols,
+                connected = stats.connected,
+                messages = stats.messages,
+                disconnects = stats.disconnects,
+                reconnects = stats.reconnects,
+                "Connection stats"
+            );
+        }
+    }
+
+    /// Call `report` every `interval`; runs until the task is dropped
+    pub async fn report_every(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            self.report();
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! Synthetic market data in place of the Alpaca connection, so a session's
+//! outputs, filter, strategies and monitors can be run offline and in CI
+//! without credentials. Each symbol's price follows a random walk; trades and
+//! quotes arrive at random around a mean rate and minute bars are built from
+//! the trades.
+
+use crate::daemon::shutdown_signal;
+use crate::{
+    data_type_name, decode_streaming_message, Error, OutputSink, Result, StreamingConfig,
+    StreamingData, SubscriptionCommand,
+};
+use alpaca_trading_api_rust::{StreamingDataType, StreamingMessage};
+use chrono::{DateTime, DurationRound, SecondsFormat, TimeDelta, Utc};
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::info;
+
+/// Seconds in a trading year of 252 days of 6.5 hours, the period
+/// volatility is quoted over
+const TRADING_YEAR_SECS: f64 = 252.0 * 6.5 * 3600.0;
+
+/// Exchange code of the generated trades and quotes (IEX)
+const EXCHANGE: &str = "V";
+
+/// Where a streaming session's market data comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DataSource {
+    /// The Alpaca websocket (default)
+    #[default]
+    Alpaca,
+    /// Trades, quotes and bars generated locally by the simulator
+    Sim,
+}
+
+/// How the simulator generates data
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatorConfig {
+    /// Mean trades and quotes per second, over all symbols
+    pub rate: f64,
+    /// Annualized volatility of every price, e.g. 0.3 for 30%
+    pub volatility: f64,
+    /// Bid-ask spread in basis points of the price, at least one cent
+    pub spread_bps: f64,
+    /// Price every symbol starts at; `None` picks one between $20 and $500
+    /// per symbol
+    pub start_price: Option<f64>,
+    /// Seed of the random generator, for runs with the same prices and sizes
+    pub seed: Option<u64>,
+    /// Stop after this many trades and quotes
+    pub max_messages: Option<u64>,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            rate: 10.0,
+            volatility: 0.3,
+            spread_bps: 5.0,
+            start_price: None,
+            seed: None,
+            max_messages: None,
+        }
+    }
+}
+
+impl SimulatorConfig {
+    fn validate(&self) -> Result<()> {
+        if !(self.rate > 0.0 && self.rate.is_finite()) {
+            return Err(Error::Invalid(format!(
+                "Invalid simulator rate: {}. Use a number of messages per second above 0",
+                self.rate
+            )));
+        }
+        if !(self.volatility >= 0.0 && self.volatility.is_finite()) {
+            return Err(Error::Invalid(format!(
+                "Invalid simulator volatility: {}. Use a fraction such as 0.3",
+                self.volatility
+            )));
+        }
+        if !(self.spread_bps >= 0.0 && self.spread_bps.is_finite()) {
+            return Err(Error::Invalid(format!(
+                "Invalid simulator spread: {}. Use basis points such as 5",
+                self.spread_bps
+            )));
+        }
+        if let Some(price) = self
+            .start_price
+            .filter(|price| !(*price > 0.0 && price.is_finite()))
+        {
+            return Err(Error::Invalid(format!(
+                "Invalid simulator start price: {}",
+                price
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Messages of a simulated session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SimulatorStats {
+    pub trades: u64,
+    pub quotes: u64,
+    pub bars: u64,
+    /// Messages the session's filter left out
+    pub filtered: u64,
+}
+
+/// Minute bar of one symbol, built from its trades
+#[derive(Debug, Clone)]
+struct BarState {
+    start: DateTime<Utc>,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: u64,
+    trades: u64,
+    notional: f64,
+}
+
+#[derive(Debug)]
+struct SymbolState {
+    price: f64,
+    /// Simulated time of the last price move
+    updated: f64,
+    next_trade_id: u64,
+    bar: Option<BarState>,
+}
+
+/// Prices, trade ids and bars in progress of every symbol. Prices move with
+/// the simulated time between ticks rather than the wall clock, and
+/// everything is drawn from one random generator, so a seed repeats a run's
+/// symbols, prices and sizes however fast the outputs are.
+struct Simulator {
+    settings: SimulatorConfig,
+    rng: fastrand::Rng,
+    /// Seconds of simulated time
+    clock: f64,
+    symbols: HashMap<String, SymbolState>,
+}
+
+impl Simulator {
+    fn new(settings: SimulatorConfig) -> Self {
+        let rng = settings
+            .seed
+            .map(fastrand::Rng::with_seed)
+            .unwrap_or_default();
+        Self {
+            settings,
+            rng,
+            clock: 0.0,
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Standard normal draw (Box-Muller)
+    fn normal(&mut self) -> f64 {
+        let u = 1.0 - self.rng.f64();
+        let v = self.rng.f64();
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+
+    /// Wait until the next tick; exponential gaps make the ticks a Poisson
+    /// process at `rate`
+    fn next_tick(&mut self) -> Duration {
+        let gap = -(1.0 - self.rng.f64()).ln() / self.settings.rate;
+        self.clock += gap;
+        Duration::from_secs_f64(gap)
+    }
+
+    /// Move the price of `symbol` by a geometric random walk step over the
+    /// simulated time since it last moved
+    fn advance(&mut self, symbol: &str) -> f64 {
+        if !self.symbols.contains_key(symbol) {
+            let price = self
+                .settings
+                .start_price
+                .unwrap_or_else(|| round_cents(20.0 + self.rng.f64() * 480.0));
+            self.symbols.insert(
+                symbol.to_string(),
+                SymbolState {
+                    price,
+                    updated: self.clock,
+                    next_trade_id: 1,
+                    bar: None,
+                },
+            );
+        }
+        let z = self.normal();
+        let volatility = self.settings.volatility;
+        let state = self
+            .symbols
+            .get_mut(symbol)
+            .expect("symbol state was just added");
+        let years = (self.clock - state.updated) / TRADING_YEAR_SECS;
+        state.price *=
+            (volatility * years.sqrt() * z - 0.5 * volatility * volatility * years).exp();
+        state.price = state.price.max(0.01);
+        state.updated = self.clock;
+        state.price
+    }
+
+    /// Bid and ask around `price`, on whole cents and at least a cent apart
+    fn bid_ask(&self, price: f64) -> (f64, f64) {
+        let half = (price * self.settings.spread_bps / 20_000.0).max(0.005);
+        let bid = round_cents(price - half).max(0.01);
+        let ask = round_cents(price + half).max(round_cents(bid + 0.01));
+        (bid, ask)
+    }
+
+    fn trade(&mut self, symbol: &str, now: DateTime<Utc>) -> StreamingMessage {
+        let price = self.advance(symbol);
+        let (bid, ask) = self.bid_ask(price);
+        let price = if self.rng.bool() { bid } else { ask };
+        // Mostly round lots, with some odd lots
+        let size = if self.rng.u8(..4) == 0 {
+            self.rng.u64(1..100)
+        } else {
+            self.rng.u64(1..=10) * 100
+        };
+        let state = self
+            .symbols
+            .get_mut(symbol)
+            .expect("advance adds the symbol");
+        let id = state.next_trade_id;
+        state.next_trade_id += 1;
+        let start = now.duration_trunc(TimeDelta::minutes(1)).unwrap_or(now);
+        let bar = state.bar.get_or_insert(BarState {
+            start,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 0,
+            trades: 0,
+            notional: 0.0,
+        });
+        bar.high = bar.high.max(price);
+        bar.low = bar.low.min(price);
+        bar.close = price;
+        bar.volume += size;
+        bar.trades += 1;
+        bar.notional += price * size as f64;
+        message(
+            "t",
+            serde_json::json!({
+                "S": symbol,
+                "i": id,
+                "x": EXCHANGE,
+                "p": price,
+                "s": size,
+                "c": ["@"],
+                "z": "C",
+                "t": timestamp(now),
+            }),
+        )
+    }
+
+    fn quote(&mut self, symbol: &str, now: DateTime<Utc>) -> StreamingMessage {
+        let price = self.advance(symbol);
+        let (bid, ask) = self.bid_ask(price);
+        message(
+            "q",
+            serde_json::json!({
+                "S": symbol,
+                "bx": EXCHANGE,
+                "bp": bid,
+                "bs": self.rng.u64(1..=10) * 100,
+                "ax": EXCHANGE,
+                "ap": ask,
+                "as": self.rng.u64(1..=10) * 100,
+                "c": ["R"],
+                "z": "C",
+                "t": timestamp(now),
+            }),
+        )
+    }
+
+    /// Bars of the minutes that ended by `now`, of the symbols in `symbols`;
+    /// the bars of other symbols are dropped
+    fn close_bars(&mut self, symbols: &[String], now: DateTime<Utc>) -> Vec<StreamingMessage> {
+        let mut bars: Vec<(String, BarState)> = self
+            .symbols
+            .iter_mut()
+            .filter(|(_, state)| {
+                state
+                    .bar
+                    .as_ref()
+                    .is_some_and(|bar| bar.start + TimeDelta::minutes(1) <= now)
+            })
+            .filter_map(|(symbol, state)| Some((symbol.clone(), state.bar.take()?)))
+            .filter(|(symbol, _)| symbols.contains(symbol))
+            .collect();
+        bars.sort_by(|a, b| a.0.cmp(&b.0));
+        bars.into_iter()
+            .map(|(symbol, bar)| {
+                message(
+                    "b",
+                    serde_json::json!({
+                        "S": symbol,
+                        "o": bar.open,
+                        "h": bar.high,
+                        "l": bar.low,
+                        "c": bar.close,
+                        "v": bar.volume,
+                        "n": bar.trades,
+                        "vw": bar.notional / bar.volume as f64,
+                        "t": timestamp(bar.start),
+                    }),
+                )
+            })
+            .collect()
+    }
+}
+
+fn round_cents(price: f64) -> f64 {
+    (price * 100.0).round() / 100.0
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn message(message_type: &str, data: serde_json::Value) -> StreamingMessage {
+    StreamingMessage {
+        message_type: message_type.to_string(),
+        message: None,
+        data,
+    }
+}
+
+/// Run a session on simulated data until Ctrl+C, SIGTERM or
+/// `max_messages`: trades and quotes of the configured symbols are decoded,
+/// filtered and written like stream messages, and a bar of every symbol
+/// subscribed to bars that traded is written as each minute ends. Symbols
+/// subscribed to bars only trade without their trades being written.
+/// Subscription commands change the symbols as they would on a live session.
+pub async fn run_simulator(
+    settings: &SimulatorConfig,
+    config: &StreamingConfig,
+    commands: &mut mpsc::UnboundedReceiver<SubscriptionCommand>,
+) -> Result<SimulatorStats> {
+    settings.validate()?;
+    let mut subscriptions: HashMap<StreamingDataType, Vec<String>> = [
+        StreamingDataType::Trades,
+        StreamingDataType::Quotes,
+        StreamingDataType::Bars,
+    ]
+    .into_iter()
+    .map(|data_type| {
+        let symbols = config.symbols(&data_type).to_vec();
+        (data_type, symbols)
+    })
+    .collect();
+    info!(rate = settings.rate, volatility = settings.volatility, spread_bps = settings.spread_bps, seed = ?settings.seed, symbols = config.all_symbols().len(), "Simulating market data");
+
+    let mut simulator = Simulator::new(settings.clone());
+    let mut stats = SimulatorStats::default();
+    let write = |message: &StreamingMessage, stats: &mut SimulatorStats| -> Result<()> {
+        // Recorded as if it had come from the websocket
+        if let Some(recorder) = &config.raw_recorder {
+            recorder.record_message(None, message)?;
+        }
+        let Some(data) = decode_streaming_message(message)? else {
+            return Ok(());
+        };
+        if config
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !filter.matches(&data))
+        {
+            stats.filtered += 1;
+            return Ok(());
+        }
+        config.output_mode.write_streaming_data(&data)
+    };
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut commands_open = true;
+    let mut next_tick = Instant::now();
+    loop {
+        let now = Utc::now();
+        let minute_end =
+            now.duration_trunc(TimeDelta::minutes(1)).unwrap_or(now) + TimeDelta::minutes(1);
+        let next_minute = Instant::now() + (minute_end - now).to_std().unwrap_or_default();
+        tokio::select! {
+            biased;
+            _ = &mut shutdown => {
+                info!("Received shutdown signal, stopping the simulator");
+                break;
+            }
+            command = commands.recv(), if commands_open => {
+                match command {
+                    Some(command) => apply_command(config, &mut subscriptions, command)?,
+                    None => commands_open = false,
+                }
+                continue;
+            }
+            _ = tokio::time::sleep_until(next_minute) => {}
+            _ = tokio::time::sleep_until(next_tick) => {}
+        }
+        // Bars go out as their minute ends, ahead of the trades of the next one
+        for bar in simulator.close_bars(&subscriptions[&StreamingDataType::Bars], Utc::now()) {
+            stats.bars += 1;
+            write(&bar, &mut stats)?;
+        }
+        if Instant::now() < next_tick {
+            continue;
+        }
+        next_tick += simulator.next_tick();
+
+        let mut choices = Vec::new();
+        for data_type in [
+            StreamingDataType::Trades,
+            StreamingDataType::Quotes,
+            StreamingDataType::Bars,
+        ] {
+            for symbol in &subscriptions[&data_type] {
+                // Bars are built from trades, so a symbol with bars trades even if its trades are not written
+                let kind = if data_type == StreamingDataType::Quotes {
+                    "q"
+                } else {
+                    "t"
+                };
+                if !choices.contains(&(kind, symbol)) {
+                    choices.push((kind, symbol));
+                }
+            }
+        }
+        if choices.is_empty() {
+            continue;
+        }
+        let (kind, symbol) = choices[simulator.rng.usize(..choices.len())];
+        let symbol = symbol.clone();
+        let now = Utc::now();
+        if kind == "q" {
+            let quote = simulator.quote(&symbol, now);
+            stats.quotes += 1;
+            write(&quote, &mut stats)?;
+        } else {
+            let trade = simulator.trade(&symbol, now);
+            if subscriptions[&StreamingDataType::Trades].contains(&symbol) {
+                stats.trades += 1;
+                write(&trade, &mut stats)?;
+            }
+        }
+        if settings
+            .max_messages
+            .is_some_and(|max| stats.trades + stats.quotes >= max)
+        {
+            info!(
+                messages = stats.trades + stats.quotes,
+                "Simulated message limit reached"
+            );
+            break;
+        }
+    }
+    Ok(stats)
+}
+
+/// Change the simulated symbols and write the `control` event a live session writes
+fn apply_command(
+    config: &StreamingConfig,
+    subscriptions: &mut HashMap<StreamingDataType, Vec<String>>,
+    command: SubscriptionCommand,
+) -> Result<()> {
+    let (action, data_type, symbols) = match command {
+        SubscriptionCommand::Subscribe(data_type, symbols) => {
+            let current = subscriptions.entry(data_type.clone()).or_default();
+            for symbol in &symbols {
+                if !current.contains(symbol) {
+                    current.push(symbol.clone());
+                }
+            }
+            ("subscribe", data_type, symbols)
+        }
+        SubscriptionCommand::Unsubscribe(data_type, symbols) => {
+            if let Some(current) = subscriptions.get_mut(&data_type) {
+                current.retain(|symbol| !symbols.contains(symbol));
+            }
+            ("unsubscribe", data_type, symbols)
+        }
+    };
+    info!(action, channel = data_type_name(&data_type), symbols = %symbols.join(","), "Simulated subscription change");
+    let data = StreamingData {
+        received_time: Utc::now(),
+        event_time: None,
+        message_type: "control".to_string(),
+        symbol: None,
+        data: serde_json::json!({
+            "action": action,
+            "channel": data_type_name(&data_type),
+            "symbols": symbols,
+            "active": subscriptions.get(&data_type).cloned().unwrap_or_default(),
+        }),
+    };
+    config.output_mode.write_streaming_data(&data)
+}
@@ -0,0 +1,284 @@
+//! The latest trade, quote and bars of a list of symbols from the market data
+//! snapshot endpoint, for quick checks and for seeding strategies at startup.
+
+use crate::config::DEFAULT_DATA_URL;
+use crate::rate_limit::RateLimiter;
+use crate::retry::{retry_request, HttpError, RetryPolicy, DEFAULT_REQUEST_RETRIES};
+use crate::{DataFormat, Error, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Header of the CSV written for snapshots, one row per symbol
+pub const SNAPSHOT_CSV_HEADER: &str = "symbol,trade_time,trade_price,trade_size,trade_exchange,\
+quote_time,bid_price,bid_size,ask_price,ask_size,\
+minute_time,minute_open,minute_high,minute_low,minute_close,minute_volume,\
+daily_time,daily_open,daily_high,daily_low,daily_close,daily_volume,\
+prev_daily_time,prev_daily_open,prev_daily_high,prev_daily_low,prev_daily_close,prev_daily_volume";
+
+/// Feeds the snapshot endpoint accepts
+pub const SNAPSHOT_FEEDS: [&str; 5] = ["iex", "sip", "delayed_sip", "boats", "otc"];
+
+/// The latest trade of a snapshot
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LatestTrade {
+    #[serde(alias = "t")]
+    pub timestamp: String,
+    #[serde(alias = "p")]
+    pub price: f64,
+    #[serde(alias = "s")]
+    pub size: u64,
+    #[serde(alias = "x", default)]
+    pub exchange: String,
+}
+
+/// The latest quote of a snapshot
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LatestQuote {
+    #[serde(alias = "t")]
+    pub timestamp: String,
+    #[serde(alias = "bp")]
+    pub bid_price: f64,
+    #[serde(alias = "bs")]
+    pub bid_size: u64,
+    #[serde(alias = "ap")]
+    pub ask_price: f64,
+    #[serde(alias = "as")]
+    pub ask_size: u64,
+}
+
+/// A minute or daily bar of a snapshot
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotBar {
+    #[serde(alias = "t")]
+    pub timestamp: String,
+    #[serde(alias = "o")]
+    pub open: f64,
+    #[serde(alias = "h")]
+    pub high: f64,
+    #[serde(alias = "l")]
+    pub low: f64,
+    #[serde(alias = "c")]
+    pub close: f64,
+    #[serde(alias = "v")]
+    pub volume: u64,
+}
+
+/// Everything `GET /v2/stocks/snapshots` returns for one symbol. Any part is
+/// `None` when the feed has nothing for it, e.g. no minute bar before the open.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    #[serde(default)]
+    pub symbol: String,
+    #[serde(alias = "latestTrade", default)]
+    pub latest_trade: Option<LatestTrade>,
+    #[serde(alias = "latestQuote", default)]
+    pub latest_quote: Option<LatestQuote>,
+    #[serde(alias = "minuteBar", default)]
+    pub minute_bar: Option<SnapshotBar>,
+    #[serde(alias = "dailyBar", default)]
+    pub daily_bar: Option<SnapshotBar>,
+    #[serde(alias = "prevDailyBar", default)]
+    pub prev_daily_bar: Option<SnapshotBar>,
+}
+
+/// The snapshots of a response body in the order of `symbols`. Symbols the
+/// response leaves out or returns as `null` are skipped.
+pub fn parse_snapshots(symbols: &[String], body: &str) -> Result<Vec<Snapshot>> {
+    let mut by_symbol: HashMap<String, Option<Snapshot>> = serde_json::from_str(body)?;
+    Ok(symbols
+        .iter()
+        .filter_map(|symbol| {
+            let mut snapshot = by_symbol.remove(symbol).flatten()?;
+            snapshot.symbol = symbol.clone();
+            Some(snapshot)
+        })
+        .collect())
+}
+
+impl Snapshot {
+    /// Last trade price minus the previous daily close, and the same as a
+    /// percentage, when both are present
+    pub fn change(&self) -> Option<(f64, f64)> {
+        let price = self.latest_trade.as_ref()?.price;
+        let prev_close = self.prev_daily_bar.as_ref()?.close;
+        if prev_close == 0.0 {
+            return None;
+        }
+        let change = price - prev_close;
+        Some((change, change / prev_close * 100.0))
+    }
+
+    /// The text written for this snapshot: several lines in plain format, one
+    /// line otherwise; CSV lines match `SNAPSHOT_CSV_HEADER`
+    pub fn format(&self, format: &DataFormat) -> Result<String> {
+        Ok(match format {
+            DataFormat::Plain => self.format_plain(),
+            DataFormat::Json => serde_json::to_string(self)?,
+            DataFormat::Csv => self.format_csv(),
+        })
+    }
+
+    fn format_plain(&self) -> String {
+        let mut lines = vec![format!("📸 {}", self.symbol)];
+        match &self.latest_trade {
+            Some(trade) => {
+                let change = match self.change() {
+                    Some((change, percent)) => {
+                        format!(" | Change: {:+.2} ({:+.2}%)", change, percent)
+                    }
+                    None => String::new(),
+                };
+                lines.push(format!(
+                    "  Trade: ${:.2} x {} | Exchange: {} | {}{}",
+                    trade.price, trade.size, trade.exchange, trade.timestamp, change
+                ));
+            }
+            None => lines.push("  Trade: -".to_string()),
+        }
+        match &self.latest_quote {
+            Some(quote) => lines.push(format!(
+                "  Quote: Bid ${:.2} x {} | Ask ${:.2} x {} | Spread ${:.2} | {}",
+                quote.bid_price,
+                quote.bid_size,
+                quote.ask_price,
+                quote.ask_size,
+                quote.ask_price - quote.bid_price,
+                quote.timestamp
+            )),
+            None => lines.push("  Quote: -".to_string()),
+        }
+        for (label, bar) in [
+            ("Minute bar", &self.minute_bar),
+            ("Daily bar", &self.daily_bar),
+            ("Previous daily bar", &self.prev_daily_bar),
+        ] {
+            match bar {
+                Some(bar) => lines.push(format!(
+                    "  {}: O: ${:.2} | H: ${:.2} | L: ${:.2} | C: ${:.2} | V: {} | {}",
+                    label, bar.open, bar.high, bar.low, bar.close, bar.volume, bar.timestamp
+                )),
+                None => lines.push(format!("  {}: -", label)),
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn format_csv(&self) -> String {
+        let mut fields = vec![self.symbol.clone()];
+        match &self.latest_trade {
+            Some(trade) => fields.extend([
+                trade.timestamp.clone(),
+                trade.price.to_string(),
+                trade.size.to_string(),
+                trade.exchange.clone(),
+            ]),
+            None => fields.extend(vec![String::new(); 4]),
+        }
+        match &self.latest_quote {
+            Some(quote) => fields.extend([
+                quote.timestamp.clone(),
+                quote.bid_price.to_string(),
+                quote.bid_size.to_string(),
+                quote.ask_price.to_string(),
+                quote.ask_size.to_string(),
+            ]),
+            None => fields.extend(vec![String::new(); 5]),
+        }
+        for bar in [&self.minute_bar, &self.daily_bar, &self.prev_daily_bar] {
+            match bar {
+                Some(bar) => fields.extend([
+                    bar.timestamp.clone(),
+                    bar.open.to_string(),
+                    bar.high.to_string(),
+                    bar.low.to_string(),
+                    bar.close.to_string(),
+                    bar.volume.to_string(),
+                ]),
+                None => fields.extend(vec![String::new(); 6]),
+            }
+        }
+        fields.join(",")
+    }
+}
+
+/// Validate a snapshot feed name
+pub fn parse_snapshot_feed(feed: &str) -> Result<String> {
+    let feed = feed.trim().to_lowercase();
+    if SNAPSHOT_FEEDS.contains(&feed.as_str()) {
+        Ok(feed)
+    } else {
+        Err(Error::Invalid(format!(
+            "Invalid feed: {}. Supported: {}",
+            feed,
+            SNAPSHOT_FEEDS.join(", ")
+        )))
+    }
+}
+
+/// Client for the snapshot endpoint of the market data API
+pub struct SnapshotClient {
+    client: reqwest::Client,
+    data_url: String,
+    key: String,
+    secret: String,
+    retry: RetryPolicy,
+}
+
+impl SnapshotClient {
+    /// Credentials from `APCA_API_KEY_ID` / `APCA_API_SECRET_KEY` and the
+    /// data API from `APCA_API_DATA_URL`
+    pub fn from_env() -> Result<Self> {
+        let var =
+            |name: &str| std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)));
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            data_url: std::env::var("APCA_API_DATA_URL")
+                .unwrap_or_else(|_| DEFAULT_DATA_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            key: var("APCA_API_KEY_ID")?,
+            secret: var("APCA_API_SECRET_KEY")?,
+            retry: RetryPolicy::for_requests(DEFAULT_REQUEST_RETRIES),
+        })
+    }
+
+    /// Retry each request up to `max_retries` times after a 429, a 5xx or a
+    /// network error
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.retry = RetryPolicy::for_requests(max_retries);
+        self
+    }
+
+    /// The snapshots of `symbols` from `feed`, in the order given
+    #[tracing::instrument(name = "fetch_snapshots", skip(self, symbols), fields(symbols = symbols.len()))]
+    pub async fn fetch_snapshots(&self, symbols: &[String], feed: &str) -> Result<Vec<Snapshot>> {
+        let url = format!("{}/v2/stocks/snapshots", self.data_url);
+        let symbols_param = symbols.join(",");
+        let body = self
+            .get(&url, &[("symbols", symbols_param.as_str()), ("feed", feed)])
+            .await?;
+        parse_snapshots(symbols, &body)
+    }
+
+    /// GET `url` with `query` within the rate limit, retried per `self.retry`
+    async fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<String> {
+        retry_request(&self.retry, move || async move {
+            RateLimiter::global().acquire().await;
+            let response = self
+                .client
+                .get(url)
+                .query(query)
+                .header("APCA-API-KEY-ID", &self.key)
+                .header("APCA-API-SECRET-KEY", &self.secret)
+                .send()
+                .await?;
+            RateLimiter::global()
+                .observe(|name| response.headers().get(name)?.to_str().ok()?.parse().ok());
+            let status = response.status().as_u16();
+            HttpError::check(status, response.text().await?)
+        })
+        .await
+    }
+}
@@ -0,0 +1,175 @@
+//! Bid/ask spreads of captured quotes, per symbol, for the analyzer: how
+//! wide the market was on average and over time, and how often it was
+//! locked or crossed.
+
+use crate::StreamingData;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// Spread statistics of one symbol's quotes. Spreads are ask minus bid, in
+/// price units; crossed quotes are counted but left out of the spreads.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SpreadStats {
+    pub symbol: String,
+    /// Quotes with both a bid and an ask
+    pub quotes: u64,
+    /// Quotes without a bid or an ask, which have no spread
+    pub one_sided: u64,
+    /// Quotes with the bid equal to the ask
+    pub locked: u64,
+    /// Quotes with the bid above the ask
+    pub crossed: u64,
+    pub average: f64,
+    pub median: f64,
+    pub max: f64,
+    /// Average spread in basis points of the midpoint
+    pub average_bps: f64,
+    /// Each spread weighted by how long it stood until the symbol's next
+    /// quote; `None` until a spread has stood for any time
+    pub time_weighted: Option<f64>,
+}
+
+impl SpreadStats {
+    /// Share of the two-sided quotes that were locked, in percent
+    pub fn locked_percent(&self) -> f64 {
+        percent(self.locked, self.quotes)
+    }
+
+    /// Share of the two-sided quotes that were crossed, in percent
+    pub fn crossed_percent(&self) -> f64 {
+        percent(self.crossed, self.quotes)
+    }
+}
+
+impl std::fmt::Display for SpreadStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} quotes, spread avg {:.4} ({:.1} bps), median {:.4}, max {:.4}, time-weighted {}, {:.2}% locked, {:.2}% crossed",
+            self.symbol,
+            self.quotes,
+            self.average,
+            self.average_bps,
+            self.median,
+            self.max,
+            self.time_weighted
+                .map_or_else(|| "-".to_string(), |spread| format!("{:.4}", spread)),
+            self.locked_percent(),
+            self.crossed_percent()
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Accumulator {
+    quotes: u64,
+    one_sided: u64,
+    locked: u64,
+    crossed: u64,
+    spreads: Vec<f64>,
+    bps: f64,
+    /// Time and spread of the previous quote; no spread if it was crossed
+    previous: Option<(DateTime<Utc>, Option<f64>)>,
+    weighted: f64,
+    seconds: f64,
+}
+
+/// Spreads of the quotes (`q` messages) seen so far, by symbol
+#[derive(Debug, Default)]
+pub struct SpreadTracker {
+    symbols: HashMap<String, Accumulator>,
+}
+
+impl SpreadTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count `data` if it is a quote; other messages are ignored. Quotes are
+    /// timed by event time when they have one, and are expected in time
+    /// order per symbol.
+    pub fn add(&mut self, data: &StreamingData) {
+        let Some(symbol) = data.symbol.as_ref().filter(|_| data.message_type == "q") else {
+            return;
+        };
+        let acc = self.symbols.entry(symbol.clone()).or_default();
+        let (bid, ask) = match (data.data["bp"].as_f64(), data.data["ap"].as_f64()) {
+            (Some(bid), Some(ask)) if bid > 0.0 && ask > 0.0 => (bid, ask),
+            _ => {
+                acc.one_sided += 1;
+                return;
+            }
+        };
+
+        let time = data.event_time.unwrap_or(data.received_time);
+        if let Some((previous, Some(spread))) = acc.previous {
+            let seconds = (time - previous).num_milliseconds().max(0) as f64 / 1000.0;
+            acc.weighted += spread * seconds;
+            acc.seconds += seconds;
+        }
+
+        acc.quotes += 1;
+        let spread = ask - bid;
+        if spread < 0.0 {
+            acc.crossed += 1;
+            acc.previous = Some((time, None));
+            return;
+        }
+        if spread == 0.0 {
+            acc.locked += 1;
+        }
+        acc.spreads.push(spread);
+        acc.bps += spread / ((ask + bid) / 2.0) * 10_000.0;
+        acc.previous = Some((time, Some(spread)));
+    }
+
+    /// Whether any quote has been seen
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Statistics of every symbol with quotes, sorted by symbol
+    pub fn stats(&self) -> Vec<SpreadStats> {
+        let mut stats: Vec<SpreadStats> = self
+            .symbols
+            .iter()
+            .map(|(symbol, acc)| {
+                let mut spreads = acc.spreads.clone();
+                spreads.sort_by(f64::total_cmp);
+                let count = spreads.len().max(1) as f64;
+                SpreadStats {
+                    symbol: symbol.clone(),
+                    quotes: acc.quotes,
+                    one_sided: acc.one_sided,
+                    locked: acc.locked,
+                    crossed: acc.crossed,
+                    average: spreads.iter().sum::<f64>() / count,
+                    median: median(&spreads),
+                    max: spreads.last().copied().unwrap_or(0.0),
+                    average_bps: acc.bps / count,
+                    time_weighted: (acc.seconds > 0.0).then(|| acc.weighted / acc.seconds),
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        stats
+    }
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64 * 100.0
+    }
+}
+
+/// Median of sorted values; 0 when there are none
+fn median(sorted: &[f64]) -> f64 {
+    let middle = sorted.len() / 2;
+    match sorted.len() {
+        0 => 0.0,
+        len if len.is_multiple_of(2) => (sorted[middle - 1] + sorted[middle]) / 2.0,
+        _ => sorted[middle],
+    }
+}
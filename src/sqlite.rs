@@ -0,0 +1,145 @@
+//! Captures in a SQLite database: every message in a `messages` table, as
+//! the JSON it was received as, and a view per message type that decodes the
+//! payload into typed columns, so `SELECT symbol, avg(price) FROM trades
+//! GROUP BY symbol` works without knowing the payload keys.
+
+use crate::{payload_fields, Error, OutputSink, Result, StreamingData};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+use std::sync::Mutex;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    received_time TEXT NOT NULL,
+    event_time TEXT,
+    message_type TEXT NOT NULL,
+    symbol TEXT,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS messages_by_type ON messages (message_type, symbol, event_time);
+";
+
+/// The view of each message type with payload fields
+const VIEWS: [(&str, &str); 10] = [
+    ("t", "trades"),
+    ("q", "quotes"),
+    ("b", "bars"),
+    ("d", "daily_bars"),
+    ("u", "updated_bars"),
+    ("c", "corrections"),
+    ("x", "cancels"),
+    ("s", "statuses"),
+    ("l", "luld"),
+    ("i", "imbalances"),
+];
+
+/// Times are stored as RFC 3339 text in UTC with microseconds, which sorts
+/// in time order
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// `CREATE VIEW` of the messages of `message_type`, with a column per
+/// payload field
+fn view_sql(message_type: &str, view: &str) -> String {
+    let columns: String = payload_fields(message_type)
+        .iter()
+        .map(|(name, key)| format!(", json_extract(data, '$.\"{}\"') AS \"{}\"", key, name))
+        .collect();
+    format!(
+        "CREATE VIEW IF NOT EXISTS \"{}\" AS SELECT received_time, event_time, symbol{} FROM messages WHERE message_type = '{}';",
+        view, columns, message_type
+    )
+}
+
+/// Writes messages to a SQLite database. Messages are inserted in a
+/// transaction that is committed on every flush, so the database holds
+/// everything up to the last flush if the process stops.
+#[derive(Debug)]
+pub struct SqliteSink {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteSink {
+    /// Open the database at `path`, creating it and its tables and views if
+    /// needed; messages already in it are kept
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        for (message_type, view) in VIEWS {
+            connection.execute_batch(&view_sql(message_type, view))?;
+        }
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn insert(&self, data: &StreamingData) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        if connection.is_autocommit() {
+            connection.execute_batch("BEGIN")?;
+        }
+        connection
+            .prepare_cached("INSERT INTO messages (received_time, event_time, message_type, symbol, data) VALUES (?1, ?2, ?3, ?4, ?5)")?
+            .execute((
+                format_time(data.received_time),
+                data.event_time.map(format_time),
+                &data.message_type,
+                &data.symbol,
+                data.data.to_string(),
+            ))?;
+        Ok(())
+    }
+
+    /// Commit the messages inserted since the last commit
+    pub fn commit(&self) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        if !connection.is_autocommit() {
+            connection.execute_batch("COMMIT")?;
+        }
+        Ok(())
+    }
+}
+
+impl OutputSink for SqliteSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        self.insert(data)
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        self.commit()
+    }
+}
+
+/// Call `on_message` with each message of the database at `path`, in the
+/// order they were inserted
+pub fn read_database(
+    path: &Path,
+    mut on_message: impl FnMut(StreamingData) -> crate::Result<()>,
+) -> Result<()> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut statement = connection.prepare(
+        "SELECT received_time, event_time, message_type, symbol, data FROM messages ORDER BY rowid",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let received_time: String = row.get(0)?;
+        let event_time: Option<String> = row.get(1)?;
+        let data: String = row.get(4)?;
+        on_message(StreamingData {
+            received_time: parse_time(&received_time)?,
+            event_time: event_time.as_deref().map(parse_time).transpose()?,
+            message_type: row.get(2)?,
+            symbol: row.get(3)?,
+            data: serde_json::from_str(&data)?,
+        })?;
+    }
+    Ok(())
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    value
+        .parse()
+        .map_err(|_| Error::Invalid(format!("Invalid time in the database: {}", value)))
+}
@@ -0,0 +1,8 @@
+//! Everything a session measures about itself, in one place: per-symbol
+//! statistics, latency, per-connection counters and the end-of-session
+//! summary.
+
+pub use crate::latency::{LatencyMonitor, LatencyStats};
+pub use crate::session_stats::{SessionStats, SymbolSessionStats};
+pub use crate::shards::{ShardMetrics, ShardStats};
+pub use crate::summary::{SessionSummary, Summary};
@@ -0,0 +1,447 @@
+//! Built-in strategies, picked by name with parameters from the command line
+//! or the `[strategies.<name>]` table of the config file.
+//!
+//! The strategies other than buy-and-hold trade on bars. Each keeps the
+//! position it wants in every symbol and orders the difference when a signal
+//! changes it, so a signal is traded once even while its order is waiting to
+//! fill.
+
+use crate::indicators::{Rsi, Sma};
+use crate::market_time::eastern_offset;
+use crate::resample::OhlcvBar;
+use crate::strategy::{Context, QuoteEvent, Strategy, TradeEvent};
+use crate::{Error, Result};
+use chrono::{NaiveDate, NaiveTime};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// Names of the built-in strategies
+pub const STRATEGIES: &[&str] = &[
+    "buy-and-hold",
+    "sma-crossover",
+    "rsi-reversion",
+    "opening-range-breakout",
+];
+
+/// Parameters of a built-in strategy, e.g. from `--param quantity=10`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StrategyParams {
+    values: HashMap<String, String>,
+}
+
+impl StrategyParams {
+    /// Parse `NAME=VALUE` pairs
+    pub fn parse(pairs: &[String]) -> Result<Self> {
+        let mut params = Self::default();
+        for pair in pairs {
+            let (name, value) = pair.split_once('=').ok_or_else(|| {
+                Error::Invalid(format!(
+                    "Invalid strategy parameter {:?}: expected NAME=VALUE",
+                    pair
+                ))
+            })?;
+            params.insert(name.trim(), value.trim());
+        }
+        Ok(params)
+    }
+
+    /// Add the parameters of `other`, replacing those already set
+    pub fn extend(&mut self, other: StrategyParams) {
+        self.values.extend(other.values);
+    }
+
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.values.insert(name.to_string(), value.to_string());
+    }
+
+    /// The parameter `name`, or `default` when it is not given
+    pub fn get<T: FromStr>(&self, name: &str, default: T) -> Result<T> {
+        match self.values.get(name) {
+            Some(value) => value.parse().map_err(|_| {
+                Error::Invalid(format!("Invalid value for parameter {}: {}", name, value))
+            }),
+            None => Ok(default),
+        }
+    }
+
+    /// Fail on parameters that are not in `known`, so a typo is not ignored
+    fn check(&self, strategy: &str, known: &[&str]) -> Result<()> {
+        let mut unknown: Vec<&str> = self
+            .values
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !known.contains(name))
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        unknown.sort_unstable();
+        Err(Error::Invalid(format!(
+            "Unknown parameters for {}: {}. It takes: {}",
+            strategy,
+            unknown.join(", "),
+            known.join(", ")
+        )))
+    }
+}
+
+/// The built-in strategy `name` with `params`
+pub fn create(name: &str, params: &StrategyParams) -> Result<Box<dyn Strategy>> {
+    match name {
+        "buy-and-hold" => Ok(Box::new(BuyAndHold::from_params(params)?)),
+        "sma-crossover" => Ok(Box::new(SmaCrossover::from_params(params)?)),
+        "rsi-reversion" => Ok(Box::new(RsiReversion::from_params(params)?)),
+        "opening-range-breakout" => Ok(Box::new(OpeningRangeBreakout::from_params(params)?)),
+        _ => Err(Error::Invalid(format!(
+            "Unknown strategy: {}. Built-in strategies: {}",
+            name,
+            STRATEGIES.join(", ")
+        ))),
+    }
+}
+
+fn check_quantity(quantity: f64) -> Result<f64> {
+    if !(quantity > 0.0 && quantity.is_finite()) {
+        return Err(Error::Invalid(format!(
+            "Invalid quantity: {}. Use a positive number of shares",
+            quantity
+        )));
+    }
+    Ok(quantity)
+}
+
+/// The position a strategy wants in each symbol
+#[derive(Debug, Clone, Default)]
+struct Targets {
+    positions: HashMap<String, f64>,
+}
+
+impl Targets {
+    fn get(&self, symbol: &str) -> f64 {
+        self.positions.get(symbol).copied().unwrap_or(0.0)
+    }
+
+    /// Want `target` shares of `symbol`, and order the difference from the
+    /// position wanted before
+    fn set(&mut self, symbol: &str, target: f64, context: &mut Context) {
+        let change = target - self.get(symbol);
+        if change > 0.0 {
+            context.buy(symbol, change);
+        } else if change < 0.0 {
+            context.sell(symbol, -change);
+        }
+        self.positions.insert(symbol.to_string(), target);
+    }
+}
+
+/// Buys `quantity` shares of every symbol at its first price and holds them;
+/// the baseline other strategies are measured against
+#[derive(Debug, Clone)]
+pub struct BuyAndHold {
+    quantity: f64,
+    bought: HashSet<String>,
+}
+
+impl BuyAndHold {
+    pub fn new(quantity: f64) -> Result<Self> {
+        Ok(Self {
+            quantity: check_quantity(quantity)?,
+            bought: HashSet::new(),
+        })
+    }
+
+    /// Parameters: `quantity` [default: 100]
+    pub fn from_params(params: &StrategyParams) -> Result<Self> {
+        params.check("buy-and-hold", &["quantity"])?;
+        Self::new(params.get("quantity", 100.0)?)
+    }
+
+    fn enter(&mut self, symbol: &str, context: &mut Context) {
+        if self.bought.insert(symbol.to_string()) {
+            context.buy(symbol, self.quantity);
+        }
+    }
+}
+
+impl Strategy for BuyAndHold {
+    fn name(&self) -> &str {
+        "buy-and-hold"
+    }
+
+    fn on_bar(&mut self, bar: &OhlcvBar, context: &mut Context) {
+        self.enter(&bar.symbol, context);
+    }
+
+    fn on_trade(&mut self, trade: &TradeEvent, context: &mut Context) {
+        self.enter(&trade.symbol, context);
+    }
+
+    fn on_quote(&mut self, quote: &QuoteEvent, context: &mut Context) {
+        self.enter(&quote.symbol, context);
+    }
+}
+
+/// Holds `quantity` shares of a symbol from when the fast SMA of its closes
+/// crosses above the slow one until it crosses back below; with `short`, is
+/// short the same quantity in between
+#[derive(Debug, Clone)]
+pub struct SmaCrossover {
+    fast: usize,
+    slow: usize,
+    quantity: f64,
+    short: bool,
+    averages: HashMap<String, (Sma, Sma, Option<bool>)>,
+    targets: Targets,
+}
+
+impl SmaCrossover {
+    pub fn new(fast: usize, slow: usize, quantity: f64, short: bool) -> Result<Self> {
+        Sma::new(fast)?;
+        Sma::new(slow)?;
+        if fast >= slow {
+            return Err(Error::Invalid(format!(
+                "Invalid periods: fast {} must be shorter than slow {}",
+                fast, slow
+            )));
+        }
+        Ok(Self {
+            fast,
+            slow,
+            quantity: check_quantity(quantity)?,
+            short,
+            averages: HashMap::new(),
+            targets: Targets::default(),
+        })
+    }
+
+    /// Parameters: `fast` [default: 10], `slow` [default: 30], `quantity`
+    /// [default: 100], `short` [default: false]
+    pub fn from_params(params: &StrategyParams) -> Result<Self> {
+        params.check("sma-crossover", &["fast", "slow", "quantity", "short"])?;
+        Self::new(
+            params.get("fast", 10)?,
+            params.get("slow", 30)?,
+            params.get("quantity", 100.0)?,
+            params.get("short", false)?,
+        )
+    }
+}
+
+impl Strategy for SmaCrossover {
+    fn name(&self) -> &str {
+        "sma-crossover"
+    }
+
+    fn on_bar(&mut self, bar: &OhlcvBar, context: &mut Context) {
+        let (fast, slow) = (self.fast, self.slow);
+        let (fast_sma, slow_sma, above) = self
+            .averages
+            .entry(bar.symbol.clone())
+            .or_insert_with(|| (Sma::new(fast).unwrap(), Sma::new(slow).unwrap(), None));
+        let (Some(fast), Some(slow)) = (fast_sma.next(bar.close), slow_sma.next(bar.close)) else {
+            return;
+        };
+        let now_above = fast > slow;
+        // Only a cross trades, not where the averages start out
+        let crossed = matches!(above, Some(was_above) if *was_above != now_above);
+        *above = Some(now_above);
+        if !crossed {
+            return;
+        }
+        let target = match (now_above, self.short) {
+            (true, _) => self.quantity,
+            (false, true) => -self.quantity,
+            (false, false) => 0.0,
+        };
+        self.targets.set(&bar.symbol, target, context);
+    }
+}
+
+/// Buys `quantity` shares of a symbol when the RSI of its closes falls below
+/// `oversold`, and sells them when it rises above `exit`
+#[derive(Debug, Clone)]
+pub struct RsiReversion {
+    period: usize,
+    oversold: f64,
+    exit: f64,
+    quantity: f64,
+    indexes: HashMap<String, Rsi>,
+    targets: Targets,
+}
+
+impl RsiReversion {
+    pub fn new(period: usize, oversold: f64, exit: f64, quantity: f64) -> Result<Self> {
+        Rsi::new(period)?;
+        if !(0.0 < oversold && oversold < exit && exit < 100.0) {
+            return Err(Error::Invalid(format!(
+                "Invalid levels: oversold {} and exit {}. Use 0 < oversold < exit < 100",
+                oversold, exit
+            )));
+        }
+        Ok(Self {
+            period,
+            oversold,
+            exit,
+            quantity: check_quantity(quantity)?,
+            indexes: HashMap::new(),
+            targets: Targets::default(),
+        })
+    }
+
+    /// Parameters: `period` [default: 14], `oversold` [default: 30], `exit`
+    /// [default: 50], `quantity` [default: 100]
+    pub fn from_params(params: &StrategyParams) -> Result<Self> {
+        params.check("rsi-reversion", &["period", "oversold", "exit", "quantity"])?;
+        Self::new(
+            params.get("period", 14)?,
+            params.get("oversold", 30.0)?,
+            params.get("exit", 50.0)?,
+            params.get("quantity", 100.0)?,
+        )
+    }
+}
+
+impl Strategy for RsiReversion {
+    fn name(&self) -> &str {
+        "rsi-reversion"
+    }
+
+    fn on_bar(&mut self, bar: &OhlcvBar, context: &mut Context) {
+        let period = self.period;
+        let rsi = self
+            .indexes
+            .entry(bar.symbol.clone())
+            .or_insert_with(|| Rsi::new(period).unwrap());
+        let Some(index) = rsi.next(bar.close) else {
+            return;
+        };
+        let held = self.targets.get(&bar.symbol) > 0.0;
+        if !held && index < self.oversold {
+            self.targets.set(&bar.symbol, self.quantity, context);
+        } else if held && index > self.exit {
+            self.targets.set(&bar.symbol, 0.0, context);
+        }
+    }
+}
+
+/// A symbol's opening range on one day
+#[derive(Debug, Clone)]
+struct OpeningRange {
+    date: NaiveDate,
+    high: f64,
+    low: f64,
+    /// Whether the day's breakout has been traded
+    traded: bool,
+}
+
+/// Marks the high and low of each symbol's bars in the first `minutes` after
+/// the 09:30 New York open, then buys `quantity` shares on the first close
+/// above the range, or with `short` sells them short on the first close below
+/// it. The position is closed at the first bar from `exit` New York time, or
+/// at the next day's first bar. Needs intraday bars.
+#[derive(Debug, Clone)]
+pub struct OpeningRangeBreakout {
+    minutes: u32,
+    quantity: f64,
+    short: bool,
+    exit: NaiveTime,
+    ranges: HashMap<String, OpeningRange>,
+    targets: Targets,
+}
+
+impl OpeningRangeBreakout {
+    pub fn new(minutes: u32, quantity: f64, short: bool, exit: NaiveTime) -> Result<Self> {
+        if minutes == 0 || Self::open() + chrono::Duration::minutes(minutes.into()) >= exit {
+            return Err(Error::Invalid(format!(
+                "Invalid opening range: {} minutes must be over 0 and end before the exit at {}",
+                minutes, exit
+            )));
+        }
+        Ok(Self {
+            minutes,
+            quantity: check_quantity(quantity)?,
+            short,
+            exit,
+            ranges: HashMap::new(),
+            targets: Targets::default(),
+        })
+    }
+
+    /// Parameters: `minutes` [default: 30], `quantity` [default: 100],
+    /// `short` [default: true], `exit` [default: 15:55]
+    pub fn from_params(params: &StrategyParams) -> Result<Self> {
+        params.check(
+            "opening-range-breakout",
+            &["minutes", "quantity", "short", "exit"],
+        )?;
+        let exit: String = params.get("exit", "15:55".to_string())?;
+        let exit = NaiveTime::parse_from_str(&exit, "%H:%M")
+            .map_err(|_| Error::Invalid(format!("Invalid exit time: {}. Use HH:MM", exit)))?;
+        Self::new(
+            params.get("minutes", 30)?,
+            params.get("quantity", 100.0)?,
+            params.get("short", true)?,
+            exit,
+        )
+    }
+
+    fn open() -> NaiveTime {
+        NaiveTime::from_hms_opt(9, 30, 0).unwrap()
+    }
+}
+
+impl Strategy for OpeningRangeBreakout {
+    fn name(&self) -> &str {
+        "opening-range-breakout"
+    }
+
+    fn on_bar(&mut self, bar: &OhlcvBar, context: &mut Context) {
+        let local = bar
+            .timestamp
+            .with_timezone(&eastern_offset(bar.timestamp.date_naive()));
+        let (date, time) = (local.date_naive(), local.time());
+        if time < Self::open() {
+            return;
+        }
+        let range_end = Self::open() + chrono::Duration::minutes(self.minutes.into());
+        let new_day = self
+            .ranges
+            .get(&bar.symbol)
+            .is_none_or(|range| range.date != date);
+        if new_day {
+            // A position the exit missed, e.g. for a gap in the bars
+            self.targets.set(&bar.symbol, 0.0, context);
+            self.ranges.insert(
+                bar.symbol.clone(),
+                OpeningRange {
+                    date,
+                    high: bar.high,
+                    low: bar.low,
+                    // Without bars from the open, the day has no range
+                    traded: time >= range_end,
+                },
+            );
+        }
+        let range = self.ranges.get_mut(&bar.symbol).unwrap();
+        if time >= self.exit {
+            range.traded = true;
+            self.targets.set(&bar.symbol, 0.0, context);
+            return;
+        }
+        if time < range_end {
+            range.high = range.high.max(bar.high);
+            range.low = range.low.min(bar.low);
+            return;
+        }
+        if range.traded {
+            return;
+        }
+        if bar.close > range.high {
+            range.traded = true;
+            self.targets.set(&bar.symbol, self.quantity, context);
+        } else if self.short && bar.close < range.low {
+            range.traded = true;
+            self.targets.set(&bar.symbol, -self.quantity, context);
+        }
+    }
+}
@@ -0,0 +1,508 @@
+//! Trading strategies driven by market events, so the same strategy runs
+//! over downloaded bars, a replayed capture or a live session.
+//!
+//! A [`Strategy`] receives bars, trades, quotes and fills of its orders as
+//! [`MarketEvent`]s, and asks for orders through the [`Context`] it is given.
+//! An [`Engine`] feeds it events and returns the orders; [`Engine::run`] goes
+//! through a series of events, and a [`StrategySink`] takes the messages of a
+//! streaming session, replay or simulation like any other output.
+
+use crate::resample::OhlcvBar;
+use crate::{OutputSink, Result, StreamingData, TradeUpdate};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Side of an order or fill
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    /// 1 for buys and -1 for sells, to sign quantities
+    pub fn sign(&self) -> f64 {
+        match self {
+            Side::Buy => 1.0,
+            Side::Sell => -1.0,
+        }
+    }
+}
+
+/// How an order is priced
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub enum OrderType {
+    /// At the market price
+    Market,
+    /// At this price or better
+    Limit { limit_price: f64 },
+}
+
+/// An order a strategy asks for
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OrderRequest {
+    pub symbol: String,
+    pub side: Side,
+    /// Shares, always positive
+    pub quantity: f64,
+    #[serde(flatten)]
+    pub order_type: OrderType,
+    /// When the strategy asked for it
+    pub created_at: DateTime<Utc>,
+}
+
+/// An execution of an order
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Fill {
+    pub symbol: String,
+    pub side: Side,
+    /// Shares of this execution, always positive
+    pub quantity: f64,
+    pub price: f64,
+    pub timestamp: DateTime<Utc>,
+    /// Charged for the execution; 0 when the broker reports none
+    #[serde(default)]
+    pub commission: f64,
+    /// Broker ID of the order, when it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<String>,
+}
+
+/// A trade print
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeEvent {
+    pub symbol: String,
+    pub timestamp: DateTime<Utc>,
+    pub price: f64,
+    pub size: u64,
+}
+
+/// A top-of-book quote
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteEvent {
+    pub symbol: String,
+    pub timestamp: DateTime<Utc>,
+    pub bid_price: f64,
+    pub bid_size: u64,
+    pub ask_price: f64,
+    pub ask_size: u64,
+}
+
+/// What a strategy reacts to, whatever the data comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketEvent {
+    Bar(OhlcvBar),
+    Trade(TradeEvent),
+    Quote(QuoteEvent),
+    Fill(Fill),
+}
+
+impl MarketEvent {
+    /// The event of a streamed or captured bar, trade or quote, or of a
+    /// `trade_update` fill; `None` for other messages and order events
+    pub fn from_streaming_data(data: &StreamingData) -> Option<Self> {
+        let timestamp = data.event_time.unwrap_or(data.received_time);
+        let payload = &data.data;
+        match data.message_type.as_str() {
+            "b" => OhlcvBar::from_bar_message(data).map(MarketEvent::Bar),
+            "t" => Some(MarketEvent::Trade(TradeEvent {
+                symbol: data.symbol.clone()?,
+                timestamp,
+                price: payload["p"].as_f64()?,
+                size: payload["s"].as_u64()?,
+            })),
+            "q" => Some(MarketEvent::Quote(QuoteEvent {
+                symbol: data.symbol.clone()?,
+                timestamp,
+                bid_price: payload["bp"].as_f64()?,
+                bid_size: payload["bs"].as_u64().unwrap_or(0),
+                ask_price: payload["ap"].as_f64()?,
+                ask_size: payload["as"].as_u64().unwrap_or(0),
+            })),
+            "trade_update" => {
+                let update: TradeUpdate = serde_json::from_value(payload.clone()).ok()?;
+                if update.event != "fill" && update.event != "partial_fill" {
+                    return None;
+                }
+                let side = match update.order.side.as_str() {
+                    "buy" => Side::Buy,
+                    "sell" => Side::Sell,
+                    _ => return None,
+                };
+                Some(MarketEvent::Fill(Fill {
+                    symbol: update.order.symbol,
+                    side,
+                    quantity: update.qty?.parse().ok()?,
+                    price: update.price?.parse().ok()?,
+                    timestamp,
+                    commission: 0.0,
+                    order_id: Some(update.order.id),
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            MarketEvent::Bar(bar) => &bar.symbol,
+            MarketEvent::Trade(trade) => &trade.symbol,
+            MarketEvent::Quote(quote) => &quote.symbol,
+            MarketEvent::Fill(fill) => &fill.symbol,
+        }
+    }
+
+    /// When the event happened; the start of a bar
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            MarketEvent::Bar(bar) => bar.timestamp,
+            MarketEvent::Trade(trade) => trade.timestamp,
+            MarketEvent::Quote(quote) => quote.timestamp,
+            MarketEvent::Fill(fill) => fill.timestamp,
+        }
+    }
+}
+
+/// What a strategy sees and does while it handles an event
+#[derive(Debug)]
+pub struct Context<'a> {
+    now: DateTime<Utc>,
+    positions: &'a HashMap<String, f64>,
+    orders: Vec<OrderRequest>,
+}
+
+impl Context<'_> {
+    /// Time of the event being handled
+    pub fn now(&self) -> DateTime<Utc> {
+        self.now
+    }
+
+    /// Shares held of `symbol` after the fills so far; negative when short
+    pub fn position(&self, symbol: &str) -> f64 {
+        self.positions.get(symbol).copied().unwrap_or(0.0)
+    }
+
+    /// Ask for an order
+    pub fn submit(&mut self, symbol: &str, side: Side, quantity: f64, order_type: OrderType) {
+        self.orders.push(OrderRequest {
+            symbol: symbol.to_string(),
+            side,
+            quantity,
+            order_type,
+            created_at: self.now,
+        });
+    }
+
+    /// Ask for a market order to buy
+    pub fn buy(&mut self, symbol: &str, quantity: f64) {
+        self.submit(symbol, Side::Buy, quantity, OrderType::Market);
+    }
+
+    /// Ask for a market order to sell
+    pub fn sell(&mut self, symbol: &str, quantity: f64) {
+        self.submit(symbol, Side::Sell, quantity, OrderType::Market);
+    }
+}
+
+/// A trading strategy. Every method has a default that does nothing, so a
+/// strategy only implements the events it trades on.
+pub trait Strategy: Send {
+    /// Name for logs and reports
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Called once, before the first event
+    fn on_start(&mut self, _context: &mut Context) {}
+
+    fn on_bar(&mut self, _bar: &OhlcvBar, _context: &mut Context) {}
+
+    fn on_trade(&mut self, _trade: &TradeEvent, _context: &mut Context) {}
+
+    fn on_quote(&mut self, _quote: &QuoteEvent, _context: &mut Context) {}
+
+    /// Called with each execution of the strategy's orders, after its
+    /// position has been updated
+    fn on_fill(&mut self, _fill: &Fill, _context: &mut Context) {}
+
+    /// Called once, after the last event
+    fn on_stop(&mut self, _context: &mut Context) {}
+}
+
+/// Boxed strategies, e.g. one picked by name, are strategies too
+impl<S: Strategy + ?Sized> Strategy for Box<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn on_start(&mut self, context: &mut Context) {
+        (**self).on_start(context)
+    }
+
+    fn on_bar(&mut self, bar: &OhlcvBar, context: &mut Context) {
+        (**self).on_bar(bar, context)
+    }
+
+    fn on_trade(&mut self, trade: &TradeEvent, context: &mut Context) {
+        (**self).on_trade(trade, context)
+    }
+
+    fn on_quote(&mut self, quote: &QuoteEvent, context: &mut Context) {
+        (**self).on_quote(quote, context)
+    }
+
+    fn on_fill(&mut self, fill: &Fill, context: &mut Context) {
+        (**self).on_fill(fill, context)
+    }
+
+    fn on_stop(&mut self, context: &mut Context) {
+        (**self).on_stop(context)
+    }
+}
+
+/// Events and orders of an engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EngineStats {
+    pub bars: u64,
+    pub trades: u64,
+    pub quotes: u64,
+    pub fills: u64,
+    /// Orders the strategy asked for
+    pub orders: u64,
+}
+
+/// Drives a strategy: hands it each event with a [`Context`], keeps its
+/// positions from fills, and returns the orders it asks for. Whatever runs
+/// the engine decides what happens to the orders.
+#[derive(Debug)]
+pub struct Engine<S> {
+    strategy: S,
+    positions: HashMap<String, f64>,
+    now: Option<DateTime<Utc>>,
+    started: bool,
+    stopped: bool,
+    stats: EngineStats,
+}
+
+impl<S: Strategy> Engine<S> {
+    pub fn new(strategy: S) -> Self {
+        Self {
+            strategy,
+            positions: HashMap::new(),
+            now: None,
+            started: false,
+            stopped: false,
+            stats: EngineStats::default(),
+        }
+    }
+
+    pub fn strategy(&self) -> &S {
+        &self.strategy
+    }
+
+    pub fn into_strategy(self) -> S {
+        self.strategy
+    }
+
+    pub fn stats(&self) -> EngineStats {
+        self.stats
+    }
+
+    /// Shares held of each symbol after the fills so far
+    pub fn positions(&self) -> &HashMap<String, f64> {
+        &self.positions
+    }
+
+    /// Start the strategy at `now`; `handle` starts it at the first event
+    /// otherwise
+    pub fn start(&mut self, now: DateTime<Utc>) -> Vec<OrderRequest> {
+        if self.started {
+            return Vec::new();
+        }
+        self.started = true;
+        self.now = Some(now);
+        let strategy = &mut self.strategy;
+        let orders = with_context(now, &self.positions, |context| strategy.on_start(context));
+        self.stats.orders += orders.len() as u64;
+        orders
+    }
+
+    /// Hand an event to the strategy and return the orders it asks for
+    pub fn handle(&mut self, event: &MarketEvent) -> Vec<OrderRequest> {
+        let mut orders = self.start(event.timestamp());
+        // Events that arrive out of order do not move the clock back
+        let now = self
+            .now
+            .map_or(event.timestamp(), |now| now.max(event.timestamp()));
+        self.now = Some(now);
+        if let MarketEvent::Fill(fill) = event {
+            *self.positions.entry(fill.symbol.clone()).or_default() +=
+                fill.side.sign() * fill.quantity;
+        }
+        let strategy = &mut self.strategy;
+        let stats = &mut self.stats;
+        let handled = with_context(now, &self.positions, |context| match event {
+            MarketEvent::Bar(bar) => {
+                stats.bars += 1;
+                strategy.on_bar(bar, context)
+            }
+            MarketEvent::Trade(trade) => {
+                stats.trades += 1;
+                strategy.on_trade(trade, context)
+            }
+            MarketEvent::Quote(quote) => {
+                stats.quotes += 1;
+                strategy.on_quote(quote, context)
+            }
+            MarketEvent::Fill(fill) => {
+                stats.fills += 1;
+                strategy.on_fill(fill, context)
+            }
+        });
+        self.stats.orders += handled.len() as u64;
+        orders.extend(handled);
+        orders
+    }
+
+    /// Stop the strategy and return the orders it asks for on the way out;
+    /// nothing if it never started or has stopped already
+    pub fn stop(&mut self) -> Vec<OrderRequest> {
+        let Some(now) = self.now.filter(|_| self.started && !self.stopped) else {
+            return Vec::new();
+        };
+        self.stopped = true;
+        let strategy = &mut self.strategy;
+        let orders = with_context(now, &self.positions, |context| strategy.on_stop(context));
+        self.stats.orders += orders.len() as u64;
+        orders
+    }
+
+    /// Run the strategy over `events` in order, e.g. downloaded bars, and
+    /// hand each order to `router`
+    pub fn run(
+        &mut self,
+        events: impl IntoIterator<Item = MarketEvent>,
+        router: &mut dyn OrderRouter,
+    ) -> Result<EngineStats> {
+        for event in events {
+            for order in self.handle(&event) {
+                router.route(order)?;
+            }
+        }
+        for order in self.stop() {
+            router.route(order)?;
+        }
+        Ok(self.stats)
+    }
+}
+
+fn with_context(
+    now: DateTime<Utc>,
+    positions: &HashMap<String, f64>,
+    handle: impl FnOnce(&mut Context),
+) -> Vec<OrderRequest> {
+    let mut context = Context {
+        now,
+        positions,
+        orders: Vec::new(),
+    };
+    handle(&mut context);
+    context.orders
+}
+
+/// Takes the orders of a strategy: a simulated broker in a backtest, or the
+/// trading API in a live session
+pub trait OrderRouter: Send {
+    fn route(&mut self, order: OrderRequest) -> Result<()>;
+}
+
+/// Logs orders instead of placing them, to watch what a strategy would do
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DryRun;
+
+impl OrderRouter for DryRun {
+    fn route(&mut self, order: OrderRequest) -> Result<()> {
+        info!(symbol = %order.symbol, side = ?order.side, quantity = order.quantity, order_type = ?order.order_type, "Order (dry run)");
+        Ok(())
+    }
+}
+
+/// Collects orders, e.g. for tests
+impl OrderRouter for Vec<OrderRequest> {
+    fn route(&mut self, order: OrderRequest) -> Result<()> {
+        self.push(order);
+        Ok(())
+    }
+}
+
+/// Runs a strategy on the messages of a session: add it to the outputs of a
+/// live stream, a replay or a simulation. Bars, trades, quotes and fills
+/// reach the strategy, and its orders go to the router; closing the sink
+/// stops the strategy. Clones share the engine, so one can be kept for
+/// `stats` while another is in the session's outputs.
+pub struct StrategySink<S> {
+    inner: Arc<Mutex<EngineAndRouter<S>>>,
+}
+
+type EngineAndRouter<S> = (Engine<S>, Box<dyn OrderRouter>);
+
+impl<S> Clone for StrategySink<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S: Strategy> StrategySink<S> {
+    pub fn new(strategy: S, router: Box<dyn OrderRouter>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new((Engine::new(strategy), router))),
+        }
+    }
+
+    pub fn stats(&self) -> EngineStats {
+        self.inner.lock().unwrap().0.stats()
+    }
+}
+
+impl<S: Strategy> std::fmt::Debug for StrategySink<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("StrategySink")
+            .field("strategy", &inner.0.strategy.name())
+            .field("stats", &inner.0.stats)
+            .finish()
+    }
+}
+
+impl<S: Strategy> OutputSink for StrategySink<S> {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        let Some(event) = MarketEvent::from_streaming_data(data) else {
+            return Ok(());
+        };
+        let (engine, router) = &mut *self.inner.lock().unwrap();
+        for order in engine.handle(&event) {
+            router.route(order)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> {
+        let (engine, router) = &mut *self.inner.lock().unwrap();
+        for order in engine.stop() {
+            router.route(order)?;
+        }
+        Ok(())
+    }
+}
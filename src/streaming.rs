@@ -0,0 +1,623 @@
+//! The streaming session: connecting, reconnecting and failing over, and
+//! changing subscriptions while it runs.
+
+use crate::{
+    daemon, data_type_name, decode_streaming_message, feed_name, market_time, Error, MessageFilter,
+    OutputSink, Result, StreamingConfig, StreamingData, TradeUpdatesConnection,
+};
+use alpaca_trading_api_rust::{
+    StreamingClient, StreamingConnection, StreamingDataType, StreamingFeed, StreamingMessage,
+};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tracing::{error, info, warn, Instrument};
+
+/// Change to the live subscription set, issued while a session is running
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionCommand {
+    Subscribe(StreamingDataType, Vec<String>),
+    Unsubscribe(StreamingDataType, Vec<String>),
+}
+
+impl std::str::FromStr for SubscriptionCommand {
+    type Err = Error;
+    
+    /// Parse control commands such as `subscribe trades AAPL,MSFT` or `unsub quotes NVDA`
+    fn from_str(line: &str) -> Result<Self> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 3 {
+            return Err(Error::Invalid(format!("Invalid command: {}. Usage: subscribe|unsubscribe <trades|quotes|bars|daily_bars|updated_bars|statuses|lulds|imbalances> <SYMBOLS>", line.trim())));
+        }
+        
+        let data_type = match parts[1].to_lowercase().as_str() {
+            "trades" | "t" => StreamingDataType::Trades,
+            "quotes" | "q" => StreamingDataType::Quotes,
+            "bars" | "b" => StreamingDataType::Bars,
+            "daily_bars" | "d" => StreamingDataType::DailyBars,
+            "updated_bars" | "u" => StreamingDataType::UpdatedBars,
+            "statuses" | "s" => StreamingDataType::Statuses,
+            "lulds" | "l" => StreamingDataType::Lulds,
+            "imbalances" | "i" => StreamingDataType::Imbalances,
+            other => return Err(Error::Invalid(format!("Invalid channel: {}. Supported: trades, quotes, bars, daily_bars, updated_bars, statuses, lulds, imbalances", other))),
+        };
+        
+        let symbols: Vec<String> = parts[2..]
+            .join(",")
+            .split(',')
+            .map(|s| s.trim().to_uppercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+        
+        match parts[0].to_lowercase().as_str() {
+            "subscribe" | "sub" => Ok(SubscriptionCommand::Subscribe(data_type, symbols)),
+            "unsubscribe" | "unsub" => Ok(SubscriptionCommand::Unsubscribe(data_type, symbols)),
+            other => Err(Error::Invalid(format!("Invalid action: {}. Supported: subscribe, unsubscribe", other))),
+        }
+    }
+}
+
+/// Cloneable handle used to change subscriptions of a running session
+#[derive(Debug, Clone)]
+pub struct SubscriptionHandle {
+    sender: mpsc::UnboundedSender<SubscriptionCommand>,
+}
+
+impl SubscriptionHandle {
+    pub fn subscribe(&self, data_type: StreamingDataType, symbols: Vec<String>) -> Result<()> {
+        self.send(SubscriptionCommand::Subscribe(data_type, symbols))
+    }
+    
+    pub fn unsubscribe(&self, data_type: StreamingDataType, symbols: Vec<String>) -> Result<()> {
+        self.send(SubscriptionCommand::Unsubscribe(data_type, symbols))
+    }
+    
+    pub fn send(&self, command: SubscriptionCommand) -> Result<()> {
+        self.sender
+            .send(command)
+            .map_err(|_| Error::Stopped("Streaming session is no longer running".to_string()))
+    }
+}
+
+pub fn subscription_channel() -> (SubscriptionHandle, mpsc::UnboundedReceiver<SubscriptionCommand>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (SubscriptionHandle { sender }, receiver)
+}
+
+/// Split a subscription map into maps of at most `chunk_size` symbols in total
+pub fn chunk_subscriptions(
+    subscriptions: &HashMap<StreamingDataType, Vec<String>>,
+    chunk_size: usize,
+) -> Vec<HashMap<StreamingDataType, Vec<String>>> {
+    let chunk_size = chunk_size.max(1);
+    let mut channels: Vec<_> = subscriptions.iter().filter(|(_, symbols)| !symbols.is_empty()).collect();
+    channels.sort_by_key(|(data_type, _)| data_type_name(data_type));
+    
+    let mut chunks = Vec::new();
+    let mut current: HashMap<StreamingDataType, Vec<String>> = HashMap::new();
+    let mut size = 0;
+    for (data_type, symbols) in channels {
+        for symbol in symbols {
+            if size == chunk_size {
+                chunks.push(std::mem::take(&mut current));
+                size = 0;
+            }
+            current.entry(data_type.clone()).or_default().push(symbol.clone());
+            size += 1;
+        }
+    }
+    if size > 0 {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Run the streaming session until Ctrl+C, reconnecting on connection loss.
+///
+/// Every reconnection re-authenticates and re-issues the current subscription map,
+/// including changes made at runtime, and emits a `reconnect` event. Every drop
+/// emits a `disconnect` event first, with `retry_in_secs` unset on the last one.
+/// The error is only returned once the retry policy gives up.
+///
+/// When the symbols exceed `max_symbols_per_connection`, each group from
+/// `connection_configs` gets its own websocket with its own retries, all writing
+/// to the same sinks; runtime subscription changes go to the connection that
+/// holds the symbol, or the least loaded one for new symbols.
+pub async fn run_streaming_client(
+    config: &StreamingConfig,
+    commands: &mut mpsc::UnboundedReceiver<SubscriptionCommand>,
+) -> Result<()> {
+    info!(feed = ?config.feed, "Using streaming feed");
+    let connections = config.connection_configs();
+    let symbols: Vec<usize> = connections.iter().map(|connection| connection.all_symbols().len()).collect();
+    config.shard_metrics.reset(&symbols);
+    if connections.len() == 1 {
+        return run_connection(config, commands).await;
+    }
+    
+    info!(connections = connections.len(), symbols = config.all_symbols().len(), "Spreading symbols over several connections");
+    let mut owners = HashMap::new();
+    let mut senders = Vec::new();
+    let mut receivers = Vec::new();
+    for (index, connection) in connections.iter().enumerate() {
+        for symbol in connection.all_symbols() {
+            owners.insert(symbol, index);
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+        senders.push(sender);
+        receivers.push(receiver);
+    }
+    let sessions = futures_util::future::try_join_all(connections.iter().zip(receivers.iter_mut()).enumerate().map(
+        |(index, (connection, receiver))| {
+            run_connection(connection, receiver).instrument(tracing::info_span!("shard", index))
+        },
+    ));
+    tokio::select! {
+        result = sessions => result.map(|_| ()),
+        _ = route_subscription_commands(commands, &senders, &mut owners) => Ok(()),
+    }
+}
+
+/// Forward each command to the connections that hold (or will hold) its
+/// symbols. Runs until the command channel closes, then waits forever so the
+/// connections decide when the session ends.
+async fn route_subscription_commands(
+    commands: &mut mpsc::UnboundedReceiver<SubscriptionCommand>,
+    senders: &[mpsc::UnboundedSender<SubscriptionCommand>],
+    owners: &mut HashMap<String, usize>,
+) {
+    while let Some(command) = commands.recv().await {
+        let mut routed: Vec<Vec<String>> = vec![Vec::new(); senders.len()];
+        match &command {
+            SubscriptionCommand::Subscribe(_, symbols) => {
+                for symbol in symbols {
+                    let index = match owners.get(symbol) {
+                        Some(index) => *index,
+                        None => {
+                            let mut load = vec![0; senders.len()];
+                            for index in owners.values() {
+                                load[*index] += 1;
+                            }
+                            let least_loaded = (0..senders.len()).min_by_key(|index| load[*index]).unwrap_or(0);
+                            owners.insert(symbol.clone(), least_loaded);
+                            least_loaded
+                        }
+                    };
+                    routed[index].push(symbol.clone());
+                }
+            }
+            SubscriptionCommand::Unsubscribe(_, symbols) => {
+                for symbol in symbols {
+                    if let Some(index) = owners.get(symbol) {
+                        routed[*index].push(symbol.clone());
+                    }
+                }
+            }
+        }
+        for (sender, symbols) in senders.iter().zip(routed) {
+            if symbols.is_empty() {
+                continue;
+            }
+            let command = match &command {
+                SubscriptionCommand::Subscribe(data_type, _) => SubscriptionCommand::Subscribe(data_type.clone(), symbols),
+                SubscriptionCommand::Unsubscribe(data_type, _) => SubscriptionCommand::Unsubscribe(data_type.clone(), symbols),
+            };
+            let _ = sender.send(command);
+        }
+    }
+    std::future::pending::<()>().await
+}
+
+/// Reconnect and failover bookkeeping of one websocket
+struct ConnectionState {
+    retry_count: u32,
+    /// Error that ended the previous session, reported in the `reconnect` event
+    last_error: Option<String>,
+    feed: StreamingFeed,
+    /// Consecutive failed sessions on `feed` that delivered no messages
+    feed_failures: u32,
+    /// Set while on the backup feed: when to try the primary again
+    fail_back_at: Option<tokio::time::Instant>,
+    /// When the current session last received a message
+    last_message: Arc<Mutex<Option<tokio::time::Instant>>>,
+    /// When the current session authenticated
+    connected_at: Option<tokio::time::Instant>,
+}
+
+/// Why a session ended without an error
+enum SessionEnd {
+    Shutdown,
+    FailBack,
+}
+
+/// One websocket with its own reconnect loop and retry budget
+async fn run_connection(
+    config: &StreamingConfig,
+    commands: &mut mpsc::UnboundedReceiver<SubscriptionCommand>,
+) -> Result<()> {
+    let mut subscriptions = HashMap::new();
+    subscriptions.insert(StreamingDataType::Trades, config.trade_symbols.clone());
+    subscriptions.insert(StreamingDataType::Quotes, config.quote_symbols.clone());
+    subscriptions.insert(StreamingDataType::Bars, config.bar_symbols.clone());
+    subscriptions.insert(StreamingDataType::DailyBars, config.daily_bar_symbols.clone());
+    subscriptions.insert(StreamingDataType::UpdatedBars, config.updated_bar_symbols.clone());
+    subscriptions.insert(StreamingDataType::Statuses, config.status_symbols.clone());
+    subscriptions.insert(StreamingDataType::Lulds, config.luld_symbols.clone());
+    subscriptions.insert(StreamingDataType::Imbalances, config.imbalance_symbols.clone());
+    
+    // Ctrl+C or SIGTERM
+    let shutdown = daemon::shutdown_signal();
+    tokio::pin!(shutdown);
+    
+    let mut state = ConnectionState {
+        retry_count: 0,
+        last_error: None,
+        feed: config.feed.clone(),
+        feed_failures: 0,
+        fail_back_at: None,
+        last_message: Arc::new(Mutex::new(None)),
+        connected_at: None,
+    };
+    
+    loop {
+        *state.last_message.lock().unwrap() = None;
+        let result = run_session(config, &mut subscriptions, commands, &mut shutdown, &mut state).await;
+        // A connection that stayed up long enough starts a fresh retry budget
+        if state.connected_at.take().is_some_and(|at| at.elapsed() >= config.retry.reset_after) {
+            state.retry_count = 0;
+        }
+        match result {
+            Ok(SessionEnd::Shutdown) => break,
+            Ok(SessionEnd::FailBack) => {
+                switch_feed(config, &mut state, config.feed.clone(), "trying the primary feed again");
+                state.last_error = Some("fail back to the primary feed".to_string());
+            }
+            Err(e) => {
+                state.retry_count += 1;
+                error!(attempt = state.retry_count, max_retries = ?config.retry.max_retries, error = %e, "Streaming error");
+                
+                // A session that delivered data shows the feed itself works
+                if state.last_message.lock().unwrap().is_some() {
+                    state.feed_failures = 0;
+                }
+                state.feed_failures += 1;
+                let next_feed = match &config.backup_feed {
+                    Some(backup) if state.fail_back_at.is_none() && state.feed_failures >= config.failover_after => {
+                        Some((backup.clone(), format!("{} failed sessions, last: {}", state.feed_failures, e)))
+                    }
+                    Some(_) if state.fail_back_at.is_some_and(|at| at <= tokio::time::Instant::now()) => {
+                        Some((config.feed.clone(), "trying the primary feed again".to_string()))
+                    }
+                    _ => None,
+                };
+                if next_feed.is_some() {
+                    // The other feed gets a fresh retry budget
+                    state.retry_count = 1;
+                }
+                
+                let exhausted = config.retry.exhausted(state.retry_count);
+                let backoff_duration = config.retry.delay(state.retry_count);
+                config.shard_metrics.record_disconnect(config.shard.unwrap_or(0));
+                let mut data = StreamingData {
+                    received_time: Utc::now(),
+                    event_time: None,
+                    message_type: "disconnect".to_string(),
+                    symbol: None,
+                    data: serde_json::json!({
+                        "attempt": state.retry_count,
+                        "max_retries": config.retry.max_retries,
+                        "error": e.to_string(),
+                        "retry_in_secs": (!exhausted).then_some(backoff_duration.as_secs_f64().ceil() as u64),
+                    }),
+                };
+                if let Some(shard) = config.shard {
+                    data.data["shard"] = serde_json::json!(shard);
+                }
+                if let Err(write_error) = config.output_mode.write_streaming_data(&data) {
+                    warn!(error = %write_error, "Failed to write disconnect event");
+                }
+                
+                if exhausted {
+                    error!("Max retries reached, exiting");
+                    return Err(e);
+                }
+                if let Some((feed, reason)) = next_feed {
+                    switch_feed(config, &mut state, feed, &reason);
+                }
+                
+                warn!(backoff_ms = backoff_duration.as_millis() as u64, "Retrying after backoff");
+                state.last_error = Some(e.to_string());
+                
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff_duration) => {}
+                    _ = &mut shutdown => {
+                        info!("Received shutdown signal, shutting down gracefully");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    info!("Streaming client terminated");
+    Ok(())
+}
+
+/// Move the connection to `feed` and write a `failover` event
+fn switch_feed(config: &StreamingConfig, state: &mut ConnectionState, feed: StreamingFeed, reason: &str) {
+    let from = feed_name(&state.feed);
+    warn!(from, to = feed_name(&feed), reason, "Switching streaming feed");
+    state.fail_back_at = (feed != config.feed).then(|| tokio::time::Instant::now() + config.failback_after);
+    state.feed_failures = 0;
+    let mut data = StreamingData {
+        received_time: Utc::now(),
+        event_time: None,
+        message_type: "failover".to_string(),
+        symbol: None,
+        data: serde_json::json!({ "from": from, "to": feed_name(&feed), "reason": reason }),
+    };
+    state.feed = feed;
+    if let Some(shard) = config.shard {
+        data.data["shard"] = serde_json::json!(shard);
+    }
+    if let Err(e) = config.output_mode.write_streaming_data(&data) {
+        warn!(error = %e, "Failed to write failover event");
+    }
+}
+
+/// Resolves with the silent time once no message has arrived for `limit`,
+/// counted from `since` until the first one; never resolves without a limit.
+/// With `market_hours_only`, silence outside market hours does not count.
+async fn silence(
+    last_message: &Mutex<Option<tokio::time::Instant>>,
+    since: tokio::time::Instant,
+    limit: Option<Duration>,
+    market_hours_only: bool,
+) -> Duration {
+    let Some(limit) = limit else {
+        return std::future::pending().await;
+    };
+    let mut since = since;
+    loop {
+        let last = last_message.lock().unwrap().unwrap_or(since).max(since);
+        let now = tokio::time::Instant::now();
+        if now < last + limit {
+            tokio::time::sleep_until(last + limit).await;
+            continue;
+        }
+        if !market_hours_only || market_time::in_extended_hours(Utc::now()) {
+            return now - last;
+        }
+        // Nothing to hear outside market hours; start counting again from now
+        since = now;
+    }
+}
+
+/// Resolves at `at`; never resolves for `None`
+async fn sleep_until_some(at: Option<tokio::time::Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// One connection lifetime; returns `Ok` on shutdown or when it is time to
+/// fail back to the primary feed, and `Err` when the connection drops
+#[tracing::instrument(name = "session", skip_all, fields(feed = feed_name(&state.feed), attempt = state.retry_count))]
+async fn run_session<S>(
+    config: &StreamingConfig,
+    subscriptions: &mut HashMap<StreamingDataType, Vec<String>>,
+    commands: &mut mpsc::UnboundedReceiver<SubscriptionCommand>,
+    shutdown: &mut S,
+    state: &mut ConnectionState,
+) -> Result<SessionEnd>
+where
+    S: std::future::Future<Output = ()> + Unpin,
+{
+    let streaming_client = StreamingClient::new(state.feed.clone()).map_err(Error::alpaca)?;
+    let mut connection = streaming_client.connect().await.map_err(Error::alpaca)?;
+    
+    info!("Authenticating");
+    timeout(config.auth_timeout, connection.authenticate()).await?.map_err(Error::alpaca)?;
+    info!("Authentication successful");
+    
+    let active = |data_type: StreamingDataType| subscriptions.get(&data_type).cloned().unwrap_or_default();
+    for chunk in chunk_subscriptions(subscriptions, config.subscribe_chunk_size) {
+        timeout(config.subscribe_timeout, connection.subscribe(chunk))
+            .instrument(tracing::info_span!("subscribe"))
+            .await?
+            .map_err(Error::alpaca)?;
+    }
+    info!(
+        trades = ?active(StreamingDataType::Trades),
+        quotes = ?active(StreamingDataType::Quotes),
+        bars = ?active(StreamingDataType::Bars),
+        daily_bars = ?active(StreamingDataType::DailyBars),
+        updated_bars = ?active(StreamingDataType::UpdatedBars),
+        statuses = ?active(StreamingDataType::Statuses),
+        lulds = ?active(StreamingDataType::Lulds),
+        imbalances = ?active(StreamingDataType::Imbalances),
+        "Subscribed to data streams"
+    );
+    
+    let mut trade_updates = if config.trade_updates {
+        let mut connection = TradeUpdatesConnection::connect().await?.record_to(config.raw_recorder.clone());
+        timeout(config.auth_timeout, connection.authenticate()).await??;
+        timeout(config.subscribe_timeout, connection.listen()).await??;
+        info!("Listening to trade updates");
+        Some(connection)
+    } else {
+        None
+    };
+    
+    if let Some(reason) = &state.last_error {
+        let mut data = StreamingData {
+            received_time: Utc::now(),
+            event_time: None,
+            message_type: "reconnect".to_string(),
+            symbol: None,
+            data: serde_json::json!({
+                "attempt": state.retry_count,
+                "reason": reason,
+                "trades": active(StreamingDataType::Trades),
+                "quotes": active(StreamingDataType::Quotes),
+                "bars": active(StreamingDataType::Bars),
+                "daily_bars": active(StreamingDataType::DailyBars),
+                "updated_bars": active(StreamingDataType::UpdatedBars),
+                "statuses": active(StreamingDataType::Statuses),
+                "lulds": active(StreamingDataType::Lulds),
+                "imbalances": active(StreamingDataType::Imbalances),
+            }),
+        };
+        if let Some(shard) = config.shard {
+            data.data["shard"] = serde_json::json!(shard);
+        }
+        config.output_mode.write_streaming_data(&data)?;
+    } else {
+        info!("Press Ctrl+C to exit gracefully");
+    }
+    let shard = config.shard.unwrap_or(0);
+    config.shard_metrics.record_connected(shard, state.last_error.is_some());
+    let connected_at = tokio::time::Instant::now();
+    state.connected_at = Some(connected_at);
+    
+    loop {
+        let output_mode = config.output_mode.clone();
+        let filter = config.filter.clone();
+        let metrics = config.shard_metrics.clone();
+        let last_message = state.last_message.clone();
+        let recorder = config.raw_recorder.clone();
+        // A subscription command interrupts `run`; the connection itself stays open
+        // and reading resumes on the next iteration.
+        tokio::select! {
+            result = connection.run(move |message| {
+                metrics.record_message(shard);
+                *last_message.lock().unwrap() = Some(tokio::time::Instant::now());
+                if let Some(recorder) = &recorder {
+                    recorder.record_message(config.shard, &message)?;
+                }
+                Ok(process_filtered_message(&message, filter.as_ref(), &output_mode)?)
+            }) => {
+                if let Err(e) = result {
+                    error!(error = %e, "Streaming connection error");
+                    return Err(Error::alpaca(e));
+                }
+                return Ok(SessionEnd::Shutdown);
+            }
+            result = run_trade_updates(trade_updates.as_mut(), config) => {
+                if let Err(e) = result {
+                    error!(error = %e, "Trade updates connection error");
+                    return Err(e);
+                }
+            }
+            Some(command) = commands.recv() => {
+                if let Err(e) = apply_subscription_command(config, &mut connection, subscriptions, command).await {
+                    warn!(error = %e, "Subscription change failed");
+                }
+            }
+            silent = silence(&state.last_message, connected_at, config.silence_timeout, config.silence_market_hours_only) => {
+                warn!(silent_secs = silent.as_secs(), "Connection is stale, reconnecting");
+                let mut data = StreamingData {
+                    received_time: Utc::now(),
+                    event_time: None,
+                    message_type: "stale_connection".to_string(),
+                    symbol: None,
+                    data: serde_json::json!({
+                        "silent_secs": silent.as_secs(),
+                        "timeout_secs": config.silence_timeout.map(|limit| limit.as_secs()),
+                    }),
+                };
+                if let Some(shard) = config.shard {
+                    data.data["shard"] = serde_json::json!(shard);
+                }
+                config.output_mode.write_streaming_data(&data)?;
+                return Err(Error::Stopped(format!("No messages for {}s", silent.as_secs())));
+            }
+            _ = sleep_until_some(state.fail_back_at) => {
+                return Ok(SessionEnd::FailBack);
+            }
+            _ = &mut *shutdown => {
+                info!("Received shutdown signal, shutting down gracefully");
+                return Ok(SessionEnd::Shutdown);
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "subscription_change", skip(config, connection, subscriptions))]
+async fn apply_subscription_command(
+    config: &StreamingConfig,
+    connection: &mut StreamingConnection,
+    subscriptions: &mut HashMap<StreamingDataType, Vec<String>>,
+    command: SubscriptionCommand,
+) -> Result<()> {
+    let (action, data_type, symbols) = match command {
+        SubscriptionCommand::Subscribe(data_type, symbols) => {
+            let request = HashMap::from([(data_type.clone(), symbols.clone())]);
+            for chunk in chunk_subscriptions(&request, config.subscribe_chunk_size) {
+                timeout(config.subscribe_timeout, connection.subscribe(chunk)).await?.map_err(Error::alpaca)?;
+            }
+            let current = subscriptions.entry(data_type.clone()).or_default();
+            for symbol in &symbols {
+                if !current.contains(symbol) {
+                    current.push(symbol.clone());
+                }
+            }
+            ("subscribe", data_type, symbols)
+        }
+        SubscriptionCommand::Unsubscribe(data_type, symbols) => {
+            let request = HashMap::from([(data_type.clone(), symbols.clone())]);
+            for chunk in chunk_subscriptions(&request, config.subscribe_chunk_size) {
+                timeout(config.subscribe_timeout, connection.unsubscribe(chunk)).await?.map_err(Error::alpaca)?;
+            }
+            if let Some(current) = subscriptions.get_mut(&data_type) {
+                current.retain(|symbol| !symbols.contains(symbol));
+            }
+            ("unsubscribe", data_type, symbols)
+        }
+    };
+    
+    let data = StreamingData {
+        received_time: Utc::now(),
+        event_time: None,
+        message_type: "control".to_string(),
+        symbol: None,
+        data: serde_json::json!({
+            "action": action,
+            "channel": data_type_name(&data_type),
+            "symbols": symbols,
+            "active": subscriptions.get(&data_type).cloned().unwrap_or_default(),
+        }),
+    };
+    config.output_mode.write_streaming_data(&data)
+}
+
+async fn run_trade_updates(connection: Option<&mut TradeUpdatesConnection>, config: &StreamingConfig) -> Result<()> {
+    match connection {
+        Some(connection) => {
+            connection
+                .run(|message| process_filtered_message(&message, config.filter.as_ref(), &config.output_mode))
+                .await?;
+            Ok(())
+        }
+        None => std::future::pending().await,
+    }
+}
+
+pub fn process_streaming_message(message: &StreamingMessage, output_mode: &dyn OutputSink) -> Result<()> {
+    process_filtered_message(message, None, output_mode)
+}
+
+/// Like `process_streaming_message`, but only writes records that pass `filter`
+#[tracing::instrument(level = "trace", name = "message", skip_all, fields(message_type = %message.message_type))]
+pub fn process_filtered_message(message: &StreamingMessage, filter: Option<&MessageFilter>, output_mode: &dyn OutputSink) -> Result<()> {
+    let Some(data) = decode_streaming_message(message)? else {
+        return Ok(());
+    };
+    if filter.is_some_and(|filter| !filter.matches(&data)) {
+        return Ok(());
+    }
+    output_mode.write_streaming_data(&data)
+}
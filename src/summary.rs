@@ -0,0 +1,141 @@
+use crate::{OutputSink, Result, StreamingData};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Reads how many messages some part of the pipeline has discarded
+type DropCounter = Box<dyn Fn() -> u64 + Send + Sync>;
+
+/// End-of-session totals, written as a `summary` message and optionally to a
+/// JSON file
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Summary {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    pub messages: u64,
+    pub by_type: BTreeMap<String, u64>,
+    pub by_symbol: BTreeMap<String, u64>,
+    pub disconnects: u64,
+    pub reconnects: u64,
+    /// Messages discarded by full writer queues and quote conflation
+    pub dropped: u64,
+}
+
+#[derive(Debug)]
+struct Counts {
+    started_at: DateTime<Utc>,
+    by_type: BTreeMap<String, u64>,
+    by_symbol: BTreeMap<String, u64>,
+}
+
+/// Counts every message written through it for the end-of-session summary.
+/// Clones share the same counts.
+#[derive(Clone)]
+pub struct SessionSummary {
+    counts: Arc<Mutex<Counts>>,
+    drop_counters: Arc<Mutex<Vec<DropCounter>>>,
+}
+
+impl std::fmt::Debug for SessionSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionSummary")
+            .field("counts", &self.counts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for SessionSummary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionSummary {
+    pub fn new() -> Self {
+        Self {
+            counts: Arc::new(Mutex::new(Counts {
+                started_at: Utc::now(),
+                by_type: BTreeMap::new(),
+                by_symbol: BTreeMap::new(),
+            })),
+            drop_counters: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Include `counter` in the summary's `dropped` total
+    pub fn add_drop_counter<F>(&self, counter: F)
+    where
+        F: Fn() -> u64 + Send + Sync + 'static,
+    {
+        self.drop_counters.lock().unwrap().push(Box::new(counter));
+    }
+
+    pub fn snapshot(&self) -> Summary {
+        let counts = self.counts.lock().unwrap();
+        let ended_at = Utc::now();
+        let count = |message_type: &str| counts.by_type.get(message_type).copied().unwrap_or(0);
+        Summary {
+            started_at: counts.started_at,
+            ended_at,
+            duration_secs: (ended_at - counts.started_at).num_milliseconds() as f64 / 1000.0,
+            messages: counts.by_type.values().sum(),
+            by_type: counts.by_type.clone(),
+            by_symbol: counts.by_symbol.clone(),
+            disconnects: count("disconnect"),
+            reconnects: count("reconnect"),
+            dropped: self
+                .drop_counters
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|counter| counter())
+                .sum(),
+        }
+    }
+
+    /// Log the summary, write it through `output` as a `summary` message and,
+    /// with a `path`, as pretty JSON to that file
+    pub fn finish(&self, output: &dyn OutputSink, path: Option<&Path>) -> Result<Summary> {
+        let summary = self.snapshot();
+        tracing::info!(
+            messages = summary.messages,
+            symbols = summary.by_symbol.len(),
+            duration_secs = summary.duration_secs,
+            disconnects = summary.disconnects,
+            reconnects = summary.reconnects,
+            dropped = summary.dropped,
+            "Session summary"
+        );
+        output.write_streaming_data(&StreamingData {
+            received_time: summary.ended_at,
+            event_time: None,
+            message_type: "summary".to_string(),
+            symbol: None,
+            data: serde_json::to_value(&summary)?,
+        })?;
+        if let Some(path) = path {
+            std::fs::write(path, serde_json::to_string_pretty(&summary)? + "\n")?;
+        }
+        Ok(summary)
+    }
+}
+
+impl OutputSink for SessionSummary {
+    fn write_streaming_data(&self, data: &StreamingData) -> crate::Result<()> {
+        if data.message_type == "summary" {
+            return Ok(());
+        }
+        let mut counts = self.counts.lock().unwrap();
+        *counts.by_type.entry(data.message_type.clone()).or_default() += 1;
+        if let Some(symbol) = &data.symbol {
+            *counts.by_symbol.entry(symbol.clone()).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
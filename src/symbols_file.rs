@@ -0,0 +1,57 @@
+//! Symbol lists kept in files, for `--symbols-file`: one symbol per line, or
+//! the CSV or JSON output of `trade assets`.
+
+use crate::compression::open_reader;
+use crate::{Error, Result};
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::path::Path;
+
+/// The symbols in `path`, upper-cased, in file order and without repeats.
+/// Blank lines and lines starting with `#` are skipped. A CSV header with a
+/// `symbol` column selects that column, a JSON line its `symbol` field, and
+/// any other line gives its first word.
+pub fn read_symbols_file(path: &Path) -> Result<Vec<String>> {
+    let mut column = None;
+    let mut symbols: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    for line in open_reader(path)?.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let symbol = if line.starts_with('{') {
+            let value: serde_json::Value = serde_json::from_str(line)?;
+            match value.get("symbol").and_then(|symbol| symbol.as_str()) {
+                Some(symbol) => symbol.to_string(),
+                None => continue,
+            }
+        } else {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if let Some(index) = fields
+                .iter()
+                .position(|field| field.eq_ignore_ascii_case("symbol"))
+            {
+                column = Some(index);
+                continue;
+            }
+            match column {
+                Some(index) => fields.get(index).copied().unwrap_or_default().to_string(),
+                None => line
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+            }
+        };
+        let symbol = symbol.trim().to_uppercase();
+        if !symbol.is_empty() && seen.insert(symbol.clone()) {
+            symbols.push(symbol);
+        }
+    }
+    if symbols.is_empty() {
+        return Err(Error::Invalid(format!("No symbols in {}", path.display())));
+    }
+    Ok(symbols)
+}
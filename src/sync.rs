@@ -0,0 +1,102 @@
+//! What a local market-data file holds, so `historical sync` and `historical
+//! backfill` fetch only the bars that are missing from it.
+
+use crate::compression::open_reader;
+use crate::{DataFormat, Error, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// The timestamp of the last record of each symbol in a CSV or JSON-lines
+/// file written by `historical`; see `for_each_record`
+pub fn last_timestamps(path: &Path, format: &DataFormat) -> Result<HashMap<String, DateTime<Utc>>> {
+    let mut last: HashMap<String, DateTime<Utc>> = HashMap::new();
+    for_each_record(path, format, |symbol, timestamp| {
+        let entry = last.entry(symbol).or_insert(timestamp);
+        *entry = (*entry).max(timestamp);
+    })?;
+    Ok(last)
+}
+
+/// The timestamps of every record of each symbol in a CSV or JSON-lines file
+/// written by `historical`, in file order; see `for_each_record`
+pub fn stored_timestamps(
+    path: &Path,
+    format: &DataFormat,
+) -> Result<HashMap<String, Vec<DateTime<Utc>>>> {
+    let mut stored: HashMap<String, Vec<DateTime<Utc>>> = HashMap::new();
+    for_each_record(path, format, |symbol, timestamp| {
+        stored.entry(symbol).or_default().push(timestamp)
+    })?;
+    Ok(stored)
+}
+
+/// Call `record` with the symbol and timestamp of each record of a CSV or
+/// JSON-lines file, optionally compressed. A missing file has no records.
+/// Lines without a symbol and a parseable timestamp, such as the CSV headers
+/// appended runs repeat, are skipped.
+pub fn for_each_record(
+    path: &Path,
+    format: &DataFormat,
+    mut record: impl FnMut(String, DateTime<Utc>),
+) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let mut columns = (0, 1);
+    for line in open_reader(path)?.lines() {
+        let line = line?;
+        let fields = match format {
+            DataFormat::Csv => {
+                let fields: Vec<&str> = line.split(',').collect();
+                if let (Some(symbol), Some(timestamp)) = (
+                    fields.iter().position(|field| *field == "symbol"),
+                    fields.iter().position(|field| *field == "timestamp"),
+                ) {
+                    columns = (symbol, timestamp);
+                    continue;
+                }
+                fields
+                    .get(columns.0)
+                    .zip(fields.get(columns.1))
+                    .map(|(symbol, timestamp)| (symbol.to_string(), timestamp.to_string()))
+            }
+            DataFormat::Json => serde_json::from_str::<serde_json::Value>(&line)
+                .ok()
+                .and_then(|value| {
+                    Some((
+                        value.get("symbol")?.as_str()?.to_string(),
+                        value.get("timestamp")?.as_str()?.to_string(),
+                    ))
+                }),
+            DataFormat::Plain => {
+                return Err(Error::Invalid(
+                    "Plain output has no timestamps to read; use csv or json".to_string(),
+                ))
+            }
+        };
+        let Some((symbol, timestamp)) = fields else {
+            continue;
+        };
+        if let Ok(timestamp) = timestamp.parse::<DateTime<Utc>>() {
+            record(symbol, timestamp);
+        }
+    }
+    Ok(())
+}
+
+/// Where `symbol` continues when syncing several stores: the earliest of
+/// their last timestamps, so none misses bars, or `None` when any store has
+/// no bars of it yet and it has to start from the beginning
+pub fn resume_from(
+    stores: &[&HashMap<String, DateTime<Utc>>],
+    symbol: &str,
+) -> Option<DateTime<Utc>> {
+    // `None` sorts before every `Some`
+    stores
+        .iter()
+        .map(|store| store.get(symbol).copied())
+        .min()
+        .flatten()
+}
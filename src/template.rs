@@ -0,0 +1,69 @@
+use crate::filter::message_type_code;
+use crate::{payload_fields, Error, Result, StreamingData};
+use minijinja::Environment;
+
+/// User-defined plain-format lines per message type, written with
+/// [MiniJinja](https://docs.rs/minijinja) syntax, e.g.
+/// `trades={{ symbol }} {{ price }} x {{ size }}`.
+///
+/// Templates see `type`, `symbol`, `received_time`, `event_time`, the raw
+/// payload as `data`, the friendly payload fields (`price`, `bid`, `close`,
+/// ...) and `spread` for quotes. Types without a template keep the built-in
+/// line.
+#[derive(Debug, Clone, Default)]
+pub struct PlainTemplates {
+    env: Environment<'static>,
+}
+
+impl PlainTemplates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a `TYPE=TEMPLATE` spec; `TYPE` is a message type such as `t`,
+    /// `trades`, `quotes`, `bars`, `alert` or `stats`
+    pub fn add(&mut self, spec: &str) -> Result<()> {
+        let (message_type, source) = spec.split_once('=').ok_or_else(|| {
+            Error::Invalid(format!(
+                "Invalid template: {}. Use TYPE=TEMPLATE, e.g. trades=\"{{{{symbol}}}} {{{{price}}}}\"",
+                spec
+            ))
+        })?;
+        let message_type = message_type_code(message_type.trim()).to_string();
+        self.env
+            .add_template_owned(message_type, source.to_string())
+            .map_err(|e| Error::Invalid(format!("Invalid template: {} ({})", spec, e)))
+    }
+
+    /// The templated line for `data`, or `None` when its type has no template.
+    /// A template that fails to render is logged and `None` is returned.
+    pub fn render(&self, data: &StreamingData) -> Option<String> {
+        let template = self.env.get_template(&data.message_type).ok()?;
+        match template.render(context(data)) {
+            Ok(line) => Some(line),
+            Err(e) => {
+                tracing::warn!(message_type = %data.message_type, error = %e, "Template failed, using the built-in format");
+                None
+            }
+        }
+    }
+}
+
+fn context(data: &StreamingData) -> serde_json::Value {
+    let mut context = serde_json::json!({
+        "type": data.message_type,
+        "symbol": data.symbol,
+        "received_time": data.received_time.to_rfc3339(),
+        "event_time": data.event_time.map(|t| t.to_rfc3339()),
+        "data": data.data,
+    });
+    for (name, key) in payload_fields(&data.message_type) {
+        if let Some(value) = data.data.get(key) {
+            context[*name] = value.clone();
+        }
+    }
+    if let (Some(ask), Some(bid)) = (data.data["ap"].as_f64(), data.data["bp"].as_f64()) {
+        context["spread"] = serde_json::json!(ask - bid);
+    }
+    context
+}
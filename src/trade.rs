@@ -0,0 +1,8 @@
+use algorithms_trading::cli::Cli;
+use anyhow::Result;
+use clap::Parser;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    Cli::parse().run().await
+}
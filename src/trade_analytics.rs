@@ -0,0 +1,223 @@
+//! Per-symbol analytics of captured trades for the analyzer: VWAP and
+//! volume, how trade sizes are distributed, the largest prints, and how much
+//! volume the tick rule classifies as buying or selling.
+
+use crate::StreamingData;
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Lower bounds and labels of the trade size ranges, in shares
+pub const SIZE_RANGES: [(u64, &str); 6] = [
+    (0, "1-99"),
+    (100, "100-499"),
+    (500, "500-999"),
+    (1000, "1000-4999"),
+    (5000, "5000-9999"),
+    (10_000, "10000+"),
+];
+
+/// Trades of one size range
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SizeRange {
+    pub range: String,
+    pub trades: u64,
+    pub volume: u64,
+}
+
+/// One trade, as listed among the largest prints
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Print {
+    pub timestamp: DateTime<Utc>,
+    pub price: f64,
+    pub size: u64,
+}
+
+/// Analytics of one symbol's trades
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct TradeReport {
+    pub symbol: String,
+    pub trades: u64,
+    pub volume: u64,
+    /// Volume-weighted average price of every trade
+    pub vwap: f64,
+    pub sizes: Vec<SizeRange>,
+    /// The largest trades by size, largest first
+    pub largest: Vec<Print>,
+    /// Volume of trades above the last different price
+    pub buy_volume: u64,
+    /// Volume of trades below the last different price
+    pub sell_volume: u64,
+    /// Volume of trades before the price first changed
+    pub unclassified_volume: u64,
+}
+
+impl TradeReport {
+    /// Share of the volume classified as buying, in percent
+    pub fn buy_percent(&self) -> f64 {
+        percent(self.buy_volume, self.volume)
+    }
+
+    /// Share of the volume classified as selling, in percent
+    pub fn sell_percent(&self) -> f64 {
+        percent(self.sell_volume, self.volume)
+    }
+}
+
+impl std::fmt::Display for TradeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} trades, volume {}, VWAP {:.4}, {:.1}% buy / {:.1}% sell volume",
+            self.symbol,
+            self.trades,
+            self.volume,
+            self.vwap,
+            self.buy_percent(),
+            self.sell_percent()
+        )
+    }
+}
+
+/// A print as (size, sequence, time, price bits), ordered by size and then
+/// with the earlier trade first
+type PrintKey = (u64, Reverse<u64>, DateTime<Utc>, u64);
+
+/// Side the tick rule gives a trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Default)]
+struct Accumulator {
+    trades: u64,
+    volume: u64,
+    notional: f64,
+    sizes: [(u64, u64); SIZE_RANGES.len()],
+    /// The largest prints, smallest on top
+    largest: BinaryHeap<Reverse<PrintKey>>,
+    /// Last price and the side of the last price change
+    last_price: Option<f64>,
+    side: Option<Side>,
+    buy_volume: u64,
+    sell_volume: u64,
+    unclassified_volume: u64,
+}
+
+/// Analytics of the trades (`t` messages) seen so far, by symbol
+#[derive(Debug, Default)]
+pub struct TradeAnalytics {
+    top: usize,
+    symbols: HashMap<String, Accumulator>,
+}
+
+impl TradeAnalytics {
+    /// Keep the `top` largest prints of each symbol
+    pub fn new(top: usize) -> Self {
+        Self {
+            top,
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Count `data` if it is a trade; other messages are ignored. Trades are
+    /// expected in time order per symbol for the tick rule.
+    pub fn add(&mut self, data: &StreamingData) {
+        let Some(symbol) = data.symbol.as_ref().filter(|_| data.message_type == "t") else {
+            return;
+        };
+        let (Some(price), Some(size)) = (data.data["p"].as_f64(), data.data["s"].as_u64()) else {
+            return;
+        };
+        let acc = self.symbols.entry(symbol.clone()).or_default();
+        acc.trades += 1;
+        acc.volume += size;
+        acc.notional += price * size as f64;
+        let range = SIZE_RANGES
+            .iter()
+            .rposition(|(lower, _)| size >= *lower)
+            .unwrap_or(0);
+        acc.sizes[range].0 += 1;
+        acc.sizes[range].1 += size;
+
+        // Uptick buys, downtick sells, and an unchanged price keeps the side
+        // of the last change
+        match acc.last_price {
+            Some(last) if price > last => acc.side = Some(Side::Buy),
+            Some(last) if price < last => acc.side = Some(Side::Sell),
+            _ => {}
+        }
+        acc.last_price = Some(price);
+        match acc.side {
+            Some(Side::Buy) => acc.buy_volume += size,
+            Some(Side::Sell) => acc.sell_volume += size,
+            None => acc.unclassified_volume += size,
+        }
+
+        let time = data.event_time.unwrap_or(data.received_time);
+        acc.largest
+            .push(Reverse((size, Reverse(acc.trades), time, price.to_bits())));
+        if acc.largest.len() > self.top {
+            acc.largest.pop();
+        }
+    }
+
+    /// Whether any trade has been seen
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Analytics of every symbol with trades, sorted by symbol
+    pub fn reports(&self) -> Vec<TradeReport> {
+        let mut reports: Vec<TradeReport> = self
+            .symbols
+            .iter()
+            .map(|(symbol, acc)| {
+                let mut largest: Vec<_> = acc.largest.iter().map(|Reverse(print)| *print).collect();
+                largest.sort_by(|a, b| b.cmp(a));
+                TradeReport {
+                    symbol: symbol.clone(),
+                    trades: acc.trades,
+                    volume: acc.volume,
+                    vwap: if acc.volume > 0 {
+                        acc.notional / acc.volume as f64
+                    } else {
+                        0.0
+                    },
+                    sizes: SIZE_RANGES
+                        .iter()
+                        .zip(acc.sizes)
+                        .map(|((_, range), (trades, volume))| SizeRange {
+                            range: range.to_string(),
+                            trades,
+                            volume,
+                        })
+                        .collect(),
+                    largest: largest
+                        .into_iter()
+                        .map(|(size, _, timestamp, price)| Print {
+                            timestamp,
+                            price: f64::from_bits(price),
+                            size,
+                        })
+                        .collect(),
+                    buy_volume: acc.buy_volume,
+                    sell_volume: acc.sell_volume,
+                    unclassified_volume: acc.unclassified_volume,
+                }
+            })
+            .collect();
+        reports.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        reports
+    }
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64 * 100.0
+    }
+}
@@ -0,0 +1,151 @@
+use crate::raw_frames::{RawRecorder, TRADE_UPDATES};
+use alpaca_trading_api_rust::StreamingMessage;
+use crate::{Error, Result};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+pub(crate) const DEFAULT_TRADING_BASE_URL: &str = "https://paper-api.alpaca.markets";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TradeUpdateOrder {
+    pub id: String,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    pub symbol: String,
+    pub side: String,
+    #[serde(rename = "type", default)]
+    pub order_type: Option<String>,
+    #[serde(default)]
+    pub qty: Option<String>,
+    #[serde(default)]
+    pub filled_qty: Option<String>,
+    #[serde(default)]
+    pub filled_avg_price: Option<String>,
+    #[serde(default)]
+    pub limit_price: Option<String>,
+    #[serde(default)]
+    pub stop_price: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+/// A single event from Alpaca's `trade_updates` stream (fill, partial_fill, canceled, ...)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TradeUpdate {
+    pub event: String,
+    #[serde(default)]
+    pub execution_id: Option<String>,
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub price: Option<String>,
+    #[serde(default)]
+    pub qty: Option<String>,
+    #[serde(default)]
+    pub position_qty: Option<String>,
+    pub order: TradeUpdateOrder,
+}
+
+/// Derive the trading websocket URL from an `APCA_API_BASE_URL` style value
+pub fn trade_updates_url(base_url: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    let base = base.strip_suffix("/v2").unwrap_or(base);
+    let ws_base = if let Some(rest) = base.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = base.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        base.to_string()
+    };
+    format!("{}/stream", ws_base)
+}
+
+/// Connection to the Alpaca trading websocket, which carries order events
+/// rather than market data and therefore lives beside the market data stream.
+pub struct TradeUpdatesConnection {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    recorder: Option<RawRecorder>,
+}
+
+impl TradeUpdatesConnection {
+    pub async fn connect() -> Result<Self> {
+        let base_url = std::env::var("APCA_API_BASE_URL")
+            .unwrap_or_else(|_| DEFAULT_TRADING_BASE_URL.to_string());
+        let (ws, _) = connect_async(trade_updates_url(&base_url)).await?;
+        Ok(Self { ws, recorder: None })
+    }
+
+    /// Record every frame received to `recorder`, before it is parsed
+    pub fn record_to(mut self, recorder: Option<RawRecorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    pub async fn authenticate(&mut self) -> Result<()> {
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| Error::Invalid(format!("{} is not set", name)))
+        };
+        let key = var("APCA_API_KEY_ID")?;
+        let secret = var("APCA_API_SECRET_KEY")?;
+        let request = serde_json::json!({ "action": "auth", "key": key, "secret": secret });
+        self.ws.send(Message::Text(request.to_string())).await?;
+
+        let response = self.next_frame().await?;
+        match response["data"]["status"].as_str() {
+            Some("authorized") => Ok(()),
+            _ => Err(Error::Rejected(format!("Trade updates authentication failed: {}", response))),
+        }
+    }
+
+    pub async fn listen(&mut self) -> Result<()> {
+        let request = serde_json::json!({ "action": "listen", "data": { "streams": ["trade_updates"] } });
+        self.ws.send(Message::Text(request.to_string())).await?;
+
+        let response = self.next_frame().await?;
+        if response["stream"] == "listening" {
+            Ok(())
+        } else {
+            Err(Error::Rejected(format!("Failed to listen to trade_updates: {}", response)))
+        }
+    }
+
+    /// Forward every order event to `handler` as a `trade_update` message
+    pub async fn run<F>(&mut self, mut handler: F) -> Result<()>
+    where
+        F: FnMut(StreamingMessage) -> Result<()>,
+    {
+        loop {
+            let frame = self.next_frame().await?;
+            if frame["stream"] == "trade_updates" {
+                handler(StreamingMessage {
+                    message_type: "trade_update".to_string(),
+                    message: None,
+                    data: frame["data"].clone(),
+                })?;
+            }
+        }
+    }
+
+    async fn next_frame(&mut self) -> Result<serde_json::Value> {
+        while let Some(message) = self.ws.next().await {
+            // The trading stream sends binary frames to most clients
+            let payload = match message? {
+                Message::Text(text) => text.into_bytes(),
+                Message::Binary(bytes) => bytes,
+                Message::Ping(payload) => {
+                    self.ws.send(Message::Pong(payload)).await?;
+                    continue;
+                }
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            if let Some(recorder) = &self.recorder {
+                recorder.record(TRADE_UPDATES, None, &String::from_utf8_lossy(&payload))?;
+            }
+            return Ok(serde_json::from_slice(&payload)?);
+        }
+        Err(Error::Stopped("Trade updates connection closed".to_string()))
+    }
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// Status codes that stop trading: CTA `2` and UTP `H` (halt), UTP `P`
+/// (volatility pause, e.g. a LULD pause)
+pub(crate) const HALT_CODES: [&str; 3] = ["2", "H", "P"];
+/// Status codes that restart it: CTA `3`, UTP `Q` (quotation resumption) and
+/// `T` (trading resumption)
+pub(crate) const RESUME_CODES: [&str; 3] = ["3", "Q", "T"];
+
+/// An `s` stream message from the `statuses` channel: a trading halt, pause,
+/// resumption or other status change for a symbol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradingStatus {
+    #[serde(rename = "S")]
+    pub symbol: String,
+    #[serde(rename = "sc")]
+    pub status_code: String,
+    #[serde(rename = "sm", default)]
+    pub status_message: String,
+    #[serde(rename = "rc", default)]
+    pub reason_code: String,
+    #[serde(rename = "rm", default)]
+    pub reason_message: String,
+    #[serde(rename = "z", default)]
+    pub tape: String,
+    #[serde(rename = "t")]
+    pub timestamp: String,
+}
+
+impl TradingStatus {
+    pub fn is_halt(&self) -> bool {
+        HALT_CODES.contains(&self.status_code.as_str())
+    }
+
+    pub fn is_resume(&self) -> bool {
+        RESUME_CODES.contains(&self.status_code.as_str())
+    }
+
+    /// `status_message`, with the reason when one is given
+    pub fn description(&self) -> String {
+        if self.reason_message.is_empty() {
+            self.status_message.clone()
+        } else {
+            format!(
+                "{} ({}: {})",
+                self.status_message, self.reason_code, self.reason_message
+            )
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Sanity checks for downloaded bars: prices that cannot happen, moves too
+//! large to be real and volume far above the recent average.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Header of the validation report, one row per issue
+pub const VALIDATION_CSV_HEADER: &str = "symbol,timestamp,issue,detail";
+
+/// Moves from the previous close larger than this fraction are flagged
+pub const DEFAULT_MAX_GAP: f64 = 0.5;
+
+/// Volume above this multiple of the recent average is flagged
+pub const DEFAULT_VOLUME_MULTIPLE: f64 = 20.0;
+
+/// Bars the recent average volume is taken over; fewer are not judged
+const VOLUME_WINDOW: usize = 20;
+
+/// What is wrong with a bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The high is below the low
+    HighBelowLow,
+    /// The open or close is outside the high-low range
+    OutsideRange,
+    /// A price is zero or negative
+    NonPositivePrice,
+    /// The open moved more than `max_gap` from the previous close, e.g. an
+    /// unadjusted split
+    PriceGap,
+    /// The volume is more than `volume_multiple` times the recent average
+    VolumeOutlier,
+}
+
+impl IssueKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueKind::HighBelowLow => "high_below_low",
+            IssueKind::OutsideRange => "outside_range",
+            IssueKind::NonPositivePrice => "non_positive_price",
+            IssueKind::PriceGap => "price_gap",
+            IssueKind::VolumeOutlier => "volume_outlier",
+        }
+    }
+}
+
+/// The prices and volume of a bar to check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarValues {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+}
+
+/// One issue found in a bar
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct BarIssue {
+    pub symbol: String,
+    pub timestamp: String,
+    pub issue: IssueKind,
+    pub detail: String,
+}
+
+impl BarIssue {
+    /// A row of the report, matching `VALIDATION_CSV_HEADER`
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{}",
+            self.symbol,
+            self.timestamp,
+            self.issue.as_str(),
+            self.detail
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct SymbolHistory {
+    close: Option<f64>,
+    volumes: VecDeque<u64>,
+}
+
+/// Checks the bars of each symbol in time order against each other
+#[derive(Debug)]
+pub struct BarValidator {
+    pub max_gap: f64,
+    pub volume_multiple: f64,
+    history: HashMap<String, SymbolHistory>,
+    counts: BTreeMap<IssueKind, usize>,
+}
+
+impl Default for BarValidator {
+    fn default() -> Self {
+        Self {
+            max_gap: DEFAULT_MAX_GAP,
+            volume_multiple: DEFAULT_VOLUME_MULTIPLE,
+            history: HashMap::new(),
+            counts: BTreeMap::new(),
+        }
+    }
+}
+
+impl BarValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The issues of the next bar of `symbol`
+    pub fn check(&mut self, symbol: &str, timestamp: &str, bar: &BarValues) -> Vec<BarIssue> {
+        let mut issues = Vec::new();
+        let mut flag = |issue: IssueKind, detail: String| {
+            issues.push(BarIssue {
+                symbol: symbol.to_string(),
+                timestamp: timestamp.to_string(),
+                issue,
+                detail,
+            })
+        };
+
+        let prices_valid = [bar.open, bar.high, bar.low, bar.close]
+            .iter()
+            .all(|price| *price > 0.0);
+        if !prices_valid {
+            flag(
+                IssueKind::NonPositivePrice,
+                format!(
+                    "open {} high {} low {} close {}",
+                    bar.open, bar.high, bar.low, bar.close
+                ),
+            );
+        }
+        if bar.high < bar.low {
+            flag(
+                IssueKind::HighBelowLow,
+                format!("high {} < low {}", bar.high, bar.low),
+            );
+        } else {
+            for (name, price) in [("open", bar.open), ("close", bar.close)] {
+                if price > bar.high || price < bar.low {
+                    flag(
+                        IssueKind::OutsideRange,
+                        format!("{} {} outside {}-{}", name, price, bar.low, bar.high),
+                    );
+                }
+            }
+        }
+
+        let history = self.history.entry(symbol.to_string()).or_default();
+        if let Some(previous) = history.close {
+            let gap = bar.open / previous - 1.0;
+            if prices_valid && gap.abs() > self.max_gap {
+                flag(
+                    IssueKind::PriceGap,
+                    format!(
+                        "open {} is {:+.1}% from previous close {}",
+                        bar.open,
+                        gap * 100.0,
+                        previous
+                    ),
+                );
+            }
+        }
+        if history.volumes.len() == VOLUME_WINDOW {
+            let average = history.volumes.iter().sum::<u64>() as f64 / history.volumes.len() as f64;
+            if average > 0.0 && bar.volume as f64 > average * self.volume_multiple {
+                flag(
+                    IssueKind::VolumeOutlier,
+                    format!(
+                        "volume {} is {:.0}x the average of the last {} bars",
+                        bar.volume,
+                        bar.volume as f64 / average,
+                        VOLUME_WINDOW
+                    ),
+                );
+            }
+            history.volumes.pop_front();
+        }
+        history.volumes.push_back(bar.volume);
+        if prices_valid {
+            history.close = Some(bar.close);
+        }
+
+        for issue in &issues {
+            *self.counts.entry(issue.issue).or_default() += 1;
+        }
+        issues
+    }
+
+    /// Issues found so far by kind
+    pub fn counts(&self) -> &BTreeMap<IssueKind, usize> {
+        &self.counts
+    }
+}
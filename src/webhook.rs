@@ -0,0 +1,67 @@
+use crate::{Error, Result};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+enum WebhookCommand {
+    Post(serde_json::Value),
+    Shutdown,
+}
+
+/// POSTs JSON payloads from a background task, so a slow or unreachable
+/// endpoint never holds up the stream. Failed requests are logged and dropped.
+/// Clones share the same task.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    sender: mpsc::UnboundedSender<WebhookCommand>,
+    handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl Webhook {
+    /// Start the delivery task; must be called inside a Tokio runtime
+    pub fn spawn(url: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        // The URL can embed a token (Slack, Telegram), so only the host is logged
+        let host = reqwest::Url::parse(url)
+            .map_err(|e| Error::Invalid(format!("Invalid webhook URL: {}", e)))?
+            .host_str()
+            .unwrap_or_default()
+            .to_string();
+        let target = url.to_string();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(async move {
+            while let Some(WebhookCommand::Post(payload)) = receiver.recv().await {
+                let result = client
+                    .post(&target)
+                    .json(&payload)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(e) = result {
+                    tracing::warn!(%host, error = %e.without_url(), "Webhook delivery failed");
+                }
+            }
+        });
+        Ok(Self {
+            sender,
+            handle: Arc::new(Mutex::new(Some(handle))),
+        })
+    }
+
+    pub fn post(&self, payload: serde_json::Value) {
+        // Only fails once the webhook has been shut down
+        let _ = self.sender.send(WebhookCommand::Post(payload));
+    }
+
+    /// Deliver the queued payloads and stop the task
+    pub async fn shutdown(&self) {
+        let _ = self.sender.send(WebhookCommand::Shutdown);
+        let handle = self.handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            let _ = handle.await;
+        }
+    }
+}
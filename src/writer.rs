@@ -0,0 +1,279 @@
+use crate::{Error, OutputSink, Result, StreamingData};
+use clap::ValueEnum;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::VecDeque;
+use std::sync::{mpsc as std_mpsc, Arc, Condvar, Mutex};
+
+/// What a producer does when the writer queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BackpressurePolicy {
+    /// Wait for the writer to catch up; nothing is lost (default)
+    #[default]
+    Block,
+    /// Discard the oldest queued message to make room for the new one
+    DropOldest,
+}
+
+/// Snapshot of a writer pipeline's counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct WriterStats {
+    /// Messages the wrapped sink wrote without an error
+    pub written: u64,
+    /// Messages discarded under `DropOldest`
+    pub dropped: u64,
+    /// Times a producer had to wait under `Block`
+    pub blocked: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    written: AtomicU64,
+    dropped: AtomicU64,
+    blocked: AtomicU64,
+}
+
+#[derive(Debug)]
+enum WriterCommand {
+    Data(StreamingData),
+    Line(String),
+    Flush,
+    Rotate,
+    Close(std_mpsc::Sender<Result<()>>),
+}
+
+impl WriterCommand {
+    fn is_record(&self) -> bool {
+        matches!(self, WriterCommand::Data(_) | WriterCommand::Line(_))
+    }
+}
+
+/// The bounded queue between producers and the writer thread. Both sides
+/// hold the lock only to push or pop, never while waiting or writing.
+#[derive(Debug)]
+struct Queue {
+    state: Mutex<QueueState>,
+    capacity: usize,
+    /// Signalled when a command is pushed or the queue is closed
+    not_empty: Condvar,
+    /// Signalled when a command is popped or the queue is closed
+    not_full: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    commands: VecDeque<WriterCommand>,
+    /// Set once the writer has stopped or every producer is gone; later
+    /// commands are refused
+    closed: bool,
+}
+
+impl Queue {
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+}
+
+/// Moves writes off the caller's thread: records go through a bounded queue
+/// to a dedicated writer thread that owns the wrapped sink.
+///
+/// The streaming callback only pays for a queue push, so slow disks no longer
+/// stall the websocket reader. When the queue is full the `BackpressurePolicy`
+/// decides between waiting and dropping the oldest queued message.
+#[derive(Debug)]
+pub struct PipelinedSink {
+    queue: Arc<Queue>,
+    policy: BackpressurePolicy,
+    counters: Arc<Counters>,
+}
+
+impl PipelinedSink {
+    pub fn new(inner: Box<dyn OutputSink>, capacity: usize, policy: BackpressurePolicy) -> Result<Self> {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState::default()),
+            capacity: capacity.max(1),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        });
+        let counters = Arc::new(Counters::default());
+
+        let writer_queue = queue.clone();
+        let writer_counters = counters.clone();
+        std::thread::Builder::new()
+            .name("output-writer".to_string())
+            .spawn(move || writer_loop(inner, writer_queue, writer_counters))?;
+
+        Ok(Self {
+            queue,
+            policy,
+            counters,
+        })
+    }
+
+    pub fn stats(&self) -> WriterStats {
+        WriterStats {
+            written: self.counters.written.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            blocked: self.counters.blocked.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reads the `dropped` counter, for use after the sink has been boxed
+    pub fn dropped_counter(&self) -> impl Fn() -> u64 + Send + Sync + 'static {
+        let counters = self.counters.clone();
+        move || counters.dropped.load(Ordering::Relaxed)
+    }
+
+    fn enqueue(&self, command: WriterCommand) -> Result<()> {
+        let queue = &self.queue;
+        let mut state = queue.state.lock().unwrap();
+        if state.closed {
+            return Err(writer_stopped());
+        }
+
+        if state.commands.len() >= queue.capacity {
+            // Control commands always wait so a flush or close is never lost
+            let oldest = (self.policy == BackpressurePolicy::DropOldest && command.is_record())
+                .then(|| state.commands.iter().position(WriterCommand::is_record))
+                .flatten();
+            if let Some(oldest) = oldest {
+                state.commands.remove(oldest);
+                let dropped = self.counters.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped.is_power_of_two() {
+                    tracing::warn!(dropped, "Output queue full, dropping oldest messages");
+                }
+            } else {
+                self.counters.blocked.fetch_add(1, Ordering::Relaxed);
+                state = block(|| {
+                    queue
+                        .not_full
+                        .wait_while(state, |state| state.commands.len() >= queue.capacity && !state.closed)
+                        .unwrap()
+                });
+                if state.closed {
+                    return Err(writer_stopped());
+                }
+            }
+        }
+
+        state.commands.push_back(command);
+        drop(state);
+        queue.not_empty.notify_one();
+        Ok(())
+    }
+}
+
+impl Drop for PipelinedSink {
+    /// Lets the writer finish what is queued and exit without closing the
+    /// wrapped sink, as when the sink is dropped without `close`
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+/// Run `wait` on a multi-thread runtime's worker without stalling the tasks
+/// queued on it. A current-thread runtime cannot hand off its worker, but the
+/// writer runs on its own thread so waiting there still makes progress.
+fn block<T>(wait: impl FnOnce() -> T) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(wait)
+        }
+        _ => wait(),
+    }
+}
+
+fn writer_stopped() -> Error {
+    Error::Stopped("Output writer has stopped".to_string())
+}
+
+fn writer_loop(inner: Box<dyn OutputSink>, queue: Arc<Queue>, counters: Arc<Counters>) {
+    // Producers get an error instead of waiting on a writer that is gone,
+    // including one that panicked in the wrapped sink
+    struct CloseOnExit(Arc<Queue>);
+    impl Drop for CloseOnExit {
+        fn drop(&mut self) {
+            self.0.close();
+        }
+    }
+    let _close = CloseOnExit(queue.clone());
+
+    loop {
+        let command = {
+            let mut state = queue
+                .not_empty
+                .wait_while(queue.state.lock().unwrap(), |state| state.commands.is_empty() && !state.closed)
+                .unwrap();
+            let Some(command) = state.commands.pop_front() else {
+                break;
+            };
+            command
+        };
+        queue.not_full.notify_one();
+
+        match command {
+            WriterCommand::Data(data) => match inner.write_streaming_data(&data) {
+                Ok(()) => {
+                    counters.written.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => tracing::error!(error = %e, "Output write failed"),
+            },
+            WriterCommand::Line(line) => match inner.writeln(&line) {
+                Ok(()) => {
+                    counters.written.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => tracing::error!(error = %e, "Output write failed"),
+            },
+            WriterCommand::Flush => {
+                if let Err(e) = inner.flush() {
+                    tracing::error!(error = %e, "Output flush failed");
+                }
+            }
+            WriterCommand::Rotate => {
+                if let Err(e) = inner.rotate() {
+                    tracing::error!(error = %e, "Output rotation failed");
+                }
+            }
+            WriterCommand::Close(done) => {
+                // Later writes fail instead of queueing behind a writer that is gone
+                queue.close();
+                let _ = done.send(inner.close());
+                break;
+            }
+        }
+    }
+}
+
+impl OutputSink for PipelinedSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> Result<()> {
+        self.enqueue(WriterCommand::Data(data.clone()))
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.enqueue(WriterCommand::Flush)
+    }
+
+    fn rotate(&self) -> Result<()> {
+        self.enqueue(WriterCommand::Rotate)
+    }
+
+    /// Drains the queue and closes the wrapped sink
+    fn close(&self) -> Result<()> {
+        let (done_tx, done_rx) = std_mpsc::channel();
+        self.enqueue(WriterCommand::Close(done_tx))?;
+        let result = done_rx.recv().map_err(|_| writer_stopped())?;
+
+        let stats = self.stats();
+        if stats.dropped > 0 {
+            tracing::warn!(written = stats.written, dropped = stats.dropped, "Output writer closed with dropped messages");
+        } else {
+            tracing::debug!(written = stats.written, blocked = stats.blocked, "Output writer closed");
+        }
+        result
+    }
+
+    fn writeln(&self, message: &str) -> Result<()> {
+        self.enqueue(WriterCommand::Line(message.to_string()))
+    }
+}
@@ -0,0 +1,446 @@
+//! Excel workbooks, written a sheet at a time: a zip of SpreadsheetML parts
+//! with typed number and date cells, for `--xlsx`.
+
+use crate::market_time::eastern_offset;
+use crate::resample::OhlcvBar;
+use crate::{Error, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::write::DeflateEncoder;
+use flate2::Crc;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Rows a sheet holds, the header included; later rows continue on a new
+/// sheet
+pub const MAX_SHEET_ROWS: u32 = 1_048_576;
+
+/// Header of the bar sheets `bar_cells` fills; times are in New York
+pub const BAR_COLUMNS: [&str; 8] = [
+    "time",
+    "open",
+    "high",
+    "low",
+    "close",
+    "volume",
+    "trade_count",
+    "vwap",
+];
+
+/// Excel's limit on sheet name length
+const MAX_SHEET_NAME: usize = 31;
+
+/// Style of date cells in `styles.xml`
+const DATE_STYLE: u32 = 1;
+
+/// The value of a cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    /// Shown in New York time, since Excel has no time zones
+    Time(DateTime<Utc>),
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<f64> for Cell {
+    fn from(value: f64) -> Self {
+        Cell::Number(value)
+    }
+}
+
+impl From<u64> for Cell {
+    fn from(value: u64) -> Self {
+        Cell::Number(value as f64)
+    }
+}
+
+/// A finished zip entry, for the central directory
+struct Entry {
+    name: String,
+    crc: u32,
+    compressed: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// The sheet being written, compressed in memory until it is finished
+struct Sheet {
+    name: String,
+    columns: Vec<String>,
+    xml: DeflateEncoder<Vec<u8>>,
+    crc: Crc,
+    rows: u32,
+}
+
+impl Sheet {
+    fn write(&mut self, xml: &str) -> Result<()> {
+        self.xml.write_all(xml.as_bytes())?;
+        self.crc.update(xml.as_bytes());
+        Ok(())
+    }
+}
+
+/// Writes a workbook to a file. Each sheet starts with a header row; rows
+/// after `MAX_SHEET_ROWS` go on a sheet named like the first with ` (2)`.
+pub struct XlsxWriter {
+    file: BufWriter<File>,
+    offset: u64,
+    entries: Vec<Entry>,
+    sheets: Vec<String>,
+    current: Option<Sheet>,
+}
+
+impl XlsxWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            offset: 0,
+            entries: Vec::new(),
+            sheets: Vec::new(),
+            current: None,
+        })
+    }
+
+    /// Finish the current sheet and start one named `name`, made valid and
+    /// unique, with a header row of `columns`
+    pub fn start_sheet(&mut self, name: &str, columns: &[&str]) -> Result<()> {
+        self.finish_sheet()?;
+        let name = self.sheet_name(name);
+        let mut sheet = Sheet {
+            name,
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            xml: DeflateEncoder::new(Vec::new(), flate2::Compression::default()),
+            crc: Crc::new(),
+            rows: 0,
+        };
+        sheet.write(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">"#,
+            r#"<sheetViews><sheetView workbookViewId="0"><pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/></sheetView></sheetViews>"#,
+            r#"<sheetData>"#
+        ))?;
+        self.current = Some(sheet);
+        let header: Vec<Cell> = columns.iter().map(|column| Cell::from(*column)).collect();
+        self.write_row(&header)
+    }
+
+    /// Add a row to the current sheet
+    pub fn write_row(&mut self, cells: &[Cell]) -> Result<()> {
+        let full = match &self.current {
+            Some(sheet) => sheet.rows == MAX_SHEET_ROWS,
+            None => return Err(Error::Invalid("No sheet started".to_string())),
+        };
+        if full {
+            let sheet = self.current.as_ref().expect("checked above");
+            let (name, columns) = (sheet.name.clone(), sheet.columns.clone());
+            let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+            self.start_sheet(&name, &columns)?;
+        }
+        let sheet = self.current.as_mut().expect("checked above");
+        sheet.rows += 1;
+        let row = sheet.rows;
+        let mut xml = format!(r#"<row r="{}">"#, row);
+        for (index, cell) in cells.iter().enumerate() {
+            let reference = format!("{}{}", column_name(index), row);
+            match cell {
+                Cell::Text(text) => xml.push_str(&format!(
+                    r#"<c r="{}" t="inlineStr"><is><t>{}</t></is></c>"#,
+                    reference,
+                    escape(text)
+                )),
+                Cell::Number(number) if number.is_finite() => {
+                    xml.push_str(&format!(r#"<c r="{}"><v>{}</v></c>"#, reference, number))
+                }
+                Cell::Number(_) => {}
+                Cell::Time(time) => xml.push_str(&format!(
+                    r#"<c r="{}" s="{}"><v>{}</v></c>"#,
+                    reference,
+                    DATE_STYLE,
+                    excel_time(*time)
+                )),
+            }
+        }
+        xml.push_str("</row>");
+        sheet.write(&xml)
+    }
+
+    /// Finish the last sheet and write the workbook parts around the sheets
+    pub fn finish(mut self) -> Result<()> {
+        self.finish_sheet()?;
+        if self.sheets.is_empty() {
+            self.start_sheet("Sheet1", &[])?;
+            self.finish_sheet()?;
+        }
+
+        let sheets: String = (1..=self.sheets.len())
+            .map(|id| {
+                format!(
+                    r#"<Override PartName="/xl/worksheets/sheet{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+                    id
+                )
+            })
+            .collect();
+        self.write_entry(
+            "[Content_Types].xml",
+            &format!(
+                concat!(
+                    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                    r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+                    r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+                    r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+                    r#"<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>"#,
+                    r#"<Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>"#,
+                    "{}</Types>"
+                ),
+                sheets
+            ),
+        )?;
+        self.write_entry(
+            "_rels/.rels",
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+                r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/>"#,
+                "</Relationships>"
+            ),
+        )?;
+
+        let names: String = self
+            .sheets
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                format!(
+                    r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#,
+                    escape(name),
+                    index + 1,
+                    index + 1
+                )
+            })
+            .collect();
+        self.write_entry(
+            "xl/workbook.xml",
+            &format!(
+                concat!(
+                    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                    r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">"#,
+                    "<sheets>{}</sheets></workbook>"
+                ),
+                names
+            ),
+        )?;
+        let relationships: String = (1..=self.sheets.len())
+            .map(|id| {
+                format!(
+                    r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{}.xml"/>"#,
+                    id, id
+                )
+            })
+            .collect();
+        self.write_entry(
+            "xl/_rels/workbook.xml.rels",
+            &format!(
+                concat!(
+                    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+                    "{}",
+                    r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>"#,
+                    "</Relationships>"
+                ),
+                relationships,
+                self.sheets.len() + 1
+            ),
+        )?;
+        self.write_entry(
+            "xl/styles.xml",
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">"#,
+                r#"<numFmts count="1"><numFmt numFmtId="164" formatCode="yyyy-mm-dd hh:mm:ss"/></numFmts>"#,
+                r#"<fonts count="1"><font><sz val="11"/><name val="Calibri"/></font></fonts>"#,
+                r#"<fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills>"#,
+                r#"<borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders>"#,
+                r#"<cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs>"#,
+                r#"<cellXfs count="2"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/>"#,
+                r#"<xf numFmtId="164" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/></cellXfs>"#,
+                "</styleSheet>"
+            ),
+        )?;
+
+        self.write_central_directory()?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    fn finish_sheet(&mut self) -> Result<()> {
+        let Some(mut sheet) = self.current.take() else {
+            return Ok(());
+        };
+        sheet.write("</sheetData></worksheet>")?;
+        let crc = sheet.crc.sum();
+        let size = sheet.crc.amount();
+        let compressed = sheet.xml.finish()?;
+        self.sheets.push(sheet.name);
+        let name = format!("xl/worksheets/sheet{}.xml", self.sheets.len());
+        self.write_compressed(name, crc, size, &compressed)
+    }
+
+    fn write_entry(&mut self, name: &str, xml: &str) -> Result<()> {
+        let mut crc = Crc::new();
+        crc.update(xml.as_bytes());
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(xml.as_bytes())?;
+        let compressed = encoder.finish()?;
+        self.write_compressed(name.to_string(), crc.sum(), crc.amount(), &compressed)
+    }
+
+    /// Write a deflated zip entry with its local header
+    fn write_compressed(&mut self, name: String, crc: u32, size: u32, data: &[u8]) -> Result<()> {
+        let too_large = || Error::Invalid("Workbook is larger than 4 GB".to_string());
+        let entry = Entry {
+            crc,
+            compressed: u32::try_from(data.len()).map_err(|_| too_large())?,
+            size,
+            offset: u32::try_from(self.offset).map_err(|_| too_large())?,
+            name,
+        };
+        let mut header = Vec::with_capacity(30 + entry.name.len());
+        header.extend(0x04034b50u32.to_le_bytes());
+        header.extend(entry.header_fields());
+        header.extend(entry.name.as_bytes());
+        self.file.write_all(&header)?;
+        self.file.write_all(data)?;
+        self.offset += (header.len() + data.len()) as u64;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn write_central_directory(&mut self) -> Result<()> {
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend(0x02014b50u32.to_le_bytes());
+            // Version made by
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(entry.header_fields());
+            // Comment length, disk, internal and external attributes
+            directory.extend([0u8; 10]);
+            directory.extend(entry.offset.to_le_bytes());
+            directory.extend(entry.name.as_bytes());
+        }
+        let count = self.entries.len() as u16;
+        let mut end = Vec::with_capacity(22);
+        end.extend(0x06054b50u32.to_le_bytes());
+        end.extend([0u8; 4]);
+        end.extend(count.to_le_bytes());
+        end.extend(count.to_le_bytes());
+        end.extend((directory.len() as u32).to_le_bytes());
+        end.extend((self.offset as u32).to_le_bytes());
+        end.extend(0u16.to_le_bytes());
+        self.file.write_all(&directory)?;
+        self.file.write_all(&end)?;
+        Ok(())
+    }
+
+    /// `name` without the characters Excel rejects, cut to its length limit
+    /// and numbered if it is taken
+    fn sheet_name(&self, name: &str) -> String {
+        let clean: String = name
+            .chars()
+            .map(|c| if "[]:*?/\\".contains(c) { '-' } else { c })
+            .collect();
+        let clean = if clean.trim().is_empty() {
+            "Sheet".to_string()
+        } else {
+            clean
+        };
+        let taken: HashSet<String> = self.sheets.iter().map(|s| s.to_lowercase()).collect();
+        let mut number = 1;
+        loop {
+            let suffix = if number == 1 {
+                String::new()
+            } else {
+                format!(" ({})", number)
+            };
+            let base: String = clean.chars().take(MAX_SHEET_NAME - suffix.len()).collect();
+            let candidate = format!("{}{}", base, suffix);
+            if !taken.contains(&candidate.to_lowercase()) {
+                return candidate;
+            }
+            number += 1;
+        }
+    }
+}
+
+impl Entry {
+    /// The fields local and central headers share, from the version needed
+    /// to the extra field length
+    fn header_fields(&self) -> Vec<u8> {
+        let mut fields = Vec::with_capacity(26);
+        // Version needed, flags (UTF-8 names), deflate
+        fields.extend(20u16.to_le_bytes());
+        fields.extend(0x0800u16.to_le_bytes());
+        fields.extend(8u16.to_le_bytes());
+        // Modified at 1980-01-01 00:00
+        fields.extend(0u16.to_le_bytes());
+        fields.extend(0x0021u16.to_le_bytes());
+        fields.extend(self.crc.to_le_bytes());
+        fields.extend(self.compressed.to_le_bytes());
+        fields.extend(self.size.to_le_bytes());
+        fields.extend((self.name.len() as u16).to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+        fields
+    }
+}
+
+/// `bar` as a row under `BAR_COLUMNS`; the symbol is the sheet's name
+pub fn bar_cells(bar: &OhlcvBar) -> Vec<Cell> {
+    vec![
+        Cell::Time(bar.timestamp),
+        bar.open.into(),
+        bar.high.into(),
+        bar.low.into(),
+        bar.close.into(),
+        bar.volume.into(),
+        bar.trade_count.into(),
+        bar.vwap.into(),
+    ]
+}
+
+/// The letters of the column at `index`: A, B, ... Z, AA, ...
+fn column_name(index: usize) -> String {
+    let mut index = index + 1;
+    let mut name = Vec::new();
+    while index > 0 {
+        let letter = (index - 1) % 26;
+        name.push(b'A' + letter as u8);
+        index = (index - 1) / 26;
+    }
+    name.reverse();
+    String::from_utf8(name).expect("ASCII letters")
+}
+
+/// `time` in New York as Excel's serial date: days since 1899-12-30
+pub fn excel_time(time: DateTime<Utc>) -> f64 {
+    let local = time
+        .with_timezone(&eastern_offset(time.date_naive()))
+        .naive_local();
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    (local - epoch).num_milliseconds() as f64 / 86_400_000.0
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
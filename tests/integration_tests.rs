@@ -254,4 +254,77 @@ fn test_cli_all_feed_options() {
         let stderr = String::from_utf8(output.stderr).unwrap();
         assert!(!stderr.contains("Invalid feed"));
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_trade_cli_subcommands() {
+    let output = Command::new("cargo")
+        .args(["run", "--bin", "trade", "--", "--help"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for command in &["stream", "historical", "analyze", "credentials"] {
+        assert!(stdout.contains(command), "missing {}", command);
+    }
+    assert!(stdout.contains("--profile <NAME>"));
+    assert!(stdout.contains("--log-level <LEVEL>"));
+
+    // Global flags go before or after the subcommand, and the subcommand validates like the old binary
+    let output = Command::new("cargo")
+        .args([
+            "run", "--bin", "trade", "--",
+            "--log-level", "warn",
+            "historical",
+            "--symbols", "AAPL",
+            "--start", "2024-01-15",
+            "--end", "2024-01-10",
+            "--log-format", "json"
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Start date must be before end date"));
+}
+
+#[test]
+fn test_analyzer_report_formats() {
+    let temp_dir = tempdir().unwrap();
+    let capture = temp_dir.path().join("capture.jsonl");
+    std::fs::write(&capture, concat!(
+        r#"{"received_time":"2024-01-02T14:30:06Z","event_time":"2024-01-02T14:30:05Z","message_type":"t","symbol":"AAPL","data":{"p":100.0,"s":100}}"#, "\n",
+        r#"{"received_time":"2024-01-02T14:30:08Z","event_time":"2024-01-02T14:30:07Z","message_type":"q","symbol":"AAPL","data":{"bp":100.0,"ap":100.02}}"#, "\n",
+        r#"{"received_time":"2024-01-02T14:30:09Z","event_time":"2024-01-02T14:30:08Z","message_type":"t","symbol":"MSFT","data":{"p":370.0,"s":50}}"#, "\n",
+    )).unwrap();
+
+    // JSON on the console is the only thing on stdout
+    let output = Command::new("cargo")
+        .args(["run", "--bin", "data-analyzer", "--", "--input", capture.to_str().unwrap(), "--report-format", "json"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["messages"], 3);
+    assert_eq!(report["trades"], 2);
+    assert_eq!(report["symbols"][0]["symbol"], "AAPL");
+    assert_eq!(report["symbols"][0]["quotes"], 1);
+    assert_eq!(report["trade_analytics"][1]["vwap"], 370.0);
+    assert_eq!(report["spreads"][0]["symbol"], "AAPL");
+
+    // CSV to a file, with the format taken from the extension
+    let csv = temp_dir.path().join("report.csv");
+    let output = Command::new("cargo")
+        .args(["run", "--bin", "data-analyzer", "--", "--input", capture.to_str().unwrap(), "--report", csv.to_str().unwrap()])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("Data Analysis Summary"));
+    let lines: Vec<String> = std::fs::read_to_string(&csv).unwrap().lines().map(str::to_string).collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("symbol,messages,trades,quotes,bars,first,last,trade_volume,vwap"));
+    assert_eq!(lines[1], ",3,2,1,0,,,,,,,,,,,");
+    assert!(lines[2].starts_with("AAPL,2,1,1,0,2024-01-02T14:30:05Z,2024-01-02T14:30:07Z,100,100.0000,"));
+}
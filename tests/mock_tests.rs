@@ -1,8 +1,16 @@
-use algorithms_trading::{DataFormat, OutputMode};
-use alpaca_trading_api_rust::{Bar, StockBarsResponse};
+use algorithms_trading::conflation::{parse_duration, parse_interval, QuoteConflation, QuoteConflator};
+use algorithms_trading::rotation::parse_size;
+use algorithms_trading::trade_updates::trade_updates_url;
+use algorithms_trading::{
+    feed_name, parse_feed, process_filtered_message, AlertEngine, AlertRules, process_streaming_message, subscription_channel, chunk_subscriptions, BackpressurePolicy, CaptureBuffer, Compression, DataFormat, LatencyMonitor,
+    MessageFilter, OutputMode, OutputSink, PipelinedSink, RotateInterval, RotatingFileSink, RotationPolicy, SessionStats, StreamingConfig,
+    StreamingData, StructuredCsvSink, SubscriptionCommand, SymbolSessionStats, TradeUpdate,
+};
+use alpaca_trading_api_rust::{Bar, StockBarsResponse, StreamingDataType, StreamingFeed, StreamingMessage};
 use std::collections::HashMap;
 use tempfile::tempdir;
 use std::fs;
+use std::sync::Mutex;
 
 // Mock data creation helpers
 fn create_mock_bar(_symbol: &str, timestamp: &str, open: f64, close: f64) -> Bar {
@@ -244,4 +252,4320 @@ fn test_page_size_bounds() {
     for size in invalid_sizes {
         assert!(size == 0 || size > 10000);
     }
-}
\ No newline at end of file
+}
+fn create_mock_trade_update(event: &str) -> StreamingMessage {
+    StreamingMessage {
+        message_type: "trade_update".to_string(),
+        message: None,
+        data: serde_json::json!({
+            "event": event,
+            "execution_id": "exec-1",
+            "timestamp": "2024-01-15T14:30:00Z",
+            "price": "150.25",
+            "qty": "5",
+            "position_qty": "5",
+            "order": {
+                "id": "order-1",
+                "client_order_id": "client-1",
+                "symbol": "AAPL",
+                "side": "buy",
+                "type": "limit",
+                "qty": "10",
+                "filled_qty": "5",
+                "filled_avg_price": "150.25",
+                "limit_price": "150.50",
+                "status": "partially_filled"
+            }
+        }),
+    }
+}
+
+#[test]
+fn test_trade_update_parsing() {
+    let message = create_mock_trade_update("partial_fill");
+    let update: TradeUpdate = serde_json::from_value(message.data).unwrap();
+
+    assert_eq!(update.event, "partial_fill");
+    assert_eq!(update.price.as_deref(), Some("150.25"));
+    assert_eq!(update.order.symbol, "AAPL");
+    assert_eq!(update.order.order_type.as_deref(), Some("limit"));
+    assert_eq!(update.order.filled_qty.as_deref(), Some("5"));
+}
+
+#[test]
+fn test_trade_update_written_as_json() {
+    let temp_dir = tempdir().unwrap();
+    let file_path = temp_dir.path().join("orders.json");
+    let output_mode = OutputMode::create_file_mode(&file_path, DataFormat::Json, false).unwrap();
+
+    process_streaming_message(&create_mock_trade_update("fill"), &output_mode).unwrap();
+
+    let content = fs::read_to_string(&file_path).unwrap();
+    let data: StreamingData = serde_json::from_str(content.trim()).unwrap();
+    assert_eq!(data.message_type, "trade_update");
+    assert_eq!(data.symbol.as_deref(), Some("AAPL"));
+    assert_eq!(data.data["event"], "fill");
+}
+
+#[test]
+fn test_trade_update_plain_formatting() {
+    let temp_dir = tempdir().unwrap();
+    let file_path = temp_dir.path().join("orders.txt");
+    let output_mode = OutputMode::create_file_mode(&file_path, DataFormat::Plain, false).unwrap();
+
+    process_streaming_message(&create_mock_trade_update("partial_fill"), &output_mode).unwrap();
+    process_streaming_message(&create_mock_trade_update("canceled"), &output_mode).unwrap();
+
+    let content = fs::read_to_string(&file_path).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines[0], "🧩 Partial fill: AAPL buy 5 @ $150.25 (filled 5/10, order order-1)");
+    assert_eq!(lines[1], "🚫 Canceled: AAPL buy 10 (filled 5, order order-1)");
+}
+
+#[test]
+fn test_trade_updates_url() {
+    assert_eq!(trade_updates_url("https://paper-api.alpaca.markets"), "wss://paper-api.alpaca.markets/stream");
+    assert_eq!(trade_updates_url("https://api.alpaca.markets/"), "wss://api.alpaca.markets/stream");
+    assert_eq!(trade_updates_url("https://paper-api.alpaca.markets/v2"), "wss://paper-api.alpaca.markets/stream");
+}
+
+#[test]
+fn test_subscription_command_parsing() {
+    let command: SubscriptionCommand = "subscribe trades aapl,msft".parse().unwrap();
+    assert_eq!(command, SubscriptionCommand::Subscribe(StreamingDataType::Trades, vec!["AAPL".to_string(), "MSFT".to_string()]));
+
+    let command: SubscriptionCommand = "unsub quotes NVDA TSLA".parse().unwrap();
+    assert_eq!(command, SubscriptionCommand::Unsubscribe(StreamingDataType::Quotes, vec!["NVDA".to_string(), "TSLA".to_string()]));
+}
+
+#[test]
+fn test_daily_and_updated_bars() {
+    let command: SubscriptionCommand = "sub daily_bars spy".parse().unwrap();
+    assert_eq!(command, SubscriptionCommand::Subscribe(StreamingDataType::DailyBars, vec!["SPY".to_string()]));
+
+    let sink = CollectingSink::default();
+    for message_type in ["b", "d", "u"] {
+        process_streaming_message(&serde_json::from_value::<StreamingMessage>(serde_json::json!({
+            "T": message_type, "S": "SPY", "o": 500.0, "h": 505.0, "l": 499.0, "c": 504.0, "v": 1000, "t": "2024-05-03T04:00:00Z"
+        })).unwrap(), &sink).unwrap();
+    }
+
+    let records = sink.records.lock().unwrap();
+    let types: Vec<&str> = records.iter().map(|r| r.message_type.as_str()).collect();
+    assert_eq!(types, ["b", "d", "u"]);
+    let formatter = OutputMode::create_console_mode(DataFormat::Plain);
+    assert!(formatter.format_plain(&records[0]).starts_with("📈 Bar: SPY - O: $500.00"));
+    assert!(formatter.format_plain(&records[1]).starts_with("📅 Daily bar: SPY - O: $500.00"));
+    assert!(formatter.format_plain(&records[2]).starts_with("🔄 Updated bar: SPY"));
+    let filter: MessageFilter = "type==daily_bars".parse().unwrap();
+    assert_eq!(records.iter().filter(|r| filter.matches(r)).count(), 1);
+}
+
+#[test]
+fn test_subscription_chunking_and_sharding() {
+    let subscriptions = HashMap::from([
+        (StreamingDataType::Trades, vec!["AAPL".to_string(), "MSFT".to_string(), "NVDA".to_string()]),
+        (StreamingDataType::Quotes, vec!["AAPL".to_string(), "SPY".to_string()]),
+        (StreamingDataType::Bars, vec![]),
+    ]);
+    let chunks = chunk_subscriptions(&subscriptions, 2);
+    let sizes: Vec<usize> = chunks.iter().map(|chunk| chunk.values().map(Vec::len).sum()).collect();
+    assert_eq!(sizes, [2, 2, 1]);
+    // Channels are chunked in name order, so quotes come first
+    assert_eq!(chunks[0][&StreamingDataType::Quotes], ["AAPL", "SPY"]);
+    assert_eq!(chunks[1][&StreamingDataType::Trades], ["AAPL", "MSFT"]);
+    assert_eq!(chunks[2][&StreamingDataType::Trades], ["NVDA"]);
+    assert_eq!(chunk_subscriptions(&subscriptions, 4)[0].len(), 2);
+
+    let mut config = StreamingConfig::with_sinks(vec![]);
+    config.trade_symbols = vec!["AAPL".to_string(), "MSFT".to_string(), "NVDA".to_string()];
+    config.quote_symbols = vec!["AAPL".to_string(), "SPY".to_string()];
+    config.bar_symbols = vec![];
+    config.trade_updates = true;
+    assert_eq!(config.connection_configs().len(), 1);
+    config.max_symbols_per_connection = Some(2);
+    let connections = config.connection_configs();
+    assert_eq!(connections.len(), 2);
+    assert_eq!((connections[0].trade_symbols.clone(), connections[0].quote_symbols.clone()), (vec!["AAPL".to_string(), "MSFT".to_string()], vec!["AAPL".to_string()]));
+    assert_eq!((connections[1].trade_symbols.clone(), connections[1].quote_symbols.clone()), (vec!["NVDA".to_string()], vec!["SPY".to_string()]));
+    assert!(connections[0].trade_updates && !connections[1].trade_updates);
+    assert_eq!((connections[0].shard, connections[1].shard), (Some(0), Some(1)));
+
+    // Shards split evenly; the per-connection limit can add more
+    config.max_symbols_per_connection = None;
+    config.shards = 3;
+    let sizes: Vec<usize> = config.connection_configs().iter().map(|c| c.all_symbols().len()).collect();
+    assert_eq!(sizes, [2, 1, 1]);
+    config.shards = 10;
+    assert_eq!(config.connection_configs().len(), 4);
+    config.shards = 1;
+    config.max_symbols_per_connection = Some(3);
+    let sizes: Vec<usize> = config.connection_configs().iter().map(|c| c.all_symbols().len()).collect();
+    assert_eq!(sizes, [2, 2]);
+    assert!(config.shard_metrics.snapshot().is_empty());
+}
+
+#[test]
+fn test_feed_names_and_failover_format() {
+    for name in ["iex", "sip", "delayed_sip"] {
+        assert_eq!(feed_name(&parse_feed(name).unwrap()), name);
+    }
+    assert_eq!(parse_feed(" SIP ").unwrap(), StreamingFeed::Sip);
+    assert!(parse_feed("otc").unwrap_err().to_string().contains("Invalid feed"));
+
+    let data = StreamingData {
+        received_time: chrono::Utc::now(),
+        event_time: None,
+        message_type: "failover".to_string(),
+        symbol: None,
+        data: serde_json::json!({"from": "sip", "to": "iex", "reason": "2 failed sessions, last: No messages for 60s"}),
+    };
+    let line = OutputMode::create_console_mode(DataFormat::Plain).format_plain(&data);
+    assert_eq!(line, "🔀 Feed failover: sip -> iex (2 failed sessions, last: No messages for 60s)");
+}
+
+#[test]
+fn test_retry_policy() {
+    use algorithms_trading::{ConfigFile, RetryPolicy};
+    use std::time::Duration;
+
+    let policy = RetryPolicy::default();
+    let delays: Vec<u64> = (1..=8).map(|attempt| policy.delay(attempt).as_secs()).collect();
+    assert_eq!(delays, [2, 4, 8, 16, 32, 64, 64, 64]);
+    assert!(!policy.exhausted(4) && policy.exhausted(5));
+
+    let config: ConfigFile = r#"
+        [retry]
+        max_retries = "infinite"
+        backoff_base = "500ms"
+        backoff_max = "3s"
+        jitter = 0.5
+        reset_after = "5m"
+    "#.parse().unwrap();
+    let policy = config.retry;
+    assert!(!policy.exhausted(u32::MAX));
+    assert_eq!((policy.base_delay(1), policy.base_delay(3), policy.base_delay(40)), (Duration::from_millis(500), Duration::from_secs(2), Duration::from_secs(3)));
+    assert_eq!(policy.reset_after, Duration::from_secs(300));
+    for _ in 0..100 {
+        let delay = policy.delay(2);
+        assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1500), "{:?}", delay);
+    }
+
+    // Unset keys keep their defaults
+    let config: ConfigFile = "[retry]\nmax_retries = 10".parse().unwrap();
+    assert_eq!(config.retry, RetryPolicy { max_retries: Some(10), ..RetryPolicy::default() });
+    assert_eq!("".parse::<ConfigFile>().unwrap(), ConfigFile::default());
+    assert!("[retry]\nmax_retries = \"lots\"".parse::<ConfigFile>().unwrap_err().to_string().contains("Invalid max retries"));
+    assert!("[retry]\nbackof_base = \"1s\"".parse::<ConfigFile>().is_err());
+
+    let mut disconnect = create_mock_streaming_data("disconnect", "", serde_json::json!({
+        "attempt": 12, "max_retries": null, "error": "reset", "retry_in_secs": 3
+    }));
+    disconnect.symbol = None;
+    let line = OutputMode::create_console_mode(DataFormat::Plain).format_plain(&disconnect);
+    assert_eq!(line, "⚠️  Disconnected (attempt 12): reset - retrying in 3s");
+}
+
+#[test]
+fn test_config_symbol_lists_and_reload_changes() {
+    use algorithms_trading::{ConfigFile, ConfigWatcher};
+
+    let mut config = StreamingConfig::new(Box::new(CollectingSink::default()));
+    config.trade_symbols = vec!["AAPL".to_string(), "MSFT".to_string()];
+    config.quote_symbols = vec!["AAPL".to_string()];
+    config.bar_symbols.clear();
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(&path, "").unwrap();
+    let mut watcher = ConfigWatcher::new(&path, &config);
+
+    let file: ConfigFile = "[symbols]\ntrades = [\" aapl\", \"NVDA\"]\nbars = [\"SPY\"]".parse().unwrap();
+    file.symbols.apply(&mut config);
+    assert_eq!(config.trade_symbols, ["AAPL", "NVDA"]);
+    assert_eq!(config.quote_symbols, ["AAPL"]);
+    assert_eq!(config.bar_symbols, ["SPY"]);
+
+    // Removals go out before additions; channels the file leaves out are untouched
+    let changes = watcher.changes(&file);
+    assert_eq!(changes, [
+        SubscriptionCommand::Unsubscribe(StreamingDataType::Trades, vec!["MSFT".to_string()]),
+        SubscriptionCommand::Subscribe(StreamingDataType::Trades, vec!["NVDA".to_string()]),
+        SubscriptionCommand::Subscribe(StreamingDataType::Bars, vec!["SPY".to_string()]),
+    ]);
+    assert!(watcher.changes(&file).is_empty());
+
+    let file: ConfigFile = "[symbols]\ntrades = []".parse().unwrap();
+    assert_eq!(watcher.changes(&file), [SubscriptionCommand::Unsubscribe(StreamingDataType::Trades, vec!["AAPL".to_string(), "NVDA".to_string()])]);
+    assert!("[symbols]\noptions = [\"SPY\"]".parse::<ConfigFile>().is_err());
+}
+
+#[tokio::test]
+async fn test_config_watcher_applies_edits() {
+    use algorithms_trading::{subscription_channel, ConfigWatcher};
+    use tokio::time::{timeout, Duration};
+
+    let mut config = StreamingConfig::new(Box::new(CollectingSink::default()));
+    config.trade_symbols = vec!["AAPL".to_string()];
+    config.quote_symbols.clear();
+    config.bar_symbols.clear();
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(&path, "[symbols]\ntrades = [\"AAPL\"]").unwrap();
+    let (handle, mut commands) = subscription_channel();
+    tokio::spawn(ConfigWatcher::new(&path, &config).run(handle, Duration::from_millis(100)));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // An edit in place and one that replaces the file, as editors do, both reach the session
+    fs::write(&path, "[symbols]\ntrades = [\"AAPL\", \"MSFT\"]").unwrap();
+    let replacement = dir.path().join("config.toml.tmp");
+    fs::write(&replacement, "[symbols]\ntrades = [\"MSFT\", \"NVDA\"]").unwrap();
+    fs::rename(&replacement, &path).unwrap();
+    let mut trades = vec!["AAPL".to_string()];
+    while trades != ["MSFT", "NVDA"] {
+        match timeout(Duration::from_secs(5), commands.recv()).await.expect("config edits applied").unwrap() {
+            SubscriptionCommand::Subscribe(StreamingDataType::Trades, symbols) => trades.extend(symbols),
+            SubscriptionCommand::Unsubscribe(StreamingDataType::Trades, symbols) => trades.retain(|symbol| !symbols.contains(symbol)),
+            command => panic!("unexpected {:?}", command),
+        }
+        trades.sort();
+    }
+}
+
+#[test]
+fn test_profiles() {
+    use algorithms_trading::ConfigFile;
+
+    let config: ConfigFile = r#"
+        [profiles.live]
+        key_id = "AKLIVE"
+        secret_key = "live-secret"
+        base_url = "https://api.alpaca.markets"
+        feed = "sip"
+
+        [profiles.paper]
+        key_id = "PKPAPER"
+    "#.parse().unwrap();
+    assert_eq!(config.profiles["live"].vars(), [
+        ("APCA_API_KEY_ID", "AKLIVE".to_string()),
+        ("APCA_API_SECRET_KEY", "live-secret".to_string()),
+        ("APCA_API_BASE_URL", "https://api.alpaca.markets".to_string()),
+        ("ALPACA_FEED", "sip".to_string()),
+    ]);
+    assert_eq!(config.profiles["paper"].vars(), [("APCA_API_KEY_ID", "PKPAPER".to_string())]);
+    assert!("[profiles.live]\napi_key = \"x\"".parse::<ConfigFile>().is_err());
+
+    // A .env.<name> file alone is enough, and overrides variables already set
+    let dir = tempdir().unwrap();
+    std::env::set_var("ALGO_TEST_PROFILE_VAR", "before");
+    fs::write(dir.path().join(".env.staging"), "ALGO_TEST_PROFILE_VAR=staging\n").unwrap();
+    let vars = ConfigFile::default().activate_profile("staging", dir.path()).unwrap();
+    assert_eq!(vars, ["ALGO_TEST_PROFILE_VAR"]);
+    assert_eq!(std::env::var("ALGO_TEST_PROFILE_VAR").unwrap(), "staging");
+
+    let error = config.activate_profile("prod", dir.path()).unwrap_err().to_string();
+    assert!(error.starts_with("Unknown profile: prod"), "{}", error);
+    assert!(config.activate_profile("../live", dir.path()).unwrap_err().to_string().starts_with("Invalid profile"));
+}
+
+#[test]
+fn test_keychain_names() {
+    use algorithms_trading::keychain::{parse_credential, Keychain};
+
+    assert_eq!(parse_credential("key-id").unwrap(), "APCA_API_KEY_ID");
+    assert_eq!(parse_credential("SECRET_KEY").unwrap(), "APCA_API_SECRET_KEY");
+    assert_eq!(parse_credential("APCA_API_SECRET_KEY").unwrap(), "APCA_API_SECRET_KEY");
+    assert_eq!(parse_credential("password").unwrap_err().to_string(), "Invalid credential: password. Supported: key-id, secret-key");
+
+    assert_eq!(Keychain::new(None).account("APCA_API_KEY_ID"), "default:APCA_API_KEY_ID");
+    assert_eq!(Keychain::new(Some("live")).account("APCA_API_SECRET_KEY"), "live:APCA_API_SECRET_KEY");
+}
+
+#[test]
+fn test_stale_connection_hours_and_format() {
+    use algorithms_trading::market_time::in_extended_hours;
+    use chrono::TimeZone;
+
+    let utc = |month: u32, day: u32, hour: u32, minute: u32| chrono::Utc.with_ymd_and_hms(2024, month, day, hour, minute, 0).unwrap();
+    // Wednesday 2024-07-03, EDT: 04:00-20:00 New York is 08:00-24:00 UTC
+    assert!(!in_extended_hours(utc(7, 3, 7, 59)));
+    assert!(in_extended_hours(utc(7, 3, 8, 0)));
+    assert!(in_extended_hours(utc(7, 3, 23, 59)));
+    assert!(!in_extended_hours(utc(7, 4, 0, 0)));
+    // Tuesday 2024-01-02, EST: 09:00 UTC is 04:00 New York
+    assert!(!in_extended_hours(utc(1, 2, 8, 59)));
+    assert!(in_extended_hours(utc(1, 2, 9, 0)));
+    // Saturday
+    assert!(!in_extended_hours(utc(7, 6, 15, 0)));
+
+    let mut stale = create_mock_streaming_data("stale_connection", "", serde_json::json!({"silent_secs": 61, "timeout_secs": 60}));
+    stale.symbol = None;
+    let line = OutputMode::create_console_mode(DataFormat::Plain).format_plain(&stale);
+    assert_eq!(line, "🥶 Stale connection: no messages for 61s - reconnecting");
+    let console = algorithms_trading::ConsoleOptions::new(true, false);
+    assert_eq!(console.render(line, &stale), "\x1b[33mStale connection: no messages for 61s - reconnecting\x1b[0m");
+}
+
+#[test]
+fn test_subscription_command_parsing_invalid() {
+    assert!("subscribe trades".parse::<SubscriptionCommand>().is_err());
+    assert!("subscribe news AAPL".parse::<SubscriptionCommand>().unwrap_err().to_string().contains("Invalid channel"));
+    assert!("pause bars SPY".parse::<SubscriptionCommand>().unwrap_err().to_string().contains("Invalid action"));
+}
+
+#[tokio::test]
+async fn test_subscription_handle_sends_commands() {
+    let (handle, mut receiver) = subscription_channel();
+    handle.subscribe(StreamingDataType::Bars, vec!["SPY".to_string()]).unwrap();
+
+    let command = receiver.recv().await.unwrap();
+    assert_eq!(command, SubscriptionCommand::Subscribe(StreamingDataType::Bars, vec!["SPY".to_string()]));
+
+    drop(receiver);
+    assert!(handle.unsubscribe(StreamingDataType::Bars, vec!["SPY".to_string()]).is_err());
+}
+
+#[test]
+fn test_config_and_output_builders() {
+    use algorithms_trading::Error;
+
+    let config = StreamingConfig::builder()
+        .feed(StreamingFeed::Sip)
+        .backup_feed(StreamingFeed::Iex)
+        .trades(["aapl", " MSFT "])
+        .trades(vec!["AAPL".to_string()])
+        .bars(["SPY"])
+        .shards(2)
+        .build()
+        .unwrap();
+    assert_eq!(config.feed, StreamingFeed::Sip);
+    assert_eq!(config.trade_symbols, vec!["AAPL", "MSFT"]);
+    assert_eq!(config.bar_symbols, vec!["SPY"]);
+    assert!(config.quote_symbols.is_empty());
+    assert!(!config.trade_updates);
+    assert_eq!(config.output_mode.len(), 1);
+
+    // Nothing comes from the environment
+    assert!(matches!(StreamingConfig::builder().build(), Err(Error::Invalid(_))));
+    assert!(StreamingConfig::builder().trade_updates(true).build().is_ok());
+    assert!(StreamingConfig::builder().quotes(["BRK B"]).build().is_err());
+    assert!(StreamingConfig::builder().quotes(["AAPL"]).shards(0).build().is_err());
+    let err = StreamingConfig::builder().trades(["AAPL"]).backup_feed(StreamingFeed::Iex).build().unwrap_err();
+    assert_eq!(err.to_string(), "Invalid backup feed: iex. It must differ from the primary feed");
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("capture.jsonl");
+    let sink = OutputMode::builder().format(DataFormat::Json).file(&path).build().unwrap();
+    let config = StreamingConfig::builder().trades(["AAPL"]).sink(sink).build().unwrap();
+    config.output_mode.write_streaming_data(&create_mock_streaming_data("t", "AAPL", serde_json::json!({"p": 150.0}))).unwrap();
+    config.output_mode.close().unwrap();
+    let line = fs::read_to_string(&path).unwrap();
+    assert!(serde_json::from_str::<StreamingData>(line.trim()).is_ok());
+
+    let rotating = OutputMode::builder().file(dir.path().join("rotated.jsonl")).rotate_daily().build().unwrap();
+    assert!(format!("{:?}", rotating).contains("RotatingFileSink"));
+    assert!(OutputMode::builder().rotate_daily().build().is_err());
+    assert!(OutputMode::builder().compression(Compression::Gzip).build().is_err());
+    assert!(OutputMode::builder().file(dir.path().join("a.csv")).max_file_size(0).build().is_err());
+    assert!(OutputMode::builder()
+        .file(dir.path().join("b.jsonl.zst"))
+        .compression(Compression::Zstd)
+        .rotate_hourly()
+        .compress_rotated()
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_library_error_variants() {
+    use algorithms_trading::Error;
+
+    let err = parse_feed("opra").unwrap_err();
+    assert!(matches!(err, Error::Invalid(_)));
+    assert_eq!(err.to_string(), "Invalid feed: opra. Supported: iex, sip, delayed_sip");
+    assert!(matches!(OutputMode::from_spec("xml:out.xml", false), Err(Error::Invalid(_))));
+    assert!(matches!("pause bars SPY".parse::<SubscriptionCommand>(), Err(Error::Invalid(_))));
+
+    let (handle, receiver) = subscription_channel();
+    drop(receiver);
+    let err = handle.subscribe(StreamingDataType::Trades, vec!["AAPL".to_string()]).unwrap_err();
+    assert!(matches!(err, Error::Stopped(_)));
+
+    let dir = tempdir().unwrap();
+    let missing = dir.path().join("missing").join("out.jsonl");
+    assert!(matches!(OutputMode::create_file_mode(&missing, DataFormat::Json, false), Err(Error::Io(_))));
+
+    // Alpaca client errors are wrapped; errors of this crate passed back
+    // through the client keep their variant
+    let client = Error::alpaca(anyhow::anyhow!("client failed"));
+    assert!(matches!(client, Error::Alpaca(_)));
+    assert_eq!(client.to_string(), "client failed");
+    let returned = Error::alpaca(Error::Stopped("gone".to_string()).into());
+    assert!(matches!(returned, Error::Stopped(_)));
+
+    // Callers using anyhow convert with `?`
+    let wrapped: anyhow::Error = Error::Invalid("bad".to_string()).into();
+    assert_eq!(wrapped.to_string(), "bad");
+
+    // The grouping modules and the crate root name the same types
+    let _: algorithms_trading::stats::SessionStats = algorithms_trading::SessionStats::default();
+    let _: algorithms_trading::output::CaptureBuffer = algorithms_trading::CaptureBuffer::new(1);
+}
+
+#[derive(Debug, Default)]
+struct CollectingSink {
+    records: Mutex<Vec<StreamingData>>,
+    flushed: Mutex<bool>,
+}
+
+impl OutputSink for CollectingSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> algorithms_trading::Result<()> {
+        self.records.lock().unwrap().push(data.clone());
+        Ok(())
+    }
+
+    fn flush(&self) -> algorithms_trading::Result<()> {
+        *self.flushed.lock().unwrap() = true;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_custom_output_sink() {
+    let sink = CollectingSink::default();
+
+    process_streaming_message(&create_mock_trade_update("fill"), &sink).unwrap();
+    assert!(sink.writeln("status lines are ignored by default").is_ok());
+    sink.close().unwrap();
+
+    let records = sink.records.lock().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].message_type, "trade_update");
+    assert!(*sink.flushed.lock().unwrap());
+}
+
+#[test]
+fn test_streaming_data_event_and_received_time() {
+    let sink = CollectingSink::default();
+    let trade: StreamingMessage = serde_json::from_value(serde_json::json!({
+        "T": "t", "S": "AAPL", "i": 1, "x": "V", "p": 150.0, "s": 100, "t": "2024-05-03T14:30:00.123456Z"
+    })).unwrap();
+    process_streaming_message(&trade, &sink).unwrap();
+    process_streaming_message(&create_mock_trade_update("fill"), &sink).unwrap();
+
+    let records = sink.records.lock().unwrap();
+    let event_time = records[0].event_time.unwrap();
+    assert_eq!(event_time, "2024-05-03T14:30:00.123456Z".parse::<chrono::DateTime<chrono::Utc>>().unwrap());
+    assert!(records[0].received_time > event_time);
+    assert_eq!(records[1].event_time.unwrap().to_rfc3339(), "2024-01-15T14:30:00+00:00");
+
+    // Both times are kept in JSON, and older captures with only `timestamp` still load
+    let json = serde_json::to_value(&records[0]).unwrap();
+    assert!(json["event_time"].is_string() && json["received_time"].is_string());
+    let old: StreamingData = serde_json::from_str(r#"{"timestamp":"2024-05-03T14:30:01Z","message_type":"t","symbol":"AAPL","data":{}}"#).unwrap();
+    assert_eq!(old.received_time.to_rfc3339(), "2024-05-03T14:30:01+00:00");
+    assert!(old.event_time.is_none());
+}
+
+#[test]
+fn test_latency_monitor_percentiles() {
+    let monitor = LatencyMonitor::new(100);
+    let sink: Box<dyn OutputSink> = Box::new(monitor.clone());
+    let event_time: chrono::DateTime<chrono::Utc> = "2024-05-03T14:30:00Z".parse().unwrap();
+
+    // 1..=200 ms for AAPL; only the last 100 (101..=200 ms) stay in the window
+    for ms in 1..=200 {
+        let mut data = create_mock_bar_data("AAPL", "2024-05-03T14:30:00Z", 1.0);
+        data.event_time = Some(event_time);
+        data.received_time = event_time + chrono::Duration::milliseconds(ms);
+        sink.write_streaming_data(&data).unwrap();
+    }
+    let mut quote = create_mock_streaming_data("q", "MSFT", serde_json::json!({}));
+    quote.event_time = Some(event_time);
+    quote.received_time = event_time + chrono::Duration::microseconds(2500);
+    sink.write_streaming_data(&quote).unwrap();
+    // No event time or not market data: ignored
+    sink.write_streaming_data(&create_mock_streaming_data("t", "TSLA", serde_json::json!({}))).unwrap();
+    let mut update = create_mock_streaming_data("trade_update", "NVDA", serde_json::json!({}));
+    update.event_time = Some(event_time);
+    sink.write_streaming_data(&update).unwrap();
+
+    let stats = monitor.snapshot();
+    assert_eq!(stats.iter().map(|s| s.symbol.as_str()).collect::<Vec<_>>(), ["AAPL", "MSFT"]);
+    assert_eq!(stats[0].samples, 100);
+    assert_eq!((stats[0].p50_ms, stats[0].p95_ms, stats[0].p99_ms, stats[0].max_ms), (150.0, 195.0, 199.0, 200.0));
+    assert_eq!((stats[1].samples, stats[1].p99_ms), (1, 2.5));
+}
+
+#[test]
+fn test_session_stats_messages() {
+    let stats = SessionStats::new();
+    for (symbol, price, size) in [("AAPL", 100.0, 10), ("AAPL", 110.0, 30), ("AAPL", 95.0, 10), ("MSFT", 300.0, 5)] {
+        stats.write_streaming_data(&create_mock_streaming_data("t", symbol, serde_json::json!({
+            "T": "t", "S": symbol, "i": 1, "x": "V", "p": price, "s": size, "t": "2024-05-03T14:30:00Z"
+        }))).unwrap();
+    }
+    stats.write_streaming_data(&create_mock_bar_data("AAPL", "2024-05-03T14:30:00Z", 500.0)).unwrap();
+
+    let temp_dir = tempdir().unwrap();
+    let plain_path = temp_dir.path().join("stats.txt");
+    let sink = CollectingSink::default();
+    let outputs: Vec<std::sync::Arc<dyn OutputSink>> = vec![
+        std::sync::Arc::new(OutputMode::create_file_mode(&plain_path, DataFormat::Plain, false).unwrap()),
+    ];
+    stats.emit(&outputs, true).unwrap();
+    stats.emit(&sink, false).unwrap();
+
+    let records = sink.records.lock().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].message_type, "stats");
+    let aapl: SymbolSessionStats = serde_json::from_value(records[0].data.clone()).unwrap();
+    assert_eq!((aapl.trades, aapl.volume, aapl.high, aapl.low, aapl.last), (3, 50, 110.0, 95.0, 95.0));
+    assert_eq!(aapl.vwap, 105.0);
+    assert!(!aapl.is_final);
+
+    let plain = fs::read_to_string(&plain_path).unwrap();
+    assert!(plain.starts_with("📊 Session summary: AAPL - VWAP: $105.00 | Vol: 50 | Trades: 3 | H: $110.00 L: $95.00"));
+    assert!(plain.lines().nth(1).unwrap().contains("MSFT"));
+}
+
+#[test]
+fn test_trade_corrections_and_cancels() {
+    let stats = SessionStats::new().with_corrections(true);
+    let sink = std::sync::Arc::new(CollectingSink::default());
+    let temp_dir = tempdir().unwrap();
+    let csv_sink = StructuredCsvSink::create(temp_dir.path(), false).unwrap();
+    let outputs: Vec<std::sync::Arc<dyn OutputSink>> = vec![std::sync::Arc::new(stats.clone()), sink.clone(), std::sync::Arc::new(csv_sink)];
+    for message in [
+        serde_json::json!({"T": "t", "S": "AAPL", "i": 1, "x": "V", "p": 100.0, "s": 10, "t": "2024-05-03T14:30:00Z"}),
+        serde_json::json!({"T": "t", "S": "AAPL", "i": 2, "x": "V", "p": 120.0, "s": 10, "t": "2024-05-03T14:30:01Z"}),
+        serde_json::json!({"T": "t", "S": "AAPL", "i": 3, "x": "V", "p": 90.0, "s": 20, "t": "2024-05-03T14:30:02Z"}),
+        serde_json::json!({"T": "c", "S": "AAPL", "x": "V", "oi": 2, "op": 120.0, "os": 10, "oc": ["@"],
+            "ci": 4, "cp": 105.0, "cs": 10, "cc": ["@"], "z": "C", "t": "2024-05-03T14:30:03Z"}),
+        serde_json::json!({"T": "x", "S": "AAPL", "i": 3, "x": "V", "p": 90.0, "s": 20, "a": "C", "z": "C", "t": "2024-05-03T14:30:04Z"}),
+        serde_json::json!({"T": "x", "S": "AAPL", "i": 9, "x": "V", "p": 50.0, "s": 5, "a": "E", "z": "C", "t": "2024-05-03T14:30:05Z"}),
+    ] {
+        process_streaming_message(&serde_json::from_value::<StreamingMessage>(message).unwrap(), &outputs).unwrap();
+    }
+
+    // The unseen trade at 50.00 is ignored
+    let aapl = &stats.snapshot()[0];
+    assert_eq!((aapl.trades, aapl.volume, aapl.high, aapl.low), (2, 20, 105.0, 100.0));
+    assert_eq!(aapl.vwap, 102.5);
+
+    let records = sink.records.lock().unwrap();
+    assert_eq!(records[3].message_type, "c");
+    assert_eq!(records[3].symbol.as_deref(), Some("AAPL"));
+    assert!(records[3].event_time.is_some());
+    let formatter = OutputMode::create_console_mode(DataFormat::Plain);
+    assert!(formatter.format_plain(&records[3]).starts_with("✏️  Correction: AAPL - $120.00 x 10 -> $105.00 x 10"));
+    assert!(formatter.format_plain(&records[4]).starts_with("🚫 Trade cancel: AAPL - $90.00 x 20"));
+    assert!(formatter.format_plain(&records[5]).starts_with("🚫 Trade error: AAPL - $50.00 x 5"));
+    assert!("type==correction".parse::<MessageFilter>().unwrap().matches(&records[3]));
+
+    let corrections = fs::read_to_string(temp_dir.path().join("corrections.csv")).unwrap();
+    let lines: Vec<&str> = corrections.lines().collect();
+    assert_eq!(lines[0], "received_at,symbol,timestamp,kind,id,exchange,original_price,original_size,corrected_price,corrected_size");
+    assert!(lines[1].ends_with(",AAPL,2024-05-03T14:30:03Z,correction,2,V,120.0,10,105.0,10"));
+    assert!(lines[3].ends_with(",error,9,V,50.0,5,,"));
+}
+
+fn create_mock_quote_data(symbol: &str, bid: f64, ask: f64) -> StreamingData {
+    create_mock_streaming_data("q", symbol, serde_json::json!({
+        "T": "q", "S": symbol, "bp": bid, "bs": 1, "ap": ask, "as": 1, "t": "2024-05-03T14:30:00Z"
+    }))
+}
+
+#[test]
+fn test_quote_conflation_on_change() {
+    let sink = std::sync::Arc::new(CollectingSink::default());
+    let conflator = QuoteConflator::new(Box::new(vec![sink.clone() as std::sync::Arc<dyn OutputSink>]), QuoteConflation::OnChange);
+
+    for (symbol, bid, ask) in [("AAPL", 1.0, 2.0), ("AAPL", 1.0, 2.0), ("MSFT", 1.0, 2.0), ("AAPL", 1.5, 2.0), ("AAPL", 1.0, 2.0)] {
+        conflator.write_streaming_data(&create_mock_quote_data(symbol, bid, ask)).unwrap();
+    }
+    conflator.write_streaming_data(&create_mock_bar_data("AAPL", "2024-05-03T14:30:00Z", 1.0)).unwrap();
+
+    let records = sink.records.lock().unwrap();
+    let written: Vec<(String, f64)> = records.iter()
+        .map(|r| (r.message_type.clone(), r.data["bp"].as_f64().unwrap_or_default()))
+        .collect();
+    assert_eq!(written, [("q".to_string(), 1.0), ("q".to_string(), 1.0), ("q".to_string(), 1.5), ("q".to_string(), 1.0), ("b".to_string(), 0.0)]);
+    assert_eq!(conflator.dropped(), 1);
+}
+
+#[test]
+fn test_alert_engine_rules() {
+    let rules: AlertRules = r#"
+        [[rule]]
+        symbol = "AAPL"
+        condition = "price_cross"
+        threshold = 200.0
+
+        [[rule]]
+        name = "wide spread"
+        symbol = "*"
+        condition = "spread_above"
+        threshold = 0.5
+        cooldown = "1h"
+    "#.parse().unwrap();
+    assert!("[[rule]]\nsymbol = \"AAPL\"\ncondition = \"price_jump\"\nthreshold = 1.0".parse::<AlertRules>().is_err());
+
+    let sink = std::sync::Arc::new(CollectingSink::default());
+    let engine = AlertEngine::new(rules.rules, Box::new(vec![sink.clone() as std::sync::Arc<dyn OutputSink>]));
+    for price in [199.0, 201.0, 202.0, 198.0] {
+        engine.write_streaming_data(&create_mock_streaming_data("t", "AAPL", serde_json::json!({
+            "T": "t", "S": "AAPL", "p": price, "s": 100, "t": "2024-05-03T14:30:00Z", "x": "V", "i": 1
+        }))).unwrap();
+    }
+    // The cooldown suppresses the second wide spread
+    for ask in [1.5, 2.0, 1.2, 2.0] {
+        engine.write_streaming_data(&create_mock_quote_data("MSFT", 1.0, ask)).unwrap();
+    }
+
+    let records = sink.records.lock().unwrap();
+    let alerts: Vec<&str> = records.iter()
+        .filter(|r| r.message_type == "alert")
+        .map(|r| r.data["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(alerts, [
+        "AAPL price crossed above 200.00 (201.00)",
+        "AAPL price crossed below 200.00 (198.00)",
+        "MSFT spread 1.00 above 0.50",
+    ]);
+    assert_eq!(records.len(), 11);
+}
+
+fn create_mock_status_data(symbol: &str, code: &str) -> StreamingData {
+    let message = if code == "H" { "Trading Halt" } else { "Trading Resumption" };
+    create_mock_streaming_data("s", symbol, serde_json::json!({
+        "T": "s", "S": symbol, "sc": code, "sm": message, "rc": "T12", "rm": "Additional Information Requested",
+        "t": "2024-05-03T14:30:00Z", "z": "C"
+    }))
+}
+
+#[test]
+fn test_trading_status_halts() {
+    let sink = CollectingSink::default();
+    process_streaming_message(&serde_json::from_value::<StreamingMessage>(serde_json::json!({
+        "T": "s", "S": "AAPL", "sc": "H", "sm": "Trading Halt", "rc": "T12", "rm": "Additional Information Requested",
+        "t": "2024-05-03T14:30:00Z", "z": "C"
+    })).unwrap(), &sink).unwrap();
+    let halt = sink.records.lock().unwrap()[0].clone();
+    assert_eq!((halt.message_type.as_str(), halt.symbol.as_deref()), ("s", Some("AAPL")));
+    assert!(halt.event_time.is_some());
+
+    let formatter = OutputMode::create_console_mode(DataFormat::Plain);
+    assert!(formatter.format_plain(&halt).starts_with("🛑 HALTED: AAPL - Trading Halt (T12: Additional Information Requested) @ 2024-05-03T14:30:00Z"));
+    assert!(formatter.format_plain(&create_mock_status_data("AAPL", "T")).starts_with("✅ RESUMED: AAPL - Trading Resumption"));
+    let colored = algorithms_trading::ConsoleOptions::new(true, true).render("halt".to_string(), &halt);
+    assert_eq!(colored, "\x1b[31mhalt\x1b[0m");
+
+    let rules: AlertRules = "[[rule]]\nsymbol = \"*\"\ncondition = \"halted\"".parse().unwrap();
+    let engine = AlertEngine::new(rules.rules, Box::new(CollectingSink::default()));
+    let alerts = engine.evaluate(&halt);
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].message, "AAPL halted: Trading Halt (T12: Additional Information Requested)");
+    assert!(engine.evaluate(&create_mock_status_data("AAPL", "T")).is_empty());
+}
+
+#[test]
+fn test_luld_and_imbalance_messages() {
+    let temp_dir = tempdir().unwrap();
+    let sink = std::sync::Arc::new(CollectingSink::default());
+    let outputs: Vec<std::sync::Arc<dyn OutputSink>> = vec![sink.clone(), std::sync::Arc::new(StructuredCsvSink::create(temp_dir.path(), false).unwrap())];
+    for message in [
+        serde_json::json!({"T": "l", "S": "AAPL", "u": 190.1, "d": 171.9, "i": "B", "t": "2024-05-03T14:30:00Z", "z": "C"}),
+        serde_json::json!({"T": "i", "S": "AAPL", "p": 180.5, "t": "2024-05-03T19:50:00Z", "z": "C"}),
+    ] {
+        process_streaming_message(&serde_json::from_value::<StreamingMessage>(message).unwrap(), &outputs).unwrap();
+    }
+    outputs.flush().unwrap();
+
+    let records = sink.records.lock().unwrap();
+    let types: Vec<&str> = records.iter().map(|r| r.message_type.as_str()).collect();
+    assert_eq!(types, ["l", "i"]);
+    let formatter = OutputMode::create_console_mode(DataFormat::Plain);
+    assert!(formatter.format_plain(&records[0]).starts_with("🚦 LULD: AAPL - Up: $190.10 | Down: $171.90 (Indicator: B) @ 2024-05-03T14:30:00Z"));
+    assert!(formatter.format_plain(&records[1]).starts_with("⚖️  Imbalance: AAPL - $180.50 @ 2024-05-03T19:50:00Z"));
+    assert!("type==imbalance".parse::<MessageFilter>().unwrap().matches(&records[1]));
+
+    let lulds = fs::read_to_string(temp_dir.path().join("lulds.csv")).unwrap();
+    assert_eq!(lulds.lines().next().unwrap(), "received_at,symbol,timestamp,limit_up,limit_down,indicator");
+    assert!(lulds.lines().nth(1).unwrap().ends_with(",AAPL,2024-05-03T14:30:00Z,190.1,171.9,B"));
+    let imbalances = fs::read_to_string(temp_dir.path().join("imbalances.csv")).unwrap();
+    assert!(imbalances.lines().nth(1).unwrap().ends_with(",AAPL,2024-05-03T19:50:00Z,180.5"));
+}
+
+#[test]
+fn test_quote_conflation_sampling() {
+    let sink = std::sync::Arc::new(CollectingSink::default());
+    let conflator = QuoteConflator::new(
+        Box::new(vec![sink.clone() as std::sync::Arc<dyn OutputSink>]),
+        QuoteConflation::Sample(std::time::Duration::from_millis(250)),
+    );
+
+    for bid in [1.0, 2.0, 3.0] {
+        conflator.write_streaming_data(&create_mock_quote_data("MSFT", bid, 10.0)).unwrap();
+        conflator.write_streaming_data(&create_mock_quote_data("AAPL", bid, 10.0)).unwrap();
+    }
+    // Other messages are not held back
+    conflator.write_streaming_data(&create_mock_bar_data("AAPL", "2024-05-03T14:30:00Z", 1.0)).unwrap();
+    assert_eq!(sink.records.lock().unwrap().len(), 1);
+
+    conflator.flush_pending().unwrap();
+    conflator.write_streaming_data(&create_mock_quote_data("AAPL", 4.0, 10.0)).unwrap();
+    conflator.close().unwrap();
+
+    let records = sink.records.lock().unwrap();
+    let quotes: Vec<(&str, f64)> = records.iter()
+        .filter(|r| r.message_type == "q")
+        .map(|r| (r.symbol.as_deref().unwrap(), r.data["bp"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(quotes, [("AAPL", 3.0), ("MSFT", 3.0), ("AAPL", 4.0)]);
+    assert_eq!(conflator.dropped(), 4);
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("250ms").unwrap(), std::time::Duration::from_millis(250));
+    assert_eq!(parse_duration("1.5s").unwrap(), std::time::Duration::from_millis(1500));
+    assert_eq!(parse_duration("5m").unwrap(), std::time::Duration::from_secs(300));
+    assert!(parse_duration("250").unwrap_err().to_string().contains("Invalid duration"));
+    assert!(parse_duration("fast").is_err());
+    assert_eq!(parse_duration("0s").unwrap(), std::time::Duration::ZERO);
+    assert!(parse_duration("1e400s").unwrap_err().to_string().contains("Invalid duration"));
+    assert!(parse_duration("-1s").is_err());
+
+    assert_eq!(parse_interval("250ms").unwrap(), std::time::Duration::from_millis(250));
+    assert!(parse_interval("0ms").unwrap_err().to_string().contains("longer than zero"));
+}
+
+#[test]
+fn test_message_filter_expressions() {
+    let trade = |symbol: &str, price: f64, size: u64| -> StreamingMessage {
+        serde_json::from_value(serde_json::json!({
+            "T": "t", "S": symbol, "i": 1, "x": "V", "p": price, "s": size, "c": ["@", "I"], "t": "2024-05-03T14:30:00Z"
+        })).unwrap()
+    };
+    let filter: MessageFilter = "type==t && price>100 && size>=500".parse().unwrap();
+    let sink = CollectingSink::default();
+    for message in [trade("AAPL", 150.0, 500), trade("AAPL", 150.0, 100), trade("F", 12.0, 1000), create_mock_trade_update("fill")] {
+        process_filtered_message(&message, Some(&filter), &sink).unwrap();
+    }
+    assert_eq!(sink.records.lock().unwrap().len(), 1);
+
+    let decoded = |message: StreamingMessage| {
+        let sink = CollectingSink::default();
+        process_streaming_message(&message, &sink).unwrap();
+        let record = sink.records.lock().unwrap().remove(0);
+        record
+    };
+    let aapl = decoded(trade("AAPL", 150.0, 500));
+    let matches = |expr: &str| expr.parse::<MessageFilter>().unwrap().matches(&aapl);
+    assert!(matches("symbol==aapl && (type==trade || type==quote)"));
+    assert!(matches("!(exchange==Q) && conditions==I && c!=Z"));
+    assert!(!matches("symbol == 'MSFT' || spread > 0"));
+    assert!(matches("price <= 150 && price >= 150.0 && p != 151"));
+
+    let update = decoded(create_mock_trade_update("fill"));
+    assert!("type==trade_update && price>150 && event==fill".parse::<MessageFilter>().unwrap().matches(&update));
+
+    for invalid in ["price>", "price 100", "(type==t", "type==t &&", "symbol=='AAPL", "type==t)"] {
+        let error = invalid.parse::<MessageFilter>().unwrap_err().to_string();
+        assert!(error.starts_with("Invalid filter"), "{}: {}", invalid, error);
+    }
+}
+
+#[test]
+fn test_streaming_config_accepts_boxed_sink() {
+    let config = StreamingConfig::new(Box::new(CollectingSink::default()));
+    assert!(config.output_mode.write_streaming_data(&StreamingData {
+        received_time: chrono::Utc::now(),
+        event_time: None,
+        message_type: "success".to_string(),
+        symbol: None,
+        data: serde_json::Value::String("authenticated".to_string()),
+    }).is_ok());
+}
+
+#[test]
+fn test_output_mode_from_spec() {
+    let temp_dir = tempdir().unwrap();
+    let file_path = temp_dir.path().join("spec.jsonl");
+
+    let file_mode = OutputMode::from_spec(&format!("json:{}", file_path.display()), false).unwrap();
+    assert!(matches!(file_mode, OutputMode::File { format: DataFormat::Json, .. }));
+    assert!(file_path.exists());
+
+    assert!(matches!(OutputMode::from_spec("plain", false).unwrap(), OutputMode::Console { format: DataFormat::Plain, .. }));
+    assert!(matches!(OutputMode::from_spec("CSV:-", false).unwrap(), OutputMode::Console { format: DataFormat::Csv, .. }));
+    assert!(OutputMode::from_spec("xml:out.xml", false).unwrap_err().to_string().contains("Invalid output format"));
+}
+
+#[test]
+fn test_multiple_output_sinks() {
+    let temp_dir = tempdir().unwrap();
+    let json_path = temp_dir.path().join("tee.jsonl");
+    let plain_path = temp_dir.path().join("tee.txt");
+
+    let mut config = StreamingConfig::new(Box::new(OutputMode::create_file_mode(&json_path, DataFormat::Json, false).unwrap()));
+    config.add_sink(Box::new(OutputMode::create_file_mode(&plain_path, DataFormat::Plain, false).unwrap()));
+    config.add_sink(Box::new(CollectingSink::default()));
+    assert_eq!(config.output_mode.len(), 3);
+
+    process_streaming_message(&create_mock_trade_update("fill"), &config.output_mode).unwrap();
+    config.output_mode.close().unwrap();
+
+    let json: StreamingData = serde_json::from_str(fs::read_to_string(&json_path).unwrap().trim()).unwrap();
+    assert_eq!(json.message_type, "trade_update");
+    assert!(fs::read_to_string(&plain_path).unwrap().starts_with("✅ Fill: AAPL buy 5 @ $150.25"));
+}
+
+fn create_mock_streaming_data(message_type: &str, symbol: &str, data: serde_json::Value) -> StreamingData {
+    StreamingData {
+        received_time: chrono::Utc::now(),
+        event_time: None,
+        message_type: message_type.to_string(),
+        symbol: Some(symbol.to_string()),
+        data,
+    }
+}
+
+#[test]
+fn test_structured_csv_sink() {
+    let temp_dir = tempdir().unwrap();
+    let sink = StructuredCsvSink::create(temp_dir.path(), false).unwrap();
+
+    sink.write_streaming_data(&create_mock_streaming_data("t", "AAPL", serde_json::json!({
+        "T": "t", "S": "AAPL", "i": 52983525029461_u64, "x": "V", "p": 150.25, "s": 100, "t": "2024-01-15T14:30:00.123Z"
+    }))).unwrap();
+    sink.write_streaming_data(&create_mock_streaming_data("q", "MSFT", serde_json::json!({
+        "T": "q", "S": "MSFT", "bp": 300.10, "bs": 2, "ap": 300.15, "as": 3, "t": "2024-01-15T14:30:00.456Z"
+    }))).unwrap();
+    sink.write_streaming_data(&create_mock_streaming_data("b", "SPY", serde_json::json!({
+        "T": "b", "S": "SPY", "o": 470.0, "h": 471.5, "l": 469.8, "c": 471.0, "v": 120000, "t": "2024-01-15T14:30:00Z"
+    }))).unwrap();
+    sink.close().unwrap();
+
+    let mut trades = csv::Reader::from_path(temp_dir.path().join("trades.csv")).unwrap();
+    assert_eq!(trades.headers().unwrap(), vec!["received_at", "symbol", "timestamp", "price", "size", "exchange", "id"]);
+    let trade = trades.records().next().unwrap().unwrap();
+    assert_eq!(&trade[1], "AAPL");
+    assert_eq!(&trade[3], "150.25");
+    assert_eq!(&trade[4], "100");
+
+    let mut quotes = csv::Reader::from_path(temp_dir.path().join("quotes.csv")).unwrap();
+    assert_eq!(&quotes.headers().unwrap()[3], "bid_price");
+    let quote = quotes.records().next().unwrap().unwrap();
+    assert_eq!(&quote[1], "MSFT");
+    assert_eq!(&quote[5], "300.15");
+
+    let mut bars = csv::Reader::from_path(temp_dir.path().join("bars.csv")).unwrap();
+    let bar = bars.records().next().unwrap().unwrap();
+    assert_eq!(&bar[1], "SPY");
+    assert_eq!(&bar[7], "120000");
+}
+
+#[test]
+fn test_structured_csv_sink_daily_and_updated_bars() {
+    let temp_dir = tempdir().unwrap();
+    let sink = StructuredCsvSink::create(temp_dir.path(), false).unwrap();
+
+    sink.write_streaming_data(&create_mock_streaming_data("d", "SPY", serde_json::json!({
+        "T": "d", "S": "SPY", "o": 470.0, "h": 475.0, "l": 468.0, "c": 474.0, "v": 5000000, "t": "2024-01-15T05:00:00Z"
+    }))).unwrap();
+    sink.write_streaming_data(&create_mock_streaming_data("u", "SPY", serde_json::json!({
+        "T": "u", "S": "SPY", "o": 470.0, "h": 471.5, "l": 469.8, "c": 471.2, "v": 121000, "t": "2024-01-15T14:30:00Z"
+    }))).unwrap();
+    sink.close().unwrap();
+
+    for (name, close) in [("daily_bars.csv", "474.0"), ("updated_bars.csv", "471.2")] {
+        let mut bars = csv::Reader::from_path(temp_dir.path().join(name)).unwrap();
+        assert_eq!(&bars.headers().unwrap()[6], "close");
+        let rows: Vec<_> = bars.records().map(|row| row.unwrap()).collect();
+        assert_eq!(rows.len(), 1, "{} should hold one bar", name);
+        assert_eq!(&rows[0][1], "SPY");
+        assert_eq!(&rows[0][6], close);
+    }
+    // bars.csv keeps only minute bars
+    assert!(fs::read_to_string(temp_dir.path().join("bars.csv")).unwrap().is_empty());
+}
+
+#[test]
+fn test_structured_csv_sink_append_skips_header() {
+    let temp_dir = tempdir().unwrap();
+    let trade = serde_json::json!({
+        "T": "t", "S": "AAPL", "i": 1, "x": "V", "p": 150.0, "s": 10, "t": "2024-01-15T14:30:00Z"
+    });
+
+    for append in [false, true] {
+        let sink = StructuredCsvSink::create(temp_dir.path(), append).unwrap();
+        sink.write_streaming_data(&create_mock_streaming_data("t", "AAPL", trade.clone())).unwrap();
+    }
+
+    let content = fs::read_to_string(temp_dir.path().join("trades.csv")).unwrap();
+    assert_eq!(content.lines().count(), 3); // header + 2 rows
+    assert_eq!(content.matches("received_at").count(), 1);
+}
+
+#[cfg(feature = "nats")]
+#[test]
+fn test_nats_subjects() {
+    use algorithms_trading::nats_sink::nats_subject;
+
+    let trade = create_mock_streaming_data("t", "AAPL", serde_json::json!({}));
+    assert_eq!(nats_subject("md", &trade), "md.AAPL.trades");
+
+    let bar = create_mock_streaming_data("b", "BRK.B", serde_json::json!({}));
+    assert_eq!(nats_subject("md", &bar), "md.BRK_B.bars");
+
+    let mut status = create_mock_streaming_data("success", "", serde_json::json!("authenticated"));
+    status.symbol = None;
+    assert_eq!(nats_subject("md", &status), "md.success");
+}
+
+fn create_mock_bar_data(symbol: &str, timestamp: &str, close: f64) -> StreamingData {
+    create_mock_streaming_data("b", symbol, serde_json::json!({
+        "T": "b", "S": symbol, "o": close - 1.0, "h": close + 1.0, "l": close - 2.0, "c": close, "v": 1000, "t": timestamp
+    }))
+}
+
+#[test]
+fn test_capture_buffer_bars_and_latest() {
+    let buffer = CaptureBuffer::new(2);
+    buffer.write_streaming_data(&create_mock_bar_data("AAPL", "2024-01-15T14:30:00Z", 150.0)).unwrap();
+    buffer.write_streaming_data(&create_mock_bar_data("AAPL", "2024-01-15T14:31:00Z", 151.0)).unwrap();
+    buffer.write_streaming_data(&create_mock_bar_data("AAPL", "2024-01-15T14:32:00Z", 152.0)).unwrap();
+    buffer.write_streaming_data(&create_mock_streaming_data("t", "MSFT", serde_json::json!({
+        "T": "t", "S": "MSFT", "i": 1, "x": "V", "p": 300.0, "s": 10, "t": "2024-01-15T14:32:01Z"
+    }))).unwrap();
+
+    assert_eq!(buffer.symbols(), vec!["AAPL", "MSFT"]);
+
+    // Oldest bar was evicted by the per-symbol limit
+    let bars = buffer.bars("aapl", None, None);
+    assert_eq!(bars.len(), 2);
+    assert_eq!(bars[0].close, 151.0);
+
+    let from = "2024-01-15T14:32:00Z".parse().unwrap();
+    let bars = buffer.bars("AAPL", Some(from), None);
+    assert_eq!(bars.len(), 1);
+    assert_eq!(bars[0].close, 152.0);
+
+    let latest = buffer.latest("MSFT").unwrap();
+    assert!(latest.latest_trade.is_some());
+    assert!(latest.latest_bar.is_none());
+    assert!(buffer.latest("NVDA").is_none());
+}
+
+#[cfg(feature = "http")]
+async fn http_get(addr: std::net::SocketAddr, path: &str) -> (u16, serde_json::Value) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[cfg(feature = "http")]
+#[tokio::test]
+async fn test_http_query_server() {
+    let buffer = CaptureBuffer::new(100);
+    buffer.write_streaming_data(&create_mock_bar_data("SPY", "2024-01-15T14:30:00Z", 470.0)).unwrap();
+    buffer.write_streaming_data(&create_mock_bar_data("SPY", "2024-01-16T14:30:00Z", 472.0)).unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = algorithms_trading::http_server::router(buffer);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let (status, body) = http_get(addr, "/bars/SPY?from=2024-01-16").await;
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["close"], 472.0);
+
+    let (status, body) = http_get(addr, "/bars/SPY?to=2024-01-15").await;
+    assert_eq!(status, 200);
+    assert_eq!(body[0]["close"], 470.0);
+
+    let (status, body) = http_get(addr, "/latest/SPY").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["latest_bar"]["data"]["c"], 472.0);
+
+    let (status, _) = http_get(addr, "/latest/NVDA").await;
+    assert_eq!(status, 404);
+
+    let (status, body) = http_get(addr, "/bars/SPY?from=yesterday").await;
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().contains("Invalid time"));
+}
+
+#[cfg(feature = "grpc")]
+#[tokio::test]
+async fn test_grpc_subscribe_filters_events() {
+    use algorithms_trading::grpc_server::proto::market_data_client::MarketDataClient;
+    use algorithms_trading::grpc_server::proto::market_data_event::Event;
+    use algorithms_trading::grpc_server::proto::SubscribeRequest;
+    use algorithms_trading::grpc_server::GrpcBroadcastSink;
+
+    let sink = GrpcBroadcastSink::new(16);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let service = sink.service();
+    tokio::spawn(async move {
+        tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+            .await
+            .unwrap()
+    });
+
+    let mut client = MarketDataClient::connect(format!("http://{}", addr)).await.unwrap();
+    let mut stream = client
+        .subscribe(SubscribeRequest { symbols: vec!["aapl".to_string()], types: vec!["bars".to_string()] })
+        .await
+        .unwrap()
+        .into_inner();
+
+    sink.write_streaming_data(&create_mock_bar_data("MSFT", "2024-01-15T14:30:00Z", 300.0)).unwrap();
+    sink.write_streaming_data(&create_mock_streaming_data("t", "AAPL", serde_json::json!({
+        "T": "t", "S": "AAPL", "i": 1, "x": "V", "p": 150.25, "s": 100, "t": "2024-01-15T14:30:00Z"
+    }))).unwrap();
+    sink.write_streaming_data(&create_mock_bar_data("AAPL", "2024-01-15T14:30:00Z", 150.0)).unwrap();
+
+    let event = stream.message().await.unwrap().unwrap();
+    match event.event {
+        Some(Event::Bar(bar)) => {
+            assert_eq!(bar.symbol, "AAPL");
+            assert_eq!(bar.close, 150.0);
+            assert_eq!(bar.volume, 1000);
+        }
+        other => panic!("Expected AAPL bar, got {:?}", other),
+    }
+
+    let status = client
+        .subscribe(SubscribeRequest { symbols: vec![], types: vec!["news".to_string()] })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[test]
+fn test_json_logs_written_to_file() {
+    use algorithms_trading::logging::{init_logging, LogFormat};
+
+    let temp_dir = tempdir().unwrap();
+    let log_path = temp_dir.path().join("client.log");
+    init_logging(LogFormat::Json, Some(&log_path)).unwrap();
+
+    let span = tracing::info_span!("subscribe", feed = "iex");
+    span.in_scope(|| tracing::info!(symbols = 3, "Subscribed to data streams"));
+
+    let content = fs::read_to_string(&log_path).unwrap();
+    let line = content.lines().find(|line| line.contains("Subscribed to data streams")).unwrap();
+    let event: serde_json::Value = serde_json::from_str(line).unwrap();
+    assert_eq!(event["level"], "INFO");
+    assert_eq!(event["fields"]["symbols"], 3);
+    assert_eq!(event["span"]["name"], "subscribe");
+    assert_eq!(event["span"]["feed"], "iex");
+
+    // The global subscriber can only be installed once
+    assert!(init_logging(LogFormat::Text, None).is_err());
+}
+
+#[test]
+fn test_pipelined_sink_preserves_order() {
+    let temp_dir = tempdir().unwrap();
+    let output_path = temp_dir.path().join("pipelined.jsonl");
+    let file_mode = OutputMode::create_file_mode(&output_path, DataFormat::Json, false).unwrap();
+    let sink = PipelinedSink::new(Box::new(file_mode), 2, BackpressurePolicy::Block).unwrap();
+
+    for i in 0..100 {
+        sink.write_streaming_data(&create_mock_bar_data("SPY", "2024-01-15T14:30:00Z", i as f64)).unwrap();
+    }
+    sink.close().unwrap();
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    let closes: Vec<f64> = content
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["data"]["c"].as_f64().unwrap())
+        .collect();
+    assert_eq!(closes, (0..100).map(|i| i as f64).collect::<Vec<_>>());
+    assert_eq!(sink.stats().written, 100);
+    assert_eq!(sink.stats().dropped, 0);
+    assert!(sink.write_streaming_data(&create_mock_bar_data("SPY", "2024-01-15T14:31:00Z", 1.0)).is_err());
+}
+
+#[derive(Debug)]
+struct SlowSink {
+    records: std::sync::Arc<Mutex<Vec<StreamingData>>>,
+}
+
+impl OutputSink for SlowSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> algorithms_trading::Result<()> {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        self.records.lock().unwrap().push(data.clone());
+        Ok(())
+    }
+
+    fn flush(&self) -> algorithms_trading::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_pipelined_sink_drop_oldest() {
+    let records = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let sink = PipelinedSink::new(Box::new(SlowSink { records: records.clone() }), 4, BackpressurePolicy::DropOldest).unwrap();
+
+    for i in 0..50 {
+        sink.write_streaming_data(&create_mock_bar_data("SPY", "2024-01-15T14:30:00Z", i as f64)).unwrap();
+    }
+    sink.close().unwrap();
+
+    let stats = sink.stats();
+    assert!(stats.dropped > 0);
+    assert_eq!(stats.written + stats.dropped, 50);
+    let records = records.lock().unwrap();
+    assert_eq!(records.len() as u64, stats.written);
+    // The newest message is never the one evicted
+    assert_eq!(records.last().unwrap().data["c"], 49.0);
+}
+
+#[derive(Debug)]
+struct OddRejectingSink {
+    records: std::sync::Arc<Mutex<Vec<StreamingData>>>,
+}
+
+impl OutputSink for OddRejectingSink {
+    fn write_streaming_data(&self, data: &StreamingData) -> algorithms_trading::Result<()> {
+        if data.data["c"].as_f64().unwrap() % 2.0 == 1.0 {
+            return Err(algorithms_trading::Error::Invalid("odd close".to_string()));
+        }
+        self.records.lock().unwrap().push(data.clone());
+        Ok(())
+    }
+
+    fn flush(&self) -> algorithms_trading::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_pipelined_sink_drop_oldest_with_idle_writer() {
+    // A writer that keeps draining the queue and waiting again must not stall
+    // producers that evict from a full queue
+    let records = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let sink = std::sync::Arc::new(
+        PipelinedSink::new(Box::new(OddRejectingSink { records: records.clone() }), 1, BackpressurePolicy::DropOldest).unwrap(),
+    );
+
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    let producer = sink.clone();
+    std::thread::spawn(move || {
+        for i in 0..2000 {
+            producer.write_streaming_data(&create_mock_bar_data("SPY", "2024-01-15T14:30:00Z", i as f64)).unwrap();
+        }
+        producer.close().unwrap();
+        done_tx.send(()).unwrap();
+    });
+    done_rx.recv_timeout(std::time::Duration::from_secs(10)).expect("producer stalled on a full queue");
+
+    // Writes the sink rejected are neither written nor dropped
+    let stats = sink.stats();
+    assert_eq!(stats.written, records.lock().unwrap().len() as u64);
+    assert!(stats.written + stats.dropped <= 2000);
+    assert!(records.lock().unwrap().iter().all(|data| data.data["c"].as_f64().unwrap() % 2.0 == 0.0));
+}
+
+fn create_timed_bar_data(received: &str, close: f64) -> StreamingData {
+    let mut data = create_mock_bar_data("SPY", received, close);
+    data.received_time = received.parse().unwrap();
+    data
+}
+
+#[test]
+fn test_rotating_file_sink_daily_with_compression() {
+    use std::io::Read;
+
+    let temp_dir = tempdir().unwrap();
+    let policy = RotationPolicy { interval: Some(RotateInterval::Daily), max_bytes: None, compress: true };
+    let sink = RotatingFileSink::new(&temp_dir.path().join("capture.jsonl"), DataFormat::Json, false, policy);
+
+    sink.write_streaming_data(&create_timed_bar_data("2024-05-03T19:59:00Z", 1.0)).unwrap();
+    sink.write_streaming_data(&create_timed_bar_data("2024-05-03T20:00:00Z", 2.0)).unwrap();
+    sink.write_streaming_data(&create_timed_bar_data("2024-05-04T13:30:00Z", 3.0)).unwrap();
+    sink.close().unwrap();
+
+    let rotated = temp_dir.path().join("capture-2024-05-03.jsonl.gz");
+    assert!(rotated.exists());
+    assert!(!temp_dir.path().join("capture-2024-05-03.jsonl").exists());
+    let mut content = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(rotated).unwrap()).read_to_string(&mut content).unwrap();
+    assert_eq!(content.lines().count(), 2);
+
+    let current = temp_dir.path().join("capture-2024-05-04.jsonl");
+    assert_eq!(sink.current_path(), Some(current.clone()));
+    assert_eq!(fs::read_to_string(current).unwrap().lines().count(), 1);
+}
+
+#[test]
+fn test_rotating_file_sink_by_size() {
+    let temp_dir = tempdir().unwrap();
+    let policy = RotationPolicy { interval: None, max_bytes: Some(1), compress: false };
+    let sink = RotatingFileSink::new(&temp_dir.path().join("capture.csv"), DataFormat::Csv, false, policy);
+
+    for i in 0..3 {
+        sink.write_streaming_data(&create_mock_bar_data("SPY", "2024-05-03T14:30:00Z", i as f64)).unwrap();
+    }
+    sink.close().unwrap();
+
+    for name in ["capture.csv", "capture-1.csv", "capture-2.csv"] {
+        let content = fs::read_to_string(temp_dir.path().join(name)).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2, "{} should hold a header and one record", name);
+        assert_eq!(lines[0], "received_time,message_type,symbol,data,event_time");
+    }
+}
+
+#[test]
+fn test_rotating_file_sink_rotates_at_session_close() {
+    let temp_dir = tempdir().unwrap();
+    let policy = RotationPolicy { interval: None, max_bytes: None, compress: true };
+    let finished = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let sink = RotatingFileSink::new(&temp_dir.path().join("capture.jsonl"), DataFormat::Json, false, policy)
+        .on_finished({ let finished = finished.clone(); move |path| finished.lock().unwrap().push(path.to_path_buf()) });
+    let sink = PipelinedSink::new(Box::new(sink), 16, BackpressurePolicy::Block).unwrap();
+
+    sink.write_streaming_data(&create_mock_bar_data("SPY", "2024-05-03T14:30:00Z", 1.0)).unwrap();
+    sink.rotate().unwrap();
+    sink.rotate().unwrap();
+    sink.write_streaming_data(&create_mock_bar_data("SPY", "2024-05-06T14:30:00Z", 2.0)).unwrap();
+    sink.close().unwrap();
+
+    // The closed session's file is gzipped; the current one is left for --append
+    assert!(temp_dir.path().join("capture.jsonl.gz").exists());
+    assert_eq!(fs::read_to_string(temp_dir.path().join("capture-1.jsonl")).unwrap().lines().count(), 1);
+    assert_eq!(finished.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn test_session_summary() {
+    use algorithms_trading::{SessionSummary, Summary};
+
+    let temp_dir = tempdir().unwrap();
+    let summary = SessionSummary::new();
+    summary.add_drop_counter(|| 3);
+    summary.add_drop_counter(|| 4);
+    for data in [
+        create_mock_streaming_data("t", "AAPL", serde_json::json!({"p": 150.0, "s": 100})),
+        create_mock_streaming_data("t", "AAPL", serde_json::json!({"p": 151.0, "s": 100})),
+        create_mock_quote_data("MSFT", 300.0, 300.1),
+    ] {
+        summary.write_streaming_data(&data).unwrap();
+    }
+    for message_type in ["disconnect", "reconnect", "disconnect"] {
+        summary.write_streaming_data(&StreamingData { symbol: None, ..create_mock_streaming_data(message_type, "", serde_json::json!({})) }).unwrap();
+    }
+
+    let sink = CollectingSink::default();
+    let path = temp_dir.path().join("summary.json");
+    let result = summary.finish(&sink, Some(&path)).unwrap();
+    assert_eq!((result.messages, result.disconnects, result.reconnects, result.dropped), (6, 2, 1, 7));
+    assert_eq!(result.by_symbol["AAPL"], 2);
+    assert_eq!(result.by_type["q"], 1);
+
+    let records = sink.records.lock().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].message_type, "summary");
+    // Writing the summary back through the counter does not count it
+    summary.write_streaming_data(&records[0]).unwrap();
+    assert_eq!(summary.snapshot().messages, 6);
+    let from_file: Summary = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(from_file, result);
+
+    let line = OutputMode::create_console_mode(DataFormat::Plain).format_plain(&records[0]);
+    assert!(line.starts_with("📋 Session summary: 6 messages over 2 symbols in "), "{}", line);
+    assert!(line.ends_with("(2 disconnects, 1 reconnects, 7 dropped)"), "{}", line);
+}
+
+#[test]
+fn test_pid_file() {
+    use algorithms_trading::daemon::PidFile;
+
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("client.pid");
+    let pid_file = PidFile::create(&path).unwrap();
+    assert_eq!(fs::read_to_string(pid_file.path()).unwrap().trim(), std::process::id().to_string());
+    assert!(PidFile::create(&path).unwrap_err().to_string().contains("Already running"));
+    drop(pid_file);
+    assert!(!path.exists());
+
+    // A file left by a process that is gone is not locked, whatever it holds, and is taken over
+    for stale in ["999999999999\n", "not a pid"] {
+        fs::write(&path, stale).unwrap();
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(fs::read_to_string(pid_file.path()).unwrap().trim(), std::process::id().to_string());
+    }
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("500MB").unwrap(), 500 * 1024 * 1024);
+    assert_eq!(parse_size("1.5g").unwrap(), 1536 * 1024 * 1024);
+    assert_eq!(parse_size("4096").unwrap(), 4096);
+    assert!(parse_size("10XB").is_err());
+    assert!(parse_size("MB").is_err());
+}
+
+#[test]
+fn test_compressed_file_output_round_trip() {
+    use algorithms_trading::compression::read_to_string;
+
+    let temp_dir = tempdir().unwrap();
+    for compression in [Compression::Gzip, Compression::Zstd] {
+        for format in [DataFormat::Json, DataFormat::Csv] {
+            let path = compression.with_extension(&temp_dir.path().join(format!("capture-{:?}", format)));
+            // Two sessions, the second appending a new gzip member / zstd frame
+            for (session, append) in [(0, false), (1, true)] {
+                let output = OutputMode::create_compressed_file_mode(&path, format.clone(), append, compression).unwrap();
+                for i in 0..3 {
+                    output.write_streaming_data(&create_mock_bar_data("SPY", "2024-05-03T14:30:00Z", (session * 3 + i) as f64)).unwrap();
+                }
+                output.close().unwrap();
+            }
+
+            let raw = fs::read(&path).unwrap();
+            assert!(!String::from_utf8_lossy(&raw).contains("SPY"), "{:?} output should be compressed", compression);
+            let content = read_to_string(&path).unwrap();
+            let records = content.lines().filter(|line| line.contains("SPY")).count();
+            assert_eq!(records, 6, "{:?} {:?}", compression, format);
+        }
+    }
+    assert!(temp_dir.path().join("capture-Json.zst").exists());
+}
+
+#[test]
+fn test_compressed_file_output_write_after_close() {
+    use algorithms_trading::compression::read_to_string;
+
+    let temp_dir = tempdir().unwrap();
+    for compression in [Compression::Gzip, Compression::Zstd] {
+        let path = compression.with_extension(&temp_dir.path().join("capture.jsonl"));
+        let output = OutputMode::create_compressed_file_mode(&path, DataFormat::Json, false, compression).unwrap();
+        output.write_streaming_data(&create_mock_bar_data("SPY", "2024-05-03T14:30:00Z", 1.0)).unwrap();
+        output.close().unwrap();
+        // A late record after close starts a new member or frame instead of panicking
+        output.write_streaming_data(&create_mock_bar_data("SPY", "2024-05-03T14:31:00Z", 2.0)).unwrap();
+        output.flush().unwrap();
+        output.close().unwrap();
+        output.close().unwrap();
+
+        let content = read_to_string(&path).unwrap();
+        assert_eq!(content.lines().filter(|line| line.contains("SPY")).count(), 2, "{:?}", compression);
+    }
+}
+
+#[test]
+fn test_rotating_file_sink_with_compression() {
+    let temp_dir = tempdir().unwrap();
+    let policy = RotationPolicy { interval: Some(RotateInterval::Daily), max_bytes: None, compress: true };
+    let sink = RotatingFileSink::new(&temp_dir.path().join("capture.jsonl.zst"), DataFormat::Json, false, policy)
+        .with_compression(Compression::Zstd);
+
+    sink.write_streaming_data(&create_timed_bar_data("2024-05-03T19:59:00Z", 1.0)).unwrap();
+    sink.write_streaming_data(&create_timed_bar_data("2024-05-04T13:30:00Z", 2.0)).unwrap();
+    sink.close().unwrap();
+
+    for name in ["capture-2024-05-03.jsonl.zst", "capture-2024-05-04.jsonl.zst"] {
+        let content = algorithms_trading::compression::read_to_string(&temp_dir.path().join(name)).unwrap();
+        assert_eq!(content.lines().count(), 1, "{}", name);
+    }
+    assert!(!temp_dir.path().join("capture-2024-05-03.jsonl.zst.gz").exists());
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_dataset_writer_partitions() {
+    use algorithms_trading::dataset::DatasetWriter;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let temp_dir = tempdir().unwrap();
+    let writer = DatasetWriter::new(temp_dir.path()).unwrap().with_max_rows(2);
+
+    let bars = [
+        ("2024-05-03T14:30:00Z", 1.0),
+        ("2024-05-03T14:31:00Z", 2.0),
+        ("2024-05-03T14:32:00Z", 3.0),
+        ("2024-05-06T13:30:00Z", 4.0),
+    ];
+    for (timestamp, close) in bars {
+        writer.write_streaming_data(&create_mock_bar_data("AAPL", timestamp, close)).unwrap();
+    }
+    writer.write_streaming_data(&create_mock_streaming_data("t", "MSFT", serde_json::json!({
+        "T": "t", "S": "MSFT", "i": 7, "x": "V", "p": 300.5, "s": 10, "t": "2024-05-03T14:30:00.5Z"
+    }))).unwrap();
+    writer.write_streaming_data(&create_mock_streaming_data("success", "", serde_json::json!("authenticated"))).unwrap();
+    // Periodic flushes leave partial partitions buffered
+    writer.flush().unwrap();
+    assert!(!temp_dir.path().join("bars/symbol=AAPL/date=2024-05-03/part-1.parquet").exists());
+    assert!(!temp_dir.path().join("trades").exists());
+    writer.close().unwrap();
+
+    let read_rows = |path: std::path::PathBuf| -> usize {
+        let file = fs::File::open(&path).unwrap_or_else(|_| panic!("missing {}", path.display()));
+        ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap().map(|batch| batch.unwrap().num_rows()).sum()
+    };
+    let aapl = temp_dir.path().join("bars/symbol=AAPL");
+    // The first two bars filled a part; the third was written on close
+    assert_eq!(read_rows(aapl.join("date=2024-05-03/part-0.parquet")), 2);
+    assert_eq!(read_rows(aapl.join("date=2024-05-03/part-1.parquet")), 1);
+    assert_eq!(read_rows(aapl.join("date=2024-05-06/part-0.parquet")), 1);
+    assert_eq!(read_rows(temp_dir.path().join("trades/symbol=MSFT/date=2024-05-03/part-0.parquet")), 1);
+    assert!(!temp_dir.path().join("success").exists());
+
+    let file = fs::File::open(aapl.join("date=2024-05-06/part-0.parquet")).unwrap();
+    let batch = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap().next().unwrap().unwrap();
+    let schema = batch.schema();
+    let columns: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(columns, ["timestamp", "open", "high", "low", "close", "volume", "trade_count", "vwap", "received_at"]);
+}
+
+#[cfg(feature = "s3")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_s3_upload_and_stream_sinks() {
+    use algorithms_trading::s3_sink::{S3Config, S3StreamSink, S3Uploader};
+    use object_store::{memory::InMemory, ObjectStore};
+    use std::sync::Arc;
+
+    let store = Arc::new(InMemory::new());
+    let config = S3Config {
+        bucket: "captures".to_string(),
+        prefix: "/vm-1/".to_string(),
+    };
+    let uploader = S3Uploader::new(config.clone(), store.clone());
+
+    let temp_dir = tempdir().unwrap();
+    let policy = RotationPolicy {
+        interval: Some(RotateInterval::Daily),
+        ..Default::default()
+    };
+    let hook = uploader.clone();
+    let sink = RotatingFileSink::new(&temp_dir.path().join("capture.jsonl"), DataFormat::Json, false, policy)
+        .on_finished(move |path| hook.upload(path));
+    sink.write_streaming_data(&create_timed_bar_data("2024-05-03T14:30:00Z", 1.0)).unwrap();
+    sink.write_streaming_data(&create_timed_bar_data("2024-05-04T14:30:00Z", 2.0)).unwrap();
+    sink.close().unwrap();
+    uploader.shutdown().await.unwrap();
+
+    for name in ["capture-2024-05-03.jsonl", "capture-2024-05-04.jsonl"] {
+        let object = store.get(&config.object_key(name)).await.unwrap().bytes().await.unwrap();
+        assert_eq!(object.as_ref(), fs::read(temp_dir.path().join(name)).unwrap().as_slice(), "{}", name);
+    }
+    assert_eq!(config.object_key("a.jsonl").as_ref(), "vm-1/a.jsonl");
+
+    let key = config.object_key("session.jsonl");
+    let stream = S3StreamSink::create(store.clone(), key.clone()).await.unwrap();
+    stream.write_streaming_data(&create_mock_bar_data("MSFT", "2024-05-03T14:30:00Z", 3.0)).unwrap();
+    stream.write_streaming_data(&create_mock_bar_data("MSFT", "2024-05-03T14:31:00Z", 4.0)).unwrap();
+    stream.close().unwrap();
+    assert!(stream.write_streaming_data(&create_mock_bar_data("MSFT", "2024-05-03T14:32:00Z", 5.0)).is_err());
+
+    let object = store.get(&key).await.unwrap().bytes().await.unwrap();
+    let records: Vec<StreamingData> = std::str::from_utf8(&object)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].symbol, Some("MSFT".to_string()));
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn test_script_hook_drops_tags_and_alerts() {
+    use algorithms_trading::scripting::ScriptHook;
+
+    let script = r#"
+        fn on_message(msg) {
+            if msg.type != "t" { return true; }
+            if msg.size < 100 { return false; }
+            if msg.price > 200.0 { alert(`${msg.symbol} above 200 at ${msg.price}`); }
+            msg.data.p = msg.price * 2.0;
+            msg.tags = ["block"];
+            msg
+        }
+    "#;
+    let sink = std::sync::Arc::new(CollectingSink::default());
+    let hook = ScriptHook::compile(script, Box::new(vec![sink.clone() as std::sync::Arc<dyn OutputSink>])).unwrap();
+
+    for (price, size) in [(150.0, 10), (150.0, 500), (250.0, 1000)] {
+        process_streaming_message(&serde_json::from_value::<StreamingMessage>(serde_json::json!({
+            "T": "t", "S": "AAPL", "i": 1, "x": "V", "p": price, "s": size, "t": "2024-05-03T14:30:00Z"
+        })).unwrap(), &hook).unwrap();
+    }
+    hook.write_streaming_data(&create_mock_bar_data("AAPL", "2024-05-03T14:30:00Z", 1.0)).unwrap();
+
+    let records = sink.records.lock().unwrap();
+    let types: Vec<&str> = records.iter().map(|r| r.message_type.as_str()).collect();
+    assert_eq!(types, ["t", "t", "alert", "b"]);
+    assert_eq!(records[0].data["p"], 300.0);
+    assert_eq!(records[0].data["tags"], serde_json::json!(["block"]));
+    assert!(records[0].event_time.is_some());
+    assert_eq!(records[2].data["message"], "AAPL above 200 at 250.0");
+    assert_eq!(records[2].symbol.as_deref(), Some("AAPL"));
+
+    assert!(ScriptHook::compile("fn other(x) { x }", Box::new(CollectingSink::default())).unwrap_err().to_string().contains("on_message"));
+    assert!(ScriptHook::compile("fn on_message(msg) {", Box::new(CollectingSink::default())).is_err());
+}
+
+#[cfg(feature = "market-hours")]
+#[test]
+fn test_market_session_windows() {
+    use algorithms_trading::market_hours::{eastern_offset, next_window, CalendarDay, MarketSession};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    assert_eq!(eastern_offset(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()).local_minus_utc(), -5 * 3600);
+    assert_eq!(eastern_offset(NaiveDate::from_ymd_opt(2024, 3, 11).unwrap()).local_minus_utc(), -4 * 3600);
+    assert_eq!(eastern_offset(NaiveDate::from_ymd_opt(2024, 11, 4).unwrap()).local_minus_utc(), -5 * 3600);
+
+    let day = |date: &str, close: &str| -> CalendarDay {
+        serde_json::from_value(serde_json::json!({"date": date, "open": "09:30", "close": close, "session_open": "0400", "session_close": "2000"})).unwrap()
+    };
+    let utc = |d: u32, h: u32, m: u32| Utc.with_ymd_and_hms(2024, 7, d, h, m, 0).unwrap();
+    let july_3 = day("2024-07-03", "13:00");
+    assert_eq!(july_3.windows(&[MarketSession::Regular]).unwrap(), [(utc(3, 13, 30), utc(3, 17, 0))]);
+    assert_eq!(july_3.windows(&[MarketSession::After, MarketSession::Pre, MarketSession::Regular]).unwrap(), [(utc(3, 8, 0), utc(4, 0, 0))]);
+    assert_eq!(july_3.windows(&[MarketSession::Pre, MarketSession::After]).unwrap(), [(utc(3, 8, 0), utc(3, 13, 30)), (utc(3, 17, 0), utc(4, 0, 0))]);
+
+    // After the half day closes, the next session is after the holiday
+    let days = [july_3, day("2024-07-05", "16:00")];
+    assert_eq!(next_window(&days, &[MarketSession::Regular], utc(3, 18, 0)).unwrap(), Some((utc(5, 13, 30), utc(5, 20, 0))));
+    assert_eq!(next_window(&days, &[MarketSession::Regular], utc(3, 14, 0)).unwrap(), Some((utc(3, 13, 30), utc(3, 17, 0))));
+    assert_eq!(next_window(&days, &[MarketSession::Regular], utc(6, 0, 0)).unwrap(), None);
+}
+
+#[cfg(feature = "assets")]
+#[test]
+fn test_symbol_validation() {
+    use algorithms_trading::assets::{apply_symbol_validation, check_asset, Asset, SymbolProblem, SymbolValidation};
+
+    let asset = |status: &str, tradable: bool| -> Asset {
+        serde_json::from_value(serde_json::json!({"symbol": "AAPL", "status": status, "tradable": tradable, "exchange": "NASDAQ"})).unwrap()
+    };
+    assert_eq!(check_asset(Some(&asset("active", true))), None);
+    assert_eq!(check_asset(Some(&asset("inactive", true))), Some(SymbolProblem::Inactive));
+    assert_eq!(check_asset(Some(&asset("active", false))), Some(SymbolProblem::Untradable));
+    assert_eq!(check_asset(None), Some(SymbolProblem::Unknown));
+
+    let mut config = StreamingConfig::with_sinks(vec![]);
+    config.trade_symbols = vec!["AAPL".to_string(), "APPL".to_string()];
+    config.quote_symbols = vec!["APPL".to_string(), "MSFT".to_string()];
+    config.bar_symbols = vec!["SPY".to_string()];
+    let problems = vec![("APPL".to_string(), SymbolProblem::Unknown)];
+
+    let error = apply_symbol_validation(&mut config, &problems, SymbolValidation::Fail).unwrap_err();
+    assert_eq!(error.to_string(), "Invalid symbols: APPL (unknown)");
+    apply_symbol_validation(&mut config, &problems, SymbolValidation::Warn).unwrap();
+    assert!(config.all_symbols().contains(&"APPL".to_string()));
+    apply_symbol_validation(&mut config, &problems, SymbolValidation::Drop).unwrap();
+    assert_eq!(config.trade_symbols, ["AAPL"]);
+    assert_eq!(config.quote_symbols, ["MSFT"]);
+}
+
+#[tokio::test]
+async fn test_request_retry_classifies_errors() {
+    use algorithms_trading::retry::{is_retryable, retry_request, HttpError};
+    use algorithms_trading::{Error, RetryPolicy};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    let http = |status: u16| Error::from(HttpError { status, body: "{}".to_string() });
+    assert!(is_retryable(&http(429)));
+    assert!(is_retryable(&http(503)));
+    assert!(!is_retryable(&http(403)));
+    assert!(!is_retryable(&http(422)));
+    assert!(!is_retryable(&serde_json::from_str::<u32>("x").unwrap_err().into()));
+    assert!(is_retryable(&Error::alpaca(anyhow::anyhow!("error sending request: connection reset"))));
+    assert!(!is_retryable(&Error::alpaca(anyhow::anyhow!("API error 401: unauthorized"))));
+    assert_eq!(HttpError::check(200, "ok".to_string()).unwrap(), "ok");
+
+    let policy = RetryPolicy { backoff_base: Duration::from_millis(1), ..RetryPolicy::for_requests(2) };
+
+    // A transient error is retried until the request passes
+    let calls = AtomicU32::new(0);
+    let result = retry_request(&policy, || async {
+        match calls.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(http(500)),
+            _ => Ok("page"),
+        }
+    })
+    .await;
+    assert_eq!(result.unwrap(), "page");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // Retries run out after the budget
+    let calls = AtomicU32::new(0);
+    let result: algorithms_trading::Result<()> = retry_request(&policy, || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err(http(429))
+    })
+    .await;
+    assert!(matches!(result.unwrap_err(), Error::Http(HttpError { status: 429, .. })));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // A fatal error is not retried
+    let calls = AtomicU32::new(0);
+    let result: algorithms_trading::Result<()> = retry_request(&policy, || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err(http(404))
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_rate_limiter_budget_and_headers() {
+    use algorithms_trading::RateLimiter;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    // A fresh budget allows a burst, then paces at the per-minute rate
+    let limiter = RateLimiter::new(2);
+    assert_eq!(limiter.try_acquire(), None);
+    assert_eq!(limiter.try_acquire(), None);
+    let wait = limiter.try_acquire().unwrap();
+    assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30), "{:?}", wait);
+
+    // Remaining caps the tokens, and 0 parks until the reset
+    let limiter = RateLimiter::new(200);
+    limiter.update(Some(200), Some(1), None);
+    assert_eq!(limiter.try_acquire(), None);
+    assert!(limiter.try_acquire().is_some());
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let headers = [("x-ratelimit-remaining", 0), ("x-ratelimit-reset", now + 10)];
+    limiter.observe(|name| headers.iter().find(|(header, _)| *header == name).map(|(_, value)| *value));
+    let wait = limiter.try_acquire().unwrap();
+    assert!(wait > Duration::from_secs(8) && wait <= Duration::from_secs(10), "{:?}", wait);
+
+    // A lower account limit replaces the configured budget
+    limiter.update(Some(100), None, None);
+    assert_eq!(limiter.requests_per_minute(), 100);
+    limiter.set_requests_per_minute(50);
+    assert_eq!(limiter.requests_per_minute(), 50);
+}
+
+#[test]
+fn test_checkpoint_resume() {
+    use algorithms_trading::checkpoint::Checkpoint;
+
+    let temp_dir = tempdir().unwrap();
+    let output = temp_dir.path().join("bars.csv.gz");
+    let path = Checkpoint::path_for(&output);
+    assert_eq!(path, temp_dir.path().join("bars.csv.gz.checkpoint.json"));
+    assert!(Checkpoint::load(&path).unwrap().is_none());
+
+    let mut checkpoint = Checkpoint::new("bars AAPL,MSFT 2024-01-01..2024-02-01 1Min sip");
+    checkpoint.record_page("AAPL", Some("page2"), Some("2024-01-10T15:00:00Z"), 1000);
+    checkpoint.record_page("AAPL", Some("page3"), Some("2024-01-20T15:00:00Z"), 1000);
+    checkpoint.record_page("MSFT", None, Some("2024-01-31T20:59:00Z"), 400);
+    checkpoint.save(&path).unwrap();
+
+    let loaded = Checkpoint::load(&path).unwrap().unwrap();
+    assert_eq!(loaded, checkpoint);
+    assert_eq!(loaded.resume_token("AAPL").as_deref(), Some("page3"));
+    assert_eq!(loaded.symbols["AAPL"].records, 2000);
+    assert_eq!(loaded.symbols["AAPL"].last_timestamp.as_deref(), Some("2024-01-20T15:00:00Z"));
+    assert!(!loaded.is_done("AAPL"));
+    // A finished symbol is skipped, one never started begins at the first page
+    assert!(loaded.is_done("MSFT"));
+    assert!(loaded.resume_token("MSFT").is_none());
+    assert!(loaded.resume_token("TSLA").is_none());
+    assert!(!path.with_extension("json.tmp").exists());
+
+    // An empty last page keeps the timestamp of the page before it
+    let mut checkpoint = loaded;
+    checkpoint.record_page("AAPL", None, None, 0);
+    assert!(checkpoint.is_done("AAPL"));
+    assert_eq!(checkpoint.symbols["AAPL"].last_timestamp.as_deref(), Some("2024-01-20T15:00:00Z"));
+
+    fs::write(&path, "not json").unwrap();
+    assert!(Checkpoint::load(&path).is_err());
+}
+
+#[test]
+fn test_sync_last_timestamps() {
+    use algorithms_trading::sync::{last_timestamps, resume_from};
+
+    let temp_dir = tempdir().unwrap();
+    assert!(last_timestamps(&temp_dir.path().join("missing.csv"), &DataFormat::Csv).unwrap().is_empty());
+
+    // Appended runs repeat the header; compressed files are read too
+    let csv = temp_dir.path().join("bars.csv.gz");
+    let output = OutputMode::create_compressed_file_mode(&csv, DataFormat::Csv, false, Compression::Gzip).unwrap();
+    for line in [
+        "symbol,timestamp,open,high,low,close,volume,trade_count,vwap",
+        "AAPL,2024-01-02T05:00:00Z,187.15,188.44,183.89,185.64,82488674,1009074,185.9",
+        "MSFT,2024-01-03T05:00:00Z,369.01,373.26,368.51,370.6,23083465,263500,370.9",
+        "symbol,timestamp,open,high,low,close,volume,trade_count,vwap",
+        "AAPL,2024-01-03T05:00:00Z,184.22,185.88,183.43,184.25,58414460,656956,184.4",
+    ] {
+        output.writeln(line).unwrap();
+    }
+    output.close().unwrap();
+    let last = last_timestamps(&csv, &DataFormat::Csv).unwrap();
+    assert_eq!(last.len(), 2);
+    assert_eq!(last["AAPL"].to_rfc3339(), "2024-01-03T05:00:00+00:00");
+    assert_eq!(last["MSFT"].to_rfc3339(), "2024-01-03T05:00:00+00:00");
+
+    let json = temp_dir.path().join("bars.jsonl");
+    fs::write(&json, concat!(
+        r#"{"symbol":"AAPL","timestamp":"2024-01-04T05:00:00Z","close":181.91}"#, "\n",
+        "not json\n",
+        r#"{"symbol":"AAPL","timestamp":"2024-01-02T05:00:00Z","close":185.64}"#, "\n",
+    )).unwrap();
+    let json_last = last_timestamps(&json, &DataFormat::Json).unwrap();
+    assert_eq!(json_last["AAPL"].to_rfc3339(), "2024-01-04T05:00:00+00:00");
+    assert!(last_timestamps(&json, &DataFormat::Plain).is_err());
+
+    // A symbol continues from the store that is furthest behind, or from the
+    // start when any store lacks it
+    assert_eq!(resume_from(&[&last, &json_last], "AAPL"), Some(last["AAPL"]));
+    assert_eq!(resume_from(&[&last, &json_last], "MSFT"), None);
+    assert_eq!(resume_from(&[&last], "MSFT"), Some(last["MSFT"]));
+    assert_eq!(resume_from(&[], "MSFT"), None);
+
+    #[cfg(feature = "parquet")]
+    {
+        use algorithms_trading::dataset::{last_timestamps as dataset_last_timestamps, DatasetWriter};
+
+        let root = temp_dir.path().join("dataset");
+        let writer = DatasetWriter::new(&root).unwrap().with_max_rows(2);
+        for (timestamp, close) in [("2024-05-03T14:30:00Z", 1.0), ("2024-05-03T19:59:00Z", 2.0), ("2024-05-06T13:30:00Z", 3.0)] {
+            writer.write_streaming_data(&create_mock_bar_data("AAPL", timestamp, close)).unwrap();
+        }
+        writer.close().unwrap();
+        let last = dataset_last_timestamps(&root, "bars").unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last["AAPL"].to_rfc3339(), "2024-05-06T13:30:00+00:00");
+        assert!(dataset_last_timestamps(&root, "trades").unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_bar_cache_chunks() {
+    use algorithms_trading::cache::{BarCache, CacheKey};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    // Intraday bars are cached by month, daily and longer ones by year
+    let minute = CacheKey::containing("AAPL", "1Min", "sip", "raw", date(2023, 12, 15));
+    assert_eq!((minute.start, minute.end), (date(2023, 12, 1), date(2024, 1, 1)));
+    assert_eq!((minute.next().start, minute.next().end), (date(2024, 1, 1), date(2024, 2, 1)));
+    let daily = CacheKey::containing("AAPL", "1Day", "sip", "raw", date(2023, 6, 30));
+    assert_eq!((daily.start, daily.end), (date(2023, 1, 1), date(2024, 1, 1)));
+    assert_eq!(CacheKey::containing("AAPL", "1Hour", "iex", "raw", date(2024, 2, 29)).end, date(2024, 3, 1));
+
+    // A chunk is final once its last day is over
+    assert!(!minute.is_complete(Utc.with_ymd_and_hms(2023, 12, 31, 23, 59, 0).unwrap()));
+    assert!(minute.is_complete(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()));
+
+    let temp_dir = tempdir().unwrap();
+    let cache = BarCache::new(temp_dir.path());
+    assert!(cache.get::<serde_json::Value>(&minute).unwrap().is_none());
+    let bars = vec![serde_json::json!({"symbol": "AAPL", "timestamp": "2023-12-15T14:30:00Z", "close": 197.5})];
+    cache.put(&minute, &bars).unwrap();
+    assert_eq!(cache.get::<serde_json::Value>(&minute).unwrap(), Some(bars));
+    assert!(temp_dir.path().join("bars/sip/raw/1Min/AAPL/2023-12-01.json").exists());
+    // Other feeds, timeframes and chunks are separate entries
+    assert!(cache.get::<serde_json::Value>(&minute.next()).unwrap().is_none());
+    assert!(cache.get::<serde_json::Value>(&CacheKey::containing("AAPL", "1Min", "iex", "raw", date(2023, 12, 15))).unwrap().is_none());
+
+    cache.put(&daily, &[serde_json::json!({})]).unwrap();
+    let (files, bytes) = cache.clear().unwrap();
+    assert_eq!(files, 2);
+    assert!(bytes > 0);
+    assert!(cache.get::<serde_json::Value>(&minute).unwrap().is_none());
+    assert_eq!(cache.clear().unwrap(), (0, 0));
+}
+
+#[test]
+fn test_find_gaps() {
+    use algorithms_trading::gaps::{bar_duration, find_gaps, TradingDay};
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+    let time = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+    // Tue 2024-01-02 to Mon 2024-01-08 with the weekend closed: 5 trading days
+    let days: Vec<TradingDay> = [2, 3, 4, 5, 8]
+        .iter()
+        .map(|&d| TradingDay {
+            date: NaiveDate::from_ymd_opt(2024, 1, d).unwrap(),
+            open: time(&format!("2024-01-{:02}T14:30:00Z", d)),
+            close: time(&format!("2024-01-{:02}T21:00:00Z", d)),
+        })
+        .collect();
+
+    // Daily bars are stamped at midnight New York time
+    let daily = [time("2024-01-02T05:00:00Z"), time("2024-01-05T05:00:00Z")];
+    let gaps = find_gaps("AAPL", &[&daily], &days, None, 0.0);
+    assert_eq!(gaps.len(), 2);
+    assert_eq!((gaps[0].first_day.to_string(), gaps[0].last_day.to_string(), gaps[0].days), ("2024-01-03".to_string(), "2024-01-04".to_string(), 2));
+    assert_eq!(gaps[0].to_string(), "AAPL 2024-01-03..2024-01-04: 2 trading days, 0/2 bars");
+    // The weekend is not a gap, so Monday stands alone
+    assert_eq!((gaps[1].first_day.to_string(), gaps[1].days), ("2024-01-08".to_string(), 1));
+    // Refetch from midnight to midnight in New York
+    assert_eq!(gaps[0].range(), (time("2024-01-03T05:00:00Z"), time("2024-01-05T05:00:00Z")));
+
+    // A second store that also lacks Jan 5 widens the second gap
+    let other = [time("2024-01-02T05:00:00Z")];
+    let gaps = find_gaps("AAPL", &[&daily, &other], &days, None, 0.0);
+    assert_eq!(gaps.len(), 1);
+    assert_eq!(gaps[0].days, 4);
+
+    // Hourly bars: 7 overlap 09:30-16:00, and pre-market bars do not count
+    let hour = bar_duration("1Hour").unwrap();
+    assert_eq!(hour, Some(Duration::hours(1)));
+    let mut hourly: Vec<DateTime<Utc>> = days
+        .iter()
+        .flat_map(|day| (0..7).map(move |h| day.open - Duration::minutes(30) + Duration::hours(h)))
+        .collect();
+    hourly.retain(|t| t.date_naive().to_string() != "2024-01-04" || *t < time("2024-01-04T16:00:00Z"));
+    hourly.push(time("2024-01-04T10:00:00Z"));
+    assert!(find_gaps("AAPL", &[&hourly], &days, hour, 0.0).is_empty());
+    let gaps = find_gaps("AAPL", &[&hourly], &days, hour, 0.5);
+    assert_eq!(gaps.len(), 1);
+    assert_eq!(gaps[0].to_string(), "AAPL 2024-01-04..2024-01-04: 1 trading day, 2/7 bars");
+
+    assert_eq!(bar_duration("1Day").unwrap(), None);
+    assert!(bar_duration("1Week").is_err());
+}
+
+#[test]
+fn test_bar_validation() {
+    use algorithms_trading::validation::{BarValidator, BarValues, IssueKind, VALIDATION_CSV_HEADER};
+
+    let bar = |open, high, low, close, volume| BarValues { open, high, low, close, volume };
+    let mut validator = BarValidator::new();
+    assert!(validator.check("AAPL", "2024-01-02T05:00:00Z", &bar(100.0, 101.0, 99.0, 100.5, 1000)).is_empty());
+
+    let issues = validator.check("AAPL", "2024-01-03T05:00:00Z", &bar(100.0, 99.0, 101.0, 100.0, 1000));
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].issue, IssueKind::HighBelowLow);
+    assert_eq!(issues[0].to_csv(), "AAPL,2024-01-03T05:00:00Z,high_below_low,high 99 < low 101");
+    assert_eq!(issues[0].to_csv().split(',').count(), VALIDATION_CSV_HEADER.split(',').count());
+
+    let issues = validator.check("AAPL", "2024-01-04T05:00:00Z", &bar(100.0, 101.0, 99.0, 102.0, 1000));
+    assert_eq!(issues.iter().map(|i| i.issue).collect::<Vec<_>>(), vec![IssueKind::OutsideRange]);
+    assert!(issues[0].detail.starts_with("close 102 outside"));
+
+    // A zero price is flagged and does not become the previous close
+    let issues = validator.check("AAPL", "2024-01-05T05:00:00Z", &bar(0.0, 101.0, 0.0, 100.0, 1000));
+    assert_eq!(issues[0].issue, IssueKind::NonPositivePrice);
+
+    // An unadjusted 4:1 split shows as a gap from the previous close
+    let issues = validator.check("AAPL", "2024-01-08T05:00:00Z", &bar(25.5, 26.0, 25.0, 25.8, 1000));
+    assert_eq!(issues.iter().map(|i| i.issue).collect::<Vec<_>>(), vec![IssueKind::PriceGap]);
+    assert!(issues[0].detail.contains("-75.0% from previous close 102"), "{}", issues[0].detail);
+    // Other symbols have their own history
+    assert!(validator.check("MSFT", "2024-01-08T05:00:00Z", &bar(370.0, 371.0, 369.0, 370.5, 1000)).is_empty());
+
+    // Volume is judged once there are 20 bars to compare with
+    for day in 0..15 {
+        assert!(validator.check("AAPL", &format!("day{}", day), &bar(25.8, 26.0, 25.5, 25.8, 1000)).is_empty());
+    }
+    let issues = validator.check("AAPL", "spike", &bar(25.8, 26.0, 25.5, 25.8, 25_000));
+    assert_eq!(issues.iter().map(|i| i.issue).collect::<Vec<_>>(), vec![IssueKind::VolumeOutlier]);
+    assert!(validator.check("MSFT", "spike", &bar(370.0, 371.0, 369.0, 370.5, 25_000)).is_empty());
+
+    let counts = validator.counts();
+    assert_eq!(counts.values().sum::<usize>(), 5);
+    assert_eq!(counts[&IssueKind::PriceGap], 1);
+}
+
+#[test]
+fn test_date_arguments() {
+    use algorithms_trading::dates::{DateResolver, RangeEnd};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    // Tuesday 2024-01-16, 09:00 in New York
+    let dates = DateResolver::new(Utc.with_ymd_and_hms(2024, 1, 16, 14, 0, 0).unwrap());
+    let resolve = |value: &str, end| dates.resolve(value, end).unwrap();
+
+    assert_eq!(resolve("2024-01-15", RangeEnd::End), "2024-01-15");
+    assert_eq!(resolve("today", RangeEnd::Start), "2024-01-16");
+    assert_eq!(resolve("now", RangeEnd::End), "2024-01-16T14:00:00Z");
+    assert_eq!(resolve("2024-01-15T09:30", RangeEnd::Start), "2024-01-15T14:30:00Z");
+    assert_eq!(resolve("2024-07-15T09:30:00", RangeEnd::Start), "2024-07-15T13:30:00Z");
+    assert_eq!(resolve("2024-01-15T09:30:00-05:00", RangeEnd::Start), "2024-01-15T14:30:00Z");
+
+    // Periods end on the day after their last, which the API excludes
+    assert_eq!(resolve("2024-02", RangeEnd::Start), "2024-02-01");
+    assert_eq!(resolve("2024-02", RangeEnd::End), "2024-03-01");
+    assert_eq!(resolve("2023", RangeEnd::End), "2024-01-01");
+    assert_eq!(resolve("last-week", RangeEnd::Start), "2024-01-08");
+    assert_eq!(resolve("last-week", RangeEnd::End), "2024-01-15");
+    assert_eq!(resolve("last-month", RangeEnd::Start), "2023-12-01");
+    assert_eq!(resolve("Last-Month", RangeEnd::End), "2024-01-01");
+    assert_eq!(dates.resolve_day("last-month", RangeEnd::End).unwrap(), NaiveDate::from_ymd_opt(2023, 12, 31).unwrap());
+    assert_eq!(dates.days_back(30), "2023-12-17");
+
+    // Yesterday skips the weekend, and holidays when the calendar is known
+    let monday = DateResolver::new(Utc.with_ymd_and_hms(2024, 1, 15, 20, 0, 0).unwrap());
+    assert_eq!(monday.resolve("yesterday", RangeEnd::Start).unwrap(), "2024-01-12");
+    let tuesday = dates.clone().with_trading_days(vec![
+        NaiveDate::from_ymd_opt(2024, 1, 12).unwrap(),
+        NaiveDate::from_ymd_opt(2024, 1, 16).unwrap(),
+    ]);
+    assert_eq!(resolve("yesterday", RangeEnd::Start), "2024-01-15");
+    assert_eq!(tuesday.resolve("yesterday", RangeEnd::Start).unwrap(), "2024-01-12");
+
+    assert!(dates.resolve("01/15/2024", RangeEnd::Start).is_err());
+    assert!(dates.resolve("2024-13", RangeEnd::Start).is_err());
+}
+
+#[test]
+fn test_symbols_file() {
+    use algorithms_trading::symbols_file::read_symbols_file;
+
+    let dir = tempdir().unwrap();
+    let plain = dir.path().join("universe.txt");
+    fs::write(&plain, "# tech\naapl\nMSFT  Microsoft\n\nAAPL\n").unwrap();
+    assert_eq!(read_symbols_file(&plain).unwrap(), ["AAPL", "MSFT"]);
+
+    // The csv and json output of `trade assets`
+    let csv = dir.path().join("universe.csv");
+    fs::write(&csv, "symbol,name,exchange\nSPY,\"SPDR S&P 500 ETF Trust, Series 1\",ARCA\nQQQ,Invesco QQQ,NASDAQ\n").unwrap();
+    assert_eq!(read_symbols_file(&csv).unwrap(), ["SPY", "QQQ"]);
+    let json = dir.path().join("universe.jsonl");
+    fs::write(&json, "{\"symbol\":\"IWM\",\"exchange\":\"ARCA\"}\n{\"symbol\":\"DIA\"}\n").unwrap();
+    assert_eq!(read_symbols_file(&json).unwrap(), ["IWM", "DIA"]);
+
+    let empty = dir.path().join("empty.txt");
+    fs::write(&empty, "# nothing yet\n").unwrap();
+    assert!(read_symbols_file(&empty).is_err());
+    assert!(read_symbols_file(&dir.path().join("missing.txt")).is_err());
+}
+
+#[cfg(feature = "assets")]
+#[test]
+fn test_asset_listing() {
+    use algorithms_trading::assets::{Asset, AssetQuery, ASSETS_CSV_HEADER};
+
+    let asset: Asset = serde_json::from_value(serde_json::json!({
+        "id": "b0b6dd9d-8b9b-48a9-ba46-b9d54906e415",
+        "class": "us_equity",
+        "exchange": "NASDAQ",
+        "symbol": "AAPL",
+        "name": "Apple Inc. Common Stock",
+        "status": "active",
+        "tradable": true,
+        "marginable": true,
+        "shortable": true,
+        "easy_to_borrow": true,
+        "fractionable": true
+    }))
+    .unwrap();
+    let mut warrant = asset.clone();
+    warrant.symbol = "ABCDW".to_string();
+    warrant.name = "ABCD Corp, Warrants".to_string();
+    warrant.exchange = "NYSE".to_string();
+    warrant.shortable = false;
+    warrant.fractionable = false;
+
+    assert!(AssetQuery::default().matches(&warrant));
+    let query = AssetQuery {
+        status: Some("active".to_string()),
+        class: Some("us_equity".to_string()),
+        exchanges: vec!["NASDAQ".to_string(), "ARCA".to_string()],
+        tradable: true,
+        ..Default::default()
+    };
+    assert!(query.matches(&asset));
+    assert!(!query.matches(&warrant));
+    let shortable = AssetQuery { shortable: true, ..Default::default() };
+    assert!(shortable.matches(&asset));
+    assert!(!shortable.matches(&warrant));
+    assert!(!AssetQuery { class: Some("crypto".to_string()), ..Default::default() }.matches(&asset));
+
+    assert_eq!(ASSETS_CSV_HEADER.split(',').count(), 10);
+    assert_eq!(asset.format(&DataFormat::Csv).unwrap(), "AAPL,Apple Inc. Common Stock,NASDAQ,us_equity,active,true,true,true,true,true");
+    assert_eq!(warrant.format(&DataFormat::Csv).unwrap(), "ABCDW,\"ABCD Corp, Warrants\",NYSE,us_equity,active,true,false,true,false,true");
+    assert_eq!(warrant.format(&DataFormat::Plain).unwrap(), "🏷️ ABCDW: ABCD Corp, Warrants | NYSE us_equity | active, tradable, easy to borrow, marginable");
+    let json: serde_json::Value = serde_json::from_str(&asset.format(&DataFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["symbol"], "AAPL");
+    assert_eq!(json["fractionable"], true);
+}
+
+#[cfg(feature = "market-hours")]
+#[test]
+fn test_calendar_and_clock_output() {
+    use algorithms_trading::market_hours::{CalendarDay, MarketClock, CALENDAR_CSV_HEADER, MARKET_CLOCK_CSV_HEADER};
+
+    let day = |date: &str, close: &str| -> CalendarDay {
+        serde_json::from_value(serde_json::json!({"date": date, "open": "09:30", "close": close, "session_open": "0400", "session_close": "2000"})).unwrap()
+    };
+    let half_day = day("2024-11-29", "13:00");
+    assert!(half_day.is_early_close());
+    assert!(!day("2024-12-02", "16:00").is_early_close());
+    assert_eq!(half_day.format(&DataFormat::Plain).unwrap(), "📅 Fri 2024-11-29: regular 09:30-13:00 | extended 04:00-20:00 | early close");
+    assert_eq!(CALENDAR_CSV_HEADER.split(',').count(), 6);
+    assert_eq!(half_day.format(&DataFormat::Csv).unwrap(), "2024-11-29,09:30,13:00,04:00,20:00,true");
+    let json: serde_json::Value = serde_json::from_str(&half_day.format(&DataFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["close"], "13:00");
+
+    let clock: MarketClock = serde_json::from_value(serde_json::json!({
+        "timestamp": "2024-01-16T14:15:30.123-05:00",
+        "is_open": true,
+        "next_open": "2024-01-17T09:30:00-05:00",
+        "next_close": "2024-01-16T16:00:00-05:00"
+    }))
+    .unwrap();
+    assert_eq!(clock.format(&DataFormat::Plain).unwrap(), "🕐 Market open | now Tue 2024-01-16 14:15 ET | closes Tue 2024-01-16 16:00 ET (in 1h 44m)");
+    assert_eq!(MARKET_CLOCK_CSV_HEADER.split(',').count(), 4);
+    assert_eq!(clock.format(&DataFormat::Csv).unwrap(), "2024-01-16T14:15:30.123-05:00,true,2024-01-17T09:30:00-05:00,2024-01-16T16:00:00-05:00");
+    let closed = MarketClock { is_open: false, ..clock };
+    assert!(closed.format(&DataFormat::Plain).unwrap().ends_with("opens Wed 2024-01-17 09:30 ET (in 19h 14m)"));
+}
+
+#[test]
+fn test_merge_sorted_streams() {
+    use algorithms_trading::merge::merge_sorted;
+
+    // Bars of three symbols as (timestamp, symbol); equal timestamps keep the stream order
+    let aapl = vec![(1, "AAPL"), (3, "AAPL"), (3, "AAPL"), (7, "AAPL")];
+    let msft = vec![(2, "MSFT"), (3, "MSFT"), (8, "MSFT")];
+    let spy: Vec<(i32, &str)> = vec![(0, "SPY"), (9, "SPY")];
+    let merged: Vec<_> = merge_sorted(vec![aapl.into_iter(), msft.into_iter(), spy.into_iter()], |bar| bar.0).collect();
+    assert_eq!(merged, [(0, "SPY"), (1, "AAPL"), (2, "MSFT"), (3, "AAPL"), (3, "AAPL"), (3, "MSFT"), (7, "AAPL"), (8, "MSFT"), (9, "SPY")]);
+
+    let empty: Vec<std::vec::IntoIter<i32>> = vec![Vec::new().into_iter(), Vec::new().into_iter()];
+    assert_eq!(merge_sorted(empty, |value| *value).count(), 0);
+}
+
+#[test]
+fn test_resample_bars() {
+    use algorithms_trading::resample::{read_bars, OhlcvBar, Resampler, Session, Timeframe, TimeframeUnit};
+
+    assert_eq!(Timeframe::parse("4hour").unwrap(), Timeframe { amount: 4, unit: TimeframeUnit::Hour });
+    assert_eq!(Timeframe::parse("2Min").unwrap().to_string(), "2Min");
+    assert_eq!(Timeframe::parse("3M").unwrap().to_string(), "3Month");
+    assert!(Timeframe::parse("0Min").is_err());
+    assert!(Timeframe::parse("Min").is_err());
+    assert_eq!(Timeframe::parse("5s").unwrap().to_string(), "5Sec");
+    assert!(Timeframe::parse("5Tick").is_err());
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("minute.csv");
+    // 2024-01-02: 09:29 pre-market, 09:30-09:32 and 15:59 regular, 16:00 after hours (EST, UTC-5)
+    fs::write(&path, "symbol,timestamp,open,high,low,close,volume,trade_count,vwap\n\
+        AAPL,2024-01-02T14:29:00Z,9.00,9.00,9.00,9.00,50,1,9\n\
+        AAPL,2024-01-02T14:30:00Z,10.00,11.00,9.50,10.50,100,5,10.25\n\
+        AAPL,2024-01-02T14:31:00Z,10.50,12.00,10.00,11.50,300,7,11.25\n\
+        AAPL,2024-01-02T14:32:00Z,11.50,11.60,11.00,11.10,0,0,11.2\n\
+        AAPL,2024-01-02T20:59:00Z,11.00,11.00,11.00,11.00,50,1,11\n\
+        AAPL,2024-01-02T21:00:00Z,13.00,13.00,13.00,13.00,50,1,13\n\
+        symbol,timestamp,open,high,low,close,volume,trade_count,vwap\n\
+        AAPL,2024-01-08T14:30:00Z,12.00,12.00,12.00,12.00,10,1,12\n").unwrap();
+    let mut bars = Vec::new();
+    read_bars(&path, &DataFormat::Csv, |bar| {
+        bars.push(bar);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(bars.len(), 7);
+
+    let resample = |timeframe: &str, session: Session| -> Vec<OhlcvBar> {
+        let mut resampler = Resampler::new(Timeframe::parse(timeframe).unwrap(), session);
+        let mut out: Vec<OhlcvBar> = bars.iter().filter_map(|bar| resampler.push(bar.clone()).unwrap()).collect();
+        out.extend(resampler.finish());
+        out
+    };
+    let times = |bars: &[OhlcvBar]| bars.iter().map(|bar| bar.timestamp.to_rfc3339()).collect::<Vec<_>>();
+
+    // Counted from 09:30, without the pre-market and after-hours bars
+    let two_minute = resample("2Min", Session::Regular);
+    assert_eq!(times(&two_minute), ["2024-01-02T14:30:00+00:00", "2024-01-02T14:32:00+00:00", "2024-01-02T20:58:00+00:00", "2024-01-08T14:30:00+00:00"]);
+    let first = &two_minute[0];
+    assert_eq!((first.open, first.high, first.low, first.close), (10.0, 12.0, 9.5, 11.5));
+    assert_eq!((first.volume, first.trade_count), (400, 12));
+    assert!((first.vwap - 11.0).abs() < 1e-9);
+    // A bar without volume keeps its own VWAP
+    assert_eq!(two_minute[1].vwap, 11.2);
+    assert_eq!(first.format(&DataFormat::Csv).unwrap(), "AAPL,2024-01-02T14:30:00Z,10.00,12.00,9.50,11.50,400,12,11");
+
+    // 4Hour extended bars start at 04:00, 08:00, 12:00 and 16:00
+    let extended = resample("4Hour", Session::Extended);
+    assert_eq!(times(&extended), ["2024-01-02T13:00:00+00:00", "2024-01-02T17:00:00+00:00", "2024-01-02T21:00:00+00:00", "2024-01-08T13:00:00+00:00"]);
+    assert_eq!(extended[0].open, 9.0);
+
+    // Weeks start on Monday at midnight in New York
+    let weekly = resample("1W", Session::Regular);
+    assert_eq!(times(&weekly), ["2024-01-01T05:00:00+00:00", "2024-01-08T05:00:00+00:00"]);
+    assert_eq!(weekly[0].close, 11.0);
+    // Two trading days, though they are a week apart
+    let two_day = resample("2Day", Session::All);
+    assert_eq!(times(&two_day), ["2024-01-02T05:00:00+00:00"]);
+    assert_eq!(two_day[0].volume, 560);
+
+    let mut resampler = Resampler::new(Timeframe::parse("1Min").unwrap(), Session::All);
+    resampler.push(bars[2].clone()).unwrap();
+    assert!(resampler.push(bars[0].clone()).is_err());
+}
+
+#[test]
+fn test_fill_missing_bars() {
+    use algorithms_trading::resample::{Fill, Filler, OhlcvBar, Session, Timeframe};
+
+    let bar = |timestamp: &str, close: f64, volume: u64| OhlcvBar {
+        symbol: "AAPL".to_string(),
+        timestamp: timestamp.parse().unwrap(),
+        open: close - 1.0,
+        high: close + 1.0,
+        low: close - 2.0,
+        close,
+        volume,
+        trade_count: 3,
+        vwap: close - 0.5,
+    };
+    let hour = Timeframe::parse("1Hour").unwrap();
+    assert!(Filler::new(Timeframe::parse("1Day").unwrap(), Session::Regular, Fill::Forward).is_err());
+
+    // Regular hours of 2024-01-02 and 2024-01-03 start at 14:30 UTC: 09:30, 10:30, ... 15:30
+    let run = |fill: Fill| -> Vec<OhlcvBar> {
+        let mut filler = Filler::new(hour, Session::Regular, fill).unwrap();
+        let mut bars = filler.push(bar("2024-01-02T15:30:00Z", 10.0, 100));
+        bars.extend(filler.push(bar("2024-01-02T17:30:00Z", 12.0, 200)));
+        bars.extend(filler.push(bar("2024-01-03T15:30:00Z", 11.0, 50)));
+        bars.extend(filler.finish());
+        bars
+    };
+    let times = |bars: &[OhlcvBar]| bars.iter().map(|bar| bar.timestamp.format("%d %H:%M").to_string()).collect::<Vec<_>>();
+
+    let zero = run(Fill::ZeroVolume);
+    assert_eq!(times(&zero), [
+        "02 15:30", "02 16:30", "02 17:30", "02 18:30", "02 19:30", "02 20:30",
+        "03 14:30", "03 15:30", "03 16:30", "03 17:30", "03 18:30", "03 19:30", "03 20:30",
+    ]);
+    let flat = &zero[1];
+    assert_eq!((flat.open, flat.high, flat.low, flat.close, flat.vwap), (10.0, 10.0, 10.0, 10.0, 10.0));
+    assert_eq!((flat.volume, flat.trade_count), (0, 0));
+    // The next day opens at the previous close
+    assert_eq!(zero[6].close, 12.0);
+    assert_eq!(zero[12].close, 11.0);
+
+    let forward = run(Fill::Forward);
+    assert_eq!(forward.len(), 13);
+    assert_eq!(forward[1], OhlcvBar { timestamp: "2024-01-02T16:30:00Z".parse().unwrap(), ..bar("2024-01-02T15:30:00Z", 10.0, 100) });
+
+    assert_eq!(run(Fill::None).len(), 3);
+}
+
+#[test]
+fn test_export_profiles() {
+    use algorithms_trading::export::ExportProfile;
+    use algorithms_trading::resample::OhlcvBar;
+
+    let bar = |timestamp: &str| OhlcvBar {
+        symbol: "AAPL".to_string(),
+        timestamp: timestamp.parse().unwrap(),
+        open: 185.5,
+        high: 186.25,
+        low: 184.0,
+        close: 185.75,
+        volume: 12345,
+        trade_count: 100,
+        vwap: 185.4,
+    };
+    // 09:30 in New York, EDT
+    let minute = bar("2024-07-01T13:30:00Z");
+    // A daily bar, timestamped at New York midnight
+    let daily = bar("2024-01-02T05:00:00Z");
+
+    assert_eq!(ExportProfile::Backtrader.header(), Some("datetime,open,high,low,close,volume,openinterest"));
+    assert_eq!(ExportProfile::Backtrader.format(&minute), "2024-07-01 09:30:00,185.5,186.25,184,185.75,12345,0");
+    assert_eq!(ExportProfile::Backtrader.format(&daily), "2024-01-02 00:00:00,185.5,186.25,184,185.75,12345,0");
+
+    assert_eq!(ExportProfile::Zipline.header(), Some("date,open,high,low,close,volume,dividend,split"));
+    assert_eq!(ExportProfile::Zipline.format(&daily), "2024-01-02,185.5,186.25,184,185.75,12345,0.0,1.0");
+    assert_eq!(ExportProfile::Zipline.format(&minute), "2024-07-01 13:30:00,185.5,186.25,184,185.75,12345,0.0,1.0");
+
+    assert_eq!(ExportProfile::Metatrader.header(), None);
+    assert_eq!(ExportProfile::Metatrader.format(&minute), "2024.07.01,09:30,185.5,186.25,184,185.75,12345");
+}
+
+#[test]
+fn test_xlsx_workbook() {
+    use algorithms_trading::xlsx::{excel_time, Cell, XlsxWriter, BAR_COLUMNS};
+    use std::io::Read;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("bars.xlsx");
+    let mut workbook = XlsxWriter::create(&path).unwrap();
+    workbook.start_sheet("AAPL", &BAR_COLUMNS).unwrap();
+    let time = "2024-07-01T13:30:00Z".parse().unwrap();
+    workbook.write_row(&[Cell::Time(time), Cell::Number(185.5), Cell::from("R&D <1>")]).unwrap();
+    workbook.start_sheet("BRK/B", &BAR_COLUMNS).unwrap();
+    workbook.start_sheet("AAPL", &BAR_COLUMNS).unwrap();
+    workbook.finish().unwrap();
+
+    // 09:30 in New York, 182 days after Excel's serial 45292 for 2024-01-01
+    assert!((excel_time(time) - (45474.0 + 9.5 / 24.0)).abs() < 1e-9);
+
+    // Walk the zip's local headers and inflate each part
+    let bytes = fs::read(&path).unwrap();
+    let mut parts = HashMap::new();
+    let mut offset = 0;
+    while bytes[offset..].starts_with(b"PK\x03\x04") {
+        let field = |at: usize, len: usize| bytes[offset + at..offset + at + len].iter().rev().fold(0usize, |n, b| n << 8 | *b as usize);
+        let (compressed, name_len, extra_len) = (field(18, 4), field(26, 2), field(28, 2));
+        let name = String::from_utf8(bytes[offset + 30..offset + 30 + name_len].to_vec()).unwrap();
+        let start = offset + 30 + name_len + extra_len;
+        let mut xml = String::new();
+        flate2::read::DeflateDecoder::new(&bytes[start..start + compressed]).read_to_string(&mut xml).unwrap();
+        parts.insert(name, xml);
+        offset = start + compressed;
+    }
+    assert!(bytes[offset..].starts_with(b"PK\x01\x02"));
+
+    for part in ["[Content_Types].xml", "_rels/.rels", "xl/workbook.xml", "xl/_rels/workbook.xml.rels", "xl/styles.xml"] {
+        assert!(parts.contains_key(part), "missing {}", part);
+    }
+    let workbook = &parts["xl/workbook.xml"];
+    assert!(workbook.contains(r#"<sheet name="AAPL" sheetId="1" r:id="rId1"/>"#));
+    assert!(workbook.contains(r#"<sheet name="BRK-B" sheetId="2""#));
+    assert!(workbook.contains(r#"<sheet name="AAPL (2)" sheetId="3""#));
+    assert!(parts["[Content_Types].xml"].contains("/xl/worksheets/sheet3.xml"));
+
+    let sheet = &parts["xl/worksheets/sheet1.xml"];
+    assert!(sheet.contains(r#"<c r="A1" t="inlineStr"><is><t>time</t></is></c>"#));
+    assert!(sheet.contains(r#"<c r="A2" s="1"><v>45474.395833333"#));
+    assert!(sheet.contains(r#"<c r="B2"><v>185.5</v></c>"#));
+    assert!(sheet.contains("<t>R&amp;D &lt;1&gt;</t>"));
+}
+
+#[test]
+fn test_compare_feeds() {
+    use algorithms_trading::feed_compare::{compare_feeds, Issue};
+    use algorithms_trading::resample::OhlcvBar;
+
+    let bar = |minute: u32, close: f64, volume: u64| OhlcvBar {
+        symbol: "AAPL".to_string(),
+        timestamp: format!("2024-01-02T14:{:02}:00Z", minute).parse().unwrap(),
+        open: 100.0,
+        high: 101.0,
+        low: 99.0,
+        close,
+        volume,
+        trade_count: 10,
+        vwap: 100.0,
+    };
+    let sip = vec![bar(30, 100.0, 1000), bar(31, 100.5, 2000), bar(32, 100.2, 500), bar(33, 100.0, 400)];
+    // IEX misses 14:32, is off at 14:33 and has a bar SIP lacks at 14:34
+    let iex = vec![bar(30, 100.05, 30), bar(31, 100.5, 60), bar(33, 100.5, 20), bar(34, 100.0, 10)];
+
+    let (comparison, discrepancies) = compare_feeds("AAPL", &iex, &sip, 0.001);
+    assert_eq!(comparison.reference_bars, 4);
+    assert_eq!(comparison.missing, 1);
+    assert_eq!(comparison.extra, 1);
+    // 14:30 is 0.05% off, within the tolerance
+    assert_eq!(comparison.price_mismatches, 1);
+    assert!((comparison.max_difference - 0.005).abs() < 1e-12);
+    assert_eq!(comparison.volume_ratio(), Some(120.0 / 3900.0));
+    assert_eq!(comparison.median_volume_ratio, Some(0.03));
+
+    let issues: Vec<(Issue, &str)> = discrepancies.iter().map(|d| (d.issue, d.field.as_str())).collect();
+    assert_eq!(issues, vec![(Issue::Missing, "volume"), (Issue::Price, "close"), (Issue::Extra, "volume")]);
+    assert_eq!(discrepancies[1].format(&DataFormat::Csv).unwrap(), "AAPL,2024-01-02T14:33:00Z,price,close,100.5,100,0.005");
+    assert_eq!(discrepancies[0].format(&DataFormat::Csv).unwrap(), "AAPL,2024-01-02T14:32:00Z,missing,volume,,500,");
+    assert!(comparison.to_string().starts_with("AAPL: 3/4 reference bars (75.0%), 1 extra, 1 with prices off (max 0.500%)"));
+}
+
+#[test]
+fn test_download_estimate() {
+    use algorithms_trading::estimate::{bars_per_day, estimate_range, rate_limited_duration, weekdays, Estimate};
+    use chrono::{DateTime, Utc};
+
+    let time = |value: &str| value.parse::<DateTime<Utc>>().unwrap();
+    assert_eq!(bars_per_day("1Min").unwrap(), 960.0);
+    assert_eq!(bars_per_day("15Min").unwrap(), 64.0);
+    assert_eq!(bars_per_day("1Hour").unwrap(), 16.0);
+    assert_eq!(bars_per_day("1Day").unwrap(), 1.0);
+    assert!(bars_per_day("1Fortnight").is_err());
+
+    // Two weeks of weekdays, and half of Monday after a weekend
+    assert_eq!(weekdays(time("2024-01-01T00:00:00Z"), time("2024-01-15T00:00:00Z")), 10.0);
+    assert_eq!(weekdays(time("2024-01-06T00:00:00Z"), time("2024-01-08T12:00:00Z")), 0.5);
+
+    let estimate = estimate_range(time("2024-01-01T00:00:00Z"), time("2024-01-15T00:00:00Z"), "1Min", 1000).unwrap();
+    assert_eq!(estimate, Estimate { requests: 10, bars: 9600 });
+    let weekend = estimate_range(time("2024-01-06T00:00:00Z"), time("2024-01-08T00:00:00Z"), "1Day", 1000).unwrap();
+    assert_eq!(weekend, Estimate { requests: 1, bars: 0 });
+
+    assert_eq!(rate_limited_duration(150, 200).as_secs(), 0);
+    assert_eq!(rate_limited_duration(500, 200).as_secs(), 90);
+}
+
+#[test]
+fn test_atomic_publish() {
+    use algorithms_trading::atomic::{begin, checksum_path, partial_path, publish, sha256_file};
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("bars.csv");
+    let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    let partial = begin(&path, false, false).unwrap();
+    assert_eq!(partial, partial_path(&path));
+    fs::write(&partial, "abc").unwrap();
+    assert!(!path.exists());
+    assert_eq!(publish(&path).unwrap(), abc);
+    assert!(!partial.exists());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+    assert_eq!(fs::read_to_string(checksum_path(&path)).unwrap(), format!("{}  bars.csv\n", abc));
+
+    // Appending starts from the published file, a resumed run from its partial one
+    let partial = begin(&path, true, false).unwrap();
+    assert_eq!(fs::read_to_string(&partial).unwrap(), "abc");
+    fs::write(&partial, "abcdef").unwrap();
+    begin(&path, true, true).unwrap();
+    assert_eq!(fs::read_to_string(&partial).unwrap(), "abcdef");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+
+    let checksum = publish(&path).unwrap();
+    assert_eq!(checksum, sha256_file(&path).unwrap());
+    assert!(fs::read_to_string(checksum_path(&path)).unwrap().starts_with(&checksum));
+}
+
+#[test]
+fn test_download_metadata() {
+    use algorithms_trading::metadata::{is_metadata_line, sidecar_path, DownloadMetadata};
+    use algorithms_trading::resample::read_bars;
+
+    let metadata = DownloadMetadata {
+        timeframe: Some("1Day".to_string()),
+        adjustment: Some("raw".to_string()),
+        ..DownloadMetadata::new("bars", "iex", vec!["AAPL".to_string(), "MSFT".to_string()], "2024-01-01", "2024-02-01")
+    };
+    assert!(metadata.generator.starts_with("algorithms-trading "));
+    let line = metadata.json_line().unwrap();
+    assert!(line.starts_with(r#"{"_meta":{"generator":"#));
+    assert!(is_metadata_line(&line));
+    assert_eq!(DownloadMetadata::from_json_line(&line), Some(metadata.clone()));
+    let aapl = metadata.for_symbols(vec!["AAPL".to_string()]);
+    assert_eq!(aapl.symbols, vec!["AAPL"]);
+    assert_eq!(aapl.feed, "iex");
+
+    let bar = r#"{"symbol":"AAPL","timestamp":"2024-01-02T05:00:00Z","open":1.0,"high":2.0,"low":0.5,"close":1.5,"volume":10,"trade_count":1,"vwap":1.2}"#;
+    assert!(!is_metadata_line(bar));
+    assert_eq!(DownloadMetadata::from_json_line(bar), None);
+
+    // Readers of the records skip the metadata
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("bars.jsonl");
+    fs::write(&path, format!("{}\n{}\n", line, bar)).unwrap();
+    let mut bars = Vec::new();
+    read_bars(&path, &DataFormat::Json, |bar| {
+        bars.push(bar);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(bars.len(), 1);
+
+    let csv = dir.path().join("bars.csv");
+    metadata.write_sidecar(&csv).unwrap();
+    let sidecar: DownloadMetadata = serde_json::from_str(&fs::read_to_string(sidecar_path(&csv)).unwrap()).unwrap();
+    assert_eq!(sidecar, metadata);
+}
+
+#[test]
+fn test_activity_series() {
+    use algorithms_trading::activity::ActivitySeries;
+    use std::time::Duration;
+
+    let message = |time: &str, message_type: &str, symbol: Option<&str>, data: serde_json::Value| StreamingData {
+        received_time: "2024-01-02T15:00:00Z".parse().unwrap(),
+        event_time: Some(time.parse().unwrap()),
+        message_type: message_type.to_string(),
+        symbol: symbol.map(str::to_string),
+        data,
+    };
+    let mut series = ActivitySeries::new(Duration::from_secs(60)).unwrap();
+    assert!(ActivitySeries::new(Duration::ZERO).is_err());
+    series.add(&message("2024-01-02T14:30:05Z", "t", Some("AAPL"), serde_json::json!({"s": 100})));
+    series.add(&message("2024-01-02T14:30:59.999Z", "t", Some("AAPL"), serde_json::json!({"s": 50})));
+    series.add(&message("2024-01-02T14:30:10Z", "q", Some("MSFT"), serde_json::json!({})));
+    series.add(&message("2024-01-02T14:31:00Z", "b", Some("AAPL"), serde_json::json!({"v": 1200})));
+    // Status messages only count towards the total
+    series.add(&message("2024-01-02T14:31:30Z", "success", None, serde_json::json!({})));
+
+    let rows = series.rows();
+    let keys: Vec<(String, Option<&str>)> = rows.iter().map(|row| (row.bucket.to_rfc3339(), row.symbol.as_deref())).collect();
+    assert_eq!(keys, vec![
+        ("2024-01-02T14:30:00+00:00".to_string(), None),
+        ("2024-01-02T14:30:00+00:00".to_string(), Some("AAPL")),
+        ("2024-01-02T14:30:00+00:00".to_string(), Some("MSFT")),
+        ("2024-01-02T14:31:00+00:00".to_string(), None),
+        ("2024-01-02T14:31:00+00:00".to_string(), Some("AAPL")),
+    ]);
+    assert_eq!(rows[0].messages, 3);
+    assert_eq!(rows[1].trades, 2);
+    assert_eq!(rows[1].trade_volume, 150);
+    assert_eq!(rows[1].volume_per_minute, 150.0);
+    assert_eq!(rows[3].messages, 2);
+    assert_eq!(rows[4].bar_volume, 1200);
+    assert_eq!(series.intervals(), 2);
+    assert_eq!(series.peak(), Some(("2024-01-02T14:30:00Z".parse().unwrap(), 0.05)));
+    assert_eq!(rows[1].format(&DataFormat::Csv).unwrap(), "2024-01-02T14:30:00Z,AAPL,2,0.0333,2,2.0000,150,150.0000,0,0,0");
+    assert_eq!(rows[0].format(&DataFormat::Csv).unwrap(), "2024-01-02T14:30:00Z,,3,0.0500,2,2.0000,150,150.0000,1,0,0");
+}
+
+#[test]
+fn test_quote_spreads() {
+    use algorithms_trading::spreads::SpreadTracker;
+
+    let quote = |seconds: i64, bid: f64, ask: f64| StreamingData {
+        received_time: "2024-01-02T15:00:00Z".parse().unwrap(),
+        event_time: Some("2024-01-02T14:30:00Z".parse::<chrono::DateTime<chrono::Utc>>().unwrap() + chrono::Duration::seconds(seconds)),
+        message_type: "q".to_string(),
+        symbol: Some("AAPL".to_string()),
+        data: serde_json::json!({"bp": bid, "bs": 1, "ap": ask, "as": 1}),
+    };
+    let mut tracker = SpreadTracker::new();
+    tracker.add(&quote(0, 100.0, 100.02));
+    // Locked for two seconds, then crossed for one
+    tracker.add(&quote(1, 100.0, 100.0));
+    tracker.add(&quote(3, 100.05, 100.01));
+    tracker.add(&quote(4, 100.0, 100.04));
+    tracker.add(&quote(5, 100.0, 0.0));
+    let mut trade = quote(6, 100.0, 100.5);
+    trade.message_type = "t".to_string();
+    tracker.add(&trade);
+
+    let stats = tracker.stats();
+    assert_eq!(stats.len(), 1);
+    let aapl = &stats[0];
+    assert_eq!((aapl.quotes, aapl.locked, aapl.crossed, aapl.one_sided), (4, 1, 1, 1));
+    assert!((aapl.average - 0.02).abs() < 1e-9);
+    assert!((aapl.median - 0.02).abs() < 1e-9);
+    assert!((aapl.max - 0.04).abs() < 1e-9);
+    assert!((aapl.average_bps - 2.0).abs() < 0.01);
+    // 0.02 for one second and 0 for two; the crossed second does not count
+    assert!((aapl.time_weighted.unwrap() - 0.02 / 3.0).abs() < 1e-9);
+    assert_eq!(aapl.locked_percent(), 25.0);
+    assert_eq!(aapl.crossed_percent(), 25.0);
+}
+
+#[test]
+fn test_trade_analytics() {
+    use algorithms_trading::trade_analytics::TradeAnalytics;
+
+    let trade = |seconds: i64, price: f64, size: u64| StreamingData {
+        received_time: "2024-01-02T15:00:00Z".parse().unwrap(),
+        event_time: Some("2024-01-02T14:30:00Z".parse::<chrono::DateTime<chrono::Utc>>().unwrap() + chrono::Duration::seconds(seconds)),
+        message_type: "t".to_string(),
+        symbol: Some("AAPL".to_string()),
+        data: serde_json::json!({"p": price, "s": size}),
+    };
+    let mut analytics = TradeAnalytics::new(2);
+    analytics.add(&trade(0, 100.0, 100));
+    analytics.add(&trade(1, 100.0, 50));
+    analytics.add(&trade(2, 101.0, 2000));
+    // Unchanged price keeps the uptick
+    analytics.add(&trade(3, 101.0, 300));
+    analytics.add(&trade(4, 99.0, 2000));
+    analytics.add(&trade(5, 99.5, 12_000));
+
+    let reports = analytics.reports();
+    assert_eq!(reports.len(), 1);
+    let aapl = &reports[0];
+    assert_eq!((aapl.trades, aapl.volume), (6, 16_450));
+    let notional = 100.0 * 150.0 + 101.0 * 2300.0 + 99.0 * 2000.0 + 99.5 * 12_000.0;
+    assert!((aapl.vwap - notional / 16_450.0).abs() < 1e-9);
+    let sizes: Vec<(&str, u64, u64)> = aapl.sizes.iter().map(|range| (range.range.as_str(), range.trades, range.volume)).collect();
+    assert_eq!(sizes, vec![
+        ("1-99", 1, 50),
+        ("100-499", 2, 400),
+        ("500-999", 0, 0),
+        ("1000-4999", 2, 4000),
+        ("5000-9999", 0, 0),
+        ("10000+", 1, 12_000),
+    ]);
+    let largest: Vec<(u64, f64, i64)> = aapl.largest.iter().map(|print| (print.size, print.price, print.timestamp.timestamp() % 60)).collect();
+    assert_eq!(largest, vec![(12_000, 99.5, 5), (2000, 101.0, 2)]);
+    assert_eq!((aapl.buy_volume, aapl.sell_volume, aapl.unclassified_volume), (14_300, 2000, 150));
+}
+
+#[test]
+fn test_aggregate_trades() {
+    use algorithms_trading::resample::{OhlcvBar, Resampler, Session, Timeframe};
+
+    let message = |time: &str, message_type: &str, data: serde_json::Value| StreamingData {
+        received_time: "2024-01-02T15:00:00Z".parse().unwrap(),
+        event_time: Some(time.parse().unwrap()),
+        message_type: message_type.to_string(),
+        symbol: Some("AAPL".to_string()),
+        data,
+    };
+    let trade = |time: &str, price: f64, size: u64| OhlcvBar::from_trade(&message(time, "t", serde_json::json!({"p": price, "s": size}))).unwrap();
+    assert!(OhlcvBar::from_trade(&message("2024-01-02T14:30:00Z", "q", serde_json::json!({"bp": 1.0, "ap": 1.1}))).is_none());
+    assert!(OhlcvBar::from_trade(&message("2024-01-02T14:30:00Z", "t", serde_json::json!({"p": 1.0}))).is_none());
+
+    let mut resampler = Resampler::new(Timeframe::parse("30Sec").unwrap(), Session::All);
+    assert!(resampler.push(trade("2024-01-02T14:30:05Z", 100.0, 100)).unwrap().is_none());
+    assert!(resampler.push(trade("2024-01-02T14:30:20Z", 102.0, 300)).unwrap().is_none());
+    assert!(resampler.push(trade("2024-01-02T14:30:29Z", 99.0, 100)).unwrap().is_none());
+    let bar = resampler.push(trade("2024-01-02T14:30:31Z", 101.0, 50)).unwrap().unwrap();
+    assert_eq!(bar.timestamp, "2024-01-02T14:30:00Z".parse::<chrono::DateTime<chrono::Utc>>().unwrap());
+    assert_eq!((bar.open, bar.high, bar.low, bar.close), (100.0, 102.0, 99.0, 99.0));
+    assert_eq!((bar.volume, bar.trade_count), (500, 3));
+    assert!((bar.vwap - (100.0 * 100.0 + 102.0 * 300.0 + 99.0 * 100.0) / 500.0).abs() < 1e-9);
+
+    // Trades before the bar being built are too late to add
+    assert_eq!(resampler.current_start("AAPL"), Some("2024-01-02T14:30:30Z".parse().unwrap()));
+    assert_eq!(resampler.current_start("MSFT"), None);
+    let last = resampler.finish();
+    assert_eq!(last.len(), 1);
+    assert_eq!((last[0].open, last[0].volume, last[0].trade_count), (101.0, 50, 1));
+}
+
+#[test]
+fn test_message_selection() {
+    use algorithms_trading::dates::{DateResolver, RangeEnd};
+    use algorithms_trading::filter::{MessageSelection, TimeBound};
+
+    let message = |time: &str, message_type: &str, symbol: &str| StreamingData {
+        received_time: "2024-01-03T00:00:00Z".parse().unwrap(),
+        event_time: Some(time.parse().unwrap()),
+        message_type: message_type.to_string(),
+        symbol: Some(symbol.to_string()),
+        data: serde_json::json!({}),
+    };
+    let resolver = DateResolver::new("2024-06-01T12:00:00Z".parse().unwrap());
+    assert_eq!(TimeBound::parse("09:30", RangeEnd::Start, &resolver).unwrap(), TimeBound::TimeOfDay(chrono::NaiveTime::from_hms_opt(9, 30, 0).unwrap()));
+    // Days are New York midnight, and a month as the end includes the whole month
+    assert_eq!(TimeBound::parse("2024-01-02", RangeEnd::End, &resolver).unwrap(), TimeBound::At("2024-01-02T05:00:00Z".parse().unwrap()));
+    assert_eq!(TimeBound::parse("2024-01", RangeEnd::End, &resolver).unwrap(), TimeBound::At("2024-02-01T05:00:00Z".parse().unwrap()));
+    assert!(TimeBound::parse("25:00", RangeEnd::Start, &resolver).is_err());
+
+    let mut selection = MessageSelection::new(&["aapl".to_string()], &["quotes".to_string()]);
+    assert_eq!(selection.types, vec!["q"]);
+    selection.from = Some(TimeBound::parse("09:30", RangeEnd::Start, &resolver).unwrap());
+    selection.to = Some(TimeBound::parse("10:00", RangeEnd::End, &resolver).unwrap());
+    assert!(!selection.is_empty());
+    // 09:30 and 09:59:59 EST are in, 09:29 and 10:00 are out
+    assert!(selection.matches(&message("2024-01-02T14:30:00Z", "q", "AAPL")));
+    assert!(selection.matches(&message("2024-01-02T14:59:59Z", "q", "AAPL")));
+    assert!(!selection.matches(&message("2024-01-02T14:29:00Z", "q", "AAPL")));
+    assert!(!selection.matches(&message("2024-01-02T15:00:00Z", "q", "AAPL")));
+    // The same time of day under EDT
+    assert!(selection.matches(&message("2024-07-01T13:45:00Z", "q", "AAPL")));
+    assert!(!selection.matches(&message("2024-01-02T14:45:00Z", "t", "AAPL")));
+    assert!(!selection.matches(&message("2024-01-02T14:45:00Z", "q", "MSFT")));
+    assert!(MessageSelection::default().matches(&message("2024-01-02T03:00:00Z", "b", "MSFT")));
+}
+
+#[test]
+fn test_follow_reader() {
+    use algorithms_trading::follow::FollowReader;
+    use std::io::Write;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("live.jsonl");
+    fs::write(&path, "first\nsecond\n").unwrap();
+    assert!(FollowReader::open(&dir.path().join("live.jsonl.gz")).is_err());
+
+    let mut reader = FollowReader::open(&path).unwrap();
+    assert_eq!(reader.next_line().unwrap().as_deref(), Some("first"));
+    assert_eq!(reader.next_line().unwrap().as_deref(), Some("second"));
+    assert_eq!(reader.next_line().unwrap(), None);
+
+    // A line is only returned once its newline is written
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    write!(file, "thi").unwrap();
+    assert_eq!(reader.next_line().unwrap(), None);
+    writeln!(file, "rd\r").unwrap();
+    assert_eq!(reader.next_line().unwrap().as_deref(), Some("third"));
+
+    // A truncated file is read again from the start
+    fs::write(&path, "new\n").unwrap();
+    assert_eq!(reader.next_line().unwrap(), None);
+    assert_eq!(reader.next_line().unwrap().as_deref(), Some("new"));
+}
+
+#[test]
+fn test_capture_formats() {
+    use algorithms_trading::capture::{read_capture, CaptureFormat};
+
+    let dir = tempdir().unwrap();
+    assert_eq!(CaptureFormat::from_path(&dir.path().join("ticks.csv.gz")).unwrap(), CaptureFormat::Csv);
+    assert_eq!(CaptureFormat::from_path(&dir.path().join("ticks.jsonl")).unwrap(), CaptureFormat::Json);
+
+    // The header, console CSV with semicolons for commas, and the older four-column layout
+    let csv = dir.path().join("old.csv");
+    fs::write(&csv, concat!(
+        "received_time,message_type,symbol,data,event_time\n",
+        "2024-05-03 14:30:00.200 UTC,t,AAPL,{\"T\":\"t\";\"S\":\"AAPL\";\"i\":1;\"x\":\"V\";\"p\":150.0;\"s\":100;\"t\":\"2024-05-03T14:30:00.1Z\"},2024-05-03 14:30:00.100 UTC\n",
+        "2024-05-03 14:30:01.000 UTC,status,,\"{\"\"msg\"\":\"\"connected, ok\"\"}\"\n",
+    )).unwrap();
+    let mut messages = Vec::new();
+    read_capture(&csv, CaptureFormat::Csv, |data| {
+        messages.push(data);
+        Ok(())
+    }).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].data["p"], 150.0);
+    assert_eq!(messages[0].event_time.unwrap().to_rfc3339(), "2024-05-03T14:30:00.100+00:00");
+    assert_eq!(messages[1].data["msg"], "connected, ok");
+    assert!(messages[1].event_time.is_none());
+
+    #[cfg(feature = "sqlite")]
+    {
+        use algorithms_trading::sqlite::SqliteSink;
+
+        let path = dir.path().join("capture.db");
+        assert_eq!(CaptureFormat::from_path(&path).unwrap(), CaptureFormat::Sqlite);
+        let sink = SqliteSink::open(&path).unwrap();
+        for data in &messages {
+            sink.write_streaming_data(data).unwrap();
+        }
+        sink.close().unwrap();
+
+        let mut read = Vec::new();
+        read_capture(&path, CaptureFormat::Sqlite, |data| {
+            read.push(data);
+            Ok(())
+        }).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::to_value(&messages).unwrap());
+
+        // The trades view decodes the payload
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        let (symbol, price): (String, f64) = connection
+            .query_row("SELECT symbol, price FROM trades", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((symbol.as_str(), price), ("AAPL", 150.0));
+    }
+
+    #[cfg(feature = "parquet")]
+    {
+        use algorithms_trading::dataset::DatasetWriter;
+
+        let root = dir.path().join("dataset");
+        let writer = DatasetWriter::new(&root).unwrap();
+        for data in &messages {
+            writer.write_streaming_data(data).unwrap();
+        }
+        writer.close().unwrap();
+        assert_eq!(CaptureFormat::from_path(&root).unwrap(), CaptureFormat::Parquet);
+
+        // Only the trade is kept, with the keys of a streamed trade
+        let mut read = Vec::new();
+        read_capture(&root, CaptureFormat::Parquet, |data| {
+            read.push(data);
+            Ok(())
+        }).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].symbol.as_deref(), Some("AAPL"));
+        assert_eq!(read[0].received_time, messages[0].received_time);
+        assert_eq!(read[0].data["s"], 100);
+        assert_eq!(read[0].data["t"], "2024-05-03T14:30:00.100Z");
+    }
+}
+
+#[test]
+fn test_capture_dedup() {
+    use algorithms_trading::dedup::{Deduplicator, MessageKey};
+
+    let message = |message_type: &str, time: &str, data: serde_json::Value| StreamingData {
+        event_time: Some(time.parse().unwrap()),
+        ..create_mock_streaming_data(message_type, "AAPL", data)
+    };
+    let trade = message("t", "2024-05-03T14:30:00.1Z", serde_json::json!({"i": 1, "p": 150.0}));
+    // The failover client received the same trade later
+    let failover = StreamingData { received_time: trade.received_time + chrono::Duration::milliseconds(50), ..trade.clone() };
+    assert_eq!(MessageKey::of(&trade), MessageKey::of(&failover));
+
+    let quote = message("q", "2024-05-03T14:30:00.1Z", serde_json::json!({"bp": 149.9, "ap": 150.1}));
+    let other_quote = message("q", "2024-05-03T14:30:00.1Z", serde_json::json!({"bp": 149.9, "ap": 150.2}));
+    let next_trade = message("t", "2024-05-03T14:30:01Z", serde_json::json!({"i": 1, "p": 150.0}));
+
+    let mut deduplicator = Deduplicator::new();
+    let kept: Vec<bool> = [&trade, &quote, &failover, &other_quote, &quote, &next_trade]
+        .into_iter()
+        .map(|data| deduplicator.is_new(data))
+        .collect();
+    assert_eq!(kept, [true, true, false, true, false, true]);
+    assert_eq!(deduplicator.duplicates(), 2);
+}
+
+#[test]
+fn test_capture_diff() {
+    use algorithms_trading::capture_diff::{CaptureDiff, Issue};
+
+    let message = |message_type: &str, symbol: &str, time: &str, delay_ms: i64, data: serde_json::Value| {
+        let event_time: chrono::DateTime<chrono::Utc> = time.parse().unwrap();
+        StreamingData {
+            received_time: event_time + chrono::Duration::milliseconds(delay_ms),
+            event_time: Some(event_time),
+            ..create_mock_streaming_data(message_type, symbol, data)
+        }
+    };
+    let mut diff = CaptureDiff::new(0.001);
+    diff.add_first(&message("t", "AAPL", "2024-05-03T14:30:00Z", 10, serde_json::json!({"i": 1, "p": 150.0, "s": 100})));
+    diff.add_first(&message("t", "AAPL", "2024-05-03T14:30:01Z", 10, serde_json::json!({"i": 2, "p": 150.1, "s": 100})));
+    diff.add_first(&message("q", "AAPL", "2024-05-03T14:30:02Z", 10, serde_json::json!({"bp": 150.0, "ap": 150.2})));
+    diff.add_first(&create_mock_streaming_data("success", "", serde_json::json!("authenticated")));
+    // The second client was 5-25ms behind, missed trade 2, saw a trade the
+    // first did not, and reported a different ask
+    diff.add_second(&message("t", "AAPL", "2024-05-03T14:30:00Z", 15, serde_json::json!({"i": 1, "p": 150.0001, "s": 100})));
+    diff.add_second(&message("q", "AAPL", "2024-05-03T14:30:02Z", 35, serde_json::json!({"bp": 150.0, "ap": 150.4})));
+    diff.add_second(&message("t", "MSFT", "2024-05-03T14:30:03Z", 15, serde_json::json!({"i": 7, "p": 400.0, "s": 5})));
+
+    let (symbols, differences) = diff.finish();
+    assert_eq!(symbols.len(), 2);
+    let aapl = &symbols[0];
+    assert_eq!((aapl.first, aapl.second, aapl.matched), (3, 2, 2));
+    assert_eq!((aapl.only_first, aapl.only_second, aapl.mismatched), (1, 0, 1));
+    assert_eq!(aapl.median_skew_ms, Some(15.0));
+    assert_eq!((aapl.min_skew_ms, aapl.max_skew_ms), (Some(5.0), Some(25.0)));
+    assert_eq!(symbols[1].only_second, 1);
+    assert!(symbols[1].median_skew_ms.is_none());
+
+    let issues: Vec<_> = differences.iter().map(|d| (d.symbol.as_str(), d.issue, d.field.as_deref())).collect();
+    assert_eq!(issues, [
+        ("AAPL", Issue::OnlyFirst, None),
+        ("AAPL", Issue::Mismatch, Some("ask")),
+        ("MSFT", Issue::OnlySecond, None),
+    ]);
+    assert_eq!(differences[0].id, Some(2));
+    assert_eq!(
+        differences[1].format(&DataFormat::Csv).unwrap(),
+        "AAPL,2024-05-03T14:30:02Z,q,,mismatch,ask,150.2,150.4"
+    );
+}
+
+#[cfg(feature = "duckdb")]
+#[test]
+fn test_duckdb_query() {
+    use algorithms_trading::duckdb_query::QueryDatabase;
+
+    let database = QueryDatabase::open_in_memory().unwrap();
+    let mut loader = database.loader().unwrap();
+    for (symbol, price, size) in [("AAPL", 150.0, 100), ("AAPL", 151.0, 300), ("MSFT", 400.5, 10)] {
+        loader.append(&create_mock_streaming_data("t", symbol, serde_json::json!({
+            "T": "t", "S": symbol, "i": 1, "x": "V", "p": price, "s": size, "t": "2024-05-03T14:30:00Z"
+        }))).unwrap();
+    }
+    loader.append(&create_mock_streaming_data("q", "AAPL", serde_json::json!({"bp": 150.0, "ap": 150.1}))).unwrap();
+    loader.finish().unwrap();
+
+    let result = database
+        .query("SELECT symbol, count(*) AS trades, sum(price * size) / sum(size) AS vwap FROM trades GROUP BY symbol ORDER BY symbol")
+        .unwrap();
+    assert_eq!(result.columns, ["symbol", "trades", "vwap"]);
+    assert_eq!(result.rows.len(), 2);
+    assert_eq!(result.rows[0], [serde_json::json!("AAPL"), serde_json::json!(2), serde_json::json!(150.75)]);
+
+    assert_eq!(result.lines(&DataFormat::Plain).unwrap(), [
+        "symbol  trades  vwap",
+        "------  ------  ------",
+        "AAPL    2       150.75",
+        "MSFT    1       400.5",
+    ]);
+    assert_eq!(result.lines(&DataFormat::Csv).unwrap()[1], "AAPL,2,150.75");
+    // JSON keeps the columns in query order
+    assert_eq!(result.lines(&DataFormat::Json).unwrap()[1], r#"{"symbol":"MSFT","trades":1,"vwap":400.5}"#);
+
+    // Times come back as RFC 3339 in UTC
+    let times = database.query("SELECT max(received_time) AS last, count(*) AS quotes FROM quotes").unwrap();
+    assert!(times.rows[0][0].as_str().unwrap().ends_with('Z'));
+    assert_eq!(times.rows[0][1], 1);
+
+    assert!(database.query("SELECT nothing FROM nowhere").is_err());
+    assert!(database.query("SELECT [1, 2] AS list").unwrap_err().to_string().contains("cast it to VARCHAR"));
+}
+
+#[test]
+fn test_chart_rendering() {
+    use algorithms_trading::chart::{render_text, ChartKind, ChartOptions, Indicator};
+    use algorithms_trading::resample::OhlcvBar;
+
+    let bar = |minute: u32, open: f64, close: f64| OhlcvBar {
+        symbol: "AAPL".to_string(),
+        timestamp: format!("2024-05-03T14:{:02}:00Z", minute).parse().unwrap(),
+        open,
+        high: open.max(close) + 0.5,
+        low: open.min(close) - 0.5,
+        close,
+        volume: 100 * (minute as u64 + 1),
+        trade_count: 1,
+        vwap: (open + close) / 2.0,
+    };
+    let bars = [bar(0, 10.0, 11.0), bar(1, 11.0, 12.0), bar(2, 12.0, 10.0), bar(3, 10.0, 13.0)];
+
+    assert_eq!(Indicator::parse("SMA:2").unwrap(), Indicator::Sma(2));
+    assert_eq!(Indicator::parse("vwap").unwrap(), Indicator::Vwap);
+    assert!(Indicator::parse("sma:0").is_err());
+    assert!(Indicator::parse("rsi:14").is_err());
+    assert_eq!(Indicator::Sma(2).values(&bars), [None, Some(11.5), Some(11.0), Some(11.5)]);
+    // The EMA starts from the SMA of its first 3 closes, then weighs each close by 2 / (3 + 1)
+    assert_eq!(Indicator::Ema(3).values(&bars), [None, None, Some(11.0), Some(12.0)]);
+
+    let options = ChartOptions {
+        kind: ChartKind::Candle,
+        indicators: vec![Indicator::Sma(2)],
+        volume: true,
+        width: 13,
+        height: 8,
+        ascii: true,
+        color: false,
+    };
+    // The latest 2 bars fit in 13 characters
+    assert_eq!(render_text(&bars, &options), [
+        "AAPL 2024-05-03 14:02 to 2024-05-03 14:03: 2 bars, last 13.00 (+8.33%)",
+        "+ SMA(2)",
+        "    13.50 + |",
+        "          | #",
+        "          ||#",
+        "          |=#",
+        "    11.21 +=#",
+        "          |=#",
+        "          |=#",
+        "     9.50 +||",
+        "      400 +##",
+        "          |##",
+        "          +--",
+        "           2024-05-03 14:02",
+    ]);
+
+    let line = render_text(&bars, &ChartOptions { kind: ChartKind::Line, volume: false, ..options.clone() });
+    assert_eq!(&line[2..10], [
+        "    13.00 + *",
+        "          | |",
+        "          | |",
+        "          | |",
+        "    11.29 + +",
+        "          |+|",
+        "          | |",
+        "    10.00 +*",
+    ]);
+
+    assert!(render_text(&[], &options).is_empty());
+
+    let colored = render_text(&bars, &ChartOptions { ascii: false, color: true, ..options });
+    assert!(colored[5].contains("\x1b[31m░\x1b[0m\x1b[32m█\x1b[0m"));
+}
+
+#[cfg(feature = "charts")]
+#[test]
+fn test_chart_png() {
+    use algorithms_trading::chart::{write_png, ChartKind, ChartOptions, Indicator};
+    use algorithms_trading::resample::OhlcvBar;
+
+    let bars: Vec<OhlcvBar> = (0..50)
+        .map(|minute| OhlcvBar {
+            symbol: "AAPL".to_string(),
+            timestamp: chrono::DateTime::parse_from_rfc3339("2024-05-03T14:00:00Z").unwrap().to_utc() + chrono::Duration::minutes(minute),
+            open: 100.0 + minute as f64,
+            high: 102.0 + minute as f64,
+            low: 99.0 + minute as f64,
+            close: 101.0 + minute as f64,
+            volume: 1000,
+            trade_count: 10,
+            vwap: 100.5 + minute as f64,
+        })
+        .collect();
+    let options = ChartOptions {
+        kind: ChartKind::Candle,
+        indicators: vec![Indicator::Ema(10), Indicator::Vwap],
+        volume: true,
+        width: 320,
+        height: 200,
+        ascii: false,
+        color: false,
+    };
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("chart.png");
+    write_png(&bars, &options, &path).unwrap();
+
+    let image = fs::read(&path).unwrap();
+    assert_eq!(&image[..8], b"\x89PNG\r\n\x1a\n");
+    // Width and height of the IHDR chunk
+    assert_eq!(&image[16..24], [0, 0, 1, 64, 0, 0, 0, 200]);
+
+    // Too small an image is enlarged to fit the labels
+    let options = ChartOptions { kind: ChartKind::Line, indicators: Vec::new(), volume: false, width: 10, height: 10, ..options };
+    write_png(&bars, &options, &path).unwrap();
+    let image = fs::read(&path).unwrap();
+    assert_eq!(&image[16..24], [0, 0, 1, 24, 0, 0, 0, 120]);
+}
+
+#[tokio::test]
+async fn test_capture_replay() {
+    use algorithms_trading::capture::CaptureFormat;
+    use algorithms_trading::replay::{parse_replay_speed, run_replay, ReplaySpeed};
+
+    assert_eq!(parse_replay_speed("MAX").unwrap(), ReplaySpeed::Max);
+    assert_eq!(parse_replay_speed("10x").unwrap(), ReplaySpeed::Scaled(10.0));
+    assert_eq!(parse_replay_speed("0.5").unwrap(), ReplaySpeed::Scaled(0.5));
+    assert!(parse_replay_speed("0").is_err());
+    assert!(parse_replay_speed("fast").is_err());
+    let first: chrono::DateTime<chrono::Utc> = "2024-05-03T14:30:00Z".parse().unwrap();
+    let later = first + chrono::Duration::seconds(3);
+    assert_eq!(ReplaySpeed::Scaled(2.0).due(first, later), Some(std::time::Duration::from_millis(1500)));
+    assert_eq!(ReplaySpeed::Scaled(1.0).due(later, first), Some(std::time::Duration::ZERO));
+    assert_eq!(ReplaySpeed::Max.due(first, later), None);
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("capture.jsonl");
+    let lines: Vec<String> = (0..4)
+        .map(|index| {
+            let data = StreamingData {
+                received_time: first + chrono::Duration::milliseconds(100 * index),
+                ..create_mock_streaming_data("t", if index % 2 == 0 { "AAPL" } else { "MSFT" }, serde_json::json!({"i": index, "p": 150.0, "s": 100}))
+            };
+            serde_json::to_string(&data).unwrap()
+        })
+        .collect();
+    fs::write(&path, lines.join("\n") + "\nStatus: done\n").unwrap();
+
+    let sink = std::sync::Arc::new(CollectingSink::default());
+    let mut config = StreamingConfig::new(Box::new(CollectingSink::default()));
+    config.output_mode = vec![sink.clone()];
+    config.filter = Some("symbol==AAPL".parse().unwrap());
+    // 300ms of messages at 10 times their speed
+    let started = std::time::Instant::now();
+    let stats = run_replay(&path, CaptureFormat::Json, ReplaySpeed::Scaled(10.0), &config).await.unwrap();
+    assert!(started.elapsed() >= std::time::Duration::from_millis(30));
+    assert_eq!((stats.read, stats.written, stats.filtered), (4, 2, 2));
+    let records = sink.records.lock().unwrap();
+    let ids: Vec<_> = records.iter().map(|data| data.data["i"].as_i64().unwrap()).collect();
+    assert_eq!(ids, [0, 2]);
+    assert_eq!(records[1].received_time, first + chrono::Duration::milliseconds(200));
+}
+
+#[tokio::test]
+async fn test_simulated_session() {
+    use algorithms_trading::simulator::{run_simulator, SimulatorConfig};
+
+    let run = |settings: SimulatorConfig| async move {
+        let sink = std::sync::Arc::new(CollectingSink::default());
+        let mut config = StreamingConfig::builder().trades(["AAPL", "MSFT"]).quotes(["AAPL"]).bars(["SPY"]).build().unwrap();
+        config.output_mode = vec![sink.clone()];
+        let (handle, mut commands) = subscription_channel();
+        handle.subscribe(StreamingDataType::Quotes, vec!["NVDA".to_string()]).unwrap();
+        let stats = run_simulator(&settings, &config, &mut commands).await.unwrap();
+        let records = sink.records.lock().unwrap().clone();
+        (stats, records)
+    };
+    let settings = SimulatorConfig { rate: 5000.0, seed: Some(42), max_messages: Some(300), ..SimulatorConfig::default() };
+    let (stats, records) = run(settings.clone()).await;
+    assert_eq!(stats.trades + stats.quotes, 300);
+    assert_eq!(records[0].message_type, "control");
+    assert_eq!(records[0].data["active"], serde_json::json!(["AAPL", "NVDA"]));
+    assert_eq!(records.len(), 301);
+    let symbols: std::collections::HashSet<_> = records[1..].iter().map(|data| data.symbol.clone().unwrap()).collect();
+    assert!(symbols.contains("NVDA") && symbols.contains("MSFT"));
+    // SPY only trades into its bars
+    assert!(!symbols.contains("SPY"));
+    for data in &records[1..] {
+        assert!(data.event_time.is_some());
+        match data.message_type.as_str() {
+            "t" => assert!(data.data["p"].as_f64().unwrap() > 0.0 && data.data["s"].as_u64().unwrap() > 0),
+            "q" => assert!(data.data["ap"].as_f64().unwrap() > data.data["bp"].as_f64().unwrap()),
+            other => panic!("unexpected message type {}", other),
+        }
+    }
+    // A seed repeats the prices, however fast the run goes
+    let (_, again) = run(settings).await;
+    let prices = |records: &[StreamingData]| -> Vec<String> { records[1..].iter().map(|data| format!("{} {} {}", data.message_type, data.data["S"], data.data.get("p").unwrap_or(&data.data["bp"]))).collect() };
+    assert_eq!(prices(&records), prices(&again));
+
+    let filtered = SimulatorConfig { rate: 5000.0, max_messages: Some(50), ..SimulatorConfig::default() };
+    let sink = std::sync::Arc::new(CollectingSink::default());
+    let mut config = StreamingConfig::builder().trades(["AAPL"]).quotes(["AAPL"]).filter("type==q".parse().unwrap()).build().unwrap();
+    config.output_mode = vec![sink.clone()];
+    let (_handle, mut commands) = subscription_channel();
+    let stats = run_simulator(&filtered, &config, &mut commands).await.unwrap();
+    assert_eq!(stats.filtered, stats.trades);
+    assert_eq!(sink.records.lock().unwrap().len() as u64, stats.quotes);
+    assert!(run_simulator(&SimulatorConfig { rate: 0.0, ..filtered }, &config, &mut commands).await.is_err());
+}
+
+#[test]
+fn test_raw_frame_recording() {
+    use algorithms_trading::capture::{read_capture, CaptureFormat};
+    use algorithms_trading::raw_frames::{raw_frame, RawRecorder, MARKET_DATA, TRADE_UPDATES};
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("frames.ndjson.gz");
+    let recorder = RawRecorder::create(&path, false).unwrap();
+    let trade = StreamingMessage {
+        message_type: "t".to_string(),
+        message: None,
+        data: serde_json::json!({"S": "AAPL", "p": 150.25, "s": 100, "t": "2024-05-03T14:30:00Z", "x": "V", "i": 1, "z": "C"}),
+    };
+    recorder.record_message(Some(1), &trade).unwrap();
+    // Frames holding several messages, one of them malformed, and frames that are not JSON
+    let frame = r#"[{"T":"q","S":"MSFT","bp":410.1,"bs":2,"ap":410.2,"as":3,"t":"2024-05-03T14:30:01Z"},{"T":"t","S":"MSFT","p":"oops"}]"#;
+    recorder.record(MARKET_DATA, None, frame).unwrap();
+    recorder.record(MARKET_DATA, None, "not json").unwrap();
+    let update = create_mock_trade_update("fill");
+    recorder.record(TRADE_UPDATES, None, &serde_json::json!({"stream": "trade_updates", "data": update.data}).to_string()).unwrap();
+    recorder.record(TRADE_UPDATES, None, r#"{"stream":"authorization","data":{"status":"authorized"}}"#).unwrap();
+    recorder.close().unwrap();
+
+    let lines: Vec<String> = std::io::BufRead::lines(algorithms_trading::compression::open_reader(&path).unwrap()).map(Result::unwrap).collect();
+    assert_eq!(lines.len(), 5);
+    let first = raw_frame(&lines[0]).unwrap();
+    assert_eq!((first.source.as_str(), first.shard), (MARKET_DATA, Some(1)));
+    assert_eq!(raw_frame(&lines[1]).unwrap().frame, frame);
+    assert!(raw_frame("Status: connected").is_none());
+
+    let mut records = Vec::new();
+    read_capture(&path, CaptureFormat::Json, |data| {
+        records.push(data);
+        Ok(())
+    })
+    .unwrap();
+    let types: Vec<_> = records.iter().map(|data| data.message_type.as_str()).collect();
+    assert_eq!(types, ["t", "q", "trade_update"]);
+    assert_eq!(records[0].data["p"], 150.25);
+    assert_eq!(records[1].symbol.as_deref(), Some("MSFT"));
+    assert_eq!(records[0].received_time, first.received_time);
+}
+
+#[test]
+fn test_technical_indicators() {
+    use algorithms_trading::indicators::{Atr, BollingerBands, BollingerValue, Ema, Indicator, Macd, MacdValue, Obv, Rsi, Sma, Stochastic, Vwap, Wma};
+    use algorithms_trading::resample::OhlcvBar;
+
+    let bar = |time: &str, high: f64, low: f64, close: f64, volume: u64, vwap: f64| OhlcvBar {
+        symbol: "AAPL".to_string(),
+        timestamp: time.parse().unwrap(),
+        open: close,
+        high,
+        low,
+        close,
+        volume,
+        trade_count: 1,
+        vwap,
+    };
+    let bars = [
+        bar("2024-05-03T14:30:00Z", 11.0, 9.0, 10.0, 100, 10.0),
+        bar("2024-05-03T14:31:00Z", 12.0, 10.0, 11.0, 300, 11.0),
+        bar("2024-05-03T14:32:00Z", 15.0, 11.0, 14.0, 200, 0.0),
+        // The next trading day
+        bar("2024-05-06T13:30:00Z", 14.0, 12.0, 13.0, 50, 13.5),
+    ];
+    let round = |value: Option<f64>| value.map(|value| (value * 1000.0).round() / 1000.0);
+
+    let mut sma = Sma::new(3).unwrap();
+    assert_eq!([1.0, 2.0, 3.0, 4.0, 5.0].map(|value| sma.next(value)), [None, None, Some(2.0), Some(3.0), Some(4.0)]);
+    let mut wma = Wma::new(3).unwrap();
+    assert_eq!([1.0, 2.0, 3.0, 4.0].map(|value| round(wma.next(value))), [None, None, Some(2.333), Some(3.333)]);
+    let mut ema = Ema::new(2).unwrap();
+    assert_eq!([1.0, 2.0, 3.0].map(|value| round(ema.next(value))), [None, Some(1.5), Some(2.5)]);
+    let mut rsi = Rsi::new(2).unwrap();
+    assert_eq!([1.0, 2.0, 3.0, 2.0].map(|value| rsi.next(value)), [None, None, Some(100.0), Some(50.0)]);
+    let mut macd = Macd::new(2, 3, 2).unwrap();
+    let lines: Vec<_> = [1.0, 2.0, 3.0, 4.0, 5.0].into_iter().map(|value| macd.next(value)).collect();
+    assert_eq!(lines[..3], [None, None, None]);
+    assert_eq!(lines[3], Some(MacdValue { macd: 0.5, signal: 0.5, histogram: 0.0 }));
+    assert!(Macd::new(26, 12, 9).is_err());
+    let mut bands = BollingerBands::new(2, 2.0).unwrap();
+    assert_eq!(bands.next(1.0), None);
+    assert_eq!(bands.next(3.0), Some(BollingerValue { upper: 4.0, middle: 2.0, lower: 0.0 }));
+    assert!(Sma::new(0).is_err());
+
+    // Indicators of bars, over a slice or one bar at a time
+    assert_eq!(Sma::new(2).unwrap().compute(&bars), [None, Some(10.5), Some(12.5), Some(13.5)]);
+    let mut atr = Atr::new(2).unwrap();
+    assert_eq!(atr.compute(&bars[..3]), [None, Some(2.0), Some(3.0)]);
+    atr.reset();
+    assert_eq!(atr.update(&bars[0]), None);
+    let stochastic = Stochastic::new(2, 2).unwrap().compute(&bars[..3]);
+    assert_eq!(stochastic[1], None);
+    let last = stochastic[2].unwrap();
+    assert_eq!((round(Some(last.k)), round(Some(last.d))), (Some(80.0), Some(73.333)));
+    assert_eq!(Obv::new().compute(&bars), [Some(0.0), Some(300.0), Some(500.0), Some(450.0)]);
+    // The third bar has no VWAP, so its typical price counts; the fourth starts a new day
+    let vwap = Vwap::new().compute(&bars);
+    assert_eq!(round(vwap[2]), Some(11.611));
+    assert_eq!(vwap[3], Some(13.5));
+}
+
+#[test]
+fn test_indicator_columns() {
+    use algorithms_trading::indicators::{column_names, IndicatorColumns, IndicatorSpec};
+    use algorithms_trading::resample::OhlcvBar;
+
+    let specs = IndicatorSpec::parse_list("sma(2), RSI ,macd(2,3,2),bbands(2,1.5),stoch,obv,vwap").unwrap();
+    assert_eq!(specs[1], IndicatorSpec::Rsi(14));
+    assert_eq!(specs[2], IndicatorSpec::Macd { fast: 2, slow: 3, signal: 2 });
+    assert_eq!(specs[4], IndicatorSpec::Stochastic { k: 14, d: 3 });
+    assert_eq!(
+        column_names(&specs),
+        [
+            "sma_2", "rsi_14", "macd_2_3_2", "macd_signal_2_3_2", "macd_hist_2_3_2", "bb_upper_2_1.5", "bb_middle_2_1.5",
+            "bb_lower_2_1.5", "stoch_k_14_3", "stoch_d_14_3", "obv", "day_vwap",
+        ]
+    );
+    for invalid in ["", "sma", "sma(0)", "sma(2", "macd(3,2,1)", "bbands(20,x)", "sma(2),sma(2)", "adx(14)"] {
+        assert!(IndicatorSpec::parse_list(invalid).is_err(), "{:?} parsed", invalid);
+    }
+
+    let bar = |symbol: &str, close: f64| OhlcvBar {
+        symbol: symbol.to_string(),
+        timestamp: "2024-05-03T14:30:00Z".parse().unwrap(),
+        open: close,
+        high: close,
+        low: close,
+        close,
+        volume: 100,
+        trade_count: 1,
+        vwap: close,
+    };
+    let specs = IndicatorSpec::parse_list("sma(2),obv").unwrap();
+    let mut columns = IndicatorColumns::new(&specs).unwrap();
+    assert_eq!(columns.update(&bar("AAPL", 10.0)), [None, Some(0.0)]);
+    assert_eq!(columns.update(&bar("AAPL", 12.0)), [Some(11.0), Some(100.0)]);
+    assert_eq!(columns.update(&bar("AAPL", 11.0)), [Some(11.5), Some(0.0)]);
+
+    #[cfg(feature = "parquet")]
+    {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("bars.parquet");
+        let rows = vec![(bar("AAPL", 10.0), vec![None, Some(0.0)]), (bar("AAPL", 12.0), vec![Some(11.0), Some(100.0)])];
+        algorithms_trading::dataset::write_bars_file(&path, &column_names(&specs), &rows).unwrap();
+        let file = fs::File::open(&path).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap().next().unwrap().unwrap();
+        let schema = batch.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["symbol", "timestamp", "open", "high", "low", "close", "volume", "trade_count", "vwap", "sma_2", "obv"]);
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.column(9).null_count(), 1);
+    }
+}
+
+#[test]
+fn test_strategy_engine() {
+    use algorithms_trading::resample::OhlcvBar;
+    use algorithms_trading::decode_streaming_message;
+    use algorithms_trading::strategy::{Context, Engine, Fill, MarketEvent, OrderRequest, Side, Strategy, StrategySink};
+
+    /// Buys 10 shares on the first bar, and sells them at the close after the fill
+    #[derive(Default)]
+    struct BuyAndHold {
+        events: Vec<String>,
+    }
+
+    impl Strategy for BuyAndHold {
+        fn on_start(&mut self, _context: &mut Context) {
+            self.events.push("start".to_string());
+        }
+
+        fn on_bar(&mut self, bar: &OhlcvBar, context: &mut Context) {
+            self.events.push(format!("bar {}", bar.close));
+            if context.position(&bar.symbol) == 0.0 && self.events.len() == 2 {
+                context.buy(&bar.symbol, 10.0);
+            }
+        }
+
+        fn on_fill(&mut self, fill: &Fill, context: &mut Context) {
+            self.events.push(format!("fill {} at {}", context.position(&fill.symbol), fill.price));
+        }
+
+        fn on_stop(&mut self, context: &mut Context) {
+            self.events.push("stop".to_string());
+            let position = context.position("AAPL");
+            if position > 0.0 {
+                context.sell("AAPL", position);
+            }
+        }
+    }
+
+    let bar = |minute: u32, close: f64| {
+        MarketEvent::from_streaming_data(&create_mock_bar_data("AAPL", &format!("2024-05-03T14:{}:00Z", minute), close)).unwrap()
+    };
+    let mut fill = match MarketEvent::from_streaming_data(&decode_streaming_message(&create_mock_trade_update("partial_fill")).unwrap().unwrap()) {
+        Some(MarketEvent::Fill(fill)) => fill,
+        other => panic!("not a fill: {:?}", other),
+    };
+    assert_eq!((fill.side, fill.quantity, fill.price, fill.order_id.as_deref()), (Side::Buy, 5.0, 150.25, Some("order-1")));
+    fill.quantity = 10.0;
+    assert!(MarketEvent::from_streaming_data(&decode_streaming_message(&create_mock_trade_update("new")).unwrap().unwrap()).is_none());
+    match MarketEvent::from_streaming_data(&create_mock_quote_data("MSFT", 400.0, 400.5)) {
+        Some(MarketEvent::Quote(quote)) => assert_eq!((quote.bid_price, quote.ask_price), (400.0, 400.5)),
+        other => panic!("not a quote: {:?}", other),
+    }
+
+    // Over historical events
+    let mut engine = Engine::new(BuyAndHold::default());
+    let mut orders: Vec<OrderRequest> = Vec::new();
+    let events = [bar(30, 150.0), MarketEvent::Fill(fill.clone()), bar(31, 151.0)];
+    let stats = engine.run(events, &mut orders).unwrap();
+    assert_eq!((stats.bars, stats.fills, stats.orders), (2, 1, 2));
+    assert_eq!(engine.positions()["AAPL"], 10.0);
+    assert_eq!(orders.iter().map(|order| (order.side, order.quantity)).collect::<Vec<_>>(), [(Side::Buy, 10.0), (Side::Sell, 10.0)]);
+    assert_eq!(engine.strategy().events, ["start", "bar 150", "fill 10 at 150.25", "bar 151", "stop"]);
+    assert!(engine.stop().is_empty());
+
+    // As an output of a session: quotes and other messages reach the strategy too
+    let sink = StrategySink::new(BuyAndHold::default(), Box::new(Vec::<OrderRequest>::new()));
+    sink.write_streaming_data(&create_mock_bar_data("AAPL", "2024-05-03T14:30:00Z", 150.0)).unwrap();
+    sink.write_streaming_data(&create_mock_quote_data("AAPL", 150.0, 150.1)).unwrap();
+    sink.write_streaming_data(&create_mock_streaming_data("success", "", serde_json::json!("authenticated"))).unwrap();
+    sink.close().unwrap();
+    let stats = sink.stats();
+    assert_eq!((stats.bars, stats.quotes, stats.orders), (1, 1, 1));
+}
+
+#[test]
+fn test_backtest() {
+    use algorithms_trading::backtest::{run_backtest, BacktestConfig};
+    use algorithms_trading::costs::{FixedBps, PerOrder};
+    use algorithms_trading::resample::OhlcvBar;
+    use algorithms_trading::strategies::{self, StrategyParams};
+    use algorithms_trading::strategy::{Context, MarketEvent, OrderType, Side, Strategy};
+
+    let bar = |minute: u32, open: f64, close: f64| {
+        MarketEvent::Bar(OhlcvBar {
+            symbol: "AAPL".to_string(),
+            timestamp: format!("2024-05-03T14:{}:00Z", minute).parse().unwrap(),
+            open,
+            high: open.max(close) + 1.0,
+            low: open.min(close) - 1.0,
+            close,
+            volume: 1000,
+            trade_count: 10,
+            vwap: close,
+        })
+    };
+    let bars = || vec![bar(30, 100.0, 101.0), bar(31, 102.0, 103.0), bar(32, 104.0, 105.0), bar(33, 106.0, 107.0)];
+
+    // Buys at the open of the bar after the first, and holds
+    let strategy = strategies::create("buy-and-hold", &StrategyParams::parse(&["quantity=10".to_string()]).unwrap()).unwrap();
+    let config = BacktestConfig {
+        initial_cash: 10_000.0,
+        slippage: std::sync::Arc::new(FixedBps::new(100.0).unwrap()),
+        commission: std::sync::Arc::new(PerOrder::new(1.0).unwrap()),
+        ..BacktestConfig::default()
+    };
+    let result = run_backtest(strategy, bars(), &config).unwrap();
+    assert_eq!(result.strategy, "buy-and-hold");
+    assert_eq!(result.fills.len(), 1);
+    assert_eq!((result.fills[0].side, result.fills[0].price), (Side::Buy, 103.02));
+    assert_eq!(result.fills[0].timestamp, "2024-05-03T14:31:00Z".parse::<chrono::DateTime<chrono::Utc>>().unwrap());
+    assert_eq!(result.positions["AAPL"], 10.0);
+    assert!((result.final_cash - (10_000.0 - 1030.2 - 1.0)).abs() < 1e-9);
+    assert!((result.final_equity - (result.final_cash + 1070.0)).abs() < 1e-9);
+    let equity: Vec<f64> = result.equity_curve.iter().map(|point| (point.equity * 100.0).round() / 100.0).collect();
+    assert_eq!(equity, [10_000.0, 9_998.8, 10_018.8, 10_038.8]);
+    assert_eq!(result.commission(), 1.0);
+
+    // Latency pushes the fill to a later bar
+    let strategy = strategies::create("buy-and-hold", &StrategyParams::default()).unwrap();
+    let config = BacktestConfig { latency: std::time::Duration::from_secs(90), ..BacktestConfig::default() };
+    let result = run_backtest(strategy, bars(), &config).unwrap();
+    assert_eq!((result.fills[0].quantity, result.fills[0].price), (100.0, 104.0));
+
+    /// A limit buy below the market, and a market sell of the position on stop
+    struct LimitThenExit;
+
+    impl Strategy for LimitThenExit {
+        fn on_start(&mut self, context: &mut Context) {
+            context.submit("AAPL", Side::Buy, 5.0, OrderType::Limit { limit_price: 103.5 });
+            context.submit("AAPL", Side::Buy, 5.0, OrderType::Limit { limit_price: 50.0 });
+        }
+
+        fn on_stop(&mut self, context: &mut Context) {
+            let position = context.position("AAPL");
+            context.sell("AAPL", position);
+        }
+    }
+
+    let result = run_backtest(LimitThenExit, bars(), &BacktestConfig::default()).unwrap();
+    // The first bar is the one the orders were placed on; the second reaches the limit
+    assert_eq!(result.fills.iter().map(|fill| (fill.side, fill.price)).collect::<Vec<_>>(), [(Side::Buy, 102.0), (Side::Sell, 107.0)]);
+    assert_eq!(result.unfilled, 1);
+    assert_eq!(result.positions["AAPL"], 0.0);
+    assert!((result.total_return() - 25.0 / 100_000.0).abs() < 1e-12);
+
+    assert!(strategies::create("nope", &StrategyParams::default()).is_err());
+    assert!(strategies::create("buy-and-hold", &StrategyParams::parse(&["size=1".to_string()]).unwrap()).is_err());
+    assert!(run_backtest(LimitThenExit, bars(), &BacktestConfig { initial_cash: -1.0, ..BacktestConfig::default() }).is_err());
+    assert!(FixedBps::new(-1.0).is_err());
+}
+
+#[test]
+fn test_backtest_report() {
+    use algorithms_trading::backtest::{run_backtest, BacktestConfig};
+    use algorithms_trading::backtest_report::{BacktestReport, Direction, ROUND_TRIPS_CSV_HEADER};
+    use algorithms_trading::resample::OhlcvBar;
+    use algorithms_trading::strategy::{Context, MarketEvent, Strategy};
+
+    let bar = |day: u32, open: f64, close: f64| {
+        MarketEvent::Bar(OhlcvBar {
+            symbol: "AAPL".to_string(),
+            timestamp: format!("2024-05-{:02}T20:00:00Z", day).parse().unwrap(),
+            open,
+            high: open.max(close),
+            low: open.min(close),
+            close,
+            volume: 1000,
+            trade_count: 10,
+            vwap: close,
+        })
+    };
+    let bars = vec![bar(6, 100.0, 100.0), bar(7, 102.0, 104.0), bar(8, 103.0, 101.0), bar(9, 99.0, 98.0), bar(10, 97.0, 96.0)];
+
+    /// Buys 10 on the first day and sells 20 on the third, turning the long into a short it keeps
+    struct LongThenShort(usize);
+
+    impl Strategy for LongThenShort {
+        fn on_bar(&mut self, bar: &OhlcvBar, context: &mut Context) {
+            self.0 += 1;
+            match self.0 {
+                1 => context.buy(&bar.symbol, 10.0),
+                3 => context.sell(&bar.symbol, 20.0),
+                _ => {}
+            }
+        }
+    }
+
+    let result = run_backtest(LongThenShort(0), bars, &BacktestConfig::default()).unwrap();
+    let report = BacktestReport::new(&result);
+
+    // The long is bought at 102 and sold at 99; the short sold at 99 is still open at 96
+    assert_eq!(report.round_trips.len(), 2);
+    let (long, short) = (&report.round_trips[0], &report.round_trips[1]);
+    assert_eq!((long.direction, long.quantity, long.entry_price, long.exit_price, long.open), (Direction::Long, 10.0, 102.0, 99.0, false));
+    assert!((long.pnl + 30.0).abs() < 1e-9);
+    assert_eq!((short.direction, short.quantity, short.entry_price, short.exit_price, short.open), (Direction::Short, 10.0, 99.0, 96.0, true));
+    assert!((short.pnl - 30.0).abs() < 1e-9);
+    assert_eq!(short.exit_time, report.end.unwrap());
+
+    assert!(report.total_return.abs() < 1e-12);
+    assert!(report.annualized_return.unwrap().abs() < 1e-12);
+    assert!((report.max_drawdown - 40.0 / 100_020.0).abs() < 1e-12);
+    assert_eq!(report.max_drawdown_days, 3.0);
+    assert_eq!(report.exposure, 0.75);
+    assert_eq!((report.fills, report.closed_trades), (2, 1));
+    assert_eq!((report.win_rate, report.profit_factor, report.average_trade), (Some(0.0), Some(0.0), Some(-30.0)));
+    // Equity ends where it started, so the daily returns average about 0
+    assert!(report.sharpe_ratio.unwrap().abs() < 0.1);
+    assert!(report.sortino_ratio.unwrap().abs() < 0.1);
+
+    let dir = tempdir().unwrap();
+    let written = report.write(&dir.path().join("report.csv"), &result.equity_curve).unwrap();
+    assert_eq!(written.len(), 2);
+    let metrics = fs::read_to_string(&written[0]).unwrap();
+    assert!(metrics.starts_with("metric,value\nstrategy,"));
+    assert!(metrics.contains("\nexposure,0.75\n"));
+    let trades = fs::read_to_string(dir.path().join("report.trades.csv")).unwrap();
+    let lines: Vec<&str> = trades.lines().collect();
+    assert_eq!(lines[0], ROUND_TRIPS_CSV_HEADER);
+    assert_eq!(lines[2], "AAPL,short,2024-05-09T20:00:00Z,2024-05-10T20:00:00Z,10,99.0000,96.0000,30.00,0.030303,0.00,true");
+
+    report.write(&dir.path().join("report.json"), &result.equity_curve).unwrap();
+    let json: BacktestReport = serde_json::from_str(&fs::read_to_string(dir.path().join("report.json")).unwrap()).unwrap();
+    assert_eq!(json.round_trips, report.round_trips);
+    assert_eq!(serde_json::to_value(&report).unwrap()["round_trips"][0]["return"], serde_json::json!(long.return_pct));
+
+    report.write(&dir.path().join("report.html"), &result.equity_curve).unwrap();
+    let html = fs::read_to_string(dir.path().join("report.html")).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<polyline"));
+    assert_eq!(html.matches("<td>AAPL</td>").count(), 2);
+
+    // A benchmark that moves exactly with the equity: daily bars at midnight New York time
+    let benchmark: Vec<OhlcvBar> = [(6, 500.0), (7, 500.1), (8, 499.95), (9, 499.9), (10, 500.0), (13, 510.0)]
+        .into_iter()
+        .map(|(day, close)| OhlcvBar {
+            symbol: "SPY".to_string(),
+            timestamp: format!("2024-05-{:02}T04:00:00Z", day).parse().unwrap(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1000,
+            trade_count: 10,
+            vwap: close,
+        })
+        .collect();
+    let report = report.with_benchmark("SPY", &result.equity_curve, &benchmark).unwrap();
+    let metrics = report.benchmark.clone().unwrap();
+    assert_eq!((metrics.symbol.as_str(), metrics.days), ("SPY", 5));
+    assert!(metrics.total_return.abs() < 1e-12);
+    assert!((metrics.beta.unwrap() - 1.0).abs() < 1e-9);
+    assert!((metrics.correlation.unwrap() - 1.0).abs() < 1e-9);
+    assert!(metrics.alpha.unwrap().abs() < 1e-9);
+    assert!((metrics.max_drawdown - 0.2 / 500.1).abs() < 1e-12);
+    assert!(metrics.relative_max_drawdown < 1e-12);
+    assert!(report.metrics_csv().contains("\nbenchmark,SPY\nbenchmark_days,5\n"));
+    assert!(report.to_string().contains("vs SPY"));
+
+    // A flat benchmark explains none of the moves
+    let flat: Vec<OhlcvBar> = benchmark.iter().map(|bar| OhlcvBar { close: 500.0, ..bar.clone() }).collect();
+    let metrics = BacktestReport::new(&result).with_benchmark("SPY", &result.equity_curve, &flat).unwrap().benchmark.unwrap();
+    assert_eq!((metrics.beta, metrics.correlation, metrics.alpha), (None, None, None));
+    assert!((metrics.relative_max_drawdown - 40.0 / 100_020.0).abs() < 1e-12);
+
+    assert!(BacktestReport::new(&result).with_benchmark("SPY", &result.equity_curve, &benchmark[5..]).is_err());
+}
+
+#[test]
+fn test_builtin_strategies() {
+    use algorithms_trading::backtest::{run_backtest, BacktestConfig};
+    use algorithms_trading::config_file::ConfigFile;
+    use algorithms_trading::resample::OhlcvBar;
+    use algorithms_trading::strategies::{self, StrategyParams, STRATEGIES};
+    use algorithms_trading::strategy::{MarketEvent, Side};
+
+    let bar = |time: &str, low: f64, high: f64, close: f64| {
+        MarketEvent::Bar(OhlcvBar {
+            symbol: "AAPL".to_string(),
+            timestamp: time.parse().unwrap(),
+            open: close,
+            high,
+            low,
+            close,
+            volume: 1000,
+            trade_count: 10,
+            vwap: close,
+        })
+    };
+    let daily = || -> Vec<MarketEvent> {
+        [10.0, 10.0, 10.0, 9.0, 8.0, 9.0, 12.0, 14.0, 13.0, 9.0, 7.0, 6.0]
+            .into_iter()
+            .enumerate()
+            .map(|(day, close)| bar(&format!("2024-05-{:02}T04:00:00Z", day + 1), close, close, close))
+            .collect()
+    };
+    let fills = |name: &str, params: &[&str], events: Vec<MarketEvent>| -> Vec<(Side, f64, f64)> {
+        let params = StrategyParams::parse(&params.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap();
+        let result = run_backtest(strategies::create(name, &params).unwrap(), events, &BacktestConfig::default()).unwrap();
+        assert_eq!(result.strategy, name);
+        result.fills.iter().map(|fill| (fill.side, fill.quantity, fill.price)).collect()
+    };
+
+    // The 2-day SMA crosses above the 3-day one on the 7th close and back below on the 10th;
+    // orders fill at the next day's open
+    assert_eq!(fills("sma-crossover", &["fast=2", "slow=3", "quantity=10"], daily()), [(Side::Buy, 10.0, 14.0), (Side::Sell, 10.0, 7.0)]);
+    assert_eq!(fills("sma-crossover", &["fast=2", "slow=3", "quantity=10", "short=true"], daily()), [(Side::Buy, 10.0, 14.0), (Side::Sell, 20.0, 7.0)]);
+
+    // The 2-day RSI falls to 0 on the 4th close and is back above 50 on the 6th
+    let rsi = fills("rsi-reversion", &["period=2", "quantity=5"], daily());
+    assert_eq!(&rsi[..2], [(Side::Buy, 5.0, 8.0), (Side::Sell, 5.0, 12.0)]);
+    assert!(rsi.chunks(2).all(|pair| pair[0].0 == Side::Buy && pair.get(1).is_none_or(|exit| exit.0 == Side::Sell)));
+
+    // A 2-minute opening range from 98 to 102 on a day in EDT: the close above it buys,
+    // and the bar at the 09:35 exit sells at the next open; the next day breaks below and goes short
+    let minutes = vec![
+        bar("2024-05-03T13:29:00Z", 90.0, 110.0, 100.0),
+        bar("2024-05-03T13:30:00Z", 99.0, 101.0, 100.0),
+        bar("2024-05-03T13:31:00Z", 98.0, 102.0, 101.0),
+        bar("2024-05-03T13:32:00Z", 100.0, 103.0, 103.0),
+        bar("2024-05-03T13:33:00Z", 103.0, 105.0, 104.0),
+        bar("2024-05-03T13:34:00Z", 101.0, 105.0, 99.0),
+        bar("2024-05-03T13:35:00Z", 104.0, 106.0, 105.0),
+        bar("2024-05-06T13:30:00Z", 99.0, 101.0, 100.0),
+        bar("2024-05-06T13:32:00Z", 96.0, 100.0, 97.0),
+        bar("2024-05-06T13:33:00Z", 96.0, 98.0, 97.0),
+    ];
+    assert_eq!(
+        fills("opening-range-breakout", &["minutes=2", "exit=09:35", "quantity=10"], minutes.clone()),
+        [(Side::Buy, 10.0, 104.0), (Side::Sell, 10.0, 100.0), (Side::Sell, 10.0, 97.0)]
+    );
+    assert_eq!(fills("opening-range-breakout", &["minutes=2", "exit=09:35", "short=false"], minutes).len(), 2);
+
+    for name in STRATEGIES {
+        assert!(strategies::create(name, &StrategyParams::default()).is_ok());
+    }
+    for (name, params) in [
+        ("sma-crossover", "slow=5"),
+        ("sma-crossover", "fast=0"),
+        ("rsi-reversion", "exit=20"),
+        ("opening-range-breakout", "exit=9:00"),
+        ("opening-range-breakout", "exit=noon"),
+        ("rsi-reversion", "fast=2"),
+    ] {
+        assert!(strategies::create(name, &StrategyParams::parse(&[params.to_string()]).unwrap()).is_err(), "{} {}", name, params);
+    }
+
+    // Parameters from the config file, overridden by the command line
+    let config: ConfigFile = "[strategies.sma-crossover]\nfast = 2\nslow = 3\nquantity = 2.5\nshort = true\n".parse().unwrap();
+    let mut params = config.strategy_params("sma-crossover");
+    assert_eq!(params.get("fast", 0).unwrap(), 2);
+    assert!(params.get("short", false).unwrap());
+    params.extend(StrategyParams::parse(&["quantity=4".to_string()]).unwrap());
+    assert_eq!(params.get("quantity", 0.0).unwrap(), 4.0);
+    assert_eq!(config.strategy_params("rsi-reversion"), StrategyParams::default());
+}
+
+#[test]
+fn test_monte_carlo() {
+    use algorithms_trading::monte_carlo::{simulate, MonteCarloConfig, Resampling};
+
+    let pnls = [100.0, -300.0, 50.0, 200.0, -100.0];
+    let shuffle = MonteCarloConfig { runs: 2000, method: Resampling::Shuffle, seed: Some(42) };
+    let result = simulate(&pnls, 1000.0, &shuffle).unwrap();
+    assert_eq!((result.runs, result.trades), (2000, 5));
+    // Every order of the same trades ends at the same equity
+    assert_eq!((result.final_equity.min, result.final_equity.max), (950.0, 950.0));
+    assert_eq!(result.probability_of_loss, 1.0);
+    // The worst order loses 400 in a row from the initial 1000, with the gains only after
+    assert!((result.max_drawdown.max - 0.4).abs() < 1e-12);
+    assert!(result.max_drawdown.min < 0.3);
+    assert!(result.max_drawdown.p5 <= result.max_drawdown.median && result.max_drawdown.median <= result.max_drawdown.p95);
+    // A seed repeats the simulation
+    assert_eq!(simulate(&pnls, 1000.0, &shuffle).unwrap(), result);
+
+    let bootstrap = MonteCarloConfig { runs: 2000, method: Resampling::Bootstrap, seed: Some(7) };
+    let result = simulate(&pnls, 1000.0, &bootstrap).unwrap();
+    assert!(result.final_equity.min >= 1000.0 - 5.0 * 300.0 && result.final_equity.max <= 1000.0 + 5.0 * 200.0);
+    assert!(result.final_equity.min < 950.0 && result.final_equity.max > 950.0);
+    assert!(result.probability_of_loss > 0.0 && result.probability_of_loss < 1.0);
+    assert!((result.final_equity.mean - 950.0).abs() < 20.0);
+
+    assert!(simulate(&[], 1000.0, &bootstrap).is_err());
+    assert!(simulate(&pnls, 1000.0, &MonteCarloConfig { runs: 0, ..bootstrap }).is_err());
+}
+
+#[test]
+fn test_cost_models() {
+    use algorithms_trading::backtest::{run_backtest, BacktestConfig};
+    use algorithms_trading::config_file::ConfigFile;
+    use algorithms_trading::costs::*;
+    use algorithms_trading::strategy::{Context, MarketEvent, QuoteEvent, Side, Strategy, TradeEvent};
+    use std::sync::Arc;
+
+    let buy = |quantity: f64, volume: Option<f64>| Execution { side: Side::Buy, quantity, price: 100.0, spread: Some(0.2), volume };
+    let sell = Execution { side: Side::Sell, ..buy(100.0, Some(400.0)) };
+
+    assert_eq!(NoSlippage.price(&sell), 100.0);
+    assert!((FixedBps::new(10.0).unwrap().price(&buy(1.0, None)) - 100.1).abs() < 1e-9);
+    assert!((FixedBps::new(10.0).unwrap().price(&sell) - 99.9).abs() < 1e-9);
+    assert!((SpreadSlippage::new(0.5).unwrap().price(&buy(1.0, None)) - 100.1).abs() < 1e-9);
+    assert_eq!(SpreadSlippage::new(0.5).unwrap().price(&Execution { spread: None, ..sell }), 100.0);
+    // A quarter of the volume pays half the impact; no volume pays all of it
+    let impact = VolumeShare::new(20.0).unwrap();
+    assert!((impact.price(&sell) - 99.9).abs() < 1e-9);
+    assert!((impact.price(&buy(5.0, None)) - 100.2).abs() < 1e-9);
+
+    assert_eq!(PerShare::new(0.005, 1.0).unwrap().commission(100.0, 50.0, 0.0), 1.0);
+    assert_eq!(PerShare::new(0.005, 1.0).unwrap().commission(1000.0, 50.0, 0.0), 5.0);
+    assert_eq!(PerOrder::new(2.5).unwrap().commission(1.0, 50.0, 0.0), 2.5);
+    let tiered = Tiered::new(vec![Tier { up_to: Some(1000.0), per_share: 0.01 }, Tier { up_to: None, per_share: 0.001 }], 0.5).unwrap();
+    assert_eq!(tiered.commission(10.0, 50.0, 0.0), 0.5);
+    assert!((tiered.commission(500.0, 50.0, 800.0) - (200.0 * 0.01 + 300.0 * 0.001)).abs() < 1e-9);
+    assert!((tiered.commission(2000.0, 50.0, 5000.0) - 2.0).abs() < 1e-9);
+    let both: Vec<Arc<dyn CommissionModel>> = vec![Arc::new(PerShare::new(0.01, 0.0).unwrap()), Arc::new(PerOrder::new(1.0).unwrap())];
+    assert_eq!(both.commission(100.0, 50.0, 0.0), 2.0);
+
+    assert!(Tiered::new(vec![], 0.0).is_err());
+    assert!(Tiered::new(vec![Tier { up_to: Some(10.0), per_share: 0.01 }], 0.0).is_err());
+    assert!(Tiered::new(vec![Tier { up_to: Some(10.0), per_share: 0.01 }, Tier { up_to: Some(5.0), per_share: 0.0 }, Tier { up_to: None, per_share: 0.0 }], 0.0).is_err());
+    assert!(SpreadSlippage::new(f64::NAN).is_err());
+
+    // Models from the config file
+    let config: ConfigFile = r#"
+        [costs.slippage]
+        model = "spread"
+        fraction = 0.5
+
+        [costs.commission]
+        model = "tiered"
+        minimum = 0.35
+        tiers = [{ up_to = 300000, per_share = 0.0035 }, { per_share = 0.002 }]
+    "#.parse().unwrap();
+    assert_eq!(config.costs.slippage, SlippageConfig::Spread { fraction: 0.5 });
+    assert_eq!(config.costs.commission.build().unwrap().commission(1000.0, 10.0, 0.0), 3.5);
+    assert_eq!(ConfigFile::default().costs, CostsConfig::default());
+    assert!("[costs.slippage]\nmodel = \"magic\"\n".parse::<ConfigFile>().is_err());
+    assert!(CommissionConfig::PerShare { rate: -1.0, minimum: 0.0 }.build().is_err());
+
+    // In a backtest, a spread model pays from the last quote's spread, and a bar's volume sets the impact
+    struct BuyOnFirstQuote(bool);
+
+    impl Strategy for BuyOnFirstQuote {
+        fn on_quote(&mut self, quote: &QuoteEvent, context: &mut Context) {
+            if !std::mem::replace(&mut self.0, true) {
+                context.buy(&quote.symbol, 100.0);
+            }
+        }
+    }
+
+    let time = |second: u32| format!("2024-05-03T14:30:{:02}Z", second).parse().unwrap();
+    let quote = MarketEvent::Quote(QuoteEvent { symbol: "AAPL".to_string(), timestamp: time(0), bid_price: 99.9, bid_size: 3, ask_price: 100.1, ask_size: 4 });
+    let trade = MarketEvent::Trade(TradeEvent { symbol: "AAPL".to_string(), timestamp: time(1), price: 100.0, size: 100 });
+    let config = BacktestConfig {
+        slippage: config.costs.slippage.build().unwrap(),
+        commission: Arc::new(PerShare::new(0.01, 0.0).unwrap()),
+        ..BacktestConfig::default()
+    };
+    let result = run_backtest(BuyOnFirstQuote(false), vec![quote, trade], &config).unwrap();
+    assert!((result.fills[0].price - 100.1).abs() < 1e-9);
+    assert!((result.fills[0].commission - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_order_requests_and_responses() {
+    use algorithms_trading::orders::*;
+    use algorithms_trading::strategy::{OrderRequest, OrderType, Side};
+
+    // Requests serialize to the body of POST /v2/orders
+    let kind = OrderKind::new("stop_limit", Some(101.0), Some(100.5), None, None).unwrap();
+    let order = NewOrder::new(" aapl", Side::Buy, 10.0, kind).time_in_force(TimeInForce::Gtc).client_order_id(Some("ma-1".to_string()));
+    order.validate().unwrap();
+    assert_eq!(serde_json::to_value(&order).unwrap(), serde_json::json!({
+        "symbol": "AAPL", "qty": 10.0, "side": "buy", "type": "stop_limit", "stop_price": 100.5, "limit_price": 101.0,
+        "time_in_force": "gtc", "client_order_id": "ma-1"
+    }));
+    let trailing = NewOrder::new("SPY", Side::Sell, 5.0, OrderKind::new("trailing_stop", None, None, None, Some(2.5)).unwrap());
+    assert_eq!(serde_json::to_value(&trailing).unwrap(), serde_json::json!({
+        "symbol": "SPY", "qty": 5.0, "side": "sell", "type": "trailing_stop", "trail_percent": 2.5, "time_in_force": "day"
+    }));
+    let limit = NewOrder::new("SPY", Side::Buy, 1.0, OrderKind::Limit { limit_price: 400.0 }).extended_hours(true);
+    limit.validate().unwrap();
+    assert_eq!(serde_json::to_value(&limit).unwrap()["extended_hours"], true);
+    assert_eq!(serde_json::from_value::<NewOrder>(serde_json::to_value(&order).unwrap()).unwrap(), order);
+
+    // Prices each type needs, and what Alpaca would reject
+    assert_eq!(OrderKind::new("market", None, None, None, None).unwrap(), OrderKind::Market);
+    assert!(OrderKind::new("limit", None, None, None, None).is_err());
+    assert!(OrderKind::new("market", Some(1.0), None, None, None).is_err());
+    assert!(OrderKind::new("stop", None, Some(-1.0), None, None).is_err());
+    assert!(OrderKind::new("trailing_stop", None, None, Some(1.0), Some(1.0)).is_err());
+    assert!(OrderKind::new("limit", Some(1.0), None, Some(1.0), None).is_err());
+    assert!(OrderKind::new("bracket", None, None, None, None).is_err());
+    assert!(NewOrder::new("SPY", Side::Buy, 0.0, OrderKind::Market).validate().is_err());
+    assert!(NewOrder::new("SPY", Side::Buy, 1.0, OrderKind::Market).extended_hours(true).validate().is_err());
+    assert!(limit.clone().time_in_force(TimeInForce::Gtc).validate().is_err());
+    assert!(trailing.clone().time_in_force(TimeInForce::Ioc).validate().is_err());
+    assert!(ReplaceOrder::default().validate().is_err());
+    assert!(ReplaceOrder { limit_price: Some(0.0), ..ReplaceOrder::default() }.validate().is_err());
+    let replace = ReplaceOrder { qty: Some(3.0), limit_price: Some(99.0), ..ReplaceOrder::default() };
+    replace.validate().unwrap();
+    assert_eq!(serde_json::to_value(&replace).unwrap(), serde_json::json!({ "qty": 3.0, "limit_price": 99.0 }));
+
+    // Strategy orders become requests
+    let request = OrderRequest {
+        symbol: "MSFT".to_string(),
+        side: Side::Sell,
+        quantity: 2.0,
+        order_type: OrderType::Limit { limit_price: 410.0 },
+        created_at: "2024-05-03T14:30:00Z".parse().unwrap(),
+    };
+    let order = NewOrder::from_request(&request, TimeInForce::Day);
+    assert_eq!(order, NewOrder::new("MSFT", Side::Sell, 2.0, OrderKind::Limit { limit_price: 410.0 }));
+
+    // Responses carry decimals as strings
+    let body = r#"{"id":"61e69015-8549-4bfd-b9c3-01e75843f47d","client_order_id":"ma-1","created_at":"2024-05-03T14:30:00.123456Z",
+        "updated_at":"2024-05-03T14:30:01Z","submitted_at":"2024-05-03T14:30:00.2Z","filled_at":"2024-05-03T14:30:01.5Z",
+        "asset_class":"us_equity","symbol":"AAPL","qty":"10","filled_qty":"4","filled_avg_price":"100.25","order_class":"",
+        "type":"limit","side":"buy","time_in_force":"day","limit_price":"100.5","stop_price":null,"status":"partially_filled",
+        "extended_hours":false,"legs":null,"trail_percent":null,"trail_price":null,"hwm":null,"replaced_by":null}"#;
+    let placed: Order = serde_json::from_str(body).unwrap();
+    assert_eq!(placed.qty, Some(10.0));
+    assert_eq!(placed.filled_avg_price, Some(100.25));
+    assert_eq!(placed.stop_price, None);
+    assert!(placed.is_open());
+    assert_eq!(placed.format(&DataFormat::Plain).unwrap(),
+        "🧾 AAPL buy 10 limit (limit $100.50) day | Filled 4/10 @ $100.25 | partially_filled | 61e69015-8549-4bfd-b9c3-01e75843f47d");
+    let csv = placed.format(&DataFormat::Csv).unwrap();
+    assert_eq!(csv.split(',').count(), ORDERS_CSV_HEADER.split(',').count());
+    assert_eq!(csv, "61e69015-8549-4bfd-b9c3-01e75843f47d,ma-1,AAPL,buy,limit,10,4,100.25,100.5,,,,day,false,partially_filled,2024-05-03T14:30:00.123Z,2024-05-03T14:30:01.500Z");
+    let json = placed.format(&DataFormat::Json).unwrap();
+    assert_eq!(serde_json::from_str::<Order>(&json).unwrap(), placed);
+    assert!(serde_json::from_str::<Order>(&body.replace("\"100.25\"", "\"abc\"")).is_err());
+
+    let query = OrderQuery { status: Some("all".to_string()), limit: Some(100), symbols: vec!["AAPL".to_string(), "MSFT".to_string()], side: Some(Side::Sell) };
+    assert_eq!(query.params(), vec![("status", "all".to_string()), ("limit", "100".to_string()), ("symbols", "AAPL,MSFT".to_string()), ("side", "sell".to_string())]);
+    assert!(OrderQuery::default().params().is_empty());
+}
+
+#[test]
+fn test_live_order_queue_and_own_fills() {
+    use algorithms_trading::decode_streaming_message;
+    use algorithms_trading::orders::{OrderKind, OrderQueue, OwnOrders, TimeInForce};
+    use algorithms_trading::strategies::{self, StrategyParams};
+    use algorithms_trading::strategy::{OrderRouter, Side, StrategySink};
+
+    let params = StrategyParams::parse(&["quantity=10".to_string()]).unwrap();
+    let (queue, mut orders) = OrderQueue::new("buy-and-hold-20240503143000", TimeInForce::Gtc);
+    let sink = StrategySink::new(strategies::create("buy-and-hold", &params).unwrap(), Box::new(queue));
+    let own = OwnOrders::new(sink.clone(), "buy-and-hold-20240503143000");
+
+    // The strategy's orders come out of the queue with client order IDs of the run
+    own.write_streaming_data(&create_mock_bar_data("AAPL", "2024-05-03T14:30:00Z", 150.0)).unwrap();
+    let order = orders.try_recv().unwrap();
+    assert_eq!((order.symbol.as_str(), order.side, order.qty, order.kind), ("AAPL", Side::Buy, 10.0, OrderKind::Market));
+    assert_eq!(order.time_in_force, TimeInForce::Gtc);
+    assert_eq!(order.client_order_id.as_deref(), Some("buy-and-hold-20240503143000-1"));
+
+    // Fills of other orders of the account, or of another run, do not reach the strategy
+    let fill = |client_order_id: &str| {
+        let mut message = create_mock_trade_update("fill");
+        message.data["order"]["client_order_id"] = serde_json::json!(client_order_id);
+        decode_streaming_message(&message).unwrap().unwrap()
+    };
+    own.write_streaming_data(&fill("client-1")).unwrap();
+    own.write_streaming_data(&fill("buy-and-hold-202405031430001-1")).unwrap();
+    assert_eq!(sink.stats().fills, 0);
+    own.write_streaming_data(&fill("buy-and-hold-20240503143000-1")).unwrap();
+    own.write_streaming_data(&create_mock_quote_data("AAPL", 150.0, 150.1)).unwrap();
+    assert_eq!((sink.stats().fills, sink.stats().quotes), (1, 1));
+    assert!(orders.try_recv().is_err());
+
+    // Once nothing places orders, routing fails
+    drop(orders);
+    let (mut queue, orders) = OrderQueue::new("x", TimeInForce::Day);
+    drop(orders);
+    let request = algorithms_trading::strategy::OrderRequest {
+        symbol: "AAPL".to_string(),
+        side: Side::Sell,
+        quantity: 1.0,
+        order_type: algorithms_trading::strategy::OrderType::Market,
+        created_at: "2024-05-03T14:30:00Z".parse().unwrap(),
+    };
+    assert!(queue.route(request).is_err());
+}
+
+#[test]
+fn test_portfolio_positions_and_pnl() {
+    use algorithms_trading::decode_streaming_message;
+    use algorithms_trading::portfolio::{AccountPosition, Portfolio, PortfolioTotals};
+    use algorithms_trading::strategy::{Fill, Side};
+
+    // Startup: the account is short MSFT, with its size signed by Alpaca
+    let held: Vec<AccountPosition> = serde_json::from_str(r#"[{"asset_id":"b6d1aa75","symbol":"MSFT","exchange":"NASDAQ","qty":"-4",
+        "side":"short","avg_entry_price":"300.5","market_value":"-1196","current_price":"299.0"}]"#).unwrap();
+    let portfolio = Portfolio::new();
+    assert_eq!(portfolio.reconcile(&held), 1);
+    let msft = portfolio.position("MSFT").unwrap();
+    assert_eq!((msft.quantity, msft.average_cost, msft.last_price), (-4.0, 300.5, Some(299.0)));
+    assert!((msft.unrealized_pnl() - 6.0).abs() < 1e-9);
+    assert_eq!(portfolio.reconcile(&held), 0);
+
+    // Buys average into the cost; a sell through zero realizes the closed shares and opens a short at its price
+    portfolio.write_streaming_data(&decode_streaming_message(&create_mock_trade_update("fill")).unwrap().unwrap()).unwrap();
+    let fill = |side, quantity, price, commission| Fill {
+        symbol: "AAPL".to_string(),
+        side,
+        quantity,
+        price,
+        timestamp: "2024-01-15T14:31:00Z".parse().unwrap(),
+        commission,
+        order_id: None,
+    };
+    portfolio.apply_fill(&fill(Side::Buy, 5.0, 152.25, 0.0));
+    let aapl = portfolio.position("AAPL").unwrap();
+    assert_eq!((aapl.quantity, aapl.average_cost, aapl.realized_pnl), (10.0, 151.25, 0.0));
+    portfolio.apply_fill(&fill(Side::Sell, 15.0, 155.0, 1.0));
+    let aapl = portfolio.position("AAPL").unwrap();
+    assert_eq!((aapl.quantity, aapl.average_cost), (-5.0, 155.0));
+    assert!((aapl.realized_pnl - 36.5).abs() < 1e-9);
+
+    // Quotes mark at the midpoint and bars at the close; symbols never held are ignored
+    portfolio.write_streaming_data(&create_mock_quote_data("AAPL", 154.0, 154.2)).unwrap();
+    assert!((portfolio.position("AAPL").unwrap().unrealized_pnl() - 4.5).abs() < 1e-9);
+    portfolio.write_streaming_data(&create_mock_bar_data("MSFT", "2024-01-15T14:31:00Z", 301.0)).unwrap();
+    portfolio.write_streaming_data(&create_mock_bar_data("TSLA", "2024-01-15T14:31:00Z", 200.0)).unwrap();
+    assert!(portfolio.position("TSLA").is_none());
+    let totals = portfolio.totals();
+    assert_eq!(totals.positions, 2);
+    assert!((totals.market_value - (-5.0 * 154.1 - 4.0 * 301.0)).abs() < 1e-9);
+    assert!((totals.total_pnl - (4.5 - 2.0 + 36.5)).abs() < 1e-9);
+
+    // Snapshots go through the outputs: a position per symbol, then the totals
+    let sink = CollectingSink::default();
+    portfolio.emit(&sink, true).unwrap();
+    let records = sink.records.lock().unwrap();
+    let written: Vec<(&str, Option<&str>)> = records.iter().map(|r| (r.message_type.as_str(), r.symbol.as_deref())).collect();
+    assert_eq!(written, [("position", Some("AAPL")), ("position", Some("MSFT")), ("portfolio", None)]);
+    assert_eq!(records[0].data["final"], true);
+    let formatter = OutputMode::create_console_mode(DataFormat::Plain);
+    assert!(formatter.format_plain(&records[0]).starts_with("💼 Final position: AAPL -5 @ $155.00 | Last: $154.10"));
+    assert!(formatter.format_plain(&records[2]).starts_with("💰 Final portfolio: 2 positions"));
+    let totals: PortfolioTotals = serde_json::from_value(records[2].data.clone()).unwrap();
+    assert!(totals.is_final);
+
+    // Positions the account no longer holds become flat and keep their realized P&L
+    assert_eq!(portfolio.reconcile(&[]), 2);
+    let aapl = portfolio.position("AAPL").unwrap();
+    assert!(aapl.is_flat());
+    assert!((aapl.realized_pnl - 36.5).abs() < 1e-9);
+    assert_eq!(portfolio.totals().positions, 0);
+}
+
+#[cfg(feature = "historical-trades")]
+#[test]
+fn test_historical_trades_page() {
+    use algorithms_trading::historical_trades::{parse_trades_page, TRADES_CSV_HEADER};
+
+    let body = r#"{"trades":[{"t":"2024-01-02T14:30:00.123Z","x":"V","p":187.15,"s":100,"c":["@","F"],"i":52983525029461,"z":"C"},
+        {"t":"2024-01-02T14:30:01Z","x":"P","p":187.2,"s":5,"i":7}],"symbol":"AAPL","next_page_token":"QUFQTHwy"}"#;
+    let (trades, next) = parse_trades_page("AAPL", body).unwrap();
+    assert_eq!(next.as_deref(), Some("QUFQTHwy"));
+    assert_eq!(trades.len(), 2);
+    assert_eq!(trades[0].symbol, "AAPL");
+    assert_eq!(trades[0].conditions, vec!["@", "F"]);
+    assert_eq!(trades[1].tape, "");
+
+    let csv = trades[0].format(&DataFormat::Csv).unwrap();
+    assert_eq!(csv, "AAPL,2024-01-02T14:30:00.123Z,187.15,100,V,52983525029461,@ F,C");
+    assert_eq!(csv.split(',').count(), TRADES_CSV_HEADER.split(',').count());
+    assert!(trades[1].format(&DataFormat::Plain).unwrap().contains("$187.20 x 5"));
+    let json: serde_json::Value = serde_json::from_str(&trades[0].format(&DataFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["price"], 187.15);
+
+    // An empty range has `null` trades and no next page
+    let (trades, next) = parse_trades_page("AAPL", r#"{"trades":null,"symbol":"AAPL","next_page_token":null}"#).unwrap();
+    assert!(trades.is_empty());
+    assert!(next.is_none());
+
+    #[cfg(feature = "parquet")]
+    {
+        let row = parse_trades_page("AAPL", body).unwrap().0[0].to_dataset_row().unwrap();
+        assert_eq!(row.conditions.as_deref(), Some("@ F"));
+        assert_eq!(row.tape.as_deref(), Some("C"));
+    }
+}
+
+#[cfg(feature = "historical-trades")]
+#[test]
+fn test_historical_auctions_page() {
+    use algorithms_trading::historical_trades::{parse_auctions_page, AUCTIONS_CSV_HEADER};
+
+    let body = r#"{"auctions":{"AAPL":[{"d":"2024-01-02",
+        "o":[{"c":"O","p":187.05,"s":120,"t":"2024-01-02T14:30:00.1Z","x":"P"},{"c":"Q","p":187.15,"s":38712,"t":"2024-01-02T14:30:00.5Z","x":"Q"}],
+        "c":[{"c":"M","p":185.64,"s":5489021,"t":"2024-01-02T21:00:00.2Z","x":"Q"}]}]},"next_page_token":null}"#;
+    let (prints, next) = parse_auctions_page("AAPL", body).unwrap();
+    assert!(next.is_none());
+    assert_eq!(prints.len(), 3);
+    assert_eq!(prints.iter().map(|p| p.auction.as_str()).collect::<Vec<_>>(), vec!["open", "open", "close"]);
+    assert_eq!(prints.iter().map(|p| p.official).collect::<Vec<_>>(), vec![false, true, true]);
+    assert_eq!(prints[2].date, "2024-01-02");
+
+    let csv = prints[1].format(&DataFormat::Csv).unwrap();
+    assert_eq!(csv, "AAPL,2024-01-02,open,2024-01-02T14:30:00.5Z,187.15,38712,Q,Q,true");
+    assert_eq!(csv.split(',').count(), AUCTIONS_CSV_HEADER.split(',').count());
+    let plain = prints[2].format(&DataFormat::Plain).unwrap();
+    assert!(plain.contains("Closing auction: AAPL 2024-01-02 | $185.64 x 5489021"));
+    assert!(plain.ends_with("(official)"));
+    let json: serde_json::Value = serde_json::from_str(&prints[0].format(&DataFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["condition"], "O");
+
+    // Other symbols on the page are not ours
+    let (prints, _) = parse_auctions_page("MSFT", body).unwrap();
+    assert!(prints.is_empty());
+}
+
+#[cfg(feature = "corporate-actions")]
+#[test]
+fn test_corporate_actions_page_and_split_factor() {
+    use algorithms_trading::corporate_actions::{
+        parse_corporate_actions_page, split_adjustment_factor, CorporateActionKind, CORPORATE_ACTIONS_CSV_HEADER,
+    };
+    use chrono::NaiveDate;
+
+    let body = r#"{"corporate_actions":{
+        "forward_splits":[{"id":"a1","symbol":"NVDA","cusip":"67066G104","new_rate":10,"old_rate":1,"process_date":"2024-06-10","ex_date":"2024-06-10","record_date":"2024-06-06","payable_date":"2024-06-07"}],
+        "reverse_splits":[{"id":"a2","symbol":"NVDA","new_rate":1,"old_rate":2,"process_date":"2024-09-02","ex_date":"2024-09-02"}],
+        "cash_dividends":[{"id":"a3","symbol":"AAPL","rate":0.25,"special":false,"foreign":false,"process_date":"2024-05-10","ex_date":"2024-05-10","record_date":"2024-05-13","payable_date":"2024-05-16"}],
+        "stock_and_cash_mergers":[{"id":"a4","acquirer_symbol":"XOM","acquirer_rate":1.0407,"acquiree_symbol":"PXD","acquiree_rate":1,"cash_rate":0.5,"process_date":"2024-05-03","effective_date":"2024-05-03","payable_date":"2024-05-03"}]},
+        "next_page_token":"next"}"#;
+    let (actions, next) = parse_corporate_actions_page(body).unwrap();
+    assert_eq!(next.as_deref(), Some("next"));
+    assert_eq!(actions.len(), 4);
+
+    let split = &actions[0];
+    assert_eq!((split.kind, split.split_ratio()), (CorporateActionKind::ForwardSplit, Some(10.0)));
+    assert!(split.format(&DataFormat::Plain).unwrap().contains("NVDA: 2024-06-10 forward_split | 10-for-1"));
+    let csv = split.format(&DataFormat::Csv).unwrap();
+    assert_eq!(csv, "NVDA,forward_split,2024-06-10,2024-06-06,2024-06-07,1,10,,");
+    assert_eq!(csv.split(',').count(), CORPORATE_ACTIONS_CSV_HEADER.split(',').count());
+
+    let dividend = &actions[2];
+    assert_eq!((dividend.kind, dividend.rate, dividend.split_ratio()), (CorporateActionKind::CashDividend, Some(0.25), None));
+    assert!(dividend.format(&DataFormat::Plain).unwrap().contains("$0.2500 per share | Payable: 2024-05-16"));
+
+    let merger = &actions[3];
+    assert_eq!((merger.symbol.as_str(), merger.acquirer.as_deref()), ("PXD", Some("XOM")));
+    assert!(merger.format(&DataFormat::Plain).unwrap().contains("acquired by XOM: 1.0407 XOM for 1 + $0.5000 cash per share"));
+    let json: serde_json::Value = serde_json::from_str(&merger.format(&DataFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["type"], "stock_and_cash_merger");
+
+    // Splits on or before the date do not apply; later ones compound
+    let day = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+    assert_eq!(split_adjustment_factor(&actions, "NVDA", day("2024-06-07")), 0.2);
+    assert_eq!(split_adjustment_factor(&actions, "NVDA", day("2024-06-10")), 2.0);
+    assert_eq!(split_adjustment_factor(&actions, "NVDA", day("2024-09-02")), 1.0);
+    assert_eq!(split_adjustment_factor(&actions, "AAPL", day("2024-01-02")), 1.0);
+}
+
+#[cfg(feature = "corporate-actions")]
+#[test]
+fn test_price_adjustment() {
+    use algorithms_trading::corporate_actions::{adjusted_bars_csv_header, read_corporate_actions, PriceAdjuster};
+    use algorithms_trading::resample::OhlcvBar;
+    use chrono::NaiveDate;
+    use std::collections::{BTreeMap, HashMap};
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("actions.csv");
+    fs::write(&path, "symbol,type,date,record_date,payable_date,old_rate,new_rate,rate,acquirer\n\
+        AAPL,cash_dividend,2024-05-10,2024-05-13,2024-05-16,,,0.25,\n\
+        NVDA,forward_split,2024-06-10,2024-06-06,2024-06-07,1,10,,\n\
+        NVDA,stock_dividend,2024-08-01,,,,,0.5,\n").unwrap();
+    let actions = read_corporate_actions(&path).unwrap();
+    assert_eq!(actions.len(), 3);
+    assert_eq!(actions[1].split_ratio(), Some(10.0));
+    // The JSON output of `trade corporate-actions` reads back the same
+    let json = dir.path().join("actions.jsonl");
+    fs::write(&json, actions.iter().map(|action| action.format(&DataFormat::Json).unwrap() + "\n").collect::<String>()).unwrap();
+    assert_eq!(read_corporate_actions(&json).unwrap(), actions);
+
+    let day = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+    let closes: HashMap<String, BTreeMap<NaiveDate, f64>> =
+        HashMap::from([("AAPL".to_string(), BTreeMap::from([(day("2024-05-08"), 90.0), (day("2024-05-09"), 100.0), (day("2024-05-10"), 99.0)]))]);
+    let adjuster = PriceAdjuster::new(&actions, &closes, true);
+    // The dividend is priced with the close before its ex date
+    assert_eq!(adjuster.factors("AAPL", day("2024-05-08")), (1.0, 0.9975));
+    assert_eq!(adjuster.factors("AAPL", day("2024-05-10")), (1.0, 1.0));
+    // A 10-for-1 split, then a stock dividend of half a share per share
+    assert!((adjuster.factors("NVDA", day("2024-06-07")).0 - 1.0 / 15.0).abs() < 1e-12);
+    assert!((adjuster.factors("NVDA", day("2024-06-10")).0 - 1.0 / 1.5).abs() < 1e-12);
+    assert_eq!(adjuster.factors("NVDA", day("2024-08-01")), (1.0, 1.0));
+    assert_eq!(PriceAdjuster::new(&actions, &closes, false).factors("AAPL", day("2024-05-08")), (1.0, 1.0));
+
+    let bar = OhlcvBar {
+        symbol: "AAPL".to_string(),
+        timestamp: "2024-05-09T04:00:00Z".parse().unwrap(),
+        open: 98.0,
+        high: 102.0,
+        low: 96.0,
+        close: 100.0,
+        volume: 1000,
+        trade_count: 50,
+        vwap: 100.0,
+    };
+    let adjusted = adjuster.adjust(bar.clone(), day("2024-05-09"));
+    assert_eq!((adjusted.bar.close, adjusted.bar.volume, adjusted.dividend_factor), (99.75, 1000, 0.9975));
+    assert_eq!(adjusted_bars_csv_header().split(',').count(), 11);
+    assert_eq!(adjusted.format(&DataFormat::Csv).unwrap(), "AAPL,2024-05-09T04:00:00Z,97.76,101.75,95.76,99.75,1000,50,99.75,1,0.9975");
+    let json: serde_json::Value = serde_json::from_str(&adjusted.format(&DataFormat::Json).unwrap()).unwrap();
+    assert_eq!((json["close"].as_f64(), json["split_factor"].as_f64()), (Some(99.75), Some(1.0)));
+
+    let split = adjuster.adjust(OhlcvBar { symbol: "NVDA".to_string(), volume: 1000, ..bar }, day("2024-06-10"));
+    assert_eq!(split.bar.volume, 1500);
+}
+
+#[cfg(feature = "options")]
+#[test]
+fn test_options_occ_symbols_and_pages() {
+    use algorithms_trading::options::{
+        parse_contracts_page, parse_option_bars_page, parse_option_trades_page, OccSymbol, OptionRight,
+        OPTION_BARS_CSV_HEADER, OPTION_CONTRACTS_CSV_HEADER, OPTION_TRADES_CSV_HEADER,
+    };
+
+    let occ = OccSymbol::parse("AAPL240119C00190000").unwrap();
+    assert_eq!(occ.underlying, "AAPL");
+    assert_eq!(occ.expiration, chrono::NaiveDate::from_ymd_opt(2024, 1, 19).unwrap());
+    assert_eq!(occ.right, OptionRight::Call);
+    assert_eq!(occ.strike, 190.0);
+    assert_eq!(occ.to_string(), "AAPL240119C00190000");
+    assert_eq!(occ.describe(), "AAPL 2024-01-19 call $190.00");
+    let padded = OccSymbol::parse("SPXW  240315P04987500").unwrap();
+    assert_eq!((padded.underlying.as_str(), padded.right, padded.strike), ("SPXW", OptionRight::Put, 4987.5));
+    assert_eq!(padded.to_string(), "SPXW240315P04987500");
+    for bad in ["AAPL", "AAPL241319C00190000", "AAPL240119X00190000", "AAPL240119C0019000A", "TOOLONGROOT240119C00190000"] {
+        assert!(OccSymbol::parse(bad).is_err(), "{} should not parse", bad);
+    }
+
+    let body = r#"{"option_contracts":[{"id":"6e58f870","symbol":"AAPL240119P00185000","name":"AAPL Jan 19 2024 185 Put",
+        "status":"active","tradable":true,"expiration_date":"2024-01-19","root_symbol":"AAPL","underlying_symbol":"AAPL",
+        "type":"put","style":"american","strike_price":"185","size":"100","open_interest":"5123","open_interest_date":"2024-01-10",
+        "close_price":"1.27","close_price_date":"2024-01-10"}],"next_page_token":"abc"}"#;
+    let (contracts, next) = parse_contracts_page(body).unwrap();
+    assert_eq!(next.as_deref(), Some("abc"));
+    let contract = &contracts[0];
+    assert_eq!((contract.occ.right, contract.occ.strike), (OptionRight::Put, 185.0));
+    assert_eq!((contract.open_interest, contract.close_price), (Some(5123), Some(1.27)));
+    let csv = contract.format(&DataFormat::Csv).unwrap();
+    assert_eq!(csv, "AAPL240119P00185000,AAPL,2024-01-19,put,185,american,active,true,5123,2024-01-10,1.27,2024-01-10");
+    assert_eq!(csv.split(',').count(), OPTION_CONTRACTS_CSV_HEADER.split(',').count());
+    assert!(contract.format(&DataFormat::Plain).unwrap().contains("AAPL 2024-01-19 put $185.00 | american | active | OI: 5123 | Close: $1.27"));
+    let json: serde_json::Value = serde_json::from_str(&contract.format(&DataFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["underlying"], "AAPL");
+    assert_eq!(json["expiration"], "2024-01-19");
+    assert_eq!(json["right"], "put");
+
+    let body = r#"{"bars":{"AAPL240119C00190000":[{"t":"2024-01-10T05:00:00Z","o":1.5,"h":1.9,"l":1.41,"c":1.85,"v":2310,"n":412,"vw":1.7}]},"next_page_token":null}"#;
+    let (bars, next) = parse_option_bars_page(body).unwrap();
+    assert!(next.is_none());
+    let csv = bars[0].format(&DataFormat::Csv).unwrap();
+    assert_eq!(csv, "AAPL240119C00190000,AAPL,2024-01-19,call,190,2024-01-10T05:00:00Z,1.5,1.9,1.41,1.85,2310,412,1.7");
+    assert_eq!(csv.split(',').count(), OPTION_BARS_CSV_HEADER.split(',').count());
+
+    let body = r#"{"trades":{"AAPL240119C00190000":[{"t":"2024-01-10T15:31:02.5Z","x":"C","p":1.62,"s":3,"c":"I"}]},"next_page_token":null}"#;
+    let (trades, _) = parse_option_trades_page(body).unwrap();
+    let csv = trades[0].format(&DataFormat::Csv).unwrap();
+    assert_eq!(csv, "AAPL240119C00190000,AAPL,2024-01-19,call,190,2024-01-10T15:31:02.5Z,1.62,3,C,I");
+    assert_eq!(csv.split(',').count(), OPTION_TRADES_CSV_HEADER.split(',').count());
+    assert!(trades[0].format(&DataFormat::Plain).unwrap().contains("(AAPL 2024-01-19 call $190.00)"));
+}
+
+#[cfg(feature = "snapshot")]
+#[test]
+fn test_snapshot_parse_and_format() {
+    use algorithms_trading::snapshot::{parse_snapshot_feed, parse_snapshots, Snapshot, SNAPSHOT_CSV_HEADER};
+
+    let body = r#"{"AAPL":{
+        "latestTrade":{"t":"2024-01-03T20:59:59.9Z","x":"V","p":184.25,"s":100,"c":["@"],"i":52983,"z":"C"},
+        "latestQuote":{"t":"2024-01-03T20:59:59.8Z","ax":"V","ap":184.27,"as":3,"bx":"V","bp":184.24,"bs":2,"c":["R"],"z":"C"},
+        "minuteBar":{"t":"2024-01-03T20:59:00Z","o":184.2,"h":184.3,"l":184.1,"c":184.25,"v":12040,"n":120,"vw":184.22},
+        "dailyBar":{"t":"2024-01-03T05:00:00Z","o":184.22,"h":185.88,"l":183.43,"c":184.25,"v":1523041,"n":18211,"vw":184.6},
+        "prevDailyBar":{"t":"2024-01-02T05:00:00Z","o":187.15,"h":188.44,"l":183.89,"c":185.64,"v":1702311,"n":20133,"vw":185.9}},
+        "NOPE":null}"#;
+    let symbols = vec!["MSFT".to_string(), "AAPL".to_string(), "NOPE".to_string()];
+    let snapshots = parse_snapshots(&symbols, body).unwrap();
+    assert_eq!(snapshots.len(), 1);
+    let snapshot = &snapshots[0];
+    assert_eq!(snapshot.symbol, "AAPL");
+    assert_eq!(snapshot.latest_quote.as_ref().unwrap().ask_size, 3);
+    let (change, percent) = snapshot.change().unwrap();
+    assert!((change + 1.39).abs() < 1e-9);
+    assert!((percent + 0.7488).abs() < 1e-3);
+
+    let plain = snapshot.format(&DataFormat::Plain).unwrap();
+    assert!(plain.starts_with("📸 AAPL\n"));
+    assert!(plain.contains("Trade: $184.25 x 100 | Exchange: V"));
+    assert!(plain.contains("Change: -1.39 (-0.75%)"));
+    assert!(plain.contains("Spread $0.03"));
+    assert!(plain.contains("Previous daily bar: O: $187.15"));
+
+    let csv = snapshot.format(&DataFormat::Csv).unwrap();
+    assert_eq!(csv.split(',').count(), SNAPSHOT_CSV_HEADER.split(',').count());
+    assert!(csv.starts_with("AAPL,2024-01-03T20:59:59.9Z,184.25,100,V,"));
+
+    // JSON uses readable field names and reads back
+    let json = snapshot.format(&DataFormat::Json).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["prev_daily_bar"]["close"], 185.64);
+    assert_eq!(&serde_json::from_str::<Snapshot>(&json).unwrap(), snapshot);
+
+    // Missing parts stay empty
+    let snapshots = parse_snapshots(&["SPY".to_string()], r#"{"SPY":{"latestTrade":null}}"#).unwrap();
+    assert!(snapshots[0].change().is_none());
+    assert!(snapshots[0].format(&DataFormat::Plain).unwrap().contains("Minute bar: -"));
+    assert_eq!(snapshots[0].format(&DataFormat::Csv).unwrap(), format!("SPY{}", ",".repeat(27)));
+
+    assert_eq!(parse_snapshot_feed("SIP").unwrap(), "sip");
+    assert!(parse_snapshot_feed("nasdaq").is_err());
+}
+
+#[cfg(feature = "auth-check")]
+#[test]
+fn test_auth_check_diagnosis() {
+    use algorithms_trading::credentials::{mask_key, AuthReport, FeedAccess, RateLimit};
+
+    let access = |feed: &str, status: u16| FeedAccess {
+        feed: feed.to_string(),
+        status,
+        message: (status != 200).then(|| "subscription does not permit querying recent SIP data".to_string()),
+    };
+    let mut report = AuthReport {
+        base_url: "https://paper-api.alpaca.markets".to_string(),
+        data_url: "https://data.alpaca.markets".to_string(),
+        key_id: mask_key("PKABCDEFGH"),
+        paper: true,
+        configured_feed: "iex".to_string(),
+        account: serde_json::from_value(serde_json::json!({"account_number": "PA123", "status": "ACTIVE", "trading_blocked": false})).unwrap(),
+        account_status: 200,
+        feeds: vec![access("sip", 403), access("iex", 200)],
+        rate_limit: Some(RateLimit { limit: Some(200), remaining: Some(199), reset: Some(1700000000) }),
+        problems: Vec::new(),
+    };
+    assert_eq!(report.key_id, "PKAB******");
+    report.diagnose();
+    assert_eq!(report.entitlement(), Some("iex"));
+    assert!(report.problems.is_empty(), "{:?}", report.problems);
+
+    // SIP configured on an IEX-only subscription
+    report.configured_feed = "sip".to_string();
+    report.diagnose();
+    assert_eq!(report.problems, ["ALPACA_FEED is sip, but the data subscription only covers iex. Use --feed iex"]);
+
+    // A paper key against the live endpoint is rejected
+    report.configured_feed = "iex".to_string();
+    report.base_url = "https://api.alpaca.markets".to_string();
+    report.paper = false;
+    report.account = None;
+    report.account_status = 403;
+    report.feeds = vec![access("sip", 403), access("iex", 403)];
+    report.diagnose();
+    assert_eq!(report.problems.len(), 3, "{:?}", report.problems);
+    assert!(report.problems[0].starts_with("The trading API rejected the key ID and secret (403)"));
+    assert!(report.problems[1].contains("looks like a paper key"));
+    assert_eq!(report.problems[2], "No market data access on either the SIP or the IEX feed");
+}
+
+#[cfg(feature = "webhooks")]
+#[test]
+fn test_notifier_events_and_presets() {
+    use algorithms_trading::notifier::{notification, NotifierConfig, NotifierTarget, NotifyEvent};
+
+    let notifiers = NotifierConfig::parse(r#"
+        [[notifier]]
+        kind = "slack"
+        url = "https://hooks.slack.com/services/T0/B0/x"
+        events = ["retries_exhausted", "alert"]
+
+        [[notifier]]
+        kind = "telegram"
+        bot_token = "123:abc"
+        chat_id = "-100"
+    "#).unwrap();
+    assert_eq!(notifiers[0].events, [NotifyEvent::RetriesExhausted, NotifyEvent::Alert]);
+    assert_eq!(notifiers[1].events.len(), 5);
+    assert!(NotifierConfig::parse("[[notifier]]\nkind = \"email\"").is_err());
+    assert!("disconnects".parse::<NotifyEvent>().is_err());
+
+    let mut disconnect = create_mock_streaming_data("disconnect", "AAPL", serde_json::json!({
+        "attempt": 2, "max_retries": 5, "error": "connection reset", "retry_in_secs": 4
+    }));
+    disconnect.symbol = None;
+    let (event, text) = notification(&disconnect).unwrap();
+    assert_eq!(event, NotifyEvent::Disconnect);
+    assert_eq!(text, "Stream disconnected (attempt 2/5): connection reset. Retrying in 4s");
+    disconnect.data["retry_in_secs"] = serde_json::Value::Null;
+    assert_eq!(notification(&disconnect).unwrap().0, NotifyEvent::RetriesExhausted);
+
+    let alert = create_mock_streaming_data("alert", "AAPL", serde_json::json!({"message": "AAPL price 201.00 above 200.00"}));
+    let (event, text) = notification(&alert).unwrap();
+    assert_eq!(text, "Alert: AAPL - AAPL price 201.00 above 200.00");
+    assert_eq!(notifiers[0].target.payload(event, &text, &alert), serde_json::json!({"text": text}));
+    assert_eq!(notifiers[1].target.payload(event, &text, &alert), serde_json::json!({"chat_id": "-100", "text": text}));
+    let webhook = NotifierTarget::Webhook { url: "https://example.com".to_string() }.payload(event, &text, &alert);
+    assert_eq!(webhook["event"], "alert");
+    assert_eq!(webhook["data"]["message"], "AAPL price 201.00 above 200.00");
+
+    assert!(notification(&create_mock_quote_data("AAPL", 1.0, 2.0)).is_none());
+    let (event, text) = notification(&create_mock_status_data("AAPL", "H")).unwrap();
+    assert_eq!((event, text.as_str()), (NotifyEvent::Halt, "AAPL halted: Trading Halt (T12: Additional Information Requested)"));
+    assert!(notification(&create_mock_status_data("AAPL", "T")).is_none());
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn test_dashboard_state_and_render() {
+    use algorithms_trading::dashboard::{draw, DashboardState};
+    use ratatui::backend::TestBackend;
+
+    let dashboard = DashboardState::new();
+    dashboard.add_symbols(["AAPL", "SPY"]);
+    assert_eq!(dashboard.status(), "Connecting");
+    dashboard.write_streaming_data(&StreamingData {
+        received_time: chrono::Utc::now(),
+        event_time: None,
+        message_type: "success".to_string(),
+        symbol: None,
+        data: serde_json::json!("authenticated"),
+    }).unwrap();
+    assert_eq!(dashboard.status(), "Connected");
+
+    for (price, size) in [(200.0, 100), (202.0, 50)] {
+        dashboard.write_streaming_data(&create_mock_streaming_data("t", "AAPL", serde_json::json!({
+            "T": "t", "S": "AAPL", "p": price, "s": size, "t": "2024-05-03T14:30:00Z", "x": "V", "i": 1
+        }))).unwrap();
+    }
+    dashboard.write_streaming_data(&create_mock_quote_data("AAPL", 201.9, 202.1)).unwrap();
+    dashboard.write_streaming_data(&create_mock_bar_data("SPY", "2024-05-03T14:30:00Z", 500.0)).unwrap();
+
+    let aapl = dashboard.row("AAPL").unwrap();
+    assert_eq!(aapl.last, Some(202.0));
+    assert_eq!(aapl.change_percent(), Some(1.0));
+    assert!((aapl.spread().unwrap() - 0.2).abs() < 1e-9);
+    assert_eq!(aapl.volume(), 150);
+    assert_eq!(dashboard.row("SPY").unwrap().volume(), 1000);
+    assert_eq!(dashboard.log_tail(2).len(), 2);
+
+    dashboard.write_streaming_data(&create_mock_streaming_data("disconnect", "-", serde_json::json!({
+        "attempt": 1, "max_retries": 5, "error": "reset", "retry_in_secs": 2
+    }))).unwrap();
+    assert_eq!(dashboard.status(), "Reconnecting in 2s (attempt 1/5)");
+
+    let mut terminal = ratatui::Terminal::new(TestBackend::new(80, 24)).unwrap();
+    terminal.draw(|frame| draw(frame, &dashboard)).unwrap();
+    let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+    assert!(screen.contains("Reconnecting"));
+    assert!(screen.contains("AAPL"));
+    assert!(screen.contains("+1.00%"));
+    assert!(screen.contains("Disconnected (attempt 1/5)"));
+}
+
+#[test]
+fn test_console_color_and_emoji_options() {
+    use algorithms_trading::console::strip_emoji;
+    use algorithms_trading::ConsoleOptions;
+
+    let plain = OutputMode::create_console_mode(DataFormat::Plain);
+    let trade = |price: f64| create_mock_streaming_data("t", "AAPL", serde_json::json!({
+        "T": "t", "S": "AAPL", "p": price, "s": 100, "t": "2024-05-03T14:30:00Z", "x": "V", "i": 1
+    }));
+
+    let options = ConsoleOptions::new(true, false);
+    let lines: Vec<String> = [150.0, 151.0, 151.0, 149.5].into_iter()
+        .map(|price| { let data = trade(price); options.render(plain.format_plain(&data), &data) })
+        .collect();
+    assert!(lines[0].starts_with("Trade: AAPL - $150.00"));
+    assert!(lines[1].starts_with("\x1b[32mTrade: AAPL - $151.00") && lines[1].ends_with("\x1b[0m"));
+    assert!(lines[2].starts_with("Trade: AAPL - $151.00"));
+    assert!(lines[3].starts_with("\x1b[31mTrade: AAPL - $149.50"));
+
+    // The default keeps emoji and adds no color
+    let bar = create_mock_bar_data("SPY", "2024-05-03T14:30:00Z", 500.0);
+    let line = plain.format_plain(&bar);
+    assert_eq!(ConsoleOptions::default().render(line.clone(), &bar), line);
+    assert_eq!(strip_emoji("🎛️  Control: subscribe trades TSLA"), "Control: subscribe trades TSLA");
+    assert_eq!(strip_emoji("⚠️  Disconnected"), "Disconnected");
+    assert_eq!(strip_emoji("no emoji here"), "no emoji here");
+}
+
+#[test]
+fn test_plain_templates() {
+    use algorithms_trading::PlainTemplates;
+
+    let mut templates = PlainTemplates::new();
+    templates.add("trades={{ symbol }} {{ price }} x {{ size }}").unwrap();
+    templates.add("q={{ symbol }} {{ bid }}/{{ ask }} ({{ spread }})").unwrap();
+    assert!(templates.add("trades").is_err());
+    assert!(templates.add("bars={{ close").is_err());
+
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("out.txt");
+    let output = OutputMode::create_file_mode(&path, DataFormat::Plain, false).unwrap()
+        .with_templates(std::sync::Arc::new(templates));
+    output.write_streaming_data(&create_mock_streaming_data("t", "AAPL", serde_json::json!({
+        "T": "t", "S": "AAPL", "p": 150.25, "s": 100, "t": "2024-05-03T14:30:00Z", "x": "V", "i": 1
+    }))).unwrap();
+    output.write_streaming_data(&create_mock_quote_data("MSFT", 1.0, 1.25)).unwrap();
+    output.write_streaming_data(&create_mock_bar_data("SPY", "2024-05-03T14:30:00Z", 500.0)).unwrap();
+    output.close().unwrap();
+
+    let content = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines[0], "AAPL 150.25 x 100");
+    assert_eq!(lines[1], "MSFT 1.0/1.25 (0.25)");
+    // Bars have no template and keep the built-in line
+    assert!(lines[2].starts_with("📈 Bar: SPY"), "{}", lines[2]);
+}